@@ -0,0 +1,70 @@
+//! The autosave which is stored in the browser's localStorage, see [`rusted_battalions_game_render::autosave`].
+//!
+//! localStorage can only store strings, so the save is stored as hex.
+
+use std::fmt::Write;
+use std::future::Future;
+use rusted_battalions_game_render::{Game, SaveGame};
+
+
+const AUTOSAVE_KEY: &str = "rusted-battalions-autosave";
+
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+
+/// Replaces the autosave, this should be called at the end of every turn.
+pub fn save(save: &SaveGame) {
+    if let Some(storage) = storage() {
+        let bytes = save.save();
+
+        let mut value = String::with_capacity(bytes.len() * 2);
+
+        for byte in bytes {
+            let _ = write!(value, "{:02x}", byte);
+        }
+
+        if let Err(e) = storage.set_item(AUTOSAVE_KEY, &value) {
+            tracing::warn!(error = ?e, "Failed to autosave");
+        }
+    }
+}
+
+
+/// Returns the autosave of the interrupted match, or `None` if there isn't one.
+pub fn load() -> Option<SaveGame> {
+    let value = storage()?.get_item(AUTOSAVE_KEY).ok()??;
+
+    let bytes = (0..value.len())
+        .step_by(2)
+        .map(|index| value.get(index..(index + 2)).and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+        .collect::<Option<Vec<u8>>>();
+
+    match bytes.map(|bytes| SaveGame::load(&bytes)) {
+        Some(Ok(save)) => Some(save),
+        Some(Err(e)) => {
+            tracing::warn!(error = %e, "Ignoring invalid autosave");
+            None
+        },
+        None => {
+            tracing::warn!("Ignoring invalid autosave: not hex");
+            None
+        },
+    }
+}
+
+
+/// Deletes the autosave, this should be called when the match ends.
+pub fn clear() {
+    if let Some(storage) = storage() {
+        let _ = storage.remove_item(AUTOSAVE_KEY);
+    }
+}
+
+
+/// Saves the match at the end of every turn, see [`rusted_battalions_game_render::autosave::sync`].
+pub fn sync(game: &Game) -> impl Future<Output = ()> {
+    rusted_battalions_game_render::autosave::sync(game, save, clear)
+}
@@ -2,6 +2,10 @@ use wasm_bindgen::prelude::*;
 
 mod renderer;
 mod app;
+mod settings;
+mod mods;
+pub mod net;
+pub mod autosave;
 
 #[wasm_bindgen(start)]
 pub fn main_js() -> Result<(), JsValue> {
@@ -0,0 +1,104 @@
+//! Texture packs which are saved in the browser's IndexedDB.
+//!
+//! The `assets` object store contains the files, the key is the asset's path
+//! (see [`AssetOverrides::paths`]) and the value is an `ArrayBuffer` or `Uint8Array`.
+
+use rusted_battalions_game_render::assets::{AssetOverrides};
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransactionMode};
+
+
+const DATABASE: &str = "rusted-battalions-mods";
+const STORE: &str = "assets";
+
+
+/// Waits for the request to succeed and returns its result.
+///
+/// The Promise is created immediately, so that it doesn't miss the success event.
+fn wait(request: &IdbRequest) -> JsFuture {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let success = Closure::once_into_js(move |event: web_sys::Event| {
+            let request: IdbRequest = event.target().unwrap().unchecked_into();
+            let _ = resolve.call1(&JsValue::UNDEFINED, &request.result().unwrap_or(JsValue::UNDEFINED));
+        });
+
+        let error = Closure::once_into_js(move |event: web_sys::Event| {
+            let _ = reject.call1(&JsValue::UNDEFINED, &event);
+        });
+
+        request.set_onsuccess(Some(success.unchecked_ref()));
+        request.set_onerror(Some(error.unchecked_ref()));
+    });
+
+    JsFuture::from(promise)
+}
+
+
+async fn open() -> Result<IdbDatabase, JsValue> {
+    let factory = web_sys::window()
+        .and_then(|window| window.indexed_db().ok().flatten())
+        .ok_or_else(|| JsValue::from_str("IndexedDB is not supported"))?;
+
+    let request: IdbOpenDbRequest = factory.open_with_u32(DATABASE, 1)?;
+
+    // Creates the store the first time, so that the players can add files to it
+    let upgrade = Closure::once_into_js(move |event: web_sys::Event| {
+        let request: IdbOpenDbRequest = event.target().unwrap().unchecked_into();
+        let database: IdbDatabase = request.result().unwrap().unchecked_into();
+
+        if let Err(e) = database.create_object_store(STORE) {
+            tracing::warn!(error = ?e, "Failed to create mods store");
+        }
+    });
+
+    request.set_onupgradeneeded(Some(upgrade.unchecked_ref()));
+
+    Ok(wait(&request).await?.unchecked_into())
+}
+
+
+async fn try_load() -> Result<AssetOverrides, JsValue> {
+    let database = open().await?;
+
+    let store = database
+        .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readonly)?
+        .object_store(STORE)?;
+
+    // Every request is sent before waiting, otherwise the transaction would finish early
+    let requests = AssetOverrides::paths()
+        .map(|path| Ok((path, wait(&store.get(&JsValue::from_str(path))?))))
+        .collect::<Result<Vec<_>, JsValue>>()?;
+
+    let mut assets = AssetOverrides::new();
+
+    for (path, request) in requests {
+        let value = request.await?;
+
+        if !value.is_undefined() {
+            let bytes = js_sys::Uint8Array::new(&value).to_vec();
+
+            if let Err(e) = assets.insert(path, bytes) {
+                tracing::warn!(error = %e, "Skipping invalid mod asset");
+            }
+        }
+    }
+
+    database.close();
+
+    Ok(assets)
+}
+
+
+/// Loads the texture pack, if it fails then the built-in assets are used.
+pub async fn load() -> AssetOverrides {
+    match try_load().await {
+        Ok(assets) => assets,
+        Err(e) => {
+            tracing::warn!(error = ?e, "Failed to load mods");
+            AssetOverrides::new()
+        },
+    }
+}
@@ -0,0 +1,307 @@
+//! WebSocket connection to the game server.
+//!
+//! Messages are JSON, every message is tagged with a `"type"` field.
+//! The connection automatically reconnects when it is closed, and it sends a
+//! [`ClientMessage::Ping`] every few seconds so that dead connections are detected.
+
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, RefCell};
+use serde::{Serialize, Deserialize};
+use wasm_bindgen::prelude::*;
+use web_sys::{WebSocket, MessageEvent, CloseEvent};
+use futures_signals::signal::{Mutable, Signal};
+use rusted_battalions_game_render::{ReplayEvent};
+
+
+/// How often a [`ClientMessage::Ping`] is sent, in milliseconds.
+const HEARTBEAT_INTERVAL: i32 = 5_000;
+
+/// If nothing is received for this long, then the connection is closed and reconnected.
+const HEARTBEAT_TIMEOUT: f64 = 3.0 * HEARTBEAT_INTERVAL as f64;
+
+/// The delay before the first reconnect, it doubles for each failed attempt.
+const RECONNECT_DELAY: i32 = 500;
+const MAX_RECONNECT_DELAY: i32 = 30_000;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The first connection hasn't finished yet.
+    Connecting,
+
+    Connected,
+
+    /// The connection was lost, `attempt` starts at `1`.
+    Reconnecting {
+        attempt: u32,
+    },
+
+    /// The connection was closed with [`Connection::close`], it will not reconnect.
+    Closed,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Action {
+        event: ReplayEvent,
+    },
+
+    Ping,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// An action which was done by another player, or by the server.
+    Action {
+        event: ReplayEvent,
+    },
+
+    Pong,
+}
+
+
+struct Socket {
+    socket: WebSocket,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+impl Socket {
+    /// Closes the socket without triggering a reconnect.
+    ///
+    /// This doesn't drop the handlers, because it can be called from inside of a handler.
+    fn detach(&self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        self.detach();
+    }
+}
+
+
+struct Heartbeat {
+    handle: i32,
+    _callback: Closure<dyn FnMut()>,
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            window.clear_interval_with_handle(self.handle);
+        }
+    }
+}
+
+
+/// Connection to the game server which reconnects automatically.
+pub struct Connection {
+    url: String,
+    state: Mutable<ConnectionState>,
+    socket: RefCell<Option<Socket>>,
+    heartbeat: RefCell<Option<Heartbeat>>,
+
+    /// Messages which were sent while disconnected, they are sent after reconnecting.
+    outgoing: RefCell<Vec<String>>,
+
+    /// The time of the most recent message from the server.
+    last_received: Cell<f64>,
+
+    on_message: RefCell<Box<dyn FnMut(ServerMessage)>>,
+}
+
+impl Connection {
+    /// Connects to the server, `on_message` is called for every message except [`ServerMessage::Pong`].
+    pub fn new<F>(url: &str, on_message: F) -> Rc<Self> where F: FnMut(ServerMessage) + 'static {
+        let this = Rc::new(Self {
+            url: url.to_string(),
+            state: Mutable::new(ConnectionState::Connecting),
+            socket: RefCell::new(None),
+            heartbeat: RefCell::new(None),
+            outgoing: RefCell::new(vec![]),
+            last_received: Cell::new(0.0),
+            on_message: RefCell::new(Box::new(on_message)),
+        });
+
+        Self::open(&this);
+        Self::start_heartbeat(&this);
+
+        this
+    }
+
+    pub fn state(&self) -> impl Signal<Item = ConnectionState> {
+        self.state.signal()
+    }
+
+    /// Sends an action to the server, if it isn't connected then the action is sent after reconnecting.
+    pub fn send(&self, event: ReplayEvent) {
+        let message = serde_json::to_string(&ClientMessage::Action { event }).unwrap();
+
+        if !self.send_raw(&message) {
+            self.outgoing.borrow_mut().push(message);
+        }
+    }
+
+    /// Closes the connection, it will not reconnect.
+    pub fn close(&self) {
+        self.state.set(ConnectionState::Closed);
+        self.detach();
+        self.heartbeat.replace(None);
+    }
+
+    fn detach(&self) {
+        if let Some(socket) = self.socket.borrow().as_ref() {
+            socket.detach();
+        }
+    }
+
+    /// Returns whether the message was sent.
+    fn send_raw(&self, message: &str) -> bool {
+        if self.state.get() != ConnectionState::Connected {
+            return false;
+        }
+
+        match self.socket.borrow().as_ref() {
+            Some(socket) => socket.socket.send_with_str(message).is_ok(),
+            None => false,
+        }
+    }
+
+    fn open(this: &Rc<Self>) {
+        let socket = match WebSocket::new(&this.url) {
+            Ok(socket) => socket,
+            Err(e) => {
+                tracing::warn!(url = %this.url, error = ?e, "Failed to connect");
+                Self::reconnect(this);
+                return;
+            },
+        };
+
+        let on_open = Closure::<dyn FnMut()>::new({
+            let this = Rc::downgrade(this);
+
+            move || {
+                if let Some(this) = this.upgrade() {
+                    this.state.set(ConnectionState::Connected);
+                    this.last_received.set(js_sys::Date::now());
+
+                    let outgoing = std::mem::take(&mut *this.outgoing.borrow_mut());
+
+                    for message in outgoing {
+                        this.send_raw(&message);
+                    }
+                }
+            }
+        });
+
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new({
+            let this = Rc::downgrade(this);
+
+            move |e: MessageEvent| {
+                if let Some(this) = this.upgrade() {
+                    this.last_received.set(js_sys::Date::now());
+
+                    let Some(text) = e.data().as_string() else {
+                        tracing::warn!("Server sent a binary message");
+                        return;
+                    };
+
+                    match serde_json::from_str(&text) {
+                        Ok(ServerMessage::Pong) => {},
+                        Ok(message) => (this.on_message.borrow_mut())(message),
+                        Err(e) => tracing::warn!(error = %e, "Invalid server message"),
+                    }
+                }
+            }
+        });
+
+        let on_close = Closure::<dyn FnMut(CloseEvent)>::new({
+            let this = Rc::downgrade(this);
+
+            move |e: CloseEvent| {
+                if let Some(this) = this.upgrade() {
+                    tracing::warn!(code = e.code(), reason = %e.reason(), "Connection closed");
+                    Self::reconnect(&this);
+                }
+            }
+        });
+
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        this.socket.replace(Some(Socket {
+            socket,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        }));
+    }
+
+    fn reconnect(this: &Rc<Self>) {
+        let attempt = match this.state.get() {
+            ConnectionState::Closed => return,
+            ConnectionState::Reconnecting { attempt } => attempt + 1,
+            ConnectionState::Connecting | ConnectionState::Connected => 1,
+        };
+
+        this.state.set(ConnectionState::Reconnecting { attempt });
+
+        // The old socket is dropped by `open`, because this can be called from inside of its handlers
+        this.detach();
+
+        let delay = RECONNECT_DELAY.saturating_mul(1 << (attempt - 1).min(16)).min(MAX_RECONNECT_DELAY);
+
+        let callback = Closure::once_into_js({
+            let this = Rc::downgrade(this);
+
+            move || {
+                if let Some(this) = this.upgrade() {
+                    if let ConnectionState::Reconnecting { .. } = this.state.get() {
+                        Self::open(&this);
+                    }
+                }
+            }
+        });
+
+        web_sys::window().unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(callback.unchecked_ref(), delay)
+            .unwrap();
+    }
+
+    fn start_heartbeat(this: &Rc<Self>) {
+        let callback = Closure::<dyn FnMut()>::new({
+            let this: Weak<Self> = Rc::downgrade(this);
+
+            move || {
+                if let Some(this) = this.upgrade() {
+                    if this.state.get() == ConnectionState::Connected {
+                        if js_sys::Date::now() - this.last_received.get() > HEARTBEAT_TIMEOUT {
+                            tracing::warn!("Connection timed out");
+                            Self::reconnect(&this);
+
+                        } else {
+                            this.send_raw(&serde_json::to_string(&ClientMessage::Ping).unwrap());
+                        }
+                    }
+                }
+            }
+        });
+
+        let handle = web_sys::window().unwrap()
+            .set_interval_with_callback_and_timeout_and_arguments_0(callback.as_ref().unchecked_ref(), HEARTBEAT_INTERVAL)
+            .unwrap();
+
+        this.heartbeat.replace(Some(Heartbeat { handle, _callback: callback }));
+    }
+}
@@ -1,9 +1,16 @@
 use rusted_battalions_engine::backend::web::Window;
-use rusted_battalions_game_render::{Game, GameSettings, Grid, UnitAppearance};
+use rusted_battalions_game_render::{
+    Game, GameSettings, Grid, UnitAppearance, QualitySettings, PowerPreference,
+    AutosaveSettings, MapData, MapGenSettings, ReplaySettings, MatchRules, Teams,
+};
+use rusted_battalions_game_render::lobby::{Weather};
+use rusted_battalions_game_render::ui::{ControlsConfig, Input, Action};
 
-use dominator::{Dom, DomBuilder, clone, html, dom_builder, with_node, apply_methods, events};
+use crate::{settings, mods, autosave};
+
+use dominator::{Dom, DomBuilder, EventOptions, clone, html, with_node, apply_methods, events};
 use dominator::animation::{timestamps};
-use futures_signals::signal::{Mutable, SignalExt};
+use futures_signals::signal::{Mutable, Signal, SignalExt};
 
 use std::sync::Arc;
 use std::future::Future;
@@ -32,56 +39,210 @@ fn wait_for_inserted<A, F>(f: F) -> impl FnOnce(DomBuilder<A>) -> DomBuilder<A>
 }
 
 
+/// Hidden element which is read aloud by screen readers whenever the text changes.
+fn live_region<S>(politeness: &str, text: S) -> Dom where S: Signal<Item = Option<Arc<str>>> + 'static {
+    html!("div", {
+        .attr("aria-live", politeness)
+        .attr("aria-atomic", "true")
+
+        // Visually hidden, but still accessible to screen readers
+        .style("position", "absolute")
+        .style("width", "1px")
+        .style("height", "1px")
+        .style("overflow", "hidden")
+        .style("clip-path", "inset(50%)")
+        .style("white-space", "nowrap")
+
+        .text_signal(text.map(|text| text.as_deref().unwrap_or("").to_string()))
+    })
+}
+
+
+const CONTROLS_KEY: &str = "rusted-battalions-controls";
+
+/// The map is generated, so the same map is used when the autosave is resumed.
+const MAP_SEED: u64 = 1;
+
+
+fn new_match(map: Arc<MapData>) -> (Arc<Grid>, AutosaveSettings) {
+    let grid = Grid::new_match(&map);
+    grid.start(Teams::new(), MatchRules::default());
+
+    let autosave = AutosaveSettings {
+        map,
+        settings: ReplaySettings {
+            fog: false,
+            weather: Weather::Clear,
+            starting_funds: 0,
+            income: 1000,
+        },
+        turn: 1,
+
+        // Every new match has different random numbers, the map is the same so that the autosave can find it
+        rng: (js_sys::Math::random() * (u64::MAX as f64)) as u64,
+    };
+
+    (grid, autosave)
+}
+
+/// Asks the player whether they want to resume the interrupted match, otherwise it starts a new match.
+fn start_match() -> (Arc<Grid>, AutosaveSettings) {
+    let maps = [Arc::new(MapGenSettings::new(MAP_SEED, 30, 20).generate())];
+
+    if let Some(save) = autosave::load() {
+        match save.find_map(&maps) {
+            Ok(map) => {
+                let resume = web_sys::window()
+                    .and_then(|window| window.confirm_with_message("Resume the interrupted match?").ok())
+                    .unwrap_or(false);
+
+                if resume {
+                    return (Grid::from_save(map, &save), AutosaveSettings::resume(map.clone(), &save));
+                }
+            },
+            Err(e) => {
+                tracing::warn!(error = %e, "Ignoring autosave");
+            },
+        }
+
+        autosave::clear();
+    }
+
+    let [map] = maps;
+    new_match(map)
+}
+
+
 pub struct Renderer {
     game: Arc<Game>,
+
+    /// Logs a JSON snapshot of the scene layout on the next frame.
+    dump_scene: Mutable<bool>,
 }
 
 impl Renderer {
     pub fn new() -> Arc<Self> {
+        let (grid, autosave) = start_match();
+
         Arc::new(Self {
             game: Game::new(GameSettings {
                 appearance: UnitAppearance::default(),
-                grid: Grid::test(),
+                grid,
+                controls: settings::load(CONTROLS_KEY).unwrap_or_default(),
+                spectator: None,
+                quality: QualitySettings::default(),
+                power_preference: PowerPreference::HighPerformance,
+                autosave: Some(autosave),
             }),
+            dump_scene: Mutable::new(false),
         })
     }
 
-    /*fn clear(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        let size = self.size.lock_ref();
-        ctx.clear_rect(0.0, 0.0, size.width, size.height);
-    }
-
-    fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d, delta: f64) {
-        let size = self.size.lock_ref();
-
-        log::info!("HELLO {}", delta);
-        ctx.set_fill_style(&JsValue::from("red"));
-        ctx.fill_rect(20.0, 20.0, size.width - 40.0, size.height - 40.0);
-    }*/
-
     pub fn render(this: &Arc<Self>) -> Dom {
         let window = Window::new();
 
         html!("div", {
+            .global_event_with_options(&EventOptions::preventable(), clone!(this => move |e: events::KeyDown| {
+                let input = Input::Key {
+                    key: e.key(),
+                    shift: e.shift_key(),
+                };
+
+                let action = this.game.controls.lock_ref().action(&input);
+
+                let used = match action {
+                    Some(Action::DumpScene) => {
+                        this.dump_scene.set(true);
+                        true
+                    },
+                    Some(action) => this.game.action(action),
+                    None => false,
+                };
+
+                if used {
+                    e.prevent_default();
+                }
+            }))
+
+            // Renders less often while the window is in the background
+            .global_event(clone!(this => move |_: events::Focus| {
+                this.game.set_focused(true);
+            }))
+
+            .global_event(clone!(this => move |_: events::Blur| {
+                this.game.set_focused(false);
+            }))
+
+            .future(this.game.controls.signal_ref(|controls: &ControlsConfig| {
+                settings::save(CONTROLS_KEY, controls);
+            }).to_future())
+
+            .future(autosave::sync(&this.game))
+
             .child(html!("canvas" => web_sys::HtmlCanvasElement, {
                 .attr("data-raw-handle", &window.id().to_string())
 
                 .attr_signal("width", this.game.screen_size().map(|size| format!("{}", size.width)))
                 .attr_signal("height", this.game.screen_size().map(|size| format!("{}", size.height)))
 
+                .with_node!(element => {
+                    .event(clone!(this, element => move |e: events::Click| {
+                        let x = e.offset_x() as f32 / element.client_width() as f32;
+                        let y = e.offset_y() as f32 / element.client_height() as f32;
+
+                        this.game.click(x, y);
+                    }))
+
+                    .event(clone!(this, element => move |e: events::MouseMove| {
+                        let x = e.offset_x() as f32 / element.client_width() as f32;
+                        let y = e.offset_y() as f32 / element.client_height() as f32;
+
+                        this.game.hover(x, y);
+                    }))
+
+                    .event(clone!(this => move |_: events::MouseLeave| {
+                        this.game.hover_end();
+                    }))
+
+                    .event_with_options(&EventOptions::preventable(), clone!(this, element => move |e: events::Wheel| {
+                        let x = e.offset_x() as f32 / element.client_width() as f32;
+                        let y = e.offset_y() as f32 / element.client_height() as f32;
+
+                        let rows = e.delta_y().signum() as i32;
+
+                        if this.game.wheel(x, y, rows) {
+                            e.prevent_default();
+                        }
+                    }))
+                })
+
                 .apply(wait_for_inserted(clone!(this => async move {
-                    let mut game = this.game.start_engine(window).await;
+                    let assets = mods::load().await;
+                    let mut game = this.game.start_engine(window, assets).await;
 
-                    timestamps().for_each(move |time| {
+                    timestamps().for_each(clone!(this => move |time| {
                         if let Some(time) = time {
                             game.render(time);
                         }
 
+                        if this.dump_scene.replace(false) {
+                            let snapshot = game.dump_tree();
+
+                            // The logger only shows warnings, so this logs directly to the console
+                            match serde_json::to_string(&snapshot) {
+                                Ok(json) => web_sys::console::log_1(&json.into()),
+                                Err(e) => tracing::warn!(error = %e, "Failed to serialize scene snapshot"),
+                            }
+                        }
+
                         async {}
-                    }).await;
+                    })).await;
                 })))
             }))
 
+            .child(live_region("polite", this.game.focus.focused_label_signal()))
+            .child(live_region("assertive", this.game.announcer.message_signal()))
+
             .child(html!("div", {
                 .style("margin", "20px")
                 .style("margin-left", "30px")
@@ -0,0 +1,33 @@
+//! Settings which are saved in the browser's localStorage.
+
+use serde::{Serialize, de::DeserializeOwned};
+
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+
+/// Loads the setting, returns `None` if it hasn't been saved or it is invalid.
+pub fn load<A>(key: &str) -> Option<A> where A: DeserializeOwned {
+    let value = storage()?.get_item(key).ok()??;
+
+    match serde_json::from_str(&value) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            tracing::warn!(key, error = %e, "Invalid setting");
+            None
+        },
+    }
+}
+
+
+pub fn save<A>(key: &str, value: &A) where A: Serialize {
+    if let Some(storage) = storage() {
+        let value = serde_json::to_string(value).unwrap();
+
+        if let Err(e) = storage.set_item(key, &value) {
+            tracing::warn!(key, error = ?e, "Failed to save setting");
+        }
+    }
+}
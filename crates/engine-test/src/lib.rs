@@ -0,0 +1,292 @@
+//! Visual regression testing for the engine.
+//!
+//! Scenes are rendered headless into an image, which is then compared against a golden image
+//! in the `goldens` folder.
+//!
+//! The tests need a GPU adapter, so they are ignored unless the `gpu` feature is enabled.
+//! With the `gpu` feature the tests fail if there isn't a GPU adapter, they are never skipped:
+//!
+//! ```sh
+//! cargo test -p rusted-battalions-engine-test --features gpu
+//! ```
+//!
+//! To create or update the goldens, run the tests with the `UPDATE_GOLDENS` environment variable:
+//!
+//! ```sh
+//! UPDATE_GOLDENS=1 cargo test -p rusted-battalions-engine-test --features gpu
+//! ```
+
+use std::fmt;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::future::Future;
+use std::sync::Arc;
+use futures::executor::{LocalPool, LocalSpawner, block_on};
+use futures::task::LocalSpawnExt;
+use rusted_battalions_engine::{Engine, HeadlessSettings, DepthSettings, Node, Spawner, WindowSize};
+
+pub use image::{RgbaImage, Rgba};
+
+
+struct TestSpawner {
+    spawner: LocalSpawner,
+}
+
+impl Spawner for TestSpawner {
+    fn spawn_local(&self, future: Pin<Box<dyn Future<Output = ()> + 'static>>) {
+        self.spawner.spawn_local(future).unwrap();
+    }
+}
+
+
+/// Renders the scene into an image.
+///
+/// The `load` function is called after the engine is created, it is used to load
+/// the textures, spritesheets, and fonts which are used by the scene.
+///
+/// # Panics
+///
+/// If there isn't a GPU adapter available.
+#[inline]
+pub fn render<F>(window_size: WindowSize, scene: Node, load: F) -> RgbaImage where F: FnOnce(&mut Engine) {
+    render_with_depth(window_size, DepthSettings::default(), scene, load)
+}
+
+/// Same as [`render`] except it uses custom [`DepthSettings`].
+#[inline]
+pub fn render_with_depth<F>(window_size: WindowSize, depth: DepthSettings, scene: Node, load: F) -> RgbaImage where F: FnOnce(&mut Engine) {
+    render_headless(window_size, depth, None, false, false, false, scene, load).0
+}
+
+/// Same as [`render`] except it loads the [`HeadlessSettings::pipeline_cache`], and it also
+/// returns the [`Engine::pipeline_cache_data`] after rendering.
+#[inline]
+pub fn render_with_pipeline_cache<F>(window_size: WindowSize, pipeline_cache: Option<Vec<u8>>, scene: Node, load: F) -> (RgbaImage, Option<Vec<u8>>) where F: FnOnce(&mut Engine) {
+    render_headless(window_size, DepthSettings::default(), pipeline_cache, false, false, false, scene, load)
+}
+
+/// Same as [`render`] except it enables [`HeadlessSettings::gpu_animation`].
+///
+/// If the GPU doesn't support compute shaders then the vertex shader selects the frames.
+#[inline]
+pub fn render_with_gpu_animation<F>(window_size: WindowSize, scene: Node, load: F) -> RgbaImage where F: FnOnce(&mut Engine) {
+    render_headless(window_size, DepthSettings::default(), None, true, false, false, scene, load).0
+}
+
+/// Same as [`render`] except it enables [`HeadlessSettings::gpu_culling`].
+///
+/// If the GPU doesn't support culling then it renders without culling.
+#[inline]
+pub fn render_with_gpu_culling<F>(window_size: WindowSize, scene: Node, load: F) -> RgbaImage where F: FnOnce(&mut Engine) {
+    render_headless(window_size, DepthSettings::default(), None, false, true, false, scene, load).0
+}
+
+/// Same as [`render`] except it enables [`HeadlessSettings::sprite_batching`].
+///
+/// If the GPU doesn't support binding arrays then it draws each spritesheet separately.
+#[inline]
+pub fn render_with_sprite_batching<F>(window_size: WindowSize, scene: Node, load: F) -> RgbaImage where F: FnOnce(&mut Engine) {
+    render_headless(window_size, DepthSettings::default(), None, false, false, true, scene, load).0
+}
+
+fn render_headless<F>(window_size: WindowSize, depth: DepthSettings, pipeline_cache: Option<Vec<u8>>, gpu_animation: bool, gpu_culling: bool, sprite_batching: bool, scene: Node, load: F) -> (RgbaImage, Option<Vec<u8>>) where F: FnOnce(&mut Engine) {
+    let mut pool = LocalPool::new();
+
+    let spawner = Arc::new(TestSpawner {
+        spawner: pool.spawner(),
+    });
+
+    let mut engine = match block_on(Engine::new_headless(HeadlessSettings {
+        scene,
+        window_size,
+        spawner,
+        depth,
+        pipeline_cache,
+        gpu_animation,
+        gpu_culling,
+        sprite_batching,
+    })) {
+        Some(engine) => engine,
+        None => panic!("The golden tests require a GPU adapter, but there isn't one available"),
+    };
+
+    load(&mut engine);
+
+    // Runs the Signals so that the scene is up to date
+    pool.run_until_stalled();
+
+    let image = engine.render_to_image();
+
+    (image, engine.pipeline_cache_data())
+}
+
+
+/// How different an image is allowed to be from the golden.
+///
+/// GPUs don't always produce the exact same pixels, so a small difference is allowed.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Maximum difference for each color channel before the pixel is considered different.
+    pub channel: u8,
+
+    /// Maximum percentage (from `0.0` to `1.0`) of pixels which can be different.
+    pub pixels: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            pixels: 0.001,
+        }
+    }
+}
+
+
+/// The reason why an image didn't match the golden.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Size {
+        actual: (u32, u32),
+        expected: (u32, u32),
+    },
+    Pixels {
+        different: usize,
+        total: usize,
+        max_channel_diff: u8,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Size { actual, expected } => {
+                write!(f, "image is {}x{} but expected {}x{}", actual.0, actual.1, expected.0, expected.1)
+            },
+            Self::Pixels { different, total, max_channel_diff } => {
+                write!(f, "{} of {} pixels are different (max channel difference {})", different, total, max_channel_diff)
+            },
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+
+/// Compares two images, returning an error if they are more different than the tolerance.
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: Tolerance) -> Result<(), Mismatch> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(Mismatch::Size {
+            actual: actual.dimensions(),
+            expected: expected.dimensions(),
+        });
+    }
+
+    let mut different = 0;
+    let mut max_channel_diff = 0;
+
+    for (actual, expected) in actual.pixels().zip(expected.pixels()) {
+        let diff = actual.0.iter().zip(expected.0.iter())
+            .map(|(actual, expected)| actual.abs_diff(*expected))
+            .max()
+            .unwrap();
+
+        max_channel_diff = max_channel_diff.max(diff);
+
+        if diff > tolerance.channel {
+            different += 1;
+        }
+    }
+
+    let total = (actual.width() * actual.height()) as usize;
+
+    if (different as f32) > (total as f32) * tolerance.pixels {
+        Err(Mismatch::Pixels { different, total, max_channel_diff })
+
+    } else {
+        Ok(())
+    }
+}
+
+
+fn golden_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("goldens")
+        .join(format!("{}{}.png", name, suffix))
+}
+
+/// Compares the image against the golden with the same name.
+///
+/// If they don't match then the image is saved next to the golden with the `.actual.png` extension.
+#[track_caller]
+pub fn assert_golden(name: &str, actual: &RgbaImage, tolerance: Tolerance) {
+    let path = golden_path(name, "");
+
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        actual.save(&path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&path) {
+        Ok(expected) => expected.into_rgba8(),
+        Err(error) => panic!("Could not load golden {}: {}\n\nRun the tests with UPDATE_GOLDENS=1 to create it", path.display(), error),
+    };
+
+    if let Err(mismatch) = compare(actual, &expected, tolerance) {
+        let actual_path = golden_path(name, ".actual");
+
+        actual.save(&actual_path).unwrap();
+
+        panic!("Golden {} does not match: {}\n\nThe rendered image was saved to {}", name, mismatch, actual_path.display());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba(color))
+    }
+
+    #[test]
+    fn identical() {
+        let image = solid(4, 4, [10, 20, 30, 255]);
+        assert_eq!(compare(&image, &image, Tolerance::default()), Ok(()));
+    }
+
+    #[test]
+    fn within_channel_tolerance() {
+        let actual = solid(4, 4, [10, 20, 30, 255]);
+        let expected = solid(4, 4, [12, 18, 30, 255]);
+        assert_eq!(compare(&actual, &expected, Tolerance::default()), Ok(()));
+    }
+
+    #[test]
+    fn different_pixels() {
+        let actual = solid(4, 4, [10, 20, 30, 255]);
+
+        let mut expected = actual.clone();
+        expected.put_pixel(1, 1, Rgba([200, 20, 30, 255]));
+
+        assert_eq!(compare(&actual, &expected, Tolerance::default()), Err(Mismatch::Pixels {
+            different: 1,
+            total: 16,
+            max_channel_diff: 190,
+        }));
+
+        assert_eq!(compare(&actual, &expected, Tolerance { channel: 2, pixels: 0.1 }), Ok(()));
+    }
+
+    #[test]
+    fn different_size() {
+        let actual = solid(4, 4, [0, 0, 0, 255]);
+        let expected = solid(4, 2, [0, 0, 0, 255]);
+
+        assert_eq!(compare(&actual, &expected, Tolerance::default()), Err(Mismatch::Size {
+            actual: (4, 4),
+            expected: (4, 2),
+        }));
+    }
+}
@@ -0,0 +1,1155 @@
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{
+    Engine, Node, WindowSize, Spritesheet, SpritesheetSettings, Texture, Tile,
+    RgbaImage, IndexedImage, GrayscaleImage, BitmapFont, BitmapFontSettings,
+    BitmapFontSupported, BorderSize, Quadrants, CharSize, ColorRgb, Size,
+    ParentWidth, ParentHeight, Px, TilemapSize, Tileset, ScreenEffect,
+    Offset, LinePoint, GradientColors, DepthSettings, PipelineHandle,
+    CustomPipelineSettings, Order, QualitySettings, PaletteError, SpriteAnimation, AnimationMode,
+};
+use rusted_battalions_engine_test::{
+    render, render_with_depth, render_with_pipeline_cache, render_with_gpu_animation, render_with_gpu_culling,
+    render_with_sprite_batching,
+    assert_golden, compare, Tolerance,
+};
+
+
+const WINDOW_SIZE: WindowSize = WindowSize {
+    width: 64,
+    height: 64,
+};
+
+const COLORS: [[u8; 4]; 4] = [
+    [255, 0, 0, 255],
+    [0, 255, 0, 255],
+    [0, 0, 255, 255],
+    [255, 255, 0, 255],
+];
+
+
+/// Spritesheet with a row of 4 tiles, each tile is 8x8 and has a different color.
+fn load_colors(engine: &mut Engine, spritesheet: &Spritesheet) {
+    load_colors_with_draw_order(engine, spritesheet, 0);
+}
+
+fn load_colors_with_draw_order(engine: &mut Engine, spritesheet: &Spritesheet, draw_order: i32) {
+    let image = RgbaImage::from_fn("colors", 32, 8, |x, _y| {
+        image::Rgba(COLORS[(x / 8) as usize])
+    });
+
+    let texture = Texture::new();
+
+    texture.load(engine, &image);
+
+    spritesheet.load(engine, SpritesheetSettings {
+        label: "colors",
+        texture: &texture,
+        palette: None,
+        draw_order,
+        sorted: false,
+    });
+}
+
+fn color_tile(index: u32) -> Tile {
+    Tile {
+        start_x: index * 8,
+        start_y: 0,
+        end_x: (index + 1) * 8,
+        end_y: 8,
+    }
+}
+
+fn color_sprite(spritesheet: &Spritesheet, index: u32) -> Node {
+    engine::Sprite::builder()
+        .spritesheet(spritesheet.clone())
+        .tile(color_tile(index))
+        .size(Size {
+            width: Px(16),
+            height: Px(16),
+        })
+        .build()
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn rows() {
+    let spritesheet = Spritesheet::new();
+
+    let scene = engine::Column::builder()
+        .children((0..2).map(|row| {
+            engine::Row::builder()
+                .children((0..4).map(|index| color_sprite(&spritesheet, (index + row) % 4)))
+                .build()
+        }))
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| load_colors(engine, &spritesheet));
+
+    assert_golden("rows", &image, Tolerance::default());
+}
+
+
+// The warmup frame is never displayed, so it must not change the next frame.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn warmup() {
+    let spritesheet = Spritesheet::new();
+
+    let scene = engine::Column::builder()
+        .children((0..2).map(|row| {
+            engine::Row::builder()
+                .children((0..4).map(|index| color_sprite(&spritesheet, (index + row) % 4)))
+                .build()
+        }))
+        .build();
+
+    let load = |engine: &mut Engine| {
+        load_colors(engine, &spritesheet);
+        engine.warmup();
+    };
+
+    let image = render(WINDOW_SIZE, scene, load);
+
+    assert_golden("rows", &image, Tolerance::default());
+}
+
+
+// The adapter which rendered the scene is one of the enumerated adapters.
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn adapter_info() {
+    let mut info = None;
+
+    let load = |engine: &mut Engine| {
+        info = Some(engine.info());
+    };
+
+    render(WINDOW_SIZE, engine::Stack::builder().build(), load);
+
+    let info = info.unwrap();
+
+    assert!(Engine::enumerate_adapters().iter().any(|adapter| {
+        adapter.name == info.adapter.name && adapter.backend == info.adapter.backend
+    }));
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn texture_write_region() {
+    let spritesheet = Spritesheet::new();
+
+    let scene = engine::Row::builder()
+        .children((0..4).map(|index| color_sprite(&spritesheet, index)))
+        .build();
+
+    let patch = RgbaImage::from_fn("patch", 12, 4, |x, _y| {
+        image::Rgba(COLORS[(3 - (x / 4)) as usize])
+    });
+
+    let image = render(WINDOW_SIZE, scene, |engine| {
+        let texture = Texture::new();
+
+        let image = RgbaImage::from_fn("colors", 32, 8, |x, _y| {
+            image::Rgba(COLORS[(x / 8) as usize])
+        });
+
+        texture.load(engine, &image);
+
+        spritesheet.load(engine, SpritesheetSettings {
+            label: "colors",
+            texture: &texture,
+            palette: None,
+            draw_order: 0,
+            sorted: false,
+        });
+
+        // Crosses the border between the first and second tiles
+        texture.write_region(engine, 6, 2, &patch);
+    });
+
+    assert_golden("texture_write_region", &image, Tolerance::default());
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn ui_scale() {
+    let spritesheet = Spritesheet::new();
+
+    // Each sprite is 16x16 pixels, so with a scale of 2 they fill the top half of the window.
+    let scene = engine::Row::builder()
+        .children((0..2).map(|index| color_sprite(&spritesheet, index)))
+        .build();
+
+    let load = |engine: &mut Engine| {
+        load_colors(engine, &spritesheet);
+        engine.set_ui_scale(2.0);
+    };
+
+    let image = render(WINDOW_SIZE, scene, load);
+
+    assert_golden("ui_scale", &image, Tolerance::default());
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn screen_effect_invert() {
+    let spritesheet = Spritesheet::new();
+
+    let scene = engine::Row::builder()
+        .children((0..4).map(|index| color_sprite(&spritesheet, index)))
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| {
+        load_colors(engine, &spritesheet);
+
+        engine.set_screen_effect(ScreenEffect {
+            invert: 1.0,
+            ..ScreenEffect::default()
+        });
+    });
+
+    assert_golden("screen_effect_invert", &image, Tolerance::default());
+}
+
+// Rotates the color channels, so red becomes green, green becomes blue, and blue becomes red.
+const ROTATE_CHANNELS: &str = "
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(spritesheet, tile_uv(normalize_uv(in.uv), in.tile), 0);
+    return vec4(color.brg, in.alpha);
+}
+";
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn custom_pipeline() {
+    let spritesheet = Spritesheet::new();
+    let pipeline = PipelineHandle::new();
+
+    let scene = engine::Row::builder()
+        .children((0..3).map(|index| {
+            engine::Sprite::builder()
+                .spritesheet(spritesheet.clone())
+                .custom_pipeline(Some(pipeline.clone()))
+                .tile(color_tile(index))
+                .size(Size {
+                    width: Px(16),
+                    height: Px(16),
+                })
+                .build()
+        }))
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| {
+        load_colors(engine, &spritesheet);
+
+        pipeline.load(engine, CustomPipelineSettings {
+            label: "rotate_channels",
+            fragment: ROTATE_CHANNELS,
+            animated: false,
+        });
+    });
+
+    // The same as the built-in shader with the colors shifted by one tile
+    let expected = engine::Row::builder()
+        .children([1, 2, 0].into_iter().map(|index| color_sprite(&spritesheet, index)))
+        .build();
+
+    let expected = render(WINDOW_SIZE, expected, |engine| load_colors(engine, &spritesheet));
+
+    compare(&image, &expected, Tolerance::default()).unwrap();
+}
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn pipeline_cache() {
+    let spritesheet = Spritesheet::new();
+
+    // Both pipelines use the same shader, so they share the compiled pipelines
+    let pipelines = [PipelineHandle::new(), PipelineHandle::new()];
+
+    let scene = || {
+        engine::Row::builder()
+            .children((0..3).map(|index| {
+                engine::Sprite::builder()
+                    .spritesheet(spritesheet.clone())
+                    .custom_pipeline(Some(pipelines[index as usize % 2].clone()))
+                    .tile(color_tile(index))
+                    .size(Size {
+                        width: Px(16),
+                        height: Px(16),
+                    })
+                    .build()
+            }))
+            .build()
+    };
+
+    let load = |engine: &mut Engine| {
+        load_colors(engine, &spritesheet);
+
+        for pipeline in pipelines.iter() {
+            pipeline.load(engine, CustomPipelineSettings {
+                label: "rotate_channels",
+                fragment: ROTATE_CHANNELS,
+                animated: false,
+            });
+        }
+
+        engine.warmup();
+    };
+
+    let (image, data) = render_with_pipeline_cache(WINDOW_SIZE, None, scene(), load);
+
+    // The data is only available on native Vulkan, otherwise the cache is ignored
+    let (cached, _) = render_with_pipeline_cache(WINDOW_SIZE, data, scene(), load);
+
+    let expected = engine::Row::builder()
+        .children([1, 2, 0].into_iter().map(|index| color_sprite(&spritesheet, index)))
+        .build();
+
+    let expected = render(WINDOW_SIZE, expected, |engine| load_colors(engine, &spritesheet));
+
+    compare(&image, &expected, Tolerance::default()).unwrap();
+    compare(&cached, &expected, Tolerance::default()).unwrap();
+}
+
+// Only rotates the color channels after 1 second, which checks that the fragment shader can use scene.time.
+const ROTATE_CHANNELS_LATER: &str = "
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(spritesheet, tile_uv(normalize_uv(in.uv), in.tile), 0);
+    return select(color, vec4(color.brg, color.a), scene.time >= 1000.0);
+}
+";
+
+/// Disabling animations only freezes the SpriteAnimation, scene.time still changes.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn custom_pipeline_time() {
+    for quality in [QualitySettings::HIGH, QualitySettings::LOW] {
+        let spritesheet = Spritesheet::new();
+        let pipeline = PipelineHandle::new();
+
+        let scene = engine::Sprite::builder()
+            .spritesheet(spritesheet.clone())
+            .custom_pipeline(Some(pipeline.clone()))
+            .tile(color_tile(0))
+            .size(Size {
+                width: Px(16),
+                height: Px(16),
+            })
+            .build();
+
+        let image = render(WINDOW_SIZE, scene, |engine| {
+            load_colors(engine, &spritesheet);
+
+            pipeline.load(engine, CustomPipelineSettings {
+                label: "rotate_channels_later",
+                fragment: ROTATE_CHANNELS_LATER,
+                animated: true,
+            });
+
+            engine.set_quality(quality);
+            engine.set_time(1500.0);
+        });
+
+        let expected = render(WINDOW_SIZE, color_sprite(&spritesheet, 1), |engine| load_colors(engine, &spritesheet));
+
+        compare(&image, &expected, Tolerance::default()).unwrap();
+    }
+}
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn screen_effect_transition() {
+    let scene = engine::Gradient::builder()
+        .colors(GradientColors {
+            top_left: ColorRgb { r: 1.0, g: 0.0, b: 0.0 },
+            top_right: ColorRgb { r: 0.0, g: 1.0, b: 0.0 },
+            bottom_left: ColorRgb { r: 0.0, g: 0.0, b: 1.0 },
+            bottom_right: ColorRgb { r: 1.0, g: 1.0, b: 1.0 },
+        })
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| {
+        engine.set_screen_effect(ScreenEffect {
+            fade: 0.25,
+            wipe: 0.25,
+            mosaic: 0.5,
+            ..ScreenEffect::default()
+        });
+    });
+
+    assert_golden("screen_effect_transition", &image, Tolerance::default());
+}
+
+
+/// Each glyph is a diagonal line which is offset by the character code, so every glyph is different.
+///
+/// Every column is double width so that it can contain full-width characters.
+fn load_font(engine: &mut Engine, font: &BitmapFont, end: char) {
+    let image = GrayscaleImage::from_fn("font", 16 * 8, 8 * 8, |x, y| {
+        let code = ((y / 8) * 16) + (x / 8);
+        let on = ((x % 8) + code) % 4 == (y % 8) % 4;
+        image::Luma([if on { 255 } else { 0 }])
+    });
+
+    let texture = Texture::new();
+
+    texture.load(engine, &image);
+
+    font.load(engine, BitmapFontSettings {
+        texture: &texture,
+        supported: BitmapFontSupported {
+            start: '\u{0000}',
+            end,
+            replace: '\u{001A}',
+        },
+        columns: 16,
+        tile_width: 4,
+        tile_height: 8,
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn text() {
+    let font = BitmapFont::new();
+
+    let scene = engine::BitmapText::builder()
+        .text("Hi!\nOk".into())
+        .font(font.clone())
+        .text_color(ColorRgb { r: 1.0, g: 0.5, b: 0.0 })
+        .char_size(CharSize {
+            width: Px(8),
+            height: Px(16),
+        })
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| load_font(engine, &font, '\u{007F}'));
+
+    assert_golden("text", &image, Tolerance::default());
+}
+
+// Laying out each line separately must not change the output.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn text_incremental() {
+    let font = BitmapFont::new();
+
+    let scene = engine::BitmapText::builder()
+        .text("Hi!\nOk".into())
+        .incremental(true)
+        .font(font.clone())
+        .text_color(ColorRgb { r: 1.0, g: 0.5, b: 0.0 })
+        .char_size(CharSize {
+            width: Px(8),
+            height: Px(16),
+        })
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| load_font(engine, &font, '\u{007F}'));
+
+    assert_golden("text", &image, Tolerance::default());
+}
+
+// The main font only supports punctuation, so the letters use the fallback font.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn text_font_fallbacks() {
+    let font = BitmapFont::new();
+    let fallback = BitmapFont::new();
+
+    let scene = engine::BitmapText::builder()
+        .text("Hi!\nOk".into())
+        .font(font.clone())
+        .font_fallbacks(vec![fallback.clone()])
+        .text_color(ColorRgb { r: 1.0, g: 0.5, b: 0.0 })
+        .char_size(CharSize {
+            width: Px(8),
+            height: Px(16),
+        })
+        .build();
+
+    let load = |engine: &mut Engine| {
+        load_font(engine, &font, '\u{0040}');
+        load_font(engine, &fallback, '\u{007F}');
+    };
+
+    let image = render(WINDOW_SIZE, scene, load);
+
+    assert_golden("text", &image, Tolerance::default());
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn border_grid() {
+    let spritesheet = Spritesheet::new();
+
+    let quadrant = |index| {
+        engine::Sprite::builder()
+            .spritesheet(spritesheet.clone())
+            .tile(color_tile(index))
+            .build()
+    };
+
+    let scene = engine::BorderGrid::builder()
+        .size(Size {
+            width: ParentWidth(0.75),
+            height: ParentHeight(0.5),
+        })
+        .border_size(BorderSize::all(Px(8)))
+        .quadrants(Quadrants {
+            up_left: quadrant(0),
+            up: quadrant(1),
+            up_right: quadrant(0),
+
+            left: quadrant(1),
+            center: quadrant(2),
+            right: quadrant(1),
+
+            down_left: quadrant(0),
+            down: quadrant(1),
+            down_right: quadrant(3),
+        })
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| load_colors(engine, &spritesheet));
+
+    assert_golden("border_grid", &image, Tolerance::default());
+}
+
+
+/// Palette spritesheet with a single 8x8 tile, each quarter of the tile uses a different palette color.
+///
+/// The palette has 2 rows, each row is a different palette.
+fn load_indexed(engine: &mut Engine, spritesheet: &Spritesheet) {
+    let palette_image = RgbaImage::from_fn("palette", 4, 2, |x, y| {
+        image::Rgba(COLORS[((x + y) % 4) as usize])
+    });
+
+    let image = IndexedImage::from_fn("indexed", 8, 8, |x, y| {
+        image::LumaA([((y / 4) + (x / 4) * 2) as u8, 255])
+    });
+
+    let palette = Texture::new();
+    let texture = Texture::new();
+
+    palette.load(engine, &palette_image);
+    texture.load(engine, &image);
+
+    spritesheet.load(engine, SpritesheetSettings {
+        label: "indexed",
+        texture: &texture,
+        palette: Some(&palette),
+        draw_order: 0,
+        sorted: false,
+    });
+}
+
+fn indexed_sprite(spritesheet: &Spritesheet, palette: u32, size: i32) -> Node {
+    engine::Sprite::builder()
+        .spritesheet(spritesheet.clone())
+        .tile(Tile {
+            start_x: 0,
+            start_y: 0,
+            end_x: 8,
+            end_y: 8,
+        })
+        .palette(palette)
+        .size(Size {
+            width: Px(size),
+            height: Px(size),
+        })
+        .build()
+}
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn palette_sprites() {
+    let spritesheet = Spritesheet::new();
+
+    let scene = engine::Row::builder()
+        .children((0..2).map(|palette| indexed_sprite(&spritesheet, palette, 32)))
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| load_indexed(engine, &spritesheet));
+
+    assert_golden("palette_sprites", &image, Tolerance::default());
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn palette_add() {
+    let spritesheet = Spritesheet::new();
+
+    let scene = engine::Row::builder()
+        .children((0..2).map(|palette| {
+            engine::Sprite::builder()
+                .spritesheet(spritesheet.clone())
+                .tile(Tile {
+                    start_x: 0,
+                    start_y: 0,
+                    end_x: 8,
+                    end_y: 8,
+                })
+                .palette(palette)
+                .size(Size {
+                    width: Px(32),
+                    height: Px(32),
+                })
+                .build()
+        }))
+        .build();
+
+    let load = |engine: &mut Engine| {
+        let palette_image = RgbaImage::from_fn("palette", 4, 1, |x, _y| {
+            image::Rgba(COLORS[x as usize])
+        });
+
+        let image = IndexedImage::from_fn("indexed", 8, 8, |x, y| {
+            image::LumaA([((y / 4) + (x / 4) * 2) as u8, 255])
+        });
+
+        let palette = Texture::new();
+        let texture = Texture::new();
+
+        palette.load(engine, &palette_image);
+        texture.load(engine, &image);
+
+        spritesheet.load(engine, SpritesheetSettings {
+            label: "indexed",
+            texture: &texture,
+            palette: Some(&palette),
+            draw_order: 0,
+            sorted: false,
+        });
+
+        // The second row is added after the spritesheet is loaded
+        let extra = RgbaImage::from_fn("extra palette", 4, 1, |x, _y| {
+            image::Rgba(COLORS[((x + 1) % 4) as usize])
+        });
+
+        assert_eq!(spritesheet.add_palette(engine, &extra), Ok(1));
+        assert_eq!(palette.size(engine).map(|size| size.height), Some(2));
+    };
+
+    // This is the same as palette_sprites, except the palette is built in two steps
+    let image = render(WINDOW_SIZE, scene, load);
+
+    assert_golden("palette_sprites", &image, Tolerance::default());
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn palette_add_errors() {
+    let load = |engine: &mut Engine| {
+        let palette = Texture::new();
+        let texture = Texture::new();
+        let indexed = Spritesheet::new();
+        let rgba = Spritesheet::new();
+
+        palette.load(engine, &RgbaImage::from_fn("palette", 4, 1, |x, _y| image::Rgba(COLORS[x as usize])));
+        texture.load(engine, &IndexedImage::from_fn("indexed", 8, 8, |_x, _y| image::LumaA([0, 255])));
+
+        indexed.load(engine, SpritesheetSettings {
+            label: "indexed",
+            texture: &texture,
+            palette: Some(&palette),
+            draw_order: 0,
+            sorted: false,
+        });
+
+        rgba.load(engine, SpritesheetSettings {
+            label: "rgba",
+            texture: &palette,
+            palette: None,
+            draw_order: 0,
+            sorted: false,
+        });
+
+        let narrow = RgbaImage::from_fn("narrow palette", 3, 1, |_x, _y| image::Rgba([0, 0, 0, 255]));
+        let tall = RgbaImage::from_fn("tall palette", 4, 65536, |_x, _y| image::Rgba([0, 0, 0, 255]));
+
+        assert_eq!(rgba.add_palette(engine, &narrow), Err(PaletteError::NoPalette));
+        assert_eq!(indexed.add_palette(engine, &narrow), Err(PaletteError::WidthMismatch { expected: 4, found: 3 }));
+        assert!(matches!(indexed.add_palette(engine, &tall), Err(PaletteError::TooManyRows { height: 65537, .. })));
+
+        // The palette is unchanged after an error
+        assert_eq!(palette.size(engine).map(|size| size.height), Some(1));
+    };
+
+    render(WINDOW_SIZE, engine::Row::builder().build(), load);
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn tilemap() {
+    let spritesheet = Spritesheet::new();
+
+    let scene = engine::Tilemap::builder()
+        .spritesheet(spritesheet.clone())
+        .map_size(TilemapSize {
+            width: 4,
+            height: 4,
+        })
+        .tileset(Tileset {
+            columns: 4,
+            tile_width: 8,
+            tile_height: 8,
+        })
+        .cells((0..16).map(|index| {
+            // Leaves the diagonal empty
+            if index % 5 == 0 {
+                None
+
+            } else {
+                Some(index % 4)
+            }
+        }).collect())
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |engine| load_colors(engine, &spritesheet));
+
+    assert_golden("tilemap", &image, Tolerance::default());
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn shapes() {
+    let scene = engine::Stack::builder()
+        .child(engine::Rect::builder()
+            .color(ColorRgb { r: 1.0, g: 0.0, b: 0.0 })
+            .offset(Offset {
+                x: Px(8),
+                y: Px(8),
+            })
+            .size(Size {
+                width: Px(16),
+                height: Px(24),
+            })
+            .build())
+
+        .child(engine::Rect::builder()
+            .color(ColorRgb { r: 0.0, g: 0.0, b: 1.0 })
+            .alpha(0.5)
+            .offset(Offset {
+                x: Px(16),
+                y: Px(16),
+            })
+            .size(Size {
+                width: Px(16),
+                height: Px(16),
+            })
+            .build())
+
+        .child(engine::LineStrip::builder()
+            .color(ColorRgb { r: 0.0, g: 1.0, b: 0.0 })
+            .thickness(Px(2))
+            .closed(true)
+            .points(vec![
+                LinePoint { x: 0.5, y: 0.5 },
+                LinePoint { x: 0.9, y: 0.5 },
+                LinePoint { x: 0.9, y: 0.9 },
+                LinePoint { x: 0.5, y: 0.9 },
+            ])
+            .build())
+
+        .child(engine::LineStrip::builder()
+            .thickness(Px(1))
+            .points(vec![
+                LinePoint { x: 0.0, y: 1.0 },
+                LinePoint { x: 1.0, y: 0.0 },
+            ])
+            .build())
+
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |_| {});
+
+    assert_golden("shapes", &image, Tolerance::default());
+}
+
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn gradient() {
+    let scene = engine::Stack::builder()
+        .child(engine::Gradient::builder()
+            .colors(GradientColors {
+                top_left: ColorRgb { r: 1.0, g: 0.0, b: 0.0 },
+                top_right: ColorRgb { r: 0.0, g: 1.0, b: 0.0 },
+                bottom_left: ColorRgb { r: 0.0, g: 0.0, b: 1.0 },
+                bottom_right: ColorRgb { r: 1.0, g: 1.0, b: 1.0 },
+            })
+            .size(Size {
+                width: ParentWidth(1.0),
+                height: ParentHeight(0.5),
+            })
+            .build())
+
+        .child(engine::Gradient::builder()
+            .colors(GradientColors::vertical(
+                ColorRgb { r: 0.0, g: 0.0, b: 0.0 },
+                ColorRgb { r: 1.0, g: 1.0, b: 0.0 },
+            ))
+            .offset(Offset {
+                x: Px(0),
+                y: Px(32),
+            })
+            .size(Size {
+                width: ParentWidth(1.0),
+                height: ParentHeight(0.5),
+            })
+            .build())
+
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |_| {});
+
+    assert_golden("gradient", &image, Tolerance::default());
+}
+
+fn mask_scene() -> Node {
+    engine::Stack::builder()
+        .child(engine::Rect::builder()
+            .color(ColorRgb { r: 0.5, g: 0.5, b: 0.5 })
+            .size(Size {
+                width: ParentWidth(1.0),
+                height: ParentHeight(0.25),
+            })
+            .build())
+
+        .child(engine::Mask::builder()
+            // The shape of the mask
+            .child(engine::LineStrip::builder()
+                .thickness(Px(12))
+                .points(vec![
+                    LinePoint { x: 0.0, y: 0.0 },
+                    LinePoint { x: 1.0, y: 1.0 },
+                ])
+                .build())
+
+            .child(engine::Gradient::builder()
+                .colors(GradientColors::horizontal(
+                    ColorRgb { r: 1.0, g: 0.0, b: 0.0 },
+                    ColorRgb { r: 0.0, g: 0.0, b: 1.0 },
+                ))
+                .build())
+
+            .child(engine::Rect::builder()
+                .color(ColorRgb { r: 0.0, g: 1.0, b: 0.0 })
+                .alpha(0.5)
+                .size(Size {
+                    width: ParentWidth(1.0),
+                    height: ParentHeight(0.5),
+                })
+                .build())
+
+            .build())
+
+        .build()
+}
+
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn mask() {
+    let image = render(WINDOW_SIZE, mask_scene(), |_| {});
+
+    assert_golden("mask", &image, Tolerance::default());
+}
+
+/// Reversing the depth must not change the output.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn mask_reversed_z() {
+    let depth = DepthSettings {
+        reversed_z: true,
+        ..DepthSettings::default()
+    };
+
+    let image = render_with_depth(WINDOW_SIZE, depth, mask_scene(), |_| {});
+
+    assert_golden("mask", &image, Tolerance::default());
+}
+
+/// A nested Mask doesn't clip its children, but they are still clipped by the outer Mask.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn mask_nested() {
+    let scene = engine::Mask::builder()
+        .child(engine::Rect::builder()
+            .color(ColorRgb { r: 1.0, g: 1.0, b: 1.0 })
+            .size(Size {
+                width: ParentWidth(0.5),
+                height: ParentHeight(1.0),
+            })
+            .build())
+
+        .child(engine::Mask::builder()
+            .child(engine::Rect::builder()
+                .color(ColorRgb { r: 1.0, g: 0.0, b: 0.0 })
+                .size(Size {
+                    width: Px(1),
+                    height: Px(1),
+                })
+                .build())
+
+            .child(engine::Rect::builder()
+                .color(ColorRgb { r: 0.0, g: 1.0, b: 0.0 })
+                .size(Size {
+                    width: ParentWidth(1.0),
+                    height: ParentHeight(1.0),
+                })
+                .build())
+
+            .build())
+
+        .build();
+
+    let image = render(WINDOW_SIZE, scene, |_| {});
+
+    assert_eq!(image.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    assert_eq!(image.get_pixel(16, 32).0, [0, 255, 0, 255]);
+    assert_eq!(image.get_pixel(48, 32).0, [0, 0, 0, 255]);
+}
+
+
+/// Two overlapping sprites with the same order, the second sprite is drawn on top.
+fn same_order_scene(spritesheets: [&Spritesheet; 2], alphas: [f32; 2]) -> Node {
+    engine::Stack::builder()
+        .children((0..2).map(|index| {
+            engine::Sprite::builder()
+                .spritesheet(spritesheets[index as usize].clone())
+                .tile(color_tile(index))
+                .alpha(alphas[index as usize])
+                .order(Order::Global(2.0))
+                .offset(Offset {
+                    x: Px(index as i32 * 8),
+                    y: Px(index as i32 * 8),
+                })
+                .size(Size {
+                    width: Px(16),
+                    height: Px(16),
+                })
+                .build()
+        }))
+        .build()
+}
+
+/// Sprites with the same order are drawn in scene order, for both opaque and transparent sprites.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn same_order() {
+    for reversed_z in [false, true] {
+        for alpha in [1.0, 0.5] {
+            let spritesheet = Spritesheet::new();
+
+            let depth = DepthSettings {
+                reversed_z,
+                ..DepthSettings::default()
+            };
+
+            let scene = same_order_scene([&spritesheet, &spritesheet], [alpha, alpha]);
+
+            let image = render_with_depth(WINDOW_SIZE, depth, scene, |engine| load_colors(engine, &spritesheet));
+
+            let pixel = image.get_pixel(12, 12).0;
+
+            assert!(pixel[1] > pixel[0], "reversed_z: {}, alpha: {}, pixel: {:?}", reversed_z, alpha, pixel);
+        }
+    }
+}
+
+/// Sprites with the same order are drawn in scene order even if they are in different spritesheets.
+///
+/// The first sprite's spritesheet is drawn last, so it would be on top without the ties.
+/// Transparent sprites are blended with the sprites which were drawn before them, so only the first sprite is transparent.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn same_order_spritesheets() {
+    for reversed_z in [false, true] {
+        for alpha in [1.0, 0.5] {
+            let first = Spritesheet::new();
+            let second = Spritesheet::new();
+
+            let depth = DepthSettings {
+                reversed_z,
+                ..DepthSettings::default()
+            };
+
+            let scene = same_order_scene([&first, &second], [alpha, 1.0]);
+
+            let image = render_with_depth(WINDOW_SIZE, depth, scene, |engine| {
+                load_colors_with_draw_order(engine, &first, 1);
+                load_colors_with_draw_order(engine, &second, 0);
+            });
+
+            assert_eq!(image.get_pixel(12, 12).0, COLORS[1], "reversed_z: {}, alpha: {}", reversed_z, alpha);
+        }
+    }
+}
+
+
+/// Culling the offscreen sprites on the GPU must not change the output.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn gpu_culling() {
+    let spritesheet = Spritesheet::new();
+
+    // Most of the sprites are outside of the screen
+    let rows = |offset: u32| {
+        engine::Column::builder()
+            .children((0..6).map(|row| {
+                engine::Row::builder()
+                    .children((0..8).map(|index| color_sprite(&spritesheet, (index + row + offset) % 4)))
+                    .build()
+            }))
+            .build()
+    };
+
+    let scene = || {
+        engine::Stack::builder()
+            .child(rows(0))
+
+            // The sprites inside of the Mask are drawn with a separate draw
+            .child(engine::Mask::builder()
+                .child(engine::LineStrip::builder()
+                    .thickness(Px(12))
+                    .points(vec![
+                        LinePoint { x: 0.0, y: 0.0 },
+                        LinePoint { x: 1.0, y: 1.0 },
+                    ])
+                    .build())
+
+                .child(rows(1))
+
+                .build())
+
+            .build()
+    };
+
+    let image = render_with_gpu_culling(WINDOW_SIZE, scene(), |engine| load_colors(engine, &spritesheet));
+
+    let expected = render(WINDOW_SIZE, scene(), |engine| load_colors(engine, &spritesheet));
+
+    compare(&image, &expected, Tolerance::default()).unwrap();
+}
+
+
+/// Selecting the animation frames with a compute shader must display the same frames as the vertex shader.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn gpu_animation() {
+    let spritesheet = Spritesheet::new();
+
+    let animated = |start: u32, frames: u32, duration: f32, mode: AnimationMode| {
+        engine::Sprite::builder()
+            .spritesheet(spritesheet.clone())
+            .tile(color_tile(start))
+            .animation(Some(SpriteAnimation { frames, duration, offset_x: 8, offset_y: 0, mode }))
+            .size(Size {
+                width: Px(16),
+                height: Px(16),
+            })
+            .build()
+    };
+
+    // At 1400 milliseconds the frames are 0, 2, and 1
+    let scene = || {
+        engine::Row::builder()
+            .child(animated(0, 4, 350.0, AnimationMode::Loop))
+            .child(animated(0, 4, 350.0, AnimationMode::Pendulum))
+            .child(animated(1, 3, 200.0, AnimationMode::Loop))
+            .build()
+    };
+
+    let load = |engine: &mut Engine| {
+        load_colors(engine, &spritesheet);
+        engine.set_time(1400.0);
+    };
+
+    let image = render_with_gpu_animation(WINDOW_SIZE, scene(), load);
+
+    let expected = render(WINDOW_SIZE, engine::Row::builder()
+        .child(color_sprite(&spritesheet, 0))
+        .child(color_sprite(&spritesheet, 2))
+        .child(color_sprite(&spritesheet, 2))
+        .build(), |engine| load_colors(engine, &spritesheet));
+
+    compare(&image, &expected, Tolerance::default()).unwrap();
+
+    let vertex = render(WINDOW_SIZE, scene(), load);
+
+    compare(&vertex, &expected, Tolerance::default()).unwrap();
+}
+
+
+/// Drawing the opaque sprites of multiple spritesheets with a single draw call must not change the output.
+#[test]
+#[cfg_attr(not(feature = "gpu"), ignore = "requires a GPU adapter, run with `--features gpu`")]
+fn sprite_batching() {
+    // There are more spritesheets than fit into a single batch
+    let spritesheets = (0..10).map(|_| Spritesheet::new()).collect::<Vec<_>>();
+    let indexed = [Spritesheet::new(), Spritesheet::new()];
+
+    let scene = || {
+        engine::Column::builder()
+            .children((0..2).map(|row| {
+                engine::Row::builder()
+                    .children((0..4).map(|index| {
+                        let spritesheet = &spritesheets[(row * 4 + index) as usize];
+                        color_sprite(spritesheet, (index + row) % 4)
+                    }))
+                    .build()
+            }))
+
+            .child(engine::Row::builder()
+                .children((0..4).map(|index| indexed_sprite(&indexed[index % 2], (index / 2) as u32, 16)))
+                .build())
+
+            // The sprites inside of the Mask use a different stencil
+            .child(engine::Mask::builder()
+                .child(engine::LineStrip::builder()
+                    .thickness(Px(12))
+                    .points(vec![
+                        LinePoint { x: 0.0, y: 0.0 },
+                        LinePoint { x: 1.0, y: 1.0 },
+                    ])
+                    .build())
+
+                .child(engine::Row::builder()
+                    .children((0..4).map(|index| color_sprite(&spritesheets[(index + 8) as usize % 10], index)))
+                    .build())
+
+                .build())
+
+            .build()
+    };
+
+    let load = |engine: &mut Engine| {
+        for spritesheet in spritesheets.iter() {
+            load_colors(engine, spritesheet);
+        }
+
+        for spritesheet in indexed.iter() {
+            load_indexed(engine, spritesheet);
+        }
+    };
+
+    let image = render_with_sprite_batching(WINDOW_SIZE, scene(), load);
+
+    let expected = render(WINDOW_SIZE, scene(), load);
+
+    compare(&image, &expected, Tolerance::default()).unwrap();
+}
@@ -0,0 +1,191 @@
+//! Benchmarks for the layout and prerender hot paths.
+//!
+//! ```sh
+//! cargo bench -p rusted-battalions-engine --features bench
+//! ```
+//!
+//! The benchmarks which need an Engine are skipped if there isn't a GPU adapter available.
+
+use std::pin::Pin;
+use std::future::Future;
+use std::sync::Arc;
+use criterion::{Criterion, criterion_group, criterion_main};
+use futures::executor::{LocalPool, LocalSpawner, block_on};
+use futures::task::LocalSpawnExt;
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{
+    Engine, HeadlessSettings, DepthSettings, Node, Spawner, WindowSize, Spritesheet, SpritesheetSettings,
+    Texture, Tile, RgbaImage, GrayscaleImage, BitmapFont, BitmapFontSettings,
+    BitmapFontSupported, CharSize, Size, Px, ParentWidth,
+};
+use rusted_battalions_engine::bench::{relayout, InstanceUpload, HandlesLookup};
+
+
+struct BenchSpawner {
+    spawner: LocalSpawner,
+}
+
+impl Spawner for BenchSpawner {
+    fn spawn_local(&self, future: Pin<Box<dyn Future<Output = ()> + 'static>>) {
+        self.spawner.spawn_local(future).unwrap();
+    }
+}
+
+
+/// Creates a headless Engine and runs the initial layout.
+///
+/// Returns `None` if there isn't a GPU adapter available.
+fn engine<F>(scene: Node, load: F) -> Option<(LocalPool, Engine)> where F: FnOnce(&mut Engine) {
+    let mut pool = LocalPool::new();
+
+    let spawner = Arc::new(BenchSpawner {
+        spawner: pool.spawner(),
+    });
+
+    let mut engine = block_on(Engine::new_headless(HeadlessSettings {
+        scene,
+        window_size: WindowSize {
+            width: 1920,
+            height: 1080,
+        },
+        spawner,
+        depth: DepthSettings::default(),
+        pipeline_cache: None,
+        gpu_animation: false,
+        gpu_culling: false,
+        sprite_batching: false,
+    }))?;
+
+    load(&mut engine);
+
+    pool.run_until_stalled();
+
+    relayout(&mut engine);
+
+    Some((pool, engine))
+}
+
+
+fn sprites(c: &mut Criterion) {
+    let spritesheet = Spritesheet::new();
+
+    // 100 rows of 100 sprites
+    let scene = engine::Column::builder()
+        .children((0..100).map(|_| {
+            engine::Row::builder()
+                .children((0..100).map(|_| {
+                    engine::Sprite::builder()
+                        .spritesheet(spritesheet.clone())
+                        .tile(Tile {
+                            start_x: 0,
+                            start_y: 0,
+                            end_x: 8,
+                            end_y: 8,
+                        })
+                        .size(Size {
+                            width: Px(8),
+                            height: Px(8),
+                        })
+                        .build()
+                }))
+                .build()
+        }))
+        .build();
+
+    let engine = engine(scene, |engine| {
+        let image = RgbaImage::from_fn("sprites", 8, 8, |_, _| image::Rgba([255, 255, 255, 255]));
+
+        let texture = Texture::new();
+
+        texture.load(engine, &image);
+
+        spritesheet.load(engine, SpritesheetSettings {
+            label: "sprites",
+            texture: &texture,
+            palette: None,
+            draw_order: 0,
+            sorted: false,
+        });
+    });
+
+    if let Some((_pool, mut engine)) = engine {
+        c.bench_function("relayout 10k sprites", |b| b.iter(|| relayout(&mut engine)));
+    }
+}
+
+
+fn bitmap_text(c: &mut Criterion) {
+    let font = BitmapFont::new();
+
+    let text: String = (0..5000).map(|index| {
+        if index % 100 == 99 {
+            '\n'
+
+        } else {
+            char::from(b'!' + (index % 94) as u8)
+        }
+    }).collect();
+
+    let scene = engine::BitmapText::builder()
+        .text(text.into())
+        .font(font.clone())
+        .size(Size {
+            width: ParentWidth(1.0),
+            height: engine::SmallestHeight(1.0),
+        })
+        .char_size(CharSize {
+            width: Px(8),
+            height: Px(16),
+        })
+        .build();
+
+    let engine = engine(scene, |engine| {
+        let image = GrayscaleImage::from_fn("font", 16 * 8, 8 * 8, |_, _| image::Luma([255]));
+
+        let texture = Texture::new();
+
+        texture.load(engine, &image);
+
+        font.load(engine, BitmapFontSettings {
+            texture: &texture,
+            supported: BitmapFontSupported {
+                start: '\u{0000}',
+                end: '\u{007F}',
+                replace: '\u{001A}',
+            },
+            columns: 16,
+            tile_width: 4,
+            tile_height: 8,
+        });
+    });
+
+    if let Some((_pool, mut engine)) = engine {
+        c.bench_function("relayout BitmapText 5k glyphs", |b| b.iter(|| relayout(&mut engine)));
+    }
+}
+
+
+fn instance_upload(c: &mut Criterion) {
+    if let Some((_pool, engine)) = engine(engine::Stack::builder().build(), |_| {}) {
+        let mut instances = InstanceUpload::new(10_000);
+
+        c.bench_function("InstanceVec upload 10k", |b| b.iter(|| instances.upload(&engine)));
+    }
+}
+
+
+fn handles_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Handles lookup");
+
+    for len in [10, 100, 1000] {
+        let handles = HandlesLookup::new(len);
+
+        group.bench_function(format!("{}", len), |b| b.iter(|| handles.lookup_all()));
+    }
+
+    group.finish();
+}
+
+
+criterion_group!(benches, sprites, bitmap_text, instance_upload, handles_lookup);
+criterion_main!(benches);
@@ -28,14 +28,14 @@ impl Window {
 }
 
 impl HasWindowHandle for Window {
-    fn window_handle(&self) -> Result<WindowHandle, HandleError> {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
         // SAFETY: This is safe because we guarantee that each ID is unique
         Ok(unsafe { WindowHandle::borrow_raw(RawWindowHandle::from(WebWindowHandle::new(self.id))) })
     }
 }
 
 impl HasDisplayHandle for Window {
-    fn display_handle(&self) -> Result<DisplayHandle, HandleError> {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
         Ok(DisplayHandle::web())
     }
 }
@@ -0,0 +1,74 @@
+//! Internal APIs which are only exposed for the benchmarks in the `benches` folder.
+//!
+//! These are not stable, do not use them.
+
+use crate::Engine;
+use crate::scene::{Handle, Handles};
+use crate::util::buffer::{InstanceVec, InstanceVecOptions};
+
+
+/// Submits the pending buffer writes, otherwise wgpu keeps every write in staging memory.
+fn flush(engine: &Engine) {
+    engine.state.queue.submit(std::iter::empty());
+    engine.state.device.poll(wgpu::Maintain::Wait);
+}
+
+
+/// Relayouts the entire scene and prepares it for rendering, without drawing anything.
+pub fn relayout(engine: &mut Engine) {
+    engine.scene.changed.trigger_layout_change();
+
+    let _ = engine.scene.prerender(&engine.state);
+
+    flush(engine);
+}
+
+
+/// Uploads an [`InstanceVec`] which has the same size as the `GPUSprite` instances.
+pub struct InstanceUpload {
+    values: InstanceVec<[f32; 20]>,
+}
+
+impl InstanceUpload {
+    pub fn new(len: usize) -> Self {
+        Self {
+            values: InstanceVec::with_values(vec![[0.0; 20]; len]),
+        }
+    }
+
+    /// Changes a single instance and then uploads the whole buffer.
+    pub fn upload(&mut self, engine: &Engine) {
+        self.values[0][0] += 1.0;
+
+        let _ = self.values.update_buffer(&engine.state, &InstanceVecOptions {
+            label: Some("InstanceUpload"),
+        });
+
+        flush(engine);
+    }
+}
+
+
+/// Looks up every [`Handle`] in a [`Handles`].
+pub struct HandlesLookup {
+    handles: Handles<usize>,
+    keys: Vec<Handle>,
+}
+
+impl HandlesLookup {
+    pub fn new(len: usize) -> Self {
+        let mut handles = Handles::new();
+
+        let keys = (0..len).map(|index| {
+            let handle = Handle::new();
+            handles.insert(&handle, index);
+            handle
+        }).collect();
+
+        Self { handles, keys }
+    }
+
+    pub fn lookup_all(&self) -> usize {
+        self.keys.iter().map(|key| *self.handles.get(key).unwrap()).sum()
+    }
+}
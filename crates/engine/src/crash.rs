@@ -0,0 +1,319 @@
+use std::fmt;
+use std::sync::{Mutex, Once, TryLockError};
+use std::panic::PanicHookInfo;
+
+use crate::{AdapterInfo, EngineStats, SceneSnapshot, NodeSnapshot};
+use crate::profiler::{now};
+
+
+/// How often (in milliseconds) the stats and scene are recorded, because recording the scene needs an extra relayout.
+const RECORD_INTERVAL: f64 = 1000.0;
+
+
+/// The most recent state of the engine, this is global because the panic hook is global.
+///
+/// If there are multiple Engines then it contains the state of whichever Engine was updated last.
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    adapter: None,
+    stats: None,
+    stats_time: None,
+    scene: None,
+    scene_time: None,
+});
+
+static INSTALL: Once = Once::new();
+
+
+struct CrashContext {
+    adapter: Option<AdapterInfo>,
+    stats: Option<EngineStats>,
+    stats_time: Option<f64>,
+    scene: Option<SceneSnapshot>,
+    scene_time: Option<f64>,
+}
+
+#[inline]
+fn is_stale(time: Option<f64>, now: f64) -> bool {
+    time.map_or(true, |time| (now - time) >= RECORD_INTERVAL)
+}
+
+fn update(f: impl FnOnce(&mut CrashContext)) {
+    let mut lock = CONTEXT.lock().unwrap_or_else(|error| error.into_inner());
+    f(&mut lock);
+}
+
+pub(crate) fn set_adapter(adapter: &AdapterInfo) {
+    update(|context| {
+        context.adapter = Some(adapter.clone());
+    });
+}
+
+/// Records the stats, this is throttled so it doesn't clone the stats every frame.
+pub(crate) fn set_stats(stats: &EngineStats) {
+    let now = now();
+
+    update(|context| {
+        if is_stale(context.stats_time, now) {
+            context.stats = Some(stats.clone());
+            context.stats_time = Some(now);
+        }
+    });
+}
+
+/// Whether enough time has passed that the scene should be recorded again, see [`set_scene`].
+pub(crate) fn wants_scene() -> bool {
+    let now = now();
+    let lock = CONTEXT.lock().unwrap_or_else(|error| error.into_inner());
+    is_stale(lock.scene_time, now)
+}
+
+pub(crate) fn set_scene(scene: SceneSnapshot) {
+    let now = now();
+
+    update(|context| {
+        context.scene = Some(scene);
+        context.scene_time = Some(now);
+    });
+}
+
+
+/// Everything the engine knows when a panic happens, see [`EngineSettings::crash_report`](crate::EngineSettings::crash_report).
+///
+/// The [`Display`](fmt::Display) output is plain text which is intended to be attached to bug reports.
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    /// The panic message.
+    pub message: String,
+
+    /// The file and line where the panic happened.
+    pub location: Option<String>,
+
+    /// The GPU which the engine is using.
+    pub adapter: Option<AdapterInfo>,
+
+    /// The stats of a recently rendered frame, they are recorded at most once per second.
+    pub stats: Option<EngineStats>,
+
+    /// The layout from a recent relayout, it is recorded at most once per second.
+    ///
+    /// It doesn't include any changes which caused the panic.
+    pub scene: Option<SceneSnapshot>,
+}
+
+impl CrashReport {
+    fn new(info: &PanicHookInfo<'_>) -> Self {
+        let payload = info.payload();
+
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+
+        } else {
+            "Unknown panic".to_string()
+        };
+
+        let location = info.location().map(|location| location.to_string());
+
+        // The panic might have happened while the context was locked, so it doesn't wait for the lock
+        match CONTEXT.try_lock() {
+            Ok(context) => Self::with_context(message, location, &context),
+            Err(TryLockError::Poisoned(error)) => Self::with_context(message, location, &error.into_inner()),
+            Err(TryLockError::WouldBlock) => Self {
+                message,
+                location,
+                adapter: None,
+                stats: None,
+                scene: None,
+            },
+        }
+    }
+
+    fn with_context(message: String, location: Option<String>, context: &CrashContext) -> Self {
+        Self {
+            message,
+            location,
+            adapter: context.adapter.clone(),
+            stats: context.stats.clone(),
+            scene: context.scene.clone(),
+        }
+    }
+}
+
+fn fmt_node(f: &mut fmt::Formatter<'_>, node: &NodeSnapshot, depth: usize) -> fmt::Result {
+    writeln!(
+        f,
+        "{:indent$}{} x={} y={} width={} height={} order={}",
+        "",
+        node.kind,
+        node.x,
+        node.y,
+        node.width,
+        node.height,
+        node.order,
+        indent = depth * 2,
+    )?;
+
+    for child in node.children.iter() {
+        fmt_node(f, child, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== Rusted Battalions crash report ===")?;
+        writeln!(f, "message: {}", self.message)?;
+
+        if let Some(location) = &self.location {
+            writeln!(f, "location: {}", location)?;
+        }
+
+        writeln!(f, "\n=== Adapter ===")?;
+
+        match &self.adapter {
+            Some(adapter) => {
+                writeln!(f, "name: {}", adapter.name)?;
+                writeln!(f, "backend: {:?}", adapter.backend)?;
+                writeln!(f, "device type: {:?}", adapter.device_type)?;
+                writeln!(f, "driver: {} {}", adapter.driver, adapter.driver_info)?;
+            },
+            None => writeln!(f, "unknown")?,
+        }
+
+        writeln!(f, "\n=== Stats ===")?;
+
+        match &self.stats {
+            Some(stats) => {
+                writeln!(f, "cpu: layout {:.3}ms draw {:.3}ms", stats.cpu.layout, stats.cpu.draw)?;
+
+                if let Some(gpu) = stats.gpu_time() {
+                    writeln!(f, "gpu: {:.3}ms", gpu)?;
+                }
+
+                writeln!(f, "depth: {:?}", stats.depth)?;
+
+                for draw in stats.draws.iter() {
+                    writeln!(f, "draw {}: {} instances (alpha: {})", draw.label, draw.instances, draw.alpha)?;
+                }
+            },
+            None => writeln!(f, "no frames were rendered")?,
+        }
+
+        writeln!(f, "\n=== Scene ===")?;
+
+        match &self.scene {
+            Some(scene) => {
+                writeln!(f, "window: {}x{}", scene.window_width, scene.window_height)?;
+
+                match &scene.root {
+                    Some(root) => fmt_node(f, root, 0)?,
+                    None => writeln!(f, "root is invisible")?,
+                }
+            },
+            None => writeln!(f, "no layout was recorded")?,
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(target_arch = "wasm32")]
+fn write_report(report: &CrashReport) {
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = console, js_name = error)]
+        fn console_error(message: &str);
+    }
+
+    console_error(&report.to_string());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_report(report: &CrashReport) {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+
+    let path = std::env::temp_dir().join(format!("rusted-battalions-crash-{}.txt", time));
+
+    match std::fs::write(&path, report.to_string()) {
+        Ok(()) => eprintln!("Crash report was saved to {}, please attach it to the bug report", path.display()),
+        Err(error) => eprintln!("Failed to save crash report to {}: {}\n\n{}", path.display(), error, report),
+    }
+}
+
+
+/// Installs a panic hook which writes a [`CrashReport`].
+///
+/// The previous panic hook still runs afterwards, so it can be combined with other hooks (such as `console_error_panic_hook`).
+///
+/// It is only installed once, even if there are multiple Engines.
+pub(crate) fn install_panic_hook() {
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+
+        std::panic::set_hook(Box::new(move |info| {
+            write_report(&CrashReport::new(info));
+            previous(info);
+        }));
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::CrashReport;
+    use crate::{SceneSnapshot, NodeSnapshot};
+
+    #[test]
+    fn display() {
+        let report = CrashReport {
+            message: "UNEXPECTED INTERNAL BUG, PLEASE REPORT THIS".to_string(),
+            location: Some("src/scene.rs:10:5".to_string()),
+            adapter: None,
+            stats: None,
+            scene: Some(SceneSnapshot {
+                window_width: 800,
+                window_height: 600,
+                root: Some(NodeSnapshot {
+                    kind: "Stack",
+                    x: 0.0,
+                    y: 0.0,
+                    width: 800.0,
+                    height: 600.0,
+                    order: 1.0,
+                    children: vec![NodeSnapshot {
+                        kind: "Sprite",
+                        x: 10.0,
+                        y: 20.0,
+                        width: 16.0,
+                        height: 16.0,
+                        order: 2.0,
+                        children: vec![],
+                    }],
+                }),
+            }),
+        };
+
+        assert_eq!(report.to_string(), "\
+=== Rusted Battalions crash report ===
+message: UNEXPECTED INTERNAL BUG, PLEASE REPORT THIS
+location: src/scene.rs:10:5
+
+=== Adapter ===
+unknown
+
+=== Stats ===
+no frames were rendered
+
+=== Scene ===
+window: 800x600
+Stack x=0 y=0 width=800 height=600 order=1
+  Sprite x=10 y=20 width=16 height=16 order=2
+");
+    }
+}
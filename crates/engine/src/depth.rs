@@ -0,0 +1,144 @@
+//! How the [`Order`](crate::Order) of the nodes is stored in the depth buffer.
+//!
+//! Every node has an order, and the largest order in the scene is `max_order`.
+//! The vertex shaders output `order / max_order` as the depth, so the depth of every
+//! node is between `0.0` and `1.0`, and nodes with a larger depth are drawn on top.
+//!
+//! With [`DepthSettings::reversed_z`] the depth is `1.0 - (order / max_order)` instead,
+//! and nodes with a smaller depth are drawn on top.
+//!
+//! When two sprites have the same order, the sprite which is later in the scene is drawn on top,
+//! even if they are in different spritesheets. Each sprite stores how many sprites with the same
+//! order were laid out before it, and the shader adds that many `tie_step` to the order.
+//! The ties are spread across the first half of [`DepthSettings::min_order_step`], so they are
+//! never drawn on top of a node whose order is at least `min_order_step` larger.
+//!
+//! Transparent sprites don't write to the depth buffer, so a transparent sprite is drawn on top of
+//! the transparent sprites which were drawn before it, see [`SpritesheetSettings::draw_order`](crate::SpritesheetSettings::draw_order).
+//!
+//! Other nodes with the same order are drawn in the order that they were laid out, and the depth
+//! test passes for equal depths, so the later node is on top if both nodes are drawn by the same renderer.
+//! In both cases the result is the same every frame, even when the scene is laid out again.
+//!
+//! The orders are stored as `f32`, so two orders can only be distinguished if they are at least
+//! `max_order * f32::EPSILON` apart. This means a scene with a huge `max_order` loses precision
+//! for the small differences, such as [`Order::Parent(0.1)`](crate::Order::Parent).
+
+
+/// The format of the depth buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthFormat {
+    /// At least 24 bits of depth, and 8 bits of stencil.
+    #[default]
+    Depth24PlusStencil8,
+
+    /// 32-bit float depth, without a stencil.
+    ///
+    /// [`Mask`](crate::Mask) nodes need the stencil, so they don't clip their children with this format.
+    Depth32Float,
+}
+
+impl DepthFormat {
+    #[inline]
+    pub(crate) fn texture_format(&self) -> wgpu::TextureFormat {
+        match self {
+            Self::Depth24PlusStencil8 => wgpu::TextureFormat::Depth24PlusStencil8,
+            Self::Depth32Float => wgpu::TextureFormat::Depth32Float,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn has_stencil(&self) -> bool {
+        match self {
+            Self::Depth24PlusStencil8 => true,
+            Self::Depth32Float => false,
+        }
+    }
+}
+
+
+/// Settings for the depth buffer, see the [module documentation](self) for how the orders are converted into depth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthSettings {
+    pub format: DepthFormat,
+
+    /// Stores the nodes which are on top closer to `0.0` instead of `1.0`.
+    ///
+    /// Floating point formats are more precise close to `0.0`, so this is useful with [`DepthFormat::Depth32Float`].
+    pub reversed_z: bool,
+
+    /// The smallest difference between two orders which must be drawn correctly.
+    ///
+    /// If the scene's `max_order` is so big that this difference can't be stored, then a warning is logged.
+    /// See [`DepthStats::resolution`].
+    pub min_order_step: f32,
+}
+
+impl Default for DepthSettings {
+    fn default() -> Self {
+        Self {
+            format: DepthFormat::default(),
+            reversed_z: false,
+            min_order_step: 1.0 / 64.0,
+        }
+    }
+}
+
+impl DepthSettings {
+    /// The value which the depth buffer is cleared to, it is behind every node.
+    #[inline]
+    pub(crate) fn clear_depth(&self) -> f32 {
+        if self.reversed_z { 1.0 } else { 0.0 }
+    }
+
+    #[inline]
+    pub(crate) fn compare(&self) -> wgpu::CompareFunction {
+        if self.reversed_z {
+            wgpu::CompareFunction::LessEqual
+
+        } else {
+            wgpu::CompareFunction::GreaterEqual
+        }
+    }
+}
+
+
+/// The depth precision of the most recent frame, see [`EngineStats::depth`](crate::EngineStats::depth).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DepthStats {
+    /// The largest order in the scene, every order is divided by this.
+    pub max_order: f32,
+
+    /// The smallest difference between two orders which is guaranteed to be drawn correctly.
+    pub resolution: f32,
+}
+
+impl DepthStats {
+    pub(crate) fn new(max_order: f32) -> Self {
+        Self {
+            max_order,
+            resolution: max_order.abs() * f32::EPSILON,
+        }
+    }
+
+    /// Whether orders which are `min_order_step` apart can be distinguished.
+    #[inline]
+    pub fn is_precise(&self, min_order_step: f32) -> bool {
+        self.resolution <= min_order_step
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{DepthStats, DepthSettings};
+
+    #[test]
+    fn resolution() {
+        let min_order_step = DepthSettings::default().min_order_step;
+
+        assert!(DepthStats::new(1.0).is_precise(min_order_step));
+        assert!(DepthStats::new(100_000.0).is_precise(min_order_step));
+        assert!(!DepthStats::new(1_000_000.0).is_precise(min_order_step));
+    }
+}
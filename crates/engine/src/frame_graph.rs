@@ -0,0 +1,239 @@
+/// Identifies a pass in the frame graph, see [`Engine::add_pass`](crate::Engine::add_pass).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassId {
+    /// Clears the scene target, the depth buffer, and the stencil buffer.
+    Clear,
+
+    /// Draws every [`Node`](crate::Node) in the scene, including masks, sprites, shapes, and text.
+    ///
+    /// The nodes are sorted together by their [`Order`](crate::Order), so they can't be split into separate passes.
+    Scene,
+
+    /// Applies the [`ScreenEffect`](crate::ScreenEffect), it is skipped while there isn't an effect.
+    Postprocess,
+
+    /// A pass which was added with [`Engine::add_pass`](crate::Engine::add_pass).
+    Custom(&'static str),
+}
+
+
+/// The texture which a pass draws to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassTarget {
+    /// The texture which the scene is drawn to, it is the input of the [`PassId::Postprocess`] pass.
+    ///
+    /// When there isn't a [`ScreenEffect`](crate::ScreenEffect) this is the same as [`PassTarget::Output`].
+    Scene,
+
+    /// The window, or the headless target.
+    Output,
+}
+
+
+/// Where a custom pass is inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassPosition {
+    Before(PassId),
+    After(PassId),
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct PassSettings {
+    /// The name of the pass, it must be unique. It is used for debugging and for [`PassId::Custom`].
+    pub label: &'static str,
+
+    pub position: PassPosition,
+
+    pub target: PassTarget,
+
+    /// Whether the pass uses the depth and stencil buffers which were written by the [`PassId::Scene`] pass.
+    ///
+    /// The pass must be after the [`PassId::Clear`] pass, and its pipelines must use [`PassContext::depth_format`].
+    pub depth_stencil: bool,
+}
+
+
+/// Information for creating the resources of a custom pass.
+pub struct PassContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+
+    /// The format of the pass's target.
+    pub format: wgpu::TextureFormat,
+
+    /// The format of the depth / stencil buffer, it is `None` if the pass doesn't use it.
+    pub depth_format: Option<wgpu::TextureFormat>,
+
+    pub window_size: crate::WindowSize,
+}
+
+
+/// A render pass which is inserted into the frame graph, see [`Engine::add_pass`](crate::Engine::add_pass).
+pub trait CustomPass {
+    /// This is called every frame before any passes are run, it is used for creating pipelines and writing buffers.
+    fn prepare(&mut self, _context: &PassContext) {}
+
+    /// Draws into the pass's target. The target is not cleared, so it contains the output of the previous passes.
+    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>);
+}
+
+
+pub(crate) struct CustomNode {
+    pub(crate) settings: PassSettings,
+    pub(crate) pass: Box<dyn CustomPass>,
+}
+
+
+/// The passes which are run every frame, in order.
+pub(crate) struct FrameGraph {
+    order: Vec<PassId>,
+    custom: Vec<CustomNode>,
+}
+
+impl FrameGraph {
+    pub(crate) fn new() -> Self {
+        Self {
+            order: vec![PassId::Clear, PassId::Scene, PassId::Postprocess],
+            custom: vec![],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn order(&self) -> &[PassId] {
+        &self.order
+    }
+
+    pub(crate) fn custom(&self, label: &str) -> &CustomNode {
+        self.custom.iter().find(|node| node.settings.label == label).expect("Missing custom pass")
+    }
+
+    pub(crate) fn custom_mut(&mut self) -> impl Iterator<Item = &mut CustomNode> {
+        self.custom.iter_mut()
+    }
+
+    fn index(&self, id: PassId) -> Option<usize> {
+        self.order.iter().position(|x| *x == id)
+    }
+
+    fn target(&self, id: PassId) -> PassTarget {
+        match id {
+            PassId::Clear | PassId::Scene => PassTarget::Scene,
+            PassId::Postprocess => PassTarget::Output,
+            PassId::Custom(label) => self.custom(label).settings.target,
+        }
+    }
+
+    fn uses_depth_stencil(&self, id: PassId) -> bool {
+        match id {
+            PassId::Clear | PassId::Scene => true,
+            PassId::Postprocess => false,
+            PassId::Custom(label) => self.custom(label).settings.depth_stencil,
+        }
+    }
+
+    /// Returns whether the next pass which draws to the scene target uses the depth / stencil buffers.
+    ///
+    /// This is used to merge the [`PassId::Clear`] pass into the next pass.
+    pub(crate) fn next_scene_pass_uses_depth_stencil(&self, index: usize) -> bool {
+        self.order[(index + 1)..].iter()
+            .find(|id| self.target(**id) == PassTarget::Scene)
+            .map_or(false, |id| self.uses_depth_stencil(*id))
+    }
+
+    pub(crate) fn insert(&mut self, settings: PassSettings, pass: Box<dyn CustomPass>) {
+        let label = settings.label;
+
+        assert!(self.index(PassId::Custom(label)).is_none(), "Pass {} already exists", label);
+
+        let index = match settings.position {
+            PassPosition::Before(id) => self.index(id),
+            PassPosition::After(id) => self.index(id).map(|index| index + 1),
+        }.unwrap_or_else(|| panic!("Pass {} is positioned relative to a pass which doesn't exist", label));
+
+        let clear = self.index(PassId::Clear).unwrap();
+        let postprocess = self.index(PassId::Postprocess).unwrap();
+
+        // The target is cleared by the Clear pass, so anything drawn before it is lost
+        assert!(index > clear, "Pass {} must be after the Clear pass", label);
+
+        match settings.target {
+            PassTarget::Scene => {
+                assert!(index <= postprocess, "Pass {} draws to PassTarget::Scene so it must be before the Postprocess pass", label);
+            },
+
+            // The Postprocess pass overwrites the output
+            PassTarget::Output => {
+                assert!(index > postprocess, "Pass {} draws to PassTarget::Output so it must be after the Postprocess pass", label);
+            },
+        }
+
+        self.order.insert(index, PassId::Custom(label));
+        self.custom.push(CustomNode { settings, pass });
+    }
+
+    pub(crate) fn remove(&mut self, label: &str) -> Option<Box<dyn CustomPass>> {
+        let index = self.custom.iter().position(|node| node.settings.label == label)?;
+
+        self.order.retain(|id| !matches!(id, PassId::Custom(x) if *x == label));
+
+        Some(self.custom.remove(index).pass)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameGraph, CustomPass, PassSettings, PassId, PassPosition, PassTarget};
+
+    struct Empty;
+
+    impl CustomPass for Empty {
+        fn render(&self, _render_pass: &mut wgpu::RenderPass<'_>) {}
+    }
+
+    fn settings(label: &'static str, position: PassPosition, target: PassTarget, depth_stencil: bool) -> PassSettings {
+        PassSettings { label, position, target, depth_stencil }
+    }
+
+    #[test]
+    fn order() {
+        let mut graph = FrameGraph::new();
+
+        graph.insert(settings("background", PassPosition::Before(PassId::Scene), PassTarget::Scene, false), Box::new(Empty));
+        graph.insert(settings("outline", PassPosition::After(PassId::Scene), PassTarget::Scene, true), Box::new(Empty));
+        graph.insert(settings("overlay", PassPosition::After(PassId::Postprocess), PassTarget::Output, false), Box::new(Empty));
+
+        assert_eq!(graph.order(), &[
+            PassId::Clear,
+            PassId::Custom("background"),
+            PassId::Scene,
+            PassId::Custom("outline"),
+            PassId::Postprocess,
+            PassId::Custom("overlay"),
+        ]);
+
+        // The background doesn't use the depth buffer, so the Clear pass can't be merged into it
+        assert!(!graph.next_scene_pass_uses_depth_stencil(0));
+
+        assert!(graph.remove("background").is_some());
+        assert!(graph.remove("background").is_none());
+
+        assert!(graph.next_scene_pass_uses_depth_stencil(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be after the Postprocess pass")]
+    fn output_before_postprocess() {
+        let mut graph = FrameGraph::new();
+        graph.insert(settings("overlay", PassPosition::After(PassId::Scene), PassTarget::Output, false), Box::new(Empty));
+    }
+
+    #[test]
+    #[should_panic(expected = "already exists")]
+    fn duplicate_label() {
+        let mut graph = FrameGraph::new();
+        graph.insert(settings("outline", PassPosition::After(PassId::Scene), PassTarget::Scene, true), Box::new(Empty));
+        graph.insert(settings("outline", PassPosition::After(PassId::Scene), PassTarget::Scene, true), Box::new(Empty));
+    }
+}
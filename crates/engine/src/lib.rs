@@ -5,20 +5,38 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use postprocess::Postprocess;
+pub use postprocess::ScreenEffect;
+use frame_graph::FrameGraph;
+pub use depth::{DepthSettings, DepthFormat, DepthStats};
+pub use frame_graph::{PassId, PassTarget, PassPosition, PassSettings, PassContext, CustomPass};
+use profiler::Profiler;
+use resources::ResourceTracker;
+use scene::{PipelineCache, SpriteAnimator, SpriteCulling, SpriteRenderer};
+use signal_util::FrameClock;
 
 mod util;
 mod postprocess;
+mod frame_graph;
+pub mod depth;
+mod profiler;
+mod resources;
 mod scene;
+mod crash;
 pub mod backend;
+pub mod signal_util;
+
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
 
 pub use util::buffer::{RgbaImage, IndexedImage, GrayscaleImage};
+pub use tracing::Level as LogLevel;
+pub use profiler::{EngineStats, DrawStats, CpuStats};
+pub use resources::{ResourceStats};
+pub use crash::{CrashReport};
 pub use scene::*;
 
-pub use wgpu::WindowHandle;
-
-
-const HAS_STENCIL: bool = false;
-pub(crate) const DEBUG: bool = false;
+pub use wgpu::{WindowHandle, PowerPreference, AdapterInfo};
 
 
 #[derive(Debug, Clone, Copy)]
@@ -45,6 +63,164 @@ pub struct EngineSettings<Window> where Window: wgpu::WindowHandle {
     pub scene: Node,
     pub window_size: WindowSize,
     pub spawner: Arc<dyn Spawner>,
+
+    /// Installs a global [`tracing`] subscriber which logs at this level.
+    ///
+    /// On wasm it logs to the browser console, otherwise it logs to stdout.
+    ///
+    /// If it's `None` then no subscriber is installed, so you can install your own.
+    pub log_level: Option<LogLevel>,
+
+    /// Measures how long each draw takes on the GPU, see [`Engine::stats`].
+    ///
+    /// This is ignored if the GPU doesn't support timestamp queries.
+    pub profile: bool,
+
+    /// Multiplies every [`Length::Px`], see [`Engine::set_ui_scale`].
+    pub ui_scale: f32,
+
+    pub depth: DepthSettings,
+
+    /// See [`Engine::set_quality`].
+    pub quality: QualitySettings,
+
+    /// Which GPU is preferred on devices with multiple GPUs, such as laptops with an integrated and a dedicated GPU.
+    ///
+    /// [`PowerPreference::LowPower`] prefers the integrated GPU, which uses less battery.
+    pub power_preference: PowerPreference,
+
+    /// Uses a software adapter (such as WARP or llvmpipe) instead of the GPU, this is useful for working around driver bugs.
+    ///
+    /// If there isn't a software adapter then the engine fails to start.
+    pub force_fallback_adapter: bool,
+
+    /// Installs a panic hook which saves a [`CrashReport`] when the program panics.
+    ///
+    /// On wasm the report is logged to the browser console, otherwise it is saved into the temp directory.
+    ///
+    /// The report contains a recent layout, so a [`SceneSnapshot`] is recorded at most once per second, which makes the relayout slower.
+    pub crash_report: bool,
+
+    /// The data from [`Engine::pipeline_cache_data`], so the shaders don't need to be recompiled by the driver.
+    ///
+    /// It is ignored if it was saved with a different GPU or driver.
+    pub pipeline_cache: Option<Vec<u8>>,
+
+    /// Selects the frame of every [`SpriteAnimation`] with a compute shader, so the sprite
+    /// instances don't need to be updated or recalculated when the time changes.
+    ///
+    /// If the GPU doesn't support compute shaders (e.g. WebGL) then the vertex shader selects the frame instead,
+    /// [`EngineInfo::gpu_animation`] says whether it is used.
+    pub gpu_animation: bool,
+
+    /// Keeps the sprite instances on the GPU and removes the offscreen opaque sprites with a compute shader,
+    /// so that very large maps stay fast.
+    ///
+    /// It is ignored if the GPU doesn't support compute shaders and indirect draws (e.g. WebGL),
+    /// [`EngineInfo::gpu_culling`] says whether it is used.
+    pub gpu_culling: bool,
+
+    /// Draws the opaque sprites of multiple spritesheets with a single draw call, which is much faster
+    /// for scenes with many spritesheets. Each sprite has a texture index which selects its spritesheet.
+    ///
+    /// Transparent sprites and sprites with a [`PipelineHandle`] are still drawn separately for each spritesheet.
+    ///
+    /// It is ignored if the GPU doesn't support binding arrays of textures (e.g. WebGL and GL),
+    /// [`EngineInfo::sprite_batching`] says whether it is used.
+    pub sprite_batching: bool,
+}
+
+
+/// Information about the GPU which the engine is using, see [`Engine::info`].
+///
+/// This should be included in bug reports, because many rendering bugs only happen with specific drivers.
+#[derive(Debug, Clone)]
+pub struct EngineInfo {
+    /// The name, backend, and driver of the GPU.
+    pub adapter: AdapterInfo,
+
+    /// Whether [`EngineSettings::gpu_animation`] is enabled and supported.
+    pub gpu_animation: bool,
+
+    /// Whether [`EngineSettings::gpu_culling`] is enabled and supported.
+    pub gpu_culling: bool,
+
+    /// Whether [`EngineSettings::sprite_batching`] is enabled and supported.
+    pub sprite_batching: bool,
+}
+
+
+/// Settings which trade visual quality for performance, so that slow devices can still run smoothly.
+///
+/// Some of the settings are used by the game instead of the engine, so that they can be changed in one place.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualitySettings {
+    /// Whether the [`ScreenEffect`] is rendered.
+    pub post_effects: bool,
+
+    /// Whether [`SpriteAnimation`] plays. If this is `false` then every animated sprite displays its first frame,
+    /// so the scene doesn't need to be rendered every frame.
+    ///
+    /// This doesn't affect `scene.time`, so animated [`PipelineHandle`] keep playing.
+    pub animations: bool,
+
+    /// Whether sprites are reflected in water, this is used by the game.
+    pub reflections: bool,
+
+    /// How many particle effects are displayed, from `0.0` (none) to `1.0` (all), this is used by the game.
+    pub particle_density: f32,
+}
+
+impl QualitySettings {
+    pub const LOW: Self = Self {
+        post_effects: false,
+        animations: false,
+        reflections: false,
+        particle_density: 0.0,
+    };
+
+    pub const MEDIUM: Self = Self {
+        post_effects: true,
+        animations: true,
+        reflections: false,
+        particle_density: 0.5,
+    };
+
+    pub const HIGH: Self = Self {
+        post_effects: true,
+        animations: true,
+        reflections: true,
+        particle_density: 1.0,
+    };
+}
+
+impl Default for QualitySettings {
+    /// Returns [`QualitySettings::HIGH`].
+    #[inline]
+    fn default() -> Self {
+        Self::HIGH
+    }
+}
+
+
+/// Settings for [`Engine::new_headless`].
+pub struct HeadlessSettings {
+    pub scene: Node,
+    pub window_size: WindowSize,
+    pub spawner: Arc<dyn Spawner>,
+    pub depth: DepthSettings,
+
+    /// See [`EngineSettings::pipeline_cache`].
+    pub pipeline_cache: Option<Vec<u8>>,
+
+    /// See [`EngineSettings::gpu_animation`].
+    pub gpu_animation: bool,
+
+    /// See [`EngineSettings::gpu_culling`].
+    pub gpu_culling: bool,
+
+    /// See [`EngineSettings::sprite_batching`].
+    pub sprite_batching: bool,
 }
 
 
@@ -76,27 +252,47 @@ impl Drop for DepthBuffer {
 
 pub(crate) struct EngineState {
     window_size: WindowSize,
-    surface: wgpu::Surface<'static>,
+    ui_scale: f32,
+
+    /// This is `None` when rendering headless.
+    surface: Option<wgpu::Surface<'static>>,
+
+    /// Texture which is rendered into when rendering headless.
+    headless_target: Option<wgpu::Texture>,
+
+    adapter: AdapterInfo,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    depth: DepthSettings,
     depth_buffer: DepthBuffer,
     config: wgpu::SurfaceConfiguration,
+
+    pipeline_cache: PipelineCache,
+
+    /// Whether [`EngineSettings::sprite_batching`] is enabled and supported.
+    sprite_batching: bool,
+
+    /// Whether [`EngineSettings::gpu_culling`] is enabled and supported.
+    gpu_culling: bool,
+
+    /// Whether [`EngineSettings::gpu_animation`] is enabled and supported.
+    gpu_animation: bool,
+
+    /// See [`EngineSettings::crash_report`].
+    crash_report: bool,
+
+    resources: ResourceTracker,
 }
 
 impl EngineState {
-    fn make_depth_buffer(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> DepthBuffer {
+    fn make_depth_buffer(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, depth: &DepthSettings) -> DepthBuffer {
         let size = wgpu::Extent3d {
             width: config.width,
             height: config.height,
             depth_or_array_layers: 1,
         };
 
-        let format = if HAS_STENCIL {
-            wgpu::TextureFormat::Depth24PlusStencil8
-
-        } else {
-            wgpu::TextureFormat::Depth32Float
-        };
+        let format = depth.format.texture_format();
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Depth Buffer"),
@@ -121,7 +317,7 @@ impl EngineState {
             ..wgpu::TextureViewDescriptor::default()
         });
 
-        let stencil_view = if HAS_STENCIL {
+        let stencil_view = if depth.format.has_stencil() {
             Some(texture.create_view(&wgpu::TextureViewDescriptor {
                 label: Some("Depth Buffer Stencil View"),
                 aspect: wgpu::TextureAspect::StencilOnly,
@@ -135,19 +331,105 @@ impl EngineState {
         DepthBuffer { texture, view, depth_view, stencil_view }
     }
 
+    fn make_headless_target(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: EngineState::render_format(config),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
     fn resize(&mut self, window_size: WindowSize) {
         self.window_size = window_size;
         self.config.width = window_size.width;
         self.config.height = window_size.height;
-        self.surface.configure(&self.device, &self.config);
-        self.depth_buffer = EngineState::make_depth_buffer(&self.device, &self.config);
+
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+
+        } else {
+            self.headless_target = Some(EngineState::make_headless_target(&self.device, &self.config));
+        }
+
+        self.depth_buffer = EngineState::make_depth_buffer(&self.device, &self.config, &self.depth);
+    }
+
+    /// Begins a render pass which draws to `view`.
+    ///
+    /// If `clear` is `true` then the view is cleared, and also the depth / stencil buffers if `depth_stencil` is `true`.
+    fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder, view: &'a wgpu::TextureView, label: &str, clear: bool, depth_stencil: bool) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: if clear {
+                        wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.0,
+                            g: 0.0,
+                            b: 0.0,
+                            a: 1.0,
+                        })
+
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: if depth_stencil {
+                Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_buffer.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: if clear { wgpu::LoadOp::Clear(self.depth.clear_depth()) } else { wgpu::LoadOp::Load },
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: if self.depth_buffer.has_stencil() {
+                        Some(wgpu::Operations {
+                            load: if clear { wgpu::LoadOp::Clear(0) } else { wgpu::LoadOp::Load },
+                            store: wgpu::StoreOp::Store,
+                        })
+
+                    } else {
+                        None
+                    },
+                })
+
+            } else {
+                None
+            },
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+
+    /// The format which is used for rendering, it is always sRGB if the surface supports it.
+    ///
+    /// The shaders output linear colors, and the GPU converts them into sRGB when writing.
+    #[inline]
+    pub(crate) fn format(&self) -> wgpu::TextureFormat {
+        Self::render_format(&self.config)
+    }
+
+    fn render_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+        config.view_formats.first().copied().unwrap_or(config.format)
     }
 
     pub(crate) fn depth_stencil_state(&self, depth_write: bool, stencil: Option<wgpu::StencilState>) -> wgpu::DepthStencilState {
         wgpu::DepthStencilState {
             format: self.depth_buffer.format(),
             depth_write_enabled: depth_write,
-            depth_compare: wgpu::CompareFunction::Greater,
+            depth_compare: self.depth.compare(),
             stencil: if self.depth_buffer.has_stencil() {
                 stencil.unwrap_or_else(|| wgpu::StencilState::default())
             } else {
@@ -159,60 +441,96 @@ impl EngineState {
 }
 
 
+/// The backends which [`Engine::new`] uses, so [`Engine::enumerate_adapters`] only returns adapters that the engine can use.
+const BACKENDS: wgpu::Backends = wgpu::Backends::GL;
+
+fn new_instance(backends: wgpu::Backends) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        dx12_shader_compiler: Default::default(),
+        flags: wgpu::InstanceFlags::default(),
+        gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+    })
+}
+
+
+fn init_logging(level: LogLevel) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        tracing_wasm::set_as_global_default_with_config(
+            tracing_wasm::WASMLayerConfigBuilder::new()
+                .set_max_level(level)
+                .build()
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(level)
+            .finish();
+
+        // It's okay if a subscriber was already installed
+        let _ = tracing::subscriber::set_global_default(subscriber);
+    }
+}
+
+
 pub struct Engine {
     state: EngineState,
     postprocess: Option<Postprocess>,
+    graph: FrameGraph,
+    profiler: Option<Profiler>,
+    stats: EngineStats,
     scene: Scene,
+    clock: FrameClock,
+    quality: QualitySettings,
 }
 
+// The wgpu types are only !Send on wasm
+#[cfg(target_arch = "wasm32")]
 static_assertions::assert_not_impl_all!(EngineState: Send, Sync);
+#[cfg(target_arch = "wasm32")]
 static_assertions::assert_not_impl_all!(Option<Postprocess>: Send, Sync);
 static_assertions::assert_not_impl_all!(Scene: Send, Sync);
 
 impl Engine {
     pub async fn new<Window>(settings: EngineSettings<Window>) -> Self where Window: wgpu::WindowHandle + 'static {
+        if let Some(level) = settings.log_level {
+            init_logging(level);
+        }
+
         let window = settings.window;
 
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::GL,
-            dx12_shader_compiler: Default::default(),
-            flags: wgpu::InstanceFlags::default(),
-            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
-        });
+        let instance = new_instance(BACKENDS);
 
         let surface = instance.create_surface(window).unwrap();
 
         let adapter = instance.request_adapter(
             &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
+                power_preference: settings.power_preference,
                 compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                force_fallback_adapter: settings.force_fallback_adapter,
             },
-        ).await.unwrap();
+        ).await.expect("No GPU adapter matches the EngineSettings");
 
-        let (device, queue) = adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
-                // WebGL doesn't support all of wgpu's features, so if
-                // we're building for the web we'll have to disable some.
-                required_limits: wgpu::Limits {
-                    max_texture_dimension_2d: 8192,
-                    ..wgpu::Limits::downlevel_webgl2_defaults()
-                },
-                memory_hints: wgpu::MemoryHints::default(),
-                label: None,
-            },
-            None,
-        ).await.unwrap();
+        let (device, queue, gpu_animation, gpu_culling, sprite_batching) = Self::request_device(&adapter, settings.profile, settings.gpu_animation, settings.gpu_culling, settings.sprite_batching).await;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
-        // Uses sRGB for rendering
+        // Uses sRGB for rendering, if the surface doesn't support sRGB then it renders into an sRGB view instead
         let surface_format = surface_caps.formats.iter()
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
 
+        let view_formats = if surface_format.is_srgb() {
+            vec![]
+
+        } else {
+            vec![surface_format.add_srgb_suffix()]
+        };
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -221,35 +539,250 @@ impl Engine {
             present_mode: surface_caps.present_modes[0],
             alpha_mode: surface_caps.alpha_modes[0],
             desired_maximum_frame_latency: 2,
-            view_formats: vec![],
+            view_formats,
         };
 
         surface.configure(&device, &config);
 
-        let depth_buffer = EngineState::make_depth_buffer(&device, &config);
+        let depth_buffer = EngineState::make_depth_buffer(&device, &config, &settings.depth);
+
+        let pipeline_cache = PipelineCache::new(&device, &adapter.get_info(), settings.pipeline_cache.as_deref());
 
         let state = EngineState {
             window_size: settings.window_size,
-            surface,
+            ui_scale: settings.ui_scale,
+            depth: settings.depth,
+            surface: Some(surface),
+            headless_target: None,
+            adapter: adapter.get_info(),
             device,
             queue,
             config,
             depth_buffer,
+            pipeline_cache,
+            gpu_animation,
+            gpu_culling,
+            sprite_batching,
+            crash_report: settings.crash_report,
+            resources: ResourceTracker::new(),
+        };
+
+        Self::from_state(state, settings.scene, settings.spawner, settings.profile, settings.quality)
+    }
+
+    /// Creates an Engine which renders into a texture instead of a window.
+    ///
+    /// This is used for testing, the frame can be read with [`render_to_image`](Engine::render_to_image).
+    ///
+    /// Returns `None` if there isn't a GPU adapter available.
+    pub async fn new_headless(settings: HeadlessSettings) -> Option<Self> {
+        // The same backends as Engine::new, so the tests render with the same shaders as the game
+        let instance = new_instance(BACKENDS);
+
+        let adapter = instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            },
+        ).await?;
+
+        let (device, queue, gpu_animation, gpu_culling, sprite_batching) = Self::request_device(&adapter, false, settings.gpu_animation, settings.gpu_culling, settings.sprite_batching).await;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: settings.window_size.width,
+            height: settings.window_size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            desired_maximum_frame_latency: 2,
+            view_formats: vec![],
         };
 
-        let scene = Scene::new(&state, settings.scene, settings.spawner);
+        let headless_target = EngineState::make_headless_target(&device, &config);
+
+        let depth_buffer = EngineState::make_depth_buffer(&device, &config, &settings.depth);
+
+        let pipeline_cache = PipelineCache::new(&device, &adapter.get_info(), settings.pipeline_cache.as_deref());
+
+        let state = EngineState {
+            window_size: settings.window_size,
+            ui_scale: 1.0,
+            depth: settings.depth,
+            surface: None,
+            headless_target: Some(headless_target),
+            adapter: adapter.get_info(),
+            device,
+            queue,
+            config,
+            depth_buffer,
+            pipeline_cache,
+            gpu_animation,
+            gpu_culling,
+            sprite_batching,
+            crash_report: false,
+            resources: ResourceTracker::new(),
+        };
+
+        Some(Self::from_state(state, settings.scene, settings.spawner, false, QualitySettings::default()))
+    }
+
+    /// Returns every GPU adapter which the engine can use, this includes software adapters.
+    ///
+    /// This is used to display the available GPUs, the adapter is chosen with [`EngineSettings::power_preference`].
+    ///
+    /// The browser doesn't allow enumerating the GPUs, so this isn't available on wasm.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn enumerate_adapters() -> Vec<AdapterInfo> {
+        let instance = new_instance(BACKENDS);
+
+        instance.enumerate_adapters(BACKENDS).iter().map(|adapter| adapter.get_info()).collect()
+    }
+
+    /// Returns whether GPU animation, GPU culling, and sprite batching are enabled, they are disabled if the adapter doesn't support them.
+    async fn request_device(adapter: &wgpu::Adapter, profile: bool, gpu_animation: bool, gpu_culling: bool, sprite_batching: bool) -> (wgpu::Device, wgpu::Queue, bool, bool, bool) {
+        tracing::info!(adapter = ?adapter.get_info(), "Engine adapter");
+
+        // WebGL doesn't support all of wgpu's features, so if
+        // we're building for the web we'll have to disable some.
+        let limits = wgpu::Limits {
+            max_texture_dimension_2d: 8192,
+            ..wgpu::Limits::downlevel_webgl2_defaults()
+        };
+
+        let gpu_animation = gpu_animation && SpriteAnimator::is_supported(adapter, &limits);
+
+        if gpu_animation {
+            tracing::info!("GPU animation is enabled");
+        }
+
+        let gpu_culling = gpu_culling && SpriteCulling::is_supported(adapter, &limits);
+
+        if gpu_culling {
+            tracing::info!("GPU culling is enabled");
+        }
+
+        let sprite_batching = sprite_batching && SpriteRenderer::supports_batching(adapter, &limits);
+
+        if sprite_batching {
+            tracing::info!("Sprite batching is enabled");
+        }
+
+        let (device, queue) = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: {
+                    let mut features = wgpu::Features::empty();
+
+                    if profile {
+                        features |= adapter.features() & Profiler::FEATURES;
+                    }
+
+                    features |= adapter.features() & PipelineCache::FEATURES;
+
+                    if sprite_batching {
+                        features |= SpriteRenderer::BATCHING_FEATURES;
+                    }
+
+                    features
+                },
+                required_limits: {
+                    let mut limits = limits;
+
+                    if gpu_animation {
+                        limits = SpriteAnimator::limits(limits);
+                    }
+
+                    if gpu_culling {
+                        limits = SpriteCulling::limits(limits);
+                    }
+
+                    limits
+                },
+                memory_hints: wgpu::MemoryHints::default(),
+                label: None,
+            },
+            None,
+        ).await.unwrap();
+
+        (device, queue, gpu_animation, gpu_culling, sprite_batching)
+    }
+
+    fn from_state(state: EngineState, scene: Node, spawner: Arc<dyn Spawner>, profile: bool, quality: QualitySettings) -> Self {
+        let scene = Scene::new(&state, scene, spawner);
+
+        if state.crash_report {
+            crash::set_adapter(&state.adapter);
+            crash::install_panic_hook();
+        }
 
+        let profiler = if profile {
+            Profiler::new(&state.device, &state.queue)
+        } else {
+            None
+        };
+
+        // The postprocess pass is only created when a ScreenEffect is enabled
         let postprocess = None;
-        //let postprocess = Some(Postprocess::new(&state));
 
         Self {
             state,
             postprocess,
+            graph: FrameGraph::new(),
+            profiler,
+            stats: EngineStats::default(),
             scene,
+            clock: FrameClock::new(),
+            quality,
         }
     }
 
+    /// Returns the stats for the most recently rendered frame.
+    ///
+    /// If profiling is enabled then the stats will be a few frames behind,
+    /// because it takes time for the GPU to return the timestamps.
+    #[inline]
+    pub fn stats(&self) -> &EngineStats {
+        &self.stats
+    }
+
+    /// Returns how much GPU memory is used by textures and instance buffers.
+    #[inline]
+    pub fn resources(&self) -> ResourceStats {
+        self.state.resources.stats()
+    }
+
+    /// Returns information about the GPU which is used for rendering.
+    pub fn info(&self) -> EngineInfo {
+        EngineInfo {
+            adapter: self.state.adapter.clone(),
+            gpu_animation: self.state.gpu_animation,
+            gpu_culling: self.state.gpu_culling,
+            sprite_batching: self.state.sprite_batching,
+        }
+    }
+
+    /// Returns the compiled shaders, which can be saved to disk and passed to [`EngineSettings::pipeline_cache`]
+    /// the next time the engine is created, so that startup is faster.
+    ///
+    /// This is only supported on native Vulkan, for other backends it returns `None`.
+    ///
+    /// It should be called after [`warmup`](Engine::warmup), so that every shader is included.
+    #[inline]
+    pub fn pipeline_cache_data(&self) -> Option<Vec<u8>> {
+        self.state.pipeline_cache.data()
+    }
+
+    /// Relayouts the scene and returns the computed location of every visible Node.
+    ///
+    /// This is intended for debugging layout bugs.
+    pub fn dump_tree(&mut self) -> SceneSnapshot {
+        self.scene.dump_tree(&self.state)
+    }
+
     pub fn resize(&mut self, window_size: WindowSize) {
+        tracing::debug!(width = window_size.width, height = window_size.height, "Engine::resize");
+
         self.state.resize(window_size);
 
         if let Some(postprocess) = &mut self.postprocess {
@@ -257,88 +790,344 @@ impl Engine {
         }
     }
 
+    /// Returns the current UI scale, see [`set_ui_scale`](Engine::set_ui_scale).
+    #[inline]
+    pub fn ui_scale(&self) -> f32 {
+        self.state.ui_scale
+    }
+
+    /// Multiplies every [`Length::Px`] by `ui_scale`, this scales the whole interface
+    /// without needing to change any Nodes.
+    ///
+    /// For example `2.0` makes everything twice as big, which is useful for accessibility or small screens.
+    ///
+    /// The other [`Length`] variants are relative to the screen or parent, so they are not affected.
+    pub fn set_ui_scale(&mut self, ui_scale: f32) {
+        assert!(ui_scale > 0.0, "ui_scale must be greater than 0");
+
+        if self.state.ui_scale != ui_scale {
+            tracing::debug!(ui_scale, "Engine::set_ui_scale");
+
+            self.state.ui_scale = ui_scale;
+            self.scene.changed.trigger_layout_change();
+        }
+    }
+
+    /// Sets the current time (in milliseconds), which is used for [`SpriteAnimation`].
+    ///
+    /// It is also available to every shader as `scene.time`, see [`PipelineHandle`].
+    /// `scene.time` wraps around to `0.0` every hour, because an `f32` can't precisely represent a large number of milliseconds.
+    ///
+    /// This should be called once per frame, before calling [`render`](Engine::render).
+    #[inline]
+    pub fn set_time(&mut self, time: f64) {
+        self.scene.set_time(time, self.quality.animations);
+
+        self.clock.set(time);
+
+        if let Some(postprocess) = &mut self.postprocess {
+            postprocess.set_time(self.scene.renderer.scene_uniform.time);
+
+            if postprocess.effect().is_animated() {
+                self.scene.changed.trigger_render_change();
+            }
+        }
+    }
+
+    /// Returns the clock which is used by the [`signal_util`] adapters.
+    ///
+    /// It is updated by [`set_time`](Engine::set_time).
+    #[inline]
+    pub fn clock(&self) -> FrameClock {
+        self.clock.clone()
+    }
+
+    #[inline]
+    pub fn quality(&self) -> &QualitySettings {
+        &self.quality
+    }
+
+    /// Changes the [`QualitySettings`], this can be called at any time.
+    ///
+    /// If post effects are disabled then the current [`ScreenEffect`] is removed,
+    /// after enabling them again [`set_screen_effect`](Engine::set_screen_effect) must be called.
+    pub fn set_quality(&mut self, quality: QualitySettings) {
+        if self.quality != quality {
+            tracing::debug!(?quality, "Engine::set_quality");
+
+            self.quality = quality;
+
+            self.scene.set_time(self.clock.time(), quality.animations);
+
+            if !quality.post_effects {
+                self.set_screen_effect(ScreenEffect::default());
+            }
+
+            self.scene.changed.trigger_render_change();
+        }
+    }
+
+    /// Returns the current [`ScreenEffect`].
+    #[inline]
+    pub fn screen_effect(&self) -> ScreenEffect {
+        self.postprocess.as_ref().map(|postprocess| *postprocess.effect()).unwrap_or_default()
+    }
+
+    /// Sets the full-screen [`ScreenEffect`], which is applied after the scene is rendered.
+    ///
+    /// The postprocessing pass is only used while an effect is enabled,
+    /// so there is no extra cost when every effect is disabled.
+    pub fn set_screen_effect(&mut self, effect: ScreenEffect) {
+        if effect.is_empty() || !self.quality.post_effects {
+            if self.postprocess.take().is_some() {
+                self.scene.changed.trigger_render_change();
+            }
+
+        } else {
+            let postprocess = self.postprocess.get_or_insert_with(|| Postprocess::new(&self.state));
+
+            if *postprocess.effect() != effect {
+                postprocess.set_effect(effect);
+                postprocess.set_time(self.scene.renderer.scene_uniform.time);
+                self.scene.changed.trigger_render_change();
+            }
+        }
+    }
+
+    /// Inserts a custom render pass into the frame graph, it is run every frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a pass with the same label already exists, or if the position conflicts
+    /// with the [`PassTarget`] or [`depth_stencil`](PassSettings::depth_stencil).
+    pub fn add_pass<P>(&mut self, settings: PassSettings, pass: P) where P: CustomPass + 'static {
+        self.graph.insert(settings, Box::new(pass));
+        self.scene.changed.trigger_render_change();
+    }
+
+    /// Removes a pass which was added with [`add_pass`](Engine::add_pass).
+    pub fn remove_pass(&mut self, label: &str) -> Option<Box<dyn CustomPass>> {
+        let pass = self.graph.remove(label);
+        self.scene.changed.trigger_render_change();
+        pass
+    }
+
+    /// Returns the passes which are run every frame, in order.
+    ///
+    /// The [`PassId::Postprocess`] pass is skipped while there isn't a [`ScreenEffect`].
+    pub fn passes(&self) -> &[PassId] {
+        self.graph.order()
+    }
+
+    /// Compiles every pipeline and draws the scene once without displaying it.
+    ///
+    /// Pipelines are normally compiled the first time that they're used, which
+    /// causes the first frame to take much longer than the other frames.
+    ///
+    /// This should be called after loading the spritesheets and fonts, and before the first
+    /// [`render`](Engine::render). The postprocessing pipeline is still compiled when
+    /// the first [`ScreenEffect`] is set.
+    pub fn warmup(&mut self) {
+        let _span = tracing::debug_span!("Engine::warmup").entered();
+
+        self.scene.renderer.warmup(&self.state);
+
+        // Some drivers don't finish preparing a pipeline until it is used for drawing,
+        // and this also uploads the instances and textures to the GPU.
+        let target = EngineState::make_headless_target(&self.state.device, &self.state.config);
+
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        {
+            let mut scene_prerender = self.scene.prerender(&self.state);
+
+            let mut encoder = self.state.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Warmup Encoder"),
+            });
+
+            scene_prerender.dispatch(&mut encoder);
+
+            {
+                let mut render_pass = self.state.begin_pass(&mut encoder, &view, "Warmup Pass", true, true);
+
+                scene_prerender.render(&mut render_pass, None);
+            }
+
+            self.state.queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        target.destroy();
+
+        // The frame wasn't displayed, so it needs to be rendered again.
+        self.scene.changed.trigger_render_change();
+    }
+
+    /// Logs a warning when the scene's orders become too large for the depth buffer, see [`depth`].
+    fn update_depth_stats(&mut self) {
+        let min_order_step = self.state.depth.min_order_step;
+
+        let depth = DepthStats::new(self.scene.renderer.scene_uniform.max_order);
+
+        // Only warn once, instead of every frame
+        if !depth.is_precise(min_order_step) && self.stats.depth.is_precise(min_order_step) {
+            tracing::warn!(
+                max_order = depth.max_order,
+                resolution = depth.resolution,
+                min_order_step,
+                "The scene's max order is too large, nodes whose orders are closer than the resolution may be drawn in the wrong order",
+            );
+        }
+
+        self.stats.depth = depth;
+    }
+
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        if let Some(profiler) = &mut self.profiler {
+            if let Some(stats) = profiler.poll(&self.state.device) {
+                self.stats = EngineStats { depth: self.stats.depth, cpu: self.stats.cpu, ..stats };
+            }
+        }
+
         if self.scene.should_render() {
+            let _span = tracing::trace_span!("Engine::render").entered();
+
+            let start = profiler::now();
+
             let mut scene_prerender = self.scene.prerender(&self.state);
 
-            let output = self.state.surface.get_current_texture()?;
+            let layout_end = profiler::now();
+
+            let output = match &self.state.surface {
+                Some(surface) => Some(surface.get_current_texture()?),
+                None => None,
+            };
+
+            let draws = scene_prerender.stats();
+
+            let profiler = match &mut self.profiler {
+                Some(profiler) => {
+                    if profiler.begin(&self.state.device, &draws) {
+                        Some(&*profiler)
 
-            let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                    } else {
+                        None
+                    }
+                },
+                None => {
+                    self.stats = EngineStats { draws, depth: self.stats.depth, cpu: self.stats.cpu };
+                    None
+                },
+            };
+
+            let view = match &output {
+                Some(output) => &output.texture,
+                None => self.state.headless_target.as_ref().expect("Engine is missing headless target"),
+            }.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(self.state.format()),
+                ..wgpu::TextureViewDescriptor::default()
+            });
+
+            for node in self.graph.custom_mut() {
+                node.pass.prepare(&PassContext {
+                    device: &self.state.device,
+                    queue: &self.state.queue,
+                    format: self.state.format(),
+                    depth_format: if node.settings.depth_stencil {
+                        Some(self.state.depth_buffer.format())
+                    } else {
+                        None
+                    },
+                    window_size: self.state.window_size,
+                });
+            }
 
             let mut encoder = self.state.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
 
-            {
-                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Render Pass"),
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: if let Some(postprocess) = &self.postprocess {
-                            postprocess.view()
-                        } else {
-                            &view
-                        },
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.0,
-                                g: 0.0,
-                                b: 0.0,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &self.state.depth_buffer.view,
-                        depth_ops: Some(wgpu::Operations {
-                            // TODO use reverse z-order
-                            load: wgpu::LoadOp::Clear(0.0),
-                            store: wgpu::StoreOp::Store,
-                        }),
-                        stencil_ops: if self.state.depth_buffer.has_stencil() {
-                            Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(0),
-                                store: wgpu::StoreOp::Store,
-                            })
+            // The animated and culled instances must be ready before any of the passes draw the scene
+            scene_prerender.dispatch(&mut encoder);
+
+            fn scene_view<'a>(postprocess: &'a Option<Postprocess>, view: &'a wgpu::TextureView) -> &'a wgpu::TextureView {
+                if let Some(postprocess) = postprocess {
+                    postprocess.view()
+                } else {
+                    view
+                }
+            }
+
+            // Whether the Clear pass is merged into the next pass which draws to the scene target
+            let mut clear = false;
+
+            for (index, id) in self.graph.order().iter().enumerate() {
+                match *id {
+                    PassId::Clear => {
+                        if self.graph.next_scene_pass_uses_depth_stencil(index) {
+                            clear = true;
 
                         } else {
-                            None
-                        },
-                    }),
-                    occlusion_query_set: None,
-                    timestamp_writes: None,
-                });
+                            self.state.begin_pass(&mut encoder, scene_view(&self.postprocess, &view), "Clear Pass", true, true);
+                        }
+                    },
 
-                scene_prerender.render(&mut render_pass);
-            }
+                    PassId::Scene => {
+                        let mut render_pass = self.state.begin_pass(&mut encoder, scene_view(&self.postprocess, &view), "Render Pass", clear, true);
+                        clear = false;
 
-            if let Some(postprocess) = &mut self.postprocess {
-                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Postprocessing Pass"),
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.0,
-                                g: 0.0,
-                                b: 0.0,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                    occlusion_query_set: None,
-                    timestamp_writes: None,
-                });
+                        scene_prerender.render(&mut render_pass, profiler);
+                    },
+
+                    PassId::Postprocess => {
+                        if let Some(postprocess) = &mut self.postprocess {
+                            let mut render_pass = self.state.begin_pass(&mut encoder, &view, "Postprocessing Pass", true, false);
+
+                            postprocess.render(&self.state, &mut render_pass);
+                        }
+                    },
 
-                postprocess.render(&mut render_pass);
+                    PassId::Custom(label) => {
+                        let node = self.graph.custom(label);
+
+                        let mut render_pass = match node.settings.target {
+                            PassTarget::Scene => {
+                                let render_pass = self.state.begin_pass(&mut encoder, scene_view(&self.postprocess, &view), label, clear, node.settings.depth_stencil);
+                                clear = false;
+                                render_pass
+                            },
+                            PassTarget::Output => {
+                                self.state.begin_pass(&mut encoder, &view, label, false, node.settings.depth_stencil)
+                            },
+                        };
+
+                        node.pass.render(&mut render_pass);
+                    },
+                }
+            }
+
+            if let Some(profiler) = profiler {
+                profiler.resolve(&mut encoder);
             }
 
             self.state.queue.submit(std::iter::once(encoder.finish()));
-            output.present();
+
+            if let Some(output) = output {
+                output.present();
+            }
+
+            if let Some(profiler) = profiler {
+                profiler.map();
+            }
+
+            self.stats.cpu = CpuStats {
+                layout: layout_end - start,
+                draw: profiler::now() - layout_end,
+            };
+
+            self.update_depth_stats();
+
+            if self.state.crash_report {
+                crash::set_stats(&self.stats);
+            }
 
             /*fn read_texture(encoder: , texture: &Texture, aspect: wgpu::TextureAspect) {
                 texture.as_image_copy(),
@@ -365,4 +1154,71 @@ impl Engine {
 
         Ok(())
     }
+
+    /// Renders the scene and then copies the frame into an image.
+    ///
+    /// This only works with an Engine which was created with [`new_headless`](Engine::new_headless).
+    pub fn render_to_image(&mut self) -> image::RgbaImage {
+        // Forces it to render, even if nothing changed since the last frame
+        self.scene.changed.trigger_render_change();
+
+        self.render().unwrap();
+
+        let target = self.state.headless_target.as_ref().expect("render_to_image can only be used with Engine::new_headless");
+
+        let width = self.state.config.width;
+        let height = self.state.config.height;
+
+        let unpadded_bytes_per_row = width * 4;
+
+        let padded_bytes_per_row = unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = self.state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Read Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.state.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Read Encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            target.size(),
+        );
+
+        self.state.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+
+        self.state.device.poll(wgpu::Maintain::Wait);
+
+        let image = {
+            let data = slice.get_mapped_range();
+
+            let bytes = data.chunks(padded_bytes_per_row as usize)
+                .flat_map(|row| &row[..(unpadded_bytes_per_row as usize)])
+                .copied()
+                .collect::<Vec<u8>>();
+
+            image::RgbaImage::from_raw(width, height, bytes).unwrap()
+        };
+
+        buffer.unmap();
+        buffer.destroy();
+
+        image
+    }
 }
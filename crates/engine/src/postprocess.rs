@@ -1,5 +1,69 @@
+use bytemuck::{Pod, Zeroable};
 use crate::util::builders;
+use crate::util::buffer::Uniform;
 use crate::util::macros::wgsl;
+use crate::scene::{ColorRgb, Percentage};
+
+
+/// Full-screen effect which is applied after the scene is rendered.
+///
+/// Each effect has an intensity from 0.0 to 1.0, where 0.0 disables the effect.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScreenEffect {
+    /// Color of the vignette.
+    pub tint: ColorRgb,
+
+    /// Darkens the edges of the screen with the [`tint`](ScreenEffect::tint) color.
+    pub vignette: Percentage,
+
+    /// Inverts the colors of the screen.
+    pub invert: Percentage,
+
+    /// Horizontal lines which move across the screen, this uses the [`Engine::set_time`](crate::Engine::set_time).
+    pub speed_lines: Percentage,
+
+    /// Fades the screen to black.
+    pub fade: Percentage,
+
+    /// Covers the screen with black, starting from the left edge.
+    pub wipe: Percentage,
+
+    /// Pixelates the screen into large squares.
+    pub mosaic: Percentage,
+}
+
+impl ScreenEffect {
+    /// Returns `true` if every effect is disabled.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.vignette == 0.0 &&
+        self.invert == 0.0 &&
+        self.speed_lines == 0.0 &&
+        self.fade == 0.0 &&
+        self.wipe == 0.0 &&
+        self.mosaic == 0.0
+    }
+
+    #[inline]
+    pub(crate) fn is_animated(&self) -> bool {
+        self.speed_lines != 0.0
+    }
+}
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, Default)]
+struct EffectUniform {
+    tint: [f32; 3],
+    vignette: f32,
+    invert: f32,
+    speed_lines: f32,
+    time: f32,
+    fade: f32,
+    wipe: f32,
+    mosaic: f32,
+    _padding: [f32; 2],
+}
 
 
 struct Texture {
@@ -22,7 +86,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: engine.config.format,
+            format: engine.format(),
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
@@ -80,11 +144,14 @@ pub struct Postprocess {
     texture: Texture,
     bind_group_layout: wgpu::BindGroupLayout,
     render_pipeline: wgpu::RenderPipeline,
+    effect: ScreenEffect,
+    uniform: Uniform<EffectUniform>,
 }
 
-#[allow(unused)]
 impl Postprocess {
     pub(crate) fn new(engine: &crate::EngineState) -> Self {
+        let mut uniform = Uniform::new(wgpu::ShaderStages::FRAGMENT, EffectUniform::default());
+
         let bind_group_layout = builders::BindGroupLayout::builder()
             .label("Postprocess")
             .sampler(wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::NonFiltering)
@@ -94,24 +161,50 @@ impl Postprocess {
             //.texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Uint)
             .build(engine);
 
-        let shader = engine.device.create_shader_module(wgsl![
-            "postprocess.wgsl",
-            include_str!("postprocess.wgsl"),
-        ]);
+        let shader = engine.device.create_shader_module(wgsl!("postprocess.wgsl"));
 
         let render_pipeline = builders::Pipeline::builder()
             .label("Postprocess")
             // TODO lazy load this ?
             .shader(&shader)
-            .bind_groups(&[&bind_group_layout])
+            .bind_groups(&[&bind_group_layout, Uniform::bind_group_layout(&mut uniform, engine)])
             .topology(wgpu::PrimitiveTopology::TriangleStrip)
             .strip_index_format(wgpu::IndexFormat::Uint32)
+            .depth_stencil(false)
             .build(engine);
 
         Self {
             texture: Texture::new(&bind_group_layout, engine),
             bind_group_layout,
             render_pipeline,
+            effect: ScreenEffect::default(),
+            uniform,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn effect(&self) -> &ScreenEffect {
+        &self.effect
+    }
+
+    pub(crate) fn set_effect(&mut self, effect: ScreenEffect) {
+        if self.effect != effect {
+            self.effect = effect;
+
+            let uniform = &mut *self.uniform;
+            uniform.tint = effect.tint.to_gpu();
+            uniform.vignette = effect.vignette;
+            uniform.invert = effect.invert;
+            uniform.speed_lines = effect.speed_lines;
+            uniform.fade = effect.fade;
+            uniform.wipe = effect.wipe;
+            uniform.mosaic = effect.mosaic;
+        }
+    }
+
+    pub(crate) fn set_time(&mut self, time: f32) {
+        if self.uniform.time != time {
+            self.uniform.time = time;
         }
     }
 
@@ -123,9 +216,10 @@ impl Postprocess {
         self.texture = Texture::new(&self.bind_group_layout, engine);
     }
 
-    pub(crate) fn render<'a, 'b>(&'a mut self, render_pass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
+    pub(crate) fn render<'a, 'b>(&'a mut self, engine: &crate::EngineState, render_pass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, &self.texture.bind_group, &[]);
+        render_pass.set_bind_group(1, Uniform::write(&mut self.uniform, engine), &[]);
         render_pass.draw(0..4, 0..1);
     }
 }
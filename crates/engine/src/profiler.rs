@@ -0,0 +1,290 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+
+/// Statistics for a single draw call.
+#[derive(Debug, Clone)]
+pub struct DrawStats {
+    pub label: &'static str,
+
+    /// The number of instances before [`EngineSettings::gpu_culling`](crate::EngineSettings::gpu_culling) removes the offscreen sprites.
+    pub instances: u32,
+
+    /// Whether this draw is for transparent sprites.
+    pub alpha: bool,
+
+    /// How long the draw took on the GPU (in milliseconds).
+    ///
+    /// This is only available if [`EngineSettings::profile`](crate::EngineSettings::profile)
+    /// is enabled and the GPU supports timestamp queries.
+    pub gpu_time: Option<f64>,
+}
+
+/// How long each part of [`Engine::render`](crate::Engine::render) took on the CPU (in milliseconds).
+///
+/// This is always measured, even if profiling is disabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuStats {
+    /// Updating the layout of the Nodes and uploading the instance buffers.
+    pub layout: f64,
+
+    /// Encoding the render passes and submitting them to the GPU.
+    pub draw: f64,
+}
+
+impl CpuStats {
+    /// The total CPU time (in milliseconds).
+    #[inline]
+    pub fn total(&self) -> f64 {
+        self.layout + self.draw
+    }
+}
+
+
+/// Returns the current time in milliseconds, this is only used for measuring durations.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now() -> f64 {
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = performance, js_name = now)]
+        fn performance_now() -> f64;
+    }
+
+    performance_now()
+}
+
+/// Returns the current time in milliseconds, this is only used for measuring durations.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+
+/// Statistics for the most recent frame.
+#[derive(Debug, Clone, Default)]
+pub struct EngineStats {
+    pub draws: Vec<DrawStats>,
+
+    /// The depth precision, this is updated every frame even if profiling is disabled.
+    pub depth: crate::DepthStats,
+
+    /// See [`CpuStats`].
+    pub cpu: CpuStats,
+}
+
+impl EngineStats {
+    /// The total GPU time for all draws (in milliseconds).
+    pub fn gpu_time(&self) -> Option<f64> {
+        self.draws.iter().map(|draw| draw.gpu_time).sum()
+    }
+}
+
+
+const MAPPING_PENDING: u8 = 0;
+const MAPPING_DONE: u8 = 1;
+const MAPPING_ERROR: u8 = 2;
+
+// This is the maximum allowed by wgpu
+const MAX_QUERIES: u32 = wgpu::QUERY_SET_MAX_QUERIES;
+
+
+/// Uses timestamp queries to measure how long each draw takes on the GPU.
+///
+/// The results are read asynchronously, so they are a few frames behind.
+///
+/// When the results are read, the total GPU time is logged as a `debug` event,
+/// and the time for each draw is logged as a `trace` event.
+pub(crate) struct Profiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    read_buffer: wgpu::Buffer,
+    capacity: u32,
+
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+
+    /// The draws which are waiting for their timestamps.
+    pending: Option<Vec<DrawStats>>,
+    mapping: Arc<AtomicU8>,
+}
+
+impl Profiler {
+    pub(crate) const FEATURES: wgpu::Features = wgpu::Features::TIMESTAMP_QUERY
+        .union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES);
+
+    fn make_buffers(device: &wgpu::Device, capacity: u32) -> (wgpu::QuerySet, wgpu::Buffer, wgpu::Buffer) {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Profiler Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: capacity,
+        });
+
+        let size = (capacity as u64) * wgpu::QUERY_SIZE as u64;
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Profiler Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Profiler Read Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        (query_set, resolve_buffer, read_buffer)
+    }
+
+    /// Returns `None` if the device doesn't support timestamp queries.
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if device.features().contains(Self::FEATURES) {
+            let capacity = 64;
+
+            let (query_set, resolve_buffer, read_buffer) = Self::make_buffers(device, capacity);
+
+            Some(Self {
+                query_set,
+                resolve_buffer,
+                read_buffer,
+                capacity,
+                period: queue.get_timestamp_period(),
+                pending: None,
+                mapping: Arc::new(AtomicU8::new(MAPPING_PENDING)),
+            })
+
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether the previous timestamps have finished reading.
+    pub(crate) fn poll(&mut self, device: &wgpu::Device) -> Option<EngineStats> {
+        if self.pending.is_some() {
+            device.poll(wgpu::Maintain::Poll);
+
+            match self.mapping.load(Ordering::Acquire) {
+                MAPPING_DONE => {
+                    let mut draws = self.pending.take().unwrap();
+
+                    {
+                        let range = self.read_buffer.slice(..).get_mapped_range();
+                        let timestamps: &[u64] = bytemuck::cast_slice(&range);
+
+                        for (index, draw) in draws.iter_mut().enumerate() {
+                            let start = timestamps[index * 2];
+                            let end = timestamps[index * 2 + 1];
+
+                            let nanoseconds = (end.saturating_sub(start) as f64) * (self.period as f64);
+
+                            draw.gpu_time = Some(nanoseconds / 1_000_000.0);
+                        }
+                    }
+
+                    self.read_buffer.unmap();
+
+                    let stats = EngineStats { draws, ..EngineStats::default() };
+
+                    for draw in stats.draws.iter() {
+                        tracing::trace!(
+                            label = draw.label,
+                            instances = draw.instances,
+                            alpha = draw.alpha,
+                            gpu_time = draw.gpu_time,
+                            "GPU draw",
+                        );
+                    }
+
+                    tracing::debug!(
+                        draws = stats.draws.len(),
+                        gpu_time = stats.gpu_time(),
+                        "GPU frame",
+                    );
+
+                    return Some(stats);
+                },
+                MAPPING_ERROR => {
+                    tracing::warn!("Failed to read the GPU timestamps");
+                    self.pending = None;
+                },
+                _ => {},
+            }
+        }
+
+        None
+    }
+
+    /// Returns `false` if the previous timestamps haven't finished reading yet.
+    pub(crate) fn begin(&mut self, device: &wgpu::Device, draws: &[DrawStats]) -> bool {
+        if self.pending.is_some() {
+            return false;
+        }
+
+        let needed = (draws.len() as u32) * 2;
+
+        if needed == 0 || needed > MAX_QUERIES {
+            return false;
+        }
+
+        if needed > self.capacity {
+            let capacity = needed.next_power_of_two().min(MAX_QUERIES);
+
+            let (query_set, resolve_buffer, read_buffer) = Self::make_buffers(device, capacity);
+
+            self.query_set = query_set;
+            self.resolve_buffer = resolve_buffer;
+            self.read_buffer = read_buffer;
+            self.capacity = capacity;
+        }
+
+        self.pending = Some(draws.to_vec());
+        true
+    }
+
+    #[inline]
+    pub(crate) fn write_start(&self, render_pass: &mut wgpu::RenderPass, index: u32) {
+        render_pass.write_timestamp(&self.query_set, index * 2);
+    }
+
+    #[inline]
+    pub(crate) fn write_end(&self, render_pass: &mut wgpu::RenderPass, index: u32) {
+        render_pass.write_timestamp(&self.query_set, index * 2 + 1);
+    }
+
+    /// Copies the timestamps into the read buffer, this must be called after the render pass.
+    pub(crate) fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(draws) = &self.pending {
+            let queries = (draws.len() as u32) * 2;
+            let size = (queries as u64) * wgpu::QUERY_SIZE as u64;
+
+            encoder.resolve_query_set(&self.query_set, 0..queries, &self.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.read_buffer, 0, size);
+        }
+    }
+
+    /// Starts reading the timestamps, this must be called after the commands are submitted.
+    pub(crate) fn map(&self) {
+        if self.pending.is_some() {
+            self.mapping.store(MAPPING_PENDING, Ordering::Release);
+
+            let mapping = self.mapping.clone();
+
+            self.read_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let state = if result.is_ok() {
+                    MAPPING_DONE
+                } else {
+                    MAPPING_ERROR
+                };
+
+                mapping.store(state, Ordering::Release);
+            });
+        }
+    }
+}
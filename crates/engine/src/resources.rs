@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+
+/// How much GPU memory is used by the engine's textures and instance buffers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceStats {
+    pub textures: usize,
+    pub texture_bytes: u64,
+
+    pub buffers: usize,
+    pub buffer_bytes: u64,
+}
+
+impl ResourceStats {
+    #[inline]
+    pub fn total_bytes(&self) -> u64 {
+        self.texture_bytes + self.buffer_bytes
+    }
+}
+
+
+#[derive(Default)]
+struct Counts {
+    textures: AtomicUsize,
+    texture_bytes: AtomicU64,
+
+    buffers: AtomicUsize,
+    buffer_bytes: AtomicU64,
+}
+
+
+/// Records the size of every GPU resource when it is created and destroyed.
+///
+/// It is cloned into each resource, so that the resource can remove itself when it is dropped.
+#[derive(Clone, Default)]
+pub(crate) struct ResourceTracker {
+    counts: Arc<Counts>,
+}
+
+impl ResourceTracker {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn stats(&self) -> ResourceStats {
+        ResourceStats {
+            textures: self.counts.textures.load(Ordering::Relaxed),
+            texture_bytes: self.counts.texture_bytes.load(Ordering::Relaxed),
+            buffers: self.counts.buffers.load(Ordering::Relaxed),
+            buffer_bytes: self.counts.buffer_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn add_texture(&self, bytes: u64) {
+        self.counts.textures.fetch_add(1, Ordering::Relaxed);
+        self.counts.texture_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn remove_texture(&self, bytes: u64) {
+        self.counts.textures.fetch_sub(1, Ordering::Relaxed);
+        self.counts.texture_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_buffer(&self, bytes: u64) {
+        self.counts.buffers.fetch_add(1, Ordering::Relaxed);
+        self.counts.buffer_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn remove_buffer(&self, bytes: u64) {
+        self.counts.buffers.fetch_sub(1, Ordering::Relaxed);
+        self.counts.buffer_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
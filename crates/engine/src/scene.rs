@@ -2,11 +2,19 @@ use bytemuck::{Zeroable, Pod};
 use std::future::Future;
 use std::pin::Pin;
 
-use crate::{DEBUG, Spawner};
+use crate::Spawner;
+use crate::profiler::{Profiler, DrawStats};
 use crate::util::{Arc, Atomic, Lock};
-use crate::util::buffer::{Uniform, TextureBuffer, IntoTexture};
-use sprite::{SpriteRenderer};
+use crate::util::buffer::{Uniform, TextureBuffer, RetainedImage, IntoTexture};
+pub(crate) use sprite::{SpriteRenderer};
+pub(crate) use pipeline_cache::{PipelineCache};
+pub(crate) use culling::{SpriteCulling};
+pub(crate) use animation::{SpriteAnimator};
 use bitmap_text::{BitmapTextRenderer};
+use shape::{ShapeRenderer};
+use gradient::{GradientRenderer};
+use snapshot::{SnapshotRecorder};
+use mask::{Stencil, StencilRanges};
 
 mod builder;
 mod sprite;
@@ -16,20 +24,43 @@ mod stack;
 mod wrap;
 mod grid;
 mod border_grid;
+mod tilemap;
 mod bitmap_text;
+mod shape;
+mod gradient;
+mod mask;
+mod animation;
+mod culling;
+mod pipeline_cache;
+mod node_ref;
+mod snapshot;
+
+#[cfg(feature = "ttf")]
+mod ttf;
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests;
 
 pub use builder::{Node};
-pub use sprite::{Sprite, SpriteBuilder, Spritesheet, SpritesheetSettings, Tile, RepeatTile, Repeat};
+pub use node_ref::{NodeRef};
+pub use snapshot::{SceneSnapshot, NodeSnapshot};
+pub use sprite::{Sprite, SpriteBuilder, Spritesheet, SpritesheetSettings, PaletteError, PipelineHandle, CustomPipelineSettings, Tile, RepeatTile, Repeat, RepeatOffset, SpriteAnimation, AnimationMode};
 pub use row::{Row, RowBuilder};
 pub use column::{Column, ColumnBuilder};
 pub use stack::{Stack, StackBuilder};
 pub use wrap::{Wrap, WrapBuilder};
 pub use grid::{Grid, GridBuilder, GridSize};
 pub use border_grid::{BorderGrid, BorderGridBuilder, BorderSize, Quadrants};
+pub use tilemap::{Tilemap, TilemapBuilder, TilemapSize, Tileset};
+pub use shape::{Rect, RectBuilder, LineStrip, LineStripBuilder, LinePoint};
+pub use gradient::{Gradient, GradientBuilder, GradientColors};
+pub use mask::{Mask, MaskBuilder};
 pub use bitmap_text::{
     BitmapText, BitmapTextBuilder, BitmapFont, BitmapFontSettings,
     BitmapFontSupported, ColorRgb, CharSize,
 };
+#[cfg(feature = "ttf")]
+pub use ttf::{TtfFontAtlas, TtfFontSettings, TtfError};
 
 
 static INTERNAL_BUG_MESSAGE: &'static str = "UNEXPECTED INTERNAL BUG, PLEASE REPORT THIS";
@@ -46,9 +77,9 @@ pub type Percentage = f32;
 
 /// x / y in screen space, percentage of the screen size
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct RealPosition {
-    pub(crate) x: Percentage,
-    pub(crate) y: Percentage,
+pub struct RealPosition {
+    pub x: Percentage,
+    pub y: Percentage,
 }
 
 impl RealPosition {
@@ -74,9 +105,9 @@ impl std::ops::Add for RealPosition {
 
 /// width / height in screen space, percentage of the screen size
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct RealSize {
-    pub(crate) width: Percentage,
-    pub(crate) height: Percentage,
+pub struct RealSize {
+    pub width: Percentage,
+    pub height: Percentage,
 }
 
 impl RealSize {
@@ -130,10 +161,10 @@ pub(crate) struct RealPadding {
 
 /// The x / y / width / height / z-index in screen space.
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct RealLocation {
-    pub(crate) position: RealPosition,
-    pub(crate) size: RealSize,
-    pub(crate) order: f32,
+pub struct RealLocation {
+    pub position: RealPosition,
+    pub size: RealSize,
+    pub order: f32,
 }
 
 impl RealLocation {
@@ -485,6 +516,9 @@ impl std::ops::Sub<RealSize> for SmallestSize {
 ///
 /// The default order is `Order::Above(1.0)` which means the node will
 /// display on top of all previous nodes.
+///
+/// See [`depth`](crate::depth) for how the orders are stored in the depth buffer,
+/// and the precision limits for scenes with very large orders.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Order {
     /// Ordering which is global to the entire scene.
@@ -515,7 +549,7 @@ pub use Length::{
 };
 
 /// Used for [`Offset`] / [`Size`] / [`Padding`].
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[non_exhaustive]
 pub enum Length {
     /// Zero length. Useful for [`Offset`] and [`Padding`].
@@ -551,7 +585,7 @@ impl Length {
     fn smallest_length(&self, screen: &ScreenLength) -> SmallestLength {
         match self {
             Self::Zero => SmallestLength::Screen(0.0),
-            Self::Px(x) => SmallestLength::Screen(*x as Percentage / screen.pixels),
+            Self::Px(x) => SmallestLength::Screen((*x as Percentage * screen.ui_scale) / screen.pixels),
 
             Self::ScreenWidth(x) => SmallestLength::Screen(x * screen.ratio.width),
             Self::ScreenHeight(x) => SmallestLength::Screen(x * screen.ratio.height),
@@ -568,7 +602,7 @@ impl Length {
     fn real_length(&self, parent: &RealSize, smallest: &RealSize, screen: &ScreenLength) -> Percentage {
         match self {
             Self::Zero => 0.0,
-            Self::Px(x) => *x as Percentage / screen.pixels,
+            Self::Px(x) => (*x as Percentage * screen.ui_scale) / screen.pixels,
 
             Self::ScreenWidth(x) => x * screen.ratio.width,
             Self::ScreenHeight(x) => x * screen.ratio.height,
@@ -590,11 +624,28 @@ impl Default for Length {
     }
 }
 
+impl std::ops::Neg for Length {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self {
+            Self::Zero => Self::Zero,
+            Self::Px(x) => Self::Px(-x),
+            Self::ScreenWidth(x) => Self::ScreenWidth(-x),
+            Self::ScreenHeight(x) => Self::ScreenHeight(-x),
+            Self::ParentWidth(x) => Self::ParentWidth(-x),
+            Self::ParentHeight(x) => Self::ParentHeight(-x),
+            Self::SmallestWidth(x) => Self::SmallestWidth(-x),
+            Self::SmallestHeight(x) => Self::SmallestHeight(-x),
+        }
+    }
+}
+
 
 /// Offset x / y (relative to the parent) which is added to the parent's x / y.
 ///
 /// The default is `{ x: Zero, y: Zero }` which means no offset.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Offset {
     pub x: Length,
     pub y: Length,
@@ -677,6 +728,62 @@ impl Default for Origin {
 }
 
 
+/// Presets for the [`Origin`], which place the node on an edge, corner, or center of the parent.
+///
+/// See [`anchor`](crate::StackBuilder::anchor) and [`anchor_margin`](crate::StackBuilder::anchor_margin).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Returns `0.0`, `0.5`, or `1.0` for the x and y.
+    fn percentages(&self) -> (Percentage, Percentage) {
+        match self {
+            Self::TopLeft => (0.0, 0.0),
+            Self::Top => (0.5, 0.0),
+            Self::TopRight => (1.0, 0.0),
+            Self::Left => (0.0, 0.5),
+            Self::Center => (0.5, 0.5),
+            Self::Right => (1.0, 0.5),
+            Self::BottomLeft => (0.0, 1.0),
+            Self::Bottom => (0.5, 1.0),
+            Self::BottomRight => (1.0, 1.0),
+        }
+    }
+
+    #[inline]
+    pub fn origin(&self) -> Origin {
+        let (x, y) = self.percentages();
+        Origin { x, y }
+    }
+
+    /// Converts a margin into an [`Offset`].
+    ///
+    /// The margin is the distance from the anchored edges towards the center of the parent,
+    /// so `Anchor::BottomRight` with a margin of `{ x: Px(8), y: Px(4) }` is 8 pixels
+    /// to the left of the right edge, and 4 pixels above the bottom edge.
+    ///
+    /// If the node is centered then the margin is added to the position, the same as [`Offset`].
+    pub fn margin(&self, margin: Offset) -> Offset {
+        let (x, y) = self.percentages();
+
+        Offset {
+            x: if x == 1.0 { -margin.x } else { margin.x },
+            y: if y == 1.0 { -margin.y } else { margin.y },
+        }
+    }
+}
+
+
 /// Describes the position of the Node relative to its parent.
 #[derive(Debug, Clone, Copy, Default)]
 pub(crate) struct Location {
@@ -736,6 +843,9 @@ pub(crate) struct ScreenLength {
     /// The width / height of the screen in pixels.
     pub(crate) pixels: f32,
 
+    /// Multiplier for [`Length::Px`], see [`Engine::set_ui_scale`](crate::Engine::set_ui_scale).
+    pub(crate) ui_scale: f32,
+
     /// Used for scaling the ratio when using ScreenWidth / ScreenHeight
     pub(crate) ratio: RealSize,
 }
@@ -747,9 +857,10 @@ pub(crate) struct ScreenSize {
 }
 
 impl ScreenSize {
-    pub(crate) fn new(pixel_width: f32, pixel_height: f32) -> Self {
+    pub(crate) fn new(pixel_width: f32, pixel_height: f32, ui_scale: f32) -> Self {
         let width = ScreenLength {
             pixels: pixel_width,
+            ui_scale,
             ratio: RealSize {
                 width: 1.0,
                 height: pixel_height / pixel_width,
@@ -758,6 +869,7 @@ impl ScreenSize {
 
         let height = ScreenLength {
             pixels: pixel_height,
+            ui_scale,
             ratio: RealSize {
                 width: pixel_width / pixel_height,
                 height: 1.0,
@@ -788,6 +900,27 @@ pub(crate) struct SceneLayoutInfo<'a> {
 
     /// Nodes which can be rendered without relayout.
     pub(crate) rendered_nodes: &'a mut Vec<NodeHandle>,
+
+    /// Records the layout of every Node, used for [`Scene::dump_tree`].
+    pub(crate) snapshot: Option<&'a mut SnapshotRecorder>,
+}
+
+impl<'a> SceneLayoutInfo<'a> {
+    /// Records the Node in the snapshot, this must be followed by [`exit_node`](SceneLayoutInfo::exit_node)
+    /// after the Node's children have been laid out.
+    #[inline]
+    pub(crate) fn enter_node(&mut self, kind: &'static str, location: &RealLocation) {
+        if let Some(snapshot) = &mut self.snapshot {
+            snapshot.enter(kind, location, self.screen_size);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn exit_node(&mut self) {
+        if let Some(snapshot) = &mut self.snapshot {
+            snapshot.exit();
+        }
+    }
 }
 
 
@@ -912,11 +1045,22 @@ impl<T> Handles<T> {
         })
     }
 
+    #[inline]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &(Handle, T)> {
+        self.values.iter()
+    }
+
     #[inline]
     pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut (Handle, T)> {
         self.values.iter_mut()
     }
 
+    /// Stable sorts the values, so values with the same key stay in insertion order.
+    #[inline]
+    pub(crate) fn sort_by_key<K, F>(&mut self, mut f: F) where K: Ord, F: FnMut(&T) -> K {
+        self.values.sort_by_key(|(_, value)| f(value));
+    }
+
     pub(crate) fn insert(&mut self, handle: &Handle, value: T) -> Option<T> {
         let index = self.index(&handle);
 
@@ -934,7 +1078,8 @@ impl<T> Handles<T> {
         let index = self.index(&handle);
 
         if let Some(index) = index {
-            Some(self.values.swap_remove(index).1)
+            // This preserves the insertion order, which is needed for `sort_by_key`
+            Some(self.values.remove(index).1)
 
         } else {
             None
@@ -944,6 +1089,22 @@ impl<T> Handles<T> {
 
 
 
+/// Size of a [`Texture`] in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextureSize {
+    /// Returns the number of columns and rows of tiles, partial tiles are not counted.
+    #[inline]
+    pub fn tiles(&self, tile_width: u32, tile_height: u32) -> (u32, u32) {
+        (self.width / tile_width, self.height / tile_height)
+    }
+}
+
+
 #[derive(Clone)]
 pub struct Texture {
     pub(crate) handle: Handle,
@@ -958,13 +1119,55 @@ impl Texture {
     pub fn load<T>(&self, engine: &mut crate::Engine, image: &T) where T: IntoTexture {
         let buffer = TextureBuffer::new(&engine.state, image);
 
-        engine.scene.textures.insert(&self.handle, buffer);
+        engine.scene.textures.insert(&self.handle, TextureState {
+            size: TextureState::image_size(image),
+            buffer: Some(buffer),
+            retained: None,
+        });
+
+        // TODO maybe this should trigger a relayout ?
+        // TODO somehow update the existing Spritesheets which refer to this texture
+        engine.scene.changed.trigger_render_change();
+    }
+
+    /// Same as [`load`](Texture::load) except it keeps a copy of the image on the CPU.
+    ///
+    /// This allows the texture to be evicted with [`Spritesheet::evict`], it will
+    /// then be automatically uploaded again when it is needed.
+    pub fn load_retained<T>(&self, engine: &mut crate::Engine, image: &T) where T: IntoTexture {
+        let buffer = TextureBuffer::new(&engine.state, image);
+
+        engine.scene.textures.insert(&self.handle, TextureState {
+            size: TextureState::image_size(image),
+            buffer: Some(buffer),
+            retained: Some(RetainedImage::new(image)),
+        });
 
         // TODO maybe this should trigger a relayout ?
         // TODO somehow update the existing Spritesheets which refer to this texture
         engine.scene.changed.trigger_render_change();
     }
 
+    /// Overwrites a rectangle of the texture with `image`, starting at `x` and `y`.
+    ///
+    /// This is much faster than [`load`](Texture::load) when only a small part of the
+    /// texture has changed, such as a minimap tile or a palette color.
+    ///
+    /// The image must have the same format as the texture, and it must fit inside of the texture.
+    /// If the texture was loaded with [`load_retained`](Texture::load_retained) then the CPU copy is also updated.
+    pub fn write_region<T>(&self, engine: &mut crate::Engine, x: u32, y: u32, image: &T) where T: IntoTexture {
+        let texture = engine.scene.textures.get_mut(&self.handle).expect("Texture is not loaded");
+
+        texture.write_region(&engine.state, x, y, image);
+
+        engine.scene.changed.trigger_render_change();
+    }
+
+    /// Returns the size of the texture, or `None` if it isn't loaded.
+    pub fn size(&self, engine: &crate::Engine) -> Option<TextureSize> {
+        engine.scene.textures.get(&self.handle).map(TextureState::size)
+    }
+
     pub fn unload(&self, engine: &mut crate::Engine) {
         engine.scene.textures.remove(&self.handle);
 
@@ -975,6 +1178,91 @@ impl Texture {
 }
 
 
+pub(crate) struct TextureState {
+    size: TextureSize,
+    buffer: Option<TextureBuffer>,
+    retained: Option<RetainedImage>,
+}
+
+impl TextureState {
+    fn image_size<T>(image: &T) -> TextureSize where T: IntoTexture {
+        let (width, height) = image.dimensions();
+        TextureSize { width, height }
+    }
+
+    #[inline]
+    pub(crate) fn size(&self) -> TextureSize {
+        self.size
+    }
+
+    /// Returns the GPU texture, this panics if the texture is evicted.
+    #[inline]
+    pub(crate) fn buffer(&self) -> &TextureBuffer {
+        self.buffer.as_ref().expect("Texture is evicted")
+    }
+
+    fn write_region<T>(&mut self, engine: &crate::EngineState, x: u32, y: u32, image: &T) where T: IntoTexture {
+        let (width, height) = image.dimensions();
+
+        assert!(
+            x + width <= self.size.width && y + height <= self.size.height,
+            "Region {}x{} at {},{} is outside of the texture {}x{}",
+            width, height, x, y, self.size.width, self.size.height,
+        );
+
+        if let Some(retained) = &mut self.retained {
+            retained.write_region(x, y, image);
+        }
+
+        // Evicted textures are updated when they are uploaded again
+        if let Some(buffer) = &self.buffer {
+            buffer.write_region(engine, x, y, image);
+        }
+    }
+
+    /// Adds the rows of `image` to the bottom of the texture, and returns the `y` of the first new row.
+    ///
+    /// The GPU texture is replaced, so every bind group which uses it must be recreated.
+    pub(crate) fn append_rows<T>(&mut self, engine: &crate::EngineState, image: &T) -> u32 where T: IntoTexture {
+        let (width, height) = image.dimensions();
+
+        assert_eq!(width, self.size.width, "Image width {} does not match the texture width {}", width, self.size.width);
+
+        let y = self.size.height;
+
+        if let Some(retained) = &mut self.retained {
+            retained.append_rows(image);
+        }
+
+        if let Some(buffer) = &self.buffer {
+            let buffer = buffer.grow(engine, image.label(), height);
+            buffer.write_region(engine, 0, y, image);
+            self.buffer = Some(buffer);
+        }
+
+        self.size.height += height;
+
+        y
+    }
+
+    /// Frees the GPU texture, the image is kept on the CPU so it can be uploaded again.
+    pub(crate) fn evict(&mut self) {
+        assert!(self.retained.is_some(), "Texture must be loaded with Texture::load_retained in order to be evicted");
+
+        self.buffer = None;
+    }
+
+    /// Uploads the texture again if it was evicted.
+    pub(crate) fn upload(&mut self, engine: &crate::EngineState) {
+        if self.buffer.is_none() {
+            let retained = self.retained.as_ref().expect("Texture is missing retained image");
+
+            self.buffer = Some(TextureBuffer::new(engine, retained));
+        }
+    }
+}
+
+
 /// Keeps track of whether the layout / render needs updating.
 pub(crate) struct SceneChanged {
     layout: Atomic<bool>,
@@ -1027,19 +1315,29 @@ impl SceneChanged {
 }
 
 
+#[derive(Clone)]
 pub(crate) struct Prerender<'a> {
+    pub(crate) label: &'static str,
+    pub(crate) alpha: bool,
     pub(crate) vertices: u32,
+    pub(crate) first_instance: u32,
     pub(crate) instances: u32,
+    pub(crate) stencil: Stencil,
     pub(crate) pipeline: &'a wgpu::RenderPipeline,
     // TODO figure out a way to avoid the Vec
     pub(crate) bind_groups: Vec<&'a wgpu::BindGroup>,
     pub(crate) slices: Vec<Option<wgpu::BufferSlice<'a>>>,
+
+    /// The buffer and offset of the arguments for `draw_indirect`, the `instances` is the maximum
+    /// number of instances, see [`SpriteCulling`](culling::SpriteCulling).
+    pub(crate) indirect: Option<(&'a wgpu::Buffer, u64)>,
 }
 
 impl<'a> Prerender<'a> {
-    fn render<'b>(&'a mut self, render_pass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
+    fn render(&mut self, render_pass: &mut wgpu::RenderPass<'_>) {
         if self.instances > 0 {
             render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_stencil_reference(self.stencil.reference());
 
             for (index, bind_group) in self.bind_groups.iter().enumerate() {
                 render_pass.set_bind_group(index as u32, Some(*bind_group), &[]);
@@ -1056,35 +1354,119 @@ impl<'a> Prerender<'a> {
                 }
             }
 
-            render_pass.draw(0..self.vertices, 0..self.instances);
+            match self.indirect {
+                Some((buffer, offset)) => render_pass.draw_indirect(buffer, offset),
+                None => render_pass.draw(0..self.vertices, self.first_instance..(self.first_instance + self.instances)),
+            }
         }
     }
 }
 
+/// A compute dispatch which must run before the draws which use its output,
+/// see [`SpriteAnimator`](animation::SpriteAnimator) and [`SpriteCulling`](culling::SpriteCulling).
+pub(crate) struct Dispatch<'a> {
+    pub(crate) label: &'static str,
+    pub(crate) pipeline: &'a wgpu::ComputePipeline,
+    pub(crate) bind_groups: Vec<&'a wgpu::BindGroup>,
+    pub(crate) workgroups: u32,
+}
+
 pub(crate) struct ScenePrerender<'a> {
+    /// The shapes of every [`Mask`], these are drawn first so the stencil buffer is ready.
+    pub(crate) masks: Vec<Prerender<'a>>,
     pub(crate) opaques: Vec<Prerender<'a>>,
     pub(crate) alphas: Vec<Prerender<'a>>,
+
+    /// The compute dispatches which are run before the draws.
+    pub(crate) dispatches: Vec<Dispatch<'a>>,
 }
 
 impl<'a> ScenePrerender<'a> {
     #[inline]
     fn new() -> Self {
         Self {
+            masks: vec![],
             opaques: vec![],
             alphas: vec![],
+            dispatches: vec![],
+        }
+    }
+
+    pub(crate) fn push(&mut self, prerender: Prerender<'a>) {
+        match prerender.stencil {
+            Stencil::Write(_) => self.masks.push(prerender),
+            _ if prerender.alpha => self.alphas.push(prerender),
+            _ => self.opaques.push(prerender),
+        }
+    }
+
+    /// Splits the draw into a separate draw for each [`Stencil`], the draw must contain every instance.
+    pub(crate) fn push_masked(&mut self, draw: Prerender<'a>, stencils: &StencilRanges, pipelines: &'a mask::StencilPipelines) {
+        for (range, stencil) in stencils.ranges(draw.instances as usize) {
+            self.push(Prerender {
+                first_instance: range.start as u32,
+                instances: range.len() as u32,
+                stencil,
+                pipeline: pipelines.get(draw.alpha, stencil),
+                ..draw.clone()
+            });
+        }
+    }
+
+    /// Returns the stats for every draw which will be rendered, in the same order as [`render`](ScenePrerender::render).
+    pub(crate) fn stats(&self) -> Vec<DrawStats> {
+        self.masks.iter().chain(self.opaques.iter()).chain(self.alphas.iter())
+            .filter(|prerender| prerender.instances > 0)
+            .map(|prerender| DrawStats {
+                label: prerender.label,
+                instances: prerender.instances,
+                alpha: prerender.alpha,
+                gpu_time: None,
+            })
+            .collect()
+    }
+
+    /// Runs the compute dispatches in order, this must be called before [`render`](ScenePrerender::render).
+    pub(crate) fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.dispatches.is_empty() {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Pass"),
+                timestamp_writes: None,
+            });
+
+            for dispatch in self.dispatches.iter() {
+                tracing::trace!(label = dispatch.label, workgroups = dispatch.workgroups, "Dispatch");
+
+                compute_pass.set_pipeline(dispatch.pipeline);
+
+                for (index, bind_group) in dispatch.bind_groups.iter().enumerate() {
+                    compute_pass.set_bind_group(index as u32, Some(*bind_group), &[]);
+                }
+
+                compute_pass.dispatch_workgroups(dispatch.workgroups, 1, 1);
+            }
         }
     }
 
     /// Does the actual rendering, using the prepared data.
-    /// The lifetimes are necessary in order to make it work with wgpu::RenderPass.
     #[inline]
-    pub(crate) fn render<'b>(&'a mut self, render_pass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
-        for prerender in self.opaques.iter_mut() {
-            prerender.render(render_pass);
-        }
+    pub(crate) fn render(&mut self, render_pass: &mut wgpu::RenderPass<'_>, profiler: Option<&Profiler>) {
+        let mut index = 0;
+
+        for prerender in self.masks.iter_mut().chain(self.opaques.iter_mut()).chain(self.alphas.iter_mut()) {
+            if prerender.instances > 0 {
+                if let Some(profiler) = profiler {
+                    profiler.write_start(render_pass, index);
+                }
+
+                prerender.render(render_pass);
+
+                if let Some(profiler) = profiler {
+                    profiler.write_end(render_pass, index);
+                }
 
-        for prerender in self.alphas.iter_mut() {
-            prerender.render(render_pass);
+                index += 1;
+            }
         }
     }
 }
@@ -1094,31 +1476,82 @@ impl<'a> ScenePrerender<'a> {
 #[derive(Copy, Clone, Debug, Pod, Zeroable, Default)]
 pub(crate) struct SceneUniform {
     pub(crate) max_order: f32,
-    _padding1: f32,
-    _padding2: f32,
-    _padding3: f32,
+
+    /// The time from [`Engine::set_time`](crate::Engine::set_time), in milliseconds.
+    ///
+    /// It wraps around every [`TIME_PERIOD`] milliseconds, so it stays precise in long sessions.
+    ///
+    /// It is available to the vertex and fragment stages of every shader as `scene.time`.
+    pub(crate) time: f32,
+    reversed_z: f32,
+
+    /// The time which is used for [`SpriteAnimation`](crate::SpriteAnimation), it is the same as `time`
+    /// except it stays at `0.0` while [`QualitySettings::animations`](crate::QualitySettings::animations) is disabled.
+    pub(crate) animation_time: f32,
+
+    /// How much the order of a sprite increases for each sprite with the same order before it, see [`depth`](crate::depth).
+    pub(crate) tie_step: f32,
+    _padding: [f32; 3],
+}
+
+/// The time in the [`SceneUniform`] wraps around after this many milliseconds.
+///
+/// A large `f32` loses precision (after about 4.6 hours of milliseconds it can't represent every millisecond),
+/// so the time is wrapped as an `f64` before it is converted.
+///
+/// It is one hour, which is divisible by the length of most animation cycles (such as `250.0 * 4`),
+/// so they continue seamlessly when the time wraps around.
+pub(crate) const TIME_PERIOD: f64 = 3_600_000.0;
+
+/// Converts the time from [`Engine::set_time`](crate::Engine::set_time) into the time for the shaders, see [`TIME_PERIOD`].
+#[inline]
+pub(crate) fn wrap_time(time: f64) -> f32 {
+    time.rem_euclid(TIME_PERIOD) as f32
 }
 
 pub(crate) struct SceneRenderer {
     pub(crate) scene_uniform: Uniform<SceneUniform>,
     pub(crate) sprite: SpriteRenderer,
     pub(crate) bitmap_text: BitmapTextRenderer,
+    pub(crate) shape: ShapeRenderer,
+    pub(crate) gradient: GradientRenderer,
+
+    /// The [`Stencil`] for the Nodes which are currently being laid out.
+    pub(crate) stencil: Stencil,
+
+    /// The number of [`Mask`] which have been laid out, this is used for the [`Stencil`] ids.
+    pub(crate) masks: u8,
 }
 
 impl SceneRenderer {
     #[inline]
     fn new(engine: &crate::EngineState) -> Self {
-        let mut scene_uniform = Uniform::new(wgpu::ShaderStages::VERTEX, SceneUniform {
+        // The fragment stage needs it for time based effects, such as custom pipelines which use `scene.time`
+        let visibility = if engine.gpu_animation {
+            // The SpriteAnimator uses the animation time
+            wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE
+
+        } else {
+            wgpu::ShaderStages::VERTEX_FRAGMENT
+        };
+
+        let mut scene_uniform = Uniform::new(visibility, SceneUniform {
             max_order: 1.0,
-            _padding1: 0.0,
-            _padding2: 0.0,
-            _padding3: 0.0,
+            time: 0.0,
+            reversed_z: if engine.depth.reversed_z { 1.0 } else { 0.0 },
+            animation_time: 0.0,
+            tie_step: 0.0,
+            _padding: [0.0; 3],
         });
 
         Self {
             sprite: SpriteRenderer::new(engine, &mut scene_uniform),
             bitmap_text: BitmapTextRenderer::new(engine, &mut scene_uniform),
+            shape: ShapeRenderer::new(engine, &mut scene_uniform),
+            gradient: GradientRenderer::new(engine, &mut scene_uniform),
             scene_uniform,
+            stencil: Stencil::None,
+            masks: 0,
         }
     }
 
@@ -1131,14 +1564,44 @@ impl SceneRenderer {
         self.scene_uniform.max_order = self.scene_uniform.max_order.max(order);
     }
 
+    /// Compiles the pipelines for every renderer.
+    pub(crate) fn warmup(&mut self, engine: &crate::EngineState) {
+        self.sprite.warmup(engine);
+        self.bitmap_text.warmup(engine);
+        self.shape.warmup(engine);
+        self.gradient.warmup(engine);
+    }
+
     /// This is run before doing the layout of the children,
     /// it allows the renderer to prepare any state that it
     /// needs for the layout.
     #[inline]
     fn before_layout(&mut self) {
         self.scene_uniform.max_order = 1.0;
+        self.stencil = Stencil::None;
+        self.masks = 0;
         self.sprite.before_layout();
         self.bitmap_text.before_layout();
+        self.shape.before_layout();
+        self.gradient.before_layout();
+    }
+
+    /// This is run after doing the layout of the children.
+    ///
+    /// Sprites with the same order are spread across the first half of `min_order_step`,
+    /// so they don't overlap with the next order, see [`depth`](crate::depth).
+    fn after_layout(&mut self, min_order_step: f32) {
+        let max_tie = self.sprite.max_tie();
+
+        if max_tie > 0 {
+            let headroom = min_order_step * 0.5;
+
+            self.scene_uniform.tie_step = headroom / (max_tie as f32 + 1.0);
+            self.scene_uniform.max_order += headroom;
+
+        } else if self.scene_uniform.tie_step != 0.0 {
+            self.scene_uniform.tie_step = 0.0;
+        }
     }
 
     /// This is run before doing the rendering of the children,
@@ -1148,6 +1611,8 @@ impl SceneRenderer {
     fn before_render(&mut self) {
         self.sprite.before_render();
         self.bitmap_text.before_render();
+        self.shape.before_render();
+        self.gradient.before_render();
     }
 
     #[inline]
@@ -1156,8 +1621,11 @@ impl SceneRenderer {
 
         let mut prerender = ScenePrerender::new();
 
+        // Transparent gradients are usually backgrounds, so they are drawn first
+        self.gradient.prerender(engine, bind_group, &mut prerender);
         self.sprite.prerender(engine, bind_group, &mut prerender);
         self.bitmap_text.prerender(engine, bind_group, &mut prerender);
+        self.shape.prerender(engine, bind_group, &mut prerender);
 
         prerender
     }
@@ -1168,9 +1636,14 @@ pub(crate) struct Scene {
     pub(crate) changed: Arc<SceneChanged>,
     pub(crate) renderer: SceneRenderer,
     pub(crate) rendered_nodes: Vec<NodeHandle>,
+    time_changed: bool,
+    animation_time_changed: bool,
+
+    /// Whether a relayout happened without recording a [`SceneSnapshot`] for the crash report.
+    crash_snapshot_pending: bool,
 
     /// Assets
-    pub(crate) textures: Handles<TextureBuffer>,
+    pub(crate) textures: Handles<TextureState>,
 }
 
 impl Scene {
@@ -1188,58 +1661,127 @@ impl Scene {
             renderer: SceneRenderer::new(engine),
             textures: Handles::new(),
             rendered_nodes: vec![],
+            time_changed: false,
+            animation_time_changed: false,
+            crash_snapshot_pending: false,
+        }
+    }
+
+    /// If `animations` is `false` then the sprites stay on their first frame, but `scene.time` still changes.
+    pub(crate) fn set_time(&mut self, time: f64, animations: bool) {
+        let time = wrap_time(time);
+
+        let animation_time = if animations { time } else { 0.0 };
+
+        if self.renderer.scene_uniform.time != time {
+            self.renderer.scene_uniform.time = time;
+            self.time_changed = true;
+        }
+
+        if self.renderer.scene_uniform.animation_time != animation_time {
+            self.renderer.scene_uniform.animation_time = animation_time;
+            self.animation_time_changed = true;
         }
     }
 
     #[inline]
     pub(crate) fn should_render(&self) -> bool {
-        self.changed.is_render_changed()
+        self.changed.is_render_changed() ||
+        (self.time_changed && self.renderer.sprite.uses_time()) ||
+        (self.animation_time_changed && self.renderer.sprite.is_animated()) ||
+        self.wants_crash_snapshot()
     }
 
-    /// Before rendering, this runs any necessary processing and prepares data for the render.
-    /// The lifetimes are necessary in order to make it work with wgpu::RenderPass.
-    pub(crate) fn prerender<'a>(&'a mut self, engine: &crate::EngineState) -> ScenePrerender<'a> {
-        let layout_changed = self.changed.replace_layout_changed();
-        let render_changed = self.changed.replace_render_changed();
+    /// Recording the snapshot is slow, so it is throttled. If a relayout was skipped
+    /// then it relayouts again later, so the crash report doesn't have an old layout.
+    #[inline]
+    fn wants_crash_snapshot(&self) -> bool {
+        self.crash_snapshot_pending && crate::crash::wants_scene()
+    }
+
+    fn layout(&mut self, engine: &crate::EngineState, snapshot: Option<&mut SnapshotRecorder>) {
+        let _span = tracing::debug_span!("Scene layout").entered();
+
+        self.renderer.before_layout();
 
-        if DEBUG {
-            log::warn!("rendered_nodes {}", self.rendered_nodes.len());
+        self.rendered_nodes.clear();
+
+        let child = &self.root.handle;
+
+        let mut lock = child.lock();
+
+        if lock.is_visible() {
+            let screen_size = ScreenSize::new(
+                engine.window_size.width as f32,
+                engine.window_size.height as f32,
+                engine.ui_scale,
+            );
+
+            let mut info = SceneLayoutInfo {
+                screen_size: &screen_size,
+                renderer: &mut self.renderer,
+                rendered_nodes: &mut self.rendered_nodes,
+                snapshot,
+            };
+
+            let parent = RealLocation::full();
+
+            let smallest_size = lock.smallest_size(&parent.size.smallest_size(), &mut info);
+
+            lock.update_layout(child, &parent, &smallest_size, &mut info);
         }
 
-        if layout_changed {
-            self.renderer.before_layout();
+        self.renderer.after_layout(engine.depth.min_order_step);
 
-            self.rendered_nodes.clear();
+        tracing::debug!(rendered_nodes = self.rendered_nodes.len(), "Scene layout finished");
+    }
 
-            let child = &self.root.handle;
+    /// Relayouts the scene and returns the computed location of every visible Node.
+    pub(crate) fn dump_tree(&mut self, engine: &crate::EngineState) -> SceneSnapshot {
+        let mut snapshot = SnapshotRecorder::new();
 
-            let mut lock = child.lock();
+        // The layout is done here, so it doesn't need to be done again in prerender.
+        self.changed.replace_layout_changed();
 
-            if lock.is_visible() {
-                let screen_size = ScreenSize::new(
-                    engine.window_size.width as f32,
-                    engine.window_size.height as f32,
-                );
+        self.layout(engine, Some(&mut snapshot));
 
-                let mut info = SceneLayoutInfo {
-                    screen_size: &screen_size,
-                    renderer: &mut self.renderer,
-                    rendered_nodes: &mut self.rendered_nodes,
-                };
+        // The layout rebuilt the GPU instances, so it needs to render again.
+        self.changed.trigger_render_change();
 
-                let parent = RealLocation::full();
+        snapshot.finish(&engine.window_size)
+    }
 
-                let smallest_size = lock.smallest_size(&parent.size.smallest_size(), &mut info);
+    /// Before rendering, this runs any necessary processing and prepares data for the render.
+    /// The lifetimes are necessary in order to make it work with wgpu::RenderPass.
+    pub(crate) fn prerender<'a>(&'a mut self, engine: &crate::EngineState) -> ScenePrerender<'a> {
+        let layout_changed = self.changed.replace_layout_changed() || self.wants_crash_snapshot();
+        let render_changed = self.changed.replace_render_changed();
 
-                lock.update_layout(child, &parent, &smallest_size, &mut info);
+        if layout_changed {
+            if engine.crash_report && crate::crash::wants_scene() {
+                let mut snapshot = SnapshotRecorder::new();
+
+                self.layout(engine, Some(&mut snapshot));
+
+                crate::crash::set_scene(snapshot.finish(&engine.window_size));
+
+                self.crash_snapshot_pending = false;
+
+            } else {
+                self.layout(engine, None);
+
+                self.crash_snapshot_pending = engine.crash_report;
             }
 
         } else if render_changed {
+            let _span = tracing::trace_span!("Scene render", rendered_nodes = self.rendered_nodes.len()).entered();
+
             self.renderer.before_render();
 
             let screen_size = ScreenSize::new(
                 engine.window_size.width as f32,
                 engine.window_size.height as f32,
+                engine.ui_scale,
             );
 
             let mut info = SceneRenderInfo {
@@ -1252,6 +1794,18 @@ impl Scene {
             }
         }
 
+        if layout_changed || render_changed {
+            self.renderer.sprite.update_animated();
+        }
+
+        self.time_changed = false;
+        self.animation_time_changed = false;
+
+        let _span = tracing::trace_span!("Scene prerender").entered();
+
+        self.renderer.sprite.restore_evicted(engine, &mut self.textures);
+        self.renderer.sprite.update_batches(engine, &self.textures);
+
         self.renderer.prerender(engine)
     }
 }
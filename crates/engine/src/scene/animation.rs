@@ -0,0 +1,151 @@
+use crate::util::macros::wgsl;
+use crate::util::buffer::{InstanceVec};
+use crate::scene::culling::{SpriteCulling};
+use crate::scene::sprite::{GPUSprite};
+use crate::scene::{ScenePrerender, Dispatch};
+
+
+/// Selects the frame of every [`SpriteAnimation`](crate::SpriteAnimation) with a compute shader,
+/// see [`EngineSettings::gpu_animation`](crate::EngineSettings::gpu_animation).
+///
+/// The frame is written into the sprite instances on the GPU, so the sprites don't need to be
+/// updated when the time changes. If it isn't supported then the vertex shader selects the frame instead.
+pub(crate) struct SpriteAnimator {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl SpriteAnimator {
+    /// Must be kept in sync with animate.wgsl
+    const WORKGROUP_SIZE: u32 = 64;
+
+    const DOWNLEVEL_FLAGS: wgpu::DownlevelFlags = wgpu::DownlevelFlags::COMPUTE_SHADERS;
+
+    /// Adds the limits which are needed for the compute shader to `limits`.
+    pub(crate) fn limits(limits: wgpu::Limits) -> wgpu::Limits {
+        wgpu::Limits {
+            max_storage_buffers_per_shader_stage: limits.max_storage_buffers_per_shader_stage.max(1),
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size.max(128 << 20),
+            max_compute_workgroup_size_x: limits.max_compute_workgroup_size_x.max(Self::WORKGROUP_SIZE),
+            max_compute_workgroup_size_y: limits.max_compute_workgroup_size_y.max(1),
+            max_compute_workgroup_size_z: limits.max_compute_workgroup_size_z.max(1),
+            max_compute_invocations_per_workgroup: limits.max_compute_invocations_per_workgroup.max(Self::WORKGROUP_SIZE),
+            max_compute_workgroups_per_dimension: limits.max_compute_workgroups_per_dimension.max(65535),
+            ..limits
+        }
+    }
+
+    /// Whether the adapter supports compute shaders, WebGL doesn't.
+    pub(crate) fn is_supported(adapter: &wgpu::Adapter, limits: &wgpu::Limits) -> bool {
+        adapter.get_downlevel_capabilities().flags.contains(Self::DOWNLEVEL_FLAGS) &&
+        Self::limits(limits.clone()).check_limits(&adapter.limits())
+    }
+
+    /// The `scene_uniform_layout` must be visible to the compute stage.
+    pub(crate) fn new(engine: &crate::EngineState, scene_uniform_layout: &wgpu::BindGroupLayout) -> Self {
+        let layout = engine.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Animation"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = engine.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Animation"),
+            bind_group_layouts: &[scene_uniform_layout, &layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = engine.device.create_shader_module(wgsl!("spritesheet/animate.wgsl"));
+
+        let pipeline = engine.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Sprite Animation"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: engine.pipeline_cache.native(),
+        });
+
+        Self { layout, pipeline }
+    }
+
+    #[inline]
+    fn workgroups(instances: u32) -> u32 {
+        instances.div_ceil(Self::WORKGROUP_SIZE)
+    }
+
+    /// Very large batches don't fit into a single dispatch, so the vertex shader selects their frames.
+    fn can_animate(engine: &crate::EngineState, instances: u32) -> bool {
+        let limits = engine.device.limits();
+
+        instances > 0 &&
+        Self::workgroups(instances) <= limits.max_compute_workgroups_per_dimension &&
+        (instances as u64 * SpriteCulling::SPRITE_WORDS as u64 * 4) <= limits.max_storage_buffer_binding_size as u64
+    }
+}
+
+
+/// The bind group of [`SpriteAnimator`] for a batch of sprites, it is kept between frames.
+pub(crate) struct AnimatedInstances {
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl AnimatedInstances {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self { bind_group: None }
+    }
+
+    /// Selects the frames of the `sprites` before they are drawn or culled.
+    ///
+    /// The buffer of the `sprites` must be up to date and it must have the `STORAGE` usage.
+    pub(crate) fn prerender<'a>(
+        &'a mut self,
+        engine: &crate::EngineState,
+        animator: &'a SpriteAnimator,
+        label: &'static str,
+        scene_uniform: &'a wgpu::BindGroup,
+        sprites: &InstanceVec<GPUSprite>,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        let instances = sprites.len() as u32;
+
+        let buffer = match sprites.buffer() {
+            Some(buffer) if SpriteAnimator::can_animate(engine, instances) => buffer,
+            _ => return,
+        };
+
+        // The instance buffer can be recreated when it grows, so the bind group is recreated every frame
+        self.bind_group = Some(engine.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Animation"),
+            layout: &animator.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(instances as u64 * SpriteCulling::SPRITE_WORDS as u64 * 4),
+                    }),
+                },
+            ],
+        }));
+
+        prerender.dispatches.push(Dispatch {
+            label,
+            pipeline: &animator.pipeline,
+            bind_groups: vec![scene_uniform, self.bind_group.as_ref().unwrap()],
+            workgroups: SpriteAnimator::workgroups(instances),
+        });
+    }
+}
@@ -1,33 +1,87 @@
 use std::borrow::Cow;
+use std::ops::Range;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use wgpu_helpers::VertexLayout;
 use bytemuck::{Pod, Zeroable};
 use futures_signals::signal::{Signal, SignalExt};
 
-use crate::{DEBUG, Engine, Handle};
+use crate::{Engine, Handle};
 use crate::util::unicode;
 use crate::util::macros::wgsl;
 use crate::util::buffer::{Uniform, InstanceVec, InstanceVecOptions, GrayscaleImage, TextureBuffer};
 use crate::util::builders;
 use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, simple_method};
-use crate::scene::sprite::{GPUSprite, Tile, SpritesheetPipeline, SCENE_SHADER, SPRITE_SHADER};
+use crate::scene::sprite::{GPUSprite, Tile, SpritesheetPipeline};
+use crate::scene::mask::{Stencil, StencilRanges};
 use crate::scene::{
-    NodeHandle, Location, Origin, Size, Offset, Padding, SmallestLength,
+    NodeHandle, NodeRef, Location, Origin, Size, Offset, Padding, SmallestLength,
     RealLocation, NodeLayout, SceneLayoutInfo, SceneRenderInfo, Order,
     Length, Percentage, Handles, Prerender, Texture, SceneUniform,
     ScenePrerender, RealSize, ScreenSize, SmallestSize, RealPosition,
 };
 
 
-/// RGB color.
+/// sRGB color, which is the same as the colors in image editors.
 ///
 /// Each color channel is from 0.0 to 1.0
-#[derive(Debug, Clone, Copy, Default)]
+///
+/// The colors are converted with [`to_linear`](ColorRgb::to_linear) before they are sent to the GPU,
+/// so blending and gradients are done in linear RGB, and the output is converted back into sRGB.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct ColorRgb {
     pub r: Percentage,
     pub g: Percentage,
     pub b: Percentage,
 }
 
+impl ColorRgb {
+    /// Converts a color channel from sRGB into linear.
+    fn channel_to_linear(value: f32) -> f32 {
+        if value <= 0.04045 {
+            value / 12.92
+
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    /// Converts a color channel from linear into sRGB.
+    fn channel_to_srgb(value: f32) -> f32 {
+        if value <= 0.0031308 {
+            value * 12.92
+
+        } else {
+            (1.055 * value.powf(1.0 / 2.4)) - 0.055
+        }
+    }
+
+    /// Converts from sRGB into linear RGB.
+    pub fn to_linear(&self) -> Self {
+        Self {
+            r: Self::channel_to_linear(self.r),
+            g: Self::channel_to_linear(self.g),
+            b: Self::channel_to_linear(self.b),
+        }
+    }
+
+    /// Converts from linear RGB into sRGB, this is the opposite of [`to_linear`](ColorRgb::to_linear).
+    pub fn to_srgb(&self) -> Self {
+        Self {
+            r: Self::channel_to_srgb(self.r),
+            g: Self::channel_to_srgb(self.g),
+            b: Self::channel_to_srgb(self.b),
+        }
+    }
+
+    /// The linear color which is sent to the GPU.
+    #[inline]
+    pub(crate) fn to_gpu(&self) -> [f32; 3] {
+        let linear = self.to_linear();
+        [linear.r, linear.g, linear.b]
+    }
+}
+
 
 /// Size of each character.
 ///
@@ -44,7 +98,7 @@ pub struct ColorRgb {
 /// * [`Length::SmallestWidth`]: it is an error to use `SmallestWidth`.
 ///
 /// * [`Length::SmallestHeight`]: it is an error to use `SmallestHeight`.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct CharSize {
     pub width: Length,
     pub height: Length,
@@ -62,15 +116,108 @@ impl CharSize {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable, VertexLayout, Default, PartialEq)]
 #[layout(step_mode = Instance)]
-#[layout(location = 6)]
+#[layout(location = 11)]
 pub(crate) struct GPUChar {
     pub(crate) color: [f32; 3],
 }
 
 
+/// The parameters which affect the placement of the glyphs.
+///
+/// The font isn't included, because every glyph has the same size regardless of the font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct LayoutParams {
+    char_width: u32,
+    char_height: u32,
+    line_height: u32,
+    max_width: Option<u32>,
+}
+
+impl LayoutParams {
+    fn new(char_size: &RealSize, line_height: f32, max_width: Option<Percentage>) -> Self {
+        Self {
+            char_width: char_size.width.to_bits(),
+            char_height: char_size.height.to_bits(),
+            line_height: line_height.to_bits(),
+            max_width: max_width.map(f32::to_bits),
+        }
+    }
+}
+
+
+struct CachedLayout {
+    text: Box<str>,
+    params: LayoutParams,
+    glyphs: Vec<(char, RealPosition)>,
+    size: RealSize,
+}
+
+
+/// Glyph placements of recently laid out text, so that static labels don't need to
+/// redo the grapheme segmentation and width calculations after every layout change.
+///
+/// Layouts which weren't used during the previous layout are discarded.
+pub(crate) struct TextLayoutCache {
+    current: HashMap<u64, Vec<CachedLayout>>,
+    previous: HashMap<u64, Vec<CachedLayout>>,
+}
+
+impl TextLayoutCache {
+    fn new() -> Self {
+        Self {
+            current: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    fn hash(text: &str, params: &LayoutParams) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        text.hash(&mut hasher);
+        params.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn get(&mut self, text: &str, params: &LayoutParams) -> Option<&CachedLayout> {
+        let hash = Self::hash(text, params);
+
+        let matches = |layout: &CachedLayout| &*layout.text == text && layout.params == *params;
+
+        // Layouts from the previous generation are moved into the current generation, so they stay in the cache
+        if !self.current.get(&hash).map_or(false, |layouts| layouts.iter().any(matches)) {
+            let previous = self.previous.get_mut(&hash)?;
+            let index = previous.iter().position(matches)?;
+            let layout = previous.swap_remove(index);
+            self.current.entry(hash).or_default().push(layout);
+        }
+
+        self.current.get(&hash)?.iter().find(|layout| matches(layout))
+    }
+
+    fn insert(&mut self, text: &str, params: LayoutParams, glyphs: Vec<(char, RealPosition)>, size: RealSize) {
+        let hash = Self::hash(text, &params);
+
+        self.current.entry(hash).or_default().push(CachedLayout {
+            text: text.into(),
+            params,
+            glyphs,
+            size,
+        });
+    }
+
+    /// Discards the layouts which weren't used since the last call.
+    fn next_generation(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+    }
+}
+
+
 struct Glyph {
     character: char,
 
+    /// Index of the font in the fallback chain, `0` is the main font.
+    font: usize,
+
     position: RealPosition,
     size: RealSize,
 
@@ -78,6 +225,24 @@ struct Glyph {
     gpu_char: GPUChar,
 }
 
+impl Glyph {
+    fn new(character: char, position: RealPosition, size: RealSize) -> Self {
+        let mut gpu_sprite = GPUSprite::default();
+        let gpu_char = GPUChar::default();
+
+        gpu_sprite.uv = [1.0, 1.0];
+
+        Self {
+            character,
+            font: 0,
+            position,
+            size,
+            gpu_sprite,
+            gpu_char,
+        }
+    }
+}
+
 
 /// Displays text which is stored in a spritesheet.
 ///
@@ -94,19 +259,33 @@ struct Glyph {
 pub struct BitmapText {
     // Standard fields
     visible: bool,
+    node_ref: Option<NodeRef>,
     location: Location,
 
     // Required fields
     font: Option<BitmapFont>,
     char_size: Option<CharSize>,
 
+    /// Fonts which are used for the characters which aren't supported by `font`.
+    font_fallbacks: Vec<BitmapFont>,
+
     // Optional fields
     text: Cow<'static, str>,
     text_color: ColorRgb,
     line_spacing: Length,
+    incremental: bool,
 
     // Internal state
     glyphs: Vec<Glyph>,
+
+    /// The characters of the current grapheme, it is reused to avoid allocations.
+    grapheme_chars: Vec<char>,
+
+    /// Whether the color changed, which only requires a re-render.
+    render_changed: bool,
+
+    /// The GPU chars which were pushed during the last layout, for each font in the fallback chain.
+    gpu_chars: Vec<Range<usize>>,
 }
 
 impl BitmapText {
@@ -114,117 +293,209 @@ impl BitmapText {
     fn new() -> Self {
         Self {
             visible: true,
+            node_ref: None,
             location: Location::default(),
 
             font: None,
             char_size: None,
+            font_fallbacks: vec![],
 
             text: "".into(),
             text_color: ColorRgb::default(),
             line_spacing: Length::Zero,
+            incremental: false,
 
             glyphs: vec![],
+            grapheme_chars: vec![],
+
+            render_changed: false,
+            gpu_chars: vec![],
         }
     }
 
-    fn layout_glyphs<'a>(&mut self, parent: &SmallestSize, max_width: Option<Percentage>, screen_size: &ScreenSize) -> RealSize {
-        let char_size = self.char_size.as_ref().expect("BitmapText is missing char_size");
-        let char_size = char_size.to_screen(parent, screen_size);
+    /// Lays out a single line of text, starting at `position`.
+    ///
+    /// Afterwards `position` is at the start of the next line.
+    fn layout_line(&mut self, text_line: &str, position: &mut RealPosition, size: &mut RealSize, char_size: &RealSize, line_height: f32, max_width: Option<Percentage>) {
+        let glyph_size = RealSize {
+            width: 2.0 * char_size.width,
+            height: char_size.height,
+        };
 
-        let line_spacing = self.line_spacing.smallest_length(&screen_size.height).parent_to_screen(parent).unwrap();
+        let mut width = 0.0;
 
-        let line_height = char_size.height + line_spacing;
+        for grapheme in unicode::graphemes(text_line) {
+            self.grapheme_chars.clear();
+
+            let mut unicode_width = None;
+
+            // The characters are buffered so that the grapheme is only decoded once
+            for c in grapheme.chars() {
+                let char_width = unicode::char_width(c);
+                unicode_width = Some(unicode_width.map_or(char_width, |width: u32| width.max(char_width)));
+                self.grapheme_chars.push(c);
+            }
 
+            if let Some(unicode_width) = unicode_width {
+                let unicode_display_width = if unicode_width == 0 {
+                    2
+
+                } else {
+                    unicode_width
+                };
+
+                let max_char_width = (unicode_display_width as f32) * char_size.width;
+
+                width += max_char_width;
+
+                if width > max_char_width && max_width.map(|max_width| width > max_width).unwrap_or(false) {
+                    width = max_char_width;
+                    position.x = 0.0;
+                    position.y += line_height;
+                }
+
+                for &c in self.grapheme_chars.iter() {
+                    let mut position = *position;
+
+                    position.x += unicode::char_offset(c, unicode_width) * char_size.width;
+
+                    self.glyphs.push(Glyph::new(c, position, glyph_size));
+                }
+
+                position.x = width;
+
+                size.width = size.width.max(width);
+                size.height = size.height.max(position.y + char_size.height);
+            }
+        }
+
+        position.x = 0.0;
+        position.y += line_height;
+    }
+
+    /// Lays out each line separately, so that unchanged lines are reused from the cache, see [`BitmapTextBuilder::incremental`].
+    fn layout_incremental(&mut self, layouts: &mut TextLayoutCache, params: &LayoutParams, char_size: &RealSize, line_height: f32, max_width: Option<Percentage>) -> RealSize {
         let glyph_size = RealSize {
             width: 2.0 * char_size.width,
             height: char_size.height,
         };
 
-
         let mut position = RealPosition::zero();
         let mut size = RealSize::zero();
 
-        if self.text == "" {
-            debug_assert_eq!(self.glyphs.len(), 0);
+        // The text is temporarily taken so that the lines can be borrowed while the glyphs are pushed
+        let text = std::mem::take(&mut self.text);
 
-        } else {
-            for text_line in self.text.lines() {
-                let mut width = 0.0;
+        for text_line in text.lines() {
+            if text_line == "" {
+                position.y += line_height;
+                continue;
+            }
+
+            let offset = position.y;
+            let start = self.glyphs.len();
 
-                for grapheme in unicode::graphemes(text_line) {
-                    // TODO figure out a way to avoid iterating over the characters twice
-                    let unicode_width = grapheme.chars()
-                        .map(unicode::char_width)
-                        .max();
+            // A line doesn't contain newlines, so the cached layout of a line is the
+            // same as the cached layout of a BitmapText which only contains that line.
+            if let Some(layout) = layouts.get(text_line, params) {
+                for &(c, position) in layout.glyphs.iter() {
+                    self.glyphs.push(Glyph::new(c, RealPosition { x: position.x, y: position.y + offset }, glyph_size));
+                }
 
-                    if let Some(unicode_width) = unicode_width {
-                        let unicode_display_width = if unicode_width == 0 {
-                            2
+                size.width = size.width.max(layout.size.width);
+                size.height = size.height.max(offset + layout.size.height);
 
-                        } else {
-                            unicode_width
-                        };
+            } else {
+                let mut line_position = RealPosition::zero();
+                let mut line_size = RealSize::zero();
+
+                self.layout_line(text_line, &mut line_position, &mut line_size, char_size, line_height, max_width);
+
+                let glyphs = self.glyphs[start..].iter().map(|glyph| (glyph.character, glyph.position)).collect();
+
+                layouts.insert(text_line, *params, glyphs, line_size);
 
-                        let max_char_width = (unicode_display_width as f32) * char_size.width;
+                for glyph in self.glyphs[start..].iter_mut() {
+                    glyph.position.y += offset;
+                }
 
-                        width += max_char_width;
+                size.width = size.width.max(line_size.width);
+                size.height = size.height.max(offset + line_size.height);
+            }
 
-                        if width > max_char_width && max_width.map(|max_width| width > max_width).unwrap_or(false) {
-                            width = max_char_width;
-                            position.x = 0.0;
-                            position.y += line_height;
-                        }
+            // The line always has glyphs, so the next line starts below the last wrapped row
+            position.y = self.glyphs.last().unwrap().position.y + line_height;
+        }
 
-                        let mut has_char = false;
+        self.text = text;
 
-                        for c in grapheme.chars() {
-                            has_char = true;
+        size
+    }
 
-                            let mut position = position;
+    fn layout_glyphs<'a>(&mut self, layouts: &mut TextLayoutCache, parent: &SmallestSize, max_width: Option<Percentage>, screen_size: &ScreenSize) -> RealSize {
+        let char_size = self.char_size.as_ref().expect("BitmapText is missing char_size");
+        let char_size = char_size.to_screen(parent, screen_size);
 
-                            position.x += unicode::char_offset(c, unicode_width) * char_size.width;
+        let line_spacing = self.line_spacing.smallest_length(&screen_size.height).parent_to_screen(parent).unwrap();
 
-                            let mut gpu_sprite = GPUSprite::default();
-                            let gpu_char = GPUChar::default();
+        let line_height = char_size.height + line_spacing;
 
-                            gpu_sprite.uv = [1.0, 1.0];
+        let glyph_size = RealSize {
+            width: 2.0 * char_size.width,
+            height: char_size.height,
+        };
 
-                            self.glyphs.push(Glyph {
-                                character: c,
-                                position,
-                                size: glyph_size,
-                                gpu_sprite,
-                                gpu_char,
-                            });
-                        }
 
-                        position.x = width;
+        let mut position = RealPosition::zero();
+        let mut size = RealSize::zero();
 
-                        if has_char {
-                            size.width = size.width.max(width);
-                            size.height = size.height.max(position.y + char_size.height);
-                        }
-                    }
+        if self.text == "" {
+            debug_assert_eq!(self.glyphs.len(), 0);
+
+        } else {
+            let params = LayoutParams::new(&char_size, line_height, max_width);
+
+            if self.incremental {
+                return self.layout_incremental(layouts, &params, &char_size, line_height, max_width);
+            }
+
+            if let Some(layout) = layouts.get(&self.text, &params) {
+                for &(c, position) in layout.glyphs.iter() {
+                    self.glyphs.push(Glyph::new(c, position, glyph_size));
                 }
 
-                position.x = 0.0;
-                position.y += line_height;
+                return layout.size;
             }
+
+            let start = self.glyphs.len();
+
+            // The text is temporarily taken so that the lines can be borrowed while the glyphs are pushed
+            let text = std::mem::take(&mut self.text);
+
+            for text_line in text.lines() {
+                self.layout_line(text_line, &mut position, &mut size, &char_size, line_height, max_width);
+            }
+
+            self.text = text;
+
+            let glyphs = self.glyphs[start..].iter().map(|glyph| (glyph.character, glyph.position)).collect();
+
+            layouts.insert(&self.text, params, glyphs, size);
         }
 
         size
     }
 
-    fn calculate_glyphs<'a>(&mut self, parent: &SmallestSize, width: Percentage, screen_size: &ScreenSize) {
+    fn calculate_glyphs<'a>(&mut self, layouts: &mut TextLayoutCache, parent: &SmallestSize, width: Percentage, screen_size: &ScreenSize) {
         if self.glyphs.is_empty() {
-            let _ = self.layout_glyphs(parent, Some(width), screen_size);
+            let _ = self.layout_glyphs(layouts, parent, Some(width), screen_size);
         }
     }
 
-    fn children_size<'a>(&mut self, parent: &SmallestSize, screen_size: &ScreenSize) -> RealSize {
+    fn children_size<'a>(&mut self, layouts: &mut TextLayoutCache, parent: &SmallestSize, screen_size: &ScreenSize) -> RealSize {
         match parent.width {
-            SmallestLength::Screen(width) => self.layout_glyphs(parent, Some(width), screen_size),
-            SmallestLength::SmallestWidth(_) => self.layout_glyphs(parent, None, screen_size),
+            SmallestLength::Screen(width) => self.layout_glyphs(layouts, parent, Some(width), screen_size),
+            SmallestLength::SmallestWidth(_) => self.layout_glyphs(layouts, parent, None, screen_size),
             SmallestLength::SmallestHeight(_) => panic!("BitmapText smallest height is unknown"),
             SmallestLength::ParentWidth(_) => panic!("BitmapText width is unknown"),
             SmallestLength::ParentHeight(_) => panic!("BitmapText height is unknown"),
@@ -247,6 +518,24 @@ impl BitmapTextBuilder {
         },
     );
 
+    simple_method!(
+        /// Sets the fonts which are used for characters that the main [`font`](BitmapTextBuilder::font) doesn't support.
+        ///
+        /// Each character uses the first font which supports it, starting with the main font.
+        /// If none of the fonts support it, then the main font's replacement character is used.
+        ///
+        /// This makes it possible to use a stylized font which only supports Latin characters,
+        /// and fall back to a font with wider coverage for other scripts.
+        ///
+        /// Defaults to no fallbacks.
+        font_fallbacks,
+        font_fallbacks_signal,
+        |state, value: Vec<BitmapFont>| {
+            state.font_fallbacks = value;
+            BuilderChanged::Layout
+        },
+    );
+
     simple_method!(
         /// Sets the [`CharSize`] which specifies the width / height of each character.
         char_size,
@@ -276,9 +565,14 @@ impl BitmapTextBuilder {
         text_color,
         text_color_signal,
         |state, value: ColorRgb| {
-            state.text_color = value;
-            // TODO it should update the text color without needing to relayout
-            BuilderChanged::Layout
+            if state.text_color != value {
+                state.text_color = value;
+                state.render_changed = true;
+                BuilderChanged::Render
+
+            } else {
+                BuilderChanged::None
+            }
         },
     );
 
@@ -303,12 +597,28 @@ impl BitmapTextBuilder {
             BuilderChanged::Layout
         },
     );
+
+    simple_method!(
+        /// Caches the layout of each line separately, so when lines are added to
+        /// (or removed from) the text, only the new lines need to be laid out.
+        ///
+        /// This is useful for logs, such as a chat log, which append lines to long text.
+        /// Static text should use the default, because it caches the whole text in one layout.
+        ///
+        /// Defaults to `false`.
+        incremental,
+        incremental_signal,
+        |state, value: bool| {
+            state.incremental = value;
+            BuilderChanged::Layout
+        },
+    );
 }
 
 impl NodeLayout for BitmapText {
     #[inline]
     fn is_visible(&mut self) -> bool {
-        self.visible
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
     }
 
     fn smallest_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
@@ -320,7 +630,7 @@ impl NodeLayout for BitmapText {
             let padding = self.location.padding.to_screen(parent, &smallest_size, &info.screen_size);
 
             smallest_size.with_padding(parent, padding, |parent| {
-                self.children_size(&parent, &info.screen_size)
+                self.children_size(&mut info.renderer.bitmap_text.layouts, &parent, &info.screen_size)
             })
 
         } else {
@@ -331,46 +641,95 @@ impl NodeLayout for BitmapText {
     fn update_layout<'a>(&mut self, handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
         let max_order = info.renderer.get_max_order();
 
-        let font = self.font.as_ref().expect("BitmapText is missing font");
+        let BitmapTextRenderer { fonts, layouts, .. } = &mut info.renderer.bitmap_text;
 
-        if let Some(font) = info.renderer.bitmap_text.fonts.get_mut(&font.handle) {
+        if fonts.get(&self.font.as_ref().expect("BitmapText is missing font").handle).is_some() {
             let this_location = self.location.children_location_explicit(parent, &smallest_size.real_size(), &info.screen_size, max_order);
 
+            if let Some(node_ref) = &self.node_ref {
+                node_ref.set_location(this_location);
+            }
+
             // If it has a fixed size then we need to calculate the glyphs.
-            self.calculate_glyphs(&this_location.size.smallest_size(), this_location.size.width, &info.screen_size);
+            self.calculate_glyphs(layouts, &this_location.size.smallest_size(), this_location.size.width, &info.screen_size);
+
+            // The layout already used the current color.
+            self.render_changed = false;
+            self.gpu_chars.clear();
 
             if !self.glyphs.is_empty() {
-                for glyph in self.glyphs.iter_mut() {
-                    let character = font.supported.replace(glyph.character);
+                let font = self.font.as_ref().unwrap();
+                let chain = || std::iter::once(font).chain(self.font_fallbacks.iter());
 
-                    // Always display the full width tile
-                    let tile = font.tile(character, 2);
+                for glyph in self.glyphs.iter_mut() {
+                    glyph.font = chain()
+                        .position(|font| {
+                            fonts.get(&font.handle).map_or(false, |font| font.supported.contains(glyph.character))
+                        })
+                        .unwrap_or(0);
+                }
 
-                    let char_location = RealLocation {
-                        position: this_location.position + glyph.position,
-                        size: glyph.size,
-                        order: this_location.order,
+                for (index, font) in chain().enumerate() {
+                    // Fallback fonts which aren't loaded are skipped
+                    let Some(font) = fonts.get_mut(&font.handle) else {
+                        self.gpu_chars.push(0..0);
+                        continue;
                     };
 
-                    glyph.gpu_sprite.update(&char_location);
-                    glyph.gpu_sprite.tile = [tile.start_x, tile.start_y, tile.end_x, tile.end_y];
+                    let start = font.chars.len();
+
+                    for glyph in self.glyphs.iter_mut().filter(|glyph| glyph.font == index) {
+                        let tile = font.glyph_tile(glyph.character);
+
+                        let char_location = RealLocation {
+                            position: this_location.position + glyph.position,
+                            size: glyph.size,
+                            order: this_location.order,
+                        };
+
+                        glyph.gpu_sprite.update(&char_location);
+                        glyph.gpu_sprite.tile = tile;
 
-                    glyph.gpu_char.color = [self.text_color.r, self.text_color.g, self.text_color.b];
+                        glyph.gpu_char.color = self.text_color.to_gpu();
+
+                        font.stencils.push(font.sprites.len(), info.renderer.stencil);
+                        font.sprites.push(glyph.gpu_sprite);
+                        font.chars.push(glyph.gpu_char);
+                    }
 
-                    font.sprites.push(glyph.gpu_sprite);
-                    font.chars.push(glyph.gpu_char);
+                    self.gpu_chars.push(start..font.chars.len());
                 }
 
                 info.rendered_nodes.push(handle.clone());
                 info.renderer.set_max_order(this_location.order);
             }
+
+            info.enter_node("BitmapText", &this_location);
+            info.exit_node();
         }
 
         self.glyphs.clear();
     }
 
-    #[inline]
-    fn render<'a>(&mut self, _info: &mut SceneRenderInfo<'a>) {}
+    fn render<'a>(&mut self, info: &mut SceneRenderInfo<'a>) {
+        if self.render_changed {
+            self.render_changed = false;
+
+            let font = self.font.as_ref().expect("BitmapText is missing font");
+
+            let color = self.text_color.to_gpu();
+
+            let chain = std::iter::once(font).chain(self.font_fallbacks.iter());
+
+            for (font, range) in chain.zip(self.gpu_chars.iter()) {
+                if let Some(font) = info.renderer.bitmap_text.fonts.get_mut(&font.handle) {
+                    for gpu_char in font.chars[range.clone()].iter_mut() {
+                        gpu_char.color = color;
+                    }
+                }
+            }
+        }
+    }
 }
 
 
@@ -381,33 +740,92 @@ struct BitmapFontState {
     supported: BitmapFontSupported,
     sprites: InstanceVec<GPUSprite>,
     chars: InstanceVec<GPUChar>,
+    stencils: StencilRanges,
     bind_group: wgpu::BindGroup,
+
+    /// Tiles for the first 256 characters (ASCII and Latin-1), which are used by most text.
+    cached_tiles: Vec<[u32; 4]>,
 }
 
 impl BitmapFontState {
-    fn tile(&self, c: char, width: u32) -> Tile {
-        let index = c as u32;
+    fn new(
+        columns: u32,
+        tile_width: u32,
+        tile_height: u32,
+        supported: BitmapFontSupported,
+        bind_group: wgpu::BindGroup,
+    ) -> Self {
+        let mut this = Self {
+            columns,
+            tile_width,
+            tile_height,
+            supported,
+            sprites: InstanceVec::new(),
+            chars: InstanceVec::new(),
+            stencils: StencilRanges::new(),
+            bind_group,
+            cached_tiles: vec![],
+        };
+
+        this.cached_tiles = (0..=u8::MAX)
+            .map(|index| this.uncached_glyph_tile(char::from(index)))
+            .collect();
+
+        this
+    }
+
+    fn uncached_glyph_tile(&self, c: char) -> [u32; 4] {
+        let character = self.supported.replace(c);
 
-        let row = index / self.columns;
-        let column = index - (row * self.columns);
+        // Always display the full width tile
+        let tile = self.tile(character, 2);
 
-        let start_x = column * (self.tile_width * 2);
-        let start_y = row * self.tile_height;
+        [tile.start_x, tile.start_y, tile.end_x, tile.end_y]
+    }
 
-        Tile {
-            start_x,
-            start_y,
-            end_x: start_x + (self.tile_width * width),
-            end_y: start_y + self.tile_height,
+    /// Returns the GPU tile for the character, including replacing unsupported characters.
+    #[inline]
+    fn glyph_tile(&self, c: char) -> [u32; 4] {
+        match self.cached_tiles.get(c as usize) {
+            Some(tile) => *tile,
+            None => self.uncached_glyph_tile(c),
         }
     }
+
+    #[inline]
+    fn tile(&self, c: char, width: u32) -> Tile {
+        font_tile(c, width, self.columns, self.tile_width, self.tile_height)
+    }
+}
+
+
+/// Returns the tile for the character in a font texture which uses the [`BitmapFontSettings`] layout.
+///
+/// The `width` is `1` for the left half of the tile and `2` for the whole tile.
+pub(crate) fn font_tile(c: char, width: u32, columns: u32, tile_width: u32, tile_height: u32) -> Tile {
+    let index = c as u32;
+
+    let row = index / columns;
+    let column = index - (row * columns);
+
+    let start_x = column * (tile_width * 2);
+    let start_y = row * tile_height;
+
+    Tile {
+        start_x,
+        start_y,
+        end_x: start_x + (tile_width * width),
+        end_y: start_y + tile_height,
+    }
 }
 
 
 pub(crate) struct BitmapTextRenderer {
+    bind_group_layout: wgpu::BindGroupLayout,
     pipeline: SpritesheetPipeline,
 
     fonts: Handles<BitmapFontState>,
+    layouts: TextLayoutCache,
 }
 
 impl BitmapTextRenderer {
@@ -415,29 +833,27 @@ impl BitmapTextRenderer {
     pub(crate) fn new(engine: &crate::EngineState, scene_uniform: &mut Uniform<SceneUniform>) -> Self {
         let scene_uniform_layout = Uniform::bind_group_layout(scene_uniform, engine);
 
+        let bind_group_layout = builders::BindGroupLayout::builder()
+            .label("BitmapText")
+            .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Uint)
+            .build(engine);
+
         let pipeline = SpritesheetPipeline::new(
             engine,
+            "BitmapText",
             scene_uniform_layout,
-
-            // TODO lazy load this ?
-            wgsl![
-                "spritesheet/text.wgsl",
-                SCENE_SHADER,
-                SPRITE_SHADER,
-                include_str!("../wgsl/spritesheet/text.wgsl"),
-            ],
+            wgsl!("spritesheet/text.wgsl"),
 
             &[GPUSprite::LAYOUT, GPUChar::LAYOUT],
 
-            builders::BindGroupLayout::builder()
-                .label("BitmapText")
-                .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Uint)
-                .build(engine),
+            &bind_group_layout,
         );
 
         Self {
+            bind_group_layout,
             pipeline,
             fonts: Handles::new(),
+            layouts: TextLayoutCache::new(),
         }
     }
 
@@ -448,30 +864,42 @@ impl BitmapTextRenderer {
         texture: &TextureBuffer,
         settings: BitmapFontSettings<'a>,
     ) {
-        self.fonts.insert(handle, BitmapFontState {
-            columns: settings.columns,
-            tile_width: settings.tile_width,
-            tile_height: settings.tile_height,
-            supported: settings.supported,
-            sprites: InstanceVec::new(),
-            chars: InstanceVec::new(),
-            bind_group: builders::BindGroup::builder()
-                .label("BitmapText")
-                .layout(&self.pipeline.bind_group_layout)
-                .texture_view(&texture.view)
-                .build(engine),
-        });
+        self.pipeline.init(engine);
+
+        let bind_group = builders::BindGroup::builder()
+            .label("BitmapText")
+            .layout(&self.bind_group_layout)
+            .texture_view(&texture.view)
+            .build(engine);
+
+        self.fonts.insert(handle, BitmapFontState::new(
+            settings.columns,
+            settings.tile_width,
+            settings.tile_height,
+            settings.supported,
+            bind_group,
+        ));
     }
 
     fn remove_font(&mut self, handle: &Handle) {
         self.fonts.remove(handle);
     }
 
+    /// Compiles every pipeline, see [`Engine::warmup`](crate::Engine::warmup).
+    #[inline]
+    pub(crate) fn warmup(&mut self, engine: &crate::EngineState) {
+        self.pipeline.init(engine);
+        self.pipeline.init_masked(engine);
+    }
+
     #[inline]
     pub(crate) fn before_layout(&mut self) {
+        self.layouts.next_generation();
+
         for (_, font) in self.fonts.iter_mut() {
             font.sprites.clear();
             font.chars.clear();
+            font.stencils.clear();
         }
     }
 
@@ -487,19 +915,21 @@ impl BitmapTextRenderer {
     ) {
         prerender.opaques.reserve(self.fonts.len());
 
+        if self.fonts.iter().any(|(_, font)| font.stencils.is_masked()) {
+            self.pipeline.init_masked(engine);
+        }
+
         for (_, font) in self.fonts.iter_mut() {
             let instances = font.sprites.len() as u32;
 
-            if DEBUG {
-                log::warn!("BitmapText {}", instances);
-            }
+            tracing::trace!(instances, "BitmapText");
 
             let bind_groups = vec![
                 scene_uniform,
                 &font.bind_group,
             ];
 
-            let pipeline = &self.pipeline.opaque;
+            let pipelines = self.pipeline.pipelines();
 
             let slices = vec![
                 font.sprites.update_buffer(engine, &InstanceVecOptions {
@@ -511,18 +941,24 @@ impl BitmapTextRenderer {
                 }),
             ];
 
-            prerender.opaques.push(Prerender {
+            prerender.push_masked(Prerender {
+                label: "BitmapText",
+                alpha: false,
                 vertices: 4,
+                first_instance: 0,
                 instances,
-                pipeline,
+                stencil: Stencil::None,
+                pipeline: pipelines.get(false, Stencil::None),
                 bind_groups,
                 slices,
-            });
+                indirect: None,
+            }, &font.stencils, pipelines);
         }
     }
 }
 
 
+#[derive(Debug, Clone, Copy)]
 pub struct BitmapFontSupported {
     pub start: char,
     pub end: char,
@@ -530,12 +966,18 @@ pub struct BitmapFontSupported {
 }
 
 impl BitmapFontSupported {
+    /// Whether the character is in the font.
+    #[inline]
+    fn contains(&self, c: char) -> bool {
+        c >= self.start && c <= self.end
+    }
+
     fn replace(&self, c: char) -> char {
-        if c < self.start || c > self.end {
-            self.replace
+        if self.contains(c) {
+            c
 
         } else {
-            c
+            self.replace
         }
     }
 }
@@ -562,7 +1004,8 @@ impl BitmapFont {
 
     pub fn load<'a>(&self, engine: &mut Engine, settings: BitmapFontSettings<'a>) {
         let texture = engine.scene.textures.get(&settings.texture.handle)
-            .expect("BitmapFontSettings texture is not loaded");
+            .expect("BitmapFontSettings texture is not loaded")
+            .buffer();
 
         assert_eq!(texture.texture.format(), GrayscaleImage::FORMAT, "BitmapFontSettings texture must be a GrayscaleImage");
 
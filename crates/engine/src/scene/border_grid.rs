@@ -1,12 +1,13 @@
 use futures_signals::signal::{Signal, SignalExt};
 use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, simple_method};
 use crate::scene::{
-    NodeHandle, Location, Origin, Size, Offset, Padding, Length, SmallestSize,
+    NodeHandle, NodeRef, Location, Origin, Size, Offset, Padding, Length, SmallestSize,
     RealLocation, NodeLayout, SceneLayoutInfo, SceneRenderInfo, ScreenSize,
     RealSize, RealPosition, Order,
 };
 
 
+#[derive(Debug, Clone, Copy)]
 pub struct BorderSize {
     pub up: Length,
     pub down: Length,
@@ -75,6 +76,7 @@ impl Quadrants {
 /// Displays children in a 3x3 grid where the center quadrant stretches.
 pub struct BorderGrid {
     visible: bool,
+    node_ref: Option<NodeRef>,
     location: Location,
 
     quadrants: Option<Quadrants>,
@@ -88,6 +90,7 @@ impl BorderGrid {
     fn new() -> Self {
         Self {
             visible: true,
+            node_ref: None,
             location: Location::default(),
 
             quadrants: None,
@@ -170,7 +173,7 @@ impl BorderGridBuilder {
 impl NodeLayout for BorderGrid {
     #[inline]
     fn is_visible(&mut self) -> bool {
-        self.visible
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
     }
 
     fn smallest_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
@@ -196,6 +199,12 @@ impl NodeLayout for BorderGrid {
 
         let this_location = self.location.children_location(parent, &smallest_size, &info);
 
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(this_location);
+        }
+
+        info.enter_node("BorderGrid", &this_location);
+
         let size_up = border_size.up.real_length(&parent.size, &smallest_size, &info.screen_size.height);
         let size_down = border_size.down.real_length(&parent.size, &smallest_size, &info.screen_size.height);
         let size_left = border_size.left.real_length(&parent.size, &smallest_size, &info.screen_size.width);
@@ -214,18 +223,6 @@ impl NodeLayout for BorderGrid {
         let center_up = position_up + size_up;
 
 
-        Self::update_child(&quadrants.up_left, info, &RealLocation {
-            position: RealPosition {
-                x: position_left,
-                y: position_up,
-            },
-            size: RealSize {
-                width: size_left,
-                height: size_up,
-            },
-            order: this_location.order,
-        });
-
         Self::update_child(&quadrants.up, info, &RealLocation {
             position: RealPosition {
                 x: center_left,
@@ -238,18 +235,6 @@ impl NodeLayout for BorderGrid {
             order: this_location.order,
         });
 
-        Self::update_child(&quadrants.up_right, info, &RealLocation {
-            position: RealPosition {
-                x: position_right,
-                y: position_up,
-            },
-            size: RealSize {
-                width: size_right,
-                height: size_up,
-            },
-            order: this_location.order,
-        });
-
 
         Self::update_child(&quadrants.left, info, &RealLocation {
             position: RealPosition {
@@ -301,25 +286,52 @@ impl NodeLayout for BorderGrid {
         });
 
 
-        Self::update_child(&quadrants.down_left, info, &RealLocation {
+        Self::update_child(&quadrants.down, info, &RealLocation {
             position: RealPosition {
-                x: position_left,
+                x: center_left,
                 y: position_down,
             },
             size: RealSize {
-                width: size_left,
+                width: center_width,
                 height: size_down,
             },
             order: this_location.order,
         });
 
-        Self::update_child(&quadrants.down, info, &RealLocation {
+
+        // The corners are done last so that they are displayed on top of the edges,
+        // this is needed for corners which are bigger than the border size.
+        Self::update_child(&quadrants.up_left, info, &RealLocation {
             position: RealPosition {
-                x: center_left,
+                x: position_left,
+                y: position_up,
+            },
+            size: RealSize {
+                width: size_left,
+                height: size_up,
+            },
+            order: this_location.order,
+        });
+
+        Self::update_child(&quadrants.up_right, info, &RealLocation {
+            position: RealPosition {
+                x: position_right,
+                y: position_up,
+            },
+            size: RealSize {
+                width: size_right,
+                height: size_up,
+            },
+            order: this_location.order,
+        });
+
+        Self::update_child(&quadrants.down_left, info, &RealLocation {
+            position: RealPosition {
+                x: position_left,
                 y: position_down,
             },
             size: RealSize {
-                width: center_width,
+                width: size_left,
                 height: size_down,
             },
             order: this_location.order,
@@ -337,6 +349,7 @@ impl NodeLayout for BorderGrid {
             order: this_location.order,
         });
 
+        info.exit_node();
 
         self.center_size = None;
     }
@@ -45,11 +45,11 @@ impl Callbacks {
         self.after_removed.append(&mut other.after_removed);
     }
 
-    fn after_inserted<F>(&mut self, f: F) where F: FnOnce(Arc<SceneChanged>) + 'static {
+    pub(crate) fn after_inserted<F>(&mut self, f: F) where F: FnOnce(Arc<SceneChanged>) + 'static {
         self.after_inserted.push(Box::new(f));
     }
 
-    fn after_removed<F>(&mut self, f: F) where F: FnOnce() + 'static {
+    pub(crate) fn after_removed<F>(&mut self, f: F) where F: FnOnce() + 'static {
         self.after_removed.push(Box::new(f));
     }
 
@@ -236,10 +236,10 @@ pub struct Node {
 macro_rules! make_builder {
     ($name:ident, $builder_name:ident) => {
         #[doc = ::std::concat!(
-            "Builder for [`", ::std::stringify!($name), "`] which is used to create a [`Node`]\n.",
+            "Builder for [`", ::std::stringify!($name), "`] which is used to create a [`Node`].\n",
             "\n",
             "# Usage\n",
-            "```rust\n",
+            "```rust,ignore\n",
             ::std::stringify!($name), "::builder()\n",
             "    .foo()\n",
             "    .bar()\n",
@@ -358,6 +358,16 @@ macro_rules! base_methods {
                     BuilderChanged::Layout
                 },
             );
+
+            /// Attaches a [`NodeRef`](crate::NodeRef) to this node, which can be used
+            /// to query / update the node after it has been built.
+            pub fn node_ref(mut self, node_ref: &$crate::scene::NodeRef) -> Self {
+                self.state.lock().node_ref = Some(node_ref.clone());
+
+                node_ref.attach(&mut self.callbacks);
+
+                self
+            }
         }
     };
 }
@@ -437,6 +447,34 @@ macro_rules! location_methods {
                 },
             );
 
+            $crate::scene::builder::simple_method!(
+                /// Sets the [`origin`](Self::origin) to the [`Anchor`](crate::Anchor), and removes the [`offset`](Self::offset).
+                ///
+                /// Use [`anchor_margin`](Self::anchor_margin) to add space between the node and the parent's edges.
+                anchor,
+                anchor_signal,
+                |state, value: $crate::Anchor| {
+                    state.location.origin = value.origin();
+                    state.location.offset = Offset::default();
+
+                    let $var = state;
+                    $body
+                },
+            );
+
+            /// Sets the [`origin`](Self::origin) to the [`Anchor`](crate::Anchor), and sets the [`offset`](Self::offset)
+            /// to the margin, see [`Anchor::margin`](crate::Anchor::margin).
+            #[inline]
+            pub fn anchor_margin(self, anchor: $crate::Anchor, margin: Offset) -> Self {
+                self.anchor(anchor).offset(anchor.margin(margin))
+            }
+
+            /// Places the node in the center of the parent, this is the same as `anchor(Anchor::Center)`.
+            #[inline]
+            pub fn center(self) -> Self {
+                self.anchor($crate::Anchor::Center)
+            }
+
             $crate::scene::builder::simple_method!(
                 /// Specifies the order of which nodes are drawn on top of other nodes.
                 ///
@@ -2,7 +2,7 @@ use futures_signals::signal::{Signal, SignalExt};
 use futures_signals::signal_vec::{SignalVec, SignalVecExt};
 use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, children_methods};
 use crate::scene::{
-    NodeHandle, Location, Origin, Size, Offset, Percentage, Padding, SmallestSize,
+    NodeHandle, NodeRef, Location, Origin, Size, Offset, Percentage, Padding, SmallestSize,
     SmallestLength, RealLocation, NodeLayout, SceneLayoutInfo, SceneRenderInfo, RealSize,
     Order, internal_panic,
 };
@@ -36,6 +36,7 @@ struct Child {
 /// * [`Length::SmallestHeight`]: the sum of all the children's smallest height.
 pub struct Column {
     visible: bool,
+    node_ref: Option<NodeRef>,
     location: Location,
     children: Vec<NodeHandle>,
 
@@ -50,6 +51,7 @@ impl Column {
     fn new() -> Self {
         Self {
             visible: true,
+            node_ref: None,
             location: Location::default(),
             children: vec![],
 
@@ -118,7 +120,7 @@ children_methods!(Column, ColumnBuilder);
 impl NodeLayout for Column {
     #[inline]
     fn is_visible(&mut self) -> bool {
-        self.visible
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
     }
 
     fn smallest_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
@@ -139,6 +141,12 @@ impl NodeLayout for Column {
     fn update_layout<'a>(&mut self, _handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
         let mut this_location = self.location.children_location(parent, &smallest_size.real_size(), &info);
 
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(this_location);
+        }
+
+        info.enter_node("Column", &this_location);
+
         let empty_space = (this_location.size.height - self.min_height).max(0.0);
 
         let stretch_percentage = empty_space * (1.0 / self.ratio_sum);
@@ -174,6 +182,8 @@ impl NodeLayout for Column {
             this_location.move_down(child_location.size.height);
         }
 
+        info.exit_node();
+
         self.computed_children.clear();
         self.ratio_sum = 0.0;
         self.min_height = 0.0;
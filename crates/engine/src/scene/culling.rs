@@ -0,0 +1,316 @@
+use crate::util::macros::wgsl;
+use crate::resources::ResourceTracker;
+use crate::scene::mask::{StencilRanges, StencilPipelines};
+use crate::scene::sprite::{GPUSprite, GPUPalette, GPUTextureIndex};
+use crate::scene::{Prerender, ScenePrerender, Dispatch};
+
+
+static_assertions::const_assert_eq!(std::mem::size_of::<GPUSprite>(), SpriteCulling::SPRITE_WORDS as usize * 4);
+static_assertions::const_assert_eq!(std::mem::size_of::<GPUPalette>(), 4);
+static_assertions::const_assert_eq!(std::mem::size_of::<GPUTextureIndex>(), 4);
+
+
+/// A buffer which is recreated when it is too small, it is never shrunk.
+struct GrowBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: Option<wgpu::Buffer>,
+    tracker: Option<ResourceTracker>,
+}
+
+impl GrowBuffer {
+    #[inline]
+    fn new(label: &'static str, usage: wgpu::BufferUsages) -> Self {
+        Self {
+            label,
+            usage,
+            buffer: None,
+            tracker: None,
+        }
+    }
+
+    fn destroy_buffer(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            buffer.destroy();
+
+            if let Some(tracker) = &self.tracker {
+                tracker.remove_buffer(buffer.size());
+            }
+        }
+    }
+
+    fn reserve(&mut self, engine: &crate::EngineState, size: u64) -> &wgpu::Buffer {
+        if self.buffer.as_ref().map_or(true, |buffer| buffer.size() < size) {
+            self.destroy_buffer();
+
+            let size = size.next_power_of_two();
+
+            self.buffer = Some(engine.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size,
+                usage: self.usage,
+                mapped_at_creation: false,
+            }));
+
+            engine.resources.add_buffer(size);
+
+            self.tracker = Some(engine.resources.clone());
+        }
+
+        self.buffer.as_ref().unwrap()
+    }
+
+    #[inline]
+    fn get(&self) -> &wgpu::Buffer {
+        self.buffer.as_ref().expect("GrowBuffer is not reserved")
+    }
+}
+
+impl Drop for GrowBuffer {
+    #[inline]
+    fn drop(&mut self) {
+        self.destroy_buffer();
+    }
+}
+
+
+/// Removes the opaque sprites which are outside of the screen with a compute shader,
+/// and then draws the remaining sprites with `draw_indirect`, see [`EngineSettings::gpu_culling`](crate::EngineSettings::gpu_culling).
+///
+/// Transparent sprites must be drawn in order, so they aren't culled.
+pub(crate) struct SpriteCulling {
+    normal: wgpu::ComputePipeline,
+    palette: wgpu::ComputePipeline,
+
+    /// The normal and palette pipelines for [`SpritesheetBatch`](crate::scene::sprite::SpritesheetBatch),
+    /// this is `None` if sprite batching is disabled.
+    batched: Option<(wgpu::ComputePipeline, wgpu::ComputePipeline)>,
+}
+
+impl SpriteCulling {
+    /// Must be kept in sync with cull.wgsl
+    const WORKGROUP_SIZE: u32 = 64;
+    pub(crate) const SPRITE_WORDS: u32 = 21;
+
+    const DOWNLEVEL_FLAGS: wgpu::DownlevelFlags = wgpu::DownlevelFlags::COMPUTE_SHADERS
+        .union(wgpu::DownlevelFlags::INDIRECT_EXECUTION);
+
+    /// Adds the limits which are needed for culling to `limits`.
+    pub(crate) fn limits(limits: wgpu::Limits) -> wgpu::Limits {
+        wgpu::Limits {
+            max_storage_buffers_per_shader_stage: limits.max_storage_buffers_per_shader_stage.max(8),
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size.max(128 << 20),
+            max_compute_workgroup_size_x: limits.max_compute_workgroup_size_x.max(Self::WORKGROUP_SIZE),
+            max_compute_workgroup_size_y: limits.max_compute_workgroup_size_y.max(1),
+            max_compute_workgroup_size_z: limits.max_compute_workgroup_size_z.max(1),
+            max_compute_invocations_per_workgroup: limits.max_compute_invocations_per_workgroup.max(Self::WORKGROUP_SIZE),
+            max_compute_workgroups_per_dimension: limits.max_compute_workgroups_per_dimension.max(65535),
+            ..limits
+        }
+    }
+
+    /// Whether the adapter supports compute shaders and indirect draws, the GL backend usually doesn't.
+    pub(crate) fn is_supported(adapter: &wgpu::Adapter, limits: &wgpu::Limits) -> bool {
+        adapter.get_downlevel_capabilities().flags.contains(Self::DOWNLEVEL_FLAGS) &&
+        Self::limits(limits.clone()).check_limits(&adapter.limits())
+    }
+
+    pub(crate) fn new(engine: &crate::EngineState) -> Self {
+        Self {
+            normal: Self::pipeline(engine, wgsl!("spritesheet/cull.wgsl")),
+            palette: Self::pipeline(engine, wgsl!("spritesheet/cull.wgsl", "PALETTE")),
+            batched: if engine.sprite_batching {
+                Some((
+                    Self::pipeline(engine, wgsl!("spritesheet/cull.wgsl", "BATCHED")),
+                    Self::pipeline(engine, wgsl!("spritesheet/cull.wgsl", "BATCHED", "PALETTE")),
+                ))
+
+            } else {
+                None
+            },
+        }
+    }
+
+    fn get(&self, palette: bool, batched: bool) -> &wgpu::ComputePipeline {
+        let (normal, palette_pipeline) = if batched {
+            let (normal, palette) = self.batched.as_ref().expect("Sprite batching is disabled");
+            (normal, palette)
+
+        } else {
+            (&self.normal, &self.palette)
+        };
+
+        if palette {
+            palette_pipeline
+
+        } else {
+            normal
+        }
+    }
+
+    fn pipeline(engine: &crate::EngineState, shader: wgpu::ShaderModuleDescriptor<'static>) -> wgpu::ComputePipeline {
+        let module = engine.device.create_shader_module(shader);
+
+        engine.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Sprite Culling"),
+            layout: None,
+            module: &module,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: engine.pipeline_cache.native(),
+        })
+    }
+
+    #[inline]
+    fn workgroups(instances: u32) -> u32 {
+        instances.div_ceil(Self::WORKGROUP_SIZE)
+    }
+
+    /// Very large batches don't fit into a single dispatch, so they are drawn without culling.
+    pub(crate) fn can_cull(engine: &crate::EngineState, instances: u32) -> bool {
+        let limits = engine.device.limits();
+
+        instances > 0 &&
+        Self::workgroups(instances) <= limits.max_compute_workgroups_per_dimension &&
+        (instances as u64 * Self::SPRITE_WORDS as u64 * 4) <= limits.max_storage_buffer_binding_size as u64
+    }
+}
+
+
+/// The output of [`SpriteCulling`] for a batch of sprites, it is kept between frames.
+pub(crate) struct CulledInstances {
+    ranges: GrowBuffer,
+    args: GrowBuffer,
+    sprites: GrowBuffer,
+    palettes: GrowBuffer,
+    textures: GrowBuffer,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl CulledInstances {
+    /// The size of [`wgpu::util::DrawIndirectArgs`].
+    const ARGS_SIZE: u64 = 16;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            ranges: GrowBuffer::new("Sprite Culling Ranges", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST),
+            args: GrowBuffer::new("Sprite Culling Indirect Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST),
+            sprites: GrowBuffer::new("Sprite Culled Instance Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX),
+            palettes: GrowBuffer::new("Sprite Culled Palettes Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX),
+            textures: GrowBuffer::new("Sprite Culled Textures Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX),
+            bind_group: None,
+        }
+    }
+
+    fn binding(buffer: &wgpu::Buffer, size: u64) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer,
+            offset: 0,
+            size: wgpu::BufferSize::new(size),
+        })
+    }
+
+    /// Splits the draw into a separate indirect draw for each [`Stencil`](crate::scene::mask::Stencil), the same as
+    /// [`ScenePrerender::push_masked`], [`SpriteCulling::can_cull`] must be checked first.
+    ///
+    /// The `sprites`, `palettes`, and `textures` buffers must contain the instances of the draw, and they must have the `STORAGE` usage.
+    pub(crate) fn prerender<'a>(
+        &'a mut self,
+        engine: &crate::EngineState,
+        culling: &'a SpriteCulling,
+        draw: Prerender<'a>,
+        stencils: &StencilRanges,
+        pipelines: &'a StencilPipelines,
+        sprites: &wgpu::Buffer,
+        palettes: Option<&wgpu::Buffer>,
+        textures: Option<&wgpu::Buffer>,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        let ranges = stencils.ranges(draw.instances as usize).collect::<Vec<_>>();
+
+        let ranges_data = [draw.instances, ranges.len() as u32].into_iter()
+            .chain(ranges.iter().map(|(range, _)| range.start as u32))
+            .collect::<Vec<u32>>();
+
+        // The instance count is incremented by the compute shader
+        let args_data = ranges.iter()
+            .flat_map(|_| [draw.vertices, 0, 0, 0])
+            .collect::<Vec<u32>>();
+
+        let sprites_size = draw.instances as u64 * SpriteCulling::SPRITE_WORDS as u64 * 4;
+        let palettes_size = draw.instances as u64 * 4;
+        let textures_size = draw.instances as u64 * 4;
+
+        engine.queue.write_buffer(self.ranges.reserve(engine, (ranges_data.len() * 4) as u64), 0, bytemuck::cast_slice(&ranges_data));
+        engine.queue.write_buffer(self.args.reserve(engine, (args_data.len() * 4) as u64), 0, bytemuck::cast_slice(&args_data));
+
+        self.sprites.reserve(engine, sprites_size);
+
+        if palettes.is_some() {
+            self.palettes.reserve(engine, palettes_size);
+        }
+
+        if textures.is_some() {
+            self.textures.reserve(engine, textures_size);
+        }
+
+        let pipeline = culling.get(palettes.is_some(), textures.is_some());
+
+        // The input buffers can be recreated when they grow, so the bind group is recreated every frame
+        let mut entries = vec![
+            wgpu::BindGroupEntry { binding: 0, resource: self.ranges.get().as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: self.args.get().as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 2, resource: Self::binding(sprites, sprites_size) },
+            wgpu::BindGroupEntry { binding: 3, resource: Self::binding(self.sprites.get(), sprites_size) },
+        ];
+
+        if let Some(palettes) = palettes {
+            entries.push(wgpu::BindGroupEntry { binding: 4, resource: Self::binding(palettes, palettes_size) });
+            entries.push(wgpu::BindGroupEntry { binding: 5, resource: Self::binding(self.palettes.get(), palettes_size) });
+        }
+
+        if let Some(textures) = textures {
+            entries.push(wgpu::BindGroupEntry { binding: 6, resource: Self::binding(textures, textures_size) });
+            entries.push(wgpu::BindGroupEntry { binding: 7, resource: Self::binding(self.textures.get(), textures_size) });
+        }
+
+        self.bind_group = Some(engine.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Culling"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        }));
+
+        prerender.dispatches.push(Dispatch {
+            label: draw.label,
+            pipeline,
+            bind_groups: vec![self.bind_group.as_ref().unwrap()],
+            workgroups: SpriteCulling::workgroups(draw.instances),
+        });
+
+        let args = self.args.get();
+        let culled_sprites = self.sprites.get();
+        let culled_palettes = palettes.map(|_| self.palettes.get());
+        let culled_textures = textures.map(|_| self.textures.get());
+
+        for (index, (range, stencil)) in ranges.into_iter().enumerate() {
+            let start = range.start as u64;
+
+            // The culled sprites are at the start of the range, so the vertex buffers begin at the range
+            let slices = vec![
+                Some(culled_sprites.slice((start * SpriteCulling::SPRITE_WORDS as u64 * 4)..sprites_size)),
+                culled_palettes.map(|palettes| palettes.slice((start * 4)..palettes_size)),
+                culled_textures.map(|textures| textures.slice((start * 4)..textures_size)),
+            ];
+
+            prerender.push(Prerender {
+                first_instance: 0,
+                instances: range.len() as u32,
+                stencil,
+                pipeline: pipelines.get(draw.alpha, stencil),
+                slices,
+                indirect: Some((args, index as u64 * Self::ARGS_SIZE)),
+                ..draw.clone()
+            });
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use wgpu_helpers::VertexLayout;
+use bytemuck::{Pod, Zeroable};
+use futures_signals::signal::{Signal, SignalExt};
+
+use crate::util::macros::wgsl;
+use crate::util::builders;
+use crate::util::buffer::{Uniform, InstanceVec, InstanceVecOptions};
+use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, simple_method};
+use crate::scene::shape::{ShapeRenderer};
+use crate::scene::mask::{Stencil, StencilRanges};
+use crate::scene::pipeline_cache::{LazyPipelines};
+use crate::scene::{
+    NodeRef, Location, Padding, Origin, Offset, Size, ScreenSize, SmallestSize, SceneLayoutInfo,
+    SceneRenderInfo, RealLocation, NodeLayout, NodeHandle, SceneUniform, ScenePrerender,
+    Prerender, RealSize, Order, Percentage, ColorRgb,
+};
+
+
+/// The color of each corner of a [`Gradient`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GradientColors {
+    pub top_left: ColorRgb,
+    pub top_right: ColorRgb,
+    pub bottom_left: ColorRgb,
+    pub bottom_right: ColorRgb,
+}
+
+impl GradientColors {
+    /// Gradient from the top to the bottom.
+    #[inline]
+    pub fn vertical(top: ColorRgb, bottom: ColorRgb) -> Self {
+        Self {
+            top_left: top,
+            top_right: top,
+            bottom_left: bottom,
+            bottom_right: bottom,
+        }
+    }
+
+    /// Gradient from the left to the right.
+    #[inline]
+    pub fn horizontal(left: ColorRgb, right: ColorRgb) -> Self {
+        Self {
+            top_left: left,
+            top_right: right,
+            bottom_left: left,
+            bottom_right: right,
+        }
+    }
+}
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, VertexLayout, PartialEq)]
+#[layout(step_mode = Instance)]
+pub(crate) struct GPUGradient {
+    pub(crate) position: [f32; 2],
+    pub(crate) size: [f32; 2],
+    pub(crate) order: f32,
+    pub(crate) alpha: f32,
+    pub(crate) top_left: [f32; 3],
+    pub(crate) top_right: [f32; 3],
+    pub(crate) bottom_left: [f32; 3],
+    pub(crate) bottom_right: [f32; 3],
+}
+
+impl Default for GPUGradient {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            size: [0.0, 0.0],
+            order: 1.0,
+            alpha: 1.0,
+            top_left: [0.0, 0.0, 0.0],
+            top_right: [0.0, 0.0, 0.0],
+            bottom_left: [0.0, 0.0, 0.0],
+            bottom_right: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl GPUGradient {
+    fn set_colors(&mut self, colors: GradientColors) {
+        self.top_left = colors.top_left.to_gpu();
+        self.top_right = colors.top_right.to_gpu();
+        self.bottom_left = colors.bottom_left.to_gpu();
+        self.bottom_right = colors.bottom_right.to_gpu();
+    }
+
+    fn update(&mut self, location: &RealLocation) {
+        if location.order < 1.0 {
+            panic!("Order cannot be lower than 1.0");
+        }
+
+        let location = location.convert_to_wgpu_coordinates();
+
+        // The origin point is in the lower-left corner, the same as sprites.
+        self.position = [location.position.x, location.position.y - location.size.height];
+        self.size = [location.size.width, location.size.height];
+        self.order = location.order;
+    }
+}
+
+
+/// Displays a rectangle where the color smoothly changes between the corners,
+/// this is useful for backgrounds.
+///
+/// # Sizing
+///
+/// * [`Length::SmallestWidth`](crate::Length::SmallestWidth): it is an error to use `SmallestWidth`.
+///
+/// * [`Length::SmallestHeight`](crate::Length::SmallestHeight): it is an error to use `SmallestHeight`.
+pub struct Gradient {
+    visible: bool,
+    node_ref: Option<NodeRef>,
+    location: Location,
+
+    /// Whether any of the properties changed which require a re-render.
+    render_changed: bool,
+
+    /// Whether it needs to recalculate the location.
+    location_changed: bool,
+
+    parent_location: Option<RealLocation>,
+    smallest_size: Option<RealSize>,
+    max_order: f32,
+
+    gpu_index: usize,
+    gpu_gradient: GPUGradient,
+}
+
+impl Gradient {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            visible: true,
+            node_ref: None,
+            location: Location::default(),
+
+            render_changed: false,
+            location_changed: false,
+
+            parent_location: None,
+            smallest_size: None,
+            max_order: 1.0,
+
+            gpu_index: 0,
+            gpu_gradient: GPUGradient::default(),
+        }
+    }
+
+    fn location_changed(&mut self) {
+        self.location_changed = true;
+        self.render_changed();
+    }
+
+    fn render_changed(&mut self) {
+        self.render_changed = true;
+    }
+
+    fn update_gpu(&mut self, screen: &ScreenSize) -> RealLocation {
+        let parent = self.parent_location.as_ref().unwrap();
+        let smallest = self.smallest_size.as_ref().unwrap();
+
+        let location = self.location.children_location_explicit(parent, smallest, screen, self.max_order);
+
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(location);
+        }
+
+        self.gpu_gradient.update(&location);
+
+        location
+    }
+}
+
+make_builder!(Gradient, GradientBuilder);
+base_methods!(Gradient, GradientBuilder);
+
+location_methods!(Gradient, GradientBuilder, |state| {
+    state.location_changed();
+    BuilderChanged::Render
+});
+
+impl GradientBuilder {
+    simple_method!(
+        /// Sets the color of each corner, see [`GradientColors`].
+        ///
+        /// Defaults to black.
+        colors,
+        colors_signal,
+        |state, value: GradientColors| {
+            state.gpu_gradient.set_colors(value);
+            state.render_changed();
+            BuilderChanged::Render
+        },
+    );
+
+    simple_method!(
+        /// Sets the alpha for the gradient.
+        ///
+        /// 1.0 means fully opaque, 0.0 means fully transparent.
+        alpha,
+        alpha_signal,
+        |state, value: Percentage| {
+            let changed = ShapeRenderer::alpha_changed(state.gpu_gradient.alpha, value);
+
+            state.gpu_gradient.alpha = value;
+
+            if let BuilderChanged::Render = changed {
+                state.render_changed();
+            }
+
+            changed
+        },
+    );
+}
+
+impl NodeLayout for Gradient {
+    #[inline]
+    fn is_visible(&mut self) -> bool {
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
+    }
+
+    fn smallest_size<'a>(&mut self, _parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
+        self.location.size.smallest_size(&info.screen_size)
+    }
+
+    fn update_layout<'a>(&mut self, handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
+        self.render_changed = false;
+        self.location_changed = false;
+
+        if self.gpu_gradient.alpha != 0.0 {
+            self.parent_location = Some(*parent);
+            self.smallest_size = Some(smallest_size.real_size());
+            self.max_order = info.renderer.get_max_order();
+
+            let location = self.update_gpu(&info.screen_size);
+
+            info.enter_node("Gradient", &location);
+            info.exit_node();
+
+            info.renderer.set_max_order(self.gpu_gradient.order);
+
+            self.gpu_index = info.renderer.gradient.push(self.gpu_gradient, info.renderer.stencil);
+
+            info.rendered_nodes.push(handle.clone());
+        }
+    }
+
+    fn render<'a>(&mut self, info: &mut SceneRenderInfo<'a>) {
+        assert_ne!(self.gpu_gradient.alpha, 0.0);
+
+        if self.render_changed {
+            self.render_changed = false;
+
+            if self.location_changed {
+                self.location_changed = false;
+
+                self.update_gpu(&info.screen_size);
+            }
+
+            info.renderer.gradient.update(self.gpu_index, self.gpu_gradient);
+        }
+    }
+}
+
+
+/// Renders every [`Gradient`].
+///
+/// The shader and pipelines are compiled lazily when the first gradient is rendered.
+pub(crate) struct GradientRenderer {
+    pipelines: LazyPipelines,
+    opaque: InstanceVec<GPUGradient>,
+    opaque_stencils: StencilRanges,
+    alpha: InstanceVec<GPUGradient>,
+    alpha_stencils: StencilRanges,
+}
+
+impl GradientRenderer {
+    #[inline]
+    pub(crate) fn new(engine: &crate::EngineState, scene_uniform: &mut Uniform<SceneUniform>) -> Self {
+        let scene_uniform_layout = Uniform::bind_group_layout(scene_uniform, engine);
+
+        Self {
+            pipelines: LazyPipelines::new(engine, "Gradient", &[scene_uniform_layout], wgsl!("gradient.wgsl")),
+            opaque: InstanceVec::new(),
+            opaque_stencils: StencilRanges::new(),
+            alpha: InstanceVec::new(),
+            alpha_stencils: StencilRanges::new(),
+        }
+    }
+
+    fn instances(&mut self, gradient: &GPUGradient) -> &mut InstanceVec<GPUGradient> {
+        if gradient.alpha == 1.0 {
+            &mut self.opaque
+
+        } else {
+            &mut self.alpha
+        }
+    }
+
+    pub(crate) fn push(&mut self, gradient: GPUGradient, stencil: Stencil) -> usize {
+        let (instances, stencils) = if gradient.alpha == 1.0 {
+            (&mut self.opaque, &mut self.opaque_stencils)
+
+        } else {
+            (&mut self.alpha, &mut self.alpha_stencils)
+        };
+
+        let len = instances.len();
+        stencils.push(len, stencil);
+        instances.push(gradient);
+        len
+    }
+
+    pub(crate) fn update(&mut self, index: usize, gradient: GPUGradient) {
+        let instances = self.instances(&gradient);
+
+        if instances[index] != gradient {
+            instances[index] = gradient;
+        }
+    }
+
+    fn pipeline<'a, 'c>(layout: &'a wgpu::PipelineLayout, shader: &'c wgpu::ShaderModule) -> builders::Pipeline<'a, 'static, 'c> {
+        builders::Pipeline::builder()
+            .label("Gradient")
+            .shader(shader)
+            .layout(layout)
+            .vertex_buffers(&[GPUGradient::LAYOUT])
+            .topology(wgpu::PrimitiveTopology::TriangleStrip)
+            .strip_index_format(wgpu::IndexFormat::Uint32)
+    }
+
+    fn init(&mut self, engine: &crate::EngineState, masked: bool) {
+        self.pipelines.init(engine, Self::pipeline);
+
+        if masked {
+            self.pipelines.init_masked(engine, Self::pipeline);
+        }
+    }
+
+    /// Compiles every pipeline, see [`Engine::warmup`](crate::Engine::warmup).
+    #[inline]
+    pub(crate) fn warmup(&mut self, engine: &crate::EngineState) {
+        self.init(engine, true);
+    }
+
+    #[inline]
+    pub(crate) fn before_layout(&mut self) {
+        self.opaque.clear();
+        self.opaque_stencils.clear();
+        self.alpha.clear();
+        self.alpha_stencils.clear();
+    }
+
+    #[inline]
+    pub(crate) fn before_render(&mut self) {}
+
+    pub(crate) fn prerender<'a>(
+        &'a mut self,
+        engine: &crate::EngineState,
+        scene_uniform: &'a wgpu::BindGroup,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        if self.opaque.is_empty() && self.alpha.is_empty() && !self.pipelines.is_initialized() {
+            return;
+        }
+
+        self.init(engine, self.opaque_stencils.is_masked() || self.alpha_stencils.is_masked());
+
+        let pipelines = self.pipelines.get();
+
+        let opaque_instances = self.opaque.len() as u32;
+        let alpha_instances = self.alpha.len() as u32;
+
+        tracing::trace!(opaque = opaque_instances, alpha = alpha_instances, "Gradient");
+
+        prerender.push_masked(Prerender {
+            label: "Gradient",
+            alpha: false,
+            vertices: 4,
+            first_instance: 0,
+            instances: opaque_instances,
+            stencil: Stencil::None,
+            pipeline: pipelines.get(false, Stencil::None),
+            bind_groups: vec![scene_uniform],
+            slices: vec![self.opaque.update_buffer(engine, &InstanceVecOptions {
+                label: Some("Gradient Instance Buffer"),
+            })],
+            indirect: None,
+        }, &self.opaque_stencils, pipelines);
+
+        prerender.push_masked(Prerender {
+            label: "Gradient",
+            alpha: true,
+            vertices: 4,
+            first_instance: 0,
+            instances: alpha_instances,
+            stencil: Stencil::None,
+            pipeline: pipelines.get(true, Stencil::None),
+            bind_groups: vec![scene_uniform],
+            slices: vec![self.alpha.update_buffer(engine, &InstanceVecOptions {
+                label: Some("Gradient Instance Buffer"),
+            })],
+            indirect: None,
+        }, &self.alpha_stencils, pipelines);
+    }
+}
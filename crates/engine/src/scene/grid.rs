@@ -2,7 +2,7 @@ use futures_signals::signal::{Signal, SignalExt};
 use futures_signals::signal_vec::{SignalVec, SignalVecExt};
 use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, simple_method, children_methods};
 use crate::scene::{
-    NodeHandle, Location, Origin, Size, Offset, Padding, Length, SmallestSize, SmallestLength,
+    NodeHandle, NodeRef, Location, Origin, Size, Offset, Padding, Length, SmallestSize, SmallestLength,
     RealLocation, NodeLayout, SceneLayoutInfo, SceneRenderInfo, ScreenSize, RealSize, Order,
 };
 
@@ -43,6 +43,7 @@ impl GridSize {
 /// * [`Length::SmallestHeight`]: the sum of the height of all the children (laid out on multiple rows).
 pub struct Grid {
     visible: bool,
+    node_ref: Option<NodeRef>,
     location: Location,
     children: Vec<NodeHandle>,
 
@@ -57,6 +58,7 @@ impl Grid {
     fn new() -> Self {
         Self {
             visible: true,
+            node_ref: None,
             location: Location::default(),
             children: vec![],
 
@@ -140,7 +142,7 @@ impl GridBuilder {
 impl NodeLayout for Grid {
     #[inline]
     fn is_visible(&mut self) -> bool {
-        self.visible
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
     }
 
     fn smallest_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
@@ -161,6 +163,12 @@ impl NodeLayout for Grid {
     fn update_layout<'a>(&mut self, _handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
         let this_location = self.location.children_location(parent, &smallest_size.real_size(), &info);
 
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(this_location);
+        }
+
+        info.enter_node("Grid", &this_location);
+
         let max_width = this_location.size.width;
 
         let mut width = 0.0;
@@ -188,6 +196,8 @@ impl NodeLayout for Grid {
             }
         }
 
+        info.exit_node();
+
         self.computed_grid_size = RealSize::zero();
     }
 
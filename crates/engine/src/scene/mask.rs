@@ -0,0 +1,357 @@
+use std::ops::Range;
+use std::sync::OnceLock;
+use futures_signals::signal::{Signal, SignalExt};
+use futures_signals::signal_vec::{SignalVec, SignalVecExt};
+use crate::util::builders;
+use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, children_methods};
+use crate::scene::{
+    NodeHandle, NodeRef, Location, Origin, Size, Offset, Padding, SmallestSize, Order,
+    RealLocation, NodeLayout, SceneLayoutInfo, SceneRenderInfo, RealSize,
+};
+
+
+/// How an instance interacts with the stencil buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stencil {
+    /// The instance is not inside of a [`Mask`].
+    None,
+
+    /// The instance is the shape of a [`Mask`], it writes the id into the
+    /// stencil buffer instead of being displayed.
+    Write(u8),
+
+    /// The instance is only displayed where the stencil buffer contains the id.
+    Test(u8),
+}
+
+impl Stencil {
+    #[inline]
+    pub(crate) fn reference(&self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Write(id) => *id as u32,
+            Self::Test(id) => *id as u32,
+        }
+    }
+}
+
+
+/// Records which instances use which [`Stencil`].
+///
+/// Instances are pushed in layout order, and a [`Mask`] lays out all of its
+/// children together, so the instances for each [`Stencil`] are always contiguous.
+#[derive(Debug, Clone)]
+pub(crate) struct StencilRanges {
+    starts: Vec<(usize, Stencil)>,
+}
+
+impl StencilRanges {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self { starts: vec![] }
+    }
+
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.starts.clear();
+    }
+
+    /// Must be called before pushing the instance at `index`.
+    #[inline]
+    pub(crate) fn push(&mut self, index: usize, stencil: Stencil) {
+        if self.starts.last().map(|(_, last)| *last) != Some(stencil) {
+            self.starts.push((index, stencil));
+        }
+    }
+
+    /// Whether any instance is inside of a [`Mask`].
+    #[inline]
+    pub(crate) fn is_masked(&self) -> bool {
+        self.starts.iter().any(|(_, stencil)| *stencil != Stencil::None)
+    }
+
+    /// Returns the contiguous ranges of instances, `len` is the total number of instances.
+    pub(crate) fn ranges(&self, len: usize) -> impl Iterator<Item = (Range<usize>, Stencil)> + '_ {
+        let empty = if self.starts.is_empty() {
+            Some((0..len, Stencil::None))
+
+        } else {
+            None
+        };
+
+        let ranges = self.starts.iter().enumerate().map(move |(index, (start, stencil))| {
+            let end = self.starts.get(index + 1).map_or(len, |(end, _)| *end);
+            (*start..end, *stencil)
+        });
+
+        empty.into_iter().chain(ranges)
+    }
+}
+
+
+struct MaskedPipelines {
+    write: wgpu::RenderPipeline,
+    opaque_test: wgpu::RenderPipeline,
+    alpha_test: wgpu::RenderPipeline,
+}
+
+/// The opaque and alpha pipelines for a renderer, including the pipelines for [`Mask`].
+///
+/// The [`Mask`] pipelines are only compiled when a [`Mask`] is used.
+pub(crate) struct StencilPipelines {
+    shader: wgpu::ShaderModule,
+    opaque: wgpu::RenderPipeline,
+    alpha: wgpu::RenderPipeline,
+    masked: OnceLock<MaskedPipelines>,
+}
+
+impl StencilPipelines {
+    /// The `base` function creates the pipeline builder which is shared by all of the pipelines.
+    pub(crate) fn new<'a, 'b, F>(engine: &crate::EngineState, shader: wgpu::ShaderModuleDescriptor<'static>, base: F) -> Self
+        where F: Fn(&wgpu::ShaderModule) -> builders::Pipeline<'a, 'b, '_> {
+
+        let shader = engine.device.create_shader_module(shader);
+
+        let opaque = base(&shader)
+            .build(engine);
+
+        let alpha = base(&shader)
+            .depth_write(false)
+            .blend_state(wgpu::BlendState::ALPHA_BLENDING)
+            .build(engine);
+
+        Self { shader, opaque, alpha, masked: OnceLock::new() }
+    }
+
+    /// Compiles the [`Mask`] pipelines, this does nothing if they're already compiled.
+    pub(crate) fn init_masked<'a, 'b, F>(&self, engine: &crate::EngineState, base: F)
+        where F: Fn(&wgpu::ShaderModule) -> builders::Pipeline<'a, 'b, '_> {
+
+        self.masked.get_or_init(|| {
+            let write_face = wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Always,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Replace,
+                pass_op: wgpu::StencilOperation::Replace,
+            };
+
+            let test_face = wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Equal,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Keep,
+            };
+
+            let write_stencil = wgpu::StencilState {
+                front: write_face,
+                back: write_face,
+                read_mask: 0xFF,
+                write_mask: 0xFF,
+            };
+
+            let test_stencil = wgpu::StencilState {
+                front: test_face,
+                back: test_face,
+                read_mask: 0xFF,
+                write_mask: 0x00,
+            };
+
+            let write = base(&self.shader)
+                .depth_write(false)
+                .color_write(false)
+                .stencil(write_stencil.clone())
+                .build(engine);
+
+            let opaque_test = base(&self.shader)
+                .stencil(test_stencil.clone())
+                .build(engine);
+
+            let alpha_test = base(&self.shader)
+                .depth_write(false)
+                .blend_state(wgpu::BlendState::ALPHA_BLENDING)
+                .stencil(test_stencil)
+                .build(engine);
+
+            MaskedPipelines { write, opaque_test, alpha_test }
+        });
+    }
+
+    /// Returns the pipeline for the stencil, [`init_masked`](StencilPipelines::init_masked)
+    /// must be called first if the stencil is not [`Stencil::None`].
+    pub(crate) fn get(&self, alpha: bool, stencil: Stencil) -> &wgpu::RenderPipeline {
+        match stencil {
+            Stencil::None => if alpha { &self.alpha } else { &self.opaque },
+
+            stencil => {
+                let masked = self.masked.get().expect("StencilPipelines masked pipelines are not initialized");
+
+                match stencil {
+                    Stencil::Write(_) => &masked.write,
+                    _ => if alpha { &masked.alpha_test } else { &masked.opaque_test },
+                }
+            },
+        }
+    }
+}
+
+
+struct Child {
+    size: SmallestSize,
+    handle: NodeHandle,
+}
+
+
+/// Clips its children to the shape of its first child.
+///
+/// The first visible child is not displayed, instead it is used as the shape of the mask.
+/// The rest of the children are only displayed where the first child is not fully transparent.
+///
+/// This can be used with a circular [`Sprite`](crate::Sprite) to make circular windows.
+///
+/// Masks cannot be nested, and overlapping masks clip each other. If a Mask is nested
+/// (or there are more than 255 Masks) then a warning is logged and its children are not clipped.
+///
+/// # Layout
+///
+/// The children are all displayed on the same position as the mask, the same as [`Stack`](crate::Stack).
+///
+/// # Sizing
+///
+/// * [`Length::SmallestWidth`]: the maximum of all the children's smallest width.
+///
+/// * [`Length::SmallestHeight`]: the maximum of all the children's smallest height.
+pub struct Mask {
+    visible: bool,
+    node_ref: Option<NodeRef>,
+    location: Location,
+    children: Vec<NodeHandle>,
+
+    computed_children: Vec<Child>,
+}
+
+impl Mask {
+    /// The stencil buffer has 8 bits and 0 is used for unmasked instances.
+    const MAX_MASKS: u8 = u8::MAX;
+
+    #[inline]
+    fn new() -> Self {
+        Self {
+            visible: true,
+            node_ref: None,
+            location: Location::default(),
+            children: vec![],
+
+            computed_children: vec![],
+        }
+    }
+
+    fn children_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> RealSize {
+        let mut min_size = RealSize {
+            width: 0.0,
+            height: 0.0,
+        };
+
+        self.computed_children.reserve(self.children.len());
+
+        for child in self.children.iter() {
+            let mut lock = child.lock();
+
+            if lock.is_visible() {
+                let size = lock.smallest_size(parent, info);
+
+                let real_size = size.real_size();
+
+                min_size.width = min_size.width.max(real_size.width);
+                min_size.height = min_size.height.max(real_size.height);
+
+                self.computed_children.push(Child {
+                    size,
+                    handle: child.clone(),
+                });
+            }
+        }
+
+        min_size
+    }
+}
+
+make_builder!(Mask, MaskBuilder);
+base_methods!(Mask, MaskBuilder);
+location_methods!(Mask, MaskBuilder);
+children_methods!(Mask, MaskBuilder);
+
+impl NodeLayout for Mask {
+    #[inline]
+    fn is_visible(&mut self) -> bool {
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
+    }
+
+    fn smallest_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
+        let smallest_size = self.location.size.smallest_size(&info.screen_size).parent_to_smallest(parent);
+
+        let padding = self.location.padding.to_screen(parent, &smallest_size, &info.screen_size);
+
+        smallest_size.with_padding(parent, padding, |parent| {
+            self.children_size(&parent, info)
+        })
+    }
+
+    fn update_layout<'a>(&mut self, _handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
+        let this_location = self.location.children_location(parent, &smallest_size.real_size(), &info);
+
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(this_location);
+        }
+
+        info.enter_node("Mask", &this_location);
+
+        let clip = if info.renderer.stencil != Stencil::None {
+            tracing::warn!("Mask cannot be nested, its children will not be clipped");
+            false
+
+        } else if info.renderer.masks >= Self::MAX_MASKS {
+            tracing::warn!(max = Self::MAX_MASKS, "Too many Masks, its children will not be clipped");
+            false
+
+        } else {
+            true
+        };
+
+        if !clip {
+            // The first child is the shape of the mask, so it is never displayed
+            for child in self.computed_children.iter().skip(1) {
+                let mut lock = child.handle.lock();
+                lock.update_layout(&child.handle, &this_location, &child.size, info);
+            }
+
+            info.exit_node();
+
+            self.computed_children.clear();
+            return;
+        }
+
+        info.renderer.masks += 1;
+
+        let id = info.renderer.masks;
+
+        for (index, child) in self.computed_children.iter().enumerate() {
+            info.renderer.stencil = if index == 0 {
+                Stencil::Write(id)
+
+            } else {
+                Stencil::Test(id)
+            };
+
+            let mut lock = child.handle.lock();
+            lock.update_layout(&child.handle, &this_location, &child.size, info);
+        }
+
+        info.renderer.stencil = Stencil::None;
+
+        info.exit_node();
+
+        self.computed_children.clear();
+    }
+
+    fn render<'a>(&mut self, _info: &mut SceneRenderInfo<'a>) {}
+}
@@ -0,0 +1,198 @@
+use crate::util::{Arc, Lock};
+use crate::scene::{RealLocation, SceneChanged};
+use crate::scene::builder::{Callbacks};
+
+
+struct NodeRefState {
+    location: Option<RealLocation>,
+    visible: bool,
+    root: Option<Arc<SceneChanged>>,
+
+    /// How many inserted Nodes use this NodeRef, when a Node is replaced
+    /// the new Node is inserted before the old Node is removed.
+    inserted: usize,
+}
+
+
+/// Reference to a Node which can be used to imperatively query / update the Node.
+///
+/// It is attached to a Node by using the `node_ref` method on the Node's builder.
+///
+/// # Usage
+/// ```rust
+/// use rusted_battalions_engine::{NodeRef, Sprite};
+///
+/// let node_ref = NodeRef::new();
+///
+/// let node = Sprite::builder()
+///     .node_ref(&node_ref)
+///     .build();
+///
+/// // The Node hasn't been laid out yet
+/// assert!(node_ref.location().is_none());
+/// ```
+#[derive(Clone)]
+pub struct NodeRef {
+    state: Lock<NodeRefState>,
+}
+
+impl NodeRef {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            state: Lock::new(NodeRefState {
+                location: None,
+                visible: true,
+                root: None,
+                inserted: 0,
+            }),
+        }
+    }
+
+    /// Returns the location of the Node from the most recent layout.
+    ///
+    /// Returns `None` if the Node hasn't been laid out yet, or if it was removed from the scene.
+    #[inline]
+    pub fn location(&self) -> Option<RealLocation> {
+        self.state.lock().location
+    }
+
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        self.state.lock().visible
+    }
+
+    /// Shows or hides the Node.
+    ///
+    /// The Node is only visible if both this and the Node's `visible` method are `true`.
+    pub fn set_visible(&self, visible: bool) {
+        let mut lock = self.state.lock();
+
+        if lock.visible != visible {
+            lock.visible = visible;
+
+            if let Some(root) = &lock.root {
+                root.trigger_layout_change();
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_location(&self, location: RealLocation) {
+        self.state.lock().location = Some(location);
+    }
+
+    /// Updates the root when the Node is inserted into the scene, and clears it when the Node is removed.
+    pub(crate) fn attach(&self, callbacks: &mut Callbacks) {
+        let inserted = Lock::new(false);
+
+        callbacks.after_inserted({
+            let node_ref = self.clone();
+            let inserted = inserted.clone();
+
+            move |root| {
+                *inserted.lock() = true;
+
+                let mut lock = node_ref.state.lock();
+                lock.inserted += 1;
+                lock.root = Some(root);
+            }
+        });
+
+        callbacks.after_removed({
+            let node_ref = self.clone();
+
+            move || {
+                // The Node was dropped without being inserted
+                if !*inserted.lock() {
+                    return;
+                }
+
+                let mut lock = node_ref.state.lock();
+                lock.inserted -= 1;
+
+                if lock.inserted == 0 {
+                    lock.root = None;
+                    lock.location = None;
+                }
+            }
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::future::Future;
+    use crate::Spawner;
+    use crate::util::Arc;
+    use crate::scene::{RealLocation, RealPosition, RealSize, SceneChanged};
+    use crate::scene::builder::{Callbacks};
+    use super::NodeRef;
+
+    struct NoopSpawner;
+
+    impl Spawner for NoopSpawner {
+        fn spawn_local(&self, _future: Pin<Box<dyn Future<Output = ()> + 'static>>) {}
+    }
+
+    const LOCATION: RealLocation = RealLocation {
+        position: RealPosition { x: 0.0, y: 0.0 },
+        size: RealSize { width: 1.0, height: 1.0 },
+        order: 1.0,
+    };
+
+    fn insert(node_ref: &NodeRef, root: &Arc<SceneChanged>) -> Callbacks {
+        let mut callbacks = Callbacks::new();
+        node_ref.attach(&mut callbacks);
+        callbacks.trigger_after_inserted(root);
+        callbacks
+    }
+
+    #[test]
+    fn node_ref_removed() {
+        let root = SceneChanged::new(std::sync::Arc::new(NoopSpawner));
+        let node_ref = NodeRef::new();
+
+        let old = insert(&node_ref, &root);
+        node_ref.set_location(LOCATION);
+
+        // Replacing the Node inserts the new Node before removing the old Node
+        let new = insert(&node_ref, &root);
+        drop(old);
+
+        assert!(node_ref.location().is_some());
+
+        root.replace_layout_changed();
+        node_ref.set_visible(false);
+        assert!(root.replace_layout_changed());
+
+        drop(new);
+
+        assert!(node_ref.location().is_none());
+
+        // It isn't in the scene anymore, so it doesn't relayout the scene
+        node_ref.set_visible(true);
+        assert!(!root.replace_layout_changed());
+    }
+
+    #[test]
+    fn node_ref_never_inserted() {
+        let root = SceneChanged::new(std::sync::Arc::new(NoopSpawner));
+        let node_ref = NodeRef::new();
+
+        let mut callbacks = Callbacks::new();
+        node_ref.attach(&mut callbacks);
+        drop(callbacks);
+
+        let inserted = insert(&node_ref, &root);
+        node_ref.set_location(LOCATION);
+
+        assert!(node_ref.location().is_some());
+
+        drop(inserted);
+
+        assert!(node_ref.location().is_none());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use crate::util::{Arc, Lock};
+use crate::util::builders;
+use crate::scene::mask::{StencilPipelines};
+
+
+/// The pipelines are shared when both the shader source and the layout are the same.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    /// Identifies the bind group layouts and vertex buffers of the pipeline.
+    layout: &'static str,
+    source: String,
+}
+
+impl PipelineKey {
+    fn new(layout: &'static str, shader: &wgpu::ShaderModuleDescriptor<'static>) -> Self {
+        let source = match &shader.source {
+            wgpu::ShaderSource::Wgsl(source) => source.to_string(),
+            _ => panic!("PipelineCache only supports WGSL shaders"),
+        };
+
+        Self { layout, source }
+    }
+}
+
+
+/// Compiles the pipelines for each shader and layout once, so that renderers
+/// (including [`PipelineHandle`](crate::PipelineHandle)) share the same pipelines.
+///
+/// On native Vulkan it also uses a [`wgpu::PipelineCache`], which can be saved with
+/// [`Engine::pipeline_cache_data`](crate::Engine::pipeline_cache_data) and loaded with
+/// [`EngineSettings::pipeline_cache`](crate::EngineSettings::pipeline_cache), so that the
+/// driver doesn't need to recompile the shaders when the game is started again.
+pub(crate) struct PipelineCache {
+    native: Option<wgpu::PipelineCache>,
+
+    /// See [`wgpu::util::pipeline_cache_key`].
+    native_key: Option<String>,
+
+    pipelines: Lock<HashMap<PipelineKey, Arc<StencilPipelines>>>,
+}
+
+impl PipelineCache {
+    /// Features which are needed for the native pipeline cache.
+    pub(crate) const FEATURES: wgpu::Features = wgpu::Features::PIPELINE_CACHE;
+
+    /// The `data` is from [`PipelineCache::data`], it is ignored if it was saved on a different GPU or driver.
+    pub(crate) fn new(device: &wgpu::Device, adapter: &wgpu::AdapterInfo, data: Option<&[u8]>) -> Self {
+        let native_key = if device.features().contains(Self::FEATURES) {
+            wgpu::util::pipeline_cache_key(adapter)
+
+        } else {
+            None
+        };
+
+        let native = native_key.as_ref().map(|key| {
+            let data = data.and_then(|data| Self::strip_key(data, key));
+
+            tracing::debug!(loaded = data.is_some(), "Native pipeline cache");
+
+            // SAFETY: `strip_key` checks that the data was created on an adapter with the same `pipeline_cache_key`
+            unsafe {
+                device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                    label: Some("Pipeline Cache"),
+                    data,
+                    // If the driver rejects the data then it uses an empty cache
+                    fallback: true,
+                })
+            }
+        });
+
+        Self {
+            native,
+            native_key,
+            pipelines: Lock::new(HashMap::new()),
+        }
+    }
+
+    /// The data starts with the length of the key followed by the key.
+    fn add_key(key: &str, data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(4 + key.len() + data.len());
+        output.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        output.extend_from_slice(key.as_bytes());
+        output.extend_from_slice(data);
+        output
+    }
+
+    /// Returns `None` if the data wasn't created with the same key.
+    fn strip_key<'a>(data: &'a [u8], key: &str) -> Option<&'a [u8]> {
+        let (len, data) = data.split_first_chunk::<4>()?;
+        let len = u32::from_le_bytes(*len) as usize;
+
+        if data.get(..len)? == key.as_bytes() {
+            Some(&data[len..])
+
+        } else {
+            None
+        }
+    }
+
+    /// Returns the native pipeline cache, this is `None` if the backend doesn't support it.
+    #[inline]
+    pub(crate) fn native(&self) -> Option<&wgpu::PipelineCache> {
+        self.native.as_ref()
+    }
+
+    /// Returns the serialized native pipeline cache, this is `None` if the backend doesn't support it.
+    pub(crate) fn data(&self) -> Option<Vec<u8>> {
+        let key = self.native_key.as_ref()?;
+        let data = self.native.as_ref()?.get_data()?;
+        Some(Self::add_key(key, &data))
+    }
+
+    /// The compiled pipelines are kept until the Engine is dropped, so unloading and
+    /// loading a [`PipelineHandle`](crate::PipelineHandle) doesn't recompile it.
+    fn get_or_compile<F>(&self, key: &PipelineKey, compile: F) -> Arc<StencilPipelines>
+        where F: FnOnce() -> StencilPipelines {
+
+        let mut pipelines = self.pipelines.lock();
+
+        if let Some(pipelines) = pipelines.get(key) {
+            tracing::trace!(layout = key.layout, "Pipeline cache hit");
+            return pipelines.clone();
+        }
+
+        let compiled = Arc::new(compile());
+        pipelines.insert(key.clone(), compiled.clone());
+        compiled
+    }
+}
+
+
+/// The pipelines for a renderer, they are compiled with the [`PipelineCache`] when they are first used.
+pub(crate) struct LazyPipelines {
+    key: PipelineKey,
+    layout: wgpu::PipelineLayout,
+    shader: Option<wgpu::ShaderModuleDescriptor<'static>>,
+    pipelines: Option<Arc<StencilPipelines>>,
+}
+
+impl LazyPipelines {
+    /// Renderers with the same `label` must use the same bind group layouts and vertex buffers,
+    /// because the label is used as the layout for the [`PipelineCache`].
+    pub(crate) fn new(
+        engine: &crate::EngineState,
+        label: &'static str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        shader: wgpu::ShaderModuleDescriptor<'static>,
+    ) -> Self {
+        let layout = engine.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} Pipeline Layout", label)),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            key: PipelineKey::new(label, &shader),
+            layout,
+            shader: Some(shader),
+            pipelines: None,
+        }
+    }
+
+    /// Compiles the shader and pipelines, this does nothing if they're already compiled.
+    ///
+    /// The `base` function is the same as [`StencilPipelines::new`], except it also receives the layout.
+    pub(crate) fn init<'b, F>(&mut self, engine: &crate::EngineState, base: F)
+        where F: for<'a, 'c> Fn(&'a wgpu::PipelineLayout, &'c wgpu::ShaderModule) -> builders::Pipeline<'a, 'b, 'c> {
+
+        if self.pipelines.is_none() {
+            let shader = self.shader.take().expect("LazyPipelines: missing shader");
+            let layout = &self.layout;
+
+            self.pipelines = Some(engine.pipeline_cache.get_or_compile(&self.key, || {
+                StencilPipelines::new(engine, shader, |shader| base(layout, shader))
+            }));
+        }
+    }
+
+    /// Compiles the pipelines for [`Mask`](crate::Mask), [`init`](LazyPipelines::init) must be called first.
+    pub(crate) fn init_masked<'b, F>(&self, engine: &crate::EngineState, base: F)
+        where F: for<'a, 'c> Fn(&'a wgpu::PipelineLayout, &'c wgpu::ShaderModule) -> builders::Pipeline<'a, 'b, 'c> {
+
+        let layout = &self.layout;
+        self.get().init_masked(engine, |shader| base(layout, shader));
+    }
+
+    #[inline]
+    pub(crate) fn is_initialized(&self) -> bool {
+        self.pipelines.is_some()
+    }
+
+    /// Returns the compiled pipelines, [`init`](LazyPipelines::init) must be called first.
+    #[inline]
+    pub(crate) fn get(&self) -> &StencilPipelines {
+        self.pipelines.as_ref().expect("LazyPipelines is not initialized")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::PipelineCache;
+
+    #[test]
+    fn pipeline_cache_key() {
+        let data = PipelineCache::add_key("vulkan_1_2", &[1, 2, 3]);
+
+        assert_eq!(PipelineCache::strip_key(&data, "vulkan_1_2"), Some(&[1, 2, 3][..]));
+        assert_eq!(PipelineCache::strip_key(&data, "vulkan_1_3"), None);
+        assert_eq!(PipelineCache::strip_key(&data, "vulkan"), None);
+        assert_eq!(PipelineCache::strip_key(&data[..3], "vulkan_1_2"), None);
+        assert_eq!(PipelineCache::strip_key(&[], "vulkan_1_2"), None);
+    }
+}
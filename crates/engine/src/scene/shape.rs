@@ -0,0 +1,702 @@
+use wgpu_helpers::VertexLayout;
+use bytemuck::{Pod, Zeroable};
+use futures_signals::signal::{Signal, SignalExt};
+
+use crate::util::macros::wgsl;
+use crate::util::builders;
+use crate::util::buffer::{Uniform, InstanceVec, InstanceVecOptions};
+use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, simple_method};
+use crate::scene::mask::{Stencil, StencilRanges};
+use crate::scene::pipeline_cache::{LazyPipelines};
+use crate::scene::{
+    NodeRef, Location, Padding, Origin, Offset, Size, ScreenSize, SmallestSize, SceneLayoutInfo,
+    SceneRenderInfo, RealLocation, RealPosition, NodeLayout, NodeHandle, SceneUniform, ScenePrerender,
+    Prerender, RealSize, Order, Length, Percentage, ColorRgb,
+};
+
+
+/// A solid color parallelogram, in wgpu coordinates.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, VertexLayout, PartialEq)]
+#[layout(step_mode = Instance)]
+pub(crate) struct GPUShape {
+    pub(crate) position: [f32; 2],
+    pub(crate) axis_x: [f32; 2],
+    pub(crate) axis_y: [f32; 2],
+    pub(crate) order: f32,
+    pub(crate) alpha: f32,
+    pub(crate) color: [f32; 3],
+}
+
+impl Default for GPUShape {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            axis_x: [0.0, 0.0],
+            axis_y: [0.0, 0.0],
+            order: 1.0,
+            alpha: 1.0,
+            color: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl GPUShape {
+    fn set_color(&mut self, color: ColorRgb) {
+        self.color = color.to_gpu();
+    }
+
+    /// Converts a vector from screen space into wgpu's coordinate system.
+    #[inline]
+    fn wgpu_vector(x: f32, y: f32) -> [f32; 2] {
+        [x * 2.0, y * -2.0]
+    }
+
+    /// Converts a point from screen space into wgpu's coordinate system.
+    #[inline]
+    fn wgpu_point(x: f32, y: f32) -> [f32; 2] {
+        [(x * 2.0) - 1.0, (y * -2.0) + 1.0]
+    }
+
+    fn update_rect(&mut self, location: &RealLocation) {
+        if location.order < 1.0 {
+            panic!("Order cannot be lower than 1.0");
+        }
+
+        let location = location.convert_to_wgpu_coordinates();
+
+        // The origin point is in the lower-left corner, the same as sprites.
+        self.position = [location.position.x, location.position.y - location.size.height];
+        self.axis_x = [location.size.width, 0.0];
+        self.axis_y = [0.0, location.size.height];
+        self.order = location.order;
+    }
+
+    /// `start` / `end` / `thickness` are in screen space.
+    fn update_line(&mut self, start: RealPosition, end: RealPosition, thickness: f32, screen: &ScreenSize, order: f32) {
+        if order < 1.0 {
+            panic!("Order cannot be lower than 1.0");
+        }
+
+        let pixel_width = screen.width.pixels;
+        let pixel_height = screen.height.pixels;
+
+        // The normal must be calculated in pixels, otherwise the line would be thicker in one direction
+        let dx = (end.x - start.x) * pixel_width;
+        let dy = (end.y - start.y) * pixel_height;
+
+        let length = (dx * dx + dy * dy).sqrt();
+
+        let (normal_x, normal_y) = if length == 0.0 {
+            (0.0, 0.0)
+
+        } else {
+            let half = (thickness * pixel_width) / 2.0;
+            ((-dy / length) * half / pixel_width, (dx / length) * half / pixel_height)
+        };
+
+        self.position = Self::wgpu_point(start.x - normal_x, start.y - normal_y);
+        self.axis_x = Self::wgpu_vector(end.x - start.x, end.y - start.y);
+        self.axis_y = Self::wgpu_vector(normal_x * 2.0, normal_y * 2.0);
+        self.order = order;
+    }
+}
+
+
+/// Displays a solid color rectangle, it doesn't need a spritesheet.
+///
+/// # Sizing
+///
+/// * [`Length::SmallestWidth`]: it is an error to use `SmallestWidth`.
+///
+/// * [`Length::SmallestHeight`]: it is an error to use `SmallestHeight`.
+pub struct Rect {
+    visible: bool,
+    node_ref: Option<NodeRef>,
+    location: Location,
+
+    /// Whether any of the properties changed which require a re-render.
+    render_changed: bool,
+
+    /// Whether it needs to recalculate the location.
+    location_changed: bool,
+
+    parent_location: Option<RealLocation>,
+    smallest_size: Option<RealSize>,
+    max_order: f32,
+
+    gpu_index: usize,
+    gpu_shape: GPUShape,
+}
+
+impl Rect {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            visible: true,
+            node_ref: None,
+            location: Location::default(),
+
+            render_changed: false,
+            location_changed: false,
+
+            parent_location: None,
+            smallest_size: None,
+            max_order: 1.0,
+
+            gpu_index: 0,
+            gpu_shape: GPUShape::default(),
+        }
+    }
+
+    fn location_changed(&mut self) {
+        self.location_changed = true;
+        self.render_changed();
+    }
+
+    fn render_changed(&mut self) {
+        self.render_changed = true;
+    }
+
+    fn update_gpu(&mut self, screen: &ScreenSize) -> RealLocation {
+        let parent = self.parent_location.as_ref().unwrap();
+        let smallest = self.smallest_size.as_ref().unwrap();
+
+        let location = self.location.children_location_explicit(parent, smallest, screen, self.max_order);
+
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(location);
+        }
+
+        self.gpu_shape.update_rect(&location);
+
+        location
+    }
+}
+
+make_builder!(Rect, RectBuilder);
+base_methods!(Rect, RectBuilder);
+
+location_methods!(Rect, RectBuilder, |state| {
+    state.location_changed();
+    BuilderChanged::Render
+});
+
+impl RectBuilder {
+    simple_method!(
+        /// Sets the color of the rectangle.
+        ///
+        /// Defaults to white.
+        color,
+        color_signal,
+        |state, value: ColorRgb| {
+            state.gpu_shape.set_color(value);
+            state.render_changed();
+            BuilderChanged::Render
+        },
+    );
+
+    simple_method!(
+        /// Sets the alpha for the rectangle.
+        ///
+        /// 1.0 means fully opaque, 0.0 means fully transparent.
+        alpha,
+        alpha_signal,
+        |state, value: Percentage| {
+            let changed = ShapeRenderer::alpha_changed(state.gpu_shape.alpha, value);
+
+            state.gpu_shape.alpha = value;
+
+            if let BuilderChanged::Render = changed {
+                state.render_changed();
+            }
+
+            changed
+        },
+    );
+}
+
+impl NodeLayout for Rect {
+    #[inline]
+    fn is_visible(&mut self) -> bool {
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
+    }
+
+    fn smallest_size<'a>(&mut self, _parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
+        self.location.size.smallest_size(&info.screen_size)
+    }
+
+    fn update_layout<'a>(&mut self, handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
+        self.render_changed = false;
+        self.location_changed = false;
+
+        if self.gpu_shape.alpha != 0.0 {
+            self.parent_location = Some(*parent);
+            self.smallest_size = Some(smallest_size.real_size());
+            self.max_order = info.renderer.get_max_order();
+
+            let location = self.update_gpu(&info.screen_size);
+
+            info.enter_node("Rect", &location);
+            info.exit_node();
+
+            info.renderer.set_max_order(self.gpu_shape.order);
+
+            self.gpu_index = info.renderer.shape.push(self.gpu_shape, info.renderer.stencil);
+
+            info.rendered_nodes.push(handle.clone());
+        }
+    }
+
+    fn render<'a>(&mut self, info: &mut SceneRenderInfo<'a>) {
+        assert_ne!(self.gpu_shape.alpha, 0.0);
+
+        if self.render_changed {
+            self.render_changed = false;
+
+            if self.location_changed {
+                self.location_changed = false;
+
+                self.update_gpu(&info.screen_size);
+            }
+
+            info.renderer.shape.update(self.gpu_index, self.gpu_shape);
+        }
+    }
+}
+
+
+/// Position of a point in a [`LineStrip`].
+///
+/// It is relative to the size of the [`LineStrip`], so `{ x: 0.0, y: 0.0 }` is the
+/// upper-left corner and `{ x: 1.0, y: 1.0 }` is the lower-right corner.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinePoint {
+    pub x: Percentage,
+    pub y: Percentage,
+}
+
+
+/// Displays solid color lines which connect the points, it doesn't need a spritesheet.
+///
+/// The corners are not joined, so thick lines will have small gaps at the corners.
+///
+/// # Sizing
+///
+/// * [`Length::SmallestWidth`]: it is an error to use `SmallestWidth`.
+///
+/// * [`Length::SmallestHeight`]: it is an error to use `SmallestHeight`.
+pub struct LineStrip {
+    visible: bool,
+    node_ref: Option<NodeRef>,
+    location: Location,
+
+    points: Vec<LinePoint>,
+    closed: bool,
+    thickness: Length,
+
+    /// Whether any of the properties changed which require a re-render.
+    render_changed: bool,
+
+    /// Whether it needs to recalculate the location.
+    location_changed: bool,
+
+    parent_location: Option<RealLocation>,
+    smallest_size: Option<RealSize>,
+    max_order: f32,
+
+    /// Used for every line segment, except for the position.
+    gpu_shape: GPUShape,
+
+    /// Index of the first line segment.
+    gpu_index: usize,
+    gpu_lines: Vec<GPUShape>,
+}
+
+impl LineStrip {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            visible: true,
+            node_ref: None,
+            location: Location::default(),
+
+            points: vec![],
+            closed: false,
+            thickness: Length::Px(1),
+
+            render_changed: false,
+            location_changed: false,
+
+            parent_location: None,
+            smallest_size: None,
+            max_order: 1.0,
+
+            gpu_shape: GPUShape::default(),
+
+            gpu_index: 0,
+            gpu_lines: vec![],
+        }
+    }
+
+    fn location_changed(&mut self) {
+        self.location_changed = true;
+        self.render_changed();
+    }
+
+    fn render_changed(&mut self) {
+        self.render_changed = true;
+    }
+
+    fn segments(&self) -> impl Iterator<Item = (LinePoint, LinePoint)> + '_ {
+        let closing = if self.closed && self.points.len() > 2 {
+            Some((self.points[self.points.len() - 1], self.points[0]))
+
+        } else {
+            None
+        };
+
+        self.points.windows(2).map(|points| (points[0], points[1])).chain(closing)
+    }
+
+    fn update_gpu(&mut self, screen: &ScreenSize) -> RealLocation {
+        let parent = self.parent_location.as_ref().unwrap();
+        let smallest = self.smallest_size.as_ref().unwrap();
+
+        let location = self.location.children_location_explicit(parent, smallest, screen, self.max_order);
+
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(location);
+        }
+
+        let thickness = self.thickness.real_length(&parent.size, smallest, &screen.width);
+
+        let point = |point: LinePoint| RealPosition {
+            x: location.position.x + (point.x * location.size.width),
+            y: location.position.y + (point.y * location.size.height),
+        };
+
+        self.gpu_shape.order = location.order;
+
+        let lines = self.segments().map(|(start, end)| {
+            let mut line = self.gpu_shape;
+            line.update_line(point(start), point(end), thickness, screen, location.order);
+            line
+        }).collect();
+
+        self.gpu_lines = lines;
+
+        location
+    }
+}
+
+make_builder!(LineStrip, LineStripBuilder);
+base_methods!(LineStrip, LineStripBuilder);
+
+location_methods!(LineStrip, LineStripBuilder, |state| {
+    state.location_changed();
+    BuilderChanged::Render
+});
+
+impl LineStripBuilder {
+    simple_method!(
+        /// Sets the points which are connected by lines, see [`LinePoint`].
+        ///
+        /// Defaults to no points.
+        points,
+        points_signal,
+        |state, value: Vec<LinePoint>| {
+            state.points = value;
+            BuilderChanged::Layout
+        },
+    );
+
+    simple_method!(
+        /// Whether the last point is connected to the first point, this is useful for outlines.
+        ///
+        /// Defaults to `false`.
+        closed,
+        closed_signal,
+        |state, value: bool| {
+            state.closed = value;
+            BuilderChanged::Layout
+        },
+    );
+
+    simple_method!(
+        /// Sets the thickness of the lines.
+        ///
+        /// Defaults to `Length::Px(1)`.
+        ///
+        /// # Sizing
+        ///
+        /// * [`Length::ParentWidth`]: the width of the parent space.
+        ///
+        /// * [`Length::ParentHeight`]: the height of the parent space.
+        ///
+        /// * [`Length::SmallestWidth`]: it is an error to use `SmallestWidth`.
+        ///
+        /// * [`Length::SmallestHeight`]: it is an error to use `SmallestHeight`.
+        thickness,
+        thickness_signal,
+        |state, value: Length| {
+            state.thickness = value;
+            state.location_changed();
+            BuilderChanged::Render
+        },
+    );
+
+    simple_method!(
+        /// Sets the color of the lines.
+        ///
+        /// Defaults to white.
+        color,
+        color_signal,
+        |state, value: ColorRgb| {
+            state.gpu_shape.set_color(value);
+            state.location_changed();
+            BuilderChanged::Render
+        },
+    );
+
+    simple_method!(
+        /// Sets the alpha for the lines.
+        ///
+        /// 1.0 means fully opaque, 0.0 means fully transparent.
+        alpha,
+        alpha_signal,
+        |state, value: Percentage| {
+            let changed = ShapeRenderer::alpha_changed(state.gpu_shape.alpha, value);
+
+            state.gpu_shape.alpha = value;
+
+            // The alpha is copied into every line segment
+            if let BuilderChanged::Render = changed {
+                state.location_changed();
+            }
+
+            changed
+        },
+    );
+}
+
+impl NodeLayout for LineStrip {
+    #[inline]
+    fn is_visible(&mut self) -> bool {
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
+    }
+
+    fn smallest_size<'a>(&mut self, _parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
+        self.location.size.smallest_size(&info.screen_size)
+    }
+
+    fn update_layout<'a>(&mut self, handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
+        self.render_changed = false;
+        self.location_changed = false;
+
+        if self.gpu_shape.alpha != 0.0 {
+            self.parent_location = Some(*parent);
+            self.smallest_size = Some(smallest_size.real_size());
+            self.max_order = info.renderer.get_max_order();
+
+            let location = self.update_gpu(&info.screen_size);
+
+            info.enter_node("LineStrip", &location);
+            info.exit_node();
+
+            info.renderer.set_max_order(self.gpu_shape.order);
+
+            let mut lines = self.gpu_lines.iter();
+
+            if let Some(first) = lines.next() {
+                self.gpu_index = info.renderer.shape.push(*first, info.renderer.stencil);
+
+                for line in lines {
+                    info.renderer.shape.push(*line, info.renderer.stencil);
+                }
+
+                info.rendered_nodes.push(handle.clone());
+            }
+        }
+    }
+
+    fn render<'a>(&mut self, info: &mut SceneRenderInfo<'a>) {
+        assert_ne!(self.gpu_shape.alpha, 0.0);
+
+        if self.render_changed {
+            self.render_changed = false;
+
+            if self.location_changed {
+                self.location_changed = false;
+
+                self.update_gpu(&info.screen_size);
+            }
+
+            for (index, line) in self.gpu_lines.iter().enumerate() {
+                info.renderer.shape.update(self.gpu_index + index, *line);
+            }
+        }
+    }
+}
+
+
+/// Renders every [`Rect`] and [`LineStrip`].
+///
+/// The shader and pipelines are compiled lazily when the first shape is rendered.
+pub(crate) struct ShapeRenderer {
+    pipelines: LazyPipelines,
+    opaque: InstanceVec<GPUShape>,
+    opaque_stencils: StencilRanges,
+    alpha: InstanceVec<GPUShape>,
+    alpha_stencils: StencilRanges,
+}
+
+impl ShapeRenderer {
+    #[inline]
+    pub(crate) fn new(engine: &crate::EngineState, scene_uniform: &mut Uniform<SceneUniform>) -> Self {
+        let scene_uniform_layout = Uniform::bind_group_layout(scene_uniform, engine);
+
+        Self {
+            pipelines: LazyPipelines::new(engine, "Shape", &[scene_uniform_layout], wgsl!("shape.wgsl")),
+            opaque: InstanceVec::new(),
+            opaque_stencils: StencilRanges::new(),
+            alpha: InstanceVec::new(),
+            alpha_stencils: StencilRanges::new(),
+        }
+    }
+
+    /// Opaque and transparent shapes use different pipelines, so changing
+    /// between them requires a relayout, the same as [`Sprite`](crate::Sprite).
+    pub(crate) fn alpha_changed(old: f32, new: f32) -> BuilderChanged {
+        if old != new {
+            let old = old == 1.0 || old == 0.0;
+            let new = new == 1.0 || new == 0.0;
+
+            if old || new {
+                BuilderChanged::Layout
+
+            } else {
+                BuilderChanged::Render
+            }
+
+        } else {
+            BuilderChanged::None
+        }
+    }
+
+    fn instances(&mut self, shape: &GPUShape) -> &mut InstanceVec<GPUShape> {
+        if shape.alpha == 1.0 {
+            &mut self.opaque
+
+        } else {
+            &mut self.alpha
+        }
+    }
+
+    pub(crate) fn push(&mut self, shape: GPUShape, stencil: Stencil) -> usize {
+        let (instances, stencils) = if shape.alpha == 1.0 {
+            (&mut self.opaque, &mut self.opaque_stencils)
+
+        } else {
+            (&mut self.alpha, &mut self.alpha_stencils)
+        };
+
+        let len = instances.len();
+        stencils.push(len, stencil);
+        instances.push(shape);
+        len
+    }
+
+    pub(crate) fn update(&mut self, index: usize, shape: GPUShape) {
+        let instances = self.instances(&shape);
+
+        if instances[index] != shape {
+            instances[index] = shape;
+        }
+    }
+
+    fn pipeline<'a, 'c>(layout: &'a wgpu::PipelineLayout, shader: &'c wgpu::ShaderModule) -> builders::Pipeline<'a, 'static, 'c> {
+        builders::Pipeline::builder()
+            .label("Shape")
+            .shader(shader)
+            .layout(layout)
+            .vertex_buffers(&[GPUShape::LAYOUT])
+            .topology(wgpu::PrimitiveTopology::TriangleStrip)
+            .strip_index_format(wgpu::IndexFormat::Uint32)
+    }
+
+    fn init(&mut self, engine: &crate::EngineState, masked: bool) {
+        self.pipelines.init(engine, Self::pipeline);
+
+        if masked {
+            self.pipelines.init_masked(engine, Self::pipeline);
+        }
+    }
+
+    /// Compiles every pipeline, see [`Engine::warmup`](crate::Engine::warmup).
+    #[inline]
+    pub(crate) fn warmup(&mut self, engine: &crate::EngineState) {
+        self.init(engine, true);
+    }
+
+    #[inline]
+    pub(crate) fn before_layout(&mut self) {
+        self.opaque.clear();
+        self.opaque_stencils.clear();
+        self.alpha.clear();
+        self.alpha_stencils.clear();
+    }
+
+    #[inline]
+    pub(crate) fn before_render(&mut self) {}
+
+    pub(crate) fn prerender<'a>(
+        &'a mut self,
+        engine: &crate::EngineState,
+        scene_uniform: &'a wgpu::BindGroup,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        if self.opaque.is_empty() && self.alpha.is_empty() && !self.pipelines.is_initialized() {
+            return;
+        }
+
+        self.init(engine, self.opaque_stencils.is_masked() || self.alpha_stencils.is_masked());
+
+        let pipelines = self.pipelines.get();
+
+        let opaque_instances = self.opaque.len() as u32;
+        let alpha_instances = self.alpha.len() as u32;
+
+        tracing::trace!(opaque = opaque_instances, alpha = alpha_instances, "Shape");
+
+        prerender.push_masked(Prerender {
+            label: "Shape",
+            alpha: false,
+            vertices: 4,
+            first_instance: 0,
+            instances: opaque_instances,
+            stencil: Stencil::None,
+            pipeline: pipelines.get(false, Stencil::None),
+            bind_groups: vec![scene_uniform],
+            slices: vec![self.opaque.update_buffer(engine, &InstanceVecOptions {
+                label: Some("Shape Instance Buffer"),
+            })],
+            indirect: None,
+        }, &self.opaque_stencils, pipelines);
+
+        prerender.push_masked(Prerender {
+            label: "Shape",
+            alpha: true,
+            vertices: 4,
+            first_instance: 0,
+            instances: alpha_instances,
+            stencil: Stencil::None,
+            pipeline: pipelines.get(true, Stencil::None),
+            bind_groups: vec![scene_uniform],
+            slices: vec![self.alpha.update_buffer(engine, &InstanceVecOptions {
+                label: Some("Shape Instance Buffer"),
+            })],
+            indirect: None,
+        }, &self.alpha_stencils, pipelines);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::WindowSize;
+use crate::scene::{RealLocation, ScreenSize};
+
+
+/// The layout of every visible Node in the scene.
+///
+/// This is intended for debugging, so that bug reports about the layout can include
+/// exactly what the engine computed.
+///
+/// With the `serde` feature it can be serialized (e.g. to JSON).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SceneSnapshot {
+    /// The width of the window in pixels.
+    pub window_width: u32,
+
+    /// The height of the window in pixels.
+    pub window_height: u32,
+
+    /// The root Node, this is `None` if the root Node is invisible.
+    pub root: Option<NodeSnapshot>,
+}
+
+
+/// The computed layout of a single Node.
+///
+/// The location is in pixels, relative to the upper-left corner of the window.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NodeSnapshot {
+    /// The type of the Node, for example `"Row"` or `"Sprite"`.
+    pub kind: &'static str,
+
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+
+    /// The z-index of the Node, Nodes with a bigger order are displayed on top.
+    pub order: f32,
+
+    /// The visible children of the Node, in layout order.
+    pub children: Vec<NodeSnapshot>,
+}
+
+
+/// Builds the [`SceneSnapshot`] while the layout is running.
+pub(crate) struct SnapshotRecorder {
+    stack: Vec<NodeSnapshot>,
+    root: Option<NodeSnapshot>,
+}
+
+impl SnapshotRecorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            stack: vec![],
+            root: None,
+        }
+    }
+
+    pub(crate) fn enter(&mut self, kind: &'static str, location: &RealLocation, screen: &ScreenSize) {
+        self.stack.push(NodeSnapshot {
+            kind,
+            x: location.position.x * screen.width.pixels,
+            y: location.position.y * screen.height.pixels,
+            width: location.size.width * screen.width.pixels,
+            height: location.size.height * screen.height.pixels,
+            order: location.order,
+            children: vec![],
+        });
+    }
+
+    pub(crate) fn exit(&mut self) {
+        let node = self.stack.pop().expect("SnapshotRecorder::exit called without enter");
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => {
+                debug_assert!(self.root.is_none());
+                self.root = Some(node);
+            },
+        }
+    }
+
+    pub(crate) fn finish(self, window_size: &WindowSize) -> SceneSnapshot {
+        debug_assert!(self.stack.is_empty());
+
+        SceneSnapshot {
+            window_width: window_size.width,
+            window_height: window_size.height,
+            root: self.root,
+        }
+    }
+}
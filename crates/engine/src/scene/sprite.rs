@@ -1,19 +1,24 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
 use wgpu_helpers::VertexLayout;
 use bytemuck::{Pod, Zeroable};
 use futures_signals::signal::{Signal, SignalExt};
 
-use crate::DEBUG;
 use crate::util::macros::wgsl;
 use crate::util::builders;
 use crate::util::buffer::{
     Uniform, TextureBuffer, InstanceVec, InstanceVecOptions,
-    RgbaImage, IndexedImage,
+    RgbaImage, IndexedImage, IntoTexture,
 };
 use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, simple_method};
+use crate::scene::mask::{Stencil, StencilRanges, StencilPipelines};
+use crate::scene::pipeline_cache::{LazyPipelines};
+use crate::scene::culling::{SpriteCulling, CulledInstances};
+use crate::scene::animation::{SpriteAnimator, AnimatedInstances};
 use crate::scene::{
-    Handle, Handles, Texture, Location, Padding, Origin, Offset, Size, ScreenSize, SmallestSize,
+    Handle, NodeRef, Handles, Texture, Location, Padding, Origin, Offset, Size, ScreenSize, SmallestSize,
     SceneLayoutInfo, SceneRenderInfo, RealLocation, NodeLayout,  NodeHandle, SceneUniform,
-    ScenePrerender, Prerender, Length, RealSize, ScreenLength, Order, Percentage,
+    ScenePrerender, Prerender, TextureState, TextureSize, Length, RealSize, ScreenLength, Order, Percentage,
 };
 
 
@@ -75,6 +80,76 @@ impl RepeatTile {
 }
 
 
+/// Shifts the starting point of the repeated sprite tile.
+///
+/// The offset is a percentage of the tile size, so `1.0` is the
+/// width / height of one tile. Positive values shift the tile
+/// to the left / up, negative values shift it to the right / down.
+///
+/// By animating the offset it's possible to create scrolling
+/// backgrounds without needing to move the sprite.
+///
+/// The default is `{ x: 0.0, y: 0.0 }` which means no offset.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RepeatOffset {
+    pub x: Percentage,
+    pub y: Percentage,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// When it reaches the end of the frames, it starts again from the beginning.
+    Loop,
+
+    /// When it reaches the end of the frames, it reverses direction.
+    /// When it reaches the start of the frames, it reverses direction again.
+    Pendulum,
+}
+
+/// Animates the tile of a sprite on the GPU.
+///
+/// Every `duration` milliseconds the tile moves by `offset_x` / `offset_y`
+/// pixels, up to `frames` times. This is much faster than animating the
+/// tile with a Signal, because it doesn't need to update the sprite.
+///
+/// The time is set with [`Engine::set_time`](crate::Engine::set_time).
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteAnimation {
+    pub frames: u32,
+
+    /// This must be greater than `0.0`, otherwise it is replaced with [`MIN_DURATION`](SpriteAnimation::MIN_DURATION).
+    pub duration: f32,
+
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub mode: AnimationMode,
+}
+
+impl SpriteAnimation {
+    /// The duration which is used if the `duration` isn't greater than `0.0`.
+    pub const MIN_DURATION: f32 = 1.0;
+
+    /// Converts into [`GPUSprite::animation`], the shader divides the time by the duration.
+    pub(crate) fn to_gpu(self) -> [f32; 4] {
+        let duration = if self.duration.is_nan() || self.duration <= 0.0 {
+            tracing::warn!(duration = self.duration, "SpriteAnimation duration must be greater than 0.0");
+            Self::MIN_DURATION
+
+        } else {
+            self.duration
+        };
+
+        [
+            self.frames as f32,
+            duration,
+            self.offset_x as f32,
+            self.offset_y as f32,
+        ]
+    }
+}
+
+
 /// Specifies which tile should be displayed (in pixel coordinates).
 #[derive(Debug, Clone, Copy)]
 pub struct Tile {
@@ -117,6 +192,13 @@ pub(crate) struct GPUSprite {
     pub(crate) alpha: f32,
     pub(crate) uv: [f32; 2],
     pub(crate) tile: [u32; 4],
+    pub(crate) uv_offset: [f32; 2],
+    pub(crate) flags: u32,
+    pub(crate) hue_shift: f32,
+    pub(crate) animation: [f32; 4],
+
+    /// The frame which is selected by [`SpriteAnimator`], it is only used if SpriteAnimator set the `ANIMATION_FRAME` flag.
+    pub(crate) frame: f32,
 }
 
 impl Default for GPUSprite {
@@ -128,11 +210,51 @@ impl Default for GPUSprite {
             alpha: 1.0,
             uv: [1.0, 1.0],
             tile: [0, 0, 0, 0],
+            uv_offset: [0.0, 0.0],
+            flags: 0,
+            hue_shift: 0.0,
+            animation: [0.0, 0.0, 0.0, 0.0],
+            frame: 0.0,
         }
     }
 }
 
 impl GPUSprite {
+    // These must be kept in sync with the flags in sprite.wgsl
+    pub(crate) const FLIP_X: u32 = 0b01;
+    pub(crate) const FLIP_Y: u32 = 0b10;
+    pub(crate) const ANIMATION_PENDULUM: u32 = 0b100;
+
+    // 0b1000 is the ANIMATION_FRAME flag, it is only set by SpriteAnimator on the GPU
+
+    /// The bits of `flags` above this store the tie index, see [`SpriteRenderer::next_tie`].
+    pub(crate) const TIE_SHIFT: u32 = 8;
+    pub(crate) const MAX_TIE: u32 = u32::MAX >> Self::TIE_SHIFT;
+
+    #[inline]
+    pub(crate) fn is_animated(&self) -> bool {
+        self.animation[0] > 1.0
+    }
+
+    #[inline]
+    pub(crate) fn set_flag(&mut self, flag: u32, value: bool) -> bool {
+        let old = self.flags;
+
+        if value {
+            self.flags |= flag;
+
+        } else {
+            self.flags &= !flag;
+        }
+
+        self.flags != old
+    }
+
+    #[inline]
+    pub(crate) fn set_tie(&mut self, tie: u32) {
+        self.flags = (self.flags & ((1 << Self::TIE_SHIFT) - 1)) | (tie << Self::TIE_SHIFT);
+    }
+
     pub(crate) fn update(&mut self, location: &RealLocation) {
         if location.order < 1.0 {
             panic!("Order cannot be lower than 1.0");
@@ -158,12 +280,22 @@ impl GPUSprite {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable, VertexLayout, Default, PartialEq)]
 #[layout(step_mode = Instance)]
-#[layout(location = 6)]
+#[layout(location = 11)]
 pub(crate) struct GPUPalette {
     pub(crate) palette: u32,
 }
 
 
+/// The index of the sprite's spritesheet in a [`SpritesheetBatch`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, VertexLayout, Default, PartialEq)]
+#[layout(step_mode = Instance)]
+#[layout(location = 12)]
+pub(crate) struct GPUTextureIndex {
+    pub(crate) texture: u32,
+}
+
+
 /// Displays a sprite from a spritesheet.
 ///
 /// # Sizing
@@ -173,8 +305,10 @@ pub(crate) struct GPUPalette {
 /// * [`Length::SmallestHeight`]: it is an error to use `SmallestHeight`.
 pub struct Sprite {
     visible: bool,
+    node_ref: Option<NodeRef>,
     location: Location,
     spritesheet: Option<Spritesheet>,
+    pipeline: Option<PipelineHandle>,
     repeat_tile: RepeatTile,
 
     /// Whether any of the properties changed which require a re-render.
@@ -197,8 +331,10 @@ impl Sprite {
     fn new() -> Self {
         Self {
             visible: true,
+            node_ref: None,
             location: Location::default(),
             spritesheet: None,
+            pipeline: None,
             repeat_tile: RepeatTile::default(),
 
             render_changed: false,
@@ -223,15 +359,21 @@ impl Sprite {
         self.render_changed = true;
     }
 
-    fn update_gpu(&mut self, screen: &ScreenSize) {
+    fn update_gpu(&mut self, screen: &ScreenSize) -> RealLocation {
         let parent = self.parent_location.as_ref().unwrap();
         let smallest = self.smallest_size.as_ref().unwrap();
 
         let location = self.location.children_location_explicit(parent, smallest, screen, self.max_order);
 
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(location);
+        }
+
         self.gpu_sprite.uv = self.repeat_tile.to_uv(&location.size, &parent.size, smallest, screen);
 
         self.gpu_sprite.update(&location);
+
+        location
     }
 }
 
@@ -281,6 +423,20 @@ impl SpriteBuilder {
         },
     );
 
+    simple_method!(
+        /// Draws the sprite with a custom fragment shader instead of the built-in sprite shader.
+        ///
+        /// See [`PipelineHandle`] for the details.
+        ///
+        /// Defaults to `None` which uses the built-in sprite shader.
+        custom_pipeline,
+        custom_pipeline_signal,
+        |state, value: Option<PipelineHandle>| {
+            state.pipeline = value;
+            BuilderChanged::Layout
+        },
+    );
+
     simple_method!(
         /// Sets the [`Tile`] which specifies which tile to display (in pixel coordinates).
         tile,
@@ -298,6 +454,46 @@ impl SpriteBuilder {
         },
     );
 
+    simple_method!(
+        /// Flips the sprite horizontally.
+        ///
+        /// This is the same as using [`Tile::mirror_x`], except it
+        /// doesn't require changing the tile.
+        ///
+        /// Defaults to `false`.
+        flip_x,
+        flip_x_signal,
+        |state, value: bool| {
+            if state.gpu_sprite.set_flag(GPUSprite::FLIP_X, value) {
+                state.render_changed();
+                BuilderChanged::Render
+
+            } else {
+                BuilderChanged::None
+            }
+        },
+    );
+
+    simple_method!(
+        /// Flips the sprite vertically.
+        ///
+        /// This is the same as using [`Tile::mirror_y`], except it
+        /// doesn't require changing the tile.
+        ///
+        /// Defaults to `false`.
+        flip_y,
+        flip_y_signal,
+        |state, value: bool| {
+            if state.gpu_sprite.set_flag(GPUSprite::FLIP_Y, value) {
+                state.render_changed();
+                BuilderChanged::Render
+
+            } else {
+                BuilderChanged::None
+            }
+        },
+    );
+
     simple_method!(
         /// Sets the [`RepeatTile`] which specifies how to repeat the sprite tile.
         repeat_tile,
@@ -309,6 +505,66 @@ impl SpriteBuilder {
         },
     );
 
+    simple_method!(
+        /// Sets the [`RepeatOffset`] which shifts the starting point of the repeated sprite tile.
+        repeat_offset,
+        repeat_offset_signal,
+        |state, value: RepeatOffset| {
+            state.gpu_sprite.uv_offset = [value.x, value.y];
+
+            state.render_changed();
+            BuilderChanged::Render
+        },
+    );
+
+    simple_method!(
+        /// Shifts the hue of the sprite's colors.
+        ///
+        /// This is a percentage of the color wheel, so `0.5` shifts
+        /// the hue by 180 degrees and `1.0` is a full rotation.
+        ///
+        /// This is useful for recoloring effects which aren't palettized.
+        ///
+        /// It's only supported for spritesheets which don't have a palette,
+        /// palette sprites should use [`palette`](SpriteBuilder::palette) instead.
+        ///
+        /// Defaults to `0.0` which means no hue shift.
+        hue_shift,
+        hue_shift_signal,
+        |state, value: Percentage| {
+            state.gpu_sprite.hue_shift = value;
+
+            state.render_changed();
+            BuilderChanged::Render
+        },
+    );
+
+    simple_method!(
+        /// Animates the sprite's tile on the GPU, see [`SpriteAnimation`].
+        ///
+        /// Defaults to `None` which means no animation.
+        ///
+        /// If the duration isn't greater than `0.0` then it logs a warning and uses [`SpriteAnimation::MIN_DURATION`].
+        animation,
+        animation_signal,
+        |state, value: Option<SpriteAnimation>| {
+            match value {
+                Some(value) => {
+                    state.gpu_sprite.animation = value.to_gpu();
+
+                    state.gpu_sprite.set_flag(GPUSprite::ANIMATION_PENDULUM, value.mode == AnimationMode::Pendulum);
+                },
+                None => {
+                    state.gpu_sprite.animation = [0.0, 0.0, 0.0, 0.0];
+                    state.gpu_sprite.set_flag(GPUSprite::ANIMATION_PENDULUM, false);
+                },
+            }
+
+            state.render_changed();
+            BuilderChanged::Render
+        },
+    );
+
     simple_method!(
         /// Sets the palette for this sprite.
         palette,
@@ -327,7 +583,7 @@ impl SpriteBuilder {
 impl NodeLayout for Sprite {
     #[inline]
     fn is_visible(&mut self) -> bool {
-        self.visible
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
     }
 
     fn smallest_size<'a>(&mut self, _parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
@@ -345,14 +601,20 @@ impl NodeLayout for Sprite {
             self.smallest_size = Some(smallest_size);
             self.max_order = info.renderer.get_max_order();
 
-            self.update_gpu(&info.screen_size);
+            let location = self.update_gpu(&info.screen_size);
+
+            info.enter_node("Sprite", &location);
+            info.exit_node();
 
             info.renderer.set_max_order(self.gpu_sprite.order);
 
+            self.gpu_sprite.set_tie(info.renderer.sprite.next_tie(self.gpu_sprite.order));
+
             let spritesheet = self.spritesheet.as_ref().expect("Sprite is missing spritesheet");
 
             if let Some(spritesheet) = info.renderer.sprite.spritesheets.get_mut(&spritesheet.handle) {
-                self.gpu_index = spritesheet.push(self.gpu_sprite, self.gpu_palette);
+                let pipeline = self.pipeline.as_ref().map(|pipeline| &pipeline.handle);
+                self.gpu_index = spritesheet.push(self.gpu_sprite, self.gpu_palette, info.renderer.stencil, pipeline);
             }
 
             info.rendered_nodes.push(handle.clone());
@@ -374,91 +636,339 @@ impl NodeLayout for Sprite {
             let spritesheet = self.spritesheet.as_ref().expect("Sprite is missing spritesheet");
 
             if let Some(spritesheet) = info.renderer.sprite.spritesheets.get_mut(&spritesheet.handle) {
-                spritesheet.update(self.gpu_index, self.gpu_sprite, self.gpu_palette);
+                let pipeline = self.pipeline.as_ref().map(|pipeline| &pipeline.handle);
+                spritesheet.update(self.gpu_index, self.gpu_sprite, self.gpu_palette, pipeline);
             }
         }
     }
 }
 
 
+/// The shader and pipelines are compiled lazily when the pipeline is first used.
 pub(crate) struct SpritesheetPipeline {
-    pub(crate) bind_group_layout: wgpu::BindGroupLayout,
-    pub(crate) opaque: wgpu::RenderPipeline,
-    pub(crate) alpha: wgpu::RenderPipeline,
+    pipelines: LazyPipelines,
+    vertex_buffers: &'static [wgpu::VertexBufferLayout<'static>],
 }
 
 impl SpritesheetPipeline {
-    pub(crate) fn new<'a>(
+    /// The `label` must be different for each combination of `vertex_buffers` and `bind_group_layout`, see [`LazyPipelines::new`].
+    pub(crate) fn new(
         engine: &crate::EngineState,
+        label: &'static str,
         scene_uniform_layout: &wgpu::BindGroupLayout,
-        shader: wgpu::ShaderModuleDescriptor<'a>,
-        vertex_buffers: &[wgpu::VertexBufferLayout],
-        bind_group_layout: wgpu::BindGroupLayout
+        shader: wgpu::ShaderModuleDescriptor<'static>,
+        vertex_buffers: &'static [wgpu::VertexBufferLayout<'static>],
+        bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
-        /*let stencil = wgpu::StencilFaceState {
-            compare: wgpu::CompareFunction::GreaterEqual,
-            fail_op: wgpu::StencilOperation::Keep,
-            depth_fail_op: wgpu::StencilOperation::Keep,
-            pass_op: wgpu::StencilOperation::Replace,
-        };*/
-
-        // TODO lazy load this ?
-        let shader = engine.device.create_shader_module(shader);
+        Self {
+            pipelines: LazyPipelines::new(engine, label, &[scene_uniform_layout, bind_group_layout], shader),
+            vertex_buffers,
+        }
+    }
 
-        let opaque = builders::Pipeline::builder()
+    fn pipeline<'a, 'c>(layout: &'a wgpu::PipelineLayout, vertex_buffers: &'static [wgpu::VertexBufferLayout<'static>], shader: &'c wgpu::ShaderModule) -> builders::Pipeline<'a, 'static, 'c> {
+        builders::Pipeline::builder()
             .label("Sprite")
-            // TODO lazy load this ?
-            .shader(&shader)
-            .bind_groups(&[
-                scene_uniform_layout,
-                &bind_group_layout,
-            ])
+            .shader(shader)
+            .layout(layout)
             .vertex_buffers(vertex_buffers)
             .topology(wgpu::PrimitiveTopology::TriangleStrip)
             .strip_index_format(wgpu::IndexFormat::Uint32)
-            /*.stencil(wgpu::StencilState {
-                front: stencil,
-                back: stencil,
-                read_mask: 0xFF,
-                write_mask: 0xFF,
-            })*/
-            .build(engine);
+    }
 
-        let alpha = builders::Pipeline::builder()
-            .label("Sprite")
-            .shader(&shader)
-            .bind_groups(&[
-                scene_uniform_layout,
-                &bind_group_layout,
-            ])
-            .vertex_buffers(vertex_buffers)
-            .topology(wgpu::PrimitiveTopology::TriangleStrip)
-            .strip_index_format(wgpu::IndexFormat::Uint32)
-            .depth_write(false)
-            .blend_state(wgpu::BlendState::ALPHA_BLENDING)
-            .build(engine);
+    /// Compiles the shader and pipelines, this does nothing if they're already compiled.
+    pub(crate) fn init(&mut self, engine: &crate::EngineState) {
+        let vertex_buffers = self.vertex_buffers;
+        self.pipelines.init(engine, |layout, shader| Self::pipeline(layout, vertex_buffers, shader));
+    }
+
+    /// Compiles the pipelines for [`Mask`](crate::Mask), [`init`](SpritesheetPipeline::init) must be called first.
+    pub(crate) fn init_masked(&self, engine: &crate::EngineState) {
+        let vertex_buffers = self.vertex_buffers;
+        self.pipelines.init_masked(engine, |layout, shader| Self::pipeline(layout, vertex_buffers, shader));
+    }
+
+    /// Returns the compiled pipelines, [`init`](SpritesheetPipeline::init) must be called first.
+    #[inline]
+    pub(crate) fn pipelines(&self) -> &StencilPipelines {
+        self.pipelines.get()
+    }
+}
+
+
+/// The normal and palette variants of a spritesheet shader.
+struct SpritesheetPipelines {
+    normal: SpritesheetPipeline,
+    palette: SpritesheetPipeline,
+
+    /// Whether the shader uses the scene time, so it must be re-rendered when the time changes.
+    animated: bool,
+}
+
+impl SpritesheetPipelines {
+    #[inline]
+    fn get_mut(&mut self, palette: bool) -> &mut SpritesheetPipeline {
+        if palette {
+            &mut self.palette
+
+        } else {
+            &mut self.normal
+        }
+    }
+
+    #[inline]
+    fn get(&self, palette: bool) -> &StencilPipelines {
+        if palette {
+            self.palette.pipelines()
 
-        Self { bind_group_layout, opaque, alpha }
+        } else {
+            self.normal.pipelines()
+        }
     }
 }
 
 
-pub(crate) static SCENE_SHADER: &'static str = include_str!("../wgsl/common/scene.wgsl");
-pub(crate) static SPRITE_SHADER: &'static str = include_str!("../wgsl/common/sprite.wgsl");
+/// The compute shaders which run before the sprites are drawn.
+#[derive(Clone, Copy)]
+struct SpriteCompute<'a> {
+    /// If this is `Some` then the animation frames are selected on the GPU.
+    animator: Option<&'a SpriteAnimator>,
+
+    /// If this is `Some` then the sprites are culled on the GPU, this is only used for opaque sprites.
+    culling: Option<&'a SpriteCulling>,
+}
 
 
 struct SpritesheetInstances {
     sprites: InstanceVec<GPUSprite>,
     palettes: Option<InstanceVec<GPUPalette>>,
+
+    /// This is only used by [`SpritesheetBatch`].
+    textures: Option<InstanceVec<GPUTextureIndex>>,
+
+    stencils: StencilRanges,
+
+    /// This is `None` until the instances are animated for the first time.
+    animated: Option<AnimatedInstances>,
+
+    /// This is `None` until the instances are culled for the first time.
+    culled: Option<CulledInstances>,
+}
+
+impl SpritesheetInstances {
+    fn new(palette: bool) -> Self {
+        Self {
+            sprites: InstanceVec::new(),
+            palettes: if palette { Some(InstanceVec::new()) } else { None },
+            textures: None,
+            stencils: StencilRanges::new(),
+            animated: None,
+            culled: None,
+        }
+    }
+
+    fn new_batched(palette: bool) -> Self {
+        Self {
+            textures: Some(InstanceVec::new()),
+            ..Self::new(palette)
+        }
+    }
+
+    fn clear(&mut self) {
+        self.sprites.clear();
+        self.stencils.clear();
+
+        if let Some(palettes) = &mut self.palettes {
+            palettes.clear();
+        }
+
+        if let Some(textures) = &mut self.textures {
+            textures.clear();
+        }
+    }
+
+    /// The first bind group must be the scene uniform.
+    fn prerender<'a>(
+        &'a mut self,
+        engine: &crate::EngineState,
+        label: &'static str,
+        alpha: bool,
+        bind_groups: Vec<&'a wgpu::BindGroup>,
+        pipelines: &'a StencilPipelines,
+        compute: SpriteCompute<'a>,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        let instances = self.sprites.len() as u32;
+
+        if alpha {
+            tracing::trace!(label, instances, "Spritesheet alpha");
+
+        } else {
+            tracing::trace!(label, instances, "Spritesheet opaque");
+        }
+
+        // The compute shaders use the instances as storage buffers, the usage can't change
+        // between frames so it doesn't depend on whether this batch is animated or culled
+        let usage = if engine.gpu_animation || engine.gpu_culling {
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE
+
+        } else {
+            wgpu::BufferUsages::VERTEX
+        };
+
+        let Self { sprites, palettes, textures, stencils, animated, culled } = self;
+
+        sprites.update_buffer_with_usage(engine, &InstanceVecOptions {
+            label: Some("Sprite Instance Buffer"),
+        }, usage);
+
+        if let Some(palettes) = palettes {
+            palettes.update_buffer_with_usage(engine, &InstanceVecOptions {
+                label: Some("Sprite Palettes Buffer"),
+            }, usage);
+        }
+
+        if let Some(textures) = textures {
+            textures.update_buffer_with_usage(engine, &InstanceVecOptions {
+                label: Some("Sprite Textures Buffer"),
+            }, usage);
+        }
+
+        // The frames must be selected before the sprites are copied by the culling
+        if let Some(animator) = compute.animator {
+            animated.get_or_insert_with(AnimatedInstances::new).prerender(engine, animator, label, bind_groups[0], sprites, prerender);
+        }
+
+        let draw = Prerender {
+            label,
+            alpha,
+            vertices: 4,
+            first_instance: 0,
+            instances,
+            stencil: Stencil::None,
+            pipeline: pipelines.get(alpha, Stencil::None),
+            bind_groups,
+            slices: vec![
+                sprites.slice(),
+                palettes.as_ref().and_then(|palettes| palettes.slice()),
+                textures.as_ref().and_then(|textures| textures.slice()),
+            ],
+            indirect: None,
+        };
+
+        match (compute.culling, sprites.buffer()) {
+            (Some(culling), Some(buffer)) if SpriteCulling::can_cull(engine, instances) => {
+                culled.get_or_insert_with(CulledInstances::new).prerender(
+                    engine,
+                    culling,
+                    draw,
+                    stencils,
+                    pipelines,
+                    buffer,
+                    palettes.as_ref().and_then(|palettes| palettes.buffer()),
+                    textures.as_ref().and_then(|textures| textures.buffer()),
+                    prerender,
+                );
+            },
+
+            _ => {
+                prerender.push_masked(draw, stencils, pipelines);
+            },
+        }
+    }
+}
+
+
+/// Copy of the alpha instances which is sorted by order.
+struct SortedInstances {
+    indices: Vec<usize>,
+    instances: SpritesheetInstances,
+}
+
+impl SortedInstances {
+    fn update(&mut self, source: &SpritesheetInstances) {
+        self.indices.clear();
+        self.indices.extend(0..source.sprites.len());
+
+        // Each Mask is drawn separately, so the sprites are only sorted within the same Mask
+        for (range, _) in source.stencils.ranges(source.sprites.len()) {
+            // Stable sort so that sprites with the same order are drawn in layout order
+            self.indices[range].sort_by(|a, b| {
+                source.sprites[*a].order.total_cmp(&source.sprites[*b].order)
+            });
+        }
+
+        self.instances.stencils.clone_from(&source.stencils);
+
+        let indices = &self.indices;
+
+        // Avoids uploading the buffer if nothing changed
+        let changed = !self.instances.sprites.iter().eq(indices.iter().map(|index| &source.sprites[*index]));
+
+        if changed {
+            self.instances.sprites.clear();
+            self.instances.sprites.extend(indices.iter().map(|index| source.sprites[*index]));
+        }
+
+        if let (Some(palettes), Some(source)) = (&mut self.instances.palettes, &source.palettes) {
+            let changed = !palettes.iter().eq(indices.iter().map(|index| &source[*index]));
+
+            if changed {
+                palettes.clear();
+                palettes.extend(indices.iter().map(|index| source[*index]));
+            }
+        }
+    }
 }
 
-struct SpritesheetState {
+
+/// The sprites of a spritesheet which are drawn with the same pipeline.
+struct SpriteBatch {
     opaque: SpritesheetInstances,
     alpha: SpritesheetInstances,
-    bind_group: wgpu::BindGroup,
+    sorted_alpha: Option<SortedInstances>,
 }
 
-impl SpritesheetState {
+impl SpriteBatch {
+    fn new(palette: bool, sorted: bool) -> Self {
+        let sorted_alpha = if sorted {
+            Some(SortedInstances {
+                indices: vec![],
+                instances: SpritesheetInstances::new(palette),
+            })
+
+        } else {
+            None
+        };
+
+        Self {
+            opaque: SpritesheetInstances::new(palette),
+            alpha: SpritesheetInstances::new(palette),
+            sorted_alpha,
+        }
+    }
+
+    #[inline]
+    fn is_masked(&self) -> bool {
+        self.opaque.stencils.is_masked() || self.alpha.stencils.is_masked()
+    }
+
+    #[inline]
+    fn has_sprites(&self) -> bool {
+        self.opaque.sprites.len() > 0 || self.alpha.sprites.len() > 0
+    }
+
+    #[inline]
+    fn is_animated(&self) -> bool {
+        self.opaque.sprites.iter().any(GPUSprite::is_animated) ||
+        self.alpha.sprites.iter().any(GPUSprite::is_animated)
+    }
+
+    fn clear(&mut self) {
+        self.opaque.clear();
+        self.alpha.clear();
+    }
+
     fn instances(&mut self, sprite: &GPUSprite) -> &mut SpritesheetInstances {
         if sprite.alpha == 1.0 {
             &mut self.opaque
@@ -468,11 +978,13 @@ impl SpritesheetState {
         }
     }
 
-    fn push(&mut self, sprite: GPUSprite, palette: Option<GPUPalette>) -> usize {
+    fn push(&mut self, sprite: GPUSprite, palette: Option<GPUPalette>, stencil: Stencil) -> usize {
         let instances = self.instances(&sprite);
 
         let len = instances.sprites.len();
 
+        instances.stencils.push(len, stencil);
+
         instances.sprites.push(sprite);
 
         match &mut instances.palettes {
@@ -502,222 +1014,802 @@ impl SpritesheetState {
         }
     }
 
-    pub(crate) fn prerender<'a>(
+    /// If `opaque` is `false` then the opaque sprites aren't drawn, because they are drawn by a [`SpritesheetBatch`].
+    fn prerender<'a>(
         &'a mut self,
         engine: &crate::EngineState,
+        label: &'static str,
+        opaque: bool,
         scene_uniform: &'a wgpu::BindGroup,
-        normal: &'a SpritesheetPipeline,
-        palette: &'a SpritesheetPipeline,
-    ) -> (Prerender<'a>, Prerender<'a>) {
-        let opaque = {
-            let instances = self.opaque.sprites.len() as u32;
-
-            if DEBUG {
-                log::warn!("Spritesheet opaque {}", instances);
-            }
-
-            let bind_groups = vec![
-                scene_uniform,
-                &self.bind_group,
-            ];
+        bind_group: Option<&'a wgpu::BindGroup>,
+        pipelines: &'a StencilPipelines,
+        compute: SpriteCompute<'a>,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        // An evicted spritesheet has no sprites, so it doesn't need its bind group
+        let bind_groups = std::iter::once(scene_uniform).chain(bind_group).collect::<Vec<_>>();
 
-            let pipeline = if self.opaque.palettes.is_some() {
-                &palette.opaque
-            } else {
-                &normal.opaque
-            };
-
-            let slices = vec![
-                self.opaque.sprites.update_buffer(engine, &InstanceVecOptions {
-                    label: Some("Sprite Instance Buffer"),
-                }),
-
-                self.opaque.palettes.as_mut().and_then(|palettes| {
-                    palettes.update_buffer(engine, &InstanceVecOptions {
-                        label: Some("Sprite Palettes Buffer"),
-                    })
-                }),
-            ];
+        if opaque {
+            self.opaque.prerender(engine, label, false, bind_groups.clone(), pipelines, compute, prerender);
+        }
 
-            Prerender {
-                vertices: 4,
-                instances,
-                pipeline,
-                bind_groups,
-                slices,
-            }
+        let alpha = match &mut self.sorted_alpha {
+            Some(sorted) => {
+                sorted.update(&self.alpha);
+                &mut sorted.instances
+            },
+            None => &mut self.alpha,
         };
 
-        let alpha = {
-            let instances = self.alpha.sprites.len() as u32;
+        // Transparent sprites must be drawn in order, so they aren't culled
+        alpha.prerender(engine, label, true, bind_groups, pipelines, SpriteCompute { culling: None, ..compute }, prerender);
+    }
+}
 
-            if DEBUG {
-                log::warn!("Spritesheet alpha {}", instances);
-            }
 
-            let bind_groups = vec![
-                scene_uniform,
-                &self.bind_group,
-            ];
+pub(crate) struct SpritesheetState {
+    label: &'static str,
+    draw_order: i32,
+    sorted: bool,
 
-            let pipeline = if self.alpha.palettes.is_some() {
-                &palette.alpha
-            } else {
-                &normal.alpha
-            };
-
-            let slices = vec![
-                self.alpha.sprites.update_buffer(engine, &InstanceVecOptions {
-                    label: Some("Sprite Instance Buffer"),
-                }),
-
-                self.alpha.palettes.as_mut().and_then(|palettes| {
-                    palettes.update_buffer(engine, &InstanceVecOptions {
-                        label: Some("Sprite Palettes Buffer"),
-                    })
-                }),
-            ];
+    /// The sprites which use the built-in sprite shader.
+    batch: SpriteBatch,
+
+    /// The sprites which use a [`PipelineHandle`], they are drawn after the built-in sprites.
+    custom: Vec<(Handle, SpriteBatch)>,
+
+    texture: Handle,
+    palette: Option<Handle>,
+
+    /// This is `None` if the spritesheet is evicted.
+    bind_group: Option<wgpu::BindGroup>,
+
+    /// Whether the opaque sprites of [`batch`](SpritesheetState::batch) are drawn by a [`SpritesheetBatch`],
+    /// see [`SpriteRenderer::update_batches`].
+    batched: bool,
+}
+
+impl SpritesheetState {
+    #[inline]
+    fn uses_texture(&self, handle: &Handle) -> bool {
+        self.texture.eq(handle) || self.palette.as_ref().map_or(false, |palette| palette.eq(handle))
+    }
+
+    fn batches(&self) -> impl Iterator<Item = &SpriteBatch> {
+        std::iter::once(&self.batch).chain(self.custom.iter().map(|(_, batch)| batch))
+    }
+
+    #[inline]
+    fn has_sprites(&self) -> bool {
+        self.batches().any(SpriteBatch::has_sprites)
+    }
+
+    fn batch_mut(&mut self, pipeline: Option<&Handle>) -> &mut SpriteBatch {
+        match pipeline {
+            None => &mut self.batch,
+
+            Some(pipeline) => {
+                let index = match self.custom.iter().position(|(handle, _)| handle.eq(pipeline)) {
+                    Some(index) => index,
+                    None => {
+                        self.custom.push((pipeline.clone(), SpriteBatch::new(self.palette.is_some(), self.sorted)));
+                        self.custom.len() - 1
+                    },
+                };
+
+                &mut self.custom[index].1
+            },
+        }
+    }
 
-            Prerender {
-                vertices: 4,
-                instances,
-                pipeline,
-                bind_groups,
-                slices,
+    pub(crate) fn push(&mut self, sprite: GPUSprite, palette: Option<GPUPalette>, stencil: Stencil, pipeline: Option<&Handle>) -> usize {
+        self.batch_mut(pipeline).push(sprite, palette, stencil)
+    }
+
+    pub(crate) fn update(&mut self, index: usize, sprite: GPUSprite, palette: Option<GPUPalette>, pipeline: Option<&Handle>) {
+        self.batch_mut(pipeline).update(index, sprite, palette)
+    }
+
+    fn clear(&mut self) {
+        self.batch.clear();
+
+        // Batches for pipelines which are no longer used are removed
+        self.custom.retain_mut(|(_, batch)| {
+            let used = batch.has_sprites();
+            batch.clear();
+            used
+        });
+    }
+
+    fn prerender<'a>(
+        &'a mut self,
+        engine: &crate::EngineState,
+        scene_uniform: &'a wgpu::BindGroup,
+        builtin: &'a SpritesheetPipelines,
+        custom: &'a Handles<SpritesheetPipelines>,
+        compute: SpriteCompute<'a>,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        let palette = self.palette.is_some();
+        let bind_group = self.bind_group.as_ref();
+
+        self.batch.prerender(engine, self.label, !self.batched, scene_uniform, bind_group, builtin.get(palette), compute, prerender);
+
+        for (handle, batch) in self.custom.iter_mut() {
+            // The sprites aren't drawn if the pipeline was unloaded
+            if let Some(pipelines) = custom.get(handle) {
+                batch.prerender(engine, self.label, true, scene_uniform, bind_group, pipelines.get(palette), compute, prerender);
             }
+        }
+    }
+}
+
+
+/// Replaces the instances with `source`, it is only changed if they are different, which avoids uploading the buffer.
+fn replace_instances<T, I>(instances: &mut InstanceVec<T>, source: I) where T: Pod + PartialEq, I: Iterator<Item = T> + Clone {
+    if !instances.iter().copied().eq(source.clone()) {
+        instances.clear();
+        instances.extend(source);
+    }
+}
+
+
+/// The opaque sprites of multiple spritesheets which are drawn with the built-in sprite shader in a single draw call,
+/// see [`EngineSettings::sprite_batching`](crate::EngineSettings::sprite_batching).
+///
+/// The textures of the spritesheets are bound as a `binding_array`, and each sprite has a [`GPUTextureIndex`] which selects its spritesheet.
+/// Opaque sprites use the depth buffer, so they can be drawn in any order.
+pub(crate) struct SpritesheetBatch {
+    palette: bool,
+
+    /// The spritesheets in the batch, the texture index of a sprite is the index of its spritesheet.
+    spritesheets: Vec<Handle>,
+
+    instances: SpritesheetInstances,
+
+    /// This is `None` if the spritesheets changed.
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl SpritesheetBatch {
+    /// Must be kept in sync with the size of the `binding_array` in sprite.wgsl.
+    ///
+    /// A palette spritesheet uses 2 textures, so this fits into the default limit of 16 sampled textures per shader stage.
+    pub(crate) const MAX_SPRITESHEETS: u32 = 8;
+
+    fn new(palette: bool) -> Self {
+        Self {
+            palette,
+            spritesheets: vec![],
+            instances: SpritesheetInstances::new_batched(palette),
+            bind_group: None,
+        }
+    }
+
+    /// Copies the opaque sprites of the built-in pipeline from the spritesheets.
+    fn update(&mut self, spritesheets: &[&(Handle, SpritesheetState)]) {
+        let changed = self.spritesheets.len() != spritesheets.len() ||
+            !self.spritesheets.iter().zip(spritesheets).all(|(handle, (other, _))| handle.eq(other));
+
+        if changed {
+            self.spritesheets = spritesheets.iter().map(|(handle, _)| handle.clone()).collect();
+            self.bind_group = None;
+        }
+
+        let opaques = || spritesheets.iter().map(|(_, sheet)| &sheet.batch.opaque);
+
+        let instances = &mut self.instances;
+
+        instances.stencils.clear();
+
+        let mut start = 0;
+
+        for opaque in opaques() {
+            for (range, stencil) in opaque.stencils.ranges(opaque.sprites.len()) {
+                instances.stencils.push(start + range.start, stencil);
+            }
+
+            start += opaque.sprites.len();
+        }
+
+        replace_instances(&mut instances.sprites, opaques().flat_map(|opaque| opaque.sprites.iter().copied()));
+
+        if let Some(palettes) = &mut instances.palettes {
+            replace_instances(palettes, opaques().flat_map(|opaque| opaque.palettes.as_deref().into_iter().flatten().copied()));
+        }
+
+        if let Some(textures) = &mut instances.textures {
+            replace_instances(textures, opaques().enumerate().flat_map(|(index, opaque)| {
+                std::iter::repeat(GPUTextureIndex { texture: index as u32 }).take(opaque.sprites.len())
+            }));
+        }
+    }
+
+    fn make_bind_group(
+        &self,
+        engine: &crate::EngineState,
+        layout: &wgpu::BindGroupLayout,
+        textures: &Handles<TextureState>,
+        spritesheets: &[&(Handle, SpritesheetState)],
+    ) -> wgpu::BindGroup {
+        let view = |handle: &Handle| {
+            &textures.get(handle).expect("Spritesheet texture is not loaded").buffer().view
         };
 
-        (opaque, alpha)
+        // Every element of the binding_array must be bound, so the unused elements repeat the first spritesheet
+        let sheets = (0..Self::MAX_SPRITESHEETS as usize).map(|index| {
+            &spritesheets.get(index).unwrap_or(&spritesheets[0]).1
+        });
+
+        let texture_views = sheets.clone().map(|sheet| view(&sheet.texture)).collect::<Vec<_>>();
+
+        if self.palette {
+            let palette_views = sheets.map(|sheet| view(sheet.palette.as_ref().unwrap())).collect::<Vec<_>>();
+
+            builders::BindGroup::builder()
+                .label("Spritesheet Batch")
+                .layout(layout)
+                .texture_view_array(&texture_views)
+                .texture_view_array(&palette_views)
+                .build(engine)
+
+        } else {
+            builders::BindGroup::builder()
+                .label("Spritesheet Batch")
+                .layout(layout)
+                .texture_view_array(&texture_views)
+                .build(engine)
+        }
+    }
+
+    fn prerender<'a>(
+        &'a mut self,
+        engine: &crate::EngineState,
+        scene_uniform: &'a wgpu::BindGroup,
+        pipelines: &'a SpritesheetPipelines,
+        compute: SpriteCompute<'a>,
+        prerender: &mut ScenePrerender<'a>,
+    ) {
+        let Self { palette, instances, bind_group, .. } = self;
+
+        let bind_group = bind_group.as_ref().expect("SpritesheetBatch is missing bind group");
+
+        instances.prerender(engine, "Spritesheet Batch", false, vec![scene_uniform, bind_group], pipelines.get(*palette), compute, prerender);
+    }
+}
+
+
+/// The pipelines and batches for [`EngineSettings::sprite_batching`](crate::EngineSettings::sprite_batching).
+struct SpriteBatching {
+    normal_layout: wgpu::BindGroupLayout,
+    palette_layout: wgpu::BindGroupLayout,
+    pipelines: SpritesheetPipelines,
+    batches: Vec<SpritesheetBatch>,
+
+    /// Whether the textures of a spritesheet were replaced, so the bind groups must be recreated.
+    textures_changed: bool,
+}
+
+impl SpriteBatching {
+    const NORMAL_BUFFERS: &'static [wgpu::VertexBufferLayout<'static>] = &[GPUSprite::LAYOUT, GPUTextureIndex::LAYOUT];
+    const PALETTE_BUFFERS: &'static [wgpu::VertexBufferLayout<'static>] = &[GPUSprite::LAYOUT, GPUPalette::LAYOUT, GPUTextureIndex::LAYOUT];
+
+    fn new(engine: &crate::EngineState, scene_uniform_layout: &wgpu::BindGroupLayout) -> Self {
+        let count = NonZeroU32::new(SpritesheetBatch::MAX_SPRITESHEETS).unwrap();
+
+        let normal_layout = builders::BindGroupLayout::builder()
+            .label("Sprite Batch")
+            .texture_array(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false }, count)
+            .build(engine);
+
+        let palette_layout = builders::BindGroupLayout::builder()
+            .label("Sprite Batch")
+            .texture_array(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Uint, count)
+            .texture_array(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false }, count)
+            .build(engine);
+
+        let pipelines = SpritesheetPipelines {
+            normal: SpritesheetPipeline::new(
+                engine,
+                "Sprite Batch",
+                scene_uniform_layout,
+                wgsl!("spritesheet/sprite.wgsl", "BATCHED"),
+                Self::NORMAL_BUFFERS,
+                &normal_layout,
+            ),
+
+            palette: SpritesheetPipeline::new(
+                engine,
+                "Sprite Batch Palette",
+                scene_uniform_layout,
+                wgsl!("spritesheet/sprite.wgsl", "BATCHED", "PALETTE"),
+                Self::PALETTE_BUFFERS,
+                &palette_layout,
+            ),
+
+            animated: false,
+        };
+
+        Self {
+            normal_layout,
+            palette_layout,
+            pipelines,
+            batches: vec![],
+            textures_changed: false,
+        }
     }
 }
 
 
 pub(crate) struct SpriteRenderer {
-    normal: SpritesheetPipeline,
-    palette: SpritesheetPipeline,
-    spritesheets: Handles<SpritesheetState>,
+    normal_layout: wgpu::BindGroupLayout,
+    palette_layout: wgpu::BindGroupLayout,
+    builtin: SpritesheetPipelines,
+    custom: Handles<SpritesheetPipelines>,
+    pub(crate) spritesheets: Handles<SpritesheetState>,
+
+    /// Whether any sprite has a [`SpriteAnimation`].
+    animated: bool,
+
+    /// Whether any sprite uses an animated [`PipelineHandle`].
+    uses_time: bool,
+
+    /// The next tie index for each order, see [`next_tie`](SpriteRenderer::next_tie).
+    ties: HashMap<u32, u32>,
+    max_tie: u32,
+
+    /// This is `None` if GPU animation is disabled or not supported.
+    animator: Option<SpriteAnimator>,
+
+    /// This is `None` if GPU culling is disabled or not supported.
+    culling: Option<SpriteCulling>,
+
+    /// This is `None` if sprite batching is disabled or not supported.
+    batching: Option<SpriteBatching>,
 }
 
 impl SpriteRenderer {
+    /// Features which are needed for [`EngineSettings::sprite_batching`](crate::EngineSettings::sprite_batching).
+    pub(crate) const BATCHING_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_BINDING_ARRAY
+        .union(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+    const NORMAL_BUFFERS: &'static [wgpu::VertexBufferLayout<'static>] = &[GPUSprite::LAYOUT];
+    const PALETTE_BUFFERS: &'static [wgpu::VertexBufferLayout<'static>] = &[GPUSprite::LAYOUT, GPUPalette::LAYOUT];
+
+    /// Whether the adapter supports binding arrays of textures, the GL backend doesn't.
+    pub(crate) fn supports_batching(adapter: &wgpu::Adapter, limits: &wgpu::Limits) -> bool {
+        adapter.features().contains(Self::BATCHING_FEATURES) &&
+        limits.max_sampled_textures_per_shader_stage >= SpritesheetBatch::MAX_SPRITESHEETS * 2
+    }
+
     #[inline]
     pub(crate) fn new(engine: &crate::EngineState, scene_uniform: &mut Uniform<SceneUniform>) -> Self {
         let scene_uniform_layout = Uniform::bind_group_layout(scene_uniform, engine);
 
-        let normal = SpritesheetPipeline::new(
-            engine,
-            scene_uniform_layout,
+        let normal_layout = builders::BindGroupLayout::builder()
+            .label("Sprite")
+            .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false })
+            .build(engine);
 
-            // TODO lazy load this ?
-            wgsl![
-                "spritesheet/normal.wgsl",
-                SCENE_SHADER,
-                SPRITE_SHADER,
-                include_str!("../wgsl/spritesheet/normal.wgsl"),
-            ],
+        let palette_layout = builders::BindGroupLayout::builder()
+            .label("Sprite")
+            .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Uint)
+            .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false })
+            .build(engine);
 
-            &[GPUSprite::LAYOUT],
+        let builtin = SpritesheetPipelines {
+            normal: SpritesheetPipeline::new(
+                engine,
+                "Sprite",
+                scene_uniform_layout,
+                wgsl!("spritesheet/sprite.wgsl"),
+                Self::NORMAL_BUFFERS,
+                &normal_layout,
+            ),
+
+            palette: SpritesheetPipeline::new(
+                engine,
+                "Sprite Palette",
+                scene_uniform_layout,
+                wgsl!("spritesheet/sprite.wgsl", "PALETTE"),
+                Self::PALETTE_BUFFERS,
+                &palette_layout,
+            ),
 
-            builders::BindGroupLayout::builder()
-                .label("Sprite")
-                .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false })
-                .build(engine),
-        );
+            // The built-in shader only uses the time for sprites with a SpriteAnimation
+            animated: false,
+        };
 
-        let palette = SpritesheetPipeline::new(
-            engine,
-            scene_uniform_layout,
+        Self {
+            normal_layout,
+            palette_layout,
+            builtin,
+            custom: Handles::new(),
+            spritesheets: Handles::new(),
+            animated: false,
+            uses_time: false,
+            ties: HashMap::new(),
+            max_tie: 0,
+            animator: if engine.gpu_animation { Some(SpriteAnimator::new(engine, scene_uniform_layout)) } else { None },
+            culling: if engine.gpu_culling { Some(SpriteCulling::new(engine)) } else { None },
+            batching: if engine.sprite_batching { Some(SpriteBatching::new(engine, scene_uniform_layout)) } else { None },
+        }
+    }
 
-            // TODO lazy load this ?
-            wgsl![
-                "spritesheet/palette.wgsl",
-                SCENE_SHADER,
-                SPRITE_SHADER,
-                include_str!("../wgsl/spritesheet/palette.wgsl"),
-            ],
+    fn make_bind_group(
+        normal_layout: &wgpu::BindGroupLayout,
+        palette_layout: &wgpu::BindGroupLayout,
+        engine: &crate::EngineState,
+        texture: &TextureBuffer,
+        palette: Option<&TextureBuffer>,
+    ) -> wgpu::BindGroup {
+        if let Some(palette) = palette {
+            assert_eq!(texture.texture.format(), IndexedImage::FORMAT, "texture must be an IndexedImage");
+            assert_eq!(palette.texture.format(), RgbaImage::FORMAT, "palette must be an RgbaImage");
 
-            &[GPUSprite::LAYOUT, GPUPalette::LAYOUT],
+            builders::BindGroup::builder()
+                .label("Spritesheet")
+                .layout(palette_layout)
+                .texture_view(&texture.view)
+                .texture_view(&palette.view)
+                .build(engine)
 
-            builders::BindGroupLayout::builder()
-                .label("Sprite")
-                .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Uint)
-                .texture(wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false })
-                .build(engine),
-        );
+        } else {
+            assert_eq!(texture.texture.format(), RgbaImage::FORMAT, "texture must be an RgbaImage");
 
-        Self {
-            normal,
-            palette,
-            spritesheets: Handles::new(),
+            builders::BindGroup::builder()
+                .label("Spritesheet")
+                .layout(normal_layout)
+                .texture_view(&texture.view)
+                .build(engine)
         }
     }
 
-    fn new_spritesheet(&mut self, engine: &crate::EngineState, handle: &Handle, texture: &TextureBuffer, palette: Option<&TextureBuffer>) {
-        let opaque = SpritesheetInstances {
-            sprites: InstanceVec::new(),
-            palettes: palette.map(|_| InstanceVec::new()),
-        };
+    fn new_spritesheet(
+        &mut self,
+        engine: &crate::EngineState,
+        textures: &Handles<TextureState>,
+        handle: &Handle,
+        label: &'static str,
+        draw_order: i32,
+        sorted: bool,
+        texture_handle: &Handle,
+        palette_handle: Option<&Handle>,
+    ) {
+        let texture = textures.get(texture_handle)
+            .expect("SpritesheetSettings texture is not loaded")
+            .buffer();
 
-        let alpha = SpritesheetInstances {
-            sprites: InstanceVec::new(),
-            palettes: palette.map(|_| InstanceVec::new()),
+        let palette = palette_handle.map(|palette| {
+            textures.get(palette)
+                .expect("SpritesheetSettings palette is not loaded")
+                .buffer()
+        });
+
+        // The built-in pipeline is compiled when the first spritesheet which needs it is loaded
+        self.builtin.get_mut(palette.is_some()).init(engine);
+
+        let bind_group = Self::make_bind_group(&self.normal_layout, &self.palette_layout, engine, texture, palette);
+
+        let state = SpritesheetState {
+            label,
+            draw_order,
+            sorted,
+            batch: SpriteBatch::new(palette.is_some(), sorted),
+            custom: vec![],
+            texture: texture_handle.clone(),
+            palette: palette_handle.cloned(),
+            bind_group: Some(bind_group),
+            batched: false,
         };
 
-        let state = if let Some(palette) = palette {
-            assert_eq!(texture.texture.format(), IndexedImage::FORMAT, "texture must be an IndexedImage");
-            assert_eq!(palette.texture.format(), RgbaImage::FORMAT, "palette must be an RgbaImage");
+        self.spritesheets.insert(handle, state);
+        self.spritesheets.sort_by_key(|sheet| sheet.draw_order);
 
-            SpritesheetState {
-                opaque,
-                alpha,
-                bind_group: builders::BindGroup::builder()
-                    .label("Spritesheet")
-                    .layout(&self.palette.bind_group_layout)
-                    .texture_view(&texture.view)
-                    .texture_view(&palette.view)
-                    .build(engine),
-            }
+        // The spritesheet can be loaded again with different textures
+        self.batch_textures_changed();
+    }
 
-        } else {
-            assert_eq!(texture.texture.format(), RgbaImage::FORMAT, "texture must be an RgbaImage");
+    /// Must be called when the bind group of a spritesheet is recreated, so the [`SpritesheetBatch`] bind groups are also recreated.
+    fn batch_textures_changed(&mut self) {
+        if let Some(batching) = &mut self.batching {
+            batching.textures_changed = true;
+        }
+    }
 
-            SpritesheetState {
-                opaque,
-                alpha,
-                bind_group: builders::BindGroup::builder()
-                    .label("Spritesheet")
-                    .layout(&self.normal.bind_group_layout)
-                    .texture_view(&texture.view)
-                    .build(engine),
+    fn new_custom_pipeline(
+        &mut self,
+        engine: &crate::EngineState,
+        scene_uniform: &mut Uniform<SceneUniform>,
+        handle: &Handle,
+        label: &'static str,
+        fragment: &'static str,
+        animated: bool,
+    ) {
+        let scene_uniform_layout = Uniform::bind_group_layout(scene_uniform, engine);
+
+        let shader = |defines: &[&'static str]| {
+            wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(crate::util::wgsl::preprocess_with("spritesheet/sprite.wgsl", defines, label, fragment).into()),
             }
         };
 
-        self.spritesheets.insert(handle, state);
+        let pipelines = SpritesheetPipelines {
+            normal: SpritesheetPipeline::new(
+                engine,
+                "Sprite",
+                scene_uniform_layout,
+                shader(&["CUSTOM_FRAGMENT"]),
+                Self::NORMAL_BUFFERS,
+                &self.normal_layout,
+            ),
+
+            palette: SpritesheetPipeline::new(
+                engine,
+                "Sprite Palette",
+                scene_uniform_layout,
+                shader(&["CUSTOM_FRAGMENT", "PALETTE"]),
+                Self::PALETTE_BUFFERS,
+                &self.palette_layout,
+            ),
+
+            animated,
+        };
+
+        self.custom.insert(handle, pipelines);
+    }
+
+    fn remove_custom_pipeline(&mut self, handle: &Handle) {
+        self.custom.remove(handle);
+    }
+
+    fn spritesheet_texture(&self, handle: &Handle) -> Option<&Handle> {
+        self.spritesheets.get(handle).map(|sheet| &sheet.texture)
     }
 
     fn remove_spritesheet(&mut self, handle: &Handle) {
         self.spritesheets.remove(handle);
     }
 
-    #[inline]
-    pub(crate) fn before_layout(&mut self) {
+    fn evict_spritesheet(&mut self, handle: &Handle, textures: &mut Handles<TextureState>) {
+        let texture_handles = match self.spritesheets.get_mut(handle) {
+            Some(sheet) => {
+                tracing::debug!(label = sheet.label, "Spritesheet evicted");
+
+                sheet.bind_group = None;
+
+                std::iter::once(sheet.texture.clone()).chain(sheet.palette.clone()).collect::<Vec<Handle>>()
+            },
+            None => return,
+        };
+
+        for handle in texture_handles {
+            // Textures can be shared between spritesheets, so it's only evicted if it isn't used anymore
+            let in_use = self.spritesheets.iter().any(|(_, sheet)| {
+                sheet.bind_group.is_some() && sheet.uses_texture(&handle)
+            });
+
+            if !in_use {
+                if let Some(texture) = textures.get_mut(&handle) {
+                    texture.evict();
+                }
+            }
+        }
+    }
+
+    fn add_palette(&mut self, engine: &crate::EngineState, textures: &mut Handles<TextureState>, handle: &Handle, image: &RgbaImage) -> Result<u32, PaletteError> {
+        let palette_handle = self.spritesheets.get(handle)
+            .expect("Spritesheet is not loaded")
+            .palette.clone()
+            .ok_or(PaletteError::NoPalette)?;
+
+        let palette = textures.get_mut(&palette_handle).expect("Spritesheet palette is not loaded");
+
+        let size = palette.size();
+        let (width, height) = image.dimensions();
+
+        if width != size.width {
+            return Err(PaletteError::WidthMismatch { expected: size.width, found: width });
+        }
+
+        let max = engine.device.limits().max_texture_dimension_2d;
+        let new_height = size.height.saturating_add(height);
+
+        if new_height > max {
+            return Err(PaletteError::TooManyRows { height: new_height, max });
+        }
+
+        let index = palette.append_rows(engine, image);
+
+        // The palette texture was replaced, so the bind groups need to be recreated
         for (_, sheet) in self.spritesheets.iter_mut() {
-            sheet.opaque.sprites.clear();
+            if sheet.bind_group.is_some() && sheet.uses_texture(&palette_handle) {
+                let texture = textures.get(&sheet.texture)
+                    .expect("Spritesheet texture is not loaded")
+                    .buffer();
+
+                let palette = sheet.palette.as_ref().map(|palette| {
+                    textures.get(palette)
+                        .expect("Spritesheet palette is not loaded")
+                        .buffer()
+                });
+
+                sheet.bind_group = Some(Self::make_bind_group(&self.normal_layout, &self.palette_layout, engine, texture, palette));
+            }
+        }
 
-            if let Some(palettes) = &mut sheet.opaque.palettes {
-                palettes.clear();
+        self.batch_textures_changed();
+
+        Ok(index)
+    }
+
+    /// Uploads the textures for evicted spritesheets which have sprites.
+    pub(crate) fn restore_evicted(&mut self, engine: &crate::EngineState, textures: &mut Handles<TextureState>) {
+        let mut restored = false;
+
+        for (_, sheet) in self.spritesheets.iter_mut() {
+            if sheet.bind_group.is_none() && sheet.has_sprites() {
+                restored = true;
+
+                tracing::debug!(label = sheet.label, "Spritesheet restored");
+
+                for handle in std::iter::once(&sheet.texture).chain(sheet.palette.as_ref()) {
+                    if let Some(texture) = textures.get_mut(handle) {
+                        texture.upload(engine);
+                    }
+                }
+
+                let texture = textures.get(&sheet.texture)
+                    .expect("Spritesheet texture is not loaded")
+                    .buffer();
+
+                let palette = sheet.palette.as_ref().map(|palette| {
+                    textures.get(palette)
+                        .expect("Spritesheet palette is not loaded")
+                        .buffer()
+                });
+
+                sheet.bind_group = Some(Self::make_bind_group(&self.normal_layout, &self.palette_layout, engine, texture, palette));
             }
+        }
 
-            sheet.alpha.sprites.clear();
+        if restored {
+            self.batch_textures_changed();
+        }
+    }
 
-            if let Some(palettes) = &mut sheet.alpha.palettes {
-                palettes.clear();
+    /// Groups the opaque sprites of the built-in pipeline into [`SpritesheetBatch`]es, this must be called after
+    /// [`restore_evicted`](SpriteRenderer::restore_evicted). It does nothing if sprite batching is disabled.
+    ///
+    /// Spritesheets with and without a palette are batched separately, and a spritesheet is only batched
+    /// if there is another spritesheet to batch it with. The other spritesheets are drawn separately.
+    pub(crate) fn update_batches(&mut self, engine: &crate::EngineState, textures: &Handles<TextureState>) {
+        let SpriteBatching { normal_layout, palette_layout, batches, textures_changed, .. } = match &mut self.batching {
+            Some(batching) => batching,
+            None => return,
+        };
+
+        let mut count = 0;
+
+        for palette in [false, true] {
+            // Evicted spritesheets don't have any sprites
+            let spritesheets = self.spritesheets.iter()
+                .filter(|(_, sheet)| sheet.palette.is_some() == palette && sheet.bind_group.is_some() && sheet.batch.opaque.sprites.len() > 0)
+                .collect::<Vec<_>>();
+
+            for spritesheets in spritesheets.chunks(SpritesheetBatch::MAX_SPRITESHEETS as usize) {
+                // A single spritesheet is already drawn with a single draw call
+                if spritesheets.len() < 2 {
+                    continue;
+                }
+
+                match batches.get_mut(count) {
+                    Some(batch) if batch.palette == palette => {},
+                    Some(batch) => *batch = SpritesheetBatch::new(palette),
+                    None => batches.push(SpritesheetBatch::new(palette)),
+                }
+
+                let batch = &mut batches[count];
+
+                batch.update(spritesheets);
+
+                if batch.bind_group.is_none() || *textures_changed {
+                    let layout = if palette { &*palette_layout } else { &*normal_layout };
+                    batch.bind_group = Some(batch.make_bind_group(engine, layout, textures, spritesheets));
+                }
+
+                count += 1;
+            }
+        }
+
+        batches.truncate(count);
+        *textures_changed = false;
+
+        for (handle, sheet) in self.spritesheets.iter_mut() {
+            sheet.batched = batches.iter().any(|batch| batch.spritesheets.iter().any(|other| other.eq(handle)));
+        }
+    }
+
+    /// Compiles every built-in pipeline, see [`Engine::warmup`](crate::Engine::warmup).
+    ///
+    /// Custom pipelines are compiled when they are first used, because it's not known
+    /// whether they will be used with palette spritesheets or not.
+    pub(crate) fn warmup(&mut self, engine: &crate::EngineState) {
+        for pipeline in [&mut self.builtin.normal, &mut self.builtin.palette] {
+            pipeline.init(engine);
+            pipeline.init_masked(engine);
+        }
+
+        if let Some(batching) = &mut self.batching {
+            for pipeline in [&mut batching.pipelines.normal, &mut batching.pipelines.palette] {
+                pipeline.init(engine);
+                pipeline.init_masked(engine);
             }
         }
     }
 
+    #[inline]
+    pub(crate) fn before_layout(&mut self) {
+        for (_, sheet) in self.spritesheets.iter_mut() {
+            sheet.clear();
+        }
+
+        self.ties.clear();
+        self.max_tie = 0;
+    }
+
+    /// Returns how many sprites with the same order were laid out before this sprite.
+    ///
+    /// The shaders use this to draw the sprite which is later in the scene on top,
+    /// even if the sprites are in different spritesheets. See [`depth`](crate::depth).
+    pub(crate) fn next_tie(&mut self, order: f32) -> u32 {
+        let next = self.ties.entry(order.to_bits()).or_insert(0);
+
+        let tie = *next;
+
+        *next = (tie + 1).min(GPUSprite::MAX_TIE);
+
+        self.max_tie = self.max_tie.max(tie);
+
+        tie
+    }
+
+    /// The largest [`next_tie`](SpriteRenderer::next_tie) of the current layout.
+    #[inline]
+    pub(crate) fn max_tie(&self) -> u32 {
+        self.max_tie
+    }
+
     #[inline]
     pub(crate) fn before_render(&mut self) {}
 
+    /// Whether any sprite has a [`SpriteAnimation`].
+    #[inline]
+    pub(crate) fn is_animated(&self) -> bool {
+        self.animated
+    }
+
+    /// Whether any sprite uses an animated [`PipelineHandle`].
+    #[inline]
+    pub(crate) fn uses_time(&self) -> bool {
+        self.uses_time
+    }
+
+    pub(crate) fn update_animated(&mut self) {
+        let custom = &self.custom;
+
+        self.animated = self.spritesheets.iter().any(|(_, sheet)| {
+            sheet.batches().any(SpriteBatch::is_animated)
+        });
+
+        self.uses_time = self.spritesheets.iter().any(|(_, sheet)| {
+            sheet.custom.iter().any(|(handle, batch)| {
+                batch.has_sprites() && custom.get(handle).map_or(false, |pipeline| pipeline.animated)
+            })
+        });
+    }
+
     #[inline]
     pub(crate) fn prerender<'a>(
         &'a mut self,
@@ -728,21 +1820,115 @@ impl SpriteRenderer {
         prerender.opaques.reserve(self.spritesheets.len());
         prerender.alphas.reserve(self.spritesheets.len());
 
+        for (_, sheet) in self.spritesheets.iter() {
+            let palette = sheet.palette.is_some();
+
+            if sheet.batch.is_masked() {
+                self.builtin.get_mut(palette).init_masked(engine);
+            }
+
+            for (handle, batch) in sheet.custom.iter() {
+                if let Some(pipelines) = self.custom.get_mut(handle) {
+                    let pipeline = pipelines.get_mut(palette);
+
+                    pipeline.init(engine);
+
+                    if batch.is_masked() {
+                        pipeline.init_masked(engine);
+                    }
+                }
+            }
+        }
+
+        if let Some(batching) = &mut self.batching {
+            for batch in batching.batches.iter() {
+                let pipeline = batching.pipelines.get_mut(batch.palette);
+
+                pipeline.init(engine);
+
+                if batch.instances.stencils.is_masked() {
+                    pipeline.init_masked(engine);
+                }
+            }
+        }
+
+        let compute = SpriteCompute {
+            // The frames only need to be selected while there are animated sprites
+            animator: self.animator.as_ref().filter(|_| self.animated),
+            culling: self.culling.as_ref(),
+        };
+
+        // The spritesheets are sorted by draw_order
         for (_, sheet) in self.spritesheets.iter_mut() {
-            let (opaque, alpha) = sheet.prerender(engine, scene_uniform, &self.normal, &self.palette);
+            sheet.prerender(engine, scene_uniform, &self.builtin, &self.custom, compute, prerender);
+        }
 
-            prerender.opaques.push(opaque);
-            prerender.alphas.push(alpha);
+        if let Some(SpriteBatching { pipelines, batches, .. }) = &mut self.batching {
+            for batch in batches.iter_mut() {
+                batch.prerender(engine, scene_uniform, pipelines, compute, prerender);
+            }
         }
     }
 }
 
 
 pub struct SpritesheetSettings<'a, 'b> {
+    /// Used for debugging and for [`Engine::stats`](crate::Engine::stats).
+    pub label: &'static str,
+
     pub texture: &'a Texture,
     pub palette: Option<&'b Texture>,
+
+    /// The spritesheets are drawn from the lowest to the highest `draw_order`,
+    /// spritesheets with the same `draw_order` are drawn in load order.
+    ///
+    /// Sprites with the same [`Order`] are displayed in scene order even if they are
+    /// in different spritesheets, see [`depth`](crate::depth). But transparent sprites only
+    /// blend with the sprites which were drawn before them, so the spritesheet which is
+    /// usually on top should have the higher `draw_order`.
+    pub draw_order: i32,
+
+    /// Whether the transparent sprites should be sorted by [`Order`] before drawing.
+    ///
+    /// This is needed for transparent sprites to blend correctly with each other,
+    /// but it has a performance cost, so it should only be used when needed.
+    ///
+    /// Opaque sprites use the depth buffer, so they never need to be sorted.
+    pub sorted: bool,
 }
 
+/// The reason why [`Spritesheet::add_palette`] failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteError {
+    /// The spritesheet was loaded without a palette.
+    NoPalette,
+
+    /// The image is not the same width as the palette.
+    WidthMismatch {
+        expected: u32,
+        found: u32,
+    },
+
+    /// The palette would be taller than the GPU's maximum texture size.
+    TooManyRows {
+        height: u32,
+        max: u32,
+    },
+}
+
+impl std::fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPalette => write!(f, "Spritesheet does not support palette"),
+            Self::WidthMismatch { expected, found } => write!(f, "Palette width {} does not match the spritesheet palette width {}", found, expected),
+            Self::TooManyRows { height, max } => write!(f, "Palette height {} is larger than the maximum texture size {}", height, max),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+
 #[derive(Clone)]
 pub struct Spritesheet {
     pub(crate) handle: Handle,
@@ -755,20 +1941,61 @@ impl Spritesheet {
     }
 
     pub fn load<'a, 'b>(&self, engine: &mut crate::Engine, settings: SpritesheetSettings<'a, 'b>) {
-        let texture = engine.scene.textures.get(&settings.texture.handle)
-            .expect("SpritesheetSettings texture is not loaded");
-
-        let palette = settings.palette.map(|palette| {
-            engine.scene.textures.get(&palette.handle)
-                .expect("SpritesheetSettings palette is not loaded")
-        });
-
-        engine.scene.renderer.sprite.new_spritesheet(&engine.state, &self.handle, texture, palette);
+        tracing::debug!(label = settings.label, "Spritesheet loaded");
+
+        engine.scene.renderer.sprite.new_spritesheet(
+            &engine.state,
+            &engine.scene.textures,
+            &self.handle,
+            settings.label,
+            settings.draw_order,
+            settings.sorted,
+            &settings.texture.handle,
+            settings.palette.map(|palette| &palette.handle),
+        );
 
         // TODO test this
         engine.scene.changed.trigger_layout_change();
     }
 
+    /// Returns the size of the spritesheet's texture, or `None` if the spritesheet isn't loaded.
+    ///
+    /// This can be used with [`TextureSize::tiles`] to find how many tiles are in the spritesheet.
+    pub fn size(&self, engine: &crate::Engine) -> Option<TextureSize> {
+        let texture = engine.scene.renderer.sprite.spritesheet_texture(&self.handle)?;
+        engine.scene.textures.get(texture).map(|texture| texture.size())
+    }
+
+    /// Adds the rows of `image` to the end of the spritesheet's palette, and returns the index of the first new row.
+    ///
+    /// The index can be used with [`SpriteBuilder::palette`], which allows for loading extra palettes at runtime.
+    /// The image must be the same width as the palette, and the palette can't be taller than the GPU's maximum texture size.
+    /// If it fails then the palette is unchanged.
+    ///
+    /// The palette texture is shared, so the new rows are also added to other spritesheets which use the same palette.
+    pub fn add_palette(&self, engine: &mut crate::Engine, image: &RgbaImage) -> Result<u32, PaletteError> {
+        let index = engine.scene.renderer.sprite.add_palette(
+            &engine.state,
+            &mut engine.scene.textures,
+            &self.handle,
+            image,
+        )?;
+
+        engine.scene.changed.trigger_render_change();
+
+        Ok(index)
+    }
+
+    /// Frees the GPU memory for the spritesheet's textures, which is useful for spritesheets that aren't being used.
+    ///
+    /// The textures must be loaded with [`Texture::load_retained`](crate::Texture::load_retained).
+    /// They will be automatically uploaded again when a [`Sprite`] uses this spritesheet.
+    ///
+    /// Textures which are shared with another spritesheet are only evicted after every spritesheet using them is evicted.
+    pub fn evict(&self, engine: &mut crate::Engine) {
+        engine.scene.renderer.sprite.evict_spritesheet(&self.handle, &mut engine.scene.textures);
+    }
+
     pub fn unload(&self, engine: &mut crate::Engine) {
         engine.scene.renderer.sprite.remove_spritesheet(&self.handle);
 
@@ -776,3 +2003,76 @@ impl Spritesheet {
         engine.scene.changed.trigger_layout_change();
     }
 }
+
+
+pub struct CustomPipelineSettings {
+    /// Used for debugging and for shader errors.
+    pub label: &'static str,
+
+    /// The WGSL source code for the fragment shader, see [`PipelineHandle`].
+    pub fragment: &'static str,
+
+    /// Whether the shader uses `scene.time`, which means the scene is re-rendered
+    /// every frame while a sprite is using the pipeline.
+    pub animated: bool,
+}
+
+/// A custom fragment shader which is used by [`SpriteBuilder::custom_pipeline`],
+/// for special effects such as heat shimmer or auras.
+///
+/// The fragment shader must define the `fs_main` function:
+///
+/// ```wgsl
+/// @fragment
+/// fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+///     let uv = tile_uv(normalize_uv(in.uv), in.tile);
+///     ...
+/// }
+/// ```
+///
+/// It has access to everything in the built-in sprite shader, such as the `VertexOutput`
+/// varyings, the `spritesheet` texture, the `scene` uniform, and the helper functions.
+///
+/// Time based effects should use `scene.time` and set [`CustomPipelineSettings::animated`],
+/// rather than updating the sprite every frame.
+///
+/// The same shader is used for spritesheets with and without a palette, `#ifdef PALETTE`
+/// can be used to handle both, the other preprocessor directives are also supported.
+///
+/// Each custom pipeline is a separate draw call for each spritesheet, so it should be used sparingly.
+/// The pipeline is compiled when it is first used, an invalid shader will panic at that point.
+///
+/// Compiled pipelines are cached by their shader source, so pipelines with the same `fragment`
+/// are only compiled once, and loading a pipeline again after unloading it doesn't recompile it.
+#[derive(Clone)]
+pub struct PipelineHandle {
+    pub(crate) handle: Handle,
+}
+
+impl PipelineHandle {
+    #[inline]
+    pub fn new() -> Self {
+        Self { handle: Handle::new() }
+    }
+
+    pub fn load(&self, engine: &mut crate::Engine, settings: CustomPipelineSettings) {
+        tracing::debug!(label = settings.label, "Custom pipeline loaded");
+
+        engine.scene.renderer.sprite.new_custom_pipeline(
+            &engine.state,
+            &mut engine.scene.renderer.scene_uniform,
+            &self.handle,
+            settings.label,
+            settings.fragment,
+            settings.animated,
+        );
+
+        engine.scene.changed.trigger_render_change();
+    }
+
+    /// Sprites which use the pipeline aren't displayed until the pipeline is loaded again.
+    pub fn unload(&self, engine: &mut crate::Engine) {
+        engine.scene.renderer.sprite.remove_custom_pipeline(&self.handle);
+        engine.scene.changed.trigger_render_change();
+    }
+}
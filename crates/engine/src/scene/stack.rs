@@ -2,7 +2,7 @@ use futures_signals::signal::{Signal, SignalExt};
 use futures_signals::signal_vec::{SignalVec, SignalVecExt};
 use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, children_methods};
 use crate::scene::{
-    NodeHandle, Location, Origin, Size, Offset, Padding, SmallestSize, Order,
+    NodeHandle, NodeRef, Location, Origin, Size, Offset, Padding, SmallestSize, Order,
     RealLocation, NodeLayout, SceneLayoutInfo, SceneRenderInfo, RealSize,
 };
 
@@ -26,6 +26,7 @@ struct Child {
 /// * [`Length::SmallestHeight`]: the maximum of all the children's smallest height.
 pub struct Stack {
     visible: bool,
+    node_ref: Option<NodeRef>,
     location: Location,
     children: Vec<NodeHandle>,
 
@@ -37,6 +38,7 @@ impl Stack {
     fn new() -> Self {
         Self {
             visible: true,
+            node_ref: None,
             location: Location::default(),
             children: vec![],
 
@@ -82,7 +84,7 @@ children_methods!(Stack, StackBuilder);
 impl NodeLayout for Stack {
     #[inline]
     fn is_visible(&mut self) -> bool {
-        self.visible
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
     }
 
     fn smallest_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
@@ -98,11 +100,19 @@ impl NodeLayout for Stack {
     fn update_layout<'a>(&mut self, _handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
         let this_location = self.location.children_location(parent, &smallest_size.real_size(), &info);
 
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(this_location);
+        }
+
+        info.enter_node("Stack", &this_location);
+
         for child in self.computed_children.iter() {
             let mut lock = child.handle.lock();
             lock.update_layout(&child.handle, &this_location, &child.size, info);
         }
 
+        info.exit_node();
+
         self.computed_children.clear();
     }
 
@@ -0,0 +1,362 @@
+use proptest::prelude::*;
+use super::*;
+
+
+const EPSILON: f32 = 1e-4;
+
+fn approx_eq(x: f32, y: f32) -> bool {
+    (x - y).abs() <= EPSILON * x.abs().max(y.abs()).max(1.0)
+}
+
+/// Like [`approx_eq`], but for values which were computed by subtracting numbers as large as `scale`,
+/// because the rounding error is relative to the numbers which were subtracted.
+fn approx_eq_scaled(x: f32, y: f32, scale: f32) -> bool {
+    (x - y).abs() <= EPSILON * x.abs().max(y.abs()).max(scale.abs()).max(1.0)
+}
+
+
+fn screen_size() -> impl Strategy<Value = ScreenSize> {
+    (1u32..4096, 1u32..4096).prop_map(|(width, height)| ScreenSize::new(width as f32, height as f32, 1.0))
+}
+
+fn real_size() -> impl Strategy<Value = RealSize> {
+    (0.0f32..4.0, 0.0f32..4.0).prop_map(|(width, height)| RealSize { width, height })
+}
+
+fn real_location() -> impl Strategy<Value = RealLocation> {
+    (-2.0f32..2.0, -2.0f32..2.0, real_size(), 0.0f32..100.0).prop_map(|(x, y, size, order)| RealLocation {
+        position: RealPosition { x, y },
+        size,
+        order,
+    })
+}
+
+/// Lengths which are never negative.
+fn length() -> impl Strategy<Value = Length> + Clone {
+    prop_oneof![
+        Just(Length::Zero),
+        (0i32..8192).prop_map(Length::Px),
+        (0.0f32..2.0).prop_map(Length::ScreenWidth),
+        (0.0f32..2.0).prop_map(Length::ScreenHeight),
+        (0.0f32..2.0).prop_map(Length::ParentWidth),
+        (0.0f32..2.0).prop_map(Length::ParentHeight),
+        (0.0f32..2.0).prop_map(Length::SmallestWidth),
+        (0.0f32..2.0).prop_map(Length::SmallestHeight),
+    ]
+}
+
+/// Lengths which don't depend on the parent or smallest size.
+fn screen_length() -> impl Strategy<Value = Length> + Clone {
+    prop_oneof![
+        Just(Length::Zero),
+        (0i32..8192).prop_map(Length::Px),
+        (0.0f32..2.0).prop_map(Length::ScreenWidth),
+        (0.0f32..2.0).prop_map(Length::ScreenHeight),
+    ]
+}
+
+fn padding(length: impl Strategy<Value = Length> + Clone) -> impl Strategy<Value = Padding> {
+    (length.clone(), length.clone(), length.clone(), length).prop_map(|(up, down, left, right)| Padding { up, down, left, right })
+}
+
+fn location() -> impl Strategy<Value = Location> {
+    (
+        (length(), length()),
+        (length(), length()),
+        padding(length()),
+        (0.0f32..1.0, 0.0f32..1.0),
+        prop_oneof![
+            (1.0f32..10.0).prop_map(Order::Global),
+            (1.0f32..10.0).prop_map(Order::Parent),
+            (1.0f32..10.0).prop_map(Order::Above),
+        ],
+    ).prop_map(|((x, y), (width, height), padding, (origin_x, origin_y), order)| Location {
+        offset: Offset { x, y },
+        size: Size { width, height },
+        padding,
+        origin: Origin { x: origin_x, y: origin_y },
+        order,
+    })
+}
+
+
+proptest! {
+    /// Converting a [`Length`] into a [`SmallestLength`] and then into screen space must give
+    /// the same result as converting it directly into screen space.
+    #[test]
+    fn length_screen_space_matches(length in length(), parent in real_size(), smallest in real_size(), screen in screen_size()) {
+        for screen_length in [&screen.width, &screen.height] {
+            let expected = length.real_length(&parent, &smallest, screen_length);
+
+            let actual = length.smallest_length(screen_length)
+                .to_screen(&parent.smallest_size(), &smallest.smallest_size())
+                .unwrap();
+
+            prop_assert!(approx_eq(actual, expected), "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn length_non_negative(length in length(), parent in real_size(), smallest in real_size(), screen in screen_size()) {
+        prop_assert!(length.real_length(&parent, &smallest, &screen.width) >= 0.0);
+        prop_assert!(length.real_length(&parent, &smallest, &screen.height) >= 0.0);
+    }
+
+    /// A fixed number of pixels must cover that many pixels on the screen.
+    #[test]
+    fn length_px_round_trip(px in 0i32..8192, screen in screen_size()) {
+        let width = Length::Px(px).smallest_length(&screen.width).unwrap();
+        let height = Length::Px(px).smallest_length(&screen.height).unwrap();
+
+        prop_assert!(approx_eq(width * screen.width.pixels, px as f32));
+        prop_assert!(approx_eq(height * screen.height.pixels, px as f32));
+    }
+
+    /// The UI scale must multiply pixel lengths.
+    #[test]
+    fn length_px_ui_scale(px in 0i32..8192, width in 1u32..4096, height in 1u32..4096, ui_scale in 0.5f32..4.0) {
+        let screen = ScreenSize::new(width as f32, height as f32, ui_scale);
+
+        let real_width = Length::Px(px).smallest_length(&screen.width).unwrap();
+        let real_height = Length::Px(px).smallest_length(&screen.height).unwrap();
+
+        prop_assert!(approx_eq(real_width * screen.width.pixels, px as f32 * ui_scale));
+        prop_assert!(approx_eq(real_height * screen.height.pixels, px as f32 * ui_scale));
+    }
+
+    /// Screen lengths must be relative to the same axis no matter which dimension they are used for.
+    #[test]
+    fn length_screen_ratio(x in 0.0f32..2.0, screen in screen_size()) {
+        let width_as_width = Length::ScreenWidth(x).smallest_length(&screen.width).unwrap() * screen.width.pixels;
+        let width_as_height = Length::ScreenWidth(x).smallest_length(&screen.height).unwrap() * screen.height.pixels;
+
+        let height_as_width = Length::ScreenHeight(x).smallest_length(&screen.width).unwrap() * screen.width.pixels;
+        let height_as_height = Length::ScreenHeight(x).smallest_length(&screen.height).unwrap() * screen.height.pixels;
+
+        prop_assert!(approx_eq(width_as_width, width_as_height));
+        prop_assert!(approx_eq(height_as_width, height_as_height));
+    }
+
+
+    #[test]
+    fn padding_non_negative(padding in padding(length()), parent in real_size(), smallest in real_size(), screen in screen_size()) {
+        let size = padding.to_screen(&parent.smallest_size(), &smallest.smallest_size(), &screen);
+
+        prop_assert!(size.width >= 0.0);
+        prop_assert!(size.height >= 0.0);
+    }
+
+    #[test]
+    fn padding_matches_real_padding(padding in padding(length()), parent in real_size(), smallest in real_size(), screen in screen_size()) {
+        let size = padding.to_screen(&parent.smallest_size(), &smallest.smallest_size(), &screen);
+        let real = padding.real_padding(&parent, &smallest, &screen);
+
+        prop_assert!(approx_eq(size.width, real.left + real.right));
+        prop_assert!(approx_eq(size.height, real.up + real.down));
+    }
+
+    /// Subtracting the padding from the parent must never give a negative size.
+    #[test]
+    fn padding_never_exceeds_parent(padding in padding(screen_length()), parent in real_size(), screen in screen_size()) {
+        let size = padding.to_screen(&parent.smallest_size(), &SmallestSize::zero(), &screen);
+
+        let inner = (parent.smallest_size() - size).real_size();
+
+        prop_assert!(inner.width >= 0.0 && inner.width <= parent.width);
+        prop_assert!(inner.height >= 0.0 && inner.height <= parent.height);
+    }
+
+    #[test]
+    fn with_padding_adds_padding(padding in padding(screen_length()), parent in real_size(), children in real_size(), screen in screen_size()) {
+        let padding = padding.to_screen(&parent.smallest_size(), &SmallestSize::zero(), &screen);
+
+        let this = SmallestSize {
+            width: SmallestLength::SmallestWidth(1.0),
+            height: SmallestLength::SmallestHeight(1.0),
+        };
+
+        let size = this.with_padding(&parent.smallest_size(), padding, |_| children).real_size();
+
+        prop_assert!(approx_eq(size.width, children.width + padding.width));
+        prop_assert!(approx_eq(size.height, children.height + padding.height));
+    }
+
+
+    #[test]
+    fn children_location_non_negative(location in location(), parent in real_location(), smallest in real_size(), screen in screen_size(), max_order in 0.0f32..100.0) {
+        let child = location.children_location_explicit(&parent, &smallest, &screen, max_order);
+
+        prop_assert!(child.size.width >= 0.0);
+        prop_assert!(child.size.height >= 0.0);
+    }
+
+    /// The padding is subtracted from the node's size, it must never make the node bigger.
+    #[test]
+    fn children_location_padding(location in location(), parent in real_location(), smallest in real_size(), screen in screen_size(), max_order in 0.0f32..100.0) {
+        let child = location.children_location_explicit(&parent, &smallest, &screen, max_order);
+
+        let size = location.size.real_size(&parent.size, &smallest, &screen);
+        let padding = location.padding.real_padding(&parent.size, &smallest, &screen);
+        let offset = location.offset.real_position(&parent.size, &smallest, &screen);
+
+        prop_assert!(child.size.width <= size.width);
+        prop_assert!(child.size.height <= size.height);
+
+        let origin_x = (parent.size.width - size.width) * location.origin.x;
+        let origin_y = (parent.size.height - size.height) * location.origin.y;
+
+        prop_assert!(approx_eq(child.position.x, parent.position.x + origin_x + offset.x + padding.left));
+        prop_assert!(approx_eq(child.position.y, parent.position.y + origin_y + offset.y + padding.up));
+    }
+
+    #[test]
+    fn children_location_order(location in location(), parent in real_location(), smallest in real_size(), screen in screen_size(), max_order in 0.0f32..100.0) {
+        let child = location.children_location_explicit(&parent, &smallest, &screen, max_order);
+
+        match location.order {
+            Order::Global(order) => prop_assert_eq!(child.order, order),
+            Order::Parent(order) => prop_assert!(child.order > parent.order && approx_eq(child.order, parent.order + order)),
+            Order::Above(order) => prop_assert!(child.order > max_order && approx_eq(child.order, max_order + order)),
+        }
+    }
+
+    /// The default location fills the parent exactly.
+    #[test]
+    fn children_location_default(parent in real_location(), smallest in real_size(), screen in screen_size()) {
+        let child = Location::default().children_location_explicit(&parent, &smallest, &screen, 0.0);
+
+        prop_assert!(approx_eq(child.position.x, parent.position.x));
+        prop_assert!(approx_eq(child.position.y, parent.position.y));
+        prop_assert!(approx_eq(child.size.width, parent.size.width));
+        prop_assert!(approx_eq(child.size.height, parent.size.height));
+    }
+
+    /// The margin is the distance between the node and the anchored edges of the parent.
+    #[test]
+    fn children_location_anchor_margin(parent in real_location(), width in screen_length(), height in screen_length(), x in screen_length(), y in screen_length(), screen in screen_size()) {
+        let anchors = [
+            Anchor::TopLeft, Anchor::Top, Anchor::TopRight,
+            Anchor::Left, Anchor::Center, Anchor::Right,
+            Anchor::BottomLeft, Anchor::Bottom, Anchor::BottomRight,
+        ];
+
+        let smallest = RealSize::zero();
+        let margin = Offset { x, y }.real_position(&parent.size, &smallest, &screen);
+
+        for anchor in anchors {
+            let location = Location {
+                offset: anchor.margin(Offset { x, y }),
+                size: Size { width, height },
+                origin: anchor.origin(),
+                ..Location::default()
+            };
+
+            let child = location.children_location_explicit(&parent, &smallest, &screen, 0.0);
+
+            let left = child.position.x - parent.position.x;
+            let right = (parent.position.x + parent.size.width) - (child.position.x + child.size.width);
+            let up = child.position.y - parent.position.y;
+            let down = (parent.position.y + parent.size.height) - (child.position.y + child.size.height);
+
+            let origin = anchor.origin();
+
+            let scale_x = parent.position.x.abs() + parent.size.width.abs() + child.size.width.abs() + margin.x.abs();
+            let scale_y = parent.position.y.abs() + parent.size.height.abs() + child.size.height.abs() + margin.y.abs();
+
+            for (origin, start, end, margin, scale) in [(origin.x, left, right, margin.x, scale_x), (origin.y, up, down, margin.y, scale_y)] {
+                if origin == 0.0 {
+                    prop_assert!(approx_eq_scaled(start, margin, scale), "{:?} {} != {}", anchor, start, margin);
+
+                } else if origin == 1.0 {
+                    prop_assert!(approx_eq_scaled(end, margin, scale), "{:?} {} != {}", anchor, end, margin);
+
+                } else {
+                    prop_assert!(approx_eq_scaled(start - end, 2.0 * margin, scale), "{:?} {} != {}", anchor, start - end, 2.0 * margin);
+                }
+            }
+        }
+    }
+
+
+    #[test]
+    fn wgpu_coordinates_round_trip(location in real_location()) {
+        let wgpu = location.convert_to_wgpu_coordinates();
+
+        let x = (wgpu.position.x + 1.0) / 2.0;
+        let y = (1.0 - wgpu.position.y) / 2.0;
+        let width = wgpu.size.width / 2.0;
+        let height = wgpu.size.height / 2.0;
+
+        prop_assert!(approx_eq(x, location.position.x));
+        prop_assert!(approx_eq(y, location.position.y));
+        prop_assert!(approx_eq(width, location.size.width));
+        prop_assert!(approx_eq(height, location.size.height));
+        prop_assert_eq!(wgpu.order, location.order);
+    }
+}
+
+
+#[test]
+fn wgpu_coordinates_corners() {
+    let full = RealLocation::full().convert_to_wgpu_coordinates();
+
+    assert_eq!((full.position.x, full.position.y), (-1.0, 1.0));
+    assert_eq!((full.size.width, full.size.height), (2.0, 2.0));
+
+    let center = RealLocation {
+        position: RealPosition { x: 0.5, y: 0.5 },
+        size: RealSize::zero(),
+        order: 1.0,
+    }.convert_to_wgpu_coordinates();
+
+    assert_eq!((center.position.x, center.position.y), (0.0, 0.0));
+}
+
+
+#[test]
+fn wrap_time_precision() {
+    // 100 hours, which can't be represented precisely as f32 milliseconds
+    let start = 100.0 * 60.0 * 60.0 * 1000.0;
+
+    for offset in [0.0, 0.5, 125.0, 999.75] {
+        let time = start + offset;
+        let wrapped = wrap_time(time);
+
+        assert!(wrapped >= 0.0 && (wrapped as f64) < TIME_PERIOD);
+        assert_eq!(wrapped as f64, time.rem_euclid(TIME_PERIOD));
+    }
+
+    assert_eq!(wrap_time(-1.0) as f64, TIME_PERIOD - 1.0);
+}
+
+#[test]
+fn wrap_time_animation_cycles() {
+    // The animations in the game: (duration, frames) where the cycle is duration * frames
+    for cycle in [250.0 * 4.0, 500.0 * 2.0, 500.0 * 4.0, 600.0 * 4.0, 300.0 * 6.0] {
+        assert_eq!(TIME_PERIOD % cycle, 0.0);
+    }
+
+    // The animation frame is the same before and after the time wraps around
+    let duration = 250.0;
+    let frames = 4.0;
+
+    for time in [TIME_PERIOD - 300.0, TIME_PERIOD - 10.0, TIME_PERIOD + 10.0, 5.0 * TIME_PERIOD + 260.0] {
+        let expected = (time / duration).floor() % frames;
+        let found = (wrap_time(time) as f64 / duration).floor() % frames;
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn sprite_animation_gpu() {
+    let animation = SpriteAnimation { frames: 4, duration: 250.0, offset_x: 16, offset_y: -8, mode: AnimationMode::Loop };
+    assert_eq!(animation.to_gpu(), [4.0, 250.0, 16.0, -8.0]);
+}
+
+#[test]
+fn sprite_animation_invalid_duration() {
+    for duration in [0.0, -250.0, f32::NAN] {
+        let animation = SpriteAnimation { frames: 4, duration, offset_x: 16, offset_y: 0, mode: AnimationMode::Loop };
+        assert_eq!(animation.to_gpu(), [4.0, SpriteAnimation::MIN_DURATION, 16.0, 0.0]);
+    }
+}
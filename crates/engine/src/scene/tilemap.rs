@@ -0,0 +1,330 @@
+use futures_signals::signal::{Signal, SignalExt};
+use futures_signals::signal_vec::{SignalVec, SignalVecExt, VecDiff};
+
+use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, simple_method};
+use crate::scene::sprite::{Spritesheet, GPUSprite, GPUPalette};
+use crate::scene::{
+    NodeRef, Location, Padding, Origin, Offset, Size, SmallestSize, SceneLayoutInfo, SceneRenderInfo,
+    RealLocation, RealPosition, RealSize, NodeLayout, NodeHandle, Order, Percentage,
+};
+
+
+/// The number of cells in a [`Tilemap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TilemapSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+
+/// Specifies how the tile indices of a [`Tilemap`] map to the tiles in the spritesheet.
+///
+/// The tiles are numbered from left-to-right, top-to-bottom, starting at `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tileset {
+    /// How many tiles are in each row of the spritesheet.
+    pub columns: u32,
+
+    /// The width of each tile in pixels.
+    pub tile_width: u32,
+
+    /// The height of each tile in pixels.
+    pub tile_height: u32,
+}
+
+impl Tileset {
+    fn tile(&self, index: u32) -> [u32; 4] {
+        let row = index / self.columns;
+        let column = index - (row * self.columns);
+
+        let start_x = column * self.tile_width;
+        let start_y = row * self.tile_height;
+
+        [start_x, start_y, start_x + self.tile_width, start_y + self.tile_height]
+    }
+}
+
+
+/// Displays a dense grid of tiles from a spritesheet with a single Node.
+///
+/// Each cell is an index into the [`Tileset`], or `None` for an empty cell.
+/// The cells are in row-major order, so the cell for `x` / `y` is at `(y * width) + x`.
+///
+/// This is much faster than creating a [`Sprite`](crate::Sprite) for each tile,
+/// because the cells don't need to be laid out individually.
+///
+/// When using [`cells_signal_vec`](TilemapBuilder::cells_signal_vec), changing a single cell
+/// only updates that cell, unless the cell changes to or from `None`, which requires a relayout.
+///
+/// # Sizing
+///
+/// * [`Length::SmallestWidth`]: it is an error to use `SmallestWidth`.
+///
+/// * [`Length::SmallestHeight`]: it is an error to use `SmallestHeight`.
+pub struct Tilemap {
+    visible: bool,
+    node_ref: Option<NodeRef>,
+    location: Location,
+    spritesheet: Option<Spritesheet>,
+    map_size: Option<TilemapSize>,
+    tileset: Option<Tileset>,
+    palette: Option<GPUPalette>,
+    alpha: f32,
+    cells: Vec<Option<u32>>,
+
+    /// Cells which need to be re-rendered.
+    changed_cells: Vec<usize>,
+
+    /// The index of each cell in the spritesheet's instances.
+    gpu_indices: Vec<Option<usize>>,
+    gpu_sprites: Vec<GPUSprite>,
+}
+
+impl Tilemap {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            visible: true,
+            node_ref: None,
+            location: Location::default(),
+            spritesheet: None,
+            map_size: None,
+            tileset: None,
+            palette: None,
+            alpha: 1.0,
+            cells: vec![],
+
+            changed_cells: vec![],
+
+            gpu_indices: vec![],
+            gpu_sprites: vec![],
+        }
+    }
+
+    fn update_cells(&mut self, change: VecDiff<Option<u32>>) -> BuilderChanged {
+        match change {
+            VecDiff::UpdateAt { index, value } => {
+                let old_value = std::mem::replace(&mut self.cells[index], value);
+
+                match (old_value, value) {
+                    (Some(old_value), Some(value)) => {
+                        if old_value != value {
+                            self.changed_cells.push(index);
+                            BuilderChanged::Render
+
+                        } else {
+                            BuilderChanged::None
+                        }
+                    },
+                    (None, None) => BuilderChanged::None,
+
+                    // Empty cells don't have an instance, so it needs to relayout
+                    _ => BuilderChanged::Layout,
+                }
+            },
+            change => {
+                change.apply_to_vec(&mut self.cells);
+                BuilderChanged::Layout
+            },
+        }
+    }
+}
+
+make_builder!(Tilemap, TilemapBuilder);
+base_methods!(Tilemap, TilemapBuilder);
+location_methods!(Tilemap, TilemapBuilder);
+
+impl TilemapBuilder {
+    simple_method!(
+        /// Sets the [`Spritesheet`] which will be used for the tiles.
+        spritesheet,
+        spritesheet_signal,
+        |state, value: Spritesheet| {
+            state.spritesheet = Some(value);
+            BuilderChanged::Layout
+        },
+    );
+
+    simple_method!(
+        /// Sets the number of cells in the tilemap.
+        map_size,
+        map_size_signal,
+        |state, value: TilemapSize| {
+            state.map_size = Some(value);
+            BuilderChanged::Layout
+        },
+    );
+
+    simple_method!(
+        /// Sets the [`Tileset`] which specifies where the tiles are in the spritesheet.
+        tileset,
+        tileset_signal,
+        |state, value: Tileset| {
+            state.tileset = Some(value);
+            BuilderChanged::Layout
+        },
+    );
+
+    simple_method!(
+        /// Sets the palette for every tile.
+        palette,
+        palette_signal,
+        |state, value: u32| {
+            state.palette = Some(GPUPalette {
+                palette: value,
+            });
+
+            BuilderChanged::Layout
+        },
+    );
+
+    simple_method!(
+        /// Sets the alpha for every tile.
+        ///
+        /// 1.0 means fully opaque, 0.0 means fully transparent.
+        alpha,
+        alpha_signal,
+        |state, value: Percentage| {
+            if state.alpha != value {
+                state.alpha = value;
+                BuilderChanged::Layout
+
+            } else {
+                BuilderChanged::None
+            }
+        },
+    );
+
+    simple_method!(
+        /// Sets the tile index for every cell.
+        cells,
+        cells_signal,
+        |state, value: Vec<Option<u32>>| {
+            state.cells = value;
+            BuilderChanged::Layout
+        },
+    );
+
+    /// Sets the tile index for every cell, changes to a single cell are much faster than replacing all of the cells.
+    pub fn cells_signal_vec<S>(mut self, cells: S) -> Self where S: SignalVec<Item = Option<u32>> + 'static {
+        let state = self.state.clone();
+
+        self.callbacks.spawn_local(move |root| {
+            let root = root.clone();
+
+            cells.for_each(move |change| {
+                let mut state = state.lock();
+
+                let changed = state.update_cells(change);
+
+                if state.visible {
+                    changed.trigger(&root);
+                }
+
+                async {}
+            })
+        });
+
+        self
+    }
+}
+
+impl NodeLayout for Tilemap {
+    #[inline]
+    fn is_visible(&mut self) -> bool {
+        self.visible && self.alpha != 0.0 && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
+    }
+
+    fn smallest_size<'a>(&mut self, _parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
+        self.location.size.smallest_size(&info.screen_size)
+    }
+
+    fn update_layout<'a>(&mut self, handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
+        self.changed_cells.clear();
+        self.gpu_indices.clear();
+        self.gpu_sprites.clear();
+
+        let this_location = self.location.children_location(parent, &smallest_size.real_size(), &info);
+
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(this_location);
+        }
+
+        info.enter_node("Tilemap", &this_location);
+        info.exit_node();
+
+        let map_size = self.map_size.expect("Tilemap is missing map_size");
+        let tileset = self.tileset.expect("Tilemap is missing tileset");
+        let spritesheet = self.spritesheet.as_ref().expect("Tilemap is missing spritesheet");
+
+        assert_eq!(self.cells.len(), (map_size.width * map_size.height) as usize, "Tilemap cells must have width * height cells");
+
+        let cell_size = RealSize {
+            width: this_location.size.width / map_size.width as f32,
+            height: this_location.size.height / map_size.height as f32,
+        };
+
+        let stencil = info.renderer.stencil;
+
+        // The tiles don't overlap, so they share the same tie
+        let tie = info.renderer.sprite.next_tie(this_location.order);
+
+        if let Some(spritesheet) = info.renderer.sprite.spritesheets.get_mut(&spritesheet.handle) {
+            for (index, cell) in self.cells.iter().enumerate() {
+                let mut gpu_sprite = GPUSprite::default();
+
+                gpu_sprite.alpha = self.alpha;
+                gpu_sprite.set_tie(tie);
+
+                if let Some(tile) = cell {
+                    let x = (index as u32) % map_size.width;
+                    let y = (index as u32) / map_size.width;
+
+                    gpu_sprite.update(&RealLocation {
+                        position: RealPosition {
+                            x: this_location.position.x + (x as f32 * cell_size.width),
+                            y: this_location.position.y + (y as f32 * cell_size.height),
+                        },
+                        size: cell_size,
+                        order: this_location.order,
+                    });
+
+                    gpu_sprite.tile = tileset.tile(*tile);
+
+                    self.gpu_indices.push(Some(spritesheet.push(gpu_sprite, self.palette, stencil, None)));
+
+                } else {
+                    self.gpu_indices.push(None);
+                }
+
+                self.gpu_sprites.push(gpu_sprite);
+            }
+        }
+
+        info.renderer.set_max_order(this_location.order);
+
+        info.rendered_nodes.push(handle.clone());
+    }
+
+    fn render<'a>(&mut self, info: &mut SceneRenderInfo<'a>) {
+        if !self.changed_cells.is_empty() {
+            let tileset = self.tileset.expect("Tilemap is missing tileset");
+            let spritesheet = self.spritesheet.as_ref().expect("Tilemap is missing spritesheet");
+
+            if let Some(spritesheet) = info.renderer.sprite.spritesheets.get_mut(&spritesheet.handle) {
+                for index in self.changed_cells.drain(..) {
+                    if let (Some(gpu_index), Some(tile)) = (self.gpu_indices[index], self.cells[index]) {
+                        let gpu_sprite = &mut self.gpu_sprites[index];
+
+                        gpu_sprite.tile = tileset.tile(tile);
+
+                        spritesheet.update(gpu_index, *gpu_sprite, self.palette, None);
+                    }
+                }
+
+            } else {
+                self.changed_cells.clear();
+            }
+        }
+    }
+}
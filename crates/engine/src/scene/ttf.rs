@@ -0,0 +1,227 @@
+use crate::util::buffer::{GrayscaleImage};
+use super::{Texture, BitmapFontSettings, BitmapFontSupported};
+use super::bitmap_text::{font_tile};
+
+
+/// Pixels with at least this much coverage are drawn, the text shader doesn't support anti-aliasing.
+const COVERAGE_THRESHOLD: u8 = 128;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtfError {
+    message: &'static str,
+}
+
+impl std::fmt::Display for TtfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid TTF font: {}", self.message)
+    }
+}
+
+impl std::error::Error for TtfError {}
+
+
+pub struct TtfFontSettings<'a> {
+    /// The bytes of the `.ttf` or `.otf` file.
+    pub bytes: &'a [u8],
+
+    /// The font size in pixels.
+    pub size: f32,
+
+    /// Which characters will be rasterized.
+    pub supported: BitmapFontSupported,
+
+    /// How many characters are in each row of the atlas, it must be greater than `0`.
+    pub columns: u32,
+}
+
+
+/// Glyph atlas which is generated from a TTF font, so that new languages don't need a hand-authored bitmap font.
+///
+/// The atlas uses the same layout as the bitmap fonts: each character is in a tile
+/// which is `tile_width * 2` wide, so wide characters use the whole tile.
+///
+/// The atlas is generated on the CPU, so it can also be used in a build script by saving the image.
+pub struct TtfFontAtlas {
+    pub image: GrayscaleImage,
+    pub supported: BitmapFontSupported,
+    pub columns: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+impl TtfFontAtlas {
+    pub fn new(label: &'static str, settings: TtfFontSettings) -> Result<Self, TtfError> {
+        if settings.columns == 0 {
+            return Err(TtfError { message: "columns must be greater than 0" });
+        }
+
+        let font = fontdue::Font::from_bytes(settings.bytes, fontdue::FontSettings {
+            scale: settings.size,
+            ..fontdue::FontSettings::default()
+        }).map_err(|message| TtfError { message })?;
+
+        let size = settings.size;
+        let supported = settings.supported;
+        let columns = settings.columns;
+
+        let (ascent, tile_height) = match font.horizontal_line_metrics(size) {
+            Some(metrics) => (metrics.ascent.ceil() as i32, (metrics.ascent - metrics.descent).ceil() as u32),
+            None => (size.ceil() as i32, size.ceil() as u32),
+        };
+
+        let chars = || (supported.start as u32..=supported.end as u32).filter_map(char::from_u32);
+
+        // The width of a narrow character is the widest ASCII character
+        let tile_width = chars()
+            .filter(|c| c.is_ascii_graphic())
+            .map(|c| font.metrics(c, size).advance_width.ceil() as u32)
+            .max()
+            .unwrap_or_else(|| (size / 2.0).ceil() as u32)
+            .max(1);
+
+        let cell_width = tile_width * 2;
+        let rows = (supported.end as u32 / columns) + 1;
+
+        let mut image = GrayscaleImage::from_fn(label, columns * cell_width, rows * tile_height, |_, _| image::Luma([0]));
+
+        for c in chars() {
+            if !font.has_glyph(c) {
+                continue;
+            }
+
+            let (metrics, coverage) = font.rasterize(c, size);
+
+            let tile = font_tile(c, 2, columns, tile_width, tile_height);
+
+            let tile_x = tile.start_x as i32;
+            let tile_y = tile.start_y as i32;
+
+            let glyph_x = metrics.xmin.max(0);
+            let glyph_y = ascent - (metrics.ymin + metrics.height as i32);
+
+            for y in 0..metrics.height {
+                for x in 0..metrics.width {
+                    if coverage[(y * metrics.width) + x] >= COVERAGE_THRESHOLD {
+                        let x = glyph_x + x as i32;
+                        let y = glyph_y + y as i32;
+
+                        // Parts of the glyph which are outside of the tile are cut off
+                        if x >= 0 && y >= 0 && x < cell_width as i32 && y < tile_height as i32 {
+                            image.image.put_pixel((tile_x + x) as u32, (tile_y + y) as u32, image::Luma([255]));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            image,
+            supported,
+            columns,
+            tile_width,
+            tile_height,
+        })
+    }
+
+    /// The settings for [`BitmapFont::load`](super::BitmapFont::load), the texture must be loaded with [`TtfFontAtlas::image`].
+    pub fn settings<'a>(&self, texture: &'a Texture) -> BitmapFontSettings<'a> {
+        BitmapFontSettings {
+            texture,
+            supported: self.supported,
+            columns: self.columns,
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{TtfFontAtlas, TtfFontSettings, TtfError, BitmapFontSupported};
+    use super::super::bitmap_text::{font_tile};
+
+    /// A 16 units per em font with an ascent of 12 and a descent of 4, so each unit is a pixel at size 16.
+    ///
+    /// `A` is a rectangle from (1, 0) to (5, 10) with an advance of 6, `B` fills the
+    /// whole line from (0, -4) to (8, 12) with an advance of 8, and the other characters are missing.
+    const BOXES: &[u8] = include_bytes!("../../tests/fonts/boxes.ttf");
+
+    const SUPPORTED: BitmapFontSupported = BitmapFontSupported {
+        start: ' ',
+        end: '~',
+        replace: '?',
+    };
+
+    fn settings(bytes: &[u8], columns: u32) -> TtfFontSettings<'_> {
+        TtfFontSettings {
+            bytes,
+            size: 16.0,
+            supported: SUPPORTED,
+            columns,
+        }
+    }
+
+    #[test]
+    fn invalid_font() {
+        let atlas = TtfFontAtlas::new("invalid", settings(&[0, 1, 2, 3], 16));
+
+        assert!(atlas.is_err());
+    }
+
+    #[test]
+    fn zero_columns() {
+        let atlas = TtfFontAtlas::new("zero_columns", settings(BOXES, 0));
+
+        assert_eq!(atlas.err(), Some(TtfError { message: "columns must be greater than 0" }));
+    }
+
+    #[test]
+    fn rasterize() {
+        let atlas = TtfFontAtlas::new("boxes", settings(BOXES, 16)).unwrap();
+
+        // The widest character is B
+        assert_eq!(atlas.tile_width, 8);
+        assert_eq!(atlas.tile_height, 16);
+
+        // There are 8 rows, because the last character is 126
+        assert_eq!(atlas.image.image.dimensions(), (16 * 16, 8 * 16));
+
+        let filled = |c: char| {
+            let tile = font_tile(c, 2, atlas.columns, atlas.tile_width, atlas.tile_height);
+
+            let mut pixels = vec![];
+
+            for y in tile.start_y..tile.end_y {
+                for x in tile.start_x..tile.end_x {
+                    if atlas.image.image.get_pixel(x, y).0[0] != 0 {
+                        pixels.push((x - tile.start_x, y - tile.start_y));
+                    }
+                }
+            }
+
+            pixels
+        };
+
+        let rect = |x: std::ops::Range<u32>, y: std::ops::Range<u32>| {
+            y.flat_map(|y| x.clone().map(move |x| (x, y))).collect::<Vec<_>>()
+        };
+
+        // The baseline is 12 pixels below the top of the tile
+        assert_eq!(filled('A'), rect(1..5, 2..12));
+        assert_eq!(filled('B'), rect(0..8, 0..16));
+        assert_eq!(filled(' '), vec![]);
+        assert_eq!(filled('C'), vec![]);
+
+        // Every pixel is inside of the tile for A or B
+        let total = atlas.image.image.pixels().filter(|pixel| pixel.0[0] != 0).count();
+        assert_eq!(total, filled('A').len() + filled('B').len());
+
+        let texture = super::Texture::new();
+        let settings = atlas.settings(&texture);
+        assert_eq!(settings.columns, 16);
+        assert_eq!(settings.tile_width, 8);
+        assert_eq!(settings.tile_height, 16);
+    }
+}
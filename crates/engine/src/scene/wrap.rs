@@ -2,7 +2,7 @@ use futures_signals::signal::{Signal, SignalExt};
 use futures_signals::signal_vec::{SignalVec, SignalVecExt};
 use crate::scene::builder::{Node, BuilderChanged, make_builder, base_methods, location_methods, children_methods};
 use crate::scene::{
-    NodeHandle, Location, Origin, Size, Offset, Padding, SmallestSize, SmallestLength,
+    NodeHandle, NodeRef, Location, Origin, Size, Offset, Padding, SmallestSize, SmallestLength,
     RealLocation, NodeLayout, SceneLayoutInfo, SceneRenderInfo, RealSize, Order,
 };
 
@@ -35,6 +35,7 @@ impl Row {
 /// Children are shrunk horizontally and vertically as much as possible.
 pub struct Wrap {
     visible: bool,
+    node_ref: Option<NodeRef>,
     location: Location,
     children: Vec<NodeHandle>,
 
@@ -47,6 +48,7 @@ impl Wrap {
     fn new() -> Self {
         Self {
             visible: true,
+            node_ref: None,
             location: Location::default(),
             children: vec![],
 
@@ -115,7 +117,7 @@ children_methods!(Wrap, WrapBuilder);
 impl NodeLayout for Wrap {
     #[inline]
     fn is_visible(&mut self) -> bool {
-        self.visible
+        self.visible && self.node_ref.as_ref().map_or(true, NodeRef::is_visible)
     }
 
     fn smallest_size<'a>(&mut self, parent: &SmallestSize, info: &mut SceneLayoutInfo<'a>) -> SmallestSize {
@@ -131,6 +133,12 @@ impl NodeLayout for Wrap {
     fn update_layout<'a>(&mut self, _handle: &NodeHandle, parent: &RealLocation, smallest_size: &SmallestSize, info: &mut SceneLayoutInfo<'a>) {
         let this_location = self.location.children_location(parent, &smallest_size.real_size(), &info);
 
+        if let Some(node_ref) = &self.node_ref {
+            node_ref.set_location(this_location);
+        }
+
+        info.enter_node("Wrap", &this_location);
+
         {
             let mut child_location = this_location;
 
@@ -150,6 +158,8 @@ impl NodeLayout for Wrap {
             }
         }
 
+        info.exit_node();
+
         self.rows.clear();
     }
 
@@ -0,0 +1,607 @@
+//! Signal adapters which animate a value over time.
+//!
+//! Instead of writing a tween future by hand, you can call [`smooth`](SignalUtil::smooth),
+//! [`spring`](SignalUtil::spring), or [`delay`](SignalUtil::delay) on a signal:
+//!
+//! ```rust,ignore
+//! use rusted_battalions_engine::signal_util::SignalUtil;
+//!
+//! let clock = engine.clock();
+//!
+//! builder.offset_signal(camera.signal().smooth(&clock, 250.0))
+//! ```
+//!
+//! The adapters are driven by the engine's [`FrameClock`], so they update once per frame,
+//! and they stop updating when the animation is finished.
+
+use std::pin::Pin;
+use std::collections::VecDeque;
+use std::task::{Context, Poll};
+use futures_signals::signal::{Signal, Mutable, MutableSignal};
+use crate::scene::{Length, Offset};
+
+
+/// The spring simulation is split into steps which are no longer than this (in seconds),
+/// so that it stays stable even when a frame takes a long time.
+const SPRING_STEP: f32 = 1.0 / 120.0;
+
+/// The spring stops when it is closer than this to the target.
+const SPRING_EPSILON: f32 = 0.001;
+
+
+/// The time of the current frame, this is updated by [`Engine::set_time`](crate::Engine::set_time).
+#[derive(Debug, Clone)]
+pub struct FrameClock {
+    time: Mutable<f64>,
+}
+
+impl FrameClock {
+    pub(crate) fn new() -> Self {
+        Self {
+            time: Mutable::new(0.0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set(&self, time: f64) {
+        self.time.set_neq(time);
+    }
+
+    /// The current time in milliseconds.
+    #[inline]
+    pub fn time(&self) -> f64 {
+        self.time.get()
+    }
+
+    /// Changes once per frame.
+    #[inline]
+    pub fn signal(&self) -> MutableSignal<f64> {
+        self.time.signal()
+    }
+}
+
+
+/// Values which can be animated by [`SignalUtil::smooth`].
+pub trait Interpolate: Clone + PartialEq {
+    /// Returns the value which is `percent` of the way from `self` to `other`.
+    ///
+    /// `percent` is between `0.0` and `1.0`.
+    fn interpolate(&self, other: &Self, percent: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    #[inline]
+    fn interpolate(&self, other: &Self, percent: f32) -> Self {
+        self + (other - self) * percent
+    }
+}
+
+/// Lengths can only be interpolated if they are the same kind of length,
+/// otherwise it jumps to the new length when `percent` is `1.0`.
+///
+/// [`Length::Zero`] can be interpolated with every kind of length.
+impl Interpolate for Length {
+    fn interpolate(&self, other: &Self, percent: f32) -> Self {
+        let kind = match self {
+            Self::Zero => other,
+            _ => self,
+        };
+
+        let is_same = matches!(other, Self::Zero) || std::mem::discriminant(kind) == std::mem::discriminant(other);
+
+        if is_same {
+            let from = length_amount(self);
+            let to = length_amount(other);
+            with_length_amount(kind, from + (to - from) * percent)
+
+        } else if percent >= 1.0 {
+            *other
+
+        } else {
+            *self
+        }
+    }
+}
+
+fn length_amount(length: &Length) -> f32 {
+    match length {
+        Length::Zero => 0.0,
+        Length::Px(x) => *x as f32,
+        Length::ScreenWidth(x) |
+        Length::ScreenHeight(x) |
+        Length::ParentWidth(x) |
+        Length::ParentHeight(x) |
+        Length::SmallestWidth(x) |
+        Length::SmallestHeight(x) => *x,
+    }
+}
+
+fn with_length_amount(length: &Length, x: f32) -> Length {
+    match length {
+        Length::Zero => Length::Zero,
+        Length::Px(_) => Length::Px(x.round() as i32),
+        Length::ScreenWidth(_) => Length::ScreenWidth(x),
+        Length::ScreenHeight(_) => Length::ScreenHeight(x),
+        Length::ParentWidth(_) => Length::ParentWidth(x),
+        Length::ParentHeight(_) => Length::ParentHeight(x),
+        Length::SmallestWidth(_) => Length::SmallestWidth(x),
+        Length::SmallestHeight(_) => Length::SmallestHeight(x),
+    }
+}
+
+impl Interpolate for Offset {
+    #[inline]
+    fn interpolate(&self, other: &Self, percent: f32) -> Self {
+        Self {
+            x: self.x.interpolate(&other.x, percent),
+            y: self.y.interpolate(&other.y, percent),
+        }
+    }
+}
+
+
+/// Adds animation methods to every [`Signal`].
+pub trait SignalUtil: Signal + Sized {
+    /// When the signal changes, it animates from the old value to the new value over `duration` milliseconds.
+    ///
+    /// It uses an ease out curve, so it starts fast and slows down at the end.
+    /// If the signal changes during the animation, it starts a new animation from the current value.
+    #[inline]
+    fn smooth(self, clock: &FrameClock, duration: f64) -> Smooth<Self> where Self::Item: Interpolate {
+        Smooth {
+            driver: Driver::new(self, clock, SmoothState {
+                duration,
+                value: None,
+                animation: None,
+                changed: false,
+            }),
+        }
+    }
+
+    /// Simulates a spring which pulls the value towards the signal's value.
+    ///
+    /// `stiffness` is how strongly it is pulled, and `damping` is how quickly it slows down.
+    /// If `damping` is too low then it will bounce past the new value before settling.
+    #[inline]
+    fn spring(self, clock: &FrameClock, stiffness: f32, damping: f32) -> Spring<Self> where Self: Signal<Item = f32> {
+        Spring {
+            driver: Driver::new(self, clock, SpringState {
+                stiffness,
+                damping,
+                value: None,
+                target: 0.0,
+                velocity: 0.0,
+                last_time: None,
+                changed: false,
+            }),
+        }
+    }
+
+    /// Changes `ms` milliseconds after the signal changes.
+    ///
+    /// The initial value is not delayed.
+    #[inline]
+    fn delay(self, clock: &FrameClock, ms: f64) -> Delay<Self> where Self::Item: Clone {
+        Delay {
+            driver: Driver::new(self, clock, DelayState {
+                ms,
+                value: None,
+                queue: VecDeque::new(),
+                changed: false,
+            }),
+        }
+    }
+}
+
+impl<S> SignalUtil for S where S: Signal {}
+
+
+trait Animation<A> {
+    type Output;
+
+    /// Called when the input signal changes, `now` is the current time in milliseconds.
+    fn set(&mut self, value: A, now: f64);
+
+    /// Returns the new value if it changed.
+    fn update(&mut self, now: f64) -> Option<Self::Output>;
+
+    fn is_animating(&self) -> bool;
+}
+
+
+/// Polls the input signal, and polls the clock only while it is animating,
+/// so it doesn't wake up every frame when nothing is happening.
+struct Driver<S, A> {
+    signal: Option<Pin<Box<S>>>,
+    clock: FrameClock,
+    clock_signal: MutableSignal<f64>,
+    animation: A,
+}
+
+impl<S, A> Driver<S, A> where S: Signal, A: Animation<S::Item> {
+    fn new(signal: S, clock: &FrameClock, animation: A) -> Self {
+        Self {
+            signal: Some(Box::pin(signal)),
+            clock: clock.clone(),
+            clock_signal: clock.signal(),
+            animation,
+        }
+    }
+
+    fn poll_change(&mut self, cx: &mut Context) -> Poll<Option<A::Output>> {
+        let now = self.clock.time();
+
+        if let Some(signal) = &mut self.signal {
+            loop {
+                match signal.as_mut().poll_change(cx) {
+                    Poll::Ready(Some(value)) => {
+                        self.animation.set(value, now);
+                    },
+                    Poll::Ready(None) => {
+                        self.signal = None;
+                        break;
+                    },
+                    Poll::Pending => {
+                        break;
+                    },
+                }
+            }
+        }
+
+        let output = self.animation.update(now);
+
+        if self.animation.is_animating() {
+            // Registers the waker so it is polled again on the next frame
+            while let Poll::Ready(Some(_)) = Pin::new(&mut self.clock_signal).poll_change(cx) {}
+        }
+
+        match output {
+            Some(value) => Poll::Ready(Some(value)),
+            None => if self.signal.is_none() && !self.animation.is_animating() {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            },
+        }
+    }
+}
+
+
+/// Starts fast and slows down at the end.
+#[inline]
+fn ease_out(percent: f32) -> f32 {
+    1.0 - (1.0 - percent).powi(3)
+}
+
+struct SmoothState<A> {
+    duration: f64,
+    value: Option<A>,
+
+    /// The old value, the new value, and the time when the animation started.
+    animation: Option<(A, A, f64)>,
+
+    changed: bool,
+}
+
+impl<A> Animation<A> for SmoothState<A> where A: Interpolate {
+    type Output = A;
+
+    fn set(&mut self, value: A, now: f64) {
+        match &self.value {
+            None => {
+                self.value = Some(value);
+                self.changed = true;
+            },
+            Some(current) => {
+                self.animation = Some((current.clone(), value, now));
+            },
+        }
+    }
+
+    fn update(&mut self, now: f64) -> Option<Self::Output> {
+        if let Some((from, to, start)) = &self.animation {
+            let percent = if self.duration <= 0.0 {
+                1.0
+            } else {
+                ((now - start) / self.duration).clamp(0.0, 1.0) as f32
+            };
+
+            let value = if percent >= 1.0 {
+                let to = to.clone();
+                self.animation = None;
+                to
+
+            } else {
+                from.interpolate(to, ease_out(percent))
+            };
+
+            if self.value.as_ref() != Some(&value) {
+                self.value = Some(value);
+                self.changed = true;
+            }
+        }
+
+        if self.changed {
+            self.changed = false;
+            self.value.clone()
+
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn is_animating(&self) -> bool {
+        self.animation.is_some()
+    }
+}
+
+struct SpringState {
+    stiffness: f32,
+    damping: f32,
+    value: Option<f32>,
+    target: f32,
+    velocity: f32,
+
+    /// The time of the previous update, this is `None` when the spring is resting.
+    last_time: Option<f64>,
+
+    changed: bool,
+}
+
+impl Animation<f32> for SpringState {
+    type Output = f32;
+
+    fn set(&mut self, value: f32, now: f64) {
+        self.target = value;
+
+        if self.value.is_none() {
+            self.value = Some(value);
+            self.changed = true;
+
+        } else if self.last_time.is_none() {
+            self.last_time = Some(now);
+        }
+    }
+
+    fn update(&mut self, now: f64) -> Option<Self::Output> {
+        if let (Some(last_time), Some(value)) = (self.last_time, &mut self.value) {
+            // The time is limited so it doesn't freeze after the tab was in the background
+            let mut time = (((now - last_time) / 1000.0) as f32).min(1.0);
+
+            let old = *value;
+
+            while time > 0.0 {
+                let step = time.min(SPRING_STEP);
+                time -= step;
+
+                let acceleration = self.stiffness * (self.target - *value) - self.damping * self.velocity;
+                self.velocity += acceleration * step;
+                *value += self.velocity * step;
+            }
+
+            if (self.target - *value).abs() < SPRING_EPSILON && self.velocity.abs() < SPRING_EPSILON {
+                *value = self.target;
+                self.velocity = 0.0;
+                self.last_time = None;
+
+            } else {
+                self.last_time = Some(now);
+            }
+
+            if *value != old {
+                self.changed = true;
+            }
+        }
+
+        if self.changed {
+            self.changed = false;
+            self.value
+
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn is_animating(&self) -> bool {
+        self.last_time.is_some()
+    }
+}
+
+struct DelayState<A> {
+    ms: f64,
+    value: Option<A>,
+
+    /// The values which haven't been output yet, and the time when they should be output.
+    queue: VecDeque<(f64, A)>,
+
+    changed: bool,
+}
+
+impl<A> Animation<A> for DelayState<A> where A: Clone {
+    type Output = A;
+
+    fn set(&mut self, value: A, now: f64) {
+        if self.value.is_none() {
+            self.value = Some(value);
+            self.changed = true;
+
+        } else {
+            self.queue.push_back((now + self.ms, value));
+        }
+    }
+
+    fn update(&mut self, now: f64) -> Option<Self::Output> {
+        while let Some((time, _)) = self.queue.front() {
+            if *time > now {
+                break;
+            }
+
+            let (_, value) = self.queue.pop_front().unwrap();
+            self.value = Some(value);
+            self.changed = true;
+        }
+
+        if self.changed {
+            self.changed = false;
+            self.value.clone()
+
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn is_animating(&self) -> bool {
+        !self.queue.is_empty()
+    }
+}
+
+
+macro_rules! make_signal {
+    ($name:ident, $state:ident, $item:ty, [$($bounds:tt)*]) => {
+        #[must_use = "Signals do nothing unless polled"]
+        pub struct $name<S> where S: Signal {
+            driver: Driver<S, $state<$item>>,
+        }
+
+        // The input signal is boxed, so it is never pinned
+        impl<S> Unpin for $name<S> where S: Signal {}
+
+        impl<S> Signal for $name<S> where S: Signal, $($bounds)* {
+            type Item = $item;
+
+            #[inline]
+            fn poll_change(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+                self.get_mut().driver.poll_change(cx)
+            }
+        }
+    };
+}
+
+make_signal!(Smooth, SmoothState, S::Item, [S::Item: Interpolate]);
+make_signal!(Delay, DelayState, S::Item, [S::Item: Clone]);
+
+
+#[must_use = "Signals do nothing unless polled"]
+pub struct Spring<S> where S: Signal {
+    driver: Driver<S, SpringState>,
+}
+
+impl<S> Unpin for Spring<S> where S: Signal {}
+
+impl<S> Signal for Spring<S> where S: Signal<Item = f32> {
+    type Item = f32;
+
+    #[inline]
+    fn poll_change(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.get_mut().driver.poll_change(cx)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use futures::task::noop_waker_ref;
+    use futures_signals::signal::{Mutable, Signal, SignalExt};
+    use crate::scene::{Length, Offset};
+    use super::{FrameClock, SignalUtil, Interpolate};
+
+    fn poll<S>(signal: &mut S) -> Poll<Option<S::Item>> where S: Signal + Unpin {
+        signal.poll_change_unpin(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    #[test]
+    fn smooth() {
+        let clock = FrameClock::new();
+        let input = Mutable::new(0.0);
+        let mut signal = input.signal().smooth(&clock, 100.0);
+
+        assert_eq!(poll(&mut signal), Poll::Ready(Some(0.0)));
+        assert_eq!(poll(&mut signal), Poll::Pending);
+
+        input.set(8.0);
+        assert_eq!(poll(&mut signal), Poll::Pending);
+
+        clock.set(50.0);
+        assert_eq!(poll(&mut signal), Poll::Ready(Some(7.0)));
+
+        clock.set(100.0);
+        assert_eq!(poll(&mut signal), Poll::Ready(Some(8.0)));
+
+        // It stops when the animation is finished
+        clock.set(150.0);
+        assert_eq!(poll(&mut signal), Poll::Pending);
+
+        drop(input);
+        assert_eq!(poll(&mut signal), Poll::Ready(None));
+    }
+
+    #[test]
+    fn spring() {
+        let clock = FrameClock::new();
+        let input = Mutable::new(0.0);
+        let mut signal = input.signal().spring(&clock, 170.0, 26.0);
+
+        assert_eq!(poll(&mut signal), Poll::Ready(Some(0.0)));
+
+        input.set(1.0);
+        assert_eq!(poll(&mut signal), Poll::Pending);
+
+        let mut time = 0.0;
+        let mut last = 0.0;
+
+        loop {
+            time += 16.0;
+            clock.set(time);
+
+            match poll(&mut signal) {
+                Poll::Ready(Some(value)) => {
+                    last = value;
+                },
+                Poll::Ready(None) => unreachable!(),
+                Poll::Pending => break,
+            }
+
+            assert!(time < 10_000.0);
+        }
+
+        assert_eq!(last, 1.0);
+    }
+
+    #[test]
+    fn delay() {
+        let clock = FrameClock::new();
+        let input = Mutable::new(1);
+        let mut signal = input.signal().delay(&clock, 100.0);
+
+        assert_eq!(poll(&mut signal), Poll::Ready(Some(1)));
+
+        input.set(2);
+        assert_eq!(poll(&mut signal), Poll::Pending);
+
+        clock.set(50.0);
+        input.set(3);
+        assert_eq!(poll(&mut signal), Poll::Pending);
+
+        clock.set(100.0);
+        assert_eq!(poll(&mut signal), Poll::Ready(Some(2)));
+
+        clock.set(150.0);
+        assert_eq!(poll(&mut signal), Poll::Ready(Some(3)));
+    }
+
+    #[test]
+    fn interpolate_offset() {
+        let from = Offset { x: Length::Zero, y: Length::Px(10) };
+        let to = Offset { x: Length::ParentWidth(1.0), y: Length::Px(20) };
+
+        assert_eq!(from.interpolate(&to, 0.5), Offset { x: Length::ParentWidth(0.5), y: Length::Px(15) });
+
+        // Different kinds of lengths jump at the end
+        assert_eq!(Length::Px(5).interpolate(&Length::ScreenWidth(1.0), 0.5), Length::Px(5));
+        assert_eq!(Length::Px(5).interpolate(&Length::ScreenWidth(1.0), 1.0), Length::ScreenWidth(1.0));
+    }
+}
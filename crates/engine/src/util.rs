@@ -5,6 +5,7 @@ pub(crate) mod builders;
 pub(crate) mod buffer;
 pub(crate) mod macros;
 pub(crate) mod unicode;
+pub(crate) mod wgsl;
 
 
 pub(crate) trait IsAtomic {
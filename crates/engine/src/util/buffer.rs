@@ -3,6 +3,7 @@ use wgpu::util::DeviceExt;
 use image;
 use std::ops::{Deref, DerefMut};
 use std::marker::PhantomData;
+use crate::resources::{ResourceTracker};
 
 
 pub trait IntoTexture {
@@ -21,6 +22,8 @@ pub trait IntoTexture {
 pub(crate) struct TextureBuffer {
     pub(crate) texture: wgpu::Texture,
     pub(crate) view: wgpu::TextureView,
+    bytes: u64,
+    tracker: ResourceTracker,
 }
 
 impl TextureBuffer {
@@ -29,22 +32,17 @@ impl TextureBuffer {
 
         let (width, height) = image.dimensions();
 
+        let bytes = image.bytes();
+
+        tracing::debug!(label, width, height, bytes = bytes.len(), "Texture upload");
+
         let size = wgpu::Extent3d {
             width,
             height,
             depth_or_array_layers: 1,
         };
 
-        let texture = engine.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some(&label),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: image.format(),
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
+        let texture = Self::create_texture(engine, label, size, image.format());
 
         engine.queue.write_texture(
             wgpu::ImageCopyTexture {
@@ -53,17 +51,35 @@ impl TextureBuffer {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            image.bytes(),
+            bytes,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(width * (std::mem::size_of::<T::Item>() as u32)),
+                bytes_per_row: Some((bytes.len() as u32) / height),
                 rows_per_image: Some(height),
             },
             size,
         );
 
+        Self::from_texture(engine, label, texture, bytes.len() as u64)
+    }
+
+    fn create_texture(engine: &crate::EngineState, label: &str, size: wgpu::Extent3d, format: wgpu::TextureFormat) -> wgpu::Texture {
+        engine.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // COPY_SRC is needed for grow
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    fn from_texture(engine: &crate::EngineState, label: &str, texture: wgpu::Texture, bytes: u64) -> Self {
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some(&label),
+            label: Some(label),
             format: None,
             dimension: None,
             aspect: wgpu::TextureAspect::All,
@@ -73,13 +89,148 @@ impl TextureBuffer {
             array_layer_count: None,
         });
 
-        Self { texture, view }
+        engine.resources.add_texture(bytes);
+
+        Self { texture, view, bytes, tracker: engine.resources.clone() }
+    }
+
+    /// Returns a new texture which is `height` pixels taller, the existing pixels are copied on the GPU.
+    ///
+    /// The new rows are uninitialized, they must be written with [`write_region`](TextureBuffer::write_region).
+    pub(crate) fn grow(&self, engine: &crate::EngineState, label: &'static str, height: u32) -> Self {
+        let old_size = self.texture.size();
+
+        let size = wgpu::Extent3d {
+            height: old_size.height + height,
+            ..old_size
+        };
+
+        tracing::debug!(label, width = size.width, height = size.height, "Texture grow");
+
+        let texture = Self::create_texture(engine, label, size, self.texture.format());
+
+        let mut encoder = engine.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(label),
+        });
+
+        encoder.copy_texture_to_texture(self.texture.as_image_copy(), texture.as_image_copy(), old_size);
+
+        engine.queue.submit([encoder.finish()]);
+
+        let bytes = (self.bytes / old_size.height as u64) * size.height as u64;
+
+        Self::from_texture(engine, label, texture, bytes)
+    }
+
+    /// Overwrites a rectangle of the texture, starting at `x` and `y`.
+    pub(crate) fn write_region<T>(&self, engine: &crate::EngineState, x: u32, y: u32, image: &T) where T: IntoTexture {
+        assert_eq!(image.format(), self.texture.format(), "Image format does not match the texture format");
+
+        let (width, height) = image.dimensions();
+
+        let bytes = image.bytes();
+
+        tracing::debug!(label = image.label(), x, y, width, height, bytes = bytes.len(), "Texture region upload");
+
+        engine.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+            },
+            bytes,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some((bytes.len() as u32) / height),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
     }
 }
 
 impl Drop for TextureBuffer {
     fn drop(&mut self) {
         self.texture.destroy();
+        self.tracker.remove_texture(self.bytes);
+    }
+}
+
+
+/// CPU copy of an image, so that the texture can be uploaded again after it is evicted.
+pub(crate) struct RetainedImage {
+    label: &'static str,
+    dimensions: (u32, u32),
+    format: wgpu::TextureFormat,
+    bytes: Vec<u8>,
+}
+
+impl RetainedImage {
+    pub(crate) fn new<T>(image: &T) -> Self where T: IntoTexture {
+        Self {
+            label: image.label(),
+            dimensions: image.dimensions(),
+            format: image.format(),
+            bytes: image.bytes().to_vec(),
+        }
+    }
+
+    /// Overwrites a rectangle of the image, starting at `x` and `y`.
+    pub(crate) fn write_region<T>(&mut self, x: u32, y: u32, image: &T) where T: IntoTexture {
+        assert_eq!(image.format(), self.format, "Image format does not match the texture format");
+
+        let (width, height) = image.dimensions();
+
+        let pixel = self.bytes.len() / (self.dimensions.0 * self.dimensions.1) as usize;
+
+        let row = width as usize * pixel;
+        let stride = self.dimensions.0 as usize * pixel;
+
+        let source = image.bytes();
+
+        for index in 0..(height as usize) {
+            let start = ((y as usize + index) * stride) + (x as usize * pixel);
+
+            self.bytes[start..(start + row)].copy_from_slice(&source[(index * row)..((index + 1) * row)]);
+        }
+    }
+
+    /// Adds the rows of `image` to the bottom of the image.
+    pub(crate) fn append_rows<T>(&mut self, image: &T) where T: IntoTexture {
+        assert_eq!(image.format(), self.format, "Image format does not match the texture format");
+        assert_eq!(image.dimensions().0, self.dimensions.0, "Image width does not match the texture width");
+
+        self.bytes.extend_from_slice(image.bytes());
+        self.dimensions.1 += image.dimensions().1;
+    }
+}
+
+impl IntoTexture for RetainedImage {
+    type Item = u8;
+
+    #[inline]
+    fn label(&self) -> &'static str {
+        self.label
+    }
+
+    #[inline]
+    fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    #[inline]
+    fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
     }
 }
 
@@ -375,9 +526,9 @@ pub(crate) struct VecBufferSettings<'a> {
 /// Utility for writing a `Vec<T>` into a `wgpu::Buffer`.
 ///
 /// It will automatically resize the buffer to match the Vec's capacity.
-#[repr(transparent)]
 pub(crate) struct VecBuffer<T> {
     buffer: Option<wgpu::Buffer>,
+    tracker: Option<ResourceTracker>,
     _phantom: PhantomData<Vec<T>>,
 }
 
@@ -385,10 +536,21 @@ impl<T> VecBuffer<T> where T: bytemuck::Pod  {
     pub(crate) fn new() -> Self {
         Self {
             buffer: None,
+            tracker: None,
             _phantom: PhantomData,
         }
     }
 
+    fn destroy_buffer(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            buffer.destroy();
+
+            if let Some(tracker) = &self.tracker {
+                tracker.remove_buffer(buffer.size());
+            }
+        }
+    }
+
     fn byte_capacity(values: &Vec<T>) -> u64 {
         (values.capacity() * std::mem::size_of::<T>()) as u64
     }
@@ -418,6 +580,8 @@ impl<T> VecBuffer<T> where T: bytemuck::Pod  {
 
         buffer.unmap();
 
+        engine.resources.add_buffer(vec_capacity);
+
         buffer
     }
 
@@ -443,12 +607,9 @@ impl<T> VecBuffer<T> where T: bytemuck::Pod  {
                 engine.queue.write_buffer(buffer, 0, bytemuck::cast_slice(values.as_slice()));
 
             } else {
-                buffer.destroy();
+                self.destroy_buffer();
 
-                if vec_capacity == 0 {
-                    self.buffer = None;
-
-                } else {
+                if vec_capacity != 0 {
                     self.buffer = Some(Self::make_buffer(vec_capacity, values, engine, settings));
                 }
             }
@@ -457,6 +618,10 @@ impl<T> VecBuffer<T> where T: bytemuck::Pod  {
             self.buffer = Some(Self::make_buffer(vec_capacity, values, engine, settings));
         }
 
+        if self.tracker.is_none() {
+            self.tracker = Some(engine.resources.clone());
+        }
+
         self.to_slice(values)
     }
 }
@@ -465,6 +630,10 @@ impl<T> Drop for VecBuffer<T> {
     fn drop(&mut self) {
         if let Some(buffer) = &self.buffer {
             buffer.destroy();
+
+            if let Some(tracker) = &self.tracker {
+                tracker.remove_buffer(buffer.size());
+            }
         }
     }
 }
@@ -507,13 +676,21 @@ impl<T> InstanceVec<T> where T: bytemuck::Pod {
         Self::with_values(Vec::with_capacity(capacity))
     }
 
+    #[inline]
     pub(crate) fn update_buffer(&mut self, engine: &crate::EngineState, options: &InstanceVecOptions) -> Option<wgpu::BufferSlice<'_>> {
+        self.update_buffer_with_usage(engine, options, wgpu::BufferUsages::VERTEX)
+    }
+
+    /// The `usage` must always be the same for the same InstanceVec, because the buffer is only recreated when it is resized.
+    pub(crate) fn update_buffer_with_usage(&mut self, engine: &crate::EngineState, options: &InstanceVecOptions, usage: wgpu::BufferUsages) -> Option<wgpu::BufferSlice<'_>> {
         if self.changed {
             self.changed = false;
 
+            tracing::trace!(label = options.label, len = self.values.len(), "Instance buffer upload");
+
             self.buffer.write(&self.values, engine, VecBufferSettings {
                 label: options.label,
-                usage: wgpu::BufferUsages::VERTEX,
+                usage,
             })
 
         } else {
@@ -521,6 +698,23 @@ impl<T> InstanceVec<T> where T: bytemuck::Pod {
         }
     }
 
+    /// Returns the slice which was returned by [`update_buffer`](InstanceVec::update_buffer).
+    #[inline]
+    pub(crate) fn slice(&self) -> Option<wgpu::BufferSlice<'_>> {
+        self.buffer.to_slice(&self.values)
+    }
+
+    /// Returns the buffer which was written by [`update_buffer`](InstanceVec::update_buffer), it is `None` if it is empty.
+    #[inline]
+    pub(crate) fn buffer(&self) -> Option<&wgpu::Buffer> {
+        if self.values.is_empty() {
+            None
+
+        } else {
+            self.buffer.buffer.as_ref()
+        }
+    }
+
     pub fn resize_with<F>(&mut self, new_len: usize, create: F) where F: FnMut() -> T {
         let old_len = self.values.len();
 
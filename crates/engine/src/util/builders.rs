@@ -40,6 +40,19 @@ impl<'a, 'b> BindGroup<'a, 'b> {
         self
     }
 
+    /// Binds the views to a `binding_array`, see [`BindGroupLayout::texture_array`].
+    #[inline]
+    pub(crate) fn texture_view_array(mut self, texture_views: &'b [&'b wgpu::TextureView]) -> Self {
+        let binding = self.entries.len() as u32;
+
+        self.entries.push(wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureViewArray(texture_views),
+        });
+
+        self
+    }
+
     #[inline]
     pub(crate) fn sampler(mut self, sampler: &'b wgpu::Sampler) -> Self {
         let binding = self.entries.len() as u32;
@@ -102,6 +115,25 @@ impl BindGroupLayout {
         self
     }
 
+    /// A `binding_array` of `count` textures, this requires [`wgpu::Features::TEXTURE_BINDING_ARRAY`].
+    #[inline]
+    pub(crate) fn texture_array(mut self, visibility: wgpu::ShaderStages, ty: wgpu::TextureSampleType, count: std::num::NonZeroU32) -> Self {
+        let binding = self.entries.len() as u32;
+
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: ty,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: Some(count),
+        });
+
+        self
+    }
+
     #[inline]
     pub(crate) fn sampler(mut self, visibility: wgpu::ShaderStages, ty: wgpu::SamplerBindingType) -> Self {
         let binding = self.entries.len() as u32;
@@ -127,6 +159,7 @@ impl BindGroupLayout {
 
 pub(crate) struct Pipeline<'a, 'b, 'c> {
     label: Option<&'static str>,
+    layout: Option<&'a wgpu::PipelineLayout>,
     bind_groups: Option<&'a [&'a wgpu::BindGroupLayout]>,
     shader: Option<&'c wgpu::ShaderModule>,
     vertex_buffers: Option<&'b [wgpu::VertexBufferLayout<'b>]>,
@@ -134,9 +167,11 @@ pub(crate) struct Pipeline<'a, 'b, 'c> {
     strip_index_format: Option<wgpu::IndexFormat>,
     front_face: Option<wgpu::FrontFace>,
     cull_mode: Option<wgpu::Face>,
+    depth_stencil: bool,
     depth_write: bool,
     stencil: Option<wgpu::StencilState>,
     blend_state: Option<wgpu::BlendState>,
+    color_write: bool,
 }
 
 #[allow(unused)]
@@ -144,6 +179,7 @@ impl<'a, 'b, 'c> Pipeline<'a, 'b, 'c> {
     pub(crate) fn builder() -> Self {
         Self {
             label: None,
+            layout: None,
             bind_groups: None,
             shader: None,
             vertex_buffers: None,
@@ -151,9 +187,11 @@ impl<'a, 'b, 'c> Pipeline<'a, 'b, 'c> {
             strip_index_format: None,
             front_face: None,
             cull_mode: None,
+            depth_stencil: true,
             depth_write: true,
             stencil: None,
             blend_state: None,
+            color_write: true,
         }
     }
 
@@ -163,6 +201,13 @@ impl<'a, 'b, 'c> Pipeline<'a, 'b, 'c> {
         self
     }
 
+    /// Uses an existing pipeline layout, in which case `bind_groups` is ignored.
+    #[inline]
+    pub(crate) fn layout(mut self, layout: &'a wgpu::PipelineLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
     #[inline]
     pub(crate) fn bind_groups(mut self, bind_groups: &'a [&'a wgpu::BindGroupLayout]) -> Self {
         self.bind_groups = Some(bind_groups);
@@ -205,6 +250,13 @@ impl<'a, 'b, 'c> Pipeline<'a, 'b, 'c> {
         self
     }
 
+    /// Whether the pipeline uses the depth buffer, this must be `false` for render passes without a depth attachment.
+    #[inline]
+    pub(crate) fn depth_stencil(mut self, enabled: bool) -> Self {
+        self.depth_stencil = enabled;
+        self
+    }
+
     #[inline]
     pub(crate) fn depth_write(mut self, write: bool) -> Self {
         self.depth_write = write;
@@ -223,18 +275,34 @@ impl<'a, 'b, 'c> Pipeline<'a, 'b, 'c> {
         self
     }
 
+    /// When this is `false` the pipeline only writes to the depth / stencil buffer.
+    #[inline]
+    pub(crate) fn color_write(mut self, write: bool) -> Self {
+        self.color_write = write;
+        self
+    }
+
     pub(crate) fn build(self, engine: &crate::EngineState) -> wgpu::RenderPipeline {
         let shader = self.shader.expect("Pipeline: missing shader");
 
-        let render_pipeline_layout = engine.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: self.label.map(|label| format!("{} Pipeline Layout", label)).as_deref(),
-            bind_group_layouts: self.bind_groups.unwrap_or(&[]),
-            push_constant_ranges: &[],
-        });
+        let render_pipeline_layout;
+
+        let layout = match self.layout {
+            Some(layout) => layout,
+            None => {
+                render_pipeline_layout = engine.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: self.label.map(|label| format!("{} Pipeline Layout", label)).as_deref(),
+                    bind_group_layouts: self.bind_groups.unwrap_or(&[]),
+                    push_constant_ranges: &[],
+                });
+
+                &render_pipeline_layout
+            },
+        };
 
         engine.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: self.label.map(|label| format!("{} Pipeline", label)).as_deref(),
-            layout: Some(&render_pipeline_layout),
+            layout: Some(layout),
             vertex: wgpu::VertexState {
                 module: shader,
                 entry_point: Some("vs_main"),
@@ -246,9 +314,13 @@ impl<'a, 'b, 'c> Pipeline<'a, 'b, 'c> {
                 module: shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: engine.config.format,
+                    format: engine.format(),
                     blend: Some(self.blend_state.unwrap_or_else(|| wgpu::BlendState::REPLACE)),
-                    write_mask: wgpu::ColorWrites::ALL,
+                    write_mask: if self.color_write {
+                        wgpu::ColorWrites::ALL
+                    } else {
+                        wgpu::ColorWrites::empty()
+                    },
                 })],
                 // TODO support settings constants
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -262,15 +334,19 @@ impl<'a, 'b, 'c> Pipeline<'a, 'b, 'c> {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: Some(engine.depth_stencil_state(self.depth_write, self.stencil)),
+            depth_stencil: if self.depth_stencil {
+                Some(engine.depth_stencil_state(self.depth_write, self.stencil))
+
+            } else {
+                None
+            },
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-            // TODO support caching
-            cache: None,
+            cache: engine.pipeline_cache.native(),
         })
     }
 }
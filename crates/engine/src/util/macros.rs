@@ -1,16 +1,11 @@
+/// Preprocesses a shader (relative to the `wgsl` folder) and returns a `ShaderModuleDescriptor`.
+///
+/// Any extra arguments are flags which are defined when preprocessing.
 macro_rules! wgsl {
-    ($label:literal, $path:expr$(,)?) => {
+    ($path:literal $(, $define:literal)*$(,)?) => {
         wgpu::ShaderModuleDescriptor {
-            label: Some($label),
-            source: wgpu::ShaderSource::Wgsl($path.into()),
-        }
-    };
-    ($label:literal, $($path:expr),+$(,)?) => {
-        wgpu::ShaderModuleDescriptor {
-            label: Some($label),
-            source: wgpu::ShaderSource::Wgsl([
-                $($path),+
-            ].join("\n").into()),
+            label: Some(concat!($path $(, " ", $define)*)),
+            source: wgpu::ShaderSource::Wgsl($crate::util::wgsl::preprocess($path, &[$($define),*]).into()),
         }
     };
 }
@@ -0,0 +1,258 @@
+//! A small preprocessor for WGSL shaders.
+//!
+//! It supports these directives, which must be on their own line:
+//!
+//! * `#include "path"` inserts the shader at `path`, each shader is only included once.
+//! * `#define NAME` defines a flag.
+//! * `#ifdef NAME` / `#ifndef NAME` / `#else` / `#endif` conditionally includes lines.
+//!
+//! Flags can also be passed in from Rust, which makes it possible to
+//! generate multiple variants from the same shader.
+use std::collections::HashSet;
+
+
+/// All of the shaders, paths are relative to the `wgsl` folder.
+static SHADERS: &'static [(&'static str, &'static str)] = &[
+    ("common/scene.wgsl", include_str!("../wgsl/common/scene.wgsl")),
+    ("common/animation.wgsl", include_str!("../wgsl/common/animation.wgsl")),
+    ("common/sprite.wgsl", include_str!("../wgsl/common/sprite.wgsl")),
+    ("spritesheet/sprite.wgsl", include_str!("../wgsl/spritesheet/sprite.wgsl")),
+    ("spritesheet/text.wgsl", include_str!("../wgsl/spritesheet/text.wgsl")),
+    ("spritesheet/cull.wgsl", include_str!("../wgsl/spritesheet/cull.wgsl")),
+    ("spritesheet/animate.wgsl", include_str!("../wgsl/spritesheet/animate.wgsl")),
+    ("postprocess.wgsl", include_str!("../wgsl/postprocess.wgsl")),
+    ("shape.wgsl", include_str!("../wgsl/shape.wgsl")),
+    ("gradient.wgsl", include_str!("../wgsl/gradient.wgsl")),
+];
+
+struct Preprocessor {
+    shaders: &'static [(&'static str, &'static str)],
+    output: String,
+    defines: HashSet<&'static str>,
+    included: HashSet<&'static str>,
+}
+
+impl Preprocessor {
+    fn new(shaders: &'static [(&'static str, &'static str)], defines: &[&'static str]) -> Self {
+        Self {
+            shaders,
+            output: String::new(),
+            defines: defines.iter().copied().collect(),
+            included: HashSet::new(),
+        }
+    }
+
+    fn lookup(&self, path: &str) -> (&'static str, &'static str) {
+        self.shaders.iter()
+            .find(|(x, _)| *x == path)
+            .copied()
+            .unwrap_or_else(|| panic!("Unknown shader {}", path))
+    }
+
+    fn process(&mut self, path: &str) {
+        let (path, source) = self.lookup(path);
+        self.process_source(path, source);
+    }
+
+    fn process_source(&mut self, path: &'static str, source: &'static str) {
+        if !self.included.insert(path) {
+            return;
+        }
+
+        // Whether each nested #ifdef is active or not
+        let mut stack: Vec<bool> = vec![];
+
+        for line in source.lines() {
+            let active = stack.iter().all(|x| *x);
+
+            if let Some(directive) = line.trim().strip_prefix('#') {
+                let (name, arg) = match directive.split_once(char::is_whitespace) {
+                    Some((name, arg)) => (name, arg.trim()),
+                    None => (directive, ""),
+                };
+
+                match name {
+                    "ifdef" => {
+                        stack.push(self.defines.contains(arg));
+                    },
+                    "ifndef" => {
+                        stack.push(!self.defines.contains(arg));
+                    },
+                    "else" => {
+                        let last = stack.last_mut().unwrap_or_else(|| panic!("{}: #else without #ifdef", path));
+                        *last = !*last;
+                    },
+                    "endif" => {
+                        stack.pop().unwrap_or_else(|| panic!("{}: #endif without #ifdef", path));
+                    },
+                    "define" => {
+                        if active {
+                            self.defines.insert(arg);
+                        }
+                    },
+                    "include" => {
+                        if active {
+                            let include = arg.strip_prefix('"')
+                                .and_then(|arg| arg.strip_suffix('"'))
+                                .unwrap_or_else(|| panic!("{}: invalid #include {}", path, arg));
+
+                            self.process(include);
+                        }
+                    },
+                    _ => {
+                        panic!("{}: unknown directive #{}", path, name);
+                    },
+                }
+
+            } else if active {
+                self.output.push_str(line);
+                self.output.push('\n');
+            }
+        }
+
+        assert!(stack.is_empty(), "{}: missing #endif", path);
+    }
+}
+
+
+/// Preprocesses the shader at `path` with the `defines` flags.
+pub(crate) fn preprocess(path: &str, defines: &[&'static str]) -> String {
+    let mut preprocessor = Preprocessor::new(SHADERS, defines);
+
+    preprocessor.process(path);
+
+    preprocessor.output
+}
+
+
+/// Preprocesses the shader at `path`, followed by the user-provided `source`.
+///
+/// The `source` can use the same directives, and it can see every flag which was defined by `path`.
+pub(crate) fn preprocess_with(path: &str, defines: &[&'static str], label: &'static str, source: &'static str) -> String {
+    let mut preprocessor = Preprocessor::new(SHADERS, defines);
+
+    preprocessor.process(path);
+    preprocessor.process_source(label, source);
+
+    preprocessor.output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Preprocessor, SHADERS};
+
+    static TEST_SHADERS: &'static [(&'static str, &'static str)] = &[
+        ("common.wgsl", "common\n"),
+        ("define.wgsl", "#ifdef SKIP\n#define SKIPPED\n#endif\n#define FOO\n"),
+        ("nested.wgsl", "#include \"common.wgsl\"\nnested\n"),
+        ("main.wgsl", "#include \"common.wgsl\"\n#include \"nested.wgsl\"\n#include \"main.wgsl\"\nmain\n"),
+    ];
+
+    fn process(source: &'static str, defines: &[&'static str]) -> String {
+        let mut preprocessor = Preprocessor::new(TEST_SHADERS, defines);
+        preprocessor.process_source("test.wgsl", source);
+        preprocessor.output
+    }
+
+    #[test]
+    fn wgsl_ifdef_nested() {
+        let source = "\
+#ifdef A
+a
+#ifdef B
+a b
+#else
+a !b
+#endif
+#else
+!a
+#ifndef B
+!a !b
+#endif
+#endif
+end
+";
+
+        assert_eq!(process(source, &[]), "!a\n!a !b\nend\n");
+        assert_eq!(process(source, &["A"]), "a\na !b\nend\n");
+        assert_eq!(process(source, &["B"]), "!a\nend\n");
+        assert_eq!(process(source, &["A", "B"]), "a\na b\nend\n");
+    }
+
+    #[test]
+    fn wgsl_include_once() {
+        // main.wgsl also includes itself, which is ignored
+        assert_eq!(process("#include \"main.wgsl\"\n#include \"common.wgsl\"\n", &[]), "common\nnested\nmain\n");
+    }
+
+    #[test]
+    fn wgsl_include_inactive() {
+        assert_eq!(process("#ifdef A\n#include \"common.wgsl\"\n#endif\n#include \"nested.wgsl\"\n", &[]), "common\nnested\n");
+    }
+
+    #[test]
+    fn wgsl_define() {
+        let source = "\
+#include \"define.wgsl\"
+#ifdef FOO
+foo
+#endif
+#ifdef SKIPPED
+skipped
+#endif
+#define BAR
+#ifdef BAR
+bar
+#endif
+";
+
+        assert_eq!(process(source, &[]), "foo\nbar\n");
+        assert_eq!(process(source, &["SKIP"]), "foo\nskipped\nbar\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "test.wgsl: unknown directive #foo")]
+    fn wgsl_unknown_directive() {
+        process("#foo BAR\n", &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "test.wgsl: #endif without #ifdef")]
+    fn wgsl_unbalanced_endif() {
+        process("#ifdef A\n#endif\n#endif\n", &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "test.wgsl: #else without #ifdef")]
+    fn wgsl_unbalanced_else() {
+        process("#else\n", &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "test.wgsl: missing #endif")]
+    fn wgsl_missing_endif() {
+        process("#ifdef A\n#ifdef B\n#endif\n", &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "test.wgsl: invalid #include common.wgsl")]
+    fn wgsl_invalid_include() {
+        process("#include common.wgsl\n", &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown shader missing.wgsl")]
+    fn wgsl_unknown_include() {
+        process("#include \"missing.wgsl\"\n", &[]);
+    }
+
+    #[test]
+    fn wgsl_builtin_shaders() {
+        for (path, _) in SHADERS {
+            let mut preprocessor = Preprocessor::new(SHADERS, &[]);
+            preprocessor.process(path);
+            assert!(!preprocessor.output.contains('#'), "{}", path);
+        }
+    }
+}
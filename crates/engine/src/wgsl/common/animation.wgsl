@@ -0,0 +1,17 @@
+#include "common/scene.wgsl"
+
+// Returns the frame of a SpriteAnimation at scene.animation_time, it is used by
+// animate.wgsl and by the vertex shader when the GPU doesn't support compute shaders.
+fn select_frame(frames: f32, duration: f32, pendulum: bool) -> f32 {
+    let frame = floor(scene.animation_time / duration);
+
+    if pendulum {
+        let last = frames - 1.0;
+        let total = last * 2.0;
+        let position = frame % total;
+        return select(position, total - position, position > last);
+
+    } else {
+        return frame % frames;
+    }
+}
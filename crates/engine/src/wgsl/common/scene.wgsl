@@ -1,9 +1,28 @@
 struct Scene {
     max_order: f32,
 
-    // TODO figure out how to get rid of this padding
-    _padding1: f32,
-    _padding2: f32,
-    _padding3: f32,
+    // The time in milliseconds, it is set by Engine::set_time.
+    //
+    // It wraps around to 0.0 every hour so that it stays precise, see TIME_PERIOD in scene.rs.
+    //
+    // Cosmetic animations (shimmer, pulsing) should use this instead of
+    // updating the sprites every frame.
+    time: f32,
+
+    // 1.0 if DepthSettings::reversed_z is enabled
+    reversed_z: f32,
+
+    // The time which is used for sprite animations, it stays at 0.0 while
+    // animations are disabled in the QualitySettings.
+    animation_time: f32,
+
+    // Sprites with the same order are spread apart by this much, so that the
+    // sprite which is later in the scene is on top (see depth.rs).
+    tie_step: f32,
 };
 @group(0) @binding(0) var<uniform> scene: Scene;
+
+// Converts an order into the clip space z, the GPU divides it by max_order.
+fn scene_depth(order: f32) -> f32 {
+    return select(order, scene.max_order - order, scene.reversed_z != 0.0);
+}
@@ -1,3 +1,6 @@
+#include "common/scene.wgsl"
+#include "common/animation.wgsl"
+
 struct Sprite {
     @location(0) position: vec2<f32>,
     @location(1) size: vec2<f32>,
@@ -5,6 +8,11 @@ struct Sprite {
     @location(3) alpha: f32,
     @location(4) uv: vec2<f32>,
     @location(5) tile: vec4<u32>,
+    @location(6) uv_offset: vec2<f32>,
+    @location(7) flags: u32,
+    @location(8) hue_shift: f32,
+    @location(9) animation: vec4<f32>,
+    @location(10) frame: f32,
 };
 
 
@@ -34,6 +42,61 @@ fn make_uv(uv: vec2<f32>, vert_x: i32, vert_y: i32) -> vec2<f32> {
     return vec2(uv_x, uv_y);
 }
 
+fn sprite_uv(sprite: Sprite, vert_x: i32, vert_y: i32) -> vec2<f32> {
+    return make_uv(sprite.uv, vert_x, vert_y) + sprite.uv_offset;
+}
+
+// These must be kept in sync with the flags in sprite.rs
+const FLIP_X: u32 = 1u;
+const FLIP_Y: u32 = 2u;
+const ANIMATION_PENDULUM: u32 = 4u;
+
+// The frame was selected by animate.wgsl
+const ANIMATION_FRAME: u32 = 8u;
+
+// The index of the sprite among the sprites with the same order is stored in the upper bits
+const TIE_SHIFT: u32 = 8u;
+
+fn has_flag(sprite: Sprite, flag: u32) -> bool {
+    return (sprite.flags & flag) != 0u;
+}
+
+// The animation is [frames, duration, offset_x, offset_y]
+//
+// The frame is usually selected by the compute pass in animate.wgsl, but compute
+// shaders aren't supported by WebGL, so then it is selected in the vertex shader.
+fn animation_frame(sprite: Sprite) -> f32 {
+    let frames = sprite.animation[0];
+
+    if frames < 2.0 {
+        return 0.0;
+    }
+
+    if has_flag(sprite, ANIMATION_FRAME) {
+        return sprite.frame;
+    }
+
+    return select_frame(frames, sprite.animation[1], has_flag(sprite, ANIMATION_PENDULUM));
+}
+
+// Flipping is done by swapping the start / end of the tile
+fn sprite_tile(sprite: Sprite) -> vec4<u32> {
+    let frame = animation_frame(sprite);
+    let offset = vec2<i32>(sprite.animation.zw * frame);
+
+    var tile = vec4<u32>(vec4<i32>(sprite.tile) + vec4(offset, offset));
+
+    if has_flag(sprite, FLIP_X) {
+        tile = vec4(tile[2], tile[1], tile[0], tile[3]);
+    }
+
+    if has_flag(sprite, FLIP_Y) {
+        tile = vec4(tile[0], tile[3], tile[2], tile[1]);
+    }
+
+    return tile;
+}
+
 fn tile_uv(uv: vec2<f32>, tile: vec4<u32>) -> vec2<u32> {
     let x = u32(mix(f32(tile[0]), f32(tile[2]), uv.x));
     let y = u32(mix(f32(tile[1]), f32(tile[3]), uv.y));
@@ -45,8 +108,8 @@ fn sprite_clip_position(sprite: Sprite, vert_x: i32, vert_y: i32) -> vec4<f32> {
     let x = f32(vert_x) * sprite.size.x + sprite.position.x;
     let y = f32(vert_y) * sprite.size.y + sprite.position.y;
 
-    let order = sprite.order;
+    let order = sprite.order + f32(sprite.flags >> TIE_SHIFT) * scene.tie_step;
     let max_order = scene.max_order;
 
-    return vec4<f32>(x * max_order, y * max_order, order, max_order);
+    return vec4<f32>(x * max_order, y * max_order, scene_depth(order), max_order);
 }
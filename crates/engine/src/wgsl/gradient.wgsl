@@ -0,0 +1,55 @@
+#include "common/scene.wgsl"
+
+struct Gradient {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) order: f32,
+    @location(3) alpha: f32,
+    @location(4) top_left: vec3<f32>,
+    @location(5) top_right: vec3<f32>,
+    @location(6) bottom_left: vec3<f32>,
+    @location(7) bottom_right: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) alpha: f32,
+    @location(2) @interpolate(flat) top_left: vec3<f32>,
+    @location(3) @interpolate(flat) top_right: vec3<f32>,
+    @location(4) @interpolate(flat) bottom_left: vec3<f32>,
+    @location(5) @interpolate(flat) bottom_right: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+    gradient: Gradient,
+) -> VertexOutput {
+    let vert_x = select(0.0, 1.0, in_vertex_index < 2u);
+    let vert_y = select(0.0, 1.0, in_vertex_index % 2u == 1u);
+
+    let x = vert_x * gradient.size.x + gradient.position.x;
+    let y = vert_y * gradient.size.y + gradient.position.y;
+
+    let max_order = scene.max_order;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x * max_order, y * max_order, scene_depth(gradient.order), max_order);
+    // The position is the lower-left corner, so the uv is flipped vertically
+    out.uv = vec2<f32>(vert_x, 1.0 - vert_y);
+    out.alpha = gradient.alpha;
+    out.top_left = gradient.top_left;
+    out.top_right = gradient.top_right;
+    out.bottom_left = gradient.bottom_left;
+    out.bottom_right = gradient.bottom_right;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Bilinear interpolation, because interpolating per triangle would create a seam along the diagonal
+    let top = mix(in.top_left, in.top_right, in.uv.x);
+    let bottom = mix(in.bottom_left, in.bottom_right, in.uv.x);
+    return vec4<f32>(mix(top, bottom, in.uv.y), in.alpha);
+}
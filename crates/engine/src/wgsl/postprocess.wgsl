@@ -0,0 +1,124 @@
+@group(0) @binding(0) var texture_sampler: sampler;
+@group(0) @binding(1) var depth_sampler: sampler;
+@group(0) @binding(2) var color: texture_2d<f32>;
+@group(0) @binding(3) var depth: texture_depth_2d;
+//@group(0) @binding(4) var stencil: texture_2d<u32>;
+
+struct Effect {
+    tint: vec3<f32>,
+    vignette: f32,
+    invert: f32,
+    speed_lines: f32,
+    time: f32,
+    fade: f32,
+    wipe: f32,
+    mosaic: f32,
+};
+
+@group(1) @binding(0) var<uniform> effect: Effect;
+
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    let vert_x = i32(in_vertex_index) < 2;
+    let vert_y = i32(in_vertex_index) % 2;
+
+    let uv = vec2(
+        select(0.0, 1.0, vert_x),
+        select(0.0, 1.0, vert_y == 0),
+    );
+
+    let x = f32(vert_x) * 2.0 - 1.0;
+    let y = f32(vert_y) * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn debug_depth(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(0.0, textureSample(depth, texture_sampler, in.uv), 0.0, 1.0);
+}
+
+/*
+    let size = textureDimensions(stencil);
+    let coord = vec2<u32>(in.uv * vec2<f32>(size));
+    /*let coord = vec2(
+        u32(in.uv.x * f32(size.x)),
+        u32(in.uv.y * f32(size.y)),
+    );*/
+    let s: vec4<u32> = textureLoad(stencil, coord, 0);
+    return vec4(f32(s.r), 0.0, 0.0, 1.0);
+*/
+
+/*fn debug_stencil(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(textureSample(depth, texture_sampler, in.uv), 0.0, 0.0, 1.0);
+}*/
+
+fn hash(x: f32) -> f32 {
+    return fract(sin(x * 127.1) * 43758.5453);
+}
+
+fn apply_invert(color: vec3<f32>) -> vec3<f32> {
+    return mix(color, vec3(1.0) - color, effect.invert);
+}
+
+fn apply_vignette(color: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+    // 0.0 in the center, 1.0 in the corners
+    let distance = length(uv - vec2(0.5)) * sqrt(2.0);
+    let amount = smoothstep(0.4, 1.0, distance) * effect.vignette;
+    return mix(color, effect.tint, amount);
+}
+
+fn apply_speed_lines(color: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+    let rows = 64.0;
+    let row = floor(uv.y * rows);
+
+    // Each row has its own random speed and length
+    let speed = 1.0 + hash(row) * 2.0;
+    let size = 0.1 + hash(row + 0.5) * 0.3;
+
+    // Time is in milliseconds
+    let x = fract(uv.x + (effect.time / 1000.0) * speed + hash(row + 0.25));
+
+    let line = smoothstep(1.0 - size, 1.0, x) * step(0.6, hash(row + 0.75));
+
+    return mix(color, vec3(1.0), line * effect.speed_lines * 0.6);
+}
+
+fn mosaic_uv(uv: vec2<f32>) -> vec2<f32> {
+    let size = vec2<f32>(textureDimensions(color));
+
+    // The squares grow up to 32 pixels
+    let block = max(1.0, round(effect.mosaic * 32.0));
+
+    return (floor(uv * size / block) + 0.5) * block / size;
+}
+
+fn apply_fade(color: vec3<f32>) -> vec3<f32> {
+    return mix(color, vec3(0.0), effect.fade);
+}
+
+fn apply_wipe(color: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+    return select(color, vec3(0.0), uv.x < effect.wipe);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var rgb = textureSample(color, texture_sampler, mosaic_uv(in.uv)).rgb;
+    rgb = apply_invert(rgb);
+    rgb = apply_vignette(rgb, in.uv);
+    rgb = apply_speed_lines(rgb, in.uv);
+    rgb = apply_fade(rgb);
+    rgb = apply_wipe(rgb, in.uv);
+    return vec4(rgb, 1.0);
+    //return debug_depth(in);
+}
@@ -0,0 +1,39 @@
+#include "common/scene.wgsl"
+
+struct Shape {
+    @location(0) position: vec2<f32>,
+    @location(1) axis_x: vec2<f32>,
+    @location(2) axis_y: vec2<f32>,
+    @location(3) order: f32,
+    @location(4) alpha: f32,
+    @location(5) color: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+    shape: Shape,
+) -> VertexOutput {
+    let vert_x = select(0.0, 1.0, in_vertex_index < 2u);
+    let vert_y = select(0.0, 1.0, in_vertex_index % 2u == 1u);
+
+    // Shapes are parallelograms, which allows for rotated lines
+    let position = shape.position + (shape.axis_x * vert_x) + (shape.axis_y * vert_y);
+
+    let max_order = scene.max_order;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(position * max_order, scene_depth(shape.order), max_order);
+    out.color = vec4<f32>(shape.color, shape.alpha);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
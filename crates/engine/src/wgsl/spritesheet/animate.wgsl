@@ -0,0 +1,39 @@
+// Selects the frame of every SpriteAnimation, see SpriteAnimator in animation.rs
+//
+// The frame is written into the frame field of the sprite, and the ANIMATION_FRAME
+// flag tells the vertex shader to use it instead of selecting the frame itself.
+#include "common/animation.wgsl"
+
+// These must be kept in sync with GPUSprite in sprite.rs
+const SPRITE_WORDS: u32 = 21u;
+const FLAGS: u32 = 14u;
+const ANIMATION: u32 = 16u;
+const FRAME: u32 = 20u;
+
+const ANIMATION_PENDULUM: u32 = 4u;
+const ANIMATION_FRAME: u32 = 8u;
+
+@group(1) @binding(0) var<storage, read_write> sprites: array<u32>;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let start = id.x * SPRITE_WORDS;
+
+    if start >= arrayLength(&sprites) {
+        return;
+    }
+
+    let frames = bitcast<f32>(sprites[start + ANIMATION]);
+
+    if frames < 2.0 {
+        return;
+    }
+
+    let duration = bitcast<f32>(sprites[start + ANIMATION + 1u]);
+    let flags = sprites[start + FLAGS];
+
+    let frame = select_frame(frames, duration, (flags & ANIMATION_PENDULUM) != 0u);
+
+    sprites[start + FRAME] = bitcast<u32>(frame);
+    sprites[start + FLAGS] = flags | ANIMATION_FRAME;
+}
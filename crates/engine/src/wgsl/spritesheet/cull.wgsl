@@ -0,0 +1,85 @@
+// Removes the sprites which are outside of the screen, see SpriteCulling in culling.rs
+//
+// The visible sprites of each range are copied to the start of the range in the output,
+// and the number of visible sprites is written into the indirect draw arguments of the range.
+
+// Must be kept in sync with the size of GPUSprite in sprite.rs
+const SPRITE_WORDS: u32 = 21u;
+
+struct DrawArgs {
+    vertex_count: u32,
+    instance_count: atomic<u32>,
+    first_vertex: u32,
+    first_instance: u32,
+};
+
+struct Ranges {
+    // The number of sprites in every range
+    total: u32,
+    count: u32,
+    // The index of the first sprite in each range
+    starts: array<u32>,
+};
+
+@group(0) @binding(0) var<storage, read> ranges: Ranges;
+@group(0) @binding(1) var<storage, read_write> args: array<DrawArgs>;
+@group(0) @binding(2) var<storage, read> sprites: array<u32>;
+@group(0) @binding(3) var<storage, read_write> culled_sprites: array<u32>;
+#ifdef PALETTE
+@group(0) @binding(4) var<storage, read> palettes: array<u32>;
+@group(0) @binding(5) var<storage, read_write> culled_palettes: array<u32>;
+#endif
+#ifdef BATCHED
+@group(0) @binding(6) var<storage, read> textures: array<u32>;
+@group(0) @binding(7) var<storage, read_write> culled_textures: array<u32>;
+#endif
+
+// The position and size are in wgpu coordinates, so the screen is from -1.0 to 1.0
+fn is_visible(index: u32) -> bool {
+    let start = index * SPRITE_WORDS;
+
+    let position = vec2(bitcast<f32>(sprites[start]), bitcast<f32>(sprites[start + 1u]));
+    let size = vec2(bitcast<f32>(sprites[start + 2u]), bitcast<f32>(sprites[start + 3u]));
+    let end = position + size;
+
+    return all(min(position, end) < vec2(1.0)) && all(max(position, end) > vec2(-1.0));
+}
+
+fn range_index(index: u32) -> u32 {
+    var range = 0u;
+
+    // There are usually only a few ranges, one for each Mask
+    for (var i = 1u; i < ranges.count; i += 1u) {
+        if ranges.starts[i] > index {
+            break;
+        }
+
+        range = i;
+    }
+
+    return range;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+
+    if index >= ranges.total || !is_visible(index) {
+        return;
+    }
+
+    let range = range_index(index);
+    let culled = ranges.starts[range] + atomicAdd(&args[range].instance_count, 1u);
+
+    for (var word = 0u; word < SPRITE_WORDS; word += 1u) {
+        culled_sprites[culled * SPRITE_WORDS + word] = sprites[index * SPRITE_WORDS + word];
+    }
+
+#ifdef PALETTE
+    culled_palettes[culled] = palettes[index];
+#endif
+
+#ifdef BATCHED
+    culled_textures[culled] = textures[index];
+#endif
+}
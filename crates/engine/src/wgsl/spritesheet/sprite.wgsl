@@ -0,0 +1,142 @@
+#include "common/sprite.wgsl"
+
+#ifdef PALETTE
+struct Palette {
+    @location(11) palette: u32,
+}
+#endif
+
+#ifdef BATCHED
+// The spritesheets of a SpritesheetBatch, the size must be kept in sync with
+// SpritesheetBatch::MAX_SPRITESHEETS in sprite.rs
+#ifdef PALETTE
+@group(1) @binding(0) var spritesheets: binding_array<texture_2d<u32>, 8>;
+@group(1) @binding(1) var palettes: binding_array<texture_2d<f32>, 8>;
+#else
+@group(1) @binding(0) var spritesheets: binding_array<texture_2d<f32>, 8>;
+#endif
+
+// The index of the sprite's spritesheet in the batch
+struct Batch {
+    @location(12) texture: u32,
+}
+#else
+#ifdef PALETTE
+@group(1) @binding(0) var spritesheet: texture_2d<u32>;
+@group(1) @binding(1) var palette: texture_2d<f32>;
+#else
+@group(1) @binding(0) var spritesheet: texture_2d<f32>;
+#endif
+#endif
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) alpha: f32,
+    @location(1) uv: vec2<f32>,
+    @location(2) tile: vec4<u32>,
+#ifdef PALETTE
+    @location(3) palette: u32,
+#else
+    @location(3) @interpolate(flat) hue_shift: f32,
+#endif
+#ifdef BATCHED
+    @location(4) @interpolate(flat) texture: u32,
+#endif
+};
+
+#ifndef PALETTE
+fn rgb_to_hsv(c: vec3<f32>) -> vec3<f32> {
+    let K = vec4(0.0, -1.0 / 3.0, 2.0 / 3.0, -1.0);
+    let p = mix(vec4(c.bg, K.wz), vec4(c.gb, K.xy), step(c.b, c.g));
+    let q = mix(vec4(p.xyw, c.r), vec4(c.r, p.yzx), step(p.x, c.r));
+
+    let d = q.x - min(q.w, q.y);
+    let e = 1.0e-10;
+    return vec3(abs(q.z + (q.w - q.y) / (6.0 * d + e)), d / (q.x + e), q.x);
+}
+
+fn hsv_to_rgb(c: vec3<f32>) -> vec3<f32> {
+    let K = vec4(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
+    let p = abs(fract(c.xxx + K.xyz) * 6.0 - K.www);
+    return c.z * mix(K.xxx, clamp(p - K.xxx, vec3(0.0), vec3(1.0)), c.y);
+}
+
+fn shift_hue(color: vec3<f32>, shift: f32) -> vec3<f32> {
+    let hsv = rgb_to_hsv(color);
+    return hsv_to_rgb(vec3(fract(hsv.x + shift), hsv.y, hsv.z));
+}
+#endif
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+    sprite: Sprite,
+#ifdef PALETTE
+    palette: Palette,
+#endif
+#ifdef BATCHED
+    batch: Batch,
+#endif
+) -> VertexOutput {
+    let vert_x = quad_x(in_vertex_index);
+    let vert_y = quad_y(in_vertex_index);
+
+    var out: VertexOutput;
+    out.clip_position = sprite_clip_position(sprite, vert_x, vert_y);
+    out.alpha = sprite.alpha;
+    out.uv = sprite_uv(sprite, vert_x, vert_y);
+    out.tile = sprite_tile(sprite);
+#ifdef PALETTE
+    out.palette = palette.palette;
+#else
+    out.hue_shift = sprite.hue_shift;
+#endif
+#ifdef BATCHED
+    out.texture = batch.texture;
+#endif
+    return out;
+}
+
+// Custom pipelines provide their own fs_main, see PipelineHandle
+#ifndef CUSTOM_FRAGMENT
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = tile_uv(normalize_uv(in.uv), in.tile);
+
+#ifdef PALETTE
+#ifdef BATCHED
+    let index: vec4<u32> = textureLoad(spritesheets[in.texture], uv, 0);
+#else
+    let index: vec4<u32> = textureLoad(spritesheet, uv, 0);
+#endif
+
+    if index.g == 0u {
+        discard;
+
+    } else {
+#ifdef BATCHED
+        let color = textureLoad(palettes[in.texture], vec2(index.r, in.palette), 0);
+#else
+        let color = textureLoad(palette, vec2(index.r, in.palette), 0);
+#endif
+        return vec4(color.rgb, in.alpha);
+    }
+#else
+#ifdef BATCHED
+    let color = textureLoad(spritesheets[in.texture], uv, 0);
+#else
+    let color = textureLoad(spritesheet, uv, 0);
+#endif
+
+    if color.a == 0.0 {
+        discard;
+
+    } else if in.hue_shift == 0.0 {
+        return vec4(color.rgb, in.alpha);
+
+    } else {
+        return vec4(shift_hue(color.rgb, in.hue_shift), in.alpha);
+    }
+#endif
+}
+#endif
@@ -1,8 +1,10 @@
+#include "common/sprite.wgsl"
+
 @group(1) @binding(0) var spritesheet: texture_2d<u32>;
 
 
 struct Text {
-    @location(6) color: vec3<f32>,
+    @location(11) color: vec3<f32>,
 };
 
 struct VertexOutput {
@@ -23,8 +25,8 @@ fn vs_main(
 
     var out: VertexOutput;
     out.clip_position = sprite_clip_position(sprite, vert_x, vert_y);
-    out.uv = make_uv(sprite.uv, vert_x, vert_y);
-    out.tile = sprite.tile;
+    out.uv = sprite_uv(sprite, vert_x, vert_y);
+    out.tile = sprite_tile(sprite);
     out.color = text.color;
     return out;
 }
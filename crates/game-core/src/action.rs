@@ -0,0 +1,201 @@
+use crate::{Nation};
+use crate::unit::{UnitClass, can_share_tile};
+use crate::team::{Teams};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MoveDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl MoveDirection {
+    /// Returns the tile which is next to `(x, y)` in this direction.
+    #[inline]
+    pub fn step(self, (x, y): (i64, i64)) -> (i64, i64) {
+        match self {
+            Self::Up => (x, y - 1),
+            Self::Down => (x, y + 1),
+            Self::Left => (x - 1, y),
+            Self::Right => (x + 1, y),
+        }
+    }
+}
+
+
+/// Who is on a tile of a path, see [`resolve_path`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileOccupant {
+    /// The unit can stop on the tile, because it is empty or the unit can share it (such as a transport).
+    Free,
+
+    /// An allied unit, the unit can move through it but it can't stop on it.
+    Friendly,
+
+    /// A unit which isn't allied, the unit is trapped before it.
+    Enemy,
+}
+
+
+impl TileOccupant {
+    /// Returns who is on the tile for a unit which moves through it, `others` are the other units on the tile.
+    ///
+    /// This is used by `Grid::move_path` in the game renderer and by the server, so they always agree.
+    ///
+    /// The unit can move through allied units, and it can stop on the tile if it can share it with them (see [`can_share_tile`]).
+    pub fn new(unit: (UnitClass, Nation), others: &[(UnitClass, Nation)], teams: &Teams) -> Self {
+        if others.iter().any(|(_, nation)| !teams.are_allies(*nation, unit.1)) {
+            return Self::Enemy;
+        }
+
+        let mut units = others.to_vec();
+        units.push(unit);
+
+        if can_share_tile(&units) {
+            Self::Free
+
+        } else {
+            Self::Friendly
+        }
+    }
+}
+
+
+/// Why a unit stopped moving, see [`resolve_path`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathEnd {
+    /// The unit moved along the entire path.
+    Finished,
+
+    /// The step at `index` has an enemy unit.
+    Trapped {
+        index: usize,
+    },
+
+    /// The step at `index` is impassable, or the unit doesn't have enough movement points or fuel for it.
+    Blocked {
+        index: usize,
+    },
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedPath {
+    /// How many steps of the path the unit moves, it stops on the last tile where it is allowed to stop.
+    pub steps: usize,
+    pub end: PathEnd,
+}
+
+/// Finds how far a unit moves along the path, starting at the tile `start`.
+///
+/// This is used by `Grid::move_path` in the game renderer and by the server, so they always agree.
+///
+/// Each step costs the tile's movement cost and `1` fuel. `tile` returns the movement cost of the tile
+/// for the unit (`None` if the unit can't move through it), and who is on the tile.
+pub fn resolve_path<F>(start: (i64, i64), movement: u32, fuel: u32, path: &[MoveDirection], mut tile: F) -> ResolvedPath
+    where F: FnMut((i64, i64)) -> (Option<u32>, TileOccupant) {
+
+    let mut position = start;
+    let mut movement = movement;
+    let mut fuel = fuel;
+    let mut steps = 0;
+
+    for (index, direction) in path.iter().enumerate() {
+        position = direction.step(position);
+
+        let (cost, occupant) = tile(position);
+
+        match cost {
+            Some(cost) if cost <= movement && fuel > 0 => {
+                movement -= cost;
+                fuel -= 1;
+            },
+            _ => {
+                return ResolvedPath { steps, end: PathEnd::Blocked { index } };
+            },
+        }
+
+        match occupant {
+            TileOccupant::Free => {
+                steps = index + 1;
+            },
+            TileOccupant::Friendly => {},
+            TileOccupant::Enemy => {
+                return ResolvedPath { steps, end: PathEnd::Trapped { index } };
+            },
+        }
+    }
+
+    ResolvedPath { steps, end: PathEnd::Finished }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{Nation};
+    use crate::unit::{UnitClass};
+    use crate::team::{Teams};
+    use super::{MoveDirection, TileOccupant, PathEnd, ResolvedPath, resolve_path};
+    use MoveDirection::{Right, Left};
+
+    /// A row of tiles, `'.'` costs 1, `'^'` costs 2, `'#'` is impassable, `'f'` is friendly, and `'e'` is an enemy.
+    fn row(tiles: &'static str) -> impl FnMut((i64, i64)) -> (Option<u32>, TileOccupant) {
+        move |(x, _)| {
+            match usize::try_from(x).ok().and_then(|x| tiles.as_bytes().get(x)) {
+                Some(b'.') => (Some(1), TileOccupant::Free),
+                Some(b'^') => (Some(2), TileOccupant::Free),
+                Some(b'f') => (Some(1), TileOccupant::Friendly),
+                Some(b'e') => (Some(1), TileOccupant::Enemy),
+                _ => (None, TileOccupant::Free),
+            }
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        assert_eq!(resolve_path((0, 0), 3, 99, &[Right, Right, Right], row("....")), ResolvedPath { steps: 3, end: PathEnd::Finished });
+        assert_eq!(resolve_path((0, 0), 3, 99, &[], row("....")), ResolvedPath { steps: 0, end: PathEnd::Finished });
+
+        // Outside of the map
+        assert_eq!(resolve_path((0, 0), 3, 99, &[Left], row("....")), ResolvedPath { steps: 0, end: PathEnd::Blocked { index: 0 } });
+
+        // Impassable terrain and not enough movement
+        assert_eq!(resolve_path((0, 0), 3, 99, &[Right, Right], row("..#.")), ResolvedPath { steps: 1, end: PathEnd::Blocked { index: 1 } });
+        assert_eq!(resolve_path((0, 0), 3, 99, &[Right, Right, Right], row(".^^.")), ResolvedPath { steps: 1, end: PathEnd::Blocked { index: 1 } });
+
+        // Not enough fuel
+        assert_eq!(resolve_path((0, 0), 3, 2, &[Right, Right, Right], row("....")), ResolvedPath { steps: 2, end: PathEnd::Blocked { index: 2 } });
+    }
+
+    #[test]
+    fn resolve_units() {
+        // It can move through friendly units, but it can't stop on them
+        assert_eq!(resolve_path((0, 0), 3, 99, &[Right, Right], row(".f..")), ResolvedPath { steps: 2, end: PathEnd::Finished });
+        assert_eq!(resolve_path((0, 0), 3, 99, &[Right], row(".f..")), ResolvedPath { steps: 0, end: PathEnd::Finished });
+
+        // It stops at the last free tile before the enemy
+        assert_eq!(resolve_path((0, 0), 3, 99, &[Right, Right, Right], row("..fe")), ResolvedPath { steps: 1, end: PathEnd::Trapped { index: 2 } });
+    }
+
+    #[test]
+    fn occupant() {
+        let mut teams = Teams::new();
+        teams.set_team(Nation::BlueMoon, teams.team(Nation::OrangeStar));
+
+        let infantry = (UnitClass::Infantry, Nation::OrangeStar);
+
+        assert_eq!(TileOccupant::new(infantry, &[], &teams), TileOccupant::Free);
+
+        // Units of the same nation or an allied nation can be moved through
+        assert_eq!(TileOccupant::new(infantry, &[(UnitClass::Infantry, Nation::OrangeStar)], &teams), TileOccupant::Friendly);
+        assert_eq!(TileOccupant::new(infantry, &[(UnitClass::Infantry, Nation::BlueMoon)], &teams), TileOccupant::Friendly);
+        assert_eq!(TileOccupant::new(infantry, &[(UnitClass::Infantry, Nation::GreenEarth)], &teams), TileOccupant::Enemy);
+
+        // It can stop on a transport of the same nation
+        assert_eq!(TileOccupant::new(infantry, &[(UnitClass::APC, Nation::OrangeStar)], &teams), TileOccupant::Free);
+        assert_eq!(TileOccupant::new(infantry, &[(UnitClass::APC, Nation::BlueMoon)], &teams), TileOccupant::Friendly);
+    }
+}
@@ -0,0 +1,346 @@
+//! Finds nondeterminism by comparing snapshots of the game state.
+//!
+//! Every peer (or every playback of a replay) records a [`StateSnapshot`] at the end of each turn.
+//! The hashes are cheap to send, and if they are different then [`StateSnapshot::divergence`]
+//! finds the first unit / building / field which is different.
+//!
+//! Float math and iteration order are the usual causes, so floats are hashed with their exact bits.
+
+use std::hash::{Hash, Hasher};
+
+use crate::{Nation};
+use crate::unit::{UnitClass, Rank};
+use crate::building::{BuildingClass};
+use crate::map::{StableHasher};
+
+
+/// The state of a unit which affects the gameplay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitSnapshot {
+    /// The unit's id, the ids are allocated in order so they are the same on every peer.
+    pub id: u32,
+
+    /// The unit's coord, these are floats so that units which are
+    /// slightly off of their tile are also detected.
+    pub x: f32,
+    pub y: f32,
+    pub class: UnitClass,
+    pub nation: Nation,
+    pub hp: u32,
+    pub fuel: u32,
+    pub kills: u32,
+    pub rank: Rank,
+    pub waited: bool,
+}
+
+impl UnitSnapshot {
+    fn hash<H>(&self, hasher: &mut H) where H: Hasher {
+        self.id.hash(hasher);
+        self.x.to_bits().hash(hasher);
+        self.y.to_bits().hash(hasher);
+        self.class.hash(hasher);
+        self.nation.hash(hasher);
+        self.hp.hash(hasher);
+        self.fuel.hash(hasher);
+        self.kills.hash(hasher);
+        self.rank.hash(hasher);
+        self.waited.hash(hasher);
+    }
+
+    /// Returns the name of the first field which is different.
+    fn different_field(&self, other: &Self) -> Option<&'static str> {
+        if self.id != other.id {
+            Some("id")
+
+        } else if self.x.to_bits() != other.x.to_bits() || self.y.to_bits() != other.y.to_bits() {
+            Some("coord")
+
+        } else if self.class != other.class {
+            Some("class")
+
+        } else if self.nation != other.nation {
+            Some("nation")
+
+        } else if self.hp != other.hp {
+            Some("hp")
+
+        } else if self.fuel != other.fuel {
+            Some("fuel")
+
+        } else if self.kills != other.kills {
+            Some("kills")
+
+        } else if self.rank != other.rank {
+            Some("rank")
+
+        } else if self.waited != other.waited {
+            Some("waited")
+
+        } else {
+            None
+        }
+    }
+}
+
+
+/// The state of a building which affects the gameplay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BuildingSnapshot {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub class: BuildingClass,
+    pub nation: Option<Nation>,
+}
+
+
+/// Where two snapshots first differ, see [`StateSnapshot::divergence`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    Turn {
+        expected: u32,
+        found: u32,
+    },
+
+    /// The state of the random number generator is different, so some code used a different amount of random numbers.
+    Rng,
+
+    /// A different number of units were created, even if the same units are still alive.
+    NextUnitId {
+        expected: u32,
+        found: u32,
+    },
+
+    UnitCount {
+        expected: usize,
+        found: usize,
+    },
+
+    /// The unit at `index` is different. The coord is from the expected snapshot.
+    Unit {
+        index: usize,
+        x: f32,
+        y: f32,
+        field: &'static str,
+    },
+
+    BuildingCount {
+        expected: usize,
+        found: usize,
+    },
+
+    /// The building on the tile is different.
+    Building {
+        x: u32,
+        y: u32,
+    },
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Turn { expected, found } => write!(f, "Turn is {}, expected {}", found, expected),
+            Self::Rng => write!(f, "RNG state is different"),
+            Self::NextUnitId { expected, found } => write!(f, "Next unit id is {}, expected {}", found, expected),
+            Self::UnitCount { expected, found } => write!(f, "There are {} units, expected {}", found, expected),
+            Self::Unit { index, x, y, field } => write!(f, "Unit {} at {},{} has a different {}", index, x, y, field),
+            Self::BuildingCount { expected, found } => write!(f, "There are {} buildings, expected {}", found, expected),
+            Self::Building { x, y } => write!(f, "Building at {},{} is different", x, y),
+        }
+    }
+}
+
+
+/// The gameplay state at the end of a turn.
+///
+/// The units and buildings are sorted by their id, so the order that
+/// they are stored in the grid doesn't change the hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateSnapshot {
+    pub turn: u32,
+
+    /// See [`Rng::state`](crate::random::Rng::state).
+    pub rng: u64,
+
+    /// The id of the next unit which will be created, this is needed because destroyed units don't free their ids.
+    pub next_unit_id: u32,
+
+    pub units: Vec<UnitSnapshot>,
+    pub buildings: Vec<BuildingSnapshot>,
+}
+
+impl StateSnapshot {
+    pub fn new(turn: u32, rng: u64, next_unit_id: u32, mut units: Vec<UnitSnapshot>, mut buildings: Vec<BuildingSnapshot>) -> Self {
+        units.sort_by_key(|unit| unit.id);
+        buildings.sort_by_key(|building| building.id);
+
+        Self { turn, rng, next_unit_id, units, buildings }
+    }
+
+    /// Hash which is the same on every platform, so it can be compared between peers.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+
+        self.turn.hash(&mut hasher);
+        self.rng.hash(&mut hasher);
+        self.next_unit_id.hash(&mut hasher);
+
+        self.units.len().hash(&mut hasher);
+
+        for unit in self.units.iter() {
+            unit.hash(&mut hasher);
+        }
+
+        self.buildings.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// Returns the first difference between the snapshots, or `None` if they are the same.
+    pub fn divergence(&self, found: &Self) -> Option<Divergence> {
+        if self.turn != found.turn {
+            return Some(Divergence::Turn { expected: self.turn, found: found.turn });
+        }
+
+        if self.rng != found.rng {
+            return Some(Divergence::Rng);
+        }
+
+        if self.next_unit_id != found.next_unit_id {
+            return Some(Divergence::NextUnitId { expected: self.next_unit_id, found: found.next_unit_id });
+        }
+
+        for (index, (expected, found)) in self.units.iter().zip(found.units.iter()).enumerate() {
+            if let Some(field) = expected.different_field(found) {
+                return Some(Divergence::Unit { index, x: expected.x, y: expected.y, field });
+            }
+        }
+
+        if self.units.len() != found.units.len() {
+            return Some(Divergence::UnitCount { expected: self.units.len(), found: found.units.len() });
+        }
+
+        for (expected, found) in self.buildings.iter().zip(found.buildings.iter()) {
+            if expected != found {
+                return Some(Divergence::Building { x: expected.x, y: expected.y });
+            }
+        }
+
+        if self.buildings.len() != found.buildings.len() {
+            return Some(Divergence::BuildingCount { expected: self.buildings.len(), found: found.buildings.len() });
+        }
+
+        None
+    }
+}
+
+
+/// The snapshots of every turn, so a desync can be reported with the turn it started on.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    snapshots: Vec<StateSnapshot>,
+}
+
+impl AuditLog {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the snapshot and returns its hash, which should be sent to the other peers.
+    pub fn record(&mut self, snapshot: StateSnapshot) -> u64 {
+        let hash = snapshot.stable_hash();
+        self.snapshots.push(snapshot);
+        hash
+    }
+
+    /// Returns the snapshot which was recorded for the turn.
+    pub fn get(&self, turn: u32) -> Option<&StateSnapshot> {
+        self.snapshots.iter().rev().find(|snapshot| snapshot.turn == turn)
+    }
+
+    /// Whether the hash from another peer matches the recorded snapshot for the turn.
+    ///
+    /// Returns `None` if the turn wasn't recorded.
+    pub fn matches(&self, turn: u32, hash: u64) -> Option<bool> {
+        self.get(turn).map(|snapshot| snapshot.stable_hash() == hash)
+    }
+
+    /// Compares the logs turn by turn, and returns the first difference.
+    ///
+    /// This is used after [`matches`](AuditLog::matches) returned `false`, with the log from the other peer or replay.
+    pub fn first_divergence(&self, other: &Self) -> Option<Divergence> {
+        self.snapshots.iter().zip(other.snapshots.iter()).find_map(|(expected, found)| expected.divergence(found))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{StateSnapshot, UnitSnapshot, BuildingSnapshot, AuditLog, Divergence};
+    use crate::{Nation};
+    use crate::unit::{UnitClass, Rank};
+    use crate::building::{BuildingClass};
+
+    fn unit(id: u32, x: f32, y: f32) -> UnitSnapshot {
+        UnitSnapshot {
+            id,
+            x,
+            y,
+            class: UnitClass::Infantry,
+            nation: Nation::OrangeStar,
+            hp: 100,
+            fuel: 99,
+            kills: 0,
+            rank: Rank::Rookie,
+            waited: false,
+        }
+    }
+
+    fn snapshot(units: Vec<UnitSnapshot>) -> StateSnapshot {
+        StateSnapshot::new(1, 5, 2, units, vec![
+            BuildingSnapshot { id: 0, x: 0, y: 0, class: BuildingClass::City, nation: None },
+        ])
+    }
+
+    #[test]
+    fn divergence() {
+        // The order of the units doesn't matter
+        let a = snapshot(vec![unit(0, 1.0, 0.0), unit(1, 0.0, 1.0)]);
+        let b = snapshot(vec![unit(1, 0.0, 1.0), unit(0, 1.0, 0.0)]);
+
+        assert_eq!(a.stable_hash(), b.stable_hash());
+        assert_eq!(a.divergence(&b), None);
+
+        // A tiny float error is detected
+        let c = snapshot(vec![unit(0, 1.0, 0.0), unit(1, 0.0, 1.0 + f32::EPSILON)]);
+
+        assert_ne!(a.stable_hash(), c.stable_hash());
+        assert_eq!(a.divergence(&c), Some(Divergence::Unit { index: 1, x: 0.0, y: 1.0, field: "coord" }));
+
+        let mut d = snapshot(vec![unit(0, 1.0, 0.0), unit(1, 0.0, 1.0)]);
+        d.units[0].hp = 90;
+        assert_eq!(a.divergence(&d), Some(Divergence::Unit { index: 0, x: 1.0, y: 0.0, field: "hp" }));
+
+        // The same units with different ids are different
+        let e = snapshot(vec![unit(2, 1.0, 0.0), unit(1, 0.0, 1.0)]);
+        assert_eq!(a.divergence(&e), Some(Divergence::Unit { index: 0, x: 1.0, y: 0.0, field: "id" }));
+
+        let mut f = snapshot(vec![unit(0, 1.0, 0.0), unit(1, 0.0, 1.0)]);
+        f.next_unit_id = 3;
+        assert_ne!(a.stable_hash(), f.stable_hash());
+        assert_eq!(a.divergence(&f), Some(Divergence::NextUnitId { expected: 2, found: 3 }));
+
+        let mut log = AuditLog::new();
+        let hash = log.record(a);
+
+        assert_eq!(log.matches(1, hash), Some(true));
+        assert_eq!(log.matches(1, d.stable_hash()), Some(false));
+        assert_eq!(log.matches(2, hash), None);
+
+        let mut other = AuditLog::new();
+        other.record(d);
+
+        assert_eq!(log.first_divergence(&other), Some(Divergence::Unit { index: 0, x: 1.0, y: 0.0, field: "hp" }));
+    }
+}
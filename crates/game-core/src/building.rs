@@ -0,0 +1,62 @@
+use crate::registry::{Registry, BuildingSpec};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildingClass {
+    HQ1, // Orange Star
+    HQ2, // Blue Moon
+    HQ3, // Green Earth
+    HQ4, // Yellow Comet
+    HQ5, // Black Hole
+    City,
+    Base,
+    Airport,
+    Port,
+    ComTower,
+    Lab,
+    MissileSilo,
+    MissileSiloEmpty,
+
+    /// A building class which was added by a mod, see [`Registry::add_building`].
+    Custom(u16),
+    /*BlackCrystal,
+    Laser,
+    Minicannon { direction: , grass: bool },
+    Volcano,
+    BlackOnyx, // Flying Fortress
+    Fortress,
+    BlackArmageddon,
+    BlackCannon { direction: },
+    BlackObelisk,*/
+}
+
+impl BuildingClass {
+    /// Every built-in building class, use [`Registry::building_classes`] to include the custom classes.
+    pub const ALL: &[Self] = &[
+        Self::HQ1,
+        Self::HQ2,
+        Self::HQ3,
+        Self::HQ4,
+        Self::HQ5,
+        Self::City,
+        Self::Base,
+        Self::Airport,
+        Self::Port,
+        Self::ComTower,
+        Self::Lab,
+        Self::MissileSilo,
+        Self::MissileSiloEmpty,
+    ];
+
+    /// Whether the building can be owned by a nation, buildings such as missile silos are always neutral.
+    #[inline]
+    pub fn can_have_nation(&self) -> bool {
+        self.spec().can_have_nation
+    }
+
+    /// Returns the stats of the building class from the installed [`Registry`].
+    #[inline]
+    pub fn spec(&self) -> &'static BuildingSpec {
+        Registry::get().building(*self)
+    }
+}
@@ -0,0 +1,132 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use futures::stream::Stream;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+
+
+/// Typed publish / subscribe event bus.
+///
+/// This allows different game systems to react to events without
+/// needing to know about each other.
+///
+/// # Usage
+/// ```rust
+/// use futures::stream::StreamExt;
+/// use rusted_battalions_game_core::events::{Events};
+///
+/// #[derive(Debug, Clone, PartialEq)]
+/// struct UnitDestroyed {
+///     id: u32,
+/// }
+///
+/// let events = Events::new();
+///
+/// let mut stream = events.subscribe::<UnitDestroyed>();
+///
+/// events.publish(UnitDestroyed { id: 5 });
+///
+/// futures::executor::block_on(async {
+///     assert_eq!(stream.next().await, Some(UnitDestroyed { id: 5 }));
+/// });
+/// ```
+pub struct Events {
+    // TypeId of the event -> Vec<UnboundedSender<Event>>
+    senders: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl Events {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns a Stream which receives every `T` event that is published after subscribing.
+    ///
+    /// The subscription is removed when the Stream is dropped.
+    pub fn subscribe<T>(&self) -> impl Stream<Item = T> + Send + Unpin where T: Clone + Send + 'static {
+        let (sender, receiver) = unbounded();
+
+        let mut lock = self.senders.lock().unwrap();
+
+        lock.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<UnboundedSender<T>>::new()))
+            .downcast_mut::<Vec<UnboundedSender<T>>>()
+            .unwrap()
+            .push(sender);
+
+        receiver
+    }
+
+    /// Sends the event to every subscriber of `T`.
+    pub fn publish<T>(&self, event: T) where T: Clone + Send + 'static {
+        let mut lock = self.senders.lock().unwrap();
+
+        if let Some(senders) = lock.get_mut(&TypeId::of::<T>()) {
+            let senders = senders.downcast_mut::<Vec<UnboundedSender<T>>>().unwrap();
+
+            // Removes subscribers whose Stream was dropped
+            senders.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+        }
+    }
+}
+
+impl Default for Events {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::StreamExt;
+    use futures::executor::block_on;
+    use super::Events;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Foo(u32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Bar(u32);
+
+    #[test]
+    fn events_by_type() {
+        let events = Events::new();
+
+        let mut foo = events.subscribe::<Foo>();
+        let mut bar = events.subscribe::<Bar>();
+
+        events.publish(Foo(1));
+        events.publish(Bar(2));
+
+        // Events which are published before subscribing aren't received
+        let mut late = events.subscribe::<Foo>();
+
+        events.publish(Foo(3));
+
+        drop(events);
+
+        assert_eq!(block_on(foo.by_ref().collect::<Vec<_>>()), vec![Foo(1), Foo(3)]);
+        assert_eq!(block_on(bar.by_ref().collect::<Vec<_>>()), vec![Bar(2)]);
+        assert_eq!(block_on(late.by_ref().collect::<Vec<_>>()), vec![Foo(3)]);
+    }
+
+    #[test]
+    fn events_dropped_subscriber() {
+        let events = Events::new();
+
+        let dropped = events.subscribe::<Foo>();
+        let mut kept = events.subscribe::<Foo>();
+
+        drop(dropped);
+
+        events.publish(Foo(1));
+        drop(events);
+
+        assert_eq!(block_on(kept.by_ref().collect::<Vec<_>>()), vec![Foo(1)]);
+    }
+}
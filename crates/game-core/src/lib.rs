@@ -0,0 +1,50 @@
+//! The rules of Rusted Battalions, without any rendering.
+//!
+//! This doesn't depend on the engine, so it can be used by the server, the AI, and headless tests.
+
+pub mod terrain;
+pub mod building;
+pub mod unit;
+pub mod action;
+pub mod map;
+pub mod map_gen;
+pub mod random;
+pub mod replay;
+pub mod team;
+pub mod rules;
+pub mod audit;
+pub mod registry;
+pub mod save;
+pub mod events;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Nation {
+    OrangeStar,
+    BlueMoon,
+    GreenEarth,
+    YellowComet,
+    BlackHole,
+}
+
+impl Nation {
+    pub const ALL: &[Self] = &[
+        Self::OrangeStar,
+        Self::BlueMoon,
+        Self::GreenEarth,
+        Self::YellowComet,
+        Self::BlackHole,
+    ];
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Snow,
+    Sandstorm,
+
+    /// The weather changes randomly during the match.
+    Random,
+}
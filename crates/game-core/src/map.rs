@@ -0,0 +1,137 @@
+use std::hash::{Hash, Hasher};
+use crate::{Nation};
+use crate::terrain::{TerrainClass};
+use crate::building::{BuildingClass};
+
+
+/// A building which is placed on a [`MapData`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MapBuilding {
+    pub x: u32,
+    pub y: u32,
+    pub class: BuildingClass,
+    pub nation: Option<Nation>,
+}
+
+
+/// The starting terrain and buildings of a map, this doesn't contain any rendering state.
+///
+/// Use `Grid::from_map` in the game renderer to play the map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapData {
+    pub width: u32,
+    pub height: u32,
+
+    /// The terrain for every tile, in row-major order.
+    terrain: Vec<TerrainClass>,
+
+    pub buildings: Vec<MapBuilding>,
+}
+
+impl MapData {
+    /// Creates a map where every tile is `class`.
+    pub fn new(width: u32, height: u32, class: TerrainClass) -> Self {
+        Self {
+            width,
+            height,
+            terrain: vec![class; (width * height) as usize],
+            buildings: vec![],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Coordinate out of range {},{}", x, y);
+
+        ((y * self.width) + x) as usize
+    }
+
+    #[inline]
+    pub fn get(&self, x: u32, y: u32) -> TerrainClass {
+        self.terrain[self.index(x, y)]
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: u32, y: u32, class: TerrainClass) {
+        let index = self.index(x, y);
+        self.terrain[index] = class;
+    }
+
+    /// Returns the x, y, and terrain of every tile, in row-major order.
+    pub fn tiles(&self) -> impl Iterator<Item = (u32, u32, TerrainClass)> + '_ {
+        let width = self.width;
+
+        self.terrain.iter().enumerate().map(move |(index, class)| {
+            let index = index as u32;
+            (index % width, index / width, *class)
+        })
+    }
+
+    /// Returns the building which is on the tile.
+    pub fn building_at(&self, x: u32, y: u32) -> Option<&MapBuilding> {
+        self.buildings.iter().find(|building| building.x == x && building.y == y)
+    }
+
+    /// Returns a hash of the terrain and buildings, it is used to check that two maps are the same.
+    ///
+    /// Unlike [`std::hash::DefaultHasher`] the hash is the same on every platform, so it can be saved in
+    /// files such as [`Replay`](crate::Replay).
+    ///
+    /// The fields are hashed with their derived [`Hash`] impls, so changing the terrain or building
+    /// types (or a Rust release changing how the standard library hashes `Vec` or `Option`) changes the hash,
+    /// and old files will no longer match their map.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.width.hash(&mut hasher);
+        self.height.hash(&mut hasher);
+        self.terrain.hash(&mut hasher);
+        self.buildings.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+
+/// FNV-1a hasher which produces the same hash on every platform, regardless of pointer size or endianness.
+pub(crate) struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub(crate) fn new() -> Self {
+        Self { state: 0xcbf29ce484222325 }
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    #[inline]
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    // usize is a different size on wasm32, so it is always hashed as 64 bits
+    #[inline]
+    fn write_usize(&mut self, value: usize) {
+        self.write(&(value as u64).to_le_bytes());
+    }
+
+    #[inline]
+    fn write_isize(&mut self, value: isize) {
+        self.write(&(value as i64).to_le_bytes());
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
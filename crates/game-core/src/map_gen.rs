@@ -0,0 +1,579 @@
+use crate::{Nation};
+use crate::map::{MapData, MapBuilding};
+use crate::terrain::{TerrainClass, Orientation};
+use crate::building::{BuildingClass};
+use crate::random::{Rng};
+
+
+/// The first player owns the first half of the map, the second player owns the mirrored half.
+const PLAYERS: [Nation; 2] = [Nation::OrangeStar, Nation::BlueMoon];
+
+
+/// How the two halves of a generated map mirror each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    /// The right half is the left half flipped horizontally.
+    Horizontal,
+
+    /// The bottom half is the top half flipped vertically.
+    Vertical,
+
+    /// The second half is the first half rotated by 180 degrees.
+    Rotational,
+}
+
+
+/// Settings for randomly generating a 2 player map, see [`MapGenSettings::generate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapGenSettings {
+    /// The same seed and settings always generate the same map.
+    pub seed: u64,
+
+    pub width: u32,
+    pub height: u32,
+
+    pub symmetry: Symmetry,
+
+    /// How much of the map should be land, from `0.0` to `1.0`.
+    pub land: f64,
+}
+
+impl MapGenSettings {
+    #[inline]
+    pub fn new(seed: u64, width: u32, height: u32) -> Self {
+        Self {
+            seed,
+            width,
+            height,
+            symmetry: Symmetry::Horizontal,
+            land: 0.6,
+        }
+    }
+
+    /// Generates a random map with land masses, rivers, roads, and properties.
+    ///
+    /// Both halves of the map are mirrored, so both players have the same terrain and properties.
+    pub fn generate(&self) -> MapData {
+        assert!(self.width >= 4 && self.height >= 4, "Generated maps must be at least 4x4");
+
+        let mut generator = Generator {
+            rng: Rng::new(self.seed),
+            symmetry: self.symmetry,
+            map: MapData::new(self.width, self.height, TerrainClass::Ocean),
+            occupied: vec![false; (self.width * self.height) as usize],
+        };
+
+        generator.land(self.land);
+        generator.features();
+        generator.river();
+        generator.coast();
+
+        let hq = generator.headquarters();
+        let front = generator.properties();
+
+        if let Some(front) = front {
+            generator.road(hq, front);
+        }
+
+        generator.map
+    }
+}
+
+
+type Tile = (u32, u32);
+
+struct Generator {
+    rng: Rng,
+    symmetry: Symmetry,
+    map: MapData,
+
+    /// Tiles which have a building.
+    occupied: Vec<bool>,
+}
+
+impl Generator {
+    fn mirror(&self, (x, y): Tile) -> Tile {
+        let width = self.map.width;
+        let height = self.map.height;
+
+        match self.symmetry {
+            Symmetry::Horizontal => (width - 1 - x, y),
+            Symmetry::Vertical => (x, height - 1 - y),
+            Symmetry::Rotational => (width - 1 - x, height - 1 - y),
+        }
+    }
+
+    /// Tiles on the line of symmetry are their own mirror, so they are shared by both players.
+    fn is_shared(&self, tile: Tile) -> bool {
+        self.mirror(tile) == tile
+    }
+
+    /// Returns the tiles in the first half of the map, in row-major order.
+    ///
+    /// Only the first half is generated, every change is also made to the mirrored tile.
+    fn first_half(&self) -> Vec<Tile> {
+        self.map.tiles()
+            .map(|(x, y, _)| (x, y))
+            .filter(|&(x, y)| {
+                let (mirror_x, mirror_y) = self.mirror((x, y));
+                (y, x) <= (mirror_y, mirror_x)
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn get(&self, (x, y): Tile) -> TerrainClass {
+        self.map.get(x, y)
+    }
+
+    fn set(&mut self, tile: Tile, class: TerrainClass) {
+        let (mirror_x, mirror_y) = self.mirror(tile);
+
+        self.map.set(tile.0, tile.1, class);
+        self.map.set(mirror_x, mirror_y, class);
+    }
+
+    fn is_occupied(&self, (x, y): Tile) -> bool {
+        self.occupied[((y * self.map.width) + x) as usize]
+    }
+
+    fn offsets(&self, (x, y): Tile, offsets: &'static [(i32, i32)]) -> impl Iterator<Item = Tile> {
+        let width = self.map.width as i32;
+        let height = self.map.height as i32;
+
+        offsets.iter().filter_map(move |(offset_x, offset_y)| {
+            let x = x as i32 + offset_x;
+            let y = y as i32 + offset_y;
+
+            if x >= 0 && y >= 0 && x < width && y < height {
+                Some((x as u32, y as u32))
+
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The 4 tiles which are orthogonally adjacent.
+    fn adjacent(&self, tile: Tile) -> impl Iterator<Item = Tile> {
+        self.offsets(tile, &[(0, -1), (0, 1), (-1, 0), (1, 0)])
+    }
+
+    /// The 8 tiles which are orthogonally and diagonally adjacent.
+    fn surrounding(&self, tile: Tile) -> impl Iterator<Item = Tile> {
+        self.offsets(tile, &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)])
+    }
+
+    fn is_coastal(&self, tile: Tile) -> bool {
+        self.adjacent(tile).any(|tile| self.get(tile) == TerrainClass::Ocean)
+    }
+
+    fn distance(from: Tile, to: Tile) -> u32 {
+        from.0.abs_diff(to.0) + from.1.abs_diff(to.1)
+    }
+
+    fn shuffle(&mut self, tiles: &mut [Tile]) {
+        for index in (1..tiles.len()).rev() {
+            let other = self.rng.range(index as u32 + 1) as usize;
+            tiles.swap(index, other);
+        }
+    }
+
+
+    /// Adds circles of land until enough of the map is land, and then smooths the coastlines.
+    fn land(&mut self, land: f64) {
+        let tiles = self.first_half();
+
+        let target = (self.map.width * self.map.height) as f64 * land;
+
+        let max_radius = (self.map.width.min(self.map.height) / 4).max(2);
+
+        // This has a limit so that it doesn't take too long when `land` is close to 1.0
+        for _ in 0..100 {
+            let count = self.map.tiles().filter(|(_, _, class)| *class == TerrainClass::Grass).count();
+
+            if count as f64 >= target {
+                break;
+            }
+
+            let center = tiles[self.rng.range(tiles.len() as u32) as usize];
+            let radius = 2 + self.rng.range(max_radius - 1);
+
+            for &tile in tiles.iter() {
+                let x = tile.0 as i64 - center.0 as i64;
+                let y = tile.1 as i64 - center.1 as i64;
+
+                if (x * x) + (y * y) <= (radius * radius) as i64 {
+                    self.set(tile, TerrainClass::Grass);
+                }
+            }
+        }
+
+        for _ in 0..2 {
+            self.smooth();
+        }
+    }
+
+    /// Removes small islands and fills small lakes.
+    ///
+    /// Every tile is checked with the same rule, so the map stays symmetrical.
+    fn smooth(&mut self) {
+        let mut next = self.map.clone();
+
+        for (x, y, _) in self.map.tiles() {
+            let land = self.surrounding((x, y))
+                .filter(|tile| self.get(*tile) == TerrainClass::Grass)
+                .count();
+
+            if land >= 5 {
+                next.set(x, y, TerrainClass::Grass);
+
+            } else if land <= 2 {
+                next.set(x, y, TerrainClass::Ocean);
+            }
+        }
+
+        self.map = next;
+    }
+
+    fn features(&mut self) {
+        for tile in self.first_half() {
+            if self.get(tile) == TerrainClass::Grass {
+                let chance = self.rng.random();
+
+                if chance < 0.1 {
+                    self.set(tile, TerrainClass::Forest);
+
+                } else if chance < 0.16 {
+                    let variant = self.rng.range(3);
+                    self.set(tile, TerrainClass::Mountain { variant });
+                }
+            }
+        }
+    }
+
+    /// Adds a winding river which stops when it reaches the ocean.
+    fn river(&mut self) {
+        let tiles = self.first_half().into_iter()
+            .filter(|tile| self.get(*tile) != TerrainClass::Ocean)
+            .collect::<Vec<Tile>>();
+
+        if tiles.is_empty() {
+            return;
+        }
+
+        let mut tile = tiles[self.rng.range(tiles.len() as u32) as usize];
+
+        let directions = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+        let (direction_x, direction_y) = directions[self.rng.range(4) as usize];
+
+        let length = 3 + self.rng.range(self.map.width.max(self.map.height) / 2);
+
+        for _ in 0..length {
+            self.set(tile, TerrainClass::River);
+
+            // It sometimes turns sideways so that it isn't a straight line
+            let (offset_x, offset_y) = if self.rng.chance(0.25) {
+                if self.rng.chance(0.5) {
+                    (direction_y, direction_x)
+
+                } else {
+                    (-direction_y, -direction_x)
+                }
+
+            } else {
+                (direction_x, direction_y)
+            };
+
+            let x = tile.0 as i32 + offset_x;
+            let y = tile.1 as i32 + offset_y;
+
+            if x < 0 || y < 0 || x >= self.map.width as i32 || y >= self.map.height as i32 {
+                break;
+            }
+
+            tile = (x as u32, y as u32);
+
+            if self.get(tile) == TerrainClass::Ocean {
+                break;
+            }
+        }
+    }
+
+    /// Adds shoals to the coastlines and reefs to the open sea.
+    fn coast(&mut self) {
+        for tile in self.first_half() {
+            match self.get(tile) {
+                TerrainClass::Grass if self.is_coastal(tile) && self.rng.chance(0.3) => {
+                    self.set(tile, TerrainClass::Shoal);
+                },
+
+                TerrainClass::Ocean => {
+                    let open_sea = self.surrounding(tile).all(|tile| self.get(tile) == TerrainClass::Ocean);
+
+                    if open_sea && self.rng.chance(0.05) {
+                        self.set(tile, TerrainClass::Reef);
+                    }
+                },
+
+                _ => {},
+            }
+        }
+    }
+
+
+    /// Whether a building can be placed on the tile, buildings are not placed next to each other.
+    fn can_build(&self, tile: Tile) -> bool {
+        self.get(tile) == TerrainClass::Grass &&
+        !self.is_shared(tile) &&
+        !self.is_occupied(tile) &&
+        !self.surrounding(tile).any(|tile| self.is_occupied(tile))
+    }
+
+    /// Places the building for the first player, and the mirrored building for the second player.
+    fn build(&mut self, tile: Tile, class: BuildingClass, owned: bool) {
+        let mirror = self.mirror(tile);
+
+        let mirror_class = match class {
+            BuildingClass::HQ1 => BuildingClass::HQ2,
+            class => class,
+        };
+
+        for ((x, y), class, nation) in [(tile, class, PLAYERS[0]), (mirror, mirror_class, PLAYERS[1])] {
+            self.occupied[((y * self.map.width) + x) as usize] = true;
+
+            self.map.buildings.push(MapBuilding {
+                x,
+                y,
+                class,
+                nation: if owned { Some(nation) } else { None },
+            });
+        }
+    }
+
+    /// Places the HQ and starting base for each player, as far away from the other player as possible.
+    ///
+    /// Returns the tile of the first player's HQ.
+    fn headquarters(&mut self) -> Tile {
+        let mut tiles = self.first_half().into_iter()
+            .filter(|tile| self.can_build(*tile))
+            .collect::<Vec<Tile>>();
+
+        // The map is entirely water, so it needs some land for the HQ
+        if tiles.is_empty() {
+            let tile = self.first_half().into_iter()
+                .find(|tile| !self.is_shared(*tile))
+                .unwrap();
+
+            self.set(tile, TerrainClass::Grass);
+
+            tiles.push(tile);
+        }
+
+        tiles.sort_by_key(|tile| std::cmp::Reverse(Self::distance(*tile, self.mirror(*tile))));
+
+        // Randomly chooses one of the furthest tiles
+        let furthest = (tiles.len() / 10).max(1);
+
+        let hq = tiles[self.rng.range(furthest as u32) as usize];
+
+        self.build(hq, BuildingClass::HQ1, true);
+
+        let base = self.surrounding(hq)
+            .filter(|tile| self.get(*tile) == TerrainClass::Grass && !self.is_shared(*tile) && !self.is_occupied(*tile))
+            .collect::<Vec<Tile>>();
+
+        if !base.is_empty() {
+            let base = base[self.rng.range(base.len() as u32) as usize];
+            self.build(base, BuildingClass::Base, true);
+        }
+
+        hq
+    }
+
+    /// Places the neutral properties, which the players can capture.
+    ///
+    /// Returns the first player's property which is closest to the other player.
+    fn properties(&mut self) -> Option<Tile> {
+        let mut tiles = self.first_half().into_iter()
+            .filter(|tile| self.get(*tile) == TerrainClass::Grass)
+            .collect::<Vec<Tile>>();
+
+        let count = (tiles.len() / 20).max(2);
+
+        self.shuffle(&mut tiles);
+
+        let mut placed = 0;
+        let mut front: Option<Tile> = None;
+
+        for tile in tiles {
+            if placed == count {
+                break;
+            }
+
+            if self.can_build(tile) {
+                let chance = self.rng.random();
+
+                let class = if chance < 0.5 {
+                    BuildingClass::City
+
+                } else if chance < 0.7 {
+                    BuildingClass::Base
+
+                } else if chance < 0.8 {
+                    BuildingClass::Airport
+
+                } else if chance < 0.88 {
+                    BuildingClass::ComTower
+
+                } else if self.is_coastal(tile) {
+                    BuildingClass::Port
+
+                } else {
+                    BuildingClass::City
+                };
+
+                self.build(tile, class, false);
+
+                placed += 1;
+
+                let distance = Self::distance(tile, self.mirror(tile));
+
+                if front.is_none_or(|front| distance < Self::distance(front, self.mirror(front))) {
+                    front = Some(tile);
+                }
+            }
+        }
+
+        front
+    }
+
+    /// Adds a road between two tiles, with bridges where it crosses water.
+    fn road(&mut self, from: Tile, to: Tile) {
+        let (mut x, mut y) = from;
+
+        while (x, y) != to {
+            let horizontal = x != to.0;
+
+            if horizontal {
+                if x < to.0 { x += 1 } else { x -= 1 }
+
+            } else {
+                if y < to.1 { y += 1 } else { y -= 1 }
+            }
+
+            if self.is_occupied((x, y)) {
+                continue;
+            }
+
+            let orientation = if horizontal {
+                Orientation::Horizontal
+
+            } else {
+                Orientation::Vertical
+            };
+
+            match self.get((x, y)) {
+                TerrainClass::Grass |
+                TerrainClass::Forest |
+                TerrainClass::Mountain { .. } |
+                TerrainClass::Shoal => {
+                    self.set((x, y), TerrainClass::Road { ruins: false });
+                },
+
+                TerrainClass::River |
+                TerrainClass::Ocean |
+                TerrainClass::Reef => {
+                    self.set((x, y), TerrainClass::Bridge { orientation });
+                },
+
+                _ => {},
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{MapGenSettings, Symmetry, Generator, PLAYERS};
+    use crate::map::{MapData};
+    use crate::terrain::{TerrainClass};
+    use crate::building::{BuildingClass};
+    use crate::random::{Rng};
+
+    const SYMMETRIES: [Symmetry; 3] = [Symmetry::Horizontal, Symmetry::Vertical, Symmetry::Rotational];
+
+    fn maps() -> impl Iterator<Item = (MapGenSettings, MapData)> {
+        SYMMETRIES.into_iter().flat_map(|symmetry| {
+            [(20, 14), (15, 11), (4, 4)].into_iter().flat_map(move |(width, height)| {
+                (0..10).map(move |seed| {
+                    let settings = MapGenSettings {
+                        symmetry,
+                        ..MapGenSettings::new(seed, width, height)
+                    };
+
+                    (settings, settings.generate())
+                })
+            })
+        })
+    }
+
+    fn generator(settings: &MapGenSettings) -> Generator {
+        Generator {
+            rng: Rng::new(0),
+            symmetry: settings.symmetry,
+            map: MapData::new(settings.width, settings.height, TerrainClass::Ocean),
+            occupied: vec![],
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let settings = MapGenSettings::new(5, 30, 20);
+
+        assert_eq!(settings.generate(), settings.generate());
+        assert_ne!(settings.generate(), MapGenSettings::new(6, 30, 20).generate());
+    }
+
+    #[test]
+    fn symmetrical() {
+        for (settings, map) in maps() {
+            let generator = generator(&settings);
+
+            for (x, y, class) in map.tiles() {
+                let (mirror_x, mirror_y) = generator.mirror((x, y));
+                assert_eq!(class, map.get(mirror_x, mirror_y), "{:?}", settings);
+            }
+
+            for building in map.buildings.iter() {
+                let (mirror_x, mirror_y) = generator.mirror((building.x, building.y));
+
+                let mirror = map.building_at(mirror_x, mirror_y).unwrap();
+
+                assert_eq!(map.get(building.x, building.y), TerrainClass::Grass);
+
+                if building.nation.is_some() {
+                    assert_ne!(building.nation, mirror.nation);
+
+                } else {
+                    assert_eq!(mirror.nation, None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn balanced() {
+        for (settings, map) in maps() {
+            let count = |nation, class| {
+                map.buildings.iter().filter(|building| building.nation == Some(nation) && building.class == class).count()
+            };
+
+            assert_eq!(count(PLAYERS[0], BuildingClass::HQ1), 1, "{:?}", settings);
+            assert_eq!(count(PLAYERS[1], BuildingClass::HQ2), 1, "{:?}", settings);
+            assert_eq!(count(PLAYERS[0], BuildingClass::Base), count(PLAYERS[1], BuildingClass::Base));
+        }
+    }
+}
@@ -0,0 +1,49 @@
+/// Random number generator which always produces the same numbers for the same seed.
+///
+/// This uses SplitMix64, it is fast and good enough for gameplay, but it is not cryptographically secure.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    #[inline]
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// The current state, passing it to [`Rng::new`] continues with the same numbers.
+    ///
+    /// This is used to save the generator, and by the [`audit`](crate::audit) to detect desyncs.
+    #[inline]
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+
+        let mut x = self.state;
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+        x ^ (x >> 31)
+    }
+
+    /// Returns a number from `0.0` (inclusive) to `1.0` (exclusive).
+    #[inline]
+    pub fn random(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a number from `0` (inclusive) to `max` (exclusive).
+    #[inline]
+    pub fn range(&mut self, max: u32) -> u32 {
+        (self.random() * max as f64) as u32
+    }
+
+    /// Returns `true` with a probability of `chance`, which is from `0.0` to `1.0`.
+    #[inline]
+    pub fn chance(&mut self, chance: f64) -> bool {
+        self.random() < chance
+    }
+}
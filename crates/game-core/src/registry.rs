@@ -0,0 +1,352 @@
+//! The stats of every unit and building class, which mods can extend at startup.
+//!
+//! The game doesn't match on [`UnitClass`] or [`BuildingClass`] to find their stats, instead it looks up their
+//! [`UnitSpec`] / [`BuildingSpec`], so custom classes work everywhere that the built-in classes work.
+//!
+//! ```ignore
+//! let mut registry = Registry::builtin();
+//!
+//! let hovercraft = registry.add_unit(UnitSpec {
+//!     name: "Hovercraft",
+//!     movement_class: MovementClass::Lander,
+//!     ..*registry.unit(UnitClass::Recon)
+//! });
+//!
+//! registry.install().unwrap();
+//! ```
+
+use std::sync::OnceLock;
+
+use crate::unit::{UnitClass, AttackRange};
+use crate::building::{BuildingClass};
+use crate::terrain::{TerrainInfo, MovementClass};
+
+
+/// The stats of a [`UnitClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitSpec {
+    /// The name which is displayed to the player.
+    pub name: &'static str,
+
+    /// The number of movement points per turn.
+    pub movement: u32,
+
+    /// The amount of fuel when the unit is fully supplied.
+    pub max_fuel: u32,
+
+    /// How many tiles the unit can see in fog.
+    pub vision: u32,
+
+    /// The minimum and maximum distance which the unit can attack,
+    /// or `None` if the unit cannot attack.
+    pub attack_range: Option<AttackRange>,
+
+    /// Which terrain the unit can move onto, and how much it costs.
+    pub movement_class: MovementClass,
+
+    /// Heavy units (such as the Mega Tank) have a bigger explosion when they are destroyed.
+    pub heavy: bool,
+
+    /// The row of the unit's sprites in the unit spritesheet, in tiles.
+    pub sprite_row: u32,
+
+    /// If this is `true` then every nation has its own row of sprites, starting at `sprite_row`,
+    /// in the same order as [`Nation::ALL`](crate::Nation::ALL).
+    pub nation_sprites: bool,
+
+    /// Which units it can carry, or `None` if it isn't a transport.
+    pub transport: Option<Transport>,
+
+    /// The funds which are needed to build the unit, or `None` if it can't be built.
+    pub cost: Option<u32>,
+}
+
+impl UnitSpec {
+    const fn new(name: &'static str, movement: u32, max_fuel: u32, vision: u32, attack_range: Option<(u32, u32)>, movement_class: MovementClass, sprite_row: u32) -> Self {
+        let attack_range = match attack_range {
+            Some((min, max)) => Some(AttackRange { min, max }),
+            None => None,
+        };
+
+        Self {
+            name,
+            movement,
+            max_fuel,
+            vision,
+            attack_range,
+            movement_class,
+            heavy: false,
+            sprite_row,
+            nation_sprites: false,
+            transport: None,
+            cost: None,
+        }
+    }
+
+    const fn heavy(self) -> Self {
+        Self { heavy: true, ..self }
+    }
+
+    const fn nation_sprites(self) -> Self {
+        Self { nation_sprites: true, ..self }
+    }
+
+    const fn transport(self, capacity: u32, cargo: &'static [MovementClass]) -> Self {
+        Self { transport: Some(Transport { capacity, cargo }), ..self }
+    }
+
+    const fn cost(self, cost: u32) -> Self {
+        Self { cost: Some(cost), ..self }
+    }
+}
+
+
+/// The units which a transport can carry, the carried units are on the same tile as the transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transport {
+    /// The maximum number of units which it can carry at the same time.
+    pub capacity: u32,
+
+    /// It can only carry units which have one of these movement classes.
+    pub cargo: &'static [MovementClass],
+}
+
+impl Transport {
+    #[inline]
+    pub fn can_carry(&self, class: UnitClass) -> bool {
+        self.cargo.contains(&class.movement_class())
+    }
+}
+
+
+/// The stats of a [`BuildingClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildingSpec {
+    /// The defense and movement costs of the building's tile.
+    pub info: TerrainInfo,
+
+    /// Whether the building can be owned by a nation, buildings such as missile silos are always neutral.
+    pub can_have_nation: bool,
+
+    /// The row of the building's sprites in the building spritesheet, in buildings.
+    pub sprite_row: u32,
+
+    /// The owner can build units which have one of these movement classes on it.
+    pub production: &'static [MovementClass],
+}
+
+impl BuildingSpec {
+    const fn new(info: TerrainInfo, sprite_row: u32) -> Self {
+        Self { info, can_have_nation: true, sprite_row, production: &[] }
+    }
+
+    const fn neutral(self) -> Self {
+        Self { can_have_nation: false, ..self }
+    }
+
+    const fn production(self, production: &'static [MovementClass]) -> Self {
+        Self { production, ..self }
+    }
+
+    /// Whether the unit can be built on the building.
+    #[inline]
+    pub fn can_produce(&self, class: UnitClass) -> bool {
+        self.production.contains(&class.movement_class())
+    }
+}
+
+
+const DIRECT: Option<(u32, u32)> = Some((1, 1));
+
+const SOLDIERS: &[MovementClass] = &[MovementClass::Foot, MovementClass::Boot];
+const GROUND: &[MovementClass] = &[MovementClass::Foot, MovementClass::Boot, MovementClass::Treads, MovementClass::Tires];
+const AIR: &[MovementClass] = &[MovementClass::Air];
+const NAVAL: &[MovementClass] = &[MovementClass::Sea, MovementClass::Lander];
+const FACTORY: &[MovementClass] = &[MovementClass::Foot, MovementClass::Boot, MovementClass::Treads, MovementClass::Tires, MovementClass::Pipe];
+
+/// In the same order as [`UnitClass::ALL`].
+const BUILTIN_UNITS: &[UnitSpec] = &[
+    UnitSpec::new("Infantry", 3, 99, 2, DIRECT, MovementClass::Foot, 0).nation_sprites().cost(1000),
+    UnitSpec::new("Mech", 2, 70, 2, DIRECT, MovementClass::Boot, 5).nation_sprites().cost(3000),
+    UnitSpec::new("Recon", 8, 80, 5, DIRECT, MovementClass::Tires, 10).cost(4000),
+    UnitSpec::new("APC", 6, 70, 1, None, MovementClass::Treads, 15).transport(1, SOLDIERS).cost(5000),
+    UnitSpec::new("Artillery", 5, 50, 1, Some((2, 3)), MovementClass::Treads, 17).cost(6000),
+    UnitSpec::new("Tank", 6, 70, 3, DIRECT, MovementClass::Treads, 11).cost(7000),
+    UnitSpec::new("Anti-Air", 6, 60, 2, DIRECT, MovementClass::Treads, 16).cost(8000),
+    UnitSpec::new("Missile", 4, 50, 5, Some((3, 5)), MovementClass::Tires, 19).cost(12000),
+    UnitSpec::new("Rocket", 5, 50, 1, Some((3, 5)), MovementClass::Tires, 18).cost(15000),
+    UnitSpec::new("Md. Tank", 5, 50, 1, DIRECT, MovementClass::Treads, 12).cost(16000),
+    UnitSpec::new("Piperunner", 9, 99, 4, Some((2, 5)), MovementClass::Pipe, 20).heavy().cost(20000),
+    UnitSpec::new("Neotank", 6, 99, 1, DIRECT, MovementClass::Treads, 13).cost(22000),
+    UnitSpec::new("Mega Tank", 4, 50, 1, DIRECT, MovementClass::Treads, 14).heavy().cost(28000),
+    UnitSpec::new("B Copter", 6, 99, 3, DIRECT, MovementClass::Air, 26).cost(9000),
+    UnitSpec::new("T Copter", 6, 99, 2, None, MovementClass::Air, 27).transport(1, SOLDIERS).cost(5000),
+    UnitSpec::new("Fighter", 9, 99, 2, DIRECT, MovementClass::Air, 22).cost(20000),
+    UnitSpec::new("Bomber", 7, 99, 2, DIRECT, MovementClass::Air, 23).cost(22000),
+    UnitSpec::new("Stealth", 6, 60, 4, DIRECT, MovementClass::Air, 25).cost(24000),
+    UnitSpec::new("Battleship", 5, 99, 2, Some((2, 6)), MovementClass::Sea, 28).cost(28000),
+    UnitSpec::new("Cruiser", 6, 99, 3, DIRECT, MovementClass::Sea, 29).transport(2, AIR).cost(18000),
+    UnitSpec::new("Submarine", 5, 60, 5, DIRECT, MovementClass::Sea, 30).cost(20000),
+    UnitSpec::new("Lander", 6, 99, 1, None, MovementClass::Lander, 31).transport(2, GROUND).cost(12000),
+    UnitSpec::new("Carrier", 5, 99, 4, Some((3, 8)), MovementClass::Sea, 33).transport(2, AIR).cost(30000),
+    UnitSpec::new("Black Boat", 7, 60, 1, None, MovementClass::Lander, 32).transport(2, SOLDIERS).cost(7500),
+    UnitSpec::new("Black Bomb", 9, 45, 1, None, MovementClass::Air, 24).cost(25000),
+    UnitSpec::new("Oozium", 1, 99, 1, DIRECT, MovementClass::Treads, 21).heavy(),
+];
+
+/// In the same order as [`BuildingClass::ALL`].
+const BUILTIN_BUILDINGS: &[BuildingSpec] = &[
+    BuildingSpec::new(TerrainInfo::HQ, 0),
+    BuildingSpec::new(TerrainInfo::HQ, 1),
+    BuildingSpec::new(TerrainInfo::HQ, 2),
+    BuildingSpec::new(TerrainInfo::HQ, 3),
+    BuildingSpec::new(TerrainInfo::HQ, 4),
+    BuildingSpec::new(TerrainInfo::CITY, 5),
+    BuildingSpec::new(TerrainInfo::BASE, 6).production(FACTORY),
+    BuildingSpec::new(TerrainInfo::AIRPORT, 7).production(AIR),
+    BuildingSpec::new(TerrainInfo::PORT, 8).production(NAVAL),
+    BuildingSpec::new(TerrainInfo::COM_TOWER, 9),
+    BuildingSpec::new(TerrainInfo::LAB, 10),
+    BuildingSpec::new(TerrainInfo::MISSILE_SILO, 11).neutral(),
+    BuildingSpec::new(TerrainInfo::MISSILE_SILO, 12).neutral(),
+];
+
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+
+/// Every unit and building class which exists in the game.
+///
+/// The registry can only be changed before it is used, because the classes are stored in
+/// maps and replays, so mods must be added at startup with [`install`](Registry::install).
+/// Every player must install the same mods, in the same order.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    units: Vec<UnitSpec>,
+    buildings: Vec<BuildingSpec>,
+}
+
+impl Registry {
+    /// A registry which only contains the built-in classes.
+    pub fn builtin() -> Self {
+        Self {
+            units: BUILTIN_UNITS.to_vec(),
+            buildings: BUILTIN_BUILDINGS.to_vec(),
+        }
+    }
+
+    /// Returns the installed registry, if nothing was installed then it uses [`Registry::builtin`].
+    pub fn get() -> &'static Self {
+        REGISTRY.get_or_init(Self::builtin)
+    }
+
+    /// Makes this the registry which is used by the game.
+    ///
+    /// This returns `Err` if a registry was already installed or used.
+    pub fn install(self) -> Result<(), Self> {
+        REGISTRY.set(self)
+    }
+
+    /// Adds a custom unit class.
+    pub fn add_unit(&mut self, spec: UnitSpec) -> UnitClass {
+        let id = self.units.len() - BUILTIN_UNITS.len();
+        self.units.push(spec);
+        UnitClass::Custom(id.try_into().expect("Too many custom unit classes"))
+    }
+
+    /// Adds a custom building class.
+    pub fn add_building(&mut self, spec: BuildingSpec) -> BuildingClass {
+        let id = self.buildings.len() - BUILTIN_BUILDINGS.len();
+        self.buildings.push(spec);
+        BuildingClass::Custom(id.try_into().expect("Too many custom building classes"))
+    }
+
+    /// Every unit class, including custom classes.
+    pub fn unit_classes(&self) -> impl Iterator<Item = UnitClass> + '_ {
+        let custom = (0..(self.units.len() - BUILTIN_UNITS.len())).map(|id| UnitClass::Custom(id as u16));
+        UnitClass::ALL.iter().copied().chain(custom)
+    }
+
+    /// Every building class, including custom classes.
+    pub fn building_classes(&self) -> impl Iterator<Item = BuildingClass> + '_ {
+        let custom = (0..(self.buildings.len() - BUILTIN_BUILDINGS.len())).map(|id| BuildingClass::Custom(id as u16));
+        BuildingClass::ALL.iter().copied().chain(custom)
+    }
+
+    /// # Panics
+    ///
+    /// If the class is a custom class which isn't in this registry.
+    pub fn unit(&self, class: UnitClass) -> &UnitSpec {
+        let index = match class {
+            UnitClass::Custom(id) => BUILTIN_UNITS.len() + id as usize,
+            class => UnitClass::ALL.iter().position(|x| *x == class).unwrap(),
+        };
+
+        self.units.get(index).unwrap_or_else(|| panic!("{:?} is not registered", class))
+    }
+
+    /// # Panics
+    ///
+    /// If the class is a custom class which isn't in this registry.
+    pub fn building(&self, class: BuildingClass) -> &BuildingSpec {
+        let index = match class {
+            BuildingClass::Custom(id) => BUILTIN_BUILDINGS.len() + id as usize,
+            class => BuildingClass::ALL.iter().position(|x| *x == class).unwrap(),
+        };
+
+        self.buildings.get(index).unwrap_or_else(|| panic!("{:?} is not registered", class))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Registry, UnitSpec, BuildingSpec, BUILTIN_UNITS, BUILTIN_BUILDINGS};
+    use crate::unit::{UnitClass};
+    use crate::building::{BuildingClass};
+    use crate::terrain::{MovementClass};
+
+    #[test]
+    fn builtin() {
+        assert_eq!(BUILTIN_UNITS.len(), UnitClass::ALL.len());
+        assert_eq!(BUILTIN_BUILDINGS.len(), BuildingClass::ALL.len());
+
+        let registry = Registry::builtin();
+
+        assert_eq!(registry.unit(UnitClass::Tank).name, "Tank");
+        assert_eq!(registry.unit(UnitClass::Oozium).movement, 1);
+        assert_eq!(registry.building(BuildingClass::Port).info.name, "Port");
+
+        assert_eq!(registry.unit(UnitClass::Infantry).cost, Some(1000));
+        assert_eq!(registry.unit(UnitClass::Oozium).cost, None);
+        assert!(registry.building(BuildingClass::Base).can_produce(UnitClass::Piperunner));
+        assert!(registry.building(BuildingClass::Port).can_produce(UnitClass::Lander));
+        assert!(!registry.building(BuildingClass::Airport).can_produce(UnitClass::Tank));
+        assert!(!registry.building(BuildingClass::City).can_produce(UnitClass::Infantry));
+    }
+
+    #[test]
+    fn custom() {
+        let mut registry = Registry::builtin();
+
+        let hovercraft = registry.add_unit(UnitSpec {
+            name: "Hovercraft",
+            movement_class: MovementClass::Lander,
+            ..*registry.unit(UnitClass::Recon)
+        });
+
+        let factory = registry.add_building(BuildingSpec {
+            can_have_nation: false,
+            ..*registry.building(BuildingClass::Base)
+        });
+
+        assert_eq!(hovercraft, UnitClass::Custom(0));
+        assert_eq!(registry.unit(hovercraft).name, "Hovercraft");
+        assert_eq!(registry.unit(hovercraft).movement, 8);
+        assert!(!registry.building(factory).can_have_nation);
+
+        assert_eq!(registry.unit_classes().count(), UnitClass::ALL.len() + 1);
+        assert_eq!(registry.unit_classes().last(), Some(hovercraft));
+        assert_eq!(registry.building_classes().last(), Some(factory));
+    }
+}
@@ -0,0 +1,546 @@
+//! Saving and loading matches, so they can be watched later.
+//!
+//! A replay is stored in a `.rbrep` file, every number is little-endian:
+//!
+//! | Field            | Type         |                                                     |
+//! |------------------|--------------|-----------------------------------------------------|
+//! | magic            | `b"RBREP\0"` |                                                     |
+//! | version          | `u16`        | [`REPLAY_VERSION`]                                  |
+//! | map hash         | `u64`        | [`MapData::stable_hash`]                            |
+//! | seed             | `u64`        |                                                     |
+//! | fog              | `u8`         |                                                     |
+//! | weather          | `u8`         |                                                     |
+//! | starting funds   | `u32`        |                                                     |
+//! | income           | `u32`        |                                                     |
+//! | number of events | `u32`        |                                                     |
+//! | events           |              | `f64` time, followed by a `u8` tag and the action   |
+//!
+//! The map itself isn't stored in the replay, instead it is found with [`Replay::find_map`].
+
+use std::sync::Arc;
+
+use crate::{Weather};
+use crate::action::{MoveDirection};
+use crate::unit::{Rank};
+use crate::map::{MapData};
+
+
+/// The file extension for replays, without the `.`
+pub const REPLAY_EXTENSION: &str = "rbrep";
+
+/// The version which is written by [`Replay::save`], it is increased whenever the format changes.
+pub const REPLAY_VERSION: u16 = 4;
+
+/// The oldest version which can be loaded, older versions identified units by their tile instead of their id.
+const MIN_REPLAY_VERSION: u16 = 3;
+
+const MAGIC: &[u8; 6] = b"RBREP\0";
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    /// The file doesn't start with the replay magic bytes.
+    NotReplay,
+
+    /// The replay was saved by a different version of the game.
+    Version {
+        found: u16,
+        supported: u16,
+    },
+
+    /// The file ended in the middle of the replay.
+    Truncated,
+
+    /// The file contains a value which isn't valid.
+    Invalid(&'static str),
+
+    /// None of the maps have the same [`stable_hash`](MapData::stable_hash) as the replay.
+    UnknownMap {
+        hash: u64,
+    },
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotReplay => write!(f, "File is not a replay"),
+            Self::Version { found, supported } => write!(f, "Replay version {} is not supported, expected version {}", found, supported),
+            Self::Truncated => write!(f, "Replay file is truncated"),
+            Self::Invalid(field) => write!(f, "Replay has an invalid {}", field),
+            Self::UnknownMap { hash } => write!(f, "Replay map {:016x} was not found", hash),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+
+/// A copy of the match settings when the match started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplaySettings {
+    pub fog: bool,
+    pub weather: Weather,
+    pub starting_funds: u32,
+    pub income: u32,
+}
+
+impl ReplaySettings {
+    pub(crate) fn write(&self, writer: &mut Writer) {
+        writer.u8(self.fog as u8);
+        writer.u8(match self.weather {
+            Weather::Clear => 0,
+            Weather::Rain => 1,
+            Weather::Snow => 2,
+            Weather::Sandstorm => 3,
+            Weather::Random => 4,
+        });
+        writer.u32(self.starting_funds);
+        writer.u32(self.income);
+    }
+
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self, ReplayError> {
+        let fog = match reader.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(ReplayError::Invalid("fog")),
+        };
+
+        let weather = match reader.u8()? {
+            0 => Weather::Clear,
+            1 => Weather::Rain,
+            2 => Weather::Snow,
+            3 => Weather::Sandstorm,
+            4 => Weather::Random,
+            _ => return Err(ReplayError::Invalid("weather")),
+        };
+
+        let starting_funds = reader.u32()?;
+        let income = reader.u32()?;
+
+        Ok(Self { fog, weather, starting_funds, income })
+    }
+}
+
+/// An action which changes the grid.
+///
+/// Units are identified by their id, the ids are allocated by the grid in order so they are the same every time the map is loaded.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReplayAction {
+    /// Moves the unit along the path, see `Grid::move_path` in the game renderer.
+    MovePath {
+        unit: u32,
+        path: Vec<MoveDirection>,
+    },
+
+    /// See `Grid::destroy_unit` in the game renderer.
+    DestroyUnit {
+        unit: u32,
+    },
+
+    /// Changes the [`Rank`] of the unit, this is recorded when the unit is promoted.
+    SetRank {
+        unit: u32,
+        rank: Rank,
+    },
+
+    /// Ends the current player's turn, so every unit can move again.
+    ///
+    /// This was added in version 4.
+    EndTurn,
+}
+
+impl ReplayAction {
+    const MOVE_PATH: u8 = 0;
+    const DESTROY_UNIT: u8 = 1;
+    const SET_RANK: u8 = 2;
+    const END_TURN: u8 = 3;
+
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Self::MovePath { unit, path } => {
+                writer.u8(Self::MOVE_PATH);
+                writer.u32(*unit);
+                writer.u32(path.len() as u32);
+
+                for direction in path {
+                    writer.u8(match direction {
+                        MoveDirection::Up => 0,
+                        MoveDirection::Down => 1,
+                        MoveDirection::Left => 2,
+                        MoveDirection::Right => 3,
+                    });
+                }
+            },
+
+            Self::DestroyUnit { unit } => {
+                writer.u8(Self::DESTROY_UNIT);
+                writer.u32(*unit);
+            },
+
+            Self::SetRank { unit, rank } => {
+                writer.u8(Self::SET_RANK);
+                writer.u32(*unit);
+                writer.u8(match rank {
+                    Rank::Rookie => 0,
+                    Rank::One => 1,
+                    Rank::Two => 2,
+                    Rank::Veteran => 3,
+                });
+            },
+
+            Self::EndTurn => {
+                writer.u8(Self::END_TURN);
+            },
+        }
+    }
+
+    /// Tags which were added after the file's `version` are rejected.
+    fn read(reader: &mut Reader, version: u16) -> Result<Self, ReplayError> {
+        match reader.u8()? {
+            Self::MOVE_PATH => {
+                let unit = reader.u32()?;
+                let len = reader.u32()?;
+
+                let path = (0..len).map(|_| {
+                    match reader.u8()? {
+                        0 => Ok(MoveDirection::Up),
+                        1 => Ok(MoveDirection::Down),
+                        2 => Ok(MoveDirection::Left),
+                        3 => Ok(MoveDirection::Right),
+                        _ => Err(ReplayError::Invalid("move direction")),
+                    }
+                }).collect::<Result<Vec<_>, _>>()?;
+
+                Ok(Self::MovePath { unit, path })
+            },
+
+            Self::DESTROY_UNIT => {
+                let unit = reader.u32()?;
+                Ok(Self::DestroyUnit { unit })
+            },
+
+            Self::SET_RANK => {
+                let unit = reader.u32()?;
+
+                let rank = match reader.u8()? {
+                    0 => Rank::Rookie,
+                    1 => Rank::One,
+                    2 => Rank::Two,
+                    3 => Rank::Veteran,
+                    _ => return Err(ReplayError::Invalid("rank")),
+                };
+
+                Ok(Self::SetRank { unit, rank })
+            },
+
+            Self::END_TURN if version >= 4 => Ok(Self::EndTurn),
+
+            _ => Err(ReplayError::Invalid("action")),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayEvent {
+    /// Milliseconds since the start of the match.
+    pub time: f64,
+    pub action: ReplayAction,
+}
+
+
+/// A recording of a match, see the [module documentation](self) for the file format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub map_hash: u64,
+    pub settings: ReplaySettings,
+
+    /// The seed for the match's random numbers.
+    pub seed: u64,
+
+    /// The actions, sorted by time.
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn new(map: &MapData, settings: ReplaySettings, seed: u64) -> Self {
+        Self {
+            map_hash: map.stable_hash(),
+            settings,
+            seed,
+            events: vec![],
+        }
+    }
+
+    /// Adds an action to the end of the replay, `time` is milliseconds since the start of the match.
+    pub fn push(&mut self, time: f64, action: ReplayAction) {
+        if let Some(last) = self.events.last() {
+            assert!(time >= last.time, "Replay event time {} is before the previous event {}", time, last.time);
+        }
+
+        self.events.push(ReplayEvent { time, action });
+    }
+
+    /// Returns the map which the replay was recorded on.
+    pub fn find_map<'a>(&self, maps: &'a [Arc<MapData>]) -> Result<&'a Arc<MapData>, ReplayError> {
+        maps.iter()
+            .find(|map| map.stable_hash() == self.map_hash)
+            .ok_or(ReplayError::UnknownMap { hash: self.map_hash })
+    }
+
+    pub fn save(&self) -> Vec<u8> {
+        let mut writer = Writer { bytes: vec![] };
+
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u16(REPLAY_VERSION);
+        writer.u64(self.map_hash);
+        writer.u64(self.seed);
+
+        self.settings.write(&mut writer);
+
+        writer.u32(self.events.len() as u32);
+
+        for event in self.events.iter() {
+            writer.f64(event.time);
+            event.action.write(&mut writer);
+        }
+
+        writer.bytes
+    }
+
+    pub fn load(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(ReplayError::NotReplay);
+        }
+
+        let version = reader.u16()?;
+
+        if !(MIN_REPLAY_VERSION..=REPLAY_VERSION).contains(&version) {
+            return Err(ReplayError::Version {
+                found: version,
+                supported: REPLAY_VERSION,
+            });
+        }
+
+        let map_hash = reader.u64()?;
+        let seed = reader.u64()?;
+        let settings = ReplaySettings::read(&mut reader)?;
+
+        let len = reader.u32()?;
+
+        let mut replay = Self {
+            map_hash,
+            settings,
+            seed,
+            events: vec![],
+        };
+
+        for _ in 0..len {
+            let time = reader.f64()?;
+
+            if !time.is_finite() || replay.events.last().is_some_and(|last| time < last.time) {
+                return Err(ReplayError::Invalid("event time"));
+            }
+
+            let action = ReplayAction::read(&mut reader, version)?;
+
+            replay.events.push(ReplayEvent { time, action });
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(ReplayError::Invalid("trailing data"));
+        }
+
+        Ok(replay)
+    }
+}
+
+
+pub(crate) struct Writer {
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl Writer {
+    #[inline]
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    #[inline]
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub(crate) fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+        if self.bytes.len() < len {
+            return Err(ReplayError::Truncated);
+        }
+
+        let (value, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(value)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    #[inline]
+    pub(crate) fn u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    #[inline]
+    pub(crate) fn u16(&mut self) -> Result<u16, ReplayError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    #[inline]
+    pub(crate) fn u32(&mut self) -> Result<u32, ReplayError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    #[inline]
+    pub(crate) fn u64(&mut self) -> Result<u64, ReplayError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    #[inline]
+    pub(crate) fn f32(&mut self) -> Result<f32, ReplayError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    #[inline]
+    pub(crate) fn f64(&mut self) -> Result<f64, ReplayError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::{Replay, ReplayAction, ReplaySettings, ReplayError, REPLAY_VERSION};
+    use crate::{Weather};
+    use crate::action::{MoveDirection};
+    use crate::unit::{Rank};
+    use crate::map::{MapData};
+    use crate::terrain::{TerrainClass};
+
+    fn replay() -> Replay {
+        let map = MapData::new(4, 4, TerrainClass::Grass);
+
+        let mut replay = Replay::new(&map, ReplaySettings {
+            fog: true,
+            weather: Weather::Snow,
+            starting_funds: 5000,
+            income: 1000,
+        }, 42);
+
+        replay.push(0.0, ReplayAction::MovePath {
+            unit: 0,
+            path: vec![MoveDirection::Up, MoveDirection::Right],
+        });
+
+        replay.push(1500.0, ReplayAction::DestroyUnit { unit: 1 });
+        replay.push(1500.0, ReplayAction::SetRank { unit: 2, rank: Rank::Two });
+        replay.push(2000.0, ReplayAction::EndTurn);
+
+        replay
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = replay();
+        assert_eq!(Replay::load(&replay.save()), Ok(replay));
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = replay().save();
+
+        assert_eq!(Replay::load(b"PNG"), Err(ReplayError::NotReplay));
+        assert_eq!(Replay::load(&bytes[..(bytes.len() - 1)]), Err(ReplayError::Truncated));
+
+        let mut newer = bytes.clone();
+        newer[6..8].copy_from_slice(&(REPLAY_VERSION + 1).to_le_bytes());
+
+        assert_eq!(Replay::load(&newer), Err(ReplayError::Version {
+            found: REPLAY_VERSION + 1,
+            supported: REPLAY_VERSION,
+        }));
+
+        // Version 2 identified units by their tile, so it can't be loaded
+        let mut old = bytes.clone();
+        old[6..8].copy_from_slice(&2u16.to_le_bytes());
+
+        assert_eq!(Replay::load(&old), Err(ReplayError::Version {
+            found: 2,
+            supported: REPLAY_VERSION,
+        }));
+    }
+
+    #[test]
+    fn version_3() {
+        let mut replay = replay();
+        replay.events.retain(|event| event.action != ReplayAction::EndTurn);
+
+        // Version 3 is the same, except it doesn't have EndTurn
+        let mut bytes = replay.save();
+        bytes[6..8].copy_from_slice(&3u16.to_le_bytes());
+
+        assert_eq!(Replay::load(&bytes), Ok(replay));
+    }
+
+    #[test]
+    fn version_3_end_turn() {
+        // EndTurn was added in version 4, so it is invalid in a version 3 replay
+        let mut bytes = replay().save();
+        bytes[6..8].copy_from_slice(&3u16.to_le_bytes());
+
+        assert_eq!(Replay::load(&bytes), Err(ReplayError::Invalid("action")));
+    }
+
+    #[test]
+    fn find_map() {
+        let replay = replay();
+
+        let other = Arc::new(MapData::new(4, 4, TerrainClass::Ocean));
+        let same = Arc::new(MapData::new(4, 4, TerrainClass::Grass));
+
+        assert!(Arc::ptr_eq(replay.find_map(&[other.clone(), same.clone()]).unwrap(), &same));
+
+        assert_eq!(replay.find_map(&[other]).err(), Some(ReplayError::UnknownMap { hash: replay.map_hash }));
+    }
+}
@@ -0,0 +1,219 @@
+//! The economy and victory conditions of a match, which are chosen before the match starts.
+
+use crate::{Nation};
+use crate::team::{Teams};
+use crate::unit::{UnitClass};
+
+
+/// The rules which are chosen in the lobby, they stay the same for the whole match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchRules {
+    /// The funds which every player has at the start of the match.
+    pub starting_funds: u32,
+
+    /// The funds which every property gives to its owner at the start of their turn.
+    pub income: u32,
+
+    /// A team wins when it owns this many properties, `None` disables the capture limit.
+    pub capture_limit: Option<u32>,
+
+    /// Units which can't be built.
+    pub banned_units: Vec<UnitClass>,
+}
+
+impl MatchRules {
+    /// Whether the unit can be built, units which are already on the map are not affected.
+    #[inline]
+    pub fn can_build(&self, class: UnitClass) -> bool {
+        !self.banned_units.contains(&class)
+    }
+
+    /// The funds which are received at the start of a turn.
+    #[inline]
+    pub fn income_for(&self, properties: u32) -> u32 {
+        self.income.saturating_mul(properties)
+    }
+
+    /// Returns the team which has reached the capture limit.
+    ///
+    /// `owners` contains the owner of every property on the map, `None` is neutral.
+    /// The properties of allies are added together.
+    pub fn capture_winner<I>(&self, teams: &Teams, owners: I) -> Option<u32> where I: IntoIterator<Item = Option<Nation>> {
+        let limit = self.capture_limit?;
+
+        let mut counts: Vec<(u32, u32)> = vec![];
+
+        for nation in owners.into_iter().flatten() {
+            let team = teams.team(nation);
+
+            let count = match counts.iter_mut().find(|(other, _)| *other == team) {
+                Some((_, count)) => count,
+                None => {
+                    counts.push((team, 0));
+                    &mut counts.last_mut().unwrap().1
+                },
+            };
+
+            *count += 1;
+
+            if *count >= limit {
+                return Some(team);
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            starting_funds: 0,
+            income: 1000,
+            capture_limit: None,
+            banned_units: vec![],
+        }
+    }
+}
+
+
+/// The funds of each nation, they are spent to build units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Funds {
+    /// The funds of each nation, in the same order as [`Nation::ALL`].
+    funds: [u32; Nation::ALL.len()],
+}
+
+impl Funds {
+    /// Every nation starts with the same funds, see [`MatchRules::starting_funds`].
+    pub fn new(starting_funds: u32) -> Self {
+        Self {
+            funds: [starting_funds; Nation::ALL.len()],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, nation: Nation) -> u32 {
+        self.funds[nation as usize]
+    }
+
+    #[inline]
+    pub fn add(&mut self, nation: Nation, amount: u32) {
+        let funds = &mut self.funds[nation as usize];
+        *funds = funds.saturating_add(amount);
+    }
+
+    /// Removes the funds, returns `false` and doesn't change the funds if the nation can't afford it.
+    #[inline]
+    pub fn spend(&mut self, nation: Nation, amount: u32) -> bool {
+        let funds = &mut self.funds[nation as usize];
+
+        match funds.checked_sub(amount) {
+            Some(left) => {
+                *funds = left;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+impl Default for Funds {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+
+/// The reason why a unit couldn't be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// The unit is in [`MatchRules::banned_units`], or it doesn't have a cost.
+    Banned,
+
+    /// The tile isn't a building which is owned by the nation and can produce the unit.
+    NoFactory,
+
+    /// The nation doesn't have enough funds.
+    Funds {
+        cost: u32,
+        funds: u32,
+    },
+
+    /// There is already a unit on the tile.
+    Occupied,
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Banned => write!(f, "Unit cannot be built"),
+            Self::NoFactory => write!(f, "Unit cannot be built on this tile"),
+            Self::Funds { cost, funds } => write!(f, "Unit costs {} but only {} funds are available", cost, funds),
+            Self::Occupied => write!(f, "Tile is already occupied"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchRules, Funds};
+    use crate::{Nation};
+    use crate::team::{Teams};
+    use crate::unit::{UnitClass};
+
+    #[test]
+    fn rules() {
+        let mut teams = Teams::new();
+        teams.set_team(Nation::GreenEarth, teams.team(Nation::OrangeStar));
+
+        let mut rules = MatchRules {
+            banned_units: vec![UnitClass::Tank],
+            ..MatchRules::default()
+        };
+
+        assert!(!rules.can_build(UnitClass::Tank));
+        assert!(rules.can_build(UnitClass::Infantry));
+        assert_eq!(rules.income_for(3), 3000);
+
+        let owners = [
+            Some(Nation::OrangeStar),
+            None,
+            Some(Nation::BlueMoon),
+            Some(Nation::GreenEarth),
+            Some(Nation::BlueMoon),
+            Some(Nation::OrangeStar),
+        ];
+
+        assert_eq!(rules.capture_winner(&teams, owners), None);
+
+        rules.capture_limit = Some(3);
+        assert_eq!(rules.capture_winner(&teams, owners), Some(teams.team(Nation::OrangeStar)));
+
+        rules.capture_limit = Some(4);
+        assert_eq!(rules.capture_winner(&teams, owners), None);
+    }
+
+    #[test]
+    fn funds() {
+        let mut funds = Funds::new(1000);
+
+        assert_eq!(funds.get(Nation::BlueMoon), 1000);
+
+        funds.add(Nation::BlueMoon, 3000);
+        assert_eq!(funds.get(Nation::BlueMoon), 4000);
+        assert_eq!(funds.get(Nation::OrangeStar), 1000);
+
+        assert!(funds.spend(Nation::BlueMoon, 4000));
+        assert_eq!(funds.get(Nation::BlueMoon), 0);
+
+        assert!(!funds.spend(Nation::OrangeStar, 1001));
+        assert_eq!(funds.get(Nation::OrangeStar), 1000);
+    }
+}
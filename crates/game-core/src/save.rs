@@ -0,0 +1,476 @@
+//! Saving the state of a match, so that it can be resumed later (such as after the game crashes).
+//!
+//! Unlike a [`Replay`](crate::replay::Replay) a save doesn't contain the actions, only the state
+//! at the end of a turn. It is stored in a `.rbsav` file, every number is little-endian:
+//!
+//! | Field               | Type         |                                                  |
+//! |---------------------|--------------|--------------------------------------------------|
+//! | magic               | `b"RBSAV\0"` |                                                  |
+//! | version             | `u16`        | [`SAVE_VERSION`]                                 |
+//! | map hash            | `u64`        | [`MapData::stable_hash`]                         |
+//! | settings            |              | The same as the replay settings                  |
+//! | player              | `u8`         | Index in [`Nation::ALL`]                         |
+//! | teams               | `[u32; 5]`   | The team of each nation, see [`Teams`]           |
+//! | starting funds      | `u32`        | See [`MatchRules`]                               |
+//! | income              | `u32`        |                                                  |
+//! | has capture limit   | `u8`         | `0` or `1`                                       |
+//! | capture limit       | `u32`        | `0` if there isn't a capture limit               |
+//! | number of bans      | `u32`        |                                                  |
+//! | banned units        |              | Unit classes                                     |
+//! | funds               | `[u32; 5]`   | The funds of each nation, see [`Funds`]          |
+//! | turn                | `u32`        |                                                  |
+//! | rng                 | `u64`        |                                                  |
+//! | next unit id        | `u32`        |                                                  |
+//! | number of units     | `u32`        |                                                  |
+//! | units               |              | See [`UnitSnapshot`]                             |
+//! | number of buildings | `u32`        |                                                  |
+//! | buildings           |              | See [`BuildingSnapshot`]                         |
+//!
+//! Unit and building classes are stored as a `u8` tag followed by the index, so [`Custom`](UnitClass::Custom)
+//! classes are only valid if the same mods are installed.
+
+use std::sync::Arc;
+
+use crate::{Nation};
+use crate::unit::{UnitClass, Rank};
+use crate::building::{BuildingClass};
+use crate::map::{MapData};
+use crate::team::{Teams};
+use crate::rules::{MatchRules, Funds};
+use crate::audit::{StateSnapshot, UnitSnapshot, BuildingSnapshot};
+use crate::replay::{ReplaySettings, ReplayError, Writer, Reader};
+
+
+/// The file extension for saves, without the `.`
+pub const SAVE_EXTENSION: &str = "rbsav";
+
+/// The version which is written by [`SaveGame::save`], it is increased whenever the format changes.
+pub const SAVE_VERSION: u16 = 3;
+
+const MAGIC: &[u8; 6] = b"RBSAV\0";
+
+const BUILTIN: u8 = 0;
+const CUSTOM: u8 = 1;
+
+const NO_NATION: u8 = u8::MAX;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    /// The file doesn't start with the save magic bytes.
+    NotSave,
+
+    /// The save was written by a different version of the game.
+    Version {
+        found: u16,
+        supported: u16,
+    },
+
+    /// The file ended in the middle of the save.
+    Truncated,
+
+    /// The file contains a value which isn't valid.
+    Invalid(&'static str),
+
+    /// None of the maps have the same [`stable_hash`](MapData::stable_hash) as the save.
+    UnknownMap {
+        hash: u64,
+    },
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotSave => write!(f, "File is not a save"),
+            Self::Version { found, supported } => write!(f, "Save version {} is not supported, expected version {}", found, supported),
+            Self::Truncated => write!(f, "Save file is truncated"),
+            Self::Invalid(field) => write!(f, "Save has an invalid {}", field),
+            Self::UnknownMap { hash } => write!(f, "Save map {:016x} was not found", hash),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+// The save is read with the replay's reader, which only returns these errors
+impl From<ReplayError> for SaveError {
+    fn from(error: ReplayError) -> Self {
+        match error {
+            ReplayError::Invalid(field) => Self::Invalid(field),
+            _ => Self::Truncated,
+        }
+    }
+}
+
+
+fn write_nation(writer: &mut Writer, nation: Option<Nation>) {
+    writer.u8(match nation {
+        Some(nation) => Nation::ALL.iter().position(|x| *x == nation).unwrap() as u8,
+        None => NO_NATION,
+    });
+}
+
+fn read_nation(reader: &mut Reader) -> Result<Option<Nation>, SaveError> {
+    match reader.u8()? {
+        NO_NATION => Ok(None),
+        index => Nation::ALL.get(index as usize).copied().map(Some).ok_or(SaveError::Invalid("nation")),
+    }
+}
+
+/// Writes the index of the class in `builtin`, or the index of the custom class.
+fn write_class<A>(writer: &mut Writer, builtin: &[A], class: A, custom: Option<u16>) where A: PartialEq {
+    match custom {
+        Some(index) => {
+            writer.u8(CUSTOM);
+            writer.u16(index);
+        },
+        None => {
+            writer.u8(BUILTIN);
+            writer.u16(builtin.iter().position(|x| *x == class).unwrap() as u16);
+        },
+    }
+}
+
+fn read_class<A>(reader: &mut Reader, builtin: &[A], custom: fn(u16) -> A, field: &'static str) -> Result<A, SaveError> where A: Copy {
+    let tag = reader.u8()?;
+    let index = reader.u16()?;
+
+    match tag {
+        BUILTIN => builtin.get(index as usize).copied().ok_or(SaveError::Invalid(field)),
+        CUSTOM => Ok(custom(index)),
+        _ => Err(SaveError::Invalid(field)),
+    }
+}
+
+fn write_unit_class(writer: &mut Writer, class: UnitClass) {
+    let custom = match class {
+        UnitClass::Custom(index) => Some(index),
+        _ => None,
+    };
+
+    write_class(writer, UnitClass::ALL, class, custom);
+}
+
+fn write_bool(writer: &mut Writer, value: bool) {
+    writer.u8(value as u8);
+}
+
+fn read_bool(reader: &mut Reader, field: &'static str) -> Result<bool, SaveError> {
+    match reader.u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(SaveError::Invalid(field)),
+    }
+}
+
+
+/// The state of a match at the end of a turn, see the [module documentation](self) for the file format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveGame {
+    pub map_hash: u64,
+    pub settings: ReplaySettings,
+
+    /// The nation which is controlled by the local player.
+    pub player: Nation,
+
+    pub teams: Teams,
+    pub rules: MatchRules,
+    pub funds: Funds,
+
+    pub snapshot: StateSnapshot,
+}
+
+impl SaveGame {
+    pub fn new(map: &MapData, settings: ReplaySettings, player: Nation, teams: Teams, rules: MatchRules, funds: Funds, snapshot: StateSnapshot) -> Self {
+        Self {
+            map_hash: map.stable_hash(),
+            settings,
+            player,
+            teams,
+            rules,
+            funds,
+            snapshot,
+        }
+    }
+
+    /// Returns the map which the match is played on.
+    pub fn find_map<'a>(&self, maps: &'a [Arc<MapData>]) -> Result<&'a Arc<MapData>, SaveError> {
+        maps.iter()
+            .find(|map| map.stable_hash() == self.map_hash)
+            .ok_or(SaveError::UnknownMap { hash: self.map_hash })
+    }
+
+    pub fn save(&self) -> Vec<u8> {
+        let mut writer = Writer { bytes: vec![] };
+
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u16(SAVE_VERSION);
+        writer.u64(self.map_hash);
+
+        self.settings.write(&mut writer);
+
+        write_nation(&mut writer, Some(self.player));
+
+        for nation in Nation::ALL {
+            writer.u32(self.teams.team(*nation));
+        }
+
+        writer.u32(self.rules.starting_funds);
+        writer.u32(self.rules.income);
+        write_bool(&mut writer, self.rules.capture_limit.is_some());
+        writer.u32(self.rules.capture_limit.unwrap_or(0));
+        writer.u32(self.rules.banned_units.len() as u32);
+
+        for class in self.rules.banned_units.iter() {
+            write_unit_class(&mut writer, *class);
+        }
+
+        for nation in Nation::ALL {
+            writer.u32(self.funds.get(*nation));
+        }
+
+        writer.u32(self.snapshot.turn);
+        writer.u64(self.snapshot.rng);
+        writer.u32(self.snapshot.next_unit_id);
+
+        writer.u32(self.snapshot.units.len() as u32);
+
+        for unit in self.snapshot.units.iter() {
+            writer.u32(unit.id);
+            writer.f32(unit.x);
+            writer.f32(unit.y);
+            write_unit_class(&mut writer, unit.class);
+            write_nation(&mut writer, Some(unit.nation));
+            writer.u32(unit.hp);
+            writer.u32(unit.fuel);
+            writer.u32(unit.kills);
+            writer.u8(Rank::ALL.iter().position(|x| *x == unit.rank).unwrap() as u8);
+            write_bool(&mut writer, unit.waited);
+        }
+
+        writer.u32(self.snapshot.buildings.len() as u32);
+
+        for building in self.snapshot.buildings.iter() {
+            let custom = match building.class {
+                BuildingClass::Custom(index) => Some(index),
+                _ => None,
+            };
+
+            writer.u32(building.id);
+            writer.u32(building.x);
+            writer.u32(building.y);
+            write_class(&mut writer, BuildingClass::ALL, building.class, custom);
+            write_nation(&mut writer, building.nation);
+        }
+
+        writer.bytes
+    }
+
+    pub fn load(bytes: &[u8]) -> Result<Self, SaveError> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(SaveError::NotSave);
+        }
+
+        let version = reader.u16()?;
+
+        if version != SAVE_VERSION {
+            return Err(SaveError::Version {
+                found: version,
+                supported: SAVE_VERSION,
+            });
+        }
+
+        let map_hash = reader.u64()?;
+        let settings = ReplaySettings::read(&mut reader)?;
+
+        let player = read_nation(&mut reader)?.ok_or(SaveError::Invalid("player"))?;
+
+        let mut teams = Teams::new();
+
+        for nation in Nation::ALL {
+            teams.set_team(*nation, reader.u32()?);
+        }
+
+        let starting_funds = reader.u32()?;
+        let income = reader.u32()?;
+
+        let has_capture_limit = read_bool(&mut reader, "capture limit")?;
+        let capture_limit = reader.u32()?;
+        let capture_limit = if has_capture_limit { Some(capture_limit) } else { None };
+
+        let banned_units = (0..reader.u32()?).map(|_| {
+            read_class(&mut reader, UnitClass::ALL, UnitClass::Custom, "banned unit")
+        }).collect::<Result<Vec<_>, SaveError>>()?;
+
+        let rules = MatchRules { starting_funds, income, capture_limit, banned_units };
+
+        let mut funds = Funds::new(0);
+
+        for nation in Nation::ALL {
+            funds.add(*nation, reader.u32()?);
+        }
+
+        let turn = reader.u32()?;
+        let rng = reader.u64()?;
+        let next_unit_id = reader.u32()?;
+
+        let units = (0..reader.u32()?).map(|_| {
+            let id = reader.u32()?;
+            let x = reader.f32()?;
+            let y = reader.f32()?;
+
+            if !x.is_finite() || !y.is_finite() {
+                return Err(SaveError::Invalid("unit coord"));
+            }
+
+            Ok(UnitSnapshot {
+                id,
+                x,
+                y,
+                class: read_class(&mut reader, UnitClass::ALL, UnitClass::Custom, "unit class")?,
+                nation: read_nation(&mut reader)?.ok_or(SaveError::Invalid("nation"))?,
+                hp: reader.u32()?,
+                fuel: reader.u32()?,
+                kills: reader.u32()?,
+                rank: Rank::ALL.get(reader.u8()? as usize).copied().ok_or(SaveError::Invalid("rank"))?,
+                waited: read_bool(&mut reader, "waited")?,
+            })
+        }).collect::<Result<Vec<_>, SaveError>>()?;
+
+        let buildings = (0..reader.u32()?).map(|_| {
+            Ok(BuildingSnapshot {
+                id: reader.u32()?,
+                x: reader.u32()?,
+                y: reader.u32()?,
+                class: read_class(&mut reader, BuildingClass::ALL, BuildingClass::Custom, "building class")?,
+                nation: read_nation(&mut reader)?,
+            })
+        }).collect::<Result<Vec<_>, SaveError>>()?;
+
+        if !reader.bytes.is_empty() {
+            return Err(SaveError::Invalid("trailing data"));
+        }
+
+        Ok(Self {
+            map_hash,
+            settings,
+            player,
+            teams,
+            rules,
+            funds,
+            snapshot: StateSnapshot::new(turn, rng, next_unit_id, units, buildings),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{SaveGame, SaveError, SAVE_VERSION};
+    use crate::{Nation, Weather};
+    use crate::unit::{UnitClass, Rank};
+    use crate::building::{BuildingClass};
+    use crate::map::{MapData};
+    use crate::terrain::{TerrainClass};
+    use crate::replay::{ReplaySettings};
+    use crate::team::{Teams};
+    use crate::rules::{MatchRules, Funds};
+    use crate::audit::{StateSnapshot, UnitSnapshot, BuildingSnapshot};
+
+    fn save() -> SaveGame {
+        let map = MapData::new(4, 4, TerrainClass::Grass);
+
+        let units = vec![
+            UnitSnapshot {
+                id: 0,
+                x: 1.0,
+                y: 2.0,
+                class: UnitClass::Tank,
+                nation: Nation::BlueMoon,
+                hp: 65,
+                fuel: 40,
+                kills: 2,
+                rank: Rank::One,
+                waited: true,
+            },
+            UnitSnapshot {
+                id: 1,
+                x: 3.0,
+                y: 0.0,
+                class: UnitClass::Custom(7),
+                nation: Nation::OrangeStar,
+                hp: 100,
+                fuel: 99,
+                kills: 0,
+                rank: Rank::Rookie,
+                waited: false,
+            },
+        ];
+
+        let buildings = vec![
+            BuildingSnapshot { id: 0, x: 0, y: 0, class: BuildingClass::City, nation: None },
+            BuildingSnapshot { id: 1, x: 2, y: 3, class: BuildingClass::Custom(1), nation: Some(Nation::BlackHole) },
+        ];
+
+        let mut teams = Teams::new();
+        teams.set_team(Nation::GreenEarth, teams.team(Nation::BlueMoon));
+
+        let rules = MatchRules {
+            starting_funds: 1000,
+            income: 1500,
+            capture_limit: Some(12),
+            banned_units: vec![UnitClass::Stealth, UnitClass::Custom(3)],
+        };
+
+        let mut funds = Funds::new(0);
+        funds.add(Nation::BlueMoon, 4500);
+        funds.add(Nation::BlackHole, 120);
+
+        SaveGame::new(&map, ReplaySettings {
+            fog: false,
+            weather: Weather::Rain,
+            starting_funds: 1000,
+            income: 1500,
+        }, Nation::GreenEarth, teams, rules, funds, StateSnapshot::new(5, 1234, 2, units, buildings))
+    }
+
+    #[test]
+    fn round_trip() {
+        let save = save();
+        let loaded = SaveGame::load(&save.save()).unwrap();
+
+        assert_eq!(loaded.player, Nation::GreenEarth);
+        assert!(loaded.teams.are_allies(Nation::BlueMoon, Nation::GreenEarth));
+        assert!(!loaded.teams.are_allies(Nation::BlueMoon, Nation::OrangeStar));
+        assert_eq!(loaded.rules.capture_limit, Some(12));
+        assert_eq!(loaded.rules.banned_units, [UnitClass::Stealth, UnitClass::Custom(3)]);
+        assert_eq!(loaded.funds.get(Nation::BlueMoon), 4500);
+        assert_eq!(loaded.funds.get(Nation::BlackHole), 120);
+        assert_eq!(loaded, save);
+
+        let rules = SaveGame {
+            rules: MatchRules::default(),
+            ..save
+        };
+
+        assert_eq!(SaveGame::load(&rules.save()), Ok(rules));
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = save().save();
+
+        assert_eq!(SaveGame::load(b"RBREP\0"), Err(SaveError::NotSave));
+        assert_eq!(SaveGame::load(&bytes[..(bytes.len() - 1)]), Err(SaveError::Truncated));
+
+        let mut newer = bytes.clone();
+        newer[6..8].copy_from_slice(&(SAVE_VERSION + 1).to_le_bytes());
+
+        assert_eq!(SaveGame::load(&newer), Err(SaveError::Version {
+            found: SAVE_VERSION + 1,
+            supported: SAVE_VERSION,
+        }));
+    }
+}
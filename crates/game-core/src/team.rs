@@ -0,0 +1,107 @@
+//! Teams of nations which play together, such as 2 vs 2.
+
+use crate::{Nation};
+
+
+/// Which team each nation is on, nations on the same team are allies.
+///
+/// Allies share their vision, can't attack each other, and can repair on each other's properties.
+/// The match is won when only one team has units left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Teams {
+    /// The team of each nation, in the same order as [`Nation::ALL`].
+    teams: [u32; Nation::ALL.len()],
+}
+
+impl Teams {
+    /// Every nation is on its own team.
+    pub fn new() -> Self {
+        Self {
+            teams: std::array::from_fn(|index| index as u32),
+        }
+    }
+
+    #[inline]
+    pub fn team(&self, nation: Nation) -> u32 {
+        self.teams[nation as usize]
+    }
+
+    #[inline]
+    pub fn set_team(&mut self, nation: Nation, team: u32) {
+        self.teams[nation as usize] = team;
+    }
+
+    /// Whether the nations are on the same team, a nation is always allied with itself.
+    #[inline]
+    pub fn are_allies(&self, a: Nation, b: Nation) -> bool {
+        self.team(a) == self.team(b)
+    }
+
+    /// Returns the other nations which are on the same team as `nation`.
+    pub fn allies(&self, nation: Nation) -> impl Iterator<Item = Nation> + '_ {
+        Nation::ALL.iter().copied().filter(move |other| *other != nation && self.are_allies(*other, nation))
+    }
+
+    /// Whether a property which is owned by `owner` can repair a unit of `nation`.
+    #[inline]
+    pub fn can_repair(&self, owner: Nation, nation: Nation) -> bool {
+        self.are_allies(owner, nation)
+    }
+
+    /// Returns the team which has won, `alive` is the nations which still have units.
+    ///
+    /// Returns `None` if there is more than one team left, or if there aren't any nations left.
+    pub fn winner<I>(&self, alive: I) -> Option<u32> where I: IntoIterator<Item = Nation> {
+        let mut winner = None;
+
+        for nation in alive {
+            let team = self.team(nation);
+
+            match winner {
+                None => winner = Some(team),
+                Some(winner) if winner != team => return None,
+                Some(_) => {},
+            }
+        }
+
+        winner
+    }
+}
+
+impl Default for Teams {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Teams};
+    use crate::{Nation};
+
+    #[test]
+    fn two_vs_two() {
+        let mut teams = Teams::new();
+
+        assert!(!teams.are_allies(Nation::OrangeStar, Nation::BlueMoon));
+
+        teams.set_team(Nation::OrangeStar, 0);
+        teams.set_team(Nation::GreenEarth, 0);
+        teams.set_team(Nation::BlueMoon, 1);
+        teams.set_team(Nation::YellowComet, 1);
+
+        assert!(teams.are_allies(Nation::OrangeStar, Nation::GreenEarth));
+        assert!(!teams.are_allies(Nation::OrangeStar, Nation::BlueMoon));
+        assert!(teams.can_repair(Nation::GreenEarth, Nation::OrangeStar));
+
+        assert_eq!(teams.allies(Nation::BlueMoon).collect::<Vec<_>>(), [Nation::YellowComet]);
+
+        assert_eq!(teams.winner([Nation::OrangeStar, Nation::BlueMoon]), None);
+        assert_eq!(teams.winner([Nation::OrangeStar, Nation::GreenEarth]), Some(0));
+        assert_eq!(teams.winner([Nation::YellowComet]), Some(1));
+        assert_eq!(teams.winner([]), None);
+    }
+}
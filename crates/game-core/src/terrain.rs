@@ -0,0 +1,242 @@
+use crate::building::{BuildingClass};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TerrainClass {
+    Empty,
+    Grass,
+    Road {
+        ruins: bool,
+    },
+    Bridge {
+        orientation: Orientation,
+    },
+    Forest,
+    Mountain {
+        variant: u32,
+    },
+    Pipeline,
+    Pipeseam {
+        destroyed: bool,
+    },
+    Ocean,
+    River,
+    Shoal,
+    Reef,
+}
+
+impl TerrainClass {
+    pub const ALL: &[Self] = &[
+        Self::Empty,
+        Self::Grass,
+        Self::Road { ruins: false },
+        Self::Bridge { orientation: Orientation::Horizontal },
+        Self::Forest,
+        Self::Mountain { variant: 0 },
+        Self::Pipeline,
+        Self::Pipeseam { destroyed: false, },
+        Self::Ocean,
+        Self::River,
+        Self::Shoal,
+        Self::Reef,
+    ];
+}
+
+
+/// How a unit moves, each movement class has different movement costs for each terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MovementClass {
+    Foot,
+    Boot,
+    Treads,
+    Tires,
+    Air,
+    Sea,
+    Lander,
+    Pipe,
+}
+
+impl MovementClass {
+    pub const ALL: &[Self] = &[
+        Self::Foot,
+        Self::Boot,
+        Self::Treads,
+        Self::Tires,
+        Self::Air,
+        Self::Sea,
+        Self::Lander,
+        Self::Pipe,
+    ];
+}
+
+
+/// The movement cost for each [`MovementClass`], in the same order as [`MovementClass::ALL`].
+///
+/// `None` means that the movement class cannot move onto the tile.
+type MoveCosts = [Option<u32>; 8];
+
+const LAND: MoveCosts      = [Some(1), Some(1), Some(1), Some(1), Some(1), None,    None,    None];
+const PLAIN: MoveCosts     = [Some(1), Some(1), Some(1), Some(2), Some(1), None,    None,    None];
+const WOOD: MoveCosts      = [Some(1), Some(1), Some(2), Some(3), Some(1), None,    None,    None];
+const ROUGH: MoveCosts     = [Some(2), Some(1), None,    None,    Some(1), None,    None,    None];
+const SEA: MoveCosts       = [None,    None,    None,    None,    Some(1), Some(1), Some(1), None];
+const SHOAL: MoveCosts     = [Some(1), Some(1), Some(1), Some(1), Some(1), None,    Some(1), None];
+const REEF: MoveCosts      = [None,    None,    None,    None,    Some(1), Some(2), Some(2), None];
+const PIPE: MoveCosts      = [None,    None,    None,    None,    None,    None,    None,    Some(1)];
+const BASE: MoveCosts      = [Some(1), Some(1), Some(1), Some(1), Some(1), None,    None,    Some(1)];
+const PORT: MoveCosts      = [Some(1), Some(1), Some(1), Some(1), Some(1), Some(1), Some(1), None];
+const IMPASSABLE: MoveCosts = [None; 8];
+
+
+/// Gameplay information about a terrain or building.
+///
+/// Buildings are placed on top of the terrain, so a tile with a building uses the building's info,
+/// see `Grid::terrain_info` in the game renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerrainInfo {
+    /// The name which is displayed to the player.
+    pub name: &'static str,
+
+    /// Number of defense stars, each star reduces the damage which is received by units on this tile.
+    pub defense: u32,
+
+    /// Whether the owner receives funds from it at the start of every turn.
+    pub income: bool,
+
+    costs: MoveCosts,
+}
+
+impl TerrainInfo {
+    const fn new(name: &'static str, defense: u32, costs: MoveCosts) -> Self {
+        Self { name, defense, income: false, costs }
+    }
+
+    const fn property(name: &'static str, costs: MoveCosts) -> Self {
+        Self { name, defense: 3, income: true, costs }
+    }
+
+    const EMPTY: Self         = Self::new("Empty", 0, IMPASSABLE);
+    const PLAIN: Self         = Self::new("Plain", 1, PLAIN);
+    const ROAD: Self          = Self::new("Road", 0, LAND);
+    const RUINS: Self         = Self::new("Ruins", 0, LAND);
+    const BRIDGE: Self        = Self::new("Bridge", 0, LAND);
+    const WOOD: Self          = Self::new("Wood", 2, WOOD);
+    const MOUNTAIN: Self      = Self::new("Mountain", 4, ROUGH);
+    const PIPELINE: Self      = Self::new("Pipeline", 0, PIPE);
+    const PIPESEAM: Self      = Self::new("Pipe Seam", 0, PIPE);
+    const BROKEN_SEAM: Self   = Self::new("Broken Seam", 1, PLAIN);
+    const SEA: Self           = Self::new("Sea", 0, SEA);
+    const RIVER: Self         = Self::new("River", 0, ROUGH);
+    const SHOAL: Self         = Self::new("Shoal", 0, SHOAL);
+    const REEF: Self          = Self::new("Reef", 1, REEF);
+
+    pub(crate) const HQ: Self            = Self { defense: 4, ..Self::property("HQ", LAND) };
+    pub(crate) const CITY: Self          = Self::property("City", LAND);
+    pub(crate) const BASE: Self          = Self::property("Base", BASE);
+    pub(crate) const AIRPORT: Self       = Self::property("Airport", LAND);
+    pub(crate) const PORT: Self          = Self::property("Port", PORT);
+    pub(crate) const COM_TOWER: Self     = Self::property("Com Tower", LAND);
+    pub(crate) const LAB: Self           = Self::property("Lab", LAND);
+    pub(crate) const MISSILE_SILO: Self  = Self::new("Missile Silo", 3, LAND);
+
+    /// Returns the number of movement points which are needed to move onto this tile,
+    /// or `None` if the movement class cannot move onto this tile.
+    #[inline]
+    pub fn move_cost(&self, class: MovementClass) -> Option<u32> {
+        self.costs[class as usize]
+    }
+
+    /// Whether the movement class can move onto this tile.
+    #[inline]
+    pub fn is_passable(&self, class: MovementClass) -> bool {
+        self.move_cost(class).is_some()
+    }
+}
+
+
+impl TerrainClass {
+    /// Whether the terrain is covered by water, this is only used for visuals.
+    #[inline]
+    pub fn is_water(&self) -> bool {
+        matches!(self, Self::Ocean | Self::River | Self::Shoal | Self::Reef)
+    }
+
+    /// Returns the gameplay information for the terrain.
+    pub fn info(&self) -> &'static TerrainInfo {
+        match self {
+            Self::Empty => &TerrainInfo::EMPTY,
+            Self::Grass => &TerrainInfo::PLAIN,
+            Self::Road { ruins: false } => &TerrainInfo::ROAD,
+            Self::Road { ruins: true } => &TerrainInfo::RUINS,
+            Self::Bridge { .. } => &TerrainInfo::BRIDGE,
+            Self::Forest => &TerrainInfo::WOOD,
+            Self::Mountain { .. } => &TerrainInfo::MOUNTAIN,
+            Self::Pipeline => &TerrainInfo::PIPELINE,
+            Self::Pipeseam { destroyed: false } => &TerrainInfo::PIPESEAM,
+            Self::Pipeseam { destroyed: true } => &TerrainInfo::BROKEN_SEAM,
+            Self::Ocean => &TerrainInfo::SEA,
+            Self::River => &TerrainInfo::RIVER,
+            Self::Shoal => &TerrainInfo::SHOAL,
+            Self::Reef => &TerrainInfo::REEF,
+        }
+    }
+}
+
+
+impl BuildingClass {
+    /// Returns the gameplay information for the building.
+    #[inline]
+    pub fn info(&self) -> &'static TerrainInfo {
+        &self.spec().info
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{TerrainInfo, MovementClass};
+    use super::{TerrainClass};
+    use crate::building::{BuildingClass};
+
+    #[test]
+    fn costs_order() {
+        for (index, class) in MovementClass::ALL.iter().enumerate() {
+            assert_eq!(*class as usize, index);
+        }
+    }
+
+    #[test]
+    fn buildings() {
+        for building in BuildingClass::ALL {
+            let info = building.info();
+
+            assert!(info.defense >= 3);
+            assert!(info.is_passable(MovementClass::Foot));
+            assert!(info.is_passable(MovementClass::Air));
+        }
+
+        assert!(BuildingClass::Port.info().is_passable(MovementClass::Sea));
+        assert!(!BuildingClass::City.info().is_passable(MovementClass::Sea));
+    }
+
+    #[test]
+    fn terrain() {
+        assert_eq!(TerrainClass::Empty.info(), &TerrainInfo::EMPTY);
+
+        for class in MovementClass::ALL {
+            assert!(!TerrainClass::Empty.info().is_passable(*class));
+        }
+
+        assert_eq!(TerrainClass::Mountain { variant: 2 }.info().defense, 4);
+        assert_eq!(TerrainClass::Forest.info().move_cost(MovementClass::Tires), Some(3));
+        assert_eq!(TerrainClass::Ocean.info().move_cost(MovementClass::Treads), None);
+        assert_eq!(TerrainClass::Pipeline.info().move_cost(MovementClass::Pipe), Some(1));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::registry::{Registry, UnitSpec};
+use crate::{Nation};
+use crate::terrain::{MovementClass};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnitClass {
+    Infantry,
+    Mech,
+    Recon,
+    APC,
+    Artillery,
+    Tank,
+    AntiAir,
+    Missile,
+    Rocket,
+    MediumTank,
+    Piperunner,
+    Neotank,
+    MegaTank,
+    BCopter,
+    TCopter,
+    Fighter,
+    Bomber,
+    Stealth,
+    Battleship,
+    Cruiser,
+    Submarine,
+    Lander,
+    Carrier,
+    BlackBoat,
+    BlackBomb,
+    Oozium,
+
+    /// A unit class which was added by a mod, see [`Registry::add_unit`].
+    Custom(u16),
+}
+
+impl UnitClass {
+    /// Every built-in unit class, use [`Registry::unit_classes`] to include the custom classes.
+    pub const ALL: &[Self] = &[
+        Self::Infantry,
+        Self::Mech,
+        Self::Recon,
+        Self::APC,
+        Self::Artillery,
+        Self::Tank,
+        Self::AntiAir,
+        Self::Missile,
+        Self::Rocket,
+        Self::MediumTank,
+        Self::Piperunner,
+        Self::Neotank,
+        Self::MegaTank,
+        Self::BCopter,
+        Self::TCopter,
+        Self::Fighter,
+        Self::Bomber,
+        Self::Stealth,
+        Self::Battleship,
+        Self::Cruiser,
+        Self::Submarine,
+        Self::Lander,
+        Self::Carrier,
+        Self::BlackBoat,
+        Self::BlackBomb,
+        Self::Oozium,
+    ];
+
+    /// Returns the stats of the unit class from the installed [`Registry`].
+    #[inline]
+    pub fn spec(&self) -> &'static UnitSpec {
+        Registry::get().unit(*self)
+    }
+
+    /// The number of movement points per turn.
+    #[inline]
+    pub fn movement(&self) -> u32 {
+        self.spec().movement
+    }
+
+    /// The amount of fuel when the unit is fully supplied.
+    #[inline]
+    pub fn max_fuel(&self) -> u32 {
+        self.spec().max_fuel
+    }
+
+    /// How many tiles the unit can see in fog.
+    #[inline]
+    pub fn vision(&self) -> u32 {
+        self.spec().vision
+    }
+
+    /// The minimum and maximum distance which the unit can attack,
+    /// or `None` if the unit cannot attack.
+    #[inline]
+    pub fn attack_range(&self) -> Option<AttackRange> {
+        self.spec().attack_range
+    }
+
+    #[inline]
+    pub fn movement_class(&self) -> MovementClass {
+        self.spec().movement_class
+    }
+
+    /// The funds which are needed to build the unit, or `None` if it can't be built.
+    #[inline]
+    pub fn cost(&self) -> Option<u32> {
+        self.spec().cost
+    }
+}
+
+
+/// Whether the units are allowed to be on the same tile.
+///
+/// A tile can only have one unit, unless the other units are being carried by a
+/// [`Transport`](crate::registry::Transport) of the same nation.
+pub fn can_share_tile(units: &[(UnitClass, Nation)]) -> bool {
+    if units.len() <= 1 {
+        return true;
+    }
+
+    units.iter().enumerate().any(|(index, (class, nation))| {
+        let Some(transport) = class.spec().transport else {
+            return false;
+        };
+
+        let mut cargo = units.iter().enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(_, unit)| unit);
+
+        (units.len() - 1) as u32 <= transport.capacity &&
+        cargo.all(|(cargo_class, cargo_nation)| cargo_nation == nation && transport.can_carry(*cargo_class))
+    })
+}
+
+
+/// The distance (in tiles) which a unit can attack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttackRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl AttackRange {
+    /// Direct units can move and attack in the same turn, indirect units can only do one or the other.
+    #[inline]
+    pub fn is_direct(&self) -> bool {
+        self.max == 1
+    }
+
+    /// Whether a unit which is `distance` tiles away can be attacked.
+    #[inline]
+    pub fn contains(&self, distance: u32) -> bool {
+        distance >= self.min && distance <= self.max
+    }
+}
+
+
+
+
+/// Units are promoted after destroying enemy units, each rank makes the unit stronger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rank {
+    #[default]
+    Rookie,
+    One,
+    Two,
+    Veteran,
+}
+
+impl Rank {
+    pub const ALL: &[Self] = &[
+        Self::Rookie,
+        Self::One,
+        Self::Two,
+        Self::Veteran,
+    ];
+
+    /// The number of enemy units which must be destroyed to reach this rank.
+    pub fn kills(&self) -> u32 {
+        match self {
+            Self::Rookie => 0,
+            Self::One => 1,
+            Self::Two => 3,
+            Self::Veteran => 5,
+        }
+    }
+
+    /// Returns the highest rank for the number of kills.
+    pub fn from_kills(kills: u32) -> Self {
+        Self::ALL.iter().rev().copied().find(|rank| kills >= rank.kills()).unwrap_or(Self::Rookie)
+    }
+
+    /// The percentage which is added to the unit's attack.
+    pub fn attack_bonus(&self) -> u32 {
+        match self {
+            Self::Rookie => 0,
+            Self::One => 5,
+            Self::Two => 10,
+            Self::Veteran => 20,
+        }
+    }
+
+    /// The percentage which is added to the unit's defense.
+    pub fn defense_bonus(&self) -> u32 {
+        match self {
+            Self::Rookie => 0,
+            Self::One => 0,
+            Self::Two => 5,
+            Self::Veteran => 10,
+        }
+    }
+
+    /// The text of the badge which is displayed next to the unit, rookies don't have a badge.
+    pub fn badge(&self) -> Option<&'static str> {
+        match self {
+            Self::Rookie => None,
+            Self::One => Some("I"),
+            Self::Two => Some("II"),
+            Self::Veteran => Some("V"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Rank, UnitClass, can_share_tile};
+    use crate::{Nation};
+
+    #[test]
+    fn share_tile() {
+        let orange = Nation::OrangeStar;
+        let blue = Nation::BlueMoon;
+
+        assert!(can_share_tile(&[]));
+        assert!(can_share_tile(&[(UnitClass::Tank, orange)]));
+        assert!(!can_share_tile(&[(UnitClass::Tank, orange), (UnitClass::Infantry, orange)]));
+
+        assert!(can_share_tile(&[(UnitClass::Infantry, orange), (UnitClass::APC, orange)]));
+        assert!(!can_share_tile(&[(UnitClass::APC, orange), (UnitClass::Infantry, blue)]));
+        assert!(!can_share_tile(&[(UnitClass::APC, orange), (UnitClass::Tank, orange)]));
+        assert!(!can_share_tile(&[(UnitClass::APC, orange), (UnitClass::Infantry, orange), (UnitClass::Mech, orange)]));
+
+        assert!(can_share_tile(&[(UnitClass::Lander, orange), (UnitClass::Tank, orange), (UnitClass::Mech, orange)]));
+    }
+
+    #[test]
+    fn rank_from_kills() {
+        let ranks = (0..7).map(Rank::from_kills).collect::<Vec<_>>();
+
+        assert_eq!(ranks, [
+            Rank::Rookie,
+            Rank::One,
+            Rank::One,
+            Rank::Two,
+            Rank::Two,
+            Rank::Veteran,
+            Rank::Veteran,
+        ]);
+    }
+}
@@ -0,0 +1,342 @@
+//! Lets players replace the built-in spritesheets, palettes, and fonts (e.g. with a texture pack).
+//!
+//! The built-in assets are embedded into the binary, an [`AssetOverrides`] contains the files which
+//! replace them. It is passed to [`Game::start_engine`](crate::Game::start_engine).
+//!
+//! It can also contain custom team colors for each nation, which are added to the built-in palettes.
+
+use std::collections::HashMap;
+use crate::grid::{Nation};
+
+
+/// An asset which is embedded into the binary, the path is relative to the `dist` folder.
+pub(crate) struct Asset {
+    pub(crate) path: &'static str,
+    bytes: &'static [u8],
+}
+
+macro_rules! builtin {
+    ($path:literal) => {
+        Asset {
+            path: $path,
+            bytes: include_bytes!(concat!("../../../dist/", $path)),
+        }
+    };
+}
+
+pub(crate) const EFFECT: Asset = builtin!("sprites/effect.png");
+pub(crate) const HUD: Asset = builtin!("sprites/hud.png");
+pub(crate) const BUILDINGS_PALETTE: Asset = builtin!("sprites/buildings_palette.png");
+pub(crate) const BUILDINGS_SMALL: Asset = builtin!("sprites/buildings_small.png");
+pub(crate) const TERRAIN_PALETTE: Asset = builtin!("sprites/terrain_palette.png");
+pub(crate) const TERRAIN_SMALL: Asset = builtin!("sprites/terrain_small.png");
+pub(crate) const UNITS_PALETTE: Asset = builtin!("sprites/units_palette.png");
+pub(crate) const UNITS_SMALL: Asset = builtin!("sprites/units_small.png");
+pub(crate) const UNITS_BIG: Asset = builtin!("sprites/units_big.png");
+pub(crate) const UNIFONT_ASCII: Asset = builtin!("fonts/unifont_ascii.png");
+
+#[cfg(feature = "unicode")]
+pub(crate) const UNIFONT_BMP: Asset = builtin!("fonts/unifont_bmp.png");
+
+const ASSETS: &[&Asset] = &[
+    &EFFECT,
+    &HUD,
+    &BUILDINGS_PALETTE,
+    &BUILDINGS_SMALL,
+    &TERRAIN_PALETTE,
+    &TERRAIN_SMALL,
+    &UNITS_PALETTE,
+    &UNITS_SMALL,
+    &UNITS_BIG,
+    &UNIFONT_ASCII,
+    #[cfg(feature = "unicode")]
+    &UNIFONT_BMP,
+];
+
+
+/// Custom team colors for a nation, which are added to the palettes at runtime.
+///
+/// The `units` file has two rows: the normal colors, and the grayed out colors for units which have waited.
+/// The `buildings` file has one row. They must be the same width as the built-in palettes.
+struct NationPalette {
+    nation: Nation,
+    units: &'static str,
+    buildings: &'static str,
+}
+
+const UNITS_PALETTE_ROWS: u32 = 2;
+const BUILDINGS_PALETTE_ROWS: u32 = 1;
+
+const NATION_PALETTES: &[NationPalette] = &[
+    NationPalette {
+        nation: Nation::OrangeStar,
+        units: "palettes/orange_star/units.png",
+        buildings: "palettes/orange_star/buildings.png",
+    },
+    NationPalette {
+        nation: Nation::BlueMoon,
+        units: "palettes/blue_moon/units.png",
+        buildings: "palettes/blue_moon/buildings.png",
+    },
+    NationPalette {
+        nation: Nation::GreenEarth,
+        units: "palettes/green_earth/units.png",
+        buildings: "palettes/green_earth/buildings.png",
+    },
+    NationPalette {
+        nation: Nation::YellowComet,
+        units: "palettes/yellow_comet/units.png",
+        buildings: "palettes/yellow_comet/buildings.png",
+    },
+    NationPalette {
+        nation: Nation::BlackHole,
+        units: "palettes/black_hole/units.png",
+        buildings: "palettes/black_hole/buildings.png",
+    },
+];
+
+fn nation_palette(nation: Nation) -> &'static NationPalette {
+    NATION_PALETTES.iter()
+        .find(|palette| palette.nation == nation)
+        .expect("Nation is missing a palette")
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetError {
+    /// There isn't a built-in asset with the path.
+    UnknownPath(String),
+
+    /// The file isn't a valid image.
+    Decode {
+        path: &'static str,
+        error: String,
+    },
+
+    /// The image must have the same size and color channels as the built-in asset,
+    /// because the sprites are found by their position in the spritesheet.
+    Mismatch {
+        path: &'static str,
+        expected: (u32, u32, image::ColorType),
+        found: (u32, u32, image::ColorType),
+    },
+
+    /// A nation palette has the wrong number of rows.
+    PaletteRows {
+        path: &'static str,
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl std::fmt::Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownPath(path) => write!(f, "Asset {} does not exist", path),
+            Self::Decode { path, error } => write!(f, "Asset {} is not a valid image: {}", path, error),
+            Self::Mismatch { path, expected, found } => write!(
+                f,
+                "Asset {} is {}x{} {:?}, expected {}x{} {:?}",
+                path,
+                found.0,
+                found.1,
+                found.2,
+                expected.0,
+                expected.1,
+                expected.2,
+            ),
+            Self::PaletteRows { path, expected, found } => write!(f, "Palette {} has {} rows, expected {}", path, found, expected),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+
+fn image_format(path: &'static str, bytes: &[u8]) -> Result<(u32, u32, image::ColorType), AssetError> {
+    let image = image::load_from_memory(bytes).map_err(|error| AssetError::Decode {
+        path,
+        error: error.to_string(),
+    })?;
+
+    Ok((image.width(), image.height(), image.color()))
+}
+
+
+/// Files which replace the built-in assets, every asset which isn't replaced uses the built-in file.
+///
+/// On the web the files are stored in IndexedDB, otherwise they are loaded from a `mods` folder
+/// with [`from_dir`](AssetOverrides::from_dir).
+#[derive(Debug, Clone, Default)]
+pub struct AssetOverrides {
+    files: HashMap<&'static str, Vec<u8>>,
+}
+
+impl AssetOverrides {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The path of every asset which can be replaced, such as `"sprites/units_small.png"`.
+    ///
+    /// This also contains the paths of the nation palettes, such as `"palettes/blue_moon/units.png"`.
+    pub fn paths() -> impl Iterator<Item = &'static str> {
+        ASSETS.iter()
+            .map(|asset| asset.path)
+            .chain(NATION_PALETTES.iter().flat_map(|palette| [palette.units, palette.buildings]))
+    }
+
+    /// Replaces the asset at `path`.
+    ///
+    /// The file is checked immediately, so an invalid texture pack is reported
+    /// when it is installed instead of when the engine starts.
+    ///
+    /// The width of nation palettes is checked when the engine starts, because it depends on the palettes which are used.
+    pub fn insert(&mut self, path: &str, bytes: Vec<u8>) -> Result<(), AssetError> {
+        if let Some((path, expected)) = Self::nation_palette_rows(path) {
+            let (_, found, _) = image_format(path, &bytes)?;
+
+            if found != expected {
+                return Err(AssetError::PaletteRows { path, expected, found });
+            }
+
+            self.files.insert(path, bytes);
+            return Ok(());
+        }
+
+        let asset = ASSETS.iter()
+            .find(|asset| asset.path == path)
+            .ok_or_else(|| AssetError::UnknownPath(path.to_string()))?;
+
+        let expected = image_format(asset.path, asset.bytes)?;
+        let found = image_format(asset.path, &bytes)?;
+
+        if expected != found {
+            return Err(AssetError::Mismatch { path: asset.path, expected, found });
+        }
+
+        self.files.insert(asset.path, bytes);
+        Ok(())
+    }
+
+    /// Loads every asset which exists in the folder, using the same paths as [`paths`](AssetOverrides::paths).
+    ///
+    /// Invalid files are skipped with a warning, so a broken texture pack doesn't stop the game from starting.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_dir(dir: impl AsRef<std::path::Path>) -> Self {
+        let dir = dir.as_ref();
+
+        let mut this = Self::new();
+
+        for path in Self::paths() {
+            let file = dir.join(path);
+
+            if let Ok(bytes) = std::fs::read(&file) {
+                if let Err(error) = this.insert(path, bytes) {
+                    tracing::warn!(file = %file.display(), %error, "Skipping invalid asset");
+                }
+            }
+        }
+
+        this
+    }
+
+    /// Returns the bytes of the asset, the built-in bytes are used if it wasn't replaced.
+    pub(crate) fn get(&self, asset: &Asset) -> &[u8] {
+        self.files.get(asset.path).map(|bytes| bytes.as_slice()).unwrap_or(asset.bytes)
+    }
+
+    /// Returns the custom units palette for the nation, or `None` if it uses the built-in colors.
+    pub(crate) fn units_palette(&self, nation: Nation) -> Option<&[u8]> {
+        self.files.get(nation_palette(nation).units).map(|bytes| bytes.as_slice())
+    }
+
+    /// Returns the custom buildings palette for the nation, or `None` if it uses the built-in colors.
+    pub(crate) fn buildings_palette(&self, nation: Nation) -> Option<&[u8]> {
+        self.files.get(nation_palette(nation).buildings).map(|bytes| bytes.as_slice())
+    }
+
+    /// Returns the static path and the number of rows if `path` is a nation palette.
+    fn nation_palette_rows(path: &str) -> Option<(&'static str, u32)> {
+        NATION_PALETTES.iter().find_map(|palette| {
+            if palette.units == path {
+                Some((palette.units, UNITS_PALETTE_ROWS))
+
+            } else if palette.buildings == path {
+                Some((palette.buildings, BUILDINGS_PALETTE_ROWS))
+
+            } else {
+                None
+            }
+        })
+    }
+}
+
+
+/// The palette rows which were added for the nations with custom team colors.
+///
+/// Nations which aren't in here use the built-in palette rows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct NationPalettes {
+    pub(crate) units: HashMap<Nation, u32>,
+    pub(crate) buildings: HashMap<Nation, u32>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::{Nation};
+    use super::{AssetOverrides, AssetError, UNIFONT_ASCII};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(vec![]);
+        image::GrayImage::new(width, height).write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn insert() {
+        let mut assets = AssetOverrides::new();
+
+        assert_eq!(assets.insert("sprites/nope.png", vec![]), Err(AssetError::UnknownPath("sprites/nope.png".to_string())));
+        assert!(matches!(assets.insert("fonts/unifont_ascii.png", vec![1, 2, 3]), Err(AssetError::Decode { .. })));
+        assert!(matches!(assets.insert("fonts/unifont_ascii.png", png(1, 1)), Err(AssetError::Mismatch { .. })));
+
+        assert_eq!(assets.get(&UNIFONT_ASCII), UNIFONT_ASCII.bytes);
+
+        let (width, height, _) = super::image_format(UNIFONT_ASCII.path, UNIFONT_ASCII.bytes).unwrap();
+        let replaced = png(width, height);
+
+        assets.insert("fonts/unifont_ascii.png", replaced.clone()).unwrap();
+
+        assert_eq!(assets.get(&UNIFONT_ASCII), replaced);
+    }
+
+    #[test]
+    fn insert_nation_palette() {
+        let mut assets = AssetOverrides::new();
+
+        assert!(AssetOverrides::paths().any(|path| path == "palettes/blue_moon/units.png"));
+
+        assert_eq!(assets.insert("palettes/blue_moon/units.png", png(4, 1)), Err(AssetError::PaletteRows {
+            path: "palettes/blue_moon/units.png",
+            expected: 2,
+            found: 1,
+        }));
+
+        assert!(matches!(assets.insert("palettes/blue_moon/buildings.png", vec![1, 2, 3]), Err(AssetError::Decode { .. })));
+
+        assert_eq!(assets.units_palette(Nation::BlueMoon), None);
+
+        let units = png(4, 2);
+        let buildings = png(4, 1);
+
+        assets.insert("palettes/blue_moon/units.png", units.clone()).unwrap();
+        assets.insert("palettes/blue_moon/buildings.png", buildings.clone()).unwrap();
+
+        assert_eq!(assets.units_palette(Nation::BlueMoon), Some(units.as_slice()));
+        assert_eq!(assets.buildings_palette(Nation::BlueMoon), Some(buildings.as_slice()));
+        assert_eq!(assets.units_palette(Nation::OrangeStar), None);
+    }
+}
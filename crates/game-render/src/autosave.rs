@@ -0,0 +1,277 @@
+//! Saves the match at the end of every turn, so that a match which was interrupted
+//! (such as by a crash) can be resumed with [`Grid::from_save`](crate::Grid::from_save).
+//!
+//! On the web the client stores the save in localStorage, otherwise it is stored in a file.
+//! The client uses [`sync`] to save the match whenever a [`TurnEnded`] event is published.
+
+use std::sync::Arc;
+use std::future::Future;
+use futures::future::{select, Either};
+use futures::stream::StreamExt;
+use futures_signals::signal::{SignalExt};
+
+use crate::{Game, TurnEnded};
+use crate::grid::{SaveGame};
+use crate::grid::map::{MapData};
+use rusted_battalions_game_core::replay::{ReplaySettings};
+
+
+/// The name of the autosave file in the save folder.
+pub const AUTOSAVE_FILE: &str = "autosave.rbsav";
+
+
+/// The match which is saved at the end of every turn, see [`GameSettings::autosave`](crate::GameSettings::autosave).
+#[derive(Debug, Clone)]
+pub struct AutosaveSettings {
+    pub map: Arc<MapData>,
+    pub settings: ReplaySettings,
+
+    /// The turn which the match starts on, this is `1` for a new match.
+    pub turn: u32,
+
+    /// The state of the match's [`Rng`](rusted_battalions_game_core::random::Rng) when the match starts, see [`Game::rng`].
+    ///
+    /// A new match should use a random seed, otherwise every match has the same random numbers.
+    pub rng: u64,
+}
+
+impl AutosaveSettings {
+    /// Settings for continuing the match of the save, the map must be the save's map.
+    pub fn resume(map: Arc<MapData>, save: &SaveGame) -> Self {
+        debug_assert_eq!(map.stable_hash(), save.map_hash);
+
+        Self {
+            map,
+            settings: save.settings,
+            turn: save.snapshot.turn,
+            rng: save.snapshot.rng,
+        }
+    }
+}
+
+
+/// Calls `save` at the end of every turn, and `clear` when the match ends.
+///
+/// The turns are published on the [`Game::active_grid`], so it switches to the new grid
+/// when the active grid changes. The future finishes when the game is dropped.
+pub fn sync<S, C>(game: &Game, mut save: S, mut clear: C) -> impl Future<Output = ()>
+    where S: FnMut(&SaveGame),
+          C: FnMut() {
+    let mut grids = game.active_grid_signal().to_stream();
+
+    async move {
+        let Some(grid) = grids.next().await else { return };
+
+        let mut turns = grid.events.subscribe::<TurnEnded>();
+
+        loop {
+            let next = match select(grids.next(), turns.next()).await {
+                Either::Left((grid, _)) => Either::Left(grid),
+                Either::Right((event, _)) => Either::Right(event),
+            };
+
+            match next {
+                Either::Left(Some(grid)) => {
+                    turns = grid.events.subscribe::<TurnEnded>();
+                },
+
+                Either::Right(Some(event)) => {
+                    if event.winner.is_some() {
+                        clear();
+
+                    } else if let Some(state) = &event.save {
+                        save(state);
+                    }
+                },
+
+                // The game keeps the active grid alive, so this only happens when the game is dropped
+                Either::Left(None) | Either::Right(None) => break,
+            }
+        }
+    }
+}
+
+
+/// Uses [`sync`] to save the match in the folder, see [`write`] and [`clear`].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sync_folder(game: &Game, dir: std::path::PathBuf) -> impl Future<Output = ()> {
+    let clear_dir = dir.clone();
+
+    sync(game, move |save| {
+        if let Err(error) = write(&dir, save) {
+            tracing::warn!(dir = %dir.display(), %error, "Failed to autosave");
+        }
+    }, move || {
+        clear(&clear_dir);
+    })
+}
+
+
+/// Replaces the autosave in the folder.
+///
+/// The save is written to a temporary file which is then renamed, so if the game crashes
+/// while saving then the previous autosave is kept.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(dir: impl AsRef<std::path::Path>, save: &SaveGame) -> std::io::Result<()> {
+    let dir = dir.as_ref();
+
+    std::fs::create_dir_all(dir)?;
+
+    let path = dir.join(AUTOSAVE_FILE);
+    let temp = path.with_extension("tmp");
+
+    std::fs::write(&temp, save.save())?;
+    std::fs::rename(&temp, &path)
+}
+
+/// Loads the autosave from the folder, returns `None` if there isn't an autosave or it is invalid.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(dir: impl AsRef<std::path::Path>) -> Option<SaveGame> {
+    let path = dir.as_ref().join(AUTOSAVE_FILE);
+
+    let bytes = std::fs::read(&path).ok()?;
+
+    match SaveGame::load(&bytes) {
+        Ok(save) => Some(save),
+        Err(error) => {
+            tracing::warn!(file = %path.display(), %error, "Ignoring invalid autosave");
+            None
+        },
+    }
+}
+
+/// Deletes the autosave, this should be called when the match ends.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn clear(dir: impl AsRef<std::path::Path>) {
+    let _ = std::fs::remove_file(dir.as_ref().join(AUTOSAVE_FILE));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use std::sync::Arc;
+    use crate::{Game, GameSettings, UnitAppearance, TurnEnded, QualitySettings, PowerPreference};
+    use crate::ui::{ControlsConfig, Action};
+    use crate::util::future::executor::{run_futures};
+    use crate::grid::{Grid};
+    use crate::grid::map::{MapData};
+    use crate::grid::pane::{GridPane};
+    use crate::grid::terrain::{TerrainClass};
+    use rusted_battalions_game_core::replay::{ReplaySettings};
+    use rusted_battalions_game_core::{Weather};
+    use super::{sync, AutosaveSettings};
+
+    const SETTINGS: ReplaySettings = ReplaySettings {
+        fog: false,
+        weather: Weather::Clear,
+        starting_funds: 0,
+        income: 1000,
+    };
+
+    fn new_game(map: &Arc<MapData>, grid: &Arc<Grid>, rng: u64) -> Arc<Game> {
+        Game::new(GameSettings {
+            appearance: UnitAppearance::default(),
+            grid: grid.clone(),
+            controls: ControlsConfig::default(),
+            spectator: None,
+            quality: QualitySettings::default(),
+            power_preference: PowerPreference::HighPerformance,
+            autosave: Some(AutosaveSettings {
+                map: map.clone(),
+                settings: SETTINGS,
+                turn: 1,
+                rng,
+            }),
+        })
+    }
+
+    /// Spawns [`sync`] and returns the turn and RNG state of every save, and how many times it was cleared.
+    fn spawn_sync(game: &Game, grid: &Grid) -> (Rc<RefCell<Vec<(u32, u64)>>>, Rc<RefCell<u32>>) {
+        let saves = Rc::new(RefCell::new(vec![]));
+        let cleared = Rc::new(RefCell::new(0));
+
+        grid.spawn_future(sync(game, {
+            let saves = saves.clone();
+            move |save| saves.borrow_mut().push((save.snapshot.turn, save.snapshot.rng))
+        }, {
+            let cleared = cleared.clone();
+            move || *cleared.borrow_mut() += 1
+        }));
+
+        run_futures();
+
+        (saves, cleared)
+    }
+
+    #[test]
+    fn sync_turns() {
+        let map = Arc::new(MapData::new(4, 4, TerrainClass::Grass));
+
+        let grid = Grid::from_map(&map);
+        grid.start_futures();
+
+        let game = new_game(&map, &grid, 0);
+        let (saves, cleared) = spawn_sync(&game, &grid);
+
+        grid.events.publish(TurnEnded { turn: 2, save: Some(grid.save_game(&map, SETTINGS, 2, 0)), winner: None });
+        grid.events.publish(TurnEnded { turn: 3, save: None, winner: None });
+        run_futures();
+
+        assert_eq!(*saves.borrow(), vec![(2, 0)]);
+        assert_eq!(*cleared.borrow(), 0);
+
+        grid.events.publish(TurnEnded { turn: 4, save: None, winner: Some(0) });
+        run_futures();
+
+        assert_eq!(*saves.borrow(), vec![(2, 0)]);
+        assert_eq!(*cleared.borrow(), 1);
+    }
+
+    #[test]
+    fn sync_rng() {
+        let map = Arc::new(MapData::new(4, 4, TerrainClass::Grass));
+
+        let grid = Grid::from_map(&map);
+        grid.start_futures();
+
+        let game = new_game(&map, &grid, 5);
+        let (saves, _) = spawn_sync(&game, &grid);
+
+        assert_eq!(game.rng.lock_ref().state(), 5);
+
+        game.rng.lock_mut().next_u64();
+
+        let state = game.rng.lock_ref().state();
+        assert_ne!(state, 5);
+
+        assert!(game.action(Action::EndTurn));
+        run_futures();
+
+        assert_eq!(*saves.borrow(), vec![(2, state)]);
+    }
+
+    #[test]
+    fn sync_active_grid() {
+        let map = Arc::new(MapData::new(4, 4, TerrainClass::Grass));
+
+        let first = Grid::from_map(&map);
+        let second = Grid::from_map(&map);
+        first.start_futures();
+
+        let game = new_game(&map, &first, 0);
+        let (saves, _) = spawn_sync(&game, &first);
+
+        game.add_pane(GridPane::new(second.clone()));
+        game.set_active_grid(&second);
+        run_futures();
+
+        // The turns are only saved for the active grid
+        first.events.publish(TurnEnded { turn: 2, save: Some(first.save_game(&map, SETTINGS, 2, 0)), winner: None });
+        second.events.publish(TurnEnded { turn: 3, save: Some(second.save_game(&map, SETTINGS, 3, 0)), winner: None });
+        run_futures();
+
+        assert_eq!(*saves.borrow(), vec![(3, 0)]);
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{
+    Node, Spritesheet, Size, Tile, ParentHeight, ColorRgb, GridSize,
+    SpriteAnimation, AnimationMode,
+};
+
+use crate::{UnitAppearance, Spritesheets};
+use crate::assets::{NationPalettes};
+use crate::ui::{Screen};
+use crate::grid::{Nation, Registry, UNIT_ANIMATION_TIME, UNIT_ANIMATION_FRAMES, BUILDING_ANIMATION_TIME};
+use crate::grid::unit::{Unit, UnitClass, UnitClassExt};
+use crate::grid::building::{Building, BuildingClass, BuildingClassExt};
+
+
+/// Debug screen which displays every unit and building, with every palette.
+///
+/// This is intended for artists, so they can check changes to the spritesheets.
+///
+/// Each unit row has a column for every [`Nation`], followed by the same nation after the unit has waited.
+///
+/// Each building row has a neutral column, a column for every [`Nation`], and a fog column.
+pub(crate) struct SpriteGallery {
+    appearance: Mutable<UnitAppearance>,
+    unit_small: Spritesheet,
+    unit_big: Spritesheet,
+    building: Spritesheet,
+}
+
+impl SpriteGallery {
+    const UNIT_COLUMNS: usize = 2 * Nation::ALL.len();
+    const BUILDING_COLUMNS: usize = Nation::ALL.len() + 2;
+
+    pub(crate) fn screen(appearance: Mutable<UnitAppearance>, spritesheets: &Spritesheets) -> Screen {
+        let this = Arc::new(Self {
+            appearance,
+            unit_small: spritesheets.unit_small.clone(),
+            unit_big: spritesheets.unit_big.clone(),
+            building: spritesheets.building.clone(),
+        });
+
+        Arc::new(move || Self::render(&this))
+    }
+
+    fn unit_spritesheet(&self) -> impl Signal<Item = Spritesheet> {
+        let unit_small = self.unit_small.clone();
+        let unit_big = self.unit_big.clone();
+
+        self.appearance.signal_ref(move |appearance| {
+            match appearance {
+                UnitAppearance::DualStrikeSmall => unit_small.clone(),
+                UnitAppearance::DualStrikeBig => unit_big.clone(),
+            }
+        })
+    }
+
+    fn unit_tile_size(&self) -> impl Signal<Item = u32> {
+        self.appearance.signal_ref(|appearance| appearance.unit_tile_size()).dedupe()
+    }
+
+    fn render_unit(this: &Arc<Self>, class: UnitClass, nation: Nation, waited: bool) -> Node {
+        let tile_y = class.tile_y(&nation);
+
+        engine::Sprite::builder()
+            .spritesheet_signal(this.unit_spritesheet())
+
+            .tile_signal(this.unit_tile_size().map(move |tile_size| {
+                let tile_y = tile_y * tile_size;
+
+                Tile {
+                    start_x: 0,
+                    start_y: tile_y,
+                    end_x: tile_size,
+                    end_y: tile_y + tile_size,
+                }
+            }))
+
+            // Units which have waited don't animate
+            .animation_signal(this.unit_tile_size().map(move |tile_size| {
+                if waited {
+                    None
+
+                } else {
+                    Some(SpriteAnimation {
+                        frames: UNIT_ANIMATION_FRAMES,
+                        duration: UNIT_ANIMATION_TIME as f32,
+                        offset_x: tile_size as i32,
+                        offset_y: 0,
+                        mode: AnimationMode::Pendulum,
+                    })
+                }
+            }))
+
+            // The gallery displays the built-in palettes
+            .palette(Unit::palette(&NationPalettes::default(), nation, waited))
+            .build()
+    }
+
+    fn render_building(this: &Arc<Self>, class: BuildingClass, nation: Option<Nation>, fog: bool) -> Node {
+        // Buildings such as missile silos don't have nation sprites
+        if nation.is_some() && !class.can_have_nation() {
+            return engine::Stack::builder().build();
+        }
+
+        let tile_x = if fog {
+            Building::TILE_WIDTH
+
+        } else if nation.is_some() {
+            2 * Building::TILE_WIDTH
+
+        } else {
+            0
+        };
+
+        let tile_y = class.tile_y();
+
+        engine::Sprite::builder()
+            .spritesheet(this.building.clone())
+
+            .tile(Tile {
+                start_x: tile_x,
+                start_y: tile_y,
+                end_x: tile_x + Building::TILE_WIDTH,
+                end_y: tile_y + Building::TILE_HEIGHT,
+            })
+
+            .animation(if !fog && nation.is_some() {
+                Some(SpriteAnimation {
+                    frames: 4,
+                    duration: BUILDING_ANIMATION_TIME as f32,
+                    offset_x: Building::TILE_WIDTH as i32,
+                    offset_y: 0,
+                    mode: AnimationMode::Loop,
+                })
+
+            } else {
+                None
+            })
+
+            .palette(Building::palette(&NationPalettes::default(), nation))
+            .build()
+    }
+
+    fn render(this: &Arc<Self>) -> Node {
+        let registry = Registry::get();
+
+        let unit_rows = registry.unit_classes().count() as f32;
+        let building_rows = registry.building_classes().count() as f32;
+
+        // Custom classes from mods are also displayed
+        let units = registry.unit_classes().flat_map(|class| {
+            [false, true].into_iter().flat_map(move |waited| {
+                Nation::ALL.iter().map(move |nation| (class, *nation, waited))
+            })
+        });
+
+        let buildings = registry.building_classes().flat_map(|class| {
+            let nations = std::iter::once(None)
+                .chain(Nation::ALL.iter().map(|nation| Some(*nation)))
+                .map(move |nation| (class, nation, false));
+
+            nations.chain(std::iter::once((class, None, true)))
+        });
+
+        engine::Stack::builder()
+            .child(engine::Rect::builder()
+                .color(ColorRgb { r: 0.15, g: 0.15, b: 0.2 })
+                .build())
+
+            .child(engine::Row::builder()
+                .child(engine::Grid::builder()
+                    .size(Size {
+                        width: ParentHeight(Self::UNIT_COLUMNS as f32 / unit_rows),
+                        height: ParentHeight(1.0),
+                    })
+                    .grid_size(GridSize {
+                        width: ParentHeight(1.0 / unit_rows),
+                        height: ParentHeight(1.0 / unit_rows),
+                    })
+                    .children(units.map(|(class, nation, waited)| {
+                        Self::render_unit(this, class, nation, waited)
+                    }))
+                    .build())
+
+                // Building sprites are twice as tall as they are wide
+                .child(engine::Grid::builder()
+                    .size(Size {
+                        width: ParentHeight(Self::BUILDING_COLUMNS as f32 / (2.0 * building_rows)),
+                        height: ParentHeight(1.0),
+                    })
+                    .grid_size(GridSize {
+                        width: ParentHeight(1.0 / (2.0 * building_rows)),
+                        height: ParentHeight(1.0 / building_rows),
+                    })
+                    .children(buildings.map(|(class, nation, fog)| {
+                        Self::render_building(this, class, nation, fog)
+                    }))
+                    .build())
+
+                .build())
+
+            .build()
+    }
+}
@@ -1,63 +1,107 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::future::Future;
+use futures_signals::map_ref;
 use futures_signals::signal::{Mutable, Signal, SignalExt};
 use futures_signals::signal_vec::{SignalVecExt};
 use dominator::clone;
 use rusted_battalions_engine as engine;
-use rusted_battalions_engine::{Node, Order};
+use rusted_battalions_engine::{Node, NodeRef, RealLocation, RealPosition, RealSize, Order, Size, Offset, Length, ParentWidth, ParentHeight, ScreenWidth, ScreenHeight};
 
 use crate::{Game};
 use crate::util::future::{FutureSpawner};
-use crate::util::signal::{SortedVec};
-
-use terrain::{Terrain, TerrainClass, Orientation, TerrainTile};
-use building::{Building, BuildingClass};
-use unit::{Unit, UnitClass};
-use explosion::{Explosion};
+use crate::util::signal::{SortedVec, timer};
+
+use terrain::{Terrain, TerrainClass, TerrainInfo, MovementClass, Orientation, TerrainTile};
+use building::{Building, BuildingClass, BuildingId};
+use unit::{Unit, UnitClass, UnitId, can_share_tile};
+use explosion::{Explosion, ExplosionPool};
+use popup::{Popup, PopupPool, sync_damage_popups};
+use camera::{Camera};
+use danger::{DangerZone, sync_danger_zone};
+use trap::{TrapAlert};
+use targeting::{Targeting};
+use stats::{MatchStats, sync_stats};
+use entity_index::{EntityIndex, sync_index};
+use clock::{LogicClock};
+use animation::{FrameAnimation, FrameMode};
+use map::{MapData};
+
+pub use rusted_battalions_game_core::{Nation};
+pub use rusted_battalions_game_core::events::{Events};
+pub use rusted_battalions_game_core::team::{Teams};
+pub use rusted_battalions_game_core::rules::{MatchRules, Funds, BuildError};
+pub use rusted_battalions_game_core::registry::{Registry, UnitSpec, BuildingSpec};
+
+pub use rusted_battalions_game_core::save::{SaveGame, SaveError};
+
+#[cfg(feature = "audit")]
+pub use rusted_battalions_game_core::audit::{StateSnapshot, UnitSnapshot, BuildingSnapshot};
+
+#[cfg(not(feature = "audit"))]
+use rusted_battalions_game_core::audit::{StateSnapshot, UnitSnapshot, BuildingSnapshot};
+
+use rusted_battalions_game_core::replay::{ReplaySettings};
 
 pub mod action;
 pub mod terrain;
 pub mod unit;
 pub mod building;
 pub mod explosion;
+pub mod popup;
+pub mod camera;
+pub mod danger;
+pub mod sidebar;
+pub mod tile_info;
+pub mod trap;
+pub mod targeting;
+pub mod stats;
+pub mod status;
+pub mod pane;
+pub mod animation;
+pub mod map;
+pub mod map_gen;
+mod clock;
+mod coord_index;
+mod entity_index;
 
 
 pub(crate) const UNIT_ANIMATION_TIME: f64 = 250.0;
+pub(crate) const UNIT_ANIMATION_FRAMES: u32 = 3;
 pub(crate) const EXPLOSION_ANIMATION_TIME: f64 = 500.0;
 pub(crate) const BUILDING_ANIMATION_TIME: f64 = 500.0;
 pub(crate) const TERRAIN_ANIMATION_TIME: f64 = 500.0;
 pub(crate) const FOG_ANIMATION_TIME: f64 = 1000.0;
+pub(crate) const TRAP_ANIMATION_TIME: f64 = 600.0;
+pub(crate) const MOVE_EFFECT_ANIMATION_TIME: f64 = 300.0;
+pub(crate) const BANNER_ANIMATION_TIME: f64 = 1500.0;
+pub(crate) const PORTRAIT_SLIDE_TIME: f64 = 300.0;
+pub(crate) const POWER_FADE_TIME: f64 = 400.0;
+pub(crate) const TARGET_PULSE_TIME: f64 = 800.0;
+pub(crate) const REFLECTION_ANIMATION_TIME: f64 = 2000.0;
+pub(crate) const POPUP_ANIMATION_TIME: f64 = 600.0;
+pub(crate) const VOLLEY_ANIMATION_TIME: f64 = 300.0;
+pub(crate) const VOLLEY_PAUSE_TIME: f64 = 150.0;
+
+// Size of each tile in the overlay spritesheet
+pub(crate) const OVERLAY_TILE_SIZE: u32 = 16;
 
 // Number of milliseconds to move 1 tile
 pub(crate) const UNIT_MOVE_TIME: f64 = 200.0;
 
+// Number of milliseconds for each step of the logic clock
+const LOGIC_STEP_TIME: f64 = 1000.0 / 120.0;
 
-fn lerp_f32(from: f32, to: f32, percent: f32) -> f32 {
-    ((1.0 - percent) * from) + (percent * to)
-}
-
+// Maximum number of milliseconds the logic clock can advance in a single frame
+const LOGIC_MAX_DELTA: f64 = 250.0;
 
-#[derive(Debug, Clone, Copy)]
-pub enum Nation {
-    OrangeStar,
-    BlueMoon,
-    GreenEarth,
-    YellowComet,
-    BlackHole,
-}
 
-impl Nation {
-    pub const ALL: &[Self] = &[
-        Self::OrangeStar,
-        Self::BlueMoon,
-        Self::GreenEarth,
-        Self::YellowComet,
-        Self::BlackHole,
-    ];
+fn lerp_f32(from: f32, to: f32, percent: f32) -> f32 {
+    ((1.0 - percent) * from) + (percent * to)
 }
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Coord {
     pub x: f32,
     pub y: f32,
@@ -70,6 +114,11 @@ impl Coord {
             y: lerp_f32(self.y, other.y, percent),
         }
     }
+
+    /// Returns the x / y of the tile which contains this coord.
+    fn tile(self) -> (i32, i32) {
+        (self.x.round() as i32, self.y.round() as i32)
+    }
 }
 
 
@@ -88,20 +137,89 @@ pub struct Grid {
 
     pub(crate) terrain: Terrain,
 
-    pub(crate) buildings: Vec<Arc<Building>>,
+    pub(crate) buildings: SortedVec<Building>,
+    building_index: Arc<Mutex<EntityIndex<Building>>>,
 
     pub(crate) units: SortedVec<Unit>,
+    unit_index: Arc<Mutex<EntityIndex<Unit>>>,
+
+    /// The id of the next unit which is created by [`new_unit`](Grid::new_unit).
+    ///
+    /// The ids are allocated in order, so that they are the same every time the match is played.
+    next_unit_id: AtomicU32,
+
+    pub(crate) explosions: ExplosionPool,
+
+    pub(crate) popups: PopupPool,
 
-    pub(crate) explosions: SortedVec<Explosion>,
+    pub(crate) trap_alerts: SortedVec<TrapAlert>,
 
+    /// The logic time, this is used for all of the animations and actions.
     pub(crate) time: Mutable<f64>,
+    clock: Mutex<LogicClock>,
+
+    /// How fast the game runs, `1.0` is normal speed, `2.0` is double speed, and `0.0` is paused.
+    pub time_scale: Mutable<f64>,
+
+    /// How many particle effects are displayed, from `0.0` (none) to `1.0` (all).
+    ///
+    /// This is set by the [`Game`] from its [`QualitySettings`](crate::QualitySettings).
+    pub(crate) particle_density: Mutable<f32>,
+
+    /// Idle animation frame which is shared by every unit, so that they all animate in lockstep.
+    pub(crate) unit_frame: Mutable<u32>,
+
+    pub camera: Camera,
+
+    /// The location of the grid from the most recent layout, see [`Grid::coord_to_screen`].
+    ///
+    /// If the grid is displayed in multiple panes, then the pane which is on top is used.
+    screen_location: Mutex<Option<RealLocation>>,
+
+    /// The tile which is under the mouse cursor, or `None` if the cursor isn't on the grid.
+    ///
+    /// This is updated by [`Game::hover`](crate::Game::hover).
+    pub cursor: Mutable<Option<Coord>>,
+
+    /// The nation which is controlled by the local player.
+    pub player: Mutable<Nation>,
+
+    /// Which nations are allied, allies share their vision and can't attack each other.
+    pub teams: Mutable<Teams>,
+
+    /// The funds and victory rules which were chosen in the lobby.
+    pub rules: Mutable<MatchRules>,
+
+    /// The funds of each nation, see [`Grid::start`].
+    pub funds: Mutable<Funds>,
+
+    /// The tiles which the enemy units can attack next turn.
+    pub danger_zone: Arc<DangerZone>,
+
+    /// The units which can be attacked, see [`Grid::start_targeting`].
+    pub targeting: Arc<Targeting>,
+
+    /// Damage dealt, units lost, etc. for each nation.
+    pub stats: Arc<MatchStats>,
+
+    /// Events which are published by the grid actions.
+    pub events: Events,
 
     spawner: FutureSpawner,
 }
 
 impl Grid {
     pub fn new(terrain: Terrain, buildings: Vec<Arc<Building>>, units: Vec<Arc<Unit>>) -> Arc<Self> {
-        Arc::new(Self {
+        // The indexes are populated immediately, so they can be used before the futures start
+        let building_index = EntityIndex::new(&buildings);
+        let unit_index = EntityIndex::new(&units);
+        let next_unit_id = units.iter().map(|unit| unit.id.get() + 1).max().unwrap_or(0);
+
+        let camera = Camera::new(terrain.width, terrain.height);
+        let teams = Mutable::new(Teams::new());
+        let danger_zone = DangerZone::new(terrain.width, terrain.height, teams.clone());
+
+        let grid = Arc::new(Self {
             screen_size: ScreenSize {
                 width: terrain.width * 32,
                 height: terrain.height * 32,
@@ -111,86 +229,499 @@ impl Grid {
             height: 1.0 / (terrain.height as f32),
 
             units: SortedVec::with_values(units),
-            explosions: SortedVec::new(),
-            buildings,
+            unit_index,
+            next_unit_id: AtomicU32::new(next_unit_id),
+            explosions: ExplosionPool::new(16),
+            popups: PopupPool::new(8),
+            trap_alerts: SortedVec::new(),
+            buildings: SortedVec::with_values(buildings),
+            building_index,
             terrain,
 
             time: Mutable::new(0.0),
+            clock: Mutex::new(LogicClock::new(LOGIC_STEP_TIME, LOGIC_MAX_DELTA)),
+            time_scale: Mutable::new(1.0),
+            particle_density: Mutable::new(1.0),
+            unit_frame: Mutable::new(0),
+
+            camera,
+            screen_location: Mutex::new(None),
+            cursor: Mutable::new(None),
+            player: Mutable::new(Nation::OrangeStar),
+            teams,
+            rules: Mutable::new(MatchRules::default()),
+            funds: Mutable::new(Funds::default()),
+            danger_zone,
+            targeting: Targeting::new(),
+            stats: MatchStats::new(),
+
+            events: Events::new(),
 
             spawner: FutureSpawner::new(),
+        });
+
+        if cfg!(debug_assertions) {
+            for unit in grid.units.lock_ref().iter() {
+                grid.debug_assert_occupancy(unit.coord.get());
+            }
+        }
+
+        grid.spawn_future(sync_index(&grid.units, &grid.unit_index));
+        grid.spawn_future(sync_index(&grid.buildings, &grid.building_index));
+        grid.spawn_future(sync_danger_zone(&grid));
+        grid.spawn_future(sync_damage_popups(&grid));
+        grid.spawn_future(sync_stats(&grid));
+
+        grid
+    }
+
+    /// Whether the nation is allied with the [`player`](Grid::player), the player is allied with themself.
+    pub fn is_ally(&self, nation: Nation) -> impl Signal<Item = bool> {
+        map_ref! {
+            let player = self.player.signal(),
+            let teams = self.teams.signal_cloned() => {
+                teams.are_allies(*player, nation)
+            }
+        }.dedupe()
+    }
+
+    /// Returns the team which has won, which is either the only team that still has units,
+    /// or the team which has reached the capture limit.
+    pub fn winning_team(&self) -> Option<u32> {
+        let teams = self.teams.lock_ref();
+
+        let units = self.units.lock_ref();
+
+        teams.winner(units.iter().map(|unit| unit.nation)).or_else(|| {
+            let buildings = self.buildings.lock_ref();
+
+            self.rules.lock_ref().capture_winner(&teams, buildings.iter().map(|building| building.nation.get()))
         })
     }
 
+    /// Records the gameplay state, this is compared between peers / replays to find nondeterminism.
+    ///
+    /// This should be called at the end of every turn, with the state of the match's [`Rng`](rusted_battalions_game_core::random::Rng).
+    #[cfg(feature = "audit")]
+    #[inline]
+    pub fn snapshot(&self, turn: u32, rng: u64) -> StateSnapshot {
+        self.state_snapshot(turn, rng)
+    }
 
-    /// Returns a Signal that will last for `duration` number of milliseconds.
+    fn state_snapshot(&self, turn: u32, rng: u64) -> StateSnapshot {
+        let units = self.units.lock_ref().iter().map(|unit| {
+            let coord = unit.coord.get();
+
+            UnitSnapshot {
+                id: unit.id.get(),
+                x: coord.x,
+                y: coord.y,
+                class: unit.class,
+                nation: unit.nation,
+                hp: unit.hp.get(),
+                fuel: unit.fuel.get(),
+                kills: unit.kills.get(),
+                rank: unit.rank.get(),
+                waited: unit.waited.get(),
+            }
+        }).collect();
+
+        let buildings = self.buildings.lock_ref().iter().map(|building| {
+            BuildingSnapshot {
+                id: building.id.get(),
+                x: building.coord.x as u32,
+                y: building.coord.y as u32,
+                class: building.class,
+                nation: building.nation.get(),
+            }
+        }).collect();
+
+        StateSnapshot::new(turn, rng, self.next_unit_id.load(Ordering::Relaxed), units, buildings)
+    }
+
+    /// Whether the unit can be built, banned units can't be built.
+    #[inline]
+    pub fn can_build(&self, class: UnitClass) -> bool {
+        self.rules.lock_ref().can_build(class)
+    }
+
+
+    /// Saves the state of the match, so that it can be resumed with [`from_save`](Grid::from_save).
     ///
-    /// The value of the Signal is the percentage of time from now until `duration`:
+    /// This should be called at the end of every turn, see [`autosave`](crate::autosave).
+    pub fn save_game(&self, map: &MapData, settings: ReplaySettings, turn: u32, rng: u64) -> SaveGame {
+        SaveGame::new(
+            map,
+            settings,
+            self.player.get(),
+            self.teams.get_cloned(),
+            self.rules.get_cloned(),
+            self.funds.get_cloned(),
+            self.state_snapshot(turn, rng),
+        )
+    }
+
+    /// Creates a grid with the terrain of the map, and the units and buildings of the save.
     ///
-    ///   0.0 = now
-    ///   1.0 = now + duration
+    /// The map must be the save's map, see [`SaveGame::find_map`].
+    pub fn from_save(map: &MapData, save: &SaveGame) -> Arc<Self> {
+        debug_assert_eq!(map.stable_hash(), save.map_hash);
+
+        let terrain = Terrain::from_map(map);
+
+        let buildings = save.snapshot.buildings.iter().map(|building| {
+            Building::new(BuildingId::new(building.id), Coord { x: building.x as f32, y: building.y as f32 }, building.class, building.nation)
+        }).collect();
+
+        let units = save.snapshot.units.iter().map(|snapshot| {
+            let unit = Unit::new(UnitId::new(snapshot.id), Coord { x: snapshot.x, y: snapshot.y }, snapshot.class, snapshot.nation);
+            unit.hp.set(snapshot.hp);
+            unit.fuel.set(snapshot.fuel);
+            unit.kills.set(snapshot.kills);
+            unit.rank.set(snapshot.rank);
+            unit.waited.set(snapshot.waited);
+            unit
+        }).collect();
+
+        let grid = Self::new(terrain, buildings, units);
+
+        grid.player.set(save.player);
+        grid.teams.set(save.teams.clone());
+        grid.rules.set(save.rules.clone());
+        grid.funds.set(save.funds.clone());
+
+        // Destroyed units don't free their ids, so the next id can be higher than the units which are left
+        grid.next_unit_id.fetch_max(save.snapshot.next_unit_id, Ordering::Relaxed);
+
+        grid
+    }
+
+    /// Creates a grid with the terrain and buildings of the map, without any units.
     ///
-    /// Once the Signal reaches 1.0 it will stop.
-    pub(crate) fn timer(&self, duration: f64) -> impl Signal<Item = f64> + Send {
-        struct TimerState {
-            start: f64,
-            end: f64,
+    /// The buildings are numbered in the order of [`MapData::buildings`], so they have the same ids every time.
+    pub fn from_map(map: &MapData) -> Arc<Self> {
+        let terrain = Terrain::from_map(map);
+
+        let buildings = map.buildings.iter().enumerate().map(|(index, building)| {
+            Building::new(BuildingId::new(index as u32), Coord { x: building.x as f32, y: building.y as f32 }, building.class, building.nation)
+        }).collect();
+
+        Self::new(terrain, buildings, vec![])
+    }
+
+    /// Creates a grid for a new match on the map, every player starts with an infantry on each of their bases.
+    pub fn new_match(map: &MapData) -> Arc<Self> {
+        let grid = Self::from_map(map);
+
+        for building in map.buildings.iter() {
+            match building.nation {
+                Some(nation) if building.class == BuildingClass::Base => {
+                    grid.place_unit(grid.new_unit(Coord { x: building.x as f32, y: building.y as f32 }, UnitClass::Infantry, nation));
+                },
+                _ => {},
+            }
         }
 
-        let mut state = None;
+        grid
+    }
 
-        self.time.signal_ref(move |time| {
-            let state = state.get_or_insert_with(|| {
-                TimerState {
-                    start: *time,
-                    end: time + duration,
-                }
-            });
+    /// Starts the match with the teams and rules which were chosen in the [`Lobby`](crate::lobby::Lobby).
+    ///
+    /// This is only called for a new match, a match which is resumed with [`from_save`](Grid::from_save) has already started.
+    ///
+    /// Every nation receives the starting funds, and the income for the first turn.
+    pub fn start(&self, teams: Teams, rules: MatchRules) {
+        self.teams.set(teams);
+        self.funds.set(Funds::new(rules.starting_funds));
+        self.rules.set(rules);
+        self.collect_income();
+    }
+
+    /// Every nation receives the [`income`](MatchRules::income) of the properties which it owns.
+    fn collect_income(&self) {
+        let rules = self.rules.lock_ref();
+        let buildings = self.buildings.lock_ref();
+        let mut funds = self.funds.lock_mut();
 
-            if *time >= state.end {
-                1.0
+        for nation in Nation::ALL {
+            let properties = buildings.iter()
+                .filter(|building| building.nation.get() == Some(*nation) && building.class.info().income)
+                .count();
 
-            } else {
-                (time - state.start) / duration
+            funds.add(*nation, rules.income_for(properties as u32));
+        }
+    }
+
+    /// Creates a unit with the next id, it isn't added to the grid until [`place_unit`](Grid::place_unit) is called.
+    pub fn new_unit(&self, coord: Coord, class: UnitClass, nation: Nation) -> Arc<Unit> {
+        let id = self.next_unit_id.fetch_add(1, Ordering::Relaxed);
+        Unit::new(UnitId::new(id), coord, class, nation)
+    }
+
+    /// Builds a unit on a base / airport / port which is owned by the nation, and spends its cost.
+    ///
+    /// The new unit has already waited, so it can't move until the next turn.
+    pub fn build_unit(&self, coord: Coord, class: UnitClass, nation: Nation) -> Result<Arc<Unit>, BuildError> {
+        let cost = match class.cost() {
+            Some(cost) if self.can_build(class) => cost,
+            _ => return Err(BuildError::Banned),
+        };
+
+        match self.building_at(coord) {
+            Some(building) if building.nation.get() == Some(nation) && building.class.spec().can_produce(class) => {},
+            _ => return Err(BuildError::NoFactory),
+        }
+
+        if !self.units_at(coord).is_empty() {
+            return Err(BuildError::Occupied);
+        }
+
+        {
+            let mut funds = self.funds.lock_mut();
+
+            if !funds.spend(nation, cost) {
+                return Err(BuildError::Funds { cost, funds: funds.get(nation) });
             }
-        }).stop_if(|value| *value == 1.0)
+        }
+
+        let unit = self.new_unit(coord, class, nation);
+        unit.waited.set(true);
+
+        let placed = self.place_unit(unit.clone());
+        debug_assert!(placed);
+
+        Ok(unit)
+    }
+
+    /// Updates the spatial index after a unit's coord has changed.
+    pub(crate) fn update_unit_coord(&self, unit: &Unit) {
+        self.unit_index.lock().unwrap().update_coord(unit);
+    }
+
+
+    /// Returns the unit with the given id, or `None` if it isn't on the grid.
+    pub fn unit(&self, id: UnitId) -> Option<Arc<Unit>> {
+        self.unit_index.lock().unwrap().get(id)
+    }
+
+    /// Returns the unit which is on the tile at `coord`.
+    ///
+    /// If a transport is carrying units then this might return any of them, use [`units_at`](Grid::units_at) instead.
+    pub fn unit_at(&self, coord: Coord) -> Option<Arc<Unit>> {
+        self.unit_index.lock().unwrap().at(coord)
+    }
+
+    /// Returns every unit which is on the tile at `coord`, this is more than one unit only if a transport is carrying units.
+    pub fn units_at(&self, coord: Coord) -> Vec<Arc<Unit>> {
+        self.unit_index.lock().unwrap().all_at(coord)
+    }
+
+    /// Whether the unit is allowed to stop on the tile at `coord`, see [`can_share_tile`].
+    ///
+    /// The unit itself is ignored, so this can be used for units which are already on the grid.
+    pub fn can_place(&self, unit: &Unit, coord: Coord) -> bool {
+        let mut units = self.units_at(coord).into_iter()
+            .filter(|other| other.id != unit.id)
+            .map(|other| (other.class, other.nation))
+            .collect::<Vec<_>>();
+
+        units.push((unit.class, unit.nation));
+
+        can_share_tile(&units)
+    }
+
+    /// Adds the unit to the grid, unless the tile is already occupied (see [`can_place`](Grid::can_place)).
+    ///
+    /// Returns `false` if the unit wasn't added. This should be used instead of inserting into [`units`](Grid::units).
+    pub fn place_unit(&self, unit: Arc<Unit>) -> bool {
+        let coord = unit.coord.get();
+
+        if !self.can_place(&unit, coord) {
+            tracing::warn!(?coord, class = ?unit.class, "Tile is already occupied");
+            return false;
+        }
+
+        // The index is updated immediately, so that placing another unit on the same tile fails
+        self.unit_index.lock().unwrap().insert(unit.clone());
+        self.units.insert(unit);
+        true
+    }
+
+    /// Panics in debug builds if the units on the tile aren't allowed to share it.
+    #[track_caller]
+    pub(crate) fn debug_assert_occupancy(&self, coord: Coord) {
+        if cfg!(debug_assertions) {
+            let units = self.units_at(coord).iter().map(|unit| (unit.class, unit.nation)).collect::<Vec<_>>();
+
+            assert!(can_share_tile(&units), "Units are stacked on tile {:?}: {:?}", coord.tile(), units);
+        }
+    }
+
+    /// Ends the current turn, so every unit can move again, and every nation receives its income.
+    pub fn end_turn(&self) {
+        for unit in self.units.lock_ref().iter() {
+            unit.waited.set_neq(false);
+        }
+
+        self.collect_income();
+    }
+
+    /// Returns the [`player`](Grid::player)'s next unit which hasn't waited yet, in order of [`UnitId`].
+    ///
+    /// After the last unit it wraps around to the first unit.
+    pub fn next_ready_unit(&self, current: Option<&Arc<Unit>>) -> Option<Arc<Unit>> {
+        let player = self.player.get();
+
+        let lock = self.units.lock_ref();
+
+        let mut ready = lock.iter()
+            .filter(|unit| unit.nation == player && !unit.waited.get())
+            .collect::<Vec<_>>();
+
+        ready.sort_by_key(|unit| unit.id);
+
+        ready.iter()
+            .find(|unit| current.map_or(true, |current| unit.id > current.id))
+            .or(ready.first())
+            .map(|unit| (*unit).clone())
+    }
+
+    /// Returns the units which are on the 4 tiles adjacent to `coord`.
+    pub fn units_adjacent(&self, coord: Coord) -> Vec<Arc<Unit>> {
+        self.unit_index.lock().unwrap().adjacent(coord)
+    }
+
+    /// Returns the building with the given id, or `None` if it isn't on the grid.
+    pub fn building(&self, id: BuildingId) -> Option<Arc<Building>> {
+        self.building_index.lock().unwrap().get(id)
+    }
+
+    /// Returns the building which is on the tile at `coord`.
+    pub fn building_at(&self, coord: Coord) -> Option<Arc<Building>> {
+        self.building_index.lock().unwrap().at(coord)
+    }
+
+    /// Returns the terrain of the tile which contains `coord`, or `None` if it is outside of the grid.
+    pub fn terrain_at(&self, coord: Coord) -> Option<TerrainClass> {
+        let (x, y) = coord.tile();
+
+        if x >= 0 && y >= 0 && (x as u32) < self.terrain.width && (y as u32) < self.terrain.height {
+            Some(self.terrain.get(x as u32, y as u32).class)
+
+        } else {
+            None
+        }
+    }
+
+    /// Returns the gameplay information of the tile which contains `coord`, or `None` if it is outside of the grid.
+    ///
+    /// If there is a building on the tile then the building's information is returned, instead of the terrain's.
+    pub fn terrain_info(&self, coord: Coord) -> Option<&'static TerrainInfo> {
+        let terrain = self.terrain_at(coord)?;
+
+        match self.building_at(coord) {
+            Some(building) => Some(building.class.info()),
+            None => Some(terrain.info()),
+        }
+    }
+
+    /// Returns how many movement points it costs for `class` to enter the tile at `coord`.
+    ///
+    /// Returns `None` if the tile is impassable for `class`, or if it is outside of the grid.
+    pub fn move_cost(&self, class: MovementClass, coord: Coord) -> Option<u32> {
+        self.terrain_info(coord)?.move_cost(class)
+    }
+
+
+    /// Whether the tile below `coord` is water, so units and buildings on `coord` are reflected in it.
+    pub(crate) fn is_water_below(&self, coord: Coord) -> bool {
+        self.terrain_at(Coord { x: coord.x, y: coord.y + 1.0 }).is_some_and(|class| class.is_water())
+    }
+
+    /// The alpha of the reflections, it slowly shimmers like the water is moving.
+    pub(crate) fn reflection_alpha(&self) -> impl Signal<Item = f32> {
+        self.animation(REFLECTION_ANIMATION_TIME).map(|time| {
+            0.3 + ((time * std::f64::consts::TAU).sin() * 0.05) as f32
+        })
+    }
+
+
+    /// Returns a Signal that will last for `duration` number of milliseconds.
+    ///
+    /// The value of the Signal is the percentage of time from now until `duration`:
+    ///
+    ///   0.0 = now
+    ///   1.0 = now + duration
+    ///
+    /// Once the Signal reaches 1.0 it will stop.
+    pub(crate) fn timer(&self, duration: f64) -> impl Signal<Item = f64> + Send {
+        timer(&self.time, duration)
     }
 
     pub(crate) fn animation(&self, duration: f64) -> impl Signal<Item = f64> {
         self.time.signal_ref(move |time| (time / duration))
     }
 
-    /// When it reaches the end of the frames, it starts again from the beginning.
-    pub(crate) fn animation_loop(&self, duration: f64, frames: u32) -> impl Signal<Item = u32> {
-        let modulo = frames as f64;
+    /// Returns the current frame of an animation which lasts forever, each frame lasts for `duration` milliseconds.
+    ///
+    /// The frame is based on the total grid time, so every animation with the same
+    /// settings is synchronized, and it automatically pauses when the grid is paused.
+    ///
+    /// Use [`FrameAnimation::phase`] to desynchronize animations.
+    pub fn frame_animation(&self, animation: FrameAnimation) -> impl Signal<Item = u32> + Send {
+        self.time.signal_ref(move |time| animation.frame(*time)).dedupe()
+    }
 
-        self.time.signal_ref(move |time| {
-            ((time / duration) % modulo) as u32
-        }).dedupe()
+    /// When it reaches the end of the frames, it starts again from the beginning.
+    ///
+    /// See [`frame_animation`](Grid::frame_animation).
+    pub fn animation_loop(&self, duration: f64, frames: u32) -> impl Signal<Item = u32> + Send {
+        self.frame_animation(FrameAnimation::new(duration, frames, FrameMode::Loop))
     }
 
     /// Behaves like a pendulum, oscillating between the start and end.
     ///
     /// When it reaches the end of the frames, it then reverses direction.
     /// When it reaches the start of the frames, it then reverses direction again.
-    pub(crate) fn animation_pendulum(&self, duration: f64, frames: u32) -> impl Signal<Item = u32> {
-        let frames = frames - 1;
-        let total_frames = frames * 2;
+    ///
+    /// See [`frame_animation`](Grid::frame_animation).
+    pub fn animation_pendulum(&self, duration: f64, frames: u32) -> impl Signal<Item = u32> + Send {
+        self.frame_animation(FrameAnimation::new(duration, frames, FrameMode::PingPong))
+    }
 
-        let modulo = total_frames as f64;
+    /// Plays the animation starting from now, instead of being synchronized with the grid time.
+    ///
+    /// With [`FrameMode::Once`] the Signal stops after the last frame has been displayed for
+    /// its full duration, so `for_each(...).await` can be used to wait for the animation to finish.
+    pub fn play_animation(&self, animation: FrameAnimation) -> impl Signal<Item = u32> + Send {
+        let mut start = None;
 
         self.time.signal_ref(move |time| {
-            let frame = ((time / duration) % modulo) as u32;
+            let start = *start.get_or_insert(*time);
+            let elapsed = time - start;
 
-            if frame > frames {
-                total_frames - frame
+            (animation.frame(elapsed), animation.is_finished(elapsed))
+        })
+        .stop_if(|(_, finished)| *finished)
+        .map(|(frame, _)| frame)
+        .dedupe()
+    }
 
-            } else {
-                frame
-            }
-        }).dedupe()
+    /// Plays the frames once, starting from now, see [`play_animation`](Grid::play_animation).
+    pub fn animation_once(&self, duration: f64, frames: u32) -> impl Signal<Item = u32> + Send {
+        self.play_animation(FrameAnimation::new(duration, frames, FrameMode::Once))
     }
 
 
+    /// Advances the logic time, this must be called every frame with the render time.
+    pub(crate) fn update_time(&self, render_time: f64) {
+        let time = self.clock.lock().unwrap().update(render_time, self.time_scale.get());
+        self.time.set_neq(time);
+
+        // This is only updated once per frame, instead of each unit having its own animation loop
+        self.unit_frame.set_neq(FrameAnimation::new(UNIT_ANIMATION_TIME, UNIT_ANIMATION_FRAMES, FrameMode::PingPong).frame(time));
+    }
+
     #[inline]
     pub(crate) fn start_futures(&self) {
         self.spawner.start();
@@ -210,6 +741,53 @@ impl Grid {
     }
 
 
+    /// The location of the grid on the screen, from the most recent layout of the [`GridPane`](pane::GridPane) which displayed it.
+    ///
+    /// If the grid hasn't been laid out yet then it uses the [`Camera`], the same as a pane which fills the screen.
+    fn screen_location(&self) -> RealLocation {
+        let location = *self.screen_location.lock().unwrap();
+
+        location.unwrap_or_else(|| {
+            let position = self.camera.position.get();
+            let viewport = self.camera.viewport.get();
+            let grid_size = self.camera.grid_size();
+
+            RealLocation {
+                position: RealPosition {
+                    x: -position.x / viewport.width,
+                    y: -position.y / viewport.height,
+                },
+                size: RealSize {
+                    width: grid_size.width / viewport.width,
+                    height: grid_size.height / viewport.height,
+                },
+                order: 0.0,
+            }
+        })
+    }
+
+    /// Converts a [`Coord`] into the position of the upper-left corner of its tile on the screen.
+    ///
+    /// This takes the offset / size of the [`GridPane`](pane::GridPane) and the [`Camera`] into account,
+    /// see [`GridPane::coord_to_screen`](pane::GridPane::coord_to_screen).
+    pub fn coord_to_screen(&self, coord: Coord) -> (Length, Length) {
+        let (x, y) = pane::location_coord_to_screen(&self.screen_location(), self.camera.grid_size(), coord);
+        (ScreenWidth(x), ScreenHeight(y))
+    }
+
+    /// Converts a pixel position on the screen into the [`Coord`] of the tile at that position, this is the inverse of [`Grid::coord_to_screen`].
+    ///
+    /// The pixels are relative to the [`screen_size`](Grid::screen_size).
+    ///
+    /// Returns `None` if the position is outside of the grid.
+    pub fn screen_to_coord(&self, x_px: i32, y_px: i32) -> Option<Coord> {
+        let x = x_px as f32 / self.screen_size.width as f32;
+        let y = y_px as f32 / self.screen_size.height as f32;
+
+        pane::location_screen_to_coord(&self.screen_location(), self.camera.grid_size(), x, y)
+    }
+
+    /// Position of the coord relative to the grid, this does not take the [`Camera`] into account.
     pub(crate) fn tile_offset(&self, coord: &Coord) -> (f32, f32) {
         (
             coord.x * self.width,
@@ -222,15 +800,66 @@ impl Grid {
     }
 
 
-    pub(crate) fn render(game: &Arc<Game>, this: &Arc<Self>) -> Node {
+    /// The `node_ref` receives the location of every tile of the grid, see [`GridPane::screen_to_coord`](pane::GridPane::screen_to_coord).
+    pub(crate) fn render(game: &Arc<Game>, this: &Arc<Self>, node_ref: &NodeRef) -> Node {
+        let grid_size = this.camera.grid_size();
+
         engine::Stack::builder()
+            .node_ref(node_ref)
+
+            // The grid is scaled so that the viewport fills the parent
+            .size_signal(this.camera.viewport.signal_ref(move |viewport| {
+                Size {
+                    width: ParentWidth(grid_size.width / viewport.width),
+                    height: ParentHeight(grid_size.height / viewport.height),
+                }
+            }))
+
+            .offset_signal(map_ref! {
+                let position = this.camera.position.signal(),
+                let viewport = this.camera.viewport.signal() => {
+                    Offset {
+                        x: ParentWidth(-position.x / viewport.width),
+                        y: ParentHeight(-position.y / viewport.height),
+                    }
+                }
+            })
+
             .children(this.terrain.iter().map(|tile| {
                 TerrainTile::render(game, this, tile)
             }))
 
-            .children(this.buildings.iter().map(|building| {
-                Building::render(game, this, building)
-            }))
+            .child(DangerZone::render(game, this, &this.danger_zone))
+
+            // Each child is keyed by its entry in the SortedVec, so inserting / removing
+            // an entity only creates / destroys that entity's Node, the siblings are kept.
+            // Reflections of the buildings and units in the water
+            .child(engine::Stack::builder()
+                .order(Order::Parent(0.0))
+                .visible_signal(game.quality.signal_ref(|quality| quality.reflections).dedupe())
+
+                .child(engine::Stack::builder()
+                    .order(Order::Parent(0.0))
+                    .children_signal_vec(this.buildings.signal_vec().map(clone!(game, this => move |building| {
+                        Building::render_reflection(&game, &this, &building)
+                    })))
+                    .build())
+
+                .child(engine::Stack::builder()
+                    .order(Order::Parent(0.0))
+                    .children_signal_vec(this.units.signal_vec().map(clone!(game, this => move |unit| {
+                        Unit::render_reflection(&game, &this, &unit)
+                    })))
+                    .build())
+
+                .build())
+
+            .child(engine::Stack::builder()
+                .order(Order::Parent(0.0))
+                .children_signal_vec(this.buildings.signal_vec().map(clone!(game, this => move |building| {
+                    Building::render(&game, &this, &building)
+                })))
+                .build())
 
             .child(engine::Stack::builder()
                 .order(Order::Parent(0.0))
@@ -239,6 +868,14 @@ impl Grid {
                 })))
                 .build())
 
+            // Rank badges
+            .child(engine::Stack::builder()
+                .order(Order::Parent(0.0))
+                .children_signal_vec(this.units.signal_vec().map(clone!(game, this => move |unit| {
+                    Unit::render_badge(&game, &this, &unit)
+                })))
+                .build())
+
             .child(engine::Stack::builder()
                 .order(Order::Parent(0.0))
                 .children_signal_vec(this.explosions.signal_vec().map(clone!(game, this => move |explosion| {
@@ -246,6 +883,22 @@ impl Grid {
                 })))
                 .build())
 
+            .child(engine::Stack::builder()
+                .order(Order::Parent(0.0))
+                .children_signal_vec(this.popups.signal_vec().map(clone!(game, this => move |popup| {
+                    Popup::render(&game, &this, &popup)
+                })))
+                .build())
+
+            .child(engine::Stack::builder()
+                .order(Order::Parent(0.0))
+                .children_signal_vec(this.trap_alerts.signal_vec().map(clone!(game, this => move |alert| {
+                    TrapAlert::render(&game, &this, &alert)
+                })))
+                .build())
+
+            .child(Targeting::render(game, this, &this.targeting))
+
             .build()
     }
 
@@ -351,13 +1004,13 @@ impl Grid {
         }
 
         let mut tiles = vec![
-            (0, 0, TerrainClass::random_mountain()),
-            (0, 1, TerrainClass::random_mountain()),
-            (0, 2, TerrainClass::random_mountain()),
+            (0, 0, terrain::random_mountain()),
+            (0, 1, terrain::random_mountain()),
+            (0, 2, terrain::random_mountain()),
             (1, 1, TerrainClass::Forest),
-            (1, 2, TerrainClass::random_mountain()),
-            (0, 3, TerrainClass::random_mountain()),
-            (1, 3, TerrainClass::random_mountain()),
+            (1, 2, terrain::random_mountain()),
+            (0, 3, terrain::random_mountain()),
+            (1, 3, terrain::random_mountain()),
 
             (0, 4, TerrainClass::Forest),
             (1, 4, TerrainClass::Forest),
@@ -492,36 +1145,56 @@ impl Grid {
         terrain.update_tiles();
 
         units.push(Unit::new(
+            UnitId::new(0),
             Coord { x: 13.0, y: 11.0 },
             UnitClass::Infantry,
             Nation::OrangeStar,
         ));
 
         units.push(Unit::new(
+            UnitId::new(1),
             Coord { x: 0.0, y: 17.0 },
             UnitClass::Infantry,
             Nation::OrangeStar,
         ));
 
         units.push(Unit::new(
+            UnitId::new(2),
             Coord { x: 1.0, y: 5.0 },
             UnitClass::Infantry,
             Nation::OrangeStar,
         ));
 
         units.push(Unit::new(
+            UnitId::new(3),
             Coord { x: 0.0, y: 4.0 },
             UnitClass::Infantry,
             Nation::OrangeStar,
         ));
 
         units.push(Unit::new(
+            UnitId::new(4),
             Coord { x: 0.0, y: 2.0 },
             UnitClass::Infantry,
             Nation::OrangeStar,
         ));
 
+        units.push(Unit::new(
+            UnitId::new(5),
+            Coord { x: 16.0, y: 12.0 },
+            UnitClass::Tank,
+            Nation::BlueMoon,
+        ));
+
+        units.push(Unit::new(
+            UnitId::new(6),
+            Coord { x: 18.0, y: 15.0 },
+            UnitClass::Artillery,
+            Nation::BlueMoon,
+        ));
+
         buildings.push(Building::new(
+            BuildingId::new(0),
             Coord { x: 0.0, y: 17.0 },
             BuildingClass::City,
             Some(Nation::OrangeStar),
@@ -561,6 +1234,7 @@ impl Grid {
             for y in 0..terrain.height {
                 for x in 0..terrain.width {
                     units.push(Unit::new(
+                        UnitId::new(index as u32),
                         Coord { x: x as f32, y: y as f32 },
                         UnitClass::ALL[(index + i) % UnitClass::ALL.len()],
                         Nation::ALL[(index + i) % Nation::ALL.len()],
@@ -574,3 +1248,184 @@ impl Grid {
         Self::new(terrain, vec![], units)
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, Coord, Nation, Teams, MatchRules, BuildError};
+    use super::map::{MapData, MapBuilding};
+    use super::building::{BuildingClass, BuildingId};
+    use super::terrain::{TerrainClass};
+    use super::unit::{UnitClass, UnitId};
+    use rusted_battalions_game_core::replay::{ReplaySettings};
+    use rusted_battalions_game_core::{Weather};
+    use rusted_battalions_engine::{Length};
+
+    fn ids(grid: &Grid) -> (Vec<(UnitId, Coord)>, Vec<(BuildingId, Coord)>) {
+        let mut units = grid.units.lock_ref().iter().map(|unit| (unit.id, unit.coord.get())).collect::<Vec<_>>();
+        let mut buildings = grid.buildings.lock_ref().iter().map(|building| (building.id, building.coord)).collect::<Vec<_>>();
+
+        units.sort_by_key(|(id, _)| *id);
+        buildings.sort_by_key(|(id, _)| *id);
+
+        (units, buildings)
+    }
+
+    #[test]
+    fn deterministic_ids() {
+        let mut map = MapData::new(4, 4, TerrainClass::Grass);
+
+        map.buildings.push(MapBuilding { x: 0, y: 0, class: BuildingClass::Base, nation: Some(Nation::OrangeStar) });
+        map.buildings.push(MapBuilding { x: 1, y: 1, class: BuildingClass::City, nation: None });
+        map.buildings.push(MapBuilding { x: 3, y: 3, class: BuildingClass::Base, nation: Some(Nation::BlueMoon) });
+
+        let a = Grid::new_match(&map);
+        let b = Grid::new_match(&map);
+
+        assert_eq!(ids(&a), ids(&b));
+        assert_eq!(ids(&a).0.iter().map(|(id, _)| id.get()).collect::<Vec<_>>(), vec![0, 1]);
+
+        // Ids aren't reused, even if the unit is never placed
+        a.new_unit(Coord { x: 2.0, y: 2.0 }, UnitClass::Tank, Nation::OrangeStar);
+
+        let save = a.save_game(&map, ReplaySettings {
+            fog: false,
+            weather: Weather::Clear,
+            starting_funds: 0,
+            income: 1000,
+        }, 1, 0);
+
+        let resumed = Grid::from_save(&map, &save);
+
+        assert_eq!(ids(&resumed), ids(&a));
+        assert_eq!(resumed.new_unit(Coord { x: 2.0, y: 2.0 }, UnitClass::Tank, Nation::OrangeStar).id, UnitId::new(3));
+        assert_eq!(b.new_unit(Coord { x: 2.0, y: 2.0 }, UnitClass::Tank, Nation::OrangeStar).id, UnitId::new(2));
+    }
+
+    #[test]
+    fn save_rules() {
+        let mut map = MapData::new(4, 4, TerrainClass::Grass);
+        map.buildings.push(MapBuilding { x: 0, y: 0, class: BuildingClass::City, nation: Some(Nation::BlueMoon) });
+
+        let grid = Grid::new_match(&map);
+
+        let mut teams = Teams::new();
+        teams.set_team(Nation::YellowComet, teams.team(Nation::BlueMoon));
+
+        grid.player.set(Nation::YellowComet);
+        grid.start(teams.clone(), MatchRules {
+            starting_funds: 2000,
+            income: 500,
+            capture_limit: Some(3),
+            banned_units: vec![UnitClass::Bomber],
+        });
+
+        let save = grid.save_game(&map, ReplaySettings {
+            fog: false,
+            weather: Weather::Clear,
+            starting_funds: 2000,
+            income: 500,
+        }, 1, 0);
+
+        let resumed = Grid::from_save(&map, &save);
+
+        assert_eq!(resumed.player.get(), Nation::YellowComet);
+        assert_eq!(*resumed.teams.lock_ref(), teams);
+        assert_eq!(*resumed.rules.lock_ref(), *grid.rules.lock_ref());
+        assert_eq!(resumed.funds.lock_ref().get(Nation::BlueMoon), 2500);
+        assert!(!resumed.can_build(UnitClass::Bomber));
+    }
+
+    #[test]
+    fn income() {
+        let mut map = MapData::new(4, 4, TerrainClass::Grass);
+
+        map.buildings.push(MapBuilding { x: 0, y: 0, class: BuildingClass::Base, nation: Some(Nation::OrangeStar) });
+        map.buildings.push(MapBuilding { x: 1, y: 0, class: BuildingClass::City, nation: Some(Nation::OrangeStar) });
+        map.buildings.push(MapBuilding { x: 1, y: 1, class: BuildingClass::City, nation: None });
+        map.buildings.push(MapBuilding { x: 2, y: 2, class: BuildingClass::MissileSilo, nation: None });
+        map.buildings.push(MapBuilding { x: 3, y: 3, class: BuildingClass::Base, nation: Some(Nation::BlueMoon) });
+
+        let grid = Grid::new_match(&map);
+
+        grid.start(Teams::new(), MatchRules {
+            starting_funds: 5000,
+            income: 1000,
+            ..MatchRules::default()
+        });
+
+        assert_eq!(grid.funds.lock_ref().get(Nation::OrangeStar), 7000);
+        assert_eq!(grid.funds.lock_ref().get(Nation::BlueMoon), 6000);
+        assert_eq!(grid.funds.lock_ref().get(Nation::GreenEarth), 5000);
+
+        grid.end_turn();
+
+        assert_eq!(grid.funds.lock_ref().get(Nation::OrangeStar), 9000);
+        assert_eq!(grid.funds.lock_ref().get(Nation::BlueMoon), 7000);
+        assert_eq!(grid.funds.lock_ref().get(Nation::GreenEarth), 5000);
+    }
+
+    #[test]
+    fn build_unit() {
+        let mut map = MapData::new(4, 4, TerrainClass::Grass);
+
+        map.buildings.push(MapBuilding { x: 0, y: 0, class: BuildingClass::Base, nation: Some(Nation::OrangeStar) });
+        map.buildings.push(MapBuilding { x: 1, y: 0, class: BuildingClass::Base, nation: Some(Nation::BlueMoon) });
+        map.buildings.push(MapBuilding { x: 2, y: 0, class: BuildingClass::Airport, nation: Some(Nation::OrangeStar) });
+        map.buildings.push(MapBuilding { x: 3, y: 0, class: BuildingClass::Base, nation: Some(Nation::OrangeStar) });
+
+        let grid = Grid::from_map(&map);
+
+        grid.start(Teams::new(), MatchRules {
+            starting_funds: 10000,
+            income: 0,
+            banned_units: vec![UnitClass::Tank],
+            ..MatchRules::default()
+        });
+
+        let base = Coord { x: 0.0, y: 0.0 };
+
+        assert_eq!(grid.build_unit(base, UnitClass::Tank, Nation::OrangeStar).err(), Some(BuildError::Banned));
+        assert_eq!(grid.build_unit(base, UnitClass::Oozium, Nation::OrangeStar).err(), Some(BuildError::Banned));
+        assert_eq!(grid.build_unit(Coord { x: 1.0, y: 0.0 }, UnitClass::Infantry, Nation::OrangeStar).err(), Some(BuildError::NoFactory));
+        assert_eq!(grid.build_unit(Coord { x: 2.0, y: 0.0 }, UnitClass::Infantry, Nation::OrangeStar).err(), Some(BuildError::NoFactory));
+        assert_eq!(grid.build_unit(Coord { x: 0.0, y: 1.0 }, UnitClass::Infantry, Nation::OrangeStar).err(), Some(BuildError::NoFactory));
+        assert_eq!(grid.funds.lock_ref().get(Nation::OrangeStar), 10000);
+
+        let unit = grid.build_unit(base, UnitClass::Artillery, Nation::OrangeStar).unwrap();
+
+        assert_eq!(unit.class, UnitClass::Artillery);
+        assert!(unit.waited.get());
+        assert_eq!(grid.unit_at(base).map(|unit| unit.id), Some(unit.id));
+        assert_eq!(grid.funds.lock_ref().get(Nation::OrangeStar), 4000);
+
+        assert_eq!(grid.build_unit(base, UnitClass::Infantry, Nation::OrangeStar).err(), Some(BuildError::Occupied));
+        assert_eq!(grid.build_unit(Coord { x: 3.0, y: 0.0 }, UnitClass::MediumTank, Nation::OrangeStar).err(), Some(BuildError::Funds { cost: 16000, funds: 4000 }));
+        assert!(grid.build_unit(Coord { x: 3.0, y: 0.0 }, UnitClass::Recon, Nation::OrangeStar).is_ok());
+        assert_eq!(grid.funds.lock_ref().get(Nation::OrangeStar), 0);
+    }
+
+    #[test]
+    fn screen_to_coord() {
+        let grid = Grid::from_map(&MapData::new(4, 4, TerrainClass::Grass));
+
+        // Before the grid is laid out, it is the same as a pane which fills the screen
+        for y in 0..4 {
+            for x in 0..4 {
+                let coord = Coord { x: x as f32, y: y as f32 };
+
+                let (x_px, y_px) = match grid.coord_to_screen(coord) {
+                    (Length::ScreenWidth(x), Length::ScreenHeight(y)) => {
+                        ((x * grid.screen_size.width as f32) as i32, (y * grid.screen_size.height as f32) as i32)
+                    },
+                    lengths => panic!("Invalid lengths {:?}", lengths),
+                };
+
+                assert_eq!(grid.screen_to_coord(x_px + 1, y_px + 1), Some(coord));
+            }
+        }
+
+        assert_eq!(grid.screen_to_coord(-1, 0), None);
+        assert_eq!(grid.screen_to_coord(0, grid.screen_size.height as i32), None);
+    }
+}
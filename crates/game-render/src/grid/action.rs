@@ -1,45 +1,155 @@
 use std::sync::Arc;
 use std::future::Future;
-use futures_signals::signal::{SignalExt};
+use futures::future::{join, select};
+use futures::stream::StreamExt;
+use futures_signals::signal::{Signal, SignalExt};
 use dominator::clone;
+use tracing::Instrument;
 
-use crate::grid::{EXPLOSION_ANIMATION_TIME, UNIT_MOVE_TIME, Grid, Coord};
-use crate::grid::unit::{Unit, UnitAnimation};
-use crate::grid::explosion::{Explosion, ExplosionAnimation};
+use crate::grid::{VOLLEY_ANIMATION_TIME, VOLLEY_PAUSE_TIME, UNIT_MOVE_TIME, TRAP_ANIMATION_TIME, Grid, Coord};
+use crate::grid::trap::{TrapAlert};
+use crate::grid::unit::{Unit, UnitId, UnitAnimation};
+use crate::grid::explosion::{ExplosionAnimation, MoveEffect, EffectKind, Effect};
+use crate::grid::unit::{UnitClassExt};
 
+pub use rusted_battalions_game_core::action::{MoveDirection};
+use rusted_battalions_game_core::action::{TileOccupant, PathEnd, resolve_path};
+use rusted_battalions_game_core::replay::{Replay, ReplayAction};
 
-#[derive(Debug, Clone, Copy)]
-pub enum MoveDirection {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
-impl MoveDirection {
-    fn end(self, mut start: Coord, length: f32) -> Coord {
-        match self {
-            Self::Up => start.y -= length,
-            Self::Down => start.y += length,
-            Self::Left => start.x -= length,
-            Self::Right => start.x += length,
-        }
 
-        start
+fn move_end(direction: MoveDirection, mut start: Coord, length: f32) -> Coord {
+    match direction {
+        MoveDirection::Up => start.y -= length,
+        MoveDirection::Down => start.y += length,
+        MoveDirection::Left => start.x -= length,
+        MoveDirection::Right => start.x += length,
     }
 
-    fn animation(self) -> UnitAnimation {
-        match self {
-            Self::Up => UnitAnimation::Up,
-            Self::Down => UnitAnimation::Down,
-            Self::Left => UnitAnimation::Left,
-            Self::Right => UnitAnimation::Right,
-        }
+    start
+}
+
+fn move_animation(direction: MoveDirection) -> UnitAnimation {
+    match direction {
+        MoveDirection::Up => UnitAnimation::Up,
+        MoveDirection::Down => UnitAnimation::Down,
+        MoveDirection::Left => UnitAnimation::Left,
+        MoveDirection::Right => UnitAnimation::Right,
     }
 }
 
 
+/// Published when a unit finishes moving.
+#[derive(Clone)]
+pub struct UnitMoved {
+    pub unit: Arc<Unit>,
+    pub from: Coord,
+    pub to: Coord,
+}
+
+/// Published when a unit is stopped by a unit which was hidden in fog.
+#[derive(Clone)]
+pub struct UnitTrapped {
+    pub unit: Arc<Unit>,
+    pub trapped_by: Arc<Unit>,
+    pub coord: Coord,
+}
+
+/// Published when a unit loses health, such as from an attack.
+#[derive(Clone)]
+pub struct UnitDamaged {
+    pub unit: Arc<Unit>,
+
+    /// The unit which caused the damage, this is `None` for damage which doesn't come from a unit.
+    pub attacker: Option<Arc<Unit>>,
+
+    pub coord: Coord,
+    pub from_hp: u32,
+    pub to_hp: u32,
+}
+
+/// Published when a unit is destroyed, before the explosion animation plays.
+#[derive(Clone)]
+pub struct UnitDestroyed {
+    pub unit: Arc<Unit>,
+    pub coord: Coord,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveResult {
+    /// The unit moved along the entire path.
+    Finished,
+
+    /// The path was blocked by a unit which isn't allied, so the unit stopped early.
+    Trapped {
+        /// The coord where the unit stopped.
+        coord: Coord,
+    },
+
+    /// The path entered terrain which the unit can't move through, or it cost
+    /// more movement points or fuel than the unit has, so the unit stopped early.
+    Blocked {
+        /// The coord where the unit stopped.
+        coord: Coord,
+    },
+}
+
+
 impl Grid {
+    /// Plays the actions of the replay, each action is played at its recorded time.
+    ///
+    /// The grid must be created from the replay's map, see [`Replay::find_map`].
+    pub fn play_replay(self: &Arc<Self>, replay: &Replay) -> impl Future<Output = ()> + Send {
+        let grid = self.clone();
+        let events = replay.events.clone();
+
+        async move {
+            let start = grid.time.get();
+
+            for event in events {
+                let remaining = (start + event.time) - grid.time.get();
+
+                if remaining > 0.0 {
+                    grid.wait(remaining).await;
+                }
+
+                match event.action {
+                    ReplayAction::MovePath { unit, path } => {
+                        if let Some(unit) = grid.replay_unit(unit) {
+                            grid.move_path(&unit, path).await;
+                        }
+                    },
+
+                    ReplayAction::DestroyUnit { unit } => {
+                        if let Some(unit) = grid.replay_unit(unit) {
+                            grid.destroy_unit(&unit).await;
+                        }
+                    },
+
+                    ReplayAction::SetRank { unit, rank } => {
+                        if let Some(unit) = grid.replay_unit(unit) {
+                            unit.rank.set_neq(rank);
+                        }
+                    },
+
+                    ReplayAction::EndTurn => {
+                        grid.end_turn();
+                    },
+                }
+            }
+        }
+    }
+
+    fn replay_unit(&self, id: u32) -> Option<Arc<Unit>> {
+        let unit = self.unit(UnitId::new(id));
+
+        if unit.is_none() {
+            tracing::warn!(id, "Replay action is missing unit");
+        }
+
+        unit
+    }
+
     pub fn wait(self: &Arc<Self>, duration: f64) -> impl Future<Output = ()> + Send {
         let timer = self.timer(duration);
 
@@ -49,45 +159,326 @@ impl Grid {
     }
 
 
+    /// Smoothly pans the camera so that `coord` is visible.
+    ///
+    /// It uses the [`AutoPan`](crate::grid::camera::AutoPan) settings of the camera.
+    pub fn pan_to(self: &Arc<Self>, coord: Coord) -> impl Future<Output = ()> + Send {
+        let grid = self.clone();
+
+        async move {
+            let start = grid.camera.position.get();
+            let end = grid.camera.target(coord);
+
+            let distance = (end.x - start.x).abs().max((end.y - start.y).abs());
+
+            if distance > 0.0 {
+                let speed = grid.camera.auto_pan.get().speed;
+
+                grid.timer(((distance / speed) as f64) * 1000.0)
+                    .for_each(clone!(grid => move |percent| {
+                        grid.camera.position.set(start.lerp(end, percent as f32));
+                        async {}
+                    })).await;
+            }
+        }.instrument(tracing::debug_span!("pan_to", ?coord))
+    }
+
+    /// Pans the camera whenever the Signal's coord gets close to the edge of the viewport.
+    ///
+    /// This is used to keep the cursor visible.
+    pub fn follow<S>(self: &Arc<Self>, coord: S) -> impl Future<Output = ()> + Send
+        where S: Signal<Item = Coord> + Send + 'static {
+
+        let grid = self.clone();
+
+        async move {
+            // Signals skip intermediate values, so after a pan finishes it pans to the latest coord
+            coord.for_each(move |coord| grid.pan_to(coord)).await;
+        }
+    }
+
+
     pub fn move_unit(self: &Arc<Self>, unit: &Arc<Unit>, direction: MoveDirection, length: f32) -> impl Future<Output = ()> + Send {
         let grid = self.clone();
         let unit = unit.clone();
 
         async move {
             let start = unit.coord.get();
-            let end = direction.end(start, length);
+            let end = move_end(direction, start, length);
 
-            unit.animation.set_neq(direction.animation());
+            unit.animation.set_neq(move_animation(direction));
 
-            grid.timer((length as f64) * UNIT_MOVE_TIME)
-                .for_each(clone!(unit => move |percent| {
-                    unit.coord.set(start.lerp(end, percent as f32));
-                    async {}
-                })).await;
+            let mut tile = start.tile();
+
+            // With a lower density the effect is only left behind on some of the tiles
+            let density = grid.particle_density.get();
+            let mut particles = 0.0;
+
+            // Pans the camera at the same time so the unit doesn't leave the viewport
+            join(
+                grid.pan_to(end),
+
+                grid.timer((length as f64) * UNIT_MOVE_TIME)
+                    .for_each(clone!(grid, unit => move |percent| {
+                        let coord = start.lerp(end, percent as f32);
+
+                        let new_tile = coord.tile();
+
+                        // Leaves behind an effect on the tile which the unit just moved off of
+                        if new_tile != tile {
+                            let old_tile = Coord { x: tile.0 as f32, y: tile.1 as f32 };
+
+                            if let Some(effect) = grid.terrain_at(old_tile).and_then(|terrain| unit.class.move_effect(terrain)) {
+                                particles += density;
+
+                                if particles >= 1.0 {
+                                    particles -= 1.0;
+                                    grid.play_unit_effect(&unit, old_tile, effect);
+                                }
+                            }
+
+                            tile = new_tile;
+                        }
+
+                        unit.coord.set(coord);
+                        async {}
+                    })),
+            ).await;
 
             unit.animation.set_neq(UnitAnimation::Idle);
-        }
+
+            grid.update_unit_coord(&unit);
+
+            grid.events.publish(UnitMoved {
+                unit,
+                from: start,
+                to: end,
+            });
+        }.instrument(tracing::debug_span!("move_unit", ?direction, length))
     }
 
 
-    pub fn explosion(self: &Arc<Self>, animation: ExplosionAnimation, coord: Coord) -> impl Future<Output = ()> + Send {
+    /// Moves the unit along the path, one tile per step.
+    ///
+    /// The path is planned with only the visible units, so before moving each step is checked
+    /// against every unit (including the units hidden in fog):
+    ///
+    /// * The unit can move through allied units, but it cannot stop on them,
+    ///   unless it is a transport which can carry the unit (see [`Grid::can_place`]).
+    ///
+    /// * If the path runs into a unit which isn't allied then the unit is trapped: it stops at the last free tile
+    ///   and an exclamation mark is displayed.
+    ///
+    /// The path is also checked against the terrain (see [`Grid::move_cost`]), if a tile is impassable
+    /// or the unit runs out of movement points or fuel then it stops at the last free tile before it.
+    ///
+    /// Each step uses `1` fuel, and afterwards the unit has waited until the next turn.
+    /// The path is resolved with [`resolve_path`], which is also used by the server.
+    pub fn move_path(self: &Arc<Self>, unit: &Arc<Unit>, path: Vec<MoveDirection>) -> impl Future<Output = MoveResult> + Send {
+        let grid = self.clone();
+        let unit = unit.clone();
+        let steps = path.len();
+
+        async move {
+            let class = unit.class.movement_class();
+            let (x, y) = unit.coord.get().tile();
+
+            let resolved = resolve_path((x as i64, y as i64), unit.class.movement(), unit.fuel.get(), &path, |(x, y)| {
+                let coord = Coord { x: x as f32, y: y as f32 };
+
+                let others = grid.units_at(coord).into_iter()
+                    .filter(|other| other.id != unit.id)
+                    .map(|other| (other.class, other.nation))
+                    .collect::<Vec<_>>();
+
+                let occupant = TileOccupant::new((unit.class, unit.nation), &others, &grid.teams.lock_ref());
+
+                (grid.move_cost(class, coord), occupant)
+            });
+
+            let trapped_by = match resolved.end {
+                PathEnd::Trapped { index } => {
+                    let coord = path[..=index].iter().fold(unit.coord.get(), |coord, direction| move_end(*direction, coord, 1.0));
+                    grid.unit_at(coord)
+                },
+                _ => None,
+            };
+
+            for direction in &path[..resolved.steps] {
+                grid.move_unit(&unit, *direction, 1.0).await;
+            }
+
+            unit.fuel.set(unit.fuel.get() - resolved.steps as u32);
+            unit.waited.set_neq(true);
+
+            grid.debug_assert_occupancy(unit.coord.get());
+
+            if let Some(trapped_by) = trapped_by {
+                let coord = unit.coord.get();
+
+                grid.events.publish(UnitTrapped {
+                    unit: unit.clone(),
+                    trapped_by,
+                    coord,
+                });
+
+                grid.trap_alert(coord).await;
+
+                MoveResult::Trapped { coord }
+
+            } else if let PathEnd::Blocked { .. } = resolved.end {
+                MoveResult::Blocked { coord: unit.coord.get() }
+
+            } else {
+                MoveResult::Finished
+            }
+        }.instrument(tracing::debug_span!("move_path", steps))
+    }
+
+
+    /// Displays an exclamation mark above the coord.
+    pub fn trap_alert(self: &Arc<Self>, coord: Coord) -> impl Future<Output = ()> + Send {
         let grid = self.clone();
 
         async move {
-            let explosion = Explosion::new(coord, animation);
+            let alert = TrapAlert::new(coord);
 
-            grid.explosions.insert(explosion.clone());
+            grid.trap_alerts.insert(alert.clone());
 
-            grid.timer(EXPLOSION_ANIMATION_TIME)
-                .for_each(clone!(explosion => move |percent| {
-                    explosion.percent.set(percent as f32);
+            grid.timer(TRAP_ANIMATION_TIME)
+                .for_each(clone!(alert => move |percent| {
+                    alert.percent.set(percent as f32);
                     async {}
                 })).await;
 
-            grid.explosions.remove(&explosion);
+            grid.trap_alerts.remove(&alert);
+        }.instrument(tracing::debug_span!("trap_alert", ?coord))
+    }
+
+
+    /// Acquires the effects immediately, and returns a Future which animates them and then releases them.
+    ///
+    /// The effects share a single timer which lasts as long as the longest effect, so they stay synchronized.
+    fn animate_effects(self: &Arc<Self>, effects: Vec<(EffectKind, Coord)>, handle: &Effect) -> impl Future<Output = ()> + 'static {
+        let grid = self.clone();
+        let handle = handle.clone();
+
+        let explosions = effects.into_iter()
+            .map(|(kind, coord)| (grid.explosions.acquire(coord, kind), kind.duration()))
+            .collect::<Vec<_>>();
+
+        let duration = explosions.iter().fold(0.0, |duration, (_, time)| f64::max(duration, *time));
+
+        async move {
+            let timer = grid.timer(duration)
+                .for_each(clone!(explosions => move |percent| {
+                    for (explosion, time) in explosions.iter() {
+                        explosion.percent.set(((percent * duration) / time).min(1.0) as f32);
+                    }
+
+                    async {}
+                }));
+
+            select(Box::pin(timer), Box::pin(handle.cancelled())).await;
+
+            for (explosion, _) in explosions {
+                grid.explosions.release(explosion);
+            }
         }
     }
 
+    /// Plays the effects at the same time, they are driven by a single timer.
+    ///
+    /// The effects are displayed immediately, the returned [`Effect`] can be used to wait for
+    /// them to finish, or to cancel them.
+    pub fn play_effects(self: &Arc<Self>, effects: Vec<(EffectKind, Coord)>) -> Effect {
+        let handle = Effect::new();
+        let count = effects.len();
+        let animate = self.animate_effects(effects, &handle);
+
+        self.spawn_future(clone!(handle => async move {
+            animate.await;
+            handle.finish();
+        }.instrument(tracing::debug_span!("play_effects", count))));
+
+        handle
+    }
+
+    #[inline]
+    pub fn play_effect<A>(self: &Arc<Self>, coord: Coord, effect: A) -> Effect where A: Into<EffectKind> {
+        self.play_effects(vec![(effect.into(), coord)])
+    }
+
+    /// Plays the effects one after another on the same coord, such as an explosion followed by debris.
+    ///
+    /// The first effect is displayed immediately. Cancelling the returned [`Effect`] stops
+    /// the current effect and skips the rest of the chain.
+    pub fn play_effect_chain(self: &Arc<Self>, coord: Coord, chain: Vec<EffectKind>) -> Effect {
+        let handle = Effect::new();
+        let grid = self.clone();
+        let count = chain.len();
+
+        let mut chain = chain.into_iter();
+
+        let first = chain.next().map(|effect| self.animate_effects(vec![(effect, coord)], &handle));
+
+        self.spawn_future(clone!(handle => async move {
+            if let Some(first) = first {
+                first.await;
+            }
+
+            for effect in chain {
+                if handle.is_cancelled() {
+                    break;
+                }
+
+                grid.animate_effects(vec![(effect, coord)], &handle).await;
+            }
+
+            handle.finish();
+        }.instrument(tracing::debug_span!("play_effect_chain", count, ?coord))));
+
+        handle
+    }
+
+    /// Plays the effect on the coord, it is attached to the unit so it is cancelled if
+    /// the unit is destroyed before the effect finishes.
+    pub fn play_unit_effect<A>(self: &Arc<Self>, unit: &Arc<Unit>, coord: Coord, effect: A) -> Effect where A: Into<EffectKind> {
+        let handle = self.play_effect(coord, effect);
+
+        let unit = unit.clone();
+
+        let destroyed = self.events.subscribe::<UnitDestroyed>()
+            .filter(move |event| std::future::ready(Arc::ptr_eq(&event.unit, &unit)))
+            .into_future();
+
+        self.spawn_future(clone!(handle => async move {
+            select(destroyed, Box::pin(handle.finished())).await;
+            handle.cancel();
+        }));
+
+        handle
+    }
+
+
+    pub fn explosion(self: &Arc<Self>, animation: ExplosionAnimation, coord: Coord) -> impl Future<Output = ()> + Send {
+        let grid = self.clone();
+
+        async move {
+            grid.play_effect(coord, animation).finished().await;
+        }.instrument(tracing::debug_span!("explosion", ?animation, ?coord))
+    }
+
+
+    /// Plays a [`MoveEffect`] on the coord, such as dust or a splash.
+    pub fn move_effect(self: &Arc<Self>, effect: MoveEffect, coord: Coord) -> impl Future<Output = ()> + Send {
+        let grid = self.clone();
+
+        async move {
+            grid.play_effect(coord, effect).finished().await;
+        }.instrument(tracing::debug_span!("move_effect", ?effect, ?coord))
+    }
+
 
     pub fn hide_unit(self: &Arc<Self>, unit: &Arc<Unit>, time: f64) -> impl Future<Output = ()> + Send {
         let grid = self.clone();
@@ -99,7 +490,7 @@ impl Grid {
                     unit.alpha.set((1.0 - percent) as f32);
                     async {}
                 }).await;
-        }
+        }.instrument(tracing::debug_span!("hide_unit", time))
     }
 
 
@@ -113,30 +504,232 @@ impl Grid {
                     unit.alpha.set(percent as f32);
                     async {}
                 }).await;
-        }
+        }.instrument(tracing::debug_span!("show_unit", time))
+    }
+
+
+    /// Reduces the unit's health and publishes [`UnitDamaged`], which displays the damage above the unit.
+    ///
+    /// The health can't go below `0`, but the unit isn't destroyed, use [`destroy_unit`](Grid::destroy_unit) for that.
+    pub fn damage_unit(&self, unit: &Arc<Unit>, attacker: Option<&Arc<Unit>>, damage: u32) {
+        let from_hp = unit.hp.get();
+        let to_hp = from_hp.saturating_sub(damage);
+
+        unit.hp.set_neq(to_hp);
+
+        self.events.publish(UnitDamaged {
+            unit: unit.clone(),
+            attacker: attacker.cloned(),
+            coord: unit.coord.get(),
+            from_hp,
+            to_hp,
+        });
+    }
+
+
+    /// Like [`damage_unit`](Grid::damage_unit) except the health counts down over several volleys,
+    /// with a short pause after each volley.
+    ///
+    /// The damage is split evenly between the volleys, use [`Unit::damage_volleys`] for the default number of volleys.
+    pub fn animate_damage(self: &Arc<Self>, unit: &Arc<Unit>, attacker: Option<&Arc<Unit>>, damage: u32, volleys: u32) -> impl Future<Output = ()> + Send {
+        let grid = self.clone();
+        let unit = unit.clone();
+        let attacker = attacker.cloned();
+        let volleys = volleys.max(1);
+
+        async move {
+            let from_hp = unit.hp.get();
+            let to_hp = from_hp.saturating_sub(damage);
+
+            // Published immediately, so the damage is displayed while the health counts down
+            grid.events.publish(UnitDamaged {
+                unit: unit.clone(),
+                attacker,
+                coord: unit.coord.get(),
+                from_hp,
+                to_hp,
+            });
+
+            let lost = (from_hp - to_hp) as f64;
+
+            for volley in 0..volleys {
+                let start = lost * (volley as f64 / volleys as f64);
+                let end = lost * ((volley + 1) as f64 / volleys as f64);
+
+                grid.timer(VOLLEY_ANIMATION_TIME)
+                    .for_each(clone!(unit => move |percent| {
+                        let hp = from_hp - (start + ((end - start) * percent)).round() as u32;
+                        unit.hp.set_neq(hp);
+                        async {}
+                    })).await;
+
+                grid.wait(VOLLEY_PAUSE_TIME).await;
+            }
+
+            unit.hp.set_neq(to_hp);
+        }.instrument(tracing::debug_span!("animate_damage", damage, volleys))
     }
 
 
     pub fn destroy_unit(self: &Arc<Self>, unit: &Arc<Unit>) -> impl Future<Output = ()> + Send {
         let grid = self.clone();
         let unit = unit.clone();
+        let unit_class = unit.class;
 
         async move {
             let coord = unit.coord.get();
 
-            let explosion = Explosion::new(coord, unit.class.explosion_animation());
+            let animation = unit.class.explosion_animation();
+
+            let chain = std::iter::once(EffectKind::from(animation))
+                .chain(animation.debris().map(EffectKind::from))
+                .collect();
 
-            grid.explosions.insert(explosion.clone());
+            // The explosion is displayed in the same frame that the unit is removed
+            let explosion = grid.play_effect_chain(coord, chain);
 
             grid.units.remove(&unit);
 
-            grid.timer(EXPLOSION_ANIMATION_TIME)
-                .for_each(clone!(explosion => move |percent| {
-                    explosion.percent.set(percent as f32);
-                    async {}
-                })).await;
+            // This cancels the effects which are attached to the unit
+            grid.events.publish(UnitDestroyed {
+                unit,
+                coord,
+            });
+
+            explosion.finished().await;
+        }.instrument(tracing::debug_span!("destroy_unit", class = ?unit_class))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::util::future::executor::{run_futures};
+    use crate::grid::{EXPLOSION_ANIMATION_TIME, MOVE_EFFECT_ANIMATION_TIME, Grid, Coord, Nation};
+    use crate::grid::terrain::{Terrain, TerrainClass};
+    use crate::grid::unit::{Unit, UnitId, UnitClass};
+    use crate::grid::explosion::{ExplosionAnimation, MoveEffect};
+    use super::{MoveDirection, MoveResult};
+
+    fn grid(units: Vec<Arc<Unit>>) -> Arc<Grid> {
+        let mut terrain = Terrain::new(4, 4);
+
+        for tile in terrain.iter_mut() {
+            tile.class = TerrainClass::Grass;
+        }
 
-            grid.explosions.remove(&explosion);
+        terrain.update_tiles();
+
+        let grid = Grid::new(terrain, vec![], units);
+        grid.start_futures();
+        grid.update_time(0.0);
+        run_futures();
+        grid
+    }
+
+    /// Advances the grid time in small steps, because the clock limits how much time passes in one update.
+    fn advance(grid: &Grid, start: f64, end: f64) {
+        let mut time = start;
+
+        while time < end {
+            time = (time + 50.0).min(end);
+            grid.update_time(time);
+            run_futures();
         }
     }
+
+    #[test]
+    fn effect_chain() {
+        let grid = grid(vec![]);
+
+        let effect = grid.play_effect_chain(Coord { x: 1.0, y: 1.0 }, vec![
+            ExplosionAnimation::Land.into(),
+            MoveEffect::Dust.into(),
+        ]);
+
+        // The first effect is displayed immediately
+        assert_eq!(grid.explosions.active(), 1);
+
+        run_futures();
+
+        let explosion_end = EXPLOSION_ANIMATION_TIME + 50.0;
+
+        advance(&grid, 0.0, explosion_end);
+
+        // The effects are played one at a time
+        assert_eq!(grid.explosions.active(), 1);
+        assert!(!effect.is_finished());
+
+        advance(&grid, explosion_end, explosion_end + MOVE_EFFECT_ANIMATION_TIME + 50.0);
+
+        assert_eq!(grid.explosions.active(), 0);
+        assert!(effect.is_finished());
+        assert!(!effect.is_cancelled());
+    }
+
+    #[test]
+    fn unit_effect_cancelled_on_destroy() {
+        let destroyed = Unit::new(UnitId::new(0), Coord { x: 1.0, y: 1.0 }, UnitClass::Infantry, Nation::OrangeStar);
+        let other = Unit::new(UnitId::new(1), Coord { x: 2.0, y: 2.0 }, UnitClass::Infantry, Nation::BlueMoon);
+
+        let grid = grid(vec![destroyed.clone(), other.clone()]);
+
+        let destroyed_effect = grid.play_unit_effect(&destroyed, Coord { x: 1.0, y: 0.0 }, MoveEffect::Dust);
+        let other_effect = grid.play_unit_effect(&other, Coord { x: 2.0, y: 1.0 }, MoveEffect::Dust);
+
+        run_futures();
+
+        assert_eq!(grid.explosions.active(), 2);
+
+        grid.spawn_future(grid.destroy_unit(&destroyed));
+        run_futures();
+
+        // The destroyed unit's effect was replaced by the explosion
+        assert!(destroyed_effect.is_cancelled());
+        assert!(destroyed_effect.is_finished());
+        assert!(!other_effect.is_cancelled());
+        assert_eq!(grid.explosions.active(), 2);
+
+        advance(&grid, 0.0, MOVE_EFFECT_ANIMATION_TIME + 50.0);
+
+        assert!(other_effect.is_finished());
+        assert!(!other_effect.is_cancelled());
+        assert_eq!(grid.explosions.active(), 1);
+    }
+
+    #[test]
+    fn move_through_ally() {
+        let unit = Unit::new(UnitId::new(0), Coord { x: 0.0, y: 0.0 }, UnitClass::Infantry, Nation::OrangeStar);
+        let ally = Unit::new(UnitId::new(1), Coord { x: 1.0, y: 0.0 }, UnitClass::Infantry, Nation::BlueMoon);
+
+        let grid = grid(vec![unit.clone(), ally.clone()]);
+
+        {
+            let mut teams = grid.teams.lock_mut();
+            let team = teams.team(Nation::OrangeStar);
+            teams.set_team(Nation::BlueMoon, team);
+        }
+
+        let result = Arc::new(std::sync::Mutex::new(None));
+
+        grid.spawn_future({
+            let grid = grid.clone();
+            let result = result.clone();
+            let unit = unit.clone();
+
+            async move {
+                let moved = grid.move_path(&unit, vec![MoveDirection::Right, MoveDirection::Right]).await;
+                *result.lock().unwrap() = Some(moved);
+            }
+        });
+
+        run_futures();
+
+        advance(&grid, 0.0, 5000.0);
+
+        assert_eq!(*result.lock().unwrap(), Some(MoveResult::Finished));
+        assert_eq!(unit.coord.get(), Coord { x: 2.0, y: 0.0 });
+        assert_eq!(ally.coord.get(), Coord { x: 1.0, y: 0.0 });
+    }
 }
@@ -0,0 +1,142 @@
+/// The logic clock advances in steps of `1000 / 120` milliseconds, so the time
+/// has a tiny rounding error which would otherwise make frames change one step late.
+const DRIFT_EPSILON: f64 = 1e-6;
+
+
+/// What happens when a [`FrameAnimation`] reaches the last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameMode {
+    /// Starts again from the first frame.
+    Loop,
+
+    /// Reverses direction, and then reverses again when it reaches the first frame.
+    PingPong,
+
+    /// Stays on the last frame.
+    Once,
+}
+
+
+/// Converts elapsed time into animation frames.
+///
+/// The frame is always calculated from the total elapsed time, instead of counting
+/// rendered frames, so it doesn't drift when frames are dropped or the frame rate changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameAnimation {
+    /// Number of milliseconds that each frame is displayed.
+    pub duration: f64,
+
+    /// Total number of frames, this must be at least 1.
+    pub frames: u32,
+
+    pub mode: FrameMode,
+
+    /// Number of frames to skip at the start, this is used to desynchronize
+    /// multiple animations, such as units which shouldn't all bob at the same time.
+    pub phase: f64,
+}
+
+impl FrameAnimation {
+    #[inline]
+    pub fn new(duration: f64, frames: u32, mode: FrameMode) -> Self {
+        Self { duration, frames, mode, phase: 0.0 }
+    }
+
+    /// Number of frames which have elapsed, including the [`phase`](FrameAnimation::phase).
+    fn steps(&self, elapsed: f64) -> u64 {
+        ((elapsed / self.duration) + self.phase + DRIFT_EPSILON).max(0.0).floor() as u64
+    }
+
+    /// Returns the frame which is displayed after `elapsed` milliseconds.
+    pub fn frame(&self, elapsed: f64) -> u32 {
+        assert!(self.frames > 0, "FrameAnimation must have at least 1 frame");
+
+        let steps = self.steps(elapsed);
+        let frames = self.frames as u64;
+
+        let frame = match self.mode {
+            FrameMode::Loop => steps % frames,
+
+            FrameMode::PingPong => {
+                if frames == 1 {
+                    0
+
+                } else {
+                    let last = frames - 1;
+                    let step = steps % (last * 2);
+
+                    if step > last {
+                        (last * 2) - step
+
+                    } else {
+                        step
+                    }
+                }
+            },
+
+            FrameMode::Once => steps.min(frames - 1),
+        };
+
+        frame as u32
+    }
+
+    /// Returns `true` if the animation has displayed every frame, this is only possible with [`FrameMode::Once`].
+    pub fn is_finished(&self, elapsed: f64) -> bool {
+        match self.mode {
+            FrameMode::Once => self.steps(elapsed) >= self.frames as u64,
+            FrameMode::Loop | FrameMode::PingPong => false,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameAnimation, FrameMode};
+
+    fn frames(animation: FrameAnimation) -> Vec<u32> {
+        (0..8).map(|index| animation.frame(index as f64 * 100.0)).collect()
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(frames(FrameAnimation::new(100.0, 3, FrameMode::Loop)), [0, 1, 2, 0, 1, 2, 0, 1]);
+        assert_eq!(frames(FrameAnimation::new(100.0, 3, FrameMode::PingPong)), [0, 1, 2, 1, 0, 1, 2, 1]);
+        assert_eq!(frames(FrameAnimation::new(100.0, 3, FrameMode::Once)), [0, 1, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(frames(FrameAnimation::new(100.0, 1, FrameMode::PingPong)), [0; 8]);
+    }
+
+    #[test]
+    fn phase() {
+        let animation = FrameAnimation {
+            phase: 1.0,
+            ..FrameAnimation::new(100.0, 3, FrameMode::Loop)
+        };
+
+        assert_eq!(frames(animation), [1, 2, 0, 1, 2, 0, 1, 2]);
+    }
+
+    // The logic clock adds up steps of 1000 / 120, which isn't exact.
+    #[test]
+    fn no_drift() {
+        let step = 1000.0 / 120.0;
+
+        let mut time = 0.0;
+
+        for _ in 0..120 {
+            time += step;
+        }
+
+        let animation = FrameAnimation::new(250.0, 8, FrameMode::Loop);
+
+        assert_eq!(animation.frame(time), 4);
+    }
+
+    #[test]
+    fn finished() {
+        let animation = FrameAnimation::new(100.0, 3, FrameMode::Once);
+
+        assert!(!animation.is_finished(299.0));
+        assert!(animation.is_finished(300.0));
+    }
+}
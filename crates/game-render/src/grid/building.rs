@@ -2,77 +2,100 @@ use std::sync::Arc;
 use futures_signals::map_ref;
 use futures_signals::signal::{Mutable, Signal, SignalExt};
 use rusted_battalions_engine as engine;
-use rusted_battalions_engine::{Node, Size, Offset, Tile, ParentWidth, ParentHeight, Order};
+use rusted_battalions_engine::{Node, Size, Offset, Tile, ParentWidth, ParentHeight, Order, SpriteAnimation, AnimationMode};
 
 use crate::Game;
+use crate::assets::{NationPalettes};
 use crate::grid::{BUILDING_ANIMATION_TIME, FOG_ANIMATION_TIME, Grid, Coord, Nation};
+use crate::grid::entity_index::{Entity};
 
+pub use rusted_battalions_game_core::building::{BuildingClass};
 
-#[derive(Debug, Clone, Copy)]
-pub enum BuildingClass {
-    HQ1, // Orange Star
-    HQ2, // Blue Moon
-    HQ3, // Green Earth
-    HQ4, // Yellow Comet
-    HQ5, // Black Hole
-    City,
-    Base,
-    Airport,
-    Port,
-    ComTower,
-    Lab,
-    MissileSilo,
-    MissileSiloEmpty,
-    /*BlackCrystal,
-    Laser,
-    Minicannon { direction: , grass: bool },
-    Volcano,
-    BlackOnyx, // Flying Fortress
-    Fortress,
-    BlackArmageddon,
-    BlackCannon { direction: },
-    BlackObelisk,*/
+
+pub(crate) trait BuildingClassExt {
+    /// The y position of the building's sprites in the spritesheet.
+    fn tile_y(&self) -> u32;
 }
 
-impl BuildingClass {
-    pub const ALL: &[Self] = &[
-        Self::HQ1,
-        Self::HQ2,
-        Self::HQ3,
-        Self::HQ4,
-        Self::HQ5,
-        Self::City,
-        Self::Base,
-        Self::Airport,
-        Self::Port,
-        Self::ComTower,
-        Self::Lab,
-        Self::MissileSilo,
-        Self::MissileSiloEmpty,
-    ];
-
-    fn can_have_nation(&self) -> bool {
-        match self {
-            Self::MissileSilo | Self::MissileSiloEmpty => false,
-            _ => true,
-        }
+impl BuildingClassExt for BuildingClass {
+    fn tile_y(&self) -> u32 {
+        self.spec().sprite_row * Building::TILE_HEIGHT
+    }
+}
+
+
+/// Identifier for a [`Building`] which is unique and stays the same for the lifetime of the Building.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BuildingId(u32);
+
+impl BuildingId {
+    /// The buildings on a map are numbered in the order of [`MapData::buildings`](super::map::MapData::buildings).
+    #[inline]
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    #[inline]
+    pub const fn get(self) -> u32 {
+        self.0
     }
 }
 
 
 pub struct Building {
+    pub id: BuildingId,
     pub coord: Coord,
     pub nation: Mutable<Option<Nation>>,
     pub class: BuildingClass,
     pub fog: Mutable<bool>,
 }
 
+impl Entity for Building {
+    type Id = BuildingId;
+
+    #[inline]
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+
+    #[inline]
+    fn coord(&self) -> Coord {
+        self.coord
+    }
+}
+
 impl Building {
-    const TILE_WIDTH: u32 = 16;
-    const TILE_HEIGHT: u32 = 32;
+    pub(crate) const TILE_WIDTH: u32 = 16;
+    pub(crate) const TILE_HEIGHT: u32 = 32;
+
+    /// The palette for the nation, `None` is neutral.
+    pub(crate) fn palette(palettes: &NationPalettes, nation: Option<Nation>) -> u32 {
+        if let Some(palette) = nation.and_then(|nation| palettes.buildings.get(&nation)) {
+            return *palette;
+        }
+
+        match nation {
+            None => 0,
+            Some(Nation::OrangeStar) => 0,
+            Some(Nation::BlueMoon) => 1,
+            Some(Nation::GreenEarth) => 2,
+            Some(Nation::YellowComet) => 3,
+            Some(Nation::BlackHole) => 4,
+        }
+    }
+
+    fn palette_signal(game: &Arc<Game>, this: &Arc<Self>) -> impl Signal<Item = u32> {
+        map_ref! {
+            let palettes = game.nation_palettes(),
+            let nation = this.nation.signal() => {
+                Self::palette(palettes, *nation)
+            }
+        }.dedupe()
+    }
 
-    pub fn new(coord: Coord, class: BuildingClass, nation: Option<Nation>) -> Arc<Self> {
+    pub fn new(id: BuildingId, coord: Coord, class: BuildingClass, nation: Option<Nation>) -> Arc<Self> {
         Arc::new(Self {
+            id,
             coord,
             class,
             nation: Mutable::new(nation),
@@ -84,18 +107,33 @@ impl Building {
         self.nation.signal_ref(|nation| nation.is_some()).dedupe()
     }
 
-    fn tile_x(&self, grid: &Arc<Grid>) -> impl Signal<Item = u32> {
+    /// Whether the building is displayed as being in fog.
+    fn is_fogged(&self, reveal_fog: bool) -> impl Signal<Item = bool> {
+        self.fog.signal_ref(move |fog| !reveal_fog && *fog)
+    }
+
+    fn is_animated(&self, reveal_fog: bool) -> impl Signal<Item = bool> {
+        let can_have_nation = self.class.can_have_nation();
+
+        map_ref! {
+            let fog = self.is_fogged(reveal_fog),
+            let has_nation = self.has_nation() => {
+                !*fog && can_have_nation && *has_nation
+            }
+        }.dedupe()
+    }
+
+    fn tile_x(&self, reveal_fog: bool) -> impl Signal<Item = u32> {
         let can_have_nation = self.class.can_have_nation();
 
         map_ref! {
-            let fog = self.fog.signal(),
-            let has_nation = self.has_nation(),
-            let frame = grid.animation_loop(BUILDING_ANIMATION_TIME, 4) => move {
+            let fog = self.is_fogged(reveal_fog),
+            let has_nation = self.has_nation() => move {
                 if *fog {
                     Self::TILE_WIDTH
 
                 } else if can_have_nation && *has_nation {
-                    (2 + frame) * Self::TILE_WIDTH
+                    2 * Self::TILE_WIDTH
 
                 } else {
                     0
@@ -105,24 +143,12 @@ impl Building {
     }
 
     pub fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
-        let tile_y = match this.class {
-            BuildingClass::HQ1 => 0 * Self::TILE_HEIGHT,
-            BuildingClass::HQ2 => 1 * Self::TILE_HEIGHT,
-            BuildingClass::HQ3 => 2 * Self::TILE_HEIGHT,
-            BuildingClass::HQ4 => 3 * Self::TILE_HEIGHT,
-            BuildingClass::HQ5 => 4 * Self::TILE_HEIGHT,
-            BuildingClass::City => 5 * Self::TILE_HEIGHT,
-            BuildingClass::Base => 6 * Self::TILE_HEIGHT,
-            BuildingClass::Airport => 7 * Self::TILE_HEIGHT,
-            BuildingClass::Port => 8 * Self::TILE_HEIGHT,
-            BuildingClass::ComTower => 9 * Self::TILE_HEIGHT,
-            BuildingClass::Lab => 10 * Self::TILE_HEIGHT,
-            BuildingClass::MissileSilo => 11 * Self::TILE_HEIGHT,
-            BuildingClass::MissileSiloEmpty => 12 * Self::TILE_HEIGHT,
-        };
+        let tile_y = this.class.tile_y();
 
         let (x, y) = grid.tile_offset(&this.coord);
 
+        let reveal_fog = game.reveal_fog();
+
         let offset = Offset {
             x: ParentWidth(x),
             y: ParentHeight(y - grid.height),
@@ -139,7 +165,7 @@ impl Building {
             .child(engine::Sprite::builder()
                 .spritesheet(game.spritesheets.building.clone())
 
-                .tile_signal(this.tile_x(grid).map(move |tile_x| {
+                .tile_signal(this.tile_x(reveal_fog).map(move |tile_x| {
                     Tile {
                         start_x: tile_x,
                         start_y: tile_y,
@@ -148,17 +174,23 @@ impl Building {
                     }
                 }))
 
-                .palette_signal(this.nation.signal_ref(|nation| {
-                    match nation {
-                        None => 0,
-                        Some(Nation::OrangeStar) => 0,
-                        Some(Nation::BlueMoon) => 1,
-                        Some(Nation::GreenEarth) => 2,
-                        Some(Nation::YellowComet) => 3,
-                        Some(Nation::BlackHole) => 4,
+                .animation_signal(this.is_animated(reveal_fog).map(|is_animated| {
+                    if is_animated {
+                        Some(SpriteAnimation {
+                            frames: 4,
+                            duration: BUILDING_ANIMATION_TIME as f32,
+                            offset_x: Self::TILE_WIDTH as i32,
+                            offset_y: 0,
+                            mode: AnimationMode::Loop,
+                        })
+
+                    } else {
+                        None
                     }
                 }))
 
+                .palette_signal(Self::palette_signal(game, this))
+
                 .order(Order::Parent(grid.order(&this.coord) + (2.0 / 6.0)))
                 .offset(offset)
                 .size(size)
@@ -194,4 +226,47 @@ impl Building {
 
             .build()
     }
+
+    /// Upside down copy of the building which is displayed on the water tile below the building.
+    pub(crate) fn render_reflection(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        let tile_y = this.class.tile_y();
+
+        let (x, y) = grid.tile_offset(&this.coord);
+
+        let below = Coord { x: this.coord.x, y: this.coord.y + 1.0 };
+
+        engine::Sprite::builder()
+            .spritesheet(game.spritesheets.building.clone())
+
+            .tile_signal(this.tile_x(game.reveal_fog()).map(move |tile_x| {
+                Tile {
+                    start_x: tile_x,
+                    start_y: tile_y,
+                    end_x: tile_x + Self::TILE_WIDTH,
+                    end_y: tile_y + Self::TILE_HEIGHT,
+                }
+            }))
+
+            .palette_signal(Self::palette_signal(game, this))
+
+            .visible(grid.is_water_below(this.coord))
+            .alpha_signal(grid.reflection_alpha())
+            .flip_y(true)
+
+            // Above the water, but below the fog
+            .order(Order::Parent(grid.order(&below) + (0.5 / 6.0)))
+
+            // Starts at the bottom of the building's tile, and it is squashed to half the height of the building
+            .offset(Offset {
+                x: ParentWidth(x),
+                y: ParentHeight(y + grid.height),
+            })
+
+            .size(Size {
+                width: ParentWidth(grid.width),
+                height: ParentHeight(grid.height),
+            })
+
+            .build()
+    }
 }
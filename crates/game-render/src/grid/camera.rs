@@ -0,0 +1,118 @@
+use futures_signals::signal::{Mutable};
+
+use crate::grid::{Coord};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportSize {
+    /// Number of tiles horizontally.
+    pub width: f32,
+
+    /// Number of tiles vertically.
+    pub height: f32,
+}
+
+
+/// Controls when and how fast the camera automatically pans.
+#[derive(Debug, Clone, Copy)]
+pub struct AutoPan {
+    /// If a coord gets closer than this many tiles to the edge of the viewport, then it will pan.
+    pub margin: f32,
+
+    /// Number of tiles per second.
+    pub speed: f32,
+}
+
+impl Default for AutoPan {
+    fn default() -> Self {
+        Self {
+            margin: 2.0,
+            speed: 20.0,
+        }
+    }
+}
+
+
+/// Decides which part of the grid is visible.
+pub struct Camera {
+    /// Coord of the tile which is in the upper-left corner of the viewport.
+    pub position: Mutable<Coord>,
+
+    /// The default is the size of the grid, which means the entire grid is visible.
+    pub viewport: Mutable<ViewportSize>,
+
+    pub auto_pan: Mutable<AutoPan>,
+
+    grid_size: ViewportSize,
+}
+
+impl Camera {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        let grid_size = ViewportSize {
+            width: width as f32,
+            height: height as f32,
+        };
+
+        Self {
+            position: Mutable::new(Coord { x: 0.0, y: 0.0 }),
+            viewport: Mutable::new(grid_size),
+            auto_pan: Mutable::new(AutoPan::default()),
+            grid_size,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn grid_size(&self) -> ViewportSize {
+        self.grid_size
+    }
+
+    fn clamp_axis(position: f32, viewport: f32, grid: f32) -> f32 {
+        position.min(grid - viewport).max(0.0)
+    }
+
+    fn target_axis(position: f32, coord: f32, viewport: f32, margin: f32) -> f32 {
+        // The margin can't be more than half of the viewport, otherwise it would never stop panning
+        let margin = margin.min(((viewport - 1.0) * 0.5).max(0.0));
+
+        let low = position + margin;
+        let high = position + viewport - margin - 1.0;
+
+        if coord < low {
+            coord - margin
+
+        } else if coord > high {
+            coord + margin + 1.0 - viewport
+
+        } else {
+            position
+        }
+    }
+
+    /// Moves the camera by the number of tiles, it stops at the edges of the grid.
+    pub fn pan(&self, x: f32, y: f32) {
+        let viewport = self.viewport.get();
+
+        let mut position = self.position.lock_mut();
+
+        let new_position = Coord {
+            x: Self::clamp_axis(position.x + x, viewport.width, self.grid_size.width),
+            y: Self::clamp_axis(position.y + y, viewport.height, self.grid_size.height),
+        };
+
+        if *position != new_position {
+            *position = new_position;
+        }
+    }
+
+    /// Returns the closest camera position where `coord` is inside of the margins.
+    pub fn target(&self, coord: Coord) -> Coord {
+        let position = self.position.get();
+        let viewport = self.viewport.get();
+        let margin = self.auto_pan.get().margin;
+
+        Coord {
+            x: Self::clamp_axis(Self::target_axis(position.x, coord.x, viewport.width, margin), viewport.width, self.grid_size.width),
+            y: Self::clamp_axis(Self::target_axis(position.y, coord.y, viewport.height, margin), viewport.height, self.grid_size.height),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+/// Converts the render time into the logic time of the grid.
+///
+/// The logic time advances in fixed steps, so the simulation is the same regardless
+/// of the frame rate, dropped frames, or changes to the time scale.
+pub(crate) struct LogicClock {
+    /// Number of milliseconds for each step.
+    step: f64,
+
+    /// The maximum number of milliseconds that can be simulated in a single frame.
+    ///
+    /// When the tab is backgrounded the browser stops rendering, so when it is
+    /// foregrounded again there is a very large gap in the render time. Without
+    /// this limit it would instantly skip to the end of every animation.
+    max_delta: f64,
+
+    last_render_time: Option<f64>,
+
+    /// Leftover time which isn't big enough for a full step.
+    accumulator: f64,
+
+    time: f64,
+}
+
+impl LogicClock {
+    pub(crate) fn new(step: f64, max_delta: f64) -> Self {
+        Self {
+            step,
+            max_delta,
+            last_render_time: None,
+            accumulator: 0.0,
+            time: 0.0,
+        }
+    }
+
+    /// Advances the logic time based on how much render time has passed since the last update.
+    ///
+    /// The `time_scale` is applied to the elapsed time, so changing it only affects future updates.
+    pub(crate) fn update(&mut self, render_time: f64, time_scale: f64) -> f64 {
+        if let Some(last) = self.last_render_time {
+            let delta = (render_time - last).max(0.0).min(self.max_delta);
+
+            self.accumulator += delta * time_scale;
+
+            while self.accumulator >= self.step {
+                self.accumulator -= self.step;
+                self.time += self.step;
+            }
+        }
+
+        self.last_render_time = Some(render_time);
+
+        self.time
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{LogicClock};
+
+    #[test]
+    fn step_accumulation() {
+        let mut clock = LogicClock::new(10.0, 100.0);
+
+        // The first update only records the render time
+        assert_eq!(clock.update(1000.0, 1.0), 0.0);
+
+        assert_eq!(clock.update(1004.0, 1.0), 0.0);
+        assert_eq!(clock.update(1008.0, 1.0), 0.0);
+
+        // The leftover time from the previous updates is used
+        assert_eq!(clock.update(1012.0, 1.0), 10.0);
+        assert_eq!(clock.update(1035.0, 1.0), 30.0);
+        assert_eq!(clock.update(1040.0, 1.0), 40.0);
+
+        // Time going backwards is ignored
+        assert_eq!(clock.update(900.0, 1.0), 40.0);
+        assert_eq!(clock.update(910.0, 1.0), 50.0);
+    }
+
+    #[test]
+    fn max_delta() {
+        let mut clock = LogicClock::new(10.0, 100.0);
+
+        clock.update(0.0, 1.0);
+
+        // The tab was backgrounded for a minute
+        assert_eq!(clock.update(60_000.0, 1.0), 100.0);
+        assert_eq!(clock.update(60_010.0, 1.0), 110.0);
+
+        // The delta is clamped before the time scale is applied
+        assert_eq!(clock.update(120_000.0, 2.0), 310.0);
+    }
+
+    #[test]
+    fn time_scale() {
+        let mut clock = LogicClock::new(10.0, 100.0);
+
+        clock.update(0.0, 1.0);
+
+        assert_eq!(clock.update(20.0, 2.0), 40.0);
+        assert_eq!(clock.update(40.0, 0.5), 50.0);
+
+        // Pausing doesn't lose the leftover time
+        assert_eq!(clock.update(45.0, 1.0), 50.0);
+        assert_eq!(clock.update(100.0, 0.0), 50.0);
+        assert_eq!(clock.update(105.0, 1.0), 60.0);
+    }
+}
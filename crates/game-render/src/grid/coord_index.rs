@@ -0,0 +1,82 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use smallvec::SmallVec;
+
+use crate::grid::{Coord};
+
+
+/// The x / y of a tile.
+type Tile = (i32, i32);
+
+
+/// Spatial index which maps from tiles to the ids which are on that tile.
+///
+/// Each tile usually only has a single id, so it doesn't allocate in that case.
+pub(crate) struct CoordIndex<Id> {
+    tiles: HashMap<Tile, SmallVec<[Id; 1]>>,
+
+    // This is needed in order to remove an id without knowing its coord
+    positions: HashMap<Id, Tile>,
+}
+
+impl<Id> CoordIndex<Id> where Id: Copy + Eq + Hash {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self {
+            tiles: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Returns the ids which are on the tile at `coord`.
+    pub(crate) fn get(&self, coord: Coord) -> &[Id] {
+        self.tiles.get(&coord.tile()).map(|ids| ids.as_slice()).unwrap_or(&[])
+    }
+
+    /// Returns the ids which are on the 4 tiles adjacent to `coord`.
+    pub(crate) fn adjacent(&self, coord: Coord) -> impl Iterator<Item = Id> + '_ {
+        let (x, y) = coord.tile();
+
+        [(x, y - 1), (x, y + 1), (x - 1, y), (x + 1, y)].into_iter()
+            .filter_map(|tile| self.tiles.get(&tile))
+            .flat_map(|ids| ids.iter().copied())
+    }
+
+    /// Inserts the id at `coord`, if the id already exists then it is moved to `coord`.
+    pub(crate) fn insert(&mut self, id: Id, coord: Coord) {
+        let tile = coord.tile();
+
+        if let Some(old_tile) = self.positions.insert(id, tile) {
+            if old_tile == tile {
+                return;
+            }
+
+            self.remove_from_tile(old_tile, id);
+        }
+
+        self.tiles.entry(tile).or_default().push(id);
+    }
+
+    pub(crate) fn remove(&mut self, id: Id) {
+        if let Some(tile) = self.positions.remove(&id) {
+            self.remove_from_tile(tile, id);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.tiles.clear();
+        self.positions.clear();
+    }
+
+    fn remove_from_tile(&mut self, tile: Tile, id: Id) {
+        if let Some(ids) = self.tiles.get_mut(&tile) {
+            if let Some(index) = ids.iter().position(|x| *x == id) {
+                ids.swap_remove(index);
+            }
+
+            if ids.is_empty() {
+                self.tiles.remove(&tile);
+            }
+        }
+    }
+}
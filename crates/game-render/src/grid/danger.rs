@@ -0,0 +1,397 @@
+use std::sync::{Arc, Weak, Mutex};
+use std::future::Future;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, BinaryHeap};
+use futures::stream::{StreamExt, select};
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, SignalExt};
+use futures_signals::signal_vec::{SignalVecExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Size, Offset, ParentWidth, ParentHeight, Order, Zero, TilemapSize, Tileset};
+
+use crate::Game;
+use crate::grid::{OVERLAY_TILE_SIZE, Grid, Coord, Nation, Teams};
+use crate::grid::action::{UnitMoved};
+use crate::grid::unit::{Unit, UnitId};
+
+
+/// The x / y of a tile.
+type Tile = (i32, i32);
+
+
+/// The tile in the overlay spritesheet which is used for the danger zone.
+const DANGER_TILE: u32 = 0;
+
+
+/// The state of a unit when the danger zone was last refreshed.
+#[derive(Clone, Copy, PartialEq)]
+struct UnitSnapshot {
+    tile: Tile,
+    nation: Nation,
+    fog: bool,
+}
+
+
+/// The cached danger of a single enemy unit.
+struct UnitDanger {
+    /// Every tile which was visited while calculating the movement range.
+    ///
+    /// If a unit enters or leaves one of these tiles then the danger must be recalculated.
+    explored: HashSet<Tile>,
+
+    /// The tiles which the unit can attack next turn.
+    attack: Vec<Tile>,
+}
+
+
+struct DangerState {
+    nation: Option<(Nation, Teams)>,
+    units: HashMap<UnitId, UnitSnapshot>,
+    dangers: HashMap<UnitId, UnitDanger>,
+
+    /// How many enemy units can attack each tile.
+    counts: Vec<u32>,
+}
+
+
+/// Overlay which displays every tile that the enemy units can attack next turn.
+///
+/// The danger of each enemy unit is cached, when a unit moves only the enemies whose
+/// movement range includes the old / new tile are recalculated.
+///
+/// Units which are hidden by fog are ignored. After changing the fog of a unit,
+/// [`Grid::refresh_danger_zone`] must be called.
+///
+/// The movement range uses the terrain cost of each unit's [`MovementClass`](crate::MovementClass), see [`Grid::move_cost`].
+pub struct DangerZone {
+    /// Whether the danger zone is displayed, it is only calculated while it is visible.
+    pub visible: Mutable<bool>,
+
+    /// The units of every nation which isn't allied with this nation are enemies.
+    pub nation: Mutable<Nation>,
+
+    /// Shared with [`Grid::teams`].
+    teams: Mutable<Teams>,
+
+    width: u32,
+    height: u32,
+
+    state: Mutex<DangerState>,
+
+    /// The cells for each row of the overlay.
+    rows: Vec<Mutable<Vec<Option<u32>>>>,
+}
+
+impl DangerZone {
+    pub(crate) fn new(width: u32, height: u32, teams: Mutable<Teams>) -> Arc<Self> {
+        Arc::new(Self {
+            visible: Mutable::new(false),
+            nation: Mutable::new(Nation::OrangeStar),
+            teams,
+
+            width,
+            height,
+
+            state: Mutex::new(DangerState {
+                nation: None,
+                units: HashMap::new(),
+                dangers: HashMap::new(),
+                counts: vec![0; (width * height) as usize],
+            }),
+
+            rows: (0..height).map(|_| Mutable::new(vec![None; width as usize])).collect(),
+        })
+    }
+
+    fn index(&self, tile: Tile) -> usize {
+        (tile.1 as usize * self.width as usize) + tile.0 as usize
+    }
+
+    fn contains(&self, tile: Tile) -> bool {
+        tile.0 >= 0 && tile.1 >= 0 && (tile.0 as u32) < self.width && (tile.1 as u32) < self.height
+    }
+
+    /// Returns whether an enemy unit can attack the tile at `coord` next turn.
+    ///
+    /// This is only up to date while the danger zone is visible.
+    pub fn is_dangerous(&self, coord: Coord) -> bool {
+        let tile = coord.tile();
+
+        self.contains(tile) && self.state.lock().unwrap().counts[self.index(tile)] > 0
+    }
+
+    /// Returns the tiles which the unit can move to, the unit can move through
+    /// units of the same nation but it can't stop on them.
+    ///
+    /// Each tile costs the terrain's movement points, so this finds the cheapest path to every tile.
+    fn movement_range(&self, grid: &Grid, unit: &Unit, start: Tile, blockers: &HashMap<Tile, Nation>, explored: &mut HashSet<Tile>) -> Vec<Tile> {
+        let movement = unit.class.movement();
+        let class = unit.class.movement_class();
+
+        let mut costs = HashMap::new();
+        costs.insert(start, 0);
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0, start)));
+
+        while let Some(Reverse((cost, (x, y)))) = queue.pop() {
+            // A cheaper path to this tile was already found
+            if costs[&(x, y)] < cost {
+                continue;
+            }
+
+            for tile in [(x, y - 1), (x, y + 1), (x - 1, y), (x + 1, y)] {
+                if !self.contains(tile) {
+                    continue;
+                }
+
+                explored.insert(tile);
+
+                if blockers.get(&tile).is_some_and(|nation| *nation != unit.nation) {
+                    continue;
+                }
+
+                let coord = Coord { x: tile.0 as f32, y: tile.1 as f32 };
+
+                if let Some(step) = grid.move_cost(class, coord) {
+                    let total = cost + step;
+
+                    if total <= movement && costs.get(&tile).map_or(true, |old| total < *old) {
+                        costs.insert(tile, total);
+                        queue.push(Reverse((total, tile)));
+                    }
+                }
+            }
+        }
+
+        costs.into_keys()
+            .filter(|tile| *tile == start || !blockers.contains_key(tile))
+            .collect()
+    }
+
+    fn unit_danger(&self, grid: &Grid, unit: &Unit, start: Tile, blockers: &HashMap<Tile, Nation>) -> UnitDanger {
+        let mut explored = HashSet::new();
+        let mut attack = vec![];
+
+        explored.insert(start);
+
+        if let Some(range) = unit.class.attack_range() {
+            // Indirect units can't move and attack in the same turn
+            let origins = if range.is_direct() {
+                self.movement_range(grid, unit, start, blockers, &mut explored)
+
+            } else {
+                vec![start]
+            };
+
+            let mut attacked = HashSet::new();
+
+            let max = range.max as i32;
+
+            for (x, y) in origins {
+                for offset_y in -max..=max {
+                    let remaining = max - offset_y.abs();
+
+                    for offset_x in -remaining..=remaining {
+                        let distance = (offset_x.abs() + offset_y.abs()) as u32;
+                        let tile = (x + offset_x, y + offset_y);
+
+                        if distance >= range.min && self.contains(tile) && attacked.insert(tile) {
+                            attack.push(tile);
+                        }
+                    }
+                }
+            }
+        }
+
+        UnitDanger { explored, attack }
+    }
+
+    fn refresh(&self, grid: &Grid, units: &[Arc<Unit>]) {
+        let nation = self.nation.get();
+        let teams = self.teams.get_cloned();
+
+        let current: HashMap<UnitId, UnitSnapshot> = units.iter().map(|unit| {
+            (unit.id, UnitSnapshot {
+                tile: unit.coord.get().tile(),
+                nation: unit.nation,
+                fog: unit.fog.get(),
+            })
+        }).collect();
+
+        let mut lock = self.state.lock().unwrap();
+
+        let DangerState { nation: old_nation, units: old_units, dangers, counts } = &mut *lock;
+
+        let nation_changed = old_nation.as_ref() != Some(&(nation, teams.clone()));
+
+        if nation_changed {
+            *old_nation = Some((nation, teams.clone()));
+        }
+
+        // Tiles where a unit has appeared or disappeared
+        let mut changed = HashSet::new();
+
+        for (id, old) in old_units.iter() {
+            if current.get(id) != Some(old) {
+                changed.insert(old.tile);
+            }
+        }
+
+        for (id, new) in current.iter() {
+            if old_units.get(id) != Some(new) {
+                changed.insert(new.tile);
+            }
+        }
+
+        let mut dirty_rows = HashSet::new();
+
+        dangers.retain(|id, danger| {
+            let valid = !nation_changed &&
+                old_units.get(id) == current.get(id) &&
+                danger.explored.is_disjoint(&changed);
+
+            if !valid {
+                for tile in danger.attack.iter() {
+                    counts[self.index(*tile)] -= 1;
+                    dirty_rows.insert(tile.1);
+                }
+            }
+
+            valid
+        });
+
+        let blockers: HashMap<Tile, Nation> = current.values()
+            .filter(|unit| !unit.fog)
+            .map(|unit| (unit.tile, unit.nation))
+            .collect();
+
+        for unit in units {
+            let snapshot = current[&unit.id];
+
+            if !teams.are_allies(snapshot.nation, nation) && !snapshot.fog && !dangers.contains_key(&unit.id) {
+                let danger = self.unit_danger(grid, unit, snapshot.tile, &blockers);
+
+                for tile in danger.attack.iter() {
+                    counts[self.index(*tile)] += 1;
+                    dirty_rows.insert(tile.1);
+                }
+
+                dangers.insert(unit.id, danger);
+            }
+        }
+
+        *old_units = current;
+
+        for y in dirty_rows {
+            let start = self.index((0, y));
+            let end = start + self.width as usize;
+
+            self.rows[y as usize].set_neq(counts[start..end].iter().map(|count| {
+                if *count > 0 {
+                    Some(DANGER_TILE)
+
+                } else {
+                    None
+                }
+            }).collect());
+        }
+    }
+
+    pub(crate) fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        engine::Stack::builder()
+            .order(Order::Parent(0.0))
+            .visible_signal(this.visible.signal())
+
+            .children(this.rows.iter().enumerate().map(|(y, row)| {
+                let coord = Coord { x: 0.0, y: y as f32 };
+
+                let (_, offset_y) = grid.tile_offset(&coord);
+
+                engine::Tilemap::builder()
+                    .spritesheet(game.spritesheets.overlay.clone())
+
+                    .map_size(TilemapSize {
+                        width: this.width,
+                        height: 1,
+                    })
+
+                    .tileset(Tileset {
+                        columns: 1,
+                        tile_width: OVERLAY_TILE_SIZE,
+                        tile_height: OVERLAY_TILE_SIZE,
+                    })
+
+                    .alpha(0.5)
+                    .cells_signal(row.signal_cloned())
+
+                    // Above the terrain and fog, but below the buildings and units
+                    .order(Order::Parent(grid.order(&coord) + (1.5 / 6.0)))
+
+                    .offset(Offset {
+                        x: Zero,
+                        y: ParentHeight(offset_y),
+                    })
+
+                    .size(Size {
+                        width: ParentWidth(1.0),
+                        height: ParentHeight(grid.height),
+                    })
+
+                    .build()
+            }))
+
+            .build()
+    }
+}
+
+
+/// Refreshes the danger zone whenever a unit is added / removed / moved.
+pub(crate) fn sync_danger_zone(grid: &Arc<Grid>) -> impl Future<Output = ()> + 'static {
+    let this = grid.danger_zone.clone();
+
+    let units = map_ref! {
+        let units = grid.units.signal_vec().to_signal_cloned(),
+        let visible = this.visible.signal(),
+        let _nation = this.nation.signal(),
+        let _teams = this.teams.signal_ref(|_| ()) => {
+            if *visible {
+                Some(units.clone())
+
+            } else {
+                None
+            }
+        }
+    }.to_stream();
+
+    // The coord of a unit changes every frame while it is moving, so it only refreshes after it stops
+    let moved = grid.events.subscribe::<UnitMoved>();
+
+    let grid: Weak<Grid> = Arc::downgrade(grid);
+
+    async move {
+        let mut latest = None;
+
+        select(units.map(Some), moved.map(|_| None)).for_each(move |change| {
+            if let Some(units) = change {
+                latest = units;
+            }
+
+            if let (Some(units), Some(grid)) = (&latest, grid.upgrade()) {
+                this.refresh(&grid, units);
+            }
+
+            async {}
+        }).await;
+    }
+}
+
+
+impl Grid {
+    /// Recalculates the [`DangerZone`], this must be called after changing the fog of a unit.
+    pub fn refresh_danger_zone(&self) {
+        if self.danger_zone.visible.get() {
+            self.danger_zone.refresh(self, &self.units.lock_ref());
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::{Arc, Mutex};
+use std::hash::Hash;
+use std::future::Future;
+use std::collections::HashMap;
+use futures_signals::signal_vec::{SignalVecExt, VecDiff};
+
+use crate::util::signal::{SortedVec};
+use crate::grid::{Coord};
+use crate::grid::coord_index::{CoordIndex};
+
+
+/// Something which exists on the grid, such as a unit or building.
+pub(crate) trait Entity {
+    type Id: Copy + Eq + Hash;
+
+    fn id(&self) -> Self::Id;
+
+    fn coord(&self) -> Coord;
+}
+
+
+/// Lookup tables for finding entities by their id or coord.
+pub(crate) struct EntityIndex<T> where T: Entity {
+    pub(crate) ids: HashMap<T::Id, Arc<T>>,
+    pub(crate) coords: CoordIndex<T::Id>,
+}
+
+impl<T> EntityIndex<T> where T: Entity {
+    pub(crate) fn new(values: &[Arc<T>]) -> Arc<Mutex<Self>> {
+        let mut this = Self {
+            ids: HashMap::new(),
+            coords: CoordIndex::new(),
+        };
+
+        for value in values {
+            this.insert(value.clone());
+        }
+
+        Arc::new(Mutex::new(this))
+    }
+
+    pub(crate) fn insert(&mut self, value: Arc<T>) {
+        self.coords.insert(value.id(), value.coord());
+        self.ids.insert(value.id(), value);
+    }
+
+    fn remove(&mut self, value: &T) {
+        self.coords.remove(value.id());
+        self.ids.remove(&value.id());
+    }
+
+    fn clear(&mut self) {
+        self.coords.clear();
+        self.ids.clear();
+    }
+
+    /// Updates the coord index after an entity has moved.
+    pub(crate) fn update_coord(&mut self, value: &T) {
+        if self.ids.contains_key(&value.id()) {
+            self.coords.insert(value.id(), value.coord());
+        }
+    }
+
+    pub(crate) fn get(&self, id: T::Id) -> Option<Arc<T>> {
+        self.ids.get(&id).cloned()
+    }
+
+    pub(crate) fn at(&self, coord: Coord) -> Option<Arc<T>> {
+        self.coords.get(coord).first().map(|id| self.ids[id].clone())
+    }
+
+    pub(crate) fn all_at(&self, coord: Coord) -> Vec<Arc<T>> {
+        self.coords.get(coord).iter().map(|id| self.ids[id].clone()).collect()
+    }
+
+    pub(crate) fn adjacent(&self, coord: Coord) -> Vec<Arc<T>> {
+        self.coords.adjacent(coord).map(|id| self.ids[&id].clone()).collect()
+    }
+}
+
+
+/// Keeps the index in sync with the changes to `values`.
+pub(crate) fn sync_index<T>(values: &SortedVec<T>, index: &Arc<Mutex<EntityIndex<T>>>) -> impl Future<Output = ()> + 'static
+    where T: Entity + 'static {
+
+    let index = index.clone();
+
+    // Mirror of `values`, this is needed in order to know which value was removed.
+    let mut mirror: Vec<Arc<T>> = vec![];
+
+    values.signal_vec().for_each(move |change| {
+        let mut lock = index.lock().unwrap();
+
+        match change {
+            VecDiff::Replace { values } => {
+                lock.clear();
+
+                for value in values.iter() {
+                    lock.insert(value.clone());
+                }
+
+                mirror = values;
+            },
+            VecDiff::InsertAt { index, value } => {
+                lock.insert(value.clone());
+                mirror.insert(index, value);
+            },
+            VecDiff::UpdateAt { index, value } => {
+                let old = std::mem::replace(&mut mirror[index], value.clone());
+                lock.remove(&old);
+                lock.insert(value);
+            },
+            VecDiff::RemoveAt { index } => {
+                let old = mirror.remove(index);
+                lock.remove(&old);
+            },
+            VecDiff::Move { old_index, new_index } => {
+                let value = mirror.remove(old_index);
+                mirror.insert(new_index, value);
+            },
+            VecDiff::Push { value } => {
+                lock.insert(value.clone());
+                mirror.push(value);
+            },
+            VecDiff::Pop {} => {
+                let old = mirror.pop().unwrap();
+                lock.remove(&old);
+            },
+            VecDiff::Clear {} => {
+                lock.clear();
+                mirror.clear();
+            },
+        }
+
+        async {}
+    })
+}
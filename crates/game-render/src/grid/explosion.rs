@@ -1,10 +1,15 @@
-use std::sync::Arc;
-use futures_signals::signal::{Signal, Mutable};
+use std::sync::{Arc, Mutex};
+use std::future::Future;
+use futures_signals::map_ref;
+use futures_signals::signal::{Signal, SignalExt, Mutable};
+use futures_signals::signal_vec::{SignalVec};
+use dominator::clone;
 use rusted_battalions_engine as engine;
 use rusted_battalions_engine::{Node, Size, Offset, Tile, ParentWidth, ParentHeight, Order};
 
 use crate::Game;
-use crate::grid::{Grid, Coord};
+use crate::util::signal::{SortedVec};
+use crate::grid::{EXPLOSION_ANIMATION_TIME, MOVE_EFFECT_ANIMATION_TIME, Grid, Coord};
 
 
 #[derive(Debug, Clone, Copy)]
@@ -33,6 +38,16 @@ pub enum ExplosionAnimation {
 }
 
 impl ExplosionAnimation {
+    /// The effect which is played after the explosion, such as dust after a land explosion.
+    pub(crate) fn debris(&self) -> Option<MoveEffect> {
+        match self {
+            Self::Land | Self::Mega => Some(MoveEffect::Dust),
+            Self::Sea => Some(MoveEffect::Splash),
+            // The debris falls out of view
+            Self::Air => None,
+        }
+    }
+
     fn info(&self) -> ExplosionInfo {
         match self {
             Self::Land => ExplosionInfo {
@@ -99,74 +114,327 @@ impl ExplosionAnimation {
 }
 
 
+/// Small effect which is displayed when a unit moves.
+#[derive(Debug, Clone, Copy)]
+pub enum MoveEffect {
+    /// Land units moving on a road.
+    Dust,
+
+    /// Land units moving on a shoal.
+    Splash,
+
+    /// Naval units moving on water.
+    Wake,
+}
+
+impl MoveEffect {
+    fn info(&self) -> ExplosionInfo {
+        // The movement effects are below the explosions in the effect spritesheet.
+        let tile_y = match self {
+            Self::Dust => 144,
+            Self::Splash => 160,
+            Self::Wake => 176,
+        };
+
+        ExplosionInfo {
+            width: 1.0,
+            height: 1.0,
+
+            offset_x: 0.0,
+            offset_y: 0.0,
+
+            tile_x: 0,
+            tile_y,
+            tile_width: 16,
+            tile_height: 16,
+
+            frames: 4,
+        }
+    }
+}
+
+
+/// All of the effects which can be displayed by the [`ExplosionPool`].
+#[derive(Debug, Clone, Copy)]
+pub enum EffectKind {
+    Explosion(ExplosionAnimation),
+    Move(MoveEffect),
+}
+
+impl EffectKind {
+    fn info(&self) -> ExplosionInfo {
+        match self {
+            Self::Explosion(animation) => animation.info(),
+            Self::Move(effect) => effect.info(),
+        }
+    }
+
+    /// How long the animation lasts, in milliseconds.
+    pub(crate) fn duration(&self) -> f64 {
+        match self {
+            Self::Explosion(_) => EXPLOSION_ANIMATION_TIME,
+            Self::Move(_) => MOVE_EFFECT_ANIMATION_TIME,
+        }
+    }
+}
+
+impl From<ExplosionAnimation> for EffectKind {
+    #[inline]
+    fn from(value: ExplosionAnimation) -> Self {
+        Self::Explosion(value)
+    }
+}
+
+impl From<MoveEffect> for EffectKind {
+    #[inline]
+    fn from(value: MoveEffect) -> Self {
+        Self::Move(value)
+    }
+}
+
+
+struct EffectState {
+    finished: Mutable<bool>,
+    cancelled: Mutable<bool>,
+}
+
+/// Handle to effects which are playing, see [`Grid::play_effects`].
+///
+/// Every clone of the handle refers to the same effects.
+#[derive(Clone)]
+pub struct Effect {
+    state: Arc<EffectState>,
+}
+
+impl Effect {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Arc::new(EffectState {
+                finished: Mutable::new(false),
+                cancelled: Mutable::new(false),
+            }),
+        }
+    }
+
+    /// Resolves when the effects finish playing, or when they are cancelled.
+    pub fn finished(&self) -> impl Future<Output = ()> + Send {
+        let signal = self.state.finished.signal();
+
+        async move {
+            signal.wait_for(true).await;
+        }
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.get()
+    }
+
+    /// Whether the effects were cancelled before they finished.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.get()
+    }
+
+    /// Stops the effects and hides them, this does nothing if they already finished.
+    pub fn cancel(&self) {
+        if !self.is_finished() {
+            self.state.cancelled.set_neq(true);
+        }
+    }
+
+    /// Resolves when [`cancel`](Effect::cancel) is called.
+    pub(crate) fn cancelled(&self) -> impl Future<Output = ()> + Send {
+        let signal = self.state.cancelled.signal();
+
+        async move {
+            signal.wait_for(true).await;
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        self.state.finished.set_neq(true);
+    }
+}
+
+
 pub struct Explosion {
-    coord: Coord,
-    animation: ExplosionAnimation,
+    coord: Mutable<Coord>,
+    animation: Mutable<EffectKind>,
+    active: Mutable<bool>,
     pub percent: Mutable<f32>,
 }
 
 impl Explosion {
-    pub fn new(coord: Coord, animation: ExplosionAnimation) -> Arc<Self> {
+    fn new() -> Arc<Self> {
         Arc::new(Self {
-            coord,
-            animation,
+            coord: Mutable::new(Coord { x: 0.0, y: 0.0 }),
+            animation: Mutable::new(EffectKind::Explosion(ExplosionAnimation::Land)),
+            active: Mutable::new(false),
             percent: Mutable::new(0.0),
         })
     }
 
-    fn tile(&self, info: ExplosionInfo) -> impl Signal<Item = Tile> {
-        let frames = info.frames as f32;
-        let last = info.frames - 1;
+    fn tile(&self) -> impl Signal<Item = Tile> {
+        map_ref! {
+            let animation = self.animation.signal(),
+            let percent = self.percent.signal() => {
+                let info = animation.info();
 
-        let start_y = info.tile_y;
-        let end_y = start_y + info.tile_height;
+                let frames = info.frames as f32;
+                let last = info.frames - 1;
 
-        self.percent.signal_ref(move |percent| {
-            let frame = ((percent * frames) as u32).min(last);
+                let frame = ((percent * frames) as u32).min(last);
 
-            let start_x = info.tile_x + (info.tile_width * frame);
+                let start_x = info.tile_x + (info.tile_width * frame);
 
-            Tile {
-                start_x,
-                start_y,
-                end_x: start_x + info.tile_width,
-                end_y,
+                Tile {
+                    start_x,
+                    start_y: info.tile_y,
+                    end_x: start_x + info.tile_width,
+                    end_y: info.tile_y + info.tile_height,
+                }
             }
-        })
+        }
     }
 
     pub fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
-        let info = this.animation.info();
-
         engine::Sprite::builder()
             .spritesheet(game.spritesheets.effect.clone())
 
-            .apply(|builder| {
-                match this.animation {
-                    // Air explosion is always displayed on top of everything else.
-                    ExplosionAnimation::Air => builder.order(Order::Above(1.0)),
+            .visible_signal(this.active.signal())
+
+            .order_signal({
+                let grid = grid.clone();
+
+                map_ref! {
+                    let animation = this.animation.signal(),
+                    let coord = this.coord.signal() => move {
+                        match animation {
+                            // Air explosion is always displayed on top of everything else.
+                            EffectKind::Explosion(ExplosionAnimation::Air) => Order::Above(1.0),
+
+                            // Movement effects are displayed below the unit.
+                            EffectKind::Move(_) => Order::Parent(grid.order(coord) + (3.0 / 6.0)),
 
-                    // Other explosions follow the usual order, so they can be obscured by mountains / forests.
-                    _ => builder.order(Order::Parent(grid.order(&this.coord) + (5.0 / 6.0))),
+                            // Other explosions follow the usual order, so they can be obscured by mountains / forests.
+                            _ => Order::Parent(grid.order(coord) + (5.0 / 6.0)),
+                        }
+                    }
                 }
             })
 
-            .offset({
-                let (x, y) = grid.tile_offset(&this.coord);
+            .offset_signal({
+                let grid = grid.clone();
 
-                Offset {
-                    x: ParentWidth(x + (info.offset_x * grid.width)),
-                    y: ParentHeight(y + (info.offset_y * grid.height)),
+                map_ref! {
+                    let animation = this.animation.signal(),
+                    let coord = this.coord.signal() => move {
+                        let info = animation.info();
+
+                        let (x, y) = grid.tile_offset(coord);
+
+                        Offset {
+                            x: ParentWidth(x + (info.offset_x * grid.width)),
+                            y: ParentHeight(y + (info.offset_y * grid.height)),
+                        }
+                    }
                 }
             })
 
-            .size(Size {
-                width: ParentWidth(grid.width * info.width),
-                height: ParentHeight(grid.height * info.height),
-            })
+            .size_signal(this.animation.signal_ref(clone!(grid => move |animation| {
+                let info = animation.info();
 
-            .tile_signal(this.tile(info))
+                Size {
+                    width: ParentWidth(grid.width * info.width),
+                    height: ParentHeight(grid.height * info.height),
+                }
+            })))
+
+            .tile_signal(this.tile())
 
             .build()
     }
 }
+
+
+/// Reuses the same effects (and their sprite Nodes) instead of creating new ones for every explosion / movement.
+///
+/// The pool only grows when every explosion is in use, it never shrinks.
+pub(crate) struct ExplosionPool {
+    explosions: SortedVec<Explosion>,
+    free: Mutex<Vec<Arc<Explosion>>>,
+}
+
+impl ExplosionPool {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let free: Vec<Arc<Explosion>> = (0..capacity).map(|_| Explosion::new()).collect();
+
+        Self {
+            explosions: SortedVec::with_values(free.clone()),
+            free: Mutex::new(free),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn signal_vec(&self) -> impl SignalVec<Item = Arc<Explosion>> {
+        self.explosions.signal_vec()
+    }
+
+    /// Returns an unused explosion which is displayed at `coord`.
+    pub(crate) fn acquire<A>(&self, coord: Coord, animation: A) -> Arc<Explosion> where A: Into<EffectKind> {
+        let explosion = self.free.lock().unwrap().pop();
+
+        let explosion = explosion.unwrap_or_else(|| {
+            let explosion = Explosion::new();
+            self.explosions.insert(explosion.clone());
+            explosion
+        });
+
+        explosion.coord.set(coord);
+        explosion.animation.set(animation.into());
+        explosion.percent.set(0.0);
+        explosion.active.set(true);
+
+        explosion
+    }
+
+    /// Hides the explosion and returns it to the pool.
+    pub(crate) fn release(&self, explosion: Arc<Explosion>) {
+        explosion.active.set(false);
+        self.free.lock().unwrap().push(explosion);
+    }
+
+    /// The number of explosions which are displayed.
+    #[cfg(test)]
+    pub(crate) fn active(&self) -> usize {
+        self.explosions.lock_ref().iter().filter(|explosion| explosion.active.get()).count()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use super::{Effect};
+
+    #[test]
+    fn effect_cancel() {
+        let effect = Effect::new();
+
+        effect.cancel();
+        assert!(effect.is_cancelled());
+
+        effect.finish();
+        block_on(effect.finished());
+
+        // Cancelling after the effect finished does nothing
+        let effect = Effect::new();
+
+        effect.finish();
+        effect.cancel();
+
+        assert!(effect.is_finished());
+        assert!(!effect.is_cancelled());
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use rusted_battalions_engine::{RgbaImage};
+
+use crate::grid::{Nation};
+use crate::grid::terrain::{TerrainClass};
+
+pub use rusted_battalions_game_core::map::{MapData, MapBuilding};
+
+
+fn terrain_color(class: TerrainClass) -> [u8; 4] {
+    match class {
+        TerrainClass::Empty => [0, 0, 0, 0],
+        TerrainClass::Grass => [112, 184, 64, 255],
+        TerrainClass::Road { ruins: false } => [184, 176, 152, 255],
+        TerrainClass::Road { ruins: true } => [144, 136, 112, 255],
+        TerrainClass::Bridge { .. } => [208, 200, 176, 255],
+        TerrainClass::Forest => [40, 120, 40, 255],
+        TerrainClass::Mountain { .. } => [152, 104, 56, 255],
+        TerrainClass::Pipeline => [88, 88, 96, 255],
+        TerrainClass::Pipeseam { destroyed: false } => [120, 120, 128, 255],
+        TerrainClass::Pipeseam { destroyed: true } => [144, 136, 112, 255],
+        TerrainClass::Ocean => [40, 88, 216, 255],
+        TerrainClass::River => [88, 152, 240, 255],
+        TerrainClass::Shoal => [232, 216, 144, 255],
+        TerrainClass::Reef => [32, 64, 152, 255],
+    }
+}
+
+fn nation_color(nation: Option<Nation>) -> [u8; 4] {
+    match nation {
+        None => [232, 232, 232, 255],
+        Some(Nation::OrangeStar) => [240, 96, 32, 255],
+        Some(Nation::BlueMoon) => [48, 96, 248, 255],
+        Some(Nation::GreenEarth) => [32, 176, 64, 255],
+        Some(Nation::YellowComet) => [240, 208, 32, 255],
+        Some(Nation::BlackHole) => [96, 48, 120, 255],
+    }
+}
+
+
+pub trait MapDataExt {
+    /// Draws a small preview of the map, with one color for each terrain and building.
+    ///
+    /// This doesn't use the engine or the spritesheets, so it can be used for the map select list
+    /// before the engine has loaded.
+    fn render_thumbnail(&self, width: u32, height: u32) -> RgbaImage;
+}
+
+impl MapDataExt for MapData {
+    fn render_thumbnail(&self, width: u32, height: u32) -> RgbaImage {
+        let buildings = self.buildings.iter()
+            .map(|building| ((building.x, building.y), building.nation))
+            .collect::<HashMap<(u32, u32), Option<Nation>>>();
+
+        RgbaImage::from_fn("thumbnail", width, height, |x, y| {
+            if self.width == 0 || self.height == 0 {
+                return image::Rgba([0, 0, 0, 0]);
+            }
+
+            // Nearest-neighbor scaling, so every pixel is the color of a single tile
+            let x = ((x as u64 * self.width as u64) / width as u64) as u32;
+            let y = ((y as u64 * self.height as u64) / height as u64) as u32;
+
+            let color = match buildings.get(&(x, y)) {
+                Some(nation) => nation_color(*nation),
+                None => terrain_color(self.get(x, y)),
+            };
+
+            image::Rgba(color)
+        })
+    }
+}
+
+
+/// Caches the thumbnails of maps, so that the map select list doesn't
+/// need to redraw the thumbnails while it is scrolled.
+///
+/// The thumbnails are keyed by the map's [`MapData::stable_hash`], so editing a map
+/// will render a new thumbnail.
+pub struct ThumbnailCache {
+    thumbnails: Mutex<HashMap<(u64, u32, u32), Arc<RgbaImage>>>,
+}
+
+impl ThumbnailCache {
+    pub fn new() -> Self {
+        Self {
+            thumbnails: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the thumbnail for the map, it is only rendered the first time.
+    pub fn get(&self, map: &MapData, width: u32, height: u32) -> Arc<RgbaImage> {
+        let key = (map.stable_hash(), width, height);
+
+        self.thumbnails.lock().unwrap()
+            .entry(key)
+            .or_insert_with(|| Arc::new(map.render_thumbnail(width, height)))
+            .clone()
+    }
+
+    /// Removes every thumbnail, this should be called when the map list is closed.
+    pub fn clear(&self) {
+        self.thumbnails.lock().unwrap().clear();
+    }
+}
+
+impl Default for ThumbnailCache {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::{MapDataExt, MapData, MapBuilding, ThumbnailCache, terrain_color, nation_color};
+    use crate::grid::{Nation};
+    use crate::grid::terrain::{TerrainClass};
+    use crate::grid::building::{BuildingClass};
+
+    #[test]
+    fn render_thumbnail() {
+        let mut map = MapData::new(2, 2, TerrainClass::Grass);
+
+        map.set(1, 0, TerrainClass::Ocean);
+
+        map.buildings.push(MapBuilding {
+            x: 0,
+            y: 1,
+            class: BuildingClass::City,
+            nation: Some(Nation::BlueMoon),
+        });
+
+        let thumbnail = map.render_thumbnail(4, 4);
+
+        assert_eq!(thumbnail.image.dimensions(), (4, 4));
+        assert_eq!(thumbnail.image.get_pixel(1, 1).0, terrain_color(TerrainClass::Grass));
+        assert_eq!(thumbnail.image.get_pixel(3, 0).0, terrain_color(TerrainClass::Ocean));
+        assert_eq!(thumbnail.image.get_pixel(0, 3).0, nation_color(Some(Nation::BlueMoon)));
+
+        let cache = ThumbnailCache::new();
+        assert!(Arc::ptr_eq(&cache.get(&map, 4, 4), &cache.get(&map, 4, 4)));
+        assert!(!Arc::ptr_eq(&cache.get(&map, 4, 4), &cache.get(&map, 8, 8)));
+    }
+}
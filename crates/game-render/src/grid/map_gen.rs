@@ -0,0 +1 @@
+pub use rusted_battalions_game_core::map_gen::{MapGenSettings, Symmetry};
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+use futures_signals::signal::{Mutable};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, NodeRef, RealLocation, Size, Offset};
+
+use crate::Game;
+use crate::grid::{Grid, Coord};
+use crate::grid::camera::{ViewportSize};
+
+
+/// Displays a [`Grid`] inside of a region of the screen.
+///
+/// The [`Game`] can display multiple panes at the same time,
+/// for example the main map and a small preview of another map.
+pub struct GridPane {
+    pub grid: Arc<Grid>,
+
+    /// The default is no offset.
+    pub offset: Mutable<Offset>,
+
+    /// The default is the same size as the screen.
+    pub size: Mutable<Size>,
+
+    /// The Node which contains every tile of the grid.
+    node_ref: NodeRef,
+}
+
+impl GridPane {
+    pub fn new(grid: Arc<Grid>) -> Arc<Self> {
+        Arc::new(Self {
+            grid,
+            offset: Mutable::new(Offset::default()),
+            size: Mutable::new(Size::default()),
+            node_ref: NodeRef::new(),
+        })
+    }
+
+    /// Converts a [`Coord`] into the position of the upper-left corner of its tile on the screen.
+    ///
+    /// The position is relative to the screen, from `0.0` to `1.0`, the same as [`Game::hover`].
+    ///
+    /// This uses the location of the grid from the most recent layout, so it takes the pane's
+    /// offset / size and the [`Camera`](crate::grid::camera::Camera) into account.
+    ///
+    /// Returns `None` if the pane hasn't been laid out yet.
+    pub fn coord_to_screen(&self, coord: Coord) -> Option<(f32, f32)> {
+        let location = self.node_ref.location()?;
+        Some(location_coord_to_screen(&location, self.grid.camera.grid_size(), coord))
+    }
+
+    /// Converts a position on the screen into the [`Coord`] of the tile at that position, this is the inverse of [`GridPane::coord_to_screen`].
+    ///
+    /// The position is relative to the screen, from `0.0` to `1.0`, the same as [`Game::hover`].
+    ///
+    /// Returns `None` if the position is outside of the grid, or if the pane hasn't been laid out yet.
+    pub fn screen_to_coord(&self, x: f32, y: f32) -> Option<Coord> {
+        let location = self.node_ref.location()?;
+        location_screen_to_coord(&location, self.grid.camera.grid_size(), x, y)
+    }
+
+    /// Copies the location from the most recent layout into the grid, see [`Grid::coord_to_screen`].
+    pub(crate) fn update_location(&self) {
+        if let Some(location) = self.node_ref.location() {
+            *self.grid.screen_location.lock().unwrap() = Some(location);
+        }
+    }
+
+    pub(crate) fn render(game: &Arc<Game>, this: &Arc<Self>) -> Node {
+        engine::Stack::builder()
+            .offset_signal(this.offset.signal_cloned())
+            .size_signal(this.size.signal_cloned())
+            .child(Grid::render(game, &this.grid, &this.node_ref))
+            .build()
+    }
+}
+
+
+/// The `location` is the location of the Node which contains every tile of the grid.
+pub(super) fn location_coord_to_screen(location: &RealLocation, grid_size: ViewportSize, coord: Coord) -> (f32, f32) {
+    (
+        location.position.x + ((coord.x / grid_size.width) * location.size.width),
+        location.position.y + ((coord.y / grid_size.height) * location.size.height),
+    )
+}
+
+pub(super) fn location_screen_to_coord(location: &RealLocation, grid_size: ViewportSize, x: f32, y: f32) -> Option<Coord> {
+    if location.size.width <= 0.0 || location.size.height <= 0.0 {
+        return None;
+    }
+
+    let x = ((x - location.position.x) / location.size.width) * grid_size.width;
+    let y = ((y - location.position.y) / location.size.height) * grid_size.height;
+
+    if x >= 0.0 && y >= 0.0 && x < grid_size.width && y < grid_size.height {
+        Some(Coord {
+            x: x.floor(),
+            y: y.floor(),
+        })
+
+    } else {
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rusted_battalions_engine::{RealLocation, RealPosition, RealSize};
+    use crate::grid::{Coord};
+    use crate::grid::camera::{ViewportSize};
+    use super::{location_coord_to_screen, location_screen_to_coord};
+
+    #[test]
+    fn screen_to_coord_round_trip() {
+        let grid_size = ViewportSize { width: 10.0, height: 20.0 };
+
+        // A pane in the lower-right of the screen, which is zoomed in and panned so part of the grid is offscreen
+        let location = RealLocation {
+            position: RealPosition { x: 0.25, y: -0.5 },
+            size: RealSize { width: 1.5, height: 3.0 },
+            order: 1.0,
+        };
+
+        for y in 0..20 {
+            for x in 0..10 {
+                let coord = Coord { x: x as f32, y: y as f32 };
+
+                let (screen_x, screen_y) = location_coord_to_screen(&location, grid_size, coord);
+
+                // Any position inside of the tile is converted into the tile's coord
+                let tile_width = location.size.width / grid_size.width;
+                let tile_height = location.size.height / grid_size.height;
+
+                assert_eq!(location_screen_to_coord(&location, grid_size, screen_x + tile_width * 0.5, screen_y + tile_height * 0.5), Some(coord));
+                assert_eq!(location_screen_to_coord(&location, grid_size, screen_x + tile_width * 0.99, screen_y + tile_height * 0.01), Some(coord));
+            }
+        }
+
+        assert_eq!(location_coord_to_screen(&location, grid_size, Coord { x: 0.0, y: 0.0 }), (0.25, -0.5));
+        assert_eq!(location_coord_to_screen(&location, grid_size, Coord { x: 10.0, y: 20.0 }), (1.75, 2.5));
+
+        assert_eq!(location_screen_to_coord(&location, grid_size, 0.2, 0.5), None);
+        assert_eq!(location_screen_to_coord(&location, grid_size, 1.8, 0.5), None);
+        assert_eq!(location_screen_to_coord(&location, grid_size, 0.5, -0.6), None);
+    }
+}
@@ -0,0 +1,217 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, Weak};
+use std::future::Future;
+use futures::stream::StreamExt;
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, SignalExt};
+use futures_signals::signal_vec::{SignalVec};
+use dominator::clone;
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Size, Offset, ParentWidth, ParentHeight, CharSize, ColorRgb, Order};
+use tracing::Instrument;
+
+use crate::Game;
+use crate::util::signal::{SortedVec};
+use crate::grid::{POPUP_ANIMATION_TIME, Grid, Coord};
+use crate::grid::action::{UnitDamaged};
+use crate::grid::unit::{Unit};
+
+
+/// How many tiles the popup floats upwards.
+const POPUP_RISE: f32 = 0.5;
+
+
+/// Starts fast and slows down at the end.
+fn ease_out(percent: f32) -> f32 {
+    1.0 - (1.0 - percent).powi(3)
+}
+
+
+/// Short-lived text which floats above a tile, such as the damage that a unit received.
+pub struct Popup {
+    coord: Mutable<Coord>,
+    text: Mutable<Cow<'static, str>>,
+    color: Mutable<ColorRgb>,
+    active: Mutable<bool>,
+    pub percent: Mutable<f32>,
+}
+
+impl Popup {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            coord: Mutable::new(Coord { x: 0.0, y: 0.0 }),
+            text: Mutable::new(Cow::Borrowed("")),
+            color: Mutable::new(ColorRgb::default()),
+            active: Mutable::new(false),
+            percent: Mutable::new(0.0),
+        })
+    }
+
+    /// The text which is displayed when a unit's health goes from `from` to `to`.
+    ///
+    /// The damage uses the displayed health (`0` to `10`), so it matches the unit's health number.
+    /// Returns `None` if the displayed health didn't change.
+    pub fn damage_text(from: u32, to: u32) -> Option<String> {
+        let damage = Unit::hp_to_display(from).saturating_sub(Unit::hp_to_display(to));
+
+        if damage == 0 {
+            None
+
+        } else {
+            Some(format!("-{}", damage))
+        }
+    }
+
+    pub fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        let tile_width = grid.width;
+        let tile_height = grid.height;
+
+        engine::BitmapText::builder()
+            .font(game.fonts.unifont.clone())
+
+            .visible_signal(this.active.signal())
+
+            .text_signal(this.text.signal_cloned())
+            .text_color_signal(this.color.signal())
+
+            // Always displayed on top of everything else.
+            .order(Order::Above(1.0))
+
+            // Floats upwards above the tile, slowing down as it reaches the top
+            .offset_signal({
+                let grid = grid.clone();
+
+                map_ref! {
+                    let coord = this.coord.signal(),
+                    let percent = this.percent.signal() => move {
+                        let (x, y) = grid.tile_offset(coord);
+
+                        Offset {
+                            x: ParentWidth(x),
+                            y: ParentHeight(y - (tile_height * (0.5 + (ease_out(*percent) * POPUP_RISE)))),
+                        }
+                    }
+                }
+            })
+
+            .size(Size {
+                width: ParentWidth(tile_width),
+                height: ParentHeight(tile_height),
+            })
+
+            .char_size(CharSize {
+                width: ParentWidth(0.5),
+                height: ParentHeight(0.5),
+            })
+
+            .build()
+    }
+}
+
+
+/// Reuses the same popups (and their text Nodes), in the same way as the [`ExplosionPool`](crate::grid::explosion::ExplosionPool).
+///
+/// The pool only grows when every popup is in use, it never shrinks.
+pub(crate) struct PopupPool {
+    popups: SortedVec<Popup>,
+    free: Mutex<Vec<Arc<Popup>>>,
+}
+
+impl PopupPool {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let free: Vec<Arc<Popup>> = (0..capacity).map(|_| Popup::new()).collect();
+
+        Self {
+            popups: SortedVec::with_values(free.clone()),
+            free: Mutex::new(free),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn signal_vec(&self) -> impl SignalVec<Item = Arc<Popup>> {
+        self.popups.signal_vec()
+    }
+
+    /// Returns an unused popup which is displayed above `coord`.
+    pub(crate) fn acquire(&self, coord: Coord, text: Cow<'static, str>, color: ColorRgb) -> Arc<Popup> {
+        let popup = self.free.lock().unwrap().pop();
+
+        let popup = popup.unwrap_or_else(|| {
+            let popup = Popup::new();
+            self.popups.insert(popup.clone());
+            popup
+        });
+
+        popup.coord.set(coord);
+        popup.text.set(text);
+        popup.color.set(color);
+        popup.percent.set(0.0);
+        popup.active.set(true);
+
+        popup
+    }
+
+    /// Hides the popup and returns it to the pool.
+    pub(crate) fn release(&self, popup: Arc<Popup>) {
+        popup.active.set(false);
+        self.free.lock().unwrap().push(popup);
+    }
+}
+
+
+impl Grid {
+    /// Displays text which floats upwards above the coord, and then disappears.
+    pub fn popup(self: &Arc<Self>, coord: Coord, text: Cow<'static, str>, color: ColorRgb) -> impl Future<Output = ()> + Send {
+        let grid = self.clone();
+
+        async move {
+            let popup = grid.popups.acquire(coord, text, color);
+
+            grid.timer(POPUP_ANIMATION_TIME)
+                .for_each(clone!(popup => move |percent| {
+                    popup.percent.set(percent as f32);
+                    async {}
+                })).await;
+
+            grid.popups.release(popup);
+        }.instrument(tracing::debug_span!("popup", ?coord))
+    }
+}
+
+
+/// Displays the damage above every unit which is damaged, see [`Grid::damage_unit`].
+///
+/// This only holds a weak reference to the grid, so it doesn't keep the grid alive.
+pub(crate) fn sync_damage_popups(grid: &Arc<Grid>) -> impl Future<Output = ()> + 'static {
+    let damaged = grid.events.subscribe::<UnitDamaged>();
+    let grid: Weak<Grid> = Arc::downgrade(grid);
+
+    async move {
+        damaged.for_each_concurrent(None, move |event| {
+            let grid = grid.upgrade();
+
+            async move {
+                if let Some(grid) = grid {
+                    if let Some(text) = Popup::damage_text(event.from_hp, event.to_hp) {
+                        grid.popup(event.coord, text.into(), ColorRgb { r: 1.0, g: 0.25, b: 0.2 }).await;
+                    }
+                }
+            }
+        }).await;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Popup;
+
+    #[test]
+    fn damage_text() {
+        assert_eq!(Popup::damage_text(100, 70).as_deref(), Some("-3"));
+        assert_eq!(Popup::damage_text(100, 95).as_deref(), None);
+        assert_eq!(Popup::damage_text(91, 89).as_deref(), Some("-1"));
+        assert_eq!(Popup::damage_text(25, 0).as_deref(), Some("-3"));
+        assert_eq!(Popup::damage_text(50, 80).as_deref(), None);
+    }
+}
@@ -0,0 +1,277 @@
+use std::sync::Arc;
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
+use futures_signals::signal_vec::{SignalVecExt};
+use dominator::clone;
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, NodeRef, RealLocation, Offset, Size, ParentWidth, ParentHeight, SmallestWidth, SmallestHeight};
+
+use crate::ui::{self, Theme};
+use crate::grid::{Grid, Nation};
+use crate::grid::unit::{Unit, UnitId};
+
+
+/// Maximum number of units which are displayed at the same time, the rest are reached by scrolling.
+const SIDEBAR_ROWS: usize = 10;
+
+
+/// Collapsible list of the [`player`](Grid::player)'s units.
+///
+/// Clicking on a unit selects it and pans the camera to it.
+pub struct UnitSidebar {
+    pub open: Mutable<bool>,
+
+    /// Only lists the units which haven't waited yet.
+    pub unmoved_only: Mutable<bool>,
+
+    /// The unit which was most recently selected by clicking or with [`Action::NextUnit`](crate::ui::Action::NextUnit).
+    pub selected: Mutable<Option<UnitId>>,
+
+    /// Index of the first displayed unit.
+    scroll: Mutable<usize>,
+
+    // These are used for clicking
+    header: NodeRef,
+    list: NodeRef,
+}
+
+impl UnitSidebar {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            open: Mutable::new(false),
+            unmoved_only: Mutable::new(false),
+            selected: Mutable::new(None),
+            scroll: Mutable::new(0),
+            header: NodeRef::new(),
+            list: NodeRef::new(),
+        })
+    }
+
+    fn is_listed(unit: &Unit, player: Nation, unmoved_only: bool) -> bool {
+        unit.nation == player && !(unmoved_only && unit.waited.get())
+    }
+
+    /// The listed units, sorted by [`UnitId`] so the order doesn't change.
+    fn units(&self, grid: &Grid) -> Vec<Arc<Unit>> {
+        let player = grid.player.get();
+        let unmoved_only = self.unmoved_only.get();
+
+        let mut units = grid.units.lock_ref().iter()
+            .filter(|unit| Self::is_listed(unit, player, unmoved_only))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        units.sort_by_key(|unit| unit.id);
+        units
+    }
+
+    fn units_signal(this: &Arc<Self>, grid: &Arc<Grid>) -> impl Signal<Item = Vec<Arc<Unit>>> {
+        grid.units.signal_vec()
+            .filter_signal_cloned(clone!(this, grid => move |unit| {
+                let nation = unit.nation;
+
+                map_ref! {
+                    let player = grid.player.signal(),
+                    let unmoved_only = this.unmoved_only.signal(),
+                    let waited = unit.waited.signal() => {
+                        nation == *player && !(*unmoved_only && *waited)
+                    }
+                }
+            }))
+            .to_signal_map(|units| {
+                let mut units = units.to_vec();
+                units.sort_by_key(|unit| unit.id);
+                units
+            })
+    }
+
+    fn max_scroll(len: usize) -> usize {
+        len.saturating_sub(SIDEBAR_ROWS)
+    }
+
+    /// Scrolls the list by the number of rows, negative numbers scroll upwards.
+    pub fn scroll_by(&self, grid: &Grid, rows: i32) {
+        let max = Self::max_scroll(self.units(grid).len());
+
+        let mut scroll = self.scroll.lock_mut();
+
+        let new_scroll = (*scroll as i64 + rows as i64).clamp(0, max as i64) as usize;
+
+        if *scroll != new_scroll {
+            *scroll = new_scroll;
+        }
+    }
+
+    /// Selects the unit, scrolls the list so that the unit is visible, and pans the camera to the unit.
+    pub fn select(&self, grid: &Arc<Grid>, unit: &Arc<Unit>) {
+        self.selected.set_neq(Some(unit.id));
+
+        if let Some(index) = self.units(grid).iter().position(|x| x.id == unit.id) {
+            let mut scroll = self.scroll.lock_mut();
+
+            if index < *scroll {
+                *scroll = index;
+
+            } else if index >= *scroll + SIDEBAR_ROWS {
+                *scroll = index + 1 - SIDEBAR_ROWS;
+            }
+        }
+
+        grid.spawn_future(grid.pan_to(unit.coord.get()));
+    }
+
+    fn hit(node_ref: &NodeRef, x: f32, y: f32) -> Option<RealLocation> {
+        node_ref.location().filter(|location| {
+            x >= location.position.x &&
+            y >= location.position.y &&
+            x < location.position.x + location.size.width &&
+            y < location.position.y + location.size.height
+        })
+    }
+
+    /// Returns whether the screen position is on top of the list of units.
+    pub(crate) fn contains(&self, x: f32, y: f32) -> bool {
+        self.open.get() && Self::hit(&self.list, x, y).is_some()
+    }
+
+    /// Handles a click at the screen position, returns `true` if the click was on the sidebar.
+    pub(crate) fn click(&self, grid: &Arc<Grid>, x: f32, y: f32) -> bool {
+        if !self.open.get() {
+            return false;
+        }
+
+        if Self::hit(&self.header, x, y).is_some() {
+            self.unmoved_only.set(!self.unmoved_only.get());
+            self.scroll.set(0);
+            true
+
+        } else if let Some(location) = Self::hit(&self.list, x, y) {
+            let units = self.units(grid);
+
+            let scroll = self.scroll.get().min(Self::max_scroll(units.len()));
+            let rows = units.len().saturating_sub(scroll).min(SIDEBAR_ROWS);
+
+            // Every row has the same height
+            let row = (((y - location.position.y) / location.size.height) * rows as f32) as usize;
+
+            if let Some(unit) = units.get(scroll + row) {
+                self.select(grid, unit);
+            }
+
+            true
+
+        } else {
+            false
+        }
+    }
+
+    fn render_row(theme: &Theme, this: &Arc<Self>, unit: &Arc<Unit>) -> Node {
+        let id = unit.id;
+        let class = unit.class;
+
+        engine::BitmapText::builder()
+            .text_signal(map_ref! {
+                let hp = unit.display_hp(),
+                let fuel = unit.fuel.signal(),
+                let selected = this.selected.signal_ref(move |selected| *selected == Some(id)) => {
+                    let marker = if *selected { '>' } else { ' ' };
+
+                    format!("{}{:<11}HP{:>3} Fuel{:>3}", marker, format!("{:?}", class), hp, fuel).into()
+                }
+            })
+            .font(theme.text.font.clone())
+            .text_color(theme.text.color)
+            .char_size(theme.text.char_size)
+            .size(Size {
+                width: SmallestWidth(1.0),
+                height: SmallestHeight(1.0),
+            })
+            .build()
+    }
+
+    pub(crate) fn render(theme: &Theme, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        let page = map_ref! {
+            let units = Self::units_signal(this, grid),
+            let scroll = this.scroll.signal() => {
+                let start = (*scroll).min(Self::max_scroll(units.len()));
+                let end = (start + SIDEBAR_ROWS).min(units.len());
+                units[start..end].to_vec()
+            }
+        };
+
+        ui::SpriteBorder::builder()
+            .apply(|builder| {
+                builder
+                    .offset(Offset {
+                        x: ParentWidth(0.7),
+                        y: ParentHeight(0.05),
+                    })
+                    .size(Size {
+                        width: SmallestWidth(1.0),
+                        height: SmallestHeight(1.0),
+                    })
+            })
+
+            .theme(&theme.dialog)
+
+            .center(engine::Column::builder()
+                .size(Size {
+                    width: SmallestWidth(1.0),
+                    height: SmallestHeight(1.0),
+                })
+
+                .child(engine::BitmapText::builder()
+                    .node_ref(&this.header)
+                    .text_signal(this.unmoved_only.signal_ref(|unmoved_only| {
+                        let check = if *unmoved_only { 'x' } else { ' ' };
+                        format!("Units    [{}] Unmoved only", check).into()
+                    }))
+                    .font(theme.text.font.clone())
+                    .text_color(theme.text.color)
+                    .char_size(theme.text.char_size)
+                    .size(Size {
+                        width: SmallestWidth(1.0),
+                        height: SmallestHeight(1.0),
+                    })
+                    .build())
+
+                // Which nations are on the same team as the player
+                .child(engine::BitmapText::builder()
+                    .text_signal(map_ref! {
+                        let player = grid.player.signal(),
+                        let teams = grid.teams.signal_cloned() => {
+                            let allies = teams.allies(*player).map(|nation| format!("{:?}", nation)).collect::<Vec<_>>();
+
+                            if allies.is_empty() {
+                                "Allies   None".into()
+
+                            } else {
+                                format!("Allies   {}", allies.join(", ")).into()
+                            }
+                        }
+                    })
+                    .font(theme.text.font.clone())
+                    .text_color(theme.text.color)
+                    .char_size(theme.text.char_size)
+                    .size(Size {
+                        width: SmallestWidth(1.0),
+                        height: SmallestHeight(1.0),
+                    })
+                    .build())
+
+                .child(engine::Column::builder()
+                    .node_ref(&this.list)
+                    .size(Size {
+                        width: SmallestWidth(1.0),
+                        height: SmallestHeight(1.0),
+                    })
+                    .children_signal_vec(page.to_signal_vec().map(clone!(theme, this => move |unit| {
+                        Self::render_row(&theme, &this, &unit)
+                    })))
+                    .build())
+
+                .build())
+
+            .build()
+    }
+}
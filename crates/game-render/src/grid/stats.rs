@@ -0,0 +1,192 @@
+use std::sync::{Arc, Weak};
+use std::future::Future;
+use futures::stream::{StreamExt, select};
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, Signal, SignalExt, always};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Offset, Size, ParentWidth, ParentHeight, SmallestWidth, SmallestHeight};
+
+use crate::ui::{self, Theme};
+use crate::grid::{Grid, Nation};
+use crate::grid::action::{UnitDamaged, UnitDestroyed};
+
+
+/// The statistics of a single nation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NationStats {
+    /// The health which this nation's units removed from enemy units.
+    pub damage_dealt: u32,
+
+    /// The health which this nation's units lost.
+    pub damage_taken: u32,
+
+    /// How many of this nation's units were destroyed.
+    pub units_lost: u32,
+}
+
+
+/// Statistics for every nation, they are updated by subscribing to the grid's events.
+///
+/// Replays publish the same events, so the statistics are also recalculated when a replay is played.
+pub struct MatchStats {
+    /// Whether the intel panel is displayed.
+    pub visible: Mutable<bool>,
+
+    /// The stats of each nation, in the same order as [`Nation::ALL`].
+    nations: Vec<Mutable<NationStats>>,
+}
+
+impl MatchStats {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            visible: Mutable::new(false),
+            nations: Nation::ALL.iter().map(|_| Mutable::new(NationStats::default())).collect(),
+        })
+    }
+
+    fn nation(&self, nation: Nation) -> &Mutable<NationStats> {
+        let index = Nation::ALL.iter().position(|x| *x == nation).unwrap();
+        &self.nations[index]
+    }
+
+    #[inline]
+    pub fn get(&self, nation: Nation) -> NationStats {
+        self.nation(nation).get()
+    }
+
+    #[inline]
+    pub fn signal(&self, nation: Nation) -> impl Signal<Item = NationStats> {
+        self.nation(nation).signal()
+    }
+
+    fn update(&self, nation: Nation, f: impl FnOnce(&mut NationStats)) {
+        f(&mut self.nation(nation).lock_mut());
+    }
+
+    fn damaged(&self, event: &UnitDamaged) {
+        let damage = event.from_hp.saturating_sub(event.to_hp);
+
+        if damage > 0 {
+            self.update(event.unit.nation, |stats| stats.damage_taken += damage);
+
+            if let Some(attacker) = &event.attacker {
+                self.update(attacker.nation, |stats| stats.damage_dealt += damage);
+            }
+        }
+    }
+
+    fn destroyed(&self, event: &UnitDestroyed) {
+        self.update(event.unit.nation, |stats| stats.units_lost += 1);
+    }
+}
+
+
+enum StatsEvent {
+    Damaged(UnitDamaged),
+    Destroyed(UnitDestroyed),
+}
+
+/// Keeps the [`MatchStats`] up to date.
+pub(crate) fn sync_stats(grid: &Arc<Grid>) -> impl Future<Output = ()> + 'static {
+    let damaged = grid.events.subscribe::<UnitDamaged>().map(StatsEvent::Damaged);
+    let destroyed = grid.events.subscribe::<UnitDestroyed>().map(StatsEvent::Destroyed);
+    let grid: Weak<Grid> = Arc::downgrade(grid);
+
+    async move {
+        select(damaged, destroyed).for_each(move |event| {
+            if let Some(grid) = grid.upgrade() {
+                match event {
+                    StatsEvent::Damaged(event) => grid.stats.damaged(&event),
+                    StatsEvent::Destroyed(event) => grid.stats.destroyed(&event),
+                }
+            }
+
+            async {}
+        }).await;
+    }
+}
+
+
+/// One line for each nation which has any stats.
+fn describe(nations: &[(Nation, NationStats)]) -> String {
+    let mut output = "Nation      Dealt Taken Lost".to_string();
+
+    for (nation, stats) in nations {
+        output.push_str(&format!("\n{:<11}{:>6}{:>6}{:>5}", format!("{:?}", nation), stats.damage_dealt, stats.damage_taken, stats.units_lost));
+    }
+
+    output
+}
+
+
+/// Panel in the upper-left corner which displays the [`MatchStats`], see [`Action::Intel`](crate::ui::Action::Intel).
+pub(crate) fn intel_panel(theme: &Theme, grid: &Arc<Grid>) -> Node {
+    let stats = grid.stats.clone();
+
+    let nations = Nation::ALL.iter().fold(always(vec![]).boxed(), |output, nation| {
+        let nation = *nation;
+
+        map_ref! {
+            let output = output,
+            let stats = stats.signal(nation) => {
+                let mut output = output.clone();
+
+                if *stats != NationStats::default() {
+                    output.push((nation, *stats));
+                }
+
+                output
+            }
+        }.boxed()
+    });
+
+    ui::SpriteBorder::builder()
+        .apply(|builder| {
+            builder
+                .visible_signal(grid.stats.visible.signal())
+                .offset(Offset {
+                    x: ParentWidth(0.02),
+                    y: ParentHeight(0.02),
+                })
+                .size(Size {
+                    width: SmallestWidth(1.0),
+                    height: SmallestHeight(1.0),
+                })
+        })
+
+        .theme(&theme.dialog)
+
+        .center(engine::BitmapText::builder()
+            .text_signal(nations.map(|nations| describe(&nations).into()))
+            .font(theme.text.font.clone())
+            .text_color(theme.text.color)
+            .char_size(theme.text.char_size)
+            .size(Size {
+                width: SmallestWidth(1.0),
+                height: SmallestHeight(1.0),
+            })
+            .build())
+
+        .build()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, NationStats};
+    use crate::grid::{Nation};
+
+    #[test]
+    fn describe_stats() {
+        let stats = NationStats {
+            damage_dealt: 120,
+            damage_taken: 35,
+            units_lost: 1,
+        };
+
+        assert_eq!(
+            describe(&[(Nation::OrangeStar, stats)]),
+            "Nation      Dealt Taken Lost\nOrangeStar    120    35    1",
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::Arc;
+use futures_signals::signal::{Mutable, SignalExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Offset, Anchor, Size, Zero, ParentHeight, SmallestWidth, SmallestHeight};
+
+use crate::ui::{self, Screen, Theme};
+use crate::grid::{Grid, Nation};
+
+
+/// The status of a single nation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ArmyStatus {
+    nation: Nation,
+
+    /// The number of units which the player can see.
+    units: u32,
+
+    /// Whether some of the units might be hidden by fog, this is `true` for every enemy.
+    hidden: bool,
+
+    properties: u32,
+    income: u32,
+}
+
+
+/// Returns the status of every nation which has units or properties.
+///
+/// Enemy units which are hidden by fog aren't counted, properties are always visible.
+fn army_status(grid: &Grid, reveal_fog: bool) -> Vec<ArmyStatus> {
+    let player = grid.player.get();
+    let teams = grid.teams.get_cloned();
+    let rules = grid.rules.lock_ref();
+
+    let units = grid.units.lock_ref();
+    let buildings = grid.buildings.lock_ref();
+
+    Nation::ALL.iter().filter_map(|nation| {
+        let nation = *nation;
+
+        let known = reveal_fog || teams.are_allies(player, nation);
+
+        let units = units.iter()
+            .filter(|unit| unit.nation == nation && (known || !unit.fog.get()))
+            .count() as u32;
+
+        let properties = buildings.iter()
+            .filter(|building| building.nation.get() == Some(nation) && building.class.info().income)
+            .count() as u32;
+
+        if units > 0 || properties > 0 {
+            Some(ArmyStatus {
+                nation,
+                units,
+                hidden: !known,
+                properties,
+                income: rules.income_for(properties),
+            })
+
+        } else {
+            None
+        }
+    }).collect()
+}
+
+
+/// One line for each nation, the columns are aligned because the font is monospace.
+fn describe(armies: &[ArmyStatus]) -> String {
+    let mut output = "Nation      Units Props Income".to_string();
+
+    for army in armies {
+        // The number of units is followed by a ? if some of them might be hidden
+        let hidden = if army.hidden { "?" } else { " " };
+
+        output.push_str(&format!("\n{:<11}{:>5}{}{:>6}{:>7}", format!("{:?}", army.nation), army.units, hidden, army.properties, army.income));
+    }
+
+    output
+}
+
+
+/// Full-screen summary of every army, see [`Action::Status`](crate::ui::Action::Status).
+///
+/// The status is calculated when the screen is displayed, it doesn't update while it is open.
+pub(crate) fn status_screen(theme: Mutable<Theme>, grid: Mutable<Arc<Grid>>, reveal_fog: bool) -> Screen {
+    Arc::new(move || {
+        let text = describe(&army_status(&grid.lock_ref(), reveal_fog));
+
+        engine::Stack::builder()
+            .child_signal(theme.signal_cloned().map(move |theme| {
+                Some(ui::SpriteBorder::builder()
+                    .apply(|builder| {
+                        builder
+                            .anchor_margin(Anchor::Top, Offset {
+                                x: Zero,
+                                y: ParentHeight(0.3),
+                            })
+                            .size(Size {
+                                width: SmallestWidth(1.0),
+                                height: SmallestHeight(1.0),
+                            })
+                    })
+
+                    .theme(&theme.dialog)
+
+                    .center(engine::BitmapText::builder()
+                        .text(format!("Status\n\n{}", text).into())
+                        .font(theme.text.font.clone())
+                        .text_color(theme.text.color)
+                        .char_size(theme.text.char_size)
+                        .size(Size {
+                            width: SmallestWidth(1.0),
+                            height: SmallestHeight(1.0),
+                        })
+                        .build())
+
+                    .build())
+            }))
+            .build()
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, ArmyStatus};
+    use crate::grid::{Nation};
+
+    #[test]
+    fn describe_armies() {
+        let armies = [
+            ArmyStatus {
+                nation: Nation::OrangeStar,
+                units: 12,
+                hidden: false,
+                properties: 5,
+                income: 5000,
+            },
+            ArmyStatus {
+                nation: Nation::BlueMoon,
+                units: 3,
+                hidden: true,
+                properties: 4,
+                income: 4000,
+            },
+        ];
+
+        assert_eq!(
+            describe(&armies),
+            "Nation      Units Props Income\nOrangeStar    12      5   5000\nBlueMoon       3?     4   4000",
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::sync::Arc;
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, SignalExt};
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+use dominator::clone;
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Size, Offset, ParentWidth, ParentHeight, Order, Tile};
+
+use crate::Game;
+use crate::grid::{OVERLAY_TILE_SIZE, TARGET_PULSE_TIME, Grid};
+use crate::grid::unit::{Unit, UnitId};
+
+
+/// Published when the player confirms the target of an attack.
+#[derive(Clone)]
+pub struct TargetSelected {
+    pub attacker: Arc<Unit>,
+    pub target: Arc<Unit>,
+}
+
+
+/// Lets the player choose which unit to attack.
+///
+/// Every unit which can be attacked pulses, and the selected unit also grows and shrinks.
+/// Left / right moves the selection to the previous / next target, which works the same for direct and indirect attacks.
+pub struct Targeting {
+    attacker: Mutable<Option<Arc<Unit>>>,
+
+    /// The units which can be attacked, in reading order (top to bottom, left to right).
+    targets: MutableVec<Arc<Unit>>,
+
+    selected: Mutable<Option<UnitId>>,
+}
+
+impl Targeting {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            attacker: Mutable::new(None),
+            targets: MutableVec::new(),
+            selected: Mutable::new(None),
+        })
+    }
+
+    /// Whether the player is currently choosing a target.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.attacker.lock_ref().is_some()
+    }
+
+    /// Returns the target which is currently selected.
+    pub fn selected(&self) -> Option<Arc<Unit>> {
+        let selected = self.selected.get()?;
+        self.targets.lock_ref().iter().find(|unit| unit.id == selected).cloned()
+    }
+
+    fn select_offset(&self, offset: isize) -> bool {
+        let targets = self.targets.lock_ref();
+
+        if targets.is_empty() {
+            return false;
+        }
+
+        let len = targets.len() as isize;
+
+        let index = self.selected.get()
+            .and_then(|selected| targets.iter().position(|unit| unit.id == selected))
+            .map(|index| (index as isize + offset).rem_euclid(len))
+            .unwrap_or(0);
+
+        self.selected.set_neq(Some(targets[index as usize].id));
+        true
+    }
+
+    /// Selects the next target, after the last target it wraps around to the first target.
+    #[inline]
+    pub fn next(&self) -> bool {
+        self.select_offset(1)
+    }
+
+    /// Selects the previous target, before the first target it wraps around to the last target.
+    #[inline]
+    pub fn previous(&self) -> bool {
+        self.select_offset(-1)
+    }
+
+    /// Stops targeting without attacking.
+    pub fn cancel(&self) {
+        self.attacker.set(None);
+        self.selected.set(None);
+        self.targets.lock_mut().clear();
+    }
+
+    pub(crate) fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        engine::Stack::builder()
+            .order(Order::Parent(0.0))
+            .children_signal_vec(this.targets.signal_vec_cloned().map(clone!(game, grid, this => move |target| {
+                let coord = target.coord.get();
+
+                let (x, y) = grid.tile_offset(&coord);
+
+                let tile_width = grid.width;
+                let tile_height = grid.height;
+
+                // Goes from 0.0 to 1.0 and back again
+                let pulse = grid.animation(TARGET_PULSE_TIME).map(|time| {
+                    (((time * std::f64::consts::TAU).cos() * -0.5) + 0.5) as f32
+                }).broadcast();
+
+                let is_selected = this.selected.signal_ref(move |selected| *selected == Some(target.id)).broadcast();
+
+                engine::Sprite::builder()
+                    .spritesheet(game.spritesheets.overlay.clone())
+
+                    .tile(Tile {
+                        start_x: 0,
+                        start_y: 0,
+                        end_x: OVERLAY_TILE_SIZE,
+                        end_y: OVERLAY_TILE_SIZE,
+                    })
+
+                    .alpha_signal(pulse.signal().map(|pulse| 0.25 + (pulse * 0.35)))
+
+                    // The selected target grows and shrinks around the center of its tile
+                    .offset_signal(map_ref! {
+                        let pulse = pulse.signal(),
+                        let is_selected = is_selected.signal() => move {
+                            let grow = if *is_selected { *pulse * 0.15 } else { 0.0 };
+
+                            Offset {
+                                x: ParentWidth(x - (tile_width * grow * 0.5)),
+                                y: ParentHeight(y - (tile_height * grow * 0.5)),
+                            }
+                        }
+                    })
+
+                    .size_signal(map_ref! {
+                        let pulse = pulse.signal(),
+                        let is_selected = is_selected.signal() => move {
+                            let grow = if *is_selected { *pulse * 0.15 } else { 0.0 };
+
+                            Size {
+                                width: ParentWidth(tile_width * (1.0 + grow)),
+                                height: ParentHeight(tile_height * (1.0 + grow)),
+                            }
+                        }
+                    })
+
+                    // Above the units
+                    .order(Order::Parent(grid.order(&coord) + (5.0 / 6.0)))
+
+                    .build()
+            })))
+            .build()
+    }
+}
+
+
+impl Grid {
+    /// Starts targeting with every enemy unit which `attacker` can attack from its current tile.
+    ///
+    /// Units which are hidden by fog can't be targeted, and allied units can't be targeted.
+    ///
+    /// Returns `false` if there aren't any targets, in which case targeting doesn't start.
+    pub fn start_targeting(&self, attacker: &Arc<Unit>) -> bool {
+        let Some(range) = attacker.class.attack_range() else {
+            return false;
+        };
+
+        let (x, y) = attacker.coord.get().tile();
+
+        let teams = self.teams.lock_ref();
+
+        let mut targets = self.units.lock_ref().iter()
+            .filter(|unit| {
+                let (unit_x, unit_y) = unit.coord.get().tile();
+                let distance = x.abs_diff(unit_x) + y.abs_diff(unit_y);
+
+                !teams.are_allies(unit.nation, attacker.nation) && !unit.fog.get() && range.contains(distance)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        drop(teams);
+
+        if targets.is_empty() {
+            return false;
+        }
+
+        targets.sort_by_key(|unit| {
+            let (x, y) = unit.coord.get().tile();
+            (y, x)
+        });
+
+        let targeting = &self.targeting;
+
+        targeting.selected.set(Some(targets[0].id));
+        targeting.targets.lock_mut().replace_cloned(targets);
+        targeting.attacker.set(Some(attacker.clone()));
+
+        true
+    }
+
+    /// Stops targeting and publishes [`TargetSelected`] for the selected target.
+    ///
+    /// Returns the selected target, or `None` if it isn't targeting.
+    pub fn confirm_target(&self) -> Option<Arc<Unit>> {
+        let attacker = self.targeting.attacker.get_cloned()?;
+        let target = self.targeting.selected()?;
+
+        self.targeting.cancel();
+
+        self.events.publish(TargetSelected {
+            attacker,
+            target: target.clone(),
+        });
+
+        Some(target)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::{Grid, Coord, Nation};
+    use crate::grid::map::{MapData};
+    use crate::grid::terrain::{TerrainClass};
+    use crate::grid::unit::{Unit, UnitClass};
+
+    fn unit(grid: &Grid, x: f32, y: f32, class: UnitClass, nation: Nation) -> std::sync::Arc<Unit> {
+        let unit = grid.new_unit(Coord { x, y }, class, nation);
+        assert!(grid.place_unit(unit.clone()));
+        unit
+    }
+
+    #[test]
+    fn targets() {
+        let grid = Grid::from_map(&MapData::new(8, 8, TerrainClass::Grass));
+
+        let tank = unit(&grid, 3.0, 3.0, UnitClass::Tank, Nation::OrangeStar);
+        let artillery = unit(&grid, 0.0, 0.0, UnitClass::Artillery, Nation::OrangeStar);
+
+        let right = unit(&grid, 4.0, 3.0, UnitClass::Infantry, Nation::BlueMoon);
+        let above = unit(&grid, 3.0, 2.0, UnitClass::Infantry, Nation::BlueMoon);
+        let hidden = unit(&grid, 2.0, 3.0, UnitClass::Infantry, Nation::BlueMoon);
+
+        // Allies are never targeted
+        unit(&grid, 3.0, 4.0, UnitClass::Infantry, Nation::OrangeStar);
+
+        let far = unit(&grid, 0.0, 2.0, UnitClass::Infantry, Nation::BlueMoon);
+
+        hidden.fog.set(true);
+
+        assert!(grid.start_targeting(&tank));
+        assert!(grid.targeting.is_active());
+
+        // Reading order, so the unit above is first
+        assert_eq!(grid.targeting.selected().unwrap().id, above.id);
+        assert!(grid.targeting.next());
+        assert_eq!(grid.targeting.selected().unwrap().id, right.id);
+        assert!(grid.targeting.next());
+        assert_eq!(grid.targeting.selected().unwrap().id, above.id);
+        assert!(grid.targeting.previous());
+        assert_eq!(grid.targeting.selected().unwrap().id, right.id);
+
+        assert_eq!(grid.confirm_target().unwrap().id, right.id);
+        assert!(!grid.targeting.is_active());
+        assert!(grid.confirm_target().is_none());
+
+        // Indirect units can't attack adjacent units
+        assert!(grid.start_targeting(&artillery));
+        assert_eq!(grid.targeting.selected().unwrap().id, far.id);
+        assert!(grid.targeting.next());
+        assert_eq!(grid.targeting.selected().unwrap().id, far.id);
+
+        grid.targeting.cancel();
+        assert!(!grid.targeting.is_active());
+    }
+}
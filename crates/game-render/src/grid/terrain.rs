@@ -1,11 +1,13 @@
 use std::sync::Arc;
-use futures_signals::signal::{SignalExt};
 use rusted_battalions_engine as engine;
-use rusted_battalions_engine::{SpriteBuilder, Size, Offset, Tile, Node, ParentWidth, ParentHeight, Order};
+use rusted_battalions_engine::{SpriteBuilder, Size, Offset, Tile, Node, ParentWidth, ParentHeight, Order, SpriteAnimation, AnimationMode};
 
 use crate::grid::{Game, Grid, Coord, TERRAIN_ANIMATION_TIME, FOG_ANIMATION_TIME};
+use crate::grid::map::{MapData};
 use crate::util::random::{random};
 
+pub use rusted_battalions_game_core::terrain::{Orientation, TerrainClass, TerrainInfo, MovementClass};
+
 mod sea;
 mod river;
 mod shoal;
@@ -124,9 +126,9 @@ pub struct Terrain {
 }
 
 impl Terrain {
-    /*pub fn from_map(map: &Map) -> Self {
-        let tiles = map.tiles().map(|tile| {
-            TerrainTile::new(tile.coord.x, tile.coord.y, tile.class)
+    pub fn from_map(map: &MapData) -> Self {
+        let tiles = map.tiles().map(|(x, y, class)| {
+            TerrainTile::new(x, y, class)
         }).collect();
 
         let mut terrain = Terrain {
@@ -138,7 +140,7 @@ impl Terrain {
         terrain.update_tiles();
 
         terrain
-    }*/
+    }
 
 
     pub fn new(width: u32, height: u32) -> Self {
@@ -208,40 +210,40 @@ impl Terrain {
                 let mut adjacent = Adjacent::default();
 
                 if let Some(down) = self.get_checked(tile.x, tile.y + 1) {
-                    adjacent.down = down.class.flag();
+                    adjacent.down = TerrainFlag::from_tile(&down.class);
                 }
 
                 if let Some(right) = self.get_checked(tile.x + 1, tile.y) {
-                    adjacent.right = right.class.flag();
+                    adjacent.right = TerrainFlag::from_tile(&right.class);
                 }
 
                 if let Some(down_right) = self.get_checked(tile.x + 1, tile.y + 1) {
-                    adjacent.down_right = down_right.class.flag();
+                    adjacent.down_right = TerrainFlag::from_tile(&down_right.class);
                 }
 
                 if let Some(x) = tile.x.checked_sub(1) {
                     if let Some(left) = self.get_checked(x, tile.y) {
-                        adjacent.left = left.class.flag();
+                        adjacent.left = TerrainFlag::from_tile(&left.class);
                     }
 
                     if let Some(down_left) = self.get_checked(x, tile.y + 1) {
-                        adjacent.down_left = down_left.class.flag();
+                        adjacent.down_left = TerrainFlag::from_tile(&down_left.class);
                     }
 
                     if let Some(y) = tile.y.checked_sub(1) {
                         if let Some(up_left) = self.get_checked(x, y) {
-                            adjacent.up_left = up_left.class.flag();
+                            adjacent.up_left = TerrainFlag::from_tile(&up_left.class);
                         }
                     }
                 }
 
                 if let Some(y) = tile.y.checked_sub(1) {
                     if let Some(up) = self.get_checked(tile.x, y) {
-                        adjacent.up = up.class.flag();
+                        adjacent.up = TerrainFlag::from_tile(&up.class);
                     }
 
                     if let Some(up_right) = self.get_checked(tile.x + 1, y) {
-                        adjacent.up_right = up_right.class.flag();
+                        adjacent.up_right = TerrainFlag::from_tile(&up_right.class);
                     }
                 }
 
@@ -435,74 +437,9 @@ impl TerrainRule {
 }
 
 
-#[derive(Debug, Clone, Copy)]
-pub enum Orientation {
-    Horizontal,
-    Vertical,
-}
-
-
-#[derive(Debug, Clone, Copy)]
-pub enum TerrainClass {
-    Empty,
-    Grass,
-    Road {
-        ruins: bool,
-    },
-    Bridge {
-        orientation: Orientation,
-    },
-    Forest,
-    Mountain {
-        variant: u32,
-    },
-    Pipeline,
-    Pipeseam {
-        destroyed: bool,
-    },
-    Ocean,
-    River,
-    Shoal,
-    Reef,
-}
-
-impl TerrainClass {
-    pub const ALL: &[Self] = &[
-        Self::Empty,
-        Self::Grass,
-        Self::Road { ruins: false },
-        Self::Bridge { orientation: Orientation::Horizontal },
-        Self::Forest,
-        Self::Mountain { variant: 0 },
-        Self::Pipeline,
-        Self::Pipeseam { destroyed: false, },
-        Self::Ocean,
-        Self::River,
-        Self::Shoal,
-        Self::Reef,
-    ];
-
-    fn flag(&self) -> TerrainFlag {
-        match self {
-            Self::Empty => TerrainFlag::EMPTY,
-            Self::Grass => TerrainFlag::PLAIN,
-            Self::Road { .. } => TerrainFlag::ROAD,
-            Self::Bridge { .. } => TerrainFlag::BRIDGE,
-            Self::Forest => TerrainFlag::WOOD,
-            Self::Mountain { .. } => TerrainFlag::MOUNTAIN,
-            Self::Pipeline => TerrainFlag::PIPELINE,
-            Self::Pipeseam { .. } => TerrainFlag::PIPESEAM,
-            Self::Ocean => TerrainFlag::SEA,
-            Self::River => TerrainFlag::RIVER,
-            Self::Shoal => TerrainFlag::SHOAL,
-            Self::Reef => TerrainFlag::REEF,
-        }
-    }
-
-    pub fn random_mountain() -> Self {
-        Self::Mountain {
-            variant: (random() * 3.0) as u32,
-        }
+pub(crate) fn random_mountain() -> TerrainClass {
+    TerrainClass::Mountain {
+        variant: (random() * 3.0) as u32,
     }
 }
 
@@ -812,26 +749,30 @@ impl TerrainTile {
             height: ParentHeight(grid.height * ratio),
         };
 
-        fn tile_animation(grid: &Arc<Grid>, info: TileInfo) -> impl FnOnce(SpriteBuilder) -> SpriteBuilder + '_ {
+        fn tile_animation(info: TileInfo) -> impl FnOnce(SpriteBuilder) -> SpriteBuilder {
             move |builder| {
                 let TileInfo { tile_x, tile_y, tile_width, tile_height, frame_info } = info;
 
-                let mut tile = Tile {
+                let tile = Tile {
                     start_x: tile_x,
                     start_y: tile_y,
                     end_x: tile_x + tile_width,
                     end_y: tile_y + tile_height,
                 };
 
+                let builder = builder.tile(tile);
+
                 if let Some(frame_info) = frame_info {
-                    builder.tile_signal(grid.animation_pendulum(TERRAIN_ANIMATION_TIME, frame_info.frames).map(move |frame| {
-                        tile.start_y = tile_y + (frame * frame_info.offset_y);
-                        tile.end_y = tile.start_y + tile_width;
-                        tile
+                    builder.animation(Some(SpriteAnimation {
+                        frames: frame_info.frames,
+                        duration: TERRAIN_ANIMATION_TIME as f32,
+                        offset_x: 0,
+                        offset_y: frame_info.offset_y as i32,
+                        mode: AnimationMode::Pendulum,
                     }))
 
                 } else {
-                    builder.tile(tile)
+                    builder
                 }
             }
         }
@@ -841,7 +782,7 @@ impl TerrainTile {
 
             .child(engine::Sprite::builder()
                 .spritesheet(game.spritesheets.terrain.clone())
-                .apply(tile_animation(grid, this.info))
+                .apply(tile_animation(this.info))
                 .order(Order::Parent(grid.order(&coord)))
                 .offset(offset)
                 .size(size)
@@ -867,7 +808,7 @@ impl TerrainTile {
                 })
 
                 .spritesheet(game.spritesheets.terrain.clone())
-                .apply(tile_animation(grid, this.info))
+                .apply(tile_animation(this.info))
                 .order(Order::Parent(grid.order(&coord) + (1.0 / 6.0)))
                 .offset(offset)
                 .size(size)
@@ -877,3 +818,27 @@ impl TerrainTile {
             .build()
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Terrain};
+    use crate::grid::map_gen::{MapGenSettings, Symmetry};
+
+    #[test]
+    fn auto_tiling() {
+        for symmetry in [Symmetry::Horizontal, Symmetry::Vertical, Symmetry::Rotational] {
+            for (width, height) in [(20, 14), (15, 11), (4, 4)] {
+                for seed in 0..10 {
+                    let map = MapGenSettings {
+                        symmetry,
+                        ..MapGenSettings::new(seed, width, height)
+                    }.generate();
+
+                    let terrain = Terrain::from_map(&map);
+                    assert_eq!(terrain.len(), (map.width * map.height) as usize);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+use futures_signals::map_ref;
+use futures_signals::signal::{Signal, SignalExt, always, option};
+use futures_signals::signal_vec::{SignalVecExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Offset, Size, ParentWidth, ParentHeight, SmallestWidth, SmallestHeight};
+
+use crate::ui::{self, Theme};
+use crate::grid::{Grid, Coord, Nation, TerrainInfo};
+use crate::grid::unit::{Unit, UnitClass};
+
+
+/// The stats of the unit which is on the tile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct UnitStats {
+    class: UnitClass,
+    nation: Nation,
+    hp: u32,
+    fuel: u32,
+}
+
+
+/// Describes the tile, one line for the terrain, the building owner, and the unit.
+fn describe(info: &TerrainInfo, owner: Option<Option<Nation>>, unit: Option<UnitStats>) -> String {
+    let mut output = format!("{:<9}{}", info.name, "*".repeat(info.defense as usize));
+
+    if let Some(owner) = owner {
+        match owner {
+            Some(nation) => output.push_str(&format!("\nOwner    {:?}", nation)),
+            None => output.push_str("\nOwner    Neutral"),
+        }
+    }
+
+    if let Some(unit) = unit {
+        output.push_str(&format!("\n{:<9}{:?}\nHP{:>3} Fuel{:>3}", unit.class.spec().name, unit.nation, unit.hp, unit.fuel));
+    }
+
+    output
+}
+
+
+fn unit_stats(grid: &Arc<Grid>, unit: Arc<Unit>, reveal_fog: bool) -> impl Signal<Item = Option<UnitStats>> {
+    let class = unit.class;
+    let nation = unit.nation;
+
+    map_ref! {
+        let hp = unit.display_hp(),
+        let fuel = unit.fuel.signal(),
+        let visible = Unit::is_visible(grid, &unit, reveal_fog) => {
+            // Units which are hidden by fog aren't revealed by hovering over them
+            if *visible {
+                Some(UnitStats { class, nation, hp: *hp, fuel: *fuel })
+
+            } else {
+                None
+            }
+        }
+    }
+}
+
+
+fn tile_text(grid: &Arc<Grid>, coord: Coord, reveal_fog: bool) -> impl Signal<Item = Option<String>> {
+    let info = grid.terrain_info(coord);
+
+    let owner = option(grid.building_at(coord).map(|building| building.nation.signal()));
+    let unit = option(grid.unit_at(coord).map(|unit| unit_stats(grid, unit, reveal_fog)));
+
+    map_ref! {
+        let owner = owner,
+        let unit = unit => {
+            info.map(|info| describe(info, *owner, unit.flatten()))
+        }
+    }
+}
+
+
+/// Panel in the lower-left corner which describes the tile under the [`cursor`](Grid::cursor).
+///
+/// It displays the terrain's name and defense stars, the owner of the building, and the unit's health and fuel.
+/// It is hidden while the cursor isn't on the grid.
+pub(crate) fn tile_info_panel(theme: &Theme, grid: &Arc<Grid>, reveal_fog: bool) -> Node {
+    // The units are looked up again when a unit is added or removed, or when the cursor moves
+    let text = map_ref! {
+        let cursor = grid.cursor.signal(),
+        let _units = grid.units.signal_vec().len() => {
+            *cursor
+        }
+    }.switch({
+        let grid = grid.clone();
+
+        move |cursor| {
+            match cursor {
+                Some(coord) => tile_text(&grid, coord, reveal_fog).boxed(),
+                None => always(None).boxed(),
+            }
+        }
+    }).broadcast();
+
+    ui::SpriteBorder::builder()
+        .apply(|builder| {
+            builder
+                .visible_signal(text.signal_ref(Option::is_some))
+                .offset(Offset {
+                    x: ParentWidth(0.02),
+                    y: ParentHeight(0.75),
+                })
+                .size(Size {
+                    width: SmallestWidth(1.0),
+                    height: SmallestHeight(1.0),
+                })
+        })
+
+        .theme(&theme.dialog)
+
+        .center(engine::BitmapText::builder()
+            .text_signal(text.signal_cloned().map(|text| text.unwrap_or_default().into()))
+            .font(theme.text.font.clone())
+            .text_color(theme.text.color)
+            .char_size(theme.text.char_size)
+            .size(Size {
+                width: SmallestWidth(1.0),
+                height: SmallestHeight(1.0),
+            })
+            .build())
+
+        .build()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, UnitStats};
+    use crate::grid::{Nation};
+    use crate::grid::terrain::{TerrainClass};
+    use crate::grid::unit::{UnitClass};
+
+    #[test]
+    fn describe_tile() {
+        assert_eq!(describe(TerrainClass::Grass.info(), None, None), "Plain    *");
+
+        let unit = UnitStats {
+            class: UnitClass::Tank,
+            nation: Nation::BlueMoon,
+            hp: 7,
+            fuel: 42,
+        };
+
+        assert_eq!(
+            describe(TerrainClass::Grass.info(), Some(None), Some(unit)),
+            "Plain    *\nOwner    Neutral\nTank     BlueMoon\nHP  7 Fuel 42",
+        );
+    }
+}
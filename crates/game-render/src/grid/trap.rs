@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use futures_signals::signal::{Mutable};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Size, Offset, ParentWidth, ParentHeight, CharSize, ColorRgb};
+
+use crate::Game;
+use crate::grid::{Grid, Coord};
+
+
+/// Exclamation mark which is displayed above a unit when it gets trapped.
+pub struct TrapAlert {
+    coord: Coord,
+    pub percent: Mutable<f32>,
+}
+
+impl TrapAlert {
+    pub fn new(coord: Coord) -> Arc<Self> {
+        Arc::new(Self {
+            coord,
+            percent: Mutable::new(0.0),
+        })
+    }
+
+    pub fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        let (x, y) = grid.tile_offset(&this.coord);
+
+        let tile_width = grid.width;
+        let tile_height = grid.height;
+
+        engine::BitmapText::builder()
+            .text("!".into())
+            .font(game.fonts.unifont.clone())
+            .text_color(ColorRgb { r: 1.0, g: 0.85, b: 0.0 })
+
+            // Bounces upwards above the unit
+            .offset_signal(this.percent.signal_ref(move |percent| {
+                let bounce = (percent * std::f32::consts::PI).sin() * 0.25;
+
+                Offset {
+                    // Centers it horizontally, because the character is half the width of the tile
+                    x: ParentWidth(x + (tile_width * 0.25)),
+                    y: ParentHeight(y - (tile_height * (1.0 + bounce))),
+                }
+            }))
+
+            .size(Size {
+                width: ParentWidth(grid.width),
+                height: ParentHeight(grid.height),
+            })
+
+            .char_size(CharSize {
+                width: ParentWidth(0.5),
+                height: ParentHeight(1.0),
+            })
+
+            .build()
+    }
+}
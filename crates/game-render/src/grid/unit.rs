@@ -3,149 +3,80 @@ use futures_signals::map_ref;
 use futures_signals::signal::{Mutable, Signal, SignalExt};
 use dominator::clone;
 use rusted_battalions_engine as engine;
-use rusted_battalions_engine::{Node, Size, Offset, Tile, ParentWidth, ParentHeight, Order};
+use rusted_battalions_engine::{Node, Size, Offset, Tile, ParentWidth, ParentHeight, Order, CharSize, ColorRgb};
 
 use crate::Game;
-use crate::grid::{UNIT_ANIMATION_TIME, FOG_ANIMATION_TIME, Grid, Coord, Nation};
-use crate::grid::explosion::{ExplosionAnimation};
-
-
-#[derive(Debug, Clone, Copy)]
-pub enum UnitClass {
-    Infantry,
-    Mech,
-    Recon,
-    APC,
-    Artillery,
-    Tank,
-    AntiAir,
-    Missile,
-    Rocket,
-    MediumTank,
-    Piperunner,
-    Neotank,
-    MegaTank,
-    BCopter,
-    TCopter,
-    Fighter,
-    Bomber,
-    Stealth,
-    Battleship,
-    Cruiser,
-    Submarine,
-    Lander,
-    Carrier,
-    BlackBoat,
-    BlackBomb,
-    Oozium,
-}
+use crate::assets::{NationPalettes};
+use crate::grid::{Grid, Coord, Nation};
+use crate::grid::entity_index::{Entity};
+use crate::grid::explosion::{ExplosionAnimation, MoveEffect};
+use crate::grid::terrain::{TerrainClass, MovementClass};
+
+pub use rusted_battalions_game_core::unit::{UnitClass, Rank, can_share_tile};
+
+
+pub(crate) trait UnitClassExt {
+    /// The y position of the unit's sprites in the spritesheet, in tiles.
+    fn tile_y(&self, nation: &Nation) -> u32;
 
-impl UnitClass {
-    pub const ALL: &[Self] = &[
-        Self::Infantry,
-        Self::Mech,
-        Self::Recon,
-        Self::APC,
-        Self::Artillery,
-        Self::Tank,
-        Self::AntiAir,
-        Self::Missile,
-        Self::Rocket,
-        Self::MediumTank,
-        Self::Piperunner,
-        Self::Neotank,
-        Self::MegaTank,
-        Self::BCopter,
-        Self::TCopter,
-        Self::Fighter,
-        Self::Bomber,
-        Self::Stealth,
-        Self::Battleship,
-        Self::Cruiser,
-        Self::Submarine,
-        Self::Lander,
-        Self::Carrier,
-        Self::BlackBoat,
-        Self::BlackBomb,
-        Self::Oozium,
-    ];
+    /// The effect which is left behind when the unit moves off of a tile.
+    fn move_effect(&self, terrain: TerrainClass) -> Option<MoveEffect>;
 
+    fn explosion_animation(&self) -> ExplosionAnimation;
+}
+
+impl UnitClassExt for UnitClass {
     fn tile_y(&self, nation: &Nation) -> u32 {
-        match self {
-            Self::Infantry => match nation {
-                Nation::OrangeStar => 0,
-                Nation::BlueMoon => 1,
-                Nation::GreenEarth => 2,
-                Nation::YellowComet => 3,
-                Nation::BlackHole => 4,
+        let spec = self.spec();
+
+        if spec.nation_sprites {
+            spec.sprite_row + Nation::ALL.iter().position(|x| x == nation).unwrap() as u32
+
+        } else {
+            spec.sprite_row
+        }
+    }
+
+    fn move_effect(&self, terrain: TerrainClass) -> Option<MoveEffect> {
+        match self.explosion_animation() {
+            ExplosionAnimation::Air => None,
+
+            ExplosionAnimation::Sea => match terrain {
+                TerrainClass::Ocean |
+                TerrainClass::River |
+                TerrainClass::Shoal |
+                TerrainClass::Reef => Some(MoveEffect::Wake),
+                _ => None,
             },
-            Self::Mech => match nation {
-                Nation::OrangeStar => 5,
-                Nation::BlueMoon => 6,
-                Nation::GreenEarth => 7,
-                Nation::YellowComet => 8,
-                Nation::BlackHole => 9,
+
+            ExplosionAnimation::Land |
+            ExplosionAnimation::Mega => match terrain {
+                TerrainClass::Road { .. } |
+                TerrainClass::Bridge { .. } => Some(MoveEffect::Dust),
+                TerrainClass::Shoal => Some(MoveEffect::Splash),
+                _ => None,
             },
-            Self::Recon => 10,
-            Self::Tank => 11,
-            Self::MediumTank => 12,
-            Self::Neotank => 13,
-            Self::MegaTank => 14,
-            Self::APC => 15,
-            Self::AntiAir => 16,
-            Self::Artillery => 17,
-            Self::Rocket => 18,
-            Self::Missile => 19,
-            Self::Piperunner => 20,
-            Self::Oozium => 21,
-            Self::Fighter => 22,
-            Self::Bomber => 23,
-            Self::BlackBomb => 24,
-            Self::Stealth => 25,
-            Self::BCopter => 26,
-            Self::TCopter => 27,
-            Self::Battleship => 28,
-            Self::Cruiser => 29,
-            Self::Submarine => 30,
-            Self::Lander => 31,
-            Self::BlackBoat => 32,
-            Self::Carrier => 33,
         }
     }
 
-    pub fn explosion_animation(&self) -> ExplosionAnimation {
-        match self {
-            UnitClass::Infantry |
-            UnitClass::Mech |
-            UnitClass::Recon |
-            UnitClass::APC |
-            UnitClass::Artillery |
-            UnitClass::Tank |
-            UnitClass::AntiAir |
-            UnitClass::Missile |
-            UnitClass::Rocket |
-            UnitClass::MediumTank |
-            UnitClass::Neotank => ExplosionAnimation::Land,
-
-            UnitClass::BCopter |
-            UnitClass::TCopter |
-            UnitClass::Fighter |
-            UnitClass::Bomber |
-            UnitClass::Stealth |
-            UnitClass::BlackBomb => ExplosionAnimation::Air,
-
-            UnitClass::Battleship |
-            UnitClass::Cruiser |
-            UnitClass::Submarine |
-            UnitClass::Lander |
-            UnitClass::Carrier |
-            UnitClass::BlackBoat => ExplosionAnimation::Sea,
-
-            UnitClass::MegaTank |
-            UnitClass::Piperunner => ExplosionAnimation::Mega,
-
-            // TODO
-            UnitClass::Oozium => ExplosionAnimation::Mega,
+    fn explosion_animation(&self) -> ExplosionAnimation {
+        let spec = self.spec();
+
+        if spec.heavy {
+            return ExplosionAnimation::Mega;
+        }
+
+        match spec.movement_class {
+            MovementClass::Air => ExplosionAnimation::Air,
+
+            MovementClass::Sea |
+            MovementClass::Lander => ExplosionAnimation::Sea,
+
+            MovementClass::Foot |
+            MovementClass::Boot |
+            MovementClass::Treads |
+            MovementClass::Tires |
+            MovementClass::Pipe => ExplosionAnimation::Land,
         }
     }
 }
@@ -157,8 +88,10 @@ enum UnitDirection {
     Right,
 }
 
+/// Which direction the unit is facing, this selects the sprites and mirroring.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnitAnimation {
+    /// The unit isn't moving, so it uses the idle sprites and faces the default direction for its nation.
     Idle,
     Left,
     Right,
@@ -199,27 +132,123 @@ impl UnitAnimation {
 }
 
 
+/// Identifier for a [`Unit`] which is unique and stays the same for the lifetime of the Unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UnitId(u32);
+
+impl UnitId {
+    /// The ids are allocated by the [`Grid`], see [`Grid::new_unit`].
+    #[inline]
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    #[inline]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+
 pub struct Unit {
+    pub id: UnitId,
     pub coord: Mutable<Coord>,
     pub alpha: Mutable<f32>,
+    /// This is automatically updated by [`Grid::move_unit`].
     pub animation: Mutable<UnitAnimation>,
     pub waited: Mutable<bool>,
+
+    /// Health from `0` to [`MAX_HP`](Unit::MAX_HP).
+    pub hp: Mutable<u32>,
+
+    pub fuel: Mutable<u32>,
+
+    /// Whether the unit is hidden by fog.
+    pub fog: Mutable<bool>,
+
+    /// The number of enemy units which this unit has destroyed.
+    pub kills: Mutable<u32>,
+
+    /// This is automatically updated by [`add_kill`](Unit::add_kill).
+    pub rank: Mutable<Rank>,
+
     pub nation: Nation,
     pub class: UnitClass,
 }
 
+impl Entity for Unit {
+    type Id = UnitId;
+
+    #[inline]
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+
+    #[inline]
+    fn coord(&self) -> Coord {
+        self.coord.get()
+    }
+}
+
 impl Unit {
-    pub fn new(coord: Coord, class: UnitClass, nation: Nation) -> Arc<Self> {
+    pub const MAX_HP: u32 = 100;
+
+    /// The id must be unique on the grid, use [`Grid::new_unit`] to create units during a match.
+    pub fn new(id: UnitId, coord: Coord, class: UnitClass, nation: Nation) -> Arc<Self> {
         Arc::new(Self {
+            id,
             coord: Mutable::new(coord),
             alpha: Mutable::new(1.0),
             animation: Mutable::new(UnitAnimation::Idle),
             waited: Mutable::new(false),
+            hp: Mutable::new(Self::MAX_HP),
+            fuel: Mutable::new(class.max_fuel()),
+            fog: Mutable::new(false),
+            kills: Mutable::new(0),
+            rank: Mutable::new(Rank::Rookie),
             nation,
             class,
         })
     }
 
+    /// Increases the number of kills, returns `true` if the unit was promoted to a higher [`Rank`].
+    ///
+    /// The unit is never demoted, so a rank which was set by a replay is kept.
+    pub fn add_kill(&self) -> bool {
+        let kills = self.kills.get() + 1;
+        self.kills.set(kills);
+
+        let rank = Rank::from_kills(kills);
+
+        if rank > self.rank.get() {
+            self.rank.set(rank);
+            true
+
+        } else {
+            false
+        }
+    }
+
+    /// Converts the health into the health which is displayed to the player, from `0` to `10`.
+    ///
+    /// It rounds up, so a unit with `1` health is displayed as `1`.
+    #[inline]
+    pub fn hp_to_display(hp: u32) -> u32 {
+        (hp + 9) / 10
+    }
+
+    /// The default number of volleys for [`Grid::animate_damage`], there is 1 volley for every
+    /// 20 damage, from `1` to `5` volleys.
+    #[inline]
+    pub fn damage_volleys(damage: u32) -> u32 {
+        damage.div_ceil(20).clamp(1, 5)
+    }
+
+    /// The health which is displayed to the player, from `0` to `10`.
+    pub fn display_hp(&self) -> impl Signal<Item = u32> {
+        self.hp.signal_ref(|hp| Self::hp_to_display(*hp)).dedupe()
+    }
+
     fn tile_x(&self) -> impl Signal<Item = u32> {
         self.animation.signal_ref(move |animation| animation.tile_x()).dedupe()
     }
@@ -230,10 +259,78 @@ impl Unit {
         self.animation.signal_ref(move |animation| animation.direction(&nation)).dedupe()
     }
 
-    pub fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+    /// The palette for the nation, units which have waited are grayed out.
+    ///
+    /// Nations with custom team colors use the rows which were added to the palette.
+    pub(crate) fn palette(palettes: &NationPalettes, nation: Nation, waited: bool) -> u32 {
+        let palette = palettes.units.get(&nation).copied().unwrap_or(match nation {
+            Nation::OrangeStar => 0,
+            Nation::BlueMoon => 2,
+            Nation::GreenEarth => 4,
+            Nation::YellowComet => 6,
+            Nation::BlackHole => 8,
+        });
+
+        if waited {
+            palette + 1
+
+        } else {
+            palette
+        }
+    }
+
+    fn palette_signal(game: &Arc<Game>, this: &Arc<Self>) -> impl Signal<Item = u32> {
         let nation = this.nation;
 
-        let tile_y = this.class.tile_y(&nation);
+        map_ref! {
+            let palettes = game.nation_palettes(),
+            let waited = this.waited.signal() => {
+                Self::palette(palettes, nation, *waited)
+            }
+        }.dedupe()
+    }
+
+    fn tile_signal(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> impl Signal<Item = Tile> {
+        let tile_y = this.class.tile_y(&this.nation);
+
+        map_ref! {
+            let tile_x = this.tile_x(),
+            let frame = grid.unit_frame.signal(),
+            let tile_size = game.unit_tile_size() => {
+                let tile_x = (tile_x + frame) * tile_size;
+                let tile_y = tile_y * tile_size;
+
+                Tile {
+                    start_x: tile_x,
+                    start_y: tile_y,
+                    end_x: tile_x + tile_size,
+                    end_y: tile_y + tile_size,
+                }
+            }
+        }
+    }
+
+    fn flip_x(&self) -> impl Signal<Item = bool> {
+        self.direction().map(|direction| {
+            match direction {
+                UnitDirection::Left => false,
+                UnitDirection::Right => true,
+            }
+        })
+    }
+
+    /// Allies share their vision, so the units of the player's allies are never hidden by fog.
+    pub(crate) fn is_visible(grid: &Grid, this: &Self, reveal_fog: bool) -> impl Signal<Item = bool> {
+        map_ref! {
+            let fog = this.fog.signal(),
+            let is_ally = grid.is_ally(this.nation) => {
+                reveal_fog || !fog || *is_ally
+            }
+        }.dedupe()
+    }
+
+    pub fn render(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        let reveal_fog = game.reveal_fog();
 
         engine::Sprite::builder()
             .spritesheet_signal(game.unit_spritesheet())
@@ -256,6 +353,8 @@ impl Unit {
                 Order::Parent(grid.order(coord) + (4.0 / 6.0))
             })).dedupe())
 
+            .visible_signal(Self::is_visible(grid, this, reveal_fog))
+
             .alpha_signal(this.alpha.signal())
 
             /*.alpha_signal(grid.animation(FOG_ANIMATION_TIME).map(move |time| {
@@ -269,45 +368,144 @@ impl Unit {
                 }
             }))*/
 
-            .tile_signal(map_ref! {
-                let tile_x = this.tile_x(),
-                let direction = this.direction(),
-                let tile_size = game.unit_tile_size(),
-                let frame = grid.animation_pendulum(UNIT_ANIMATION_TIME, 3) => {
-                    let tile_x = (tile_x + frame) * tile_size;
-                    let tile_y = tile_y * tile_size;
-
-                    let tile = Tile {
-                        start_x: tile_x,
-                        start_y: tile_y,
-                        end_x: tile_x + tile_size,
-                        end_y: tile_y + tile_size,
-                    };
-
-                    match direction {
-                        UnitDirection::Left => tile,
-                        UnitDirection::Right => tile.mirror_x(),
-                    }
+            .tile_signal(Self::tile_signal(game, grid, this))
+            .flip_x_signal(this.flip_x())
+
+            .palette_signal(Self::palette_signal(game, this))
+
+            .build()
+    }
+
+    /// Upside down copy of the unit which is displayed on the water tile below the unit.
+    pub(crate) fn render_reflection(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        let reveal_fog = game.reveal_fog();
+
+        engine::Sprite::builder()
+            .spritesheet_signal(game.unit_spritesheet())
+
+            // Starts at the bottom of the unit's tile, and it is squashed to half the height of the unit
+            .offset_signal(this.coord.signal_ref(clone!(grid => move |coord| {
+                let (x, y) = grid.tile_offset(coord);
+
+                Offset {
+                    x: ParentWidth(x - (grid.width * 0.5)),
+                    y: ParentHeight(y + grid.height),
                 }
+            })))
+
+            .size(Size {
+                width: ParentWidth(grid.width * 2.0),
+                height: ParentHeight(grid.height),
             })
 
-            .palette_signal(this.waited.signal_ref(move |waited| {
-                let palette = match nation {
-                    Nation::OrangeStar => 0,
-                    Nation::BlueMoon => 2,
-                    Nation::GreenEarth => 4,
-                    Nation::YellowComet => 6,
-                    Nation::BlackHole => 8,
-                };
+            // Above the water, but below the fog
+            .order_signal(this.coord.signal_ref(clone!(grid => move |coord| {
+                Order::Parent(grid.order(&Coord { x: coord.x, y: coord.y + 1.0 }) + (0.5 / 6.0))
+            })).dedupe())
 
-                if *waited {
-                    palette + 1
+            .visible_signal(clone!(grid => map_ref! {
+                let visible = Self::is_visible(&grid, this, reveal_fog),
+                let coord = this.coord.signal() => move {
+                    *visible && grid.is_water_below(*coord)
+                }
+            }).dedupe())
 
-                } else {
-                    palette
+            .alpha_signal(map_ref! {
+                let alpha = this.alpha.signal(),
+                let reflection = grid.reflection_alpha() => {
+                    alpha * reflection
                 }
-            }))
+            })
+
+            .tile_signal(Self::tile_signal(game, grid, this))
+            .flip_x_signal(this.flip_x())
+            .flip_y(true)
+
+            .palette_signal(Self::palette_signal(game, this))
+
+            .build()
+    }
+
+    /// Displays the unit's [`Rank`] in the lower-left corner of its tile.
+    pub(crate) fn render_badge(game: &Arc<Game>, grid: &Arc<Grid>, this: &Arc<Self>) -> Node {
+        let reveal_fog = game.reveal_fog();
+
+        engine::Stack::builder()
+            .order(Order::Parent(0.0))
+
+            .child_signal(this.rank.signal().map(clone!(game, grid, this => move |rank| {
+                rank.badge().map(|badge| {
+                    engine::BitmapText::builder()
+                        .text(badge.into())
+                        .font(game.fonts.unifont.clone())
+                        .text_color(ColorRgb { r: 1.0, g: 0.85, b: 0.0 })
+
+                        .offset_signal(this.coord.signal_ref(clone!(grid => move |coord| {
+                            let (x, y) = grid.tile_offset(coord);
+
+                            Offset {
+                                x: ParentWidth(x),
+                                y: ParentHeight(y + (grid.height * 0.5)),
+                            }
+                        })))
+
+                        .size(Size {
+                            width: ParentWidth(grid.width),
+                            height: ParentHeight(grid.height * 0.5),
+                        })
+
+                        .char_size(CharSize {
+                            width: ParentWidth(0.25),
+                            height: ParentHeight(1.0),
+                        })
+
+                        // Above the unit
+                        .order_signal(this.coord.signal_ref(clone!(grid => move |coord| {
+                            Order::Parent(grid.order(coord) + (4.5 / 6.0))
+                        })).dedupe())
+
+                        .visible_signal(Self::is_visible(&grid, &this, reveal_fog))
+
+                        .build()
+                })
+            })))
 
             .build()
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Unit, UnitAnimation, UnitDirection, Nation};
+
+    #[test]
+    fn damage_volleys() {
+        assert_eq!(Unit::damage_volleys(0), 1);
+        assert_eq!(Unit::damage_volleys(15), 1);
+        assert_eq!(Unit::damage_volleys(45), 3);
+        assert_eq!(Unit::damage_volleys(100), 5);
+    }
+
+    #[test]
+    fn animation() {
+        // The spritesheet has the idle sprites first, then the sideways, down and up sprites
+        assert_eq!(UnitAnimation::Idle.tile_x(), 0);
+        assert_eq!(UnitAnimation::Left.tile_x(), 3);
+        assert_eq!(UnitAnimation::Right.tile_x(), 3);
+        assert_eq!(UnitAnimation::Down.tile_x(), 6);
+        assert_eq!(UnitAnimation::Up.tile_x(), 9);
+
+        // The sprites face left, so they are only mirrored when moving right
+        assert_eq!(UnitAnimation::Right.direction(&Nation::BlueMoon), UnitDirection::Right);
+        assert_eq!(UnitAnimation::Left.direction(&Nation::OrangeStar), UnitDirection::Left);
+        assert_eq!(UnitAnimation::Up.direction(&Nation::OrangeStar), UnitDirection::Left);
+        assert_eq!(UnitAnimation::Down.direction(&Nation::OrangeStar), UnitDirection::Left);
+
+        // Idle units face the enemy, depending on their nation
+        assert_eq!(UnitAnimation::Idle.direction(&Nation::OrangeStar), UnitDirection::Right);
+        assert_eq!(UnitAnimation::Idle.direction(&Nation::GreenEarth), UnitDirection::Right);
+        assert_eq!(UnitAnimation::Idle.direction(&Nation::BlueMoon), UnitDirection::Left);
+        assert_eq!(UnitAnimation::Idle.direction(&Nation::BlackHole), UnitDirection::Left);
+    }
+}
@@ -1,26 +1,58 @@
 mod grid;
 mod util;
-mod ui;
+pub mod ui;
+pub mod lobby;
+mod spectator;
+mod gallery;
+pub mod assets;
+pub mod autosave;
 
 use std::sync::{Arc};
+use std::future::Future;
 
-use futures_signals::signal::{Mutable, Signal, SignalExt};
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, Signal, SignalExt, always};
+use futures_signals::signal_vec::{SignalVecExt};
 use dominator::clone;
 use futures::future::join;
 
 use rusted_battalions_engine as engine;
 use rusted_battalions_engine::{
-    Engine, EngineSettings, Spritesheet, SpritesheetSettings, RgbaImage,
+    Engine, EngineSettings, Spritesheet, SpritesheetSettings, PaletteError, RgbaImage,
     GrayscaleImage, IndexedImage, Texture, Node, BitmapFont, Offset,
-    CharSize, ColorRgb, BitmapText, BitmapFontSettings, BitmapFontSupported,
-    ParentWidth, ParentHeight, Px, ScreenHeight, RepeatTile, Repeat, Zero,
-    SmallestWidth, SmallestHeight, Size, Order,
+    BitmapText, BitmapFontSettings, BitmapFontSupported,
+    ParentWidth, ParentHeight, Px, Zero,
+    SmallestWidth, SmallestHeight, Size, ScreenEffect,
 };
 
 use crate::util::future::executor;
-use grid::{ScreenSize, UNIT_MOVE_TIME};
-
-pub use grid::{Grid};
+use crate::ui::{FocusManager, Announcer, Banner, Theme, ControlsConfig, Action, PowerEffect, ScreenStack, Screen, PerfOverlay, Portrait, Emotion};
+use crate::util::signal::{SortedVec};
+use crate::gallery::{SpriteGallery};
+use crate::lobby::{Co};
+use crate::assets::{AssetOverrides, NationPalettes};
+use rusted_battalions_game_core::random::{Rng};
+use grid::{ScreenSize, UNIT_MOVE_TIME, BANNER_ANIMATION_TIME, PORTRAIT_SLIDE_TIME, POWER_FADE_TIME};
+use grid::sidebar::{UnitSidebar};
+use grid::tile_info::{tile_info_panel};
+use grid::stats::{intel_panel};
+use grid::status::{status_screen};
+
+pub use grid::{Grid, Nation, Registry, UnitSpec, BuildingSpec, SaveGame, SaveError, Events, Teams, MatchRules, Funds, BuildError};
+pub use autosave::{AutosaveSettings};
+pub use grid::animation::{FrameAnimation, FrameMode};
+pub use grid::terrain::{TerrainInfo, MovementClass};
+pub use grid::map::{MapData, MapBuilding, MapDataExt, ThumbnailCache};
+pub use grid::map_gen::{MapGenSettings, Symmetry};
+pub use grid::pane::{GridPane};
+pub use spectator::{Spectator, SpectatorSettings, Playback};
+pub use rusted_battalions_game_core::replay::{
+    Replay, ReplaySettings, ReplayAction, ReplayEvent, ReplayError,
+    REPLAY_VERSION, REPLAY_EXTENSION,
+};
+pub use grid::action::{MoveDirection, UnitMoved, UnitTrapped, UnitDamaged, UnitDestroyed};
+pub use grid::targeting::{TargetSelected};
+pub use rusted_battalions_engine::{QualitySettings, PowerPreference};
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -45,6 +77,29 @@ impl Default for UnitAppearance {
 }
 
 
+/// What the game does while the window is unfocused, see [`Game::set_focused`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdlePolicy {
+    /// Renders every frame, the same as when it is focused.
+    Render,
+
+    /// Renders at most `fps` frames per second.
+    Throttle {
+        fps: f64,
+    },
+
+    /// Doesn't render or advance the grids, but the futures still run, so network messages are still processed.
+    Pause,
+}
+
+impl Default for IdlePolicy {
+    /// Returns [`IdlePolicy::Throttle`] with 10 frames per second.
+    fn default() -> Self {
+        Self::Throttle { fps: 10.0 }
+    }
+}
+
+
 struct Spritesheets {
     terrain: Spritesheet,
     building: Spritesheet,
@@ -52,6 +107,7 @@ struct Spritesheets {
     unit_big: Spritesheet,
     effect: Spritesheet,
     hud: Spritesheet,
+    overlay: Spritesheet,
 }
 
 impl Spritesheets {
@@ -63,6 +119,7 @@ impl Spritesheets {
             unit_big: Spritesheet::new(),
             effect: Spritesheet::new(),
             hud: Spritesheet::new(),
+            overlay: Spritesheet::new(),
         }
     }
 }
@@ -87,33 +144,525 @@ impl Fonts {
 
 pub struct GameSettings {
     pub appearance: UnitAppearance,
+
+    /// The main grid, it fills the screen and receives input.
     pub grid: Arc<Grid>,
+
+    pub controls: ControlsConfig,
+
+    /// If this is `Some` then the game is watched instead of played, see [`Spectator`].
+    pub spectator: Option<SpectatorSettings>,
+
+    pub quality: QualitySettings,
+
+    /// Which GPU is used on devices with multiple GPUs, see [`EngineSettings::power_preference`].
+    pub power_preference: PowerPreference,
+
+    /// If this is `Some` then [`TurnEnded`] contains the state of the match, see [`autosave`].
+    pub autosave: Option<AutosaveSettings>,
+}
+
+
+/// Published on the active grid when the turn ends, see [`Action::EndTurn`].
+#[derive(Clone)]
+pub struct TurnEnded {
+    /// The turn which is starting.
+    pub turn: u32,
+
+    /// The state of the match at the start of the turn, this is `None` if there
+    /// aren't any [`GameSettings::autosave`] or the match is over.
+    pub save: Option<SaveGame>,
+
+    /// The team which has won, see [`Grid::winning_team`].
+    pub winner: Option<u32>,
 }
 
 
 pub struct Game {
     pub unit_appearance: Mutable<UnitAppearance>,
 
+    /// The skin for the UI, changing this will redraw the UI.
+    pub theme: Mutable<Theme>,
+
+    /// Keyboard / gamepad focus for the UI.
+    pub focus: Arc<FocusManager>,
+
+    /// Messages which are read aloud by screen readers.
+    pub announcer: Arc<Announcer>,
+
+    /// Which keys / buttons are bound to which [`Action`].
+    pub controls: Mutable<ControlsConfig>,
+
+    /// List of the player's units on the active grid.
+    pub unit_sidebar: Arc<UnitSidebar>,
+
+    /// See [`show_banner`](Game::show_banner).
+    pub banner: Arc<Banner>,
+
+    /// See [`show_portrait`](Game::show_portrait).
+    pub portrait: Arc<Portrait>,
+
+    /// Menus which are displayed on top of the grids.
+    pub screens: Arc<ScreenStack>,
+
+    /// This is `Some` if the game is being watched instead of played.
+    pub spectator: Option<Arc<Spectator>>,
+
+    /// Changing this updates the engine and every grid on the next frame,
+    /// so weak devices can switch to a lower preset at any time.
+    pub quality: Mutable<QualitySettings>,
+
+    /// What to do while the window is unfocused.
+    pub idle_policy: Mutable<IdlePolicy>,
+
+    /// See [`Action::PerfOverlay`].
+    pub perf_overlay: Arc<PerfOverlay>,
+
+    /// The current turn, it starts at `1`.
+    pub turn: Mutable<u32>,
+
+    /// Random numbers for the match, such as random weather.
+    ///
+    /// It starts with [`AutosaveSettings::rng`], and its state is saved at the end of every turn,
+    /// so a resumed match continues with the same numbers.
+    pub rng: Mutable<Rng>,
+
+    /// See [`set_focused`](Game::set_focused).
+    focused: Mutable<bool>,
+
+    /// See [`power_effect`](Game::power_effect).
+    screen_effect: Mutable<ScreenEffect>,
+
+    /// See [`Action::SpriteGallery`].
+    sprite_gallery: Screen,
+
+    /// See [`Action::Status`].
+    status_screen: Screen,
+
     spritesheets: Spritesheets,
     fonts: Fonts,
 
-    grid: Mutable<Arc<Grid>>,
+    screen_size: ScreenSize,
+
+    power_preference: PowerPreference,
+
+    /// Every grid which is displayed, in the order they are displayed.
+    panes: SortedVec<GridPane>,
+
+    /// The grid which receives input, it must be one of the displayed grids.
+    active_grid: Mutable<Arc<Grid>>,
+
+    /// See [`GameSettings::autosave`].
+    autosave: Option<AutosaveSettings>,
+
+    /// The custom team colors which were loaded by [`start_engine`](Game::start_engine).
+    nation_palettes: Mutable<NationPalettes>,
 }
 
 impl Game {
     pub fn new(settings: GameSettings) -> Arc<Self> {
+        let spritesheets = Spritesheets::new();
+        let fonts = Fonts::new();
+
+        let unit_appearance = Mutable::new(settings.appearance);
+
+        let sprite_gallery = SpriteGallery::screen(unit_appearance.clone(), &spritesheets);
+
+        let spectator = settings.spectator.map(|spectator| {
+            let spectator = Spectator::new(settings.grid.clone(), spectator);
+            settings.grid.spawn_future(spectator.clone().play());
+            spectator
+        });
+
+        let theme = Mutable::new(Theme::dual_strike(spritesheets.hud.clone(), fonts.unifont.clone()));
+        let active_grid = Mutable::new(settings.grid.clone());
+
+        let reveal_fog = spectator.as_ref().map(|spectator| spectator.reveal_fog()).unwrap_or(false);
+        let status_screen = status_screen(theme.clone(), active_grid.clone(), reveal_fog);
+
         Arc::new(Self {
-            unit_appearance: Mutable::new(settings.appearance),
+            unit_appearance,
+
+            theme,
+
+            focus: FocusManager::new(),
+
+            announcer: Announcer::new(),
+
+            controls: Mutable::new(settings.controls),
+
+            unit_sidebar: UnitSidebar::new(),
+
+            banner: Banner::new(),
+
+            portrait: Portrait::new(),
+
+            screens: ScreenStack::new(),
+
+            spectator,
+
+            quality: Mutable::new(settings.quality),
+
+            idle_policy: Mutable::new(IdlePolicy::default()),
+
+            perf_overlay: PerfOverlay::new(),
+
+            turn: Mutable::new(settings.autosave.as_ref().map(|autosave| autosave.turn).unwrap_or(1)),
+
+            rng: Mutable::new(Rng::new(settings.autosave.as_ref().map(|autosave| autosave.rng).unwrap_or(0))),
+
+            focused: Mutable::new(true),
 
-            spritesheets: Spritesheets::new(),
-            fonts: Fonts::new(),
+            screen_effect: Mutable::new(ScreenEffect::default()),
 
-            grid: Mutable::new(settings.grid),
+            sprite_gallery,
+
+            status_screen,
+
+            spritesheets,
+            fonts,
+
+            screen_size: settings.grid.screen_size,
+
+            power_preference: settings.power_preference,
+
+            panes: SortedVec::with_values(vec![GridPane::new(settings.grid.clone())]),
+
+            active_grid,
+
+            autosave: settings.autosave,
+
+            nation_palettes: Mutable::new(NationPalettes::default()),
         })
     }
 
+    pub(crate) fn nation_palettes(&self) -> impl Signal<Item = NationPalettes> {
+        self.nation_palettes.signal_cloned()
+    }
+
     pub fn screen_size(&self) -> impl Signal<Item = ScreenSize> {
-        self.grid.signal_ref(|grid| grid.screen_size).dedupe()
+        always(self.screen_size)
+    }
+
+    /// The client must call this when the window gains or loses focus, see [`IdlePolicy`].
+    ///
+    /// When the window is focused again it renders on the next frame.
+    pub fn set_focused(&self, focused: bool) {
+        self.focused.set_neq(focused);
+    }
+
+    /// Returns the grid which receives input.
+    pub fn active_grid(&self) -> Arc<Grid> {
+        self.active_grid.get_cloned()
+    }
+
+    pub fn active_grid_signal(&self) -> impl Signal<Item = Arc<Grid>> {
+        self.active_grid.signal_cloned()
+    }
+
+    pub fn set_active_grid(&self, grid: &Arc<Grid>) {
+        debug_assert!(self.panes.lock_ref().iter().any(|pane| Arc::ptr_eq(&pane.grid, grid)), "Active grid is not displayed");
+
+        self.active_grid.set(grid.clone());
+    }
+
+    /// Displays the grid, the pane is displayed on top of the existing panes.
+    ///
+    /// The grids share the spritesheets and fonts of the [`Game`].
+    pub fn add_pane(&self, pane: Arc<GridPane>) {
+        self.panes.insert(pane);
+    }
+
+    pub fn remove_pane(&self, pane: &Arc<GridPane>) {
+        debug_assert!(!Arc::ptr_eq(&pane.grid, &self.active_grid.lock_ref()), "Cannot remove the active grid");
+
+        self.panes.remove(pane);
+    }
+
+    /// Slides a [`Banner`] across the screen, such as "DAY 3" at the start of a turn
+    /// or the name of a CO power when it is activated.
+    ///
+    /// Input is ignored until the future finishes. The text is also read aloud by screen readers.
+    pub fn show_banner<S>(self: &Arc<Self>, text: S) -> impl Future<Output = ()> where S: Into<Arc<str>> {
+        let game = self.clone();
+        let text = text.into();
+
+        async move {
+            game.announcer.announce(text.clone());
+
+            let timer = game.active_grid().timer(BANNER_ANIMATION_TIME);
+
+            game.banner.run(text, timer).await;
+        }
+    }
+
+    /// Displays the CO's [`Portrait`] for a dialogue line, each line can use a different [`Emotion`].
+    ///
+    /// If a different CO is speaking then their portrait slides out before the new portrait slides in.
+    pub fn show_portrait(self: &Arc<Self>, co: Co, emotion: Emotion) -> impl Future<Output = ()> {
+        let game = self.clone();
+
+        async move {
+            let grid = game.active_grid();
+
+            game.portrait.show(co, emotion, || grid.timer(PORTRAIT_SLIDE_TIME)).await;
+        }
+    }
+
+    /// Slides the [`Portrait`] off of the screen when the dialogue ends.
+    pub fn hide_portrait(self: &Arc<Self>) -> impl Future<Output = ()> {
+        let game = self.clone();
+
+        async move {
+            let timer = game.active_grid().timer(PORTRAIT_SLIDE_TIME);
+
+            game.portrait.hide(timer).await;
+        }
+    }
+
+    /// Displays a full-screen effect while a CO power is active.
+    ///
+    /// The effect fades in, stays until the `end` future finishes, and then fades out.
+    /// The CO subsystem should call this when a power is activated, with a future which
+    /// finishes when the power ends.
+    ///
+    /// If the returned future is dropped early then the effect is immediately removed.
+    pub fn power_effect<F>(self: &Arc<Self>, effect: PowerEffect, end: F) -> impl Future<Output = ()>
+        where F: Future<Output = ()> {
+
+        /// Removes the effect even if the future is cancelled.
+        struct Cleanup(Arc<Game>);
+
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                self.0.screen_effect.set_neq(ScreenEffect::default());
+            }
+        }
+
+        let game = self.clone();
+
+        async move {
+            let cleanup = Cleanup(game);
+
+            let fade_in = cleanup.0.active_grid().timer(POWER_FADE_TIME);
+
+            fade_in.for_each(|percent| {
+                cleanup.0.screen_effect.set_neq(effect.screen_effect(percent as f32));
+                async {}
+            }).await;
+
+            end.await;
+
+            let fade_out = cleanup.0.active_grid().timer(POWER_FADE_TIME);
+
+            fade_out.for_each(|percent| {
+                cleanup.0.screen_effect.set_neq(effect.screen_effect(1.0 - percent as f32));
+                async {}
+            }).await;
+        }
+    }
+
+    #[inline]
+    pub fn is_spectator(&self) -> bool {
+        self.spectator.is_some()
+    }
+
+    /// Whether units and buildings should be displayed even if they are in fog.
+    pub(crate) fn reveal_fog(&self) -> bool {
+        self.spectator.as_ref().map(|spectator| spectator.reveal_fog()).unwrap_or(false)
+    }
+
+    fn is_blocked(&self) -> bool {
+        self.banner.is_active() || self.screens.is_transitioning()
+    }
+
+    /// Runs the action, returns `true` if the action was used.
+    ///
+    /// The client should use [`controls`](Game::controls) to convert the keys / buttons into actions.
+    pub fn action(&self, action: Action) -> bool {
+        // Input is blocked while the banner or a screen transition is displayed
+        if self.is_blocked() {
+            return true;
+        }
+
+        // Spectators can move the camera, but they can't change the grid
+        if self.is_spectator() && action.changes_state() {
+            return false;
+        }
+
+        // While choosing a target, left / right moves between the targets
+        {
+            let grid = self.active_grid();
+
+            if grid.targeting.is_active() {
+                match action {
+                    Action::Focus(ui::FocusKey::Direction(ui::FocusDirection::Left)) => return grid.targeting.previous(),
+                    Action::Focus(ui::FocusKey::Direction(ui::FocusDirection::Right)) => return grid.targeting.next(),
+                    Action::Confirm => return grid.confirm_target().is_some(),
+                    Action::Cancel => {
+                        grid.targeting.cancel();
+                        return true;
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        match action {
+            Action::Focus(key) => self.focus.navigate(key),
+
+            Action::Pan(direction) => {
+                let (x, y) = match direction {
+                    ui::FocusDirection::Up => (0.0, -1.0),
+                    ui::FocusDirection::Down => (0.0, 1.0),
+                    ui::FocusDirection::Left => (-1.0, 0.0),
+                    ui::FocusDirection::Right => (1.0, 0.0),
+                };
+
+                self.active_grid.lock_ref().camera.pan(x, y);
+                true
+            },
+
+            Action::DangerZone => {
+                let grid = self.active_grid.lock_ref();
+                grid.danger_zone.visible.set(!grid.danger_zone.visible.get());
+                true
+            },
+
+            Action::Intel => {
+                let grid = self.active_grid.lock_ref();
+                grid.stats.visible.set(!grid.stats.visible.get());
+                true
+            },
+
+            Action::NextUnit => {
+                let grid = self.active_grid();
+
+                let current = self.unit_sidebar.selected.get().and_then(|id| grid.unit(id));
+
+                if let Some(unit) = grid.next_ready_unit(current.as_ref()) {
+                    self.unit_sidebar.select(&grid, &unit);
+                    true
+
+                } else {
+                    false
+                }
+            },
+
+            Action::UnitSidebar => {
+                self.unit_sidebar.open.set(!self.unit_sidebar.open.get());
+                true
+            },
+
+            Action::SpriteGallery => {
+                if self.screens.is_top(&self.sprite_gallery) {
+                    self.screens.pop();
+
+                } else {
+                    self.screens.push(self.sprite_gallery.clone());
+                }
+
+                true
+            },
+
+            Action::Status => {
+                if self.screens.is_top(&self.status_screen) {
+                    self.screens.pop();
+
+                } else {
+                    self.screens.push(self.status_screen.clone());
+                }
+
+                true
+            },
+
+            Action::PerfOverlay => {
+                self.perf_overlay.visible.set(!self.perf_overlay.visible.get());
+                true
+            },
+
+            Action::EndTurn => {
+                self.end_turn();
+                true
+            },
+
+            // TODO implement these once the turn logic exists
+            Action::Confirm | Action::Cancel => false,
+
+            // This needs access to the engine, so it's handled by the client
+            Action::DumpScene => false,
+        }
+    }
+
+    /// Starts the next turn, the units can move again.
+    fn end_turn(&self) {
+        let grid = self.active_grid();
+
+        grid.end_turn();
+
+        let turn = {
+            let mut lock = self.turn.lock_mut();
+            *lock += 1;
+            *lock
+        };
+
+        let winner = grid.winning_team();
+
+        let save = match (&self.autosave, winner) {
+            (Some(autosave), None) => Some(grid.save_game(&autosave.map, autosave.settings, turn, self.rng.lock_ref().state())),
+            _ => None,
+        };
+
+        self.announcer.announce(format!("DAY {}", turn));
+
+        grid.events.publish(TurnEnded { turn, save, winner });
+    }
+
+    /// Handles a mouse click, returns `true` if the click was used.
+    ///
+    /// The position is relative to the screen, from `0.0` to `1.0`.
+    pub fn click(&self, x: f32, y: f32) -> bool {
+        if self.is_blocked() {
+            return true;
+        }
+
+        self.unit_sidebar.click(&self.active_grid(), x, y)
+    }
+
+    /// Handles the mouse moving, this updates the [`cursor`](Grid::cursor) of the active grid.
+    ///
+    /// The position is relative to the screen, from `0.0` to `1.0`.
+    pub fn hover(&self, x: f32, y: f32) {
+        let grid = self.active_grid();
+
+        // If the grid is displayed in multiple panes, then the pane which is on top is used
+        let coord = self.panes.lock_ref().iter().rev()
+            .find(|pane| Arc::ptr_eq(&pane.grid, &grid))
+            .and_then(|pane| pane.screen_to_coord(x, y));
+
+        grid.cursor.set_neq(coord);
+    }
+
+    /// Handles the mouse leaving the screen, this clears the [`cursor`](Grid::cursor) of the active grid.
+    pub fn hover_end(&self) {
+        self.active_grid.lock_ref().cursor.set_neq(None);
+    }
+
+    /// Handles the mouse wheel, returns `true` if it was used.
+    ///
+    /// The position is relative to the screen, from `0.0` to `1.0`.
+    /// Positive `rows` scrolls downwards and negative `rows` scrolls upwards.
+    pub fn wheel(&self, x: f32, y: f32, rows: i32) -> bool {
+        if self.is_blocked() {
+            return true;
+
+        } else if self.unit_sidebar.contains(x, y) {
+            self.unit_sidebar.scroll_by(&self.active_grid(), rows);
+            true
+
+        } else {
+            false
+        }
     }
 
     pub(crate) fn unit_spritesheet(&self) -> impl Signal<Item = Spritesheet> {
@@ -134,91 +683,105 @@ impl Game {
 
     fn render(this: &Arc<Self>) -> Node {
         engine::Stack::builder()
-            .child_signal(this.grid.signal_ref(clone!(this => move |grid| {
-                Some(Grid::render(&this, grid))
+            .children_signal_vec(this.panes.signal_vec().map(clone!(this => move |pane| {
+                GridPane::render(&this, &pane)
             })))
 
-            .child(ui::SpriteBorder::builder()
-                .apply(|builder| {
-                    builder
-                        .offset(engine::Offset {
-                            x: ParentWidth(0.1),
-                            y: ParentHeight(0.4),
-                        })
-                        .size(Size {
-                            width: SmallestWidth(1.0),
-                            height: SmallestHeight(1.0),
+            .child(ScreenStack::render(&this.screens))
+
+            .child_signal(this.theme.signal_cloned().map(clone!(this => move |theme| {
+                Some(ui::SpriteBorder::builder()
+                    .apply(|builder| {
+                        builder
+                            .offset(engine::Offset {
+                                x: ParentWidth(0.1),
+                                y: ParentHeight(0.4),
+                            })
+                            .size(Size {
+                                width: SmallestWidth(1.0),
+                                height: SmallestHeight(1.0),
+                            })
+                    })
+
+                    .theme(&theme.dialog)
+
+                    .center(BitmapText::builder()
+                        .text("This is a UI dialog box.\n\nHello world!\n\nGoodbye world!".into())
+                        .font(theme.text.font.clone())
+                        .text_color(theme.text.color)
+                        .offset(Offset {
+                            x: Zero,
+                            y: Px(-2),
                         })
-                        /*.size(engine::Size {
-                            width: ParentWidth(0.2),
-                            //width: Px(832),
-                            height: ParentHeight(0.2),
-                        })*/
-                })
+                        .char_size(theme.text.char_size)
+                        .build())
 
-                .spritesheet(this.spritesheets.hud.clone())
+                    .build())
+            })))
 
-                .repeat_tile(RepeatTile {
-                    width: Repeat::Length(Px(32)),
-                    height: Repeat::Length(Px(32)),
-                })
+            .child_signal({
+                let sidebar = this.unit_sidebar.clone();
 
-                .border_size(ui::BorderSize::all(Px(10)))
-
-                //.quadrants(ui::QuadrantGrid::equal_size(0, 0, 16, 16).into())
-                //.quadrants(ui::QuadrantGrid::equal_size(0, 48, 16, 16).into())
-                .quadrants(ui::QuadrantGrid {
-                    start_x: 11,
-                    start_y: 59,
-
-                    up_height: 5,
-                    down_height: 5,
-                    left_width: 5,
-                    right_width: 5,
-
-                    center_width: 16,
-                    center_height: 16,
-                }.into())
-
-                .center(BitmapText::builder()
-                    //.text(" '-.\nABCDEFGHIJKLMNOPQRSTUVWXYZ\nabcdefghijklmnopqrstuvwxyz\nÆÖÜß\nàáäæèéêíïñóùü\n\nHello world goodbye world\n世界你好再見世界 世界你好再见世界\nनमस्कार जगत विदाई जगत\nสวัสดีชาวโลก ลาก่อนชาวโลก\nສະບາຍດີໂລກ ສະບາຍດີໂລກ\nمرحباً أيها العالم وداعاً أيها العالم\n\nT\u{031A}e\u{0303}s\u{0309}t\u{0310}i\u{1AB4}n\u{20DD}g  o\u{0489}\n\u{0000}\u{0000}\u{0000}\u{0000}T\u{0000}e\u{0000}s\u{0000}t\u{0000}i\u{0000}n\u{0000}g\n\nH̶̢̜̣̰̮͔̜̞͕̖̤͈̒͋͊̇̆̓͗͘ę̶̛͉͎̲̙͈͛̆̇̐̍̓͝͝ͅļ̵̰͓̗̩͎̈̓̎͗̈̇̓̀̀̓͘l̶̡̧̧̛̝͈̻͎̱̰̘͚̪̝̰̫̠̼͔̥̝͚͉̻̙̰̟̫͍̫̳̟̟͕̪̝͚̀́̆̓̉̒̓̈̿͌̀̃͑̚͘ͅͅǫ̵̨̢̢̡̛̙̼̤͍̩̘̬̟̞̹͔͕͙̠͉̟̥̲̝̙̥̺͉͇͓̱̗͖͖͔͍̪̰̳̳̩̠̿̇̍̐̈́́͌̓̀̊́̑̈́̈̊̋̃͛̇̃̍̇͌̆́͜͜͜͜͜͝ ̶̛̫̭͈͎̆̍̌̎̄͌̂̋̉̈́́̀͌́̐̆̓͊̽̉̎́̌̆̾̽͌́̕͘͘͘͘͘͜ẗ̴̘̙̜̤̳̺́̍̃̿̆̌̊͒̀̾̍̋̄̍̇͆͂̀͋̏̈̓̓͘͘͝͝h̵̨̪͓̯̫̯̥͇̭̭̱͉̯̮̻͙̘̻̩̠͉̥̰̟̰̗̠͕̘͈̘͎͉̜̞̤̪͖̍͂͂̋̀̃́̍̍̊̾̊̆̃͂̃̆̊̈́̔̐̽̓͘͘̕̚͘͜͝͝e̴̠̘̹͍̝̐́̂̕͝͠r̴̨̢̨̨̡̤̰͔̬̘͉̩̺̭͓̦̠̞̺͇̲̭̉͆͆͗̅̉̉̾̐̐̈́́̉͛̾͌͗͑́͋̎͗́̑͘̚̕͠͠͝͝ͅȩ̸̧̛̛̳̤̞͇̄̀̀͒̾̾͗͋̓̄̽̃͂̓͑͛̈͋̾̈́̊̔̕̕͝͝ ̶̧̡̗̳̗̳͋̈́͋̅̆͛͗͌̆̆͂̿͌͐͒͑͆m̴̧̢̢̛͎͉̩̺̥̲̺͙͎̱̱̖̼̪͍̪̱̬̩̮̞̲͈̫̭͕̗͈͉̥̙̣̺̻̩̯̪̒̆̈́̂̈́̀͊̑̅͂̀͂͊͑̽̽̃́͛̽̿͗̀̈́̀̓̈́̕͘͘̕͜͜͠ͅy̷̧͍͉̲̟̙͉͍̍̂̍͋̾̈́̋̒͌́̿̏͒̒́̊̈́͆̒́̊̆̈̀̎͛̏̆̈́̓̓̒̆͘̕͠͝ ̵̛͓̲̠͖̠̞͂̓̈͆͆̈́̇̇̄͒͋͑̉̏̈́̓́͐̅͐̉̃̃̚̕͘f̴̧̨̩̱̖̜͔̜̣͎̜͖̰̦͈̞̳̥͙̺̜̺̻̳̦̗̜̣͔̘̲̻̩̙̫̱͆̃͊̓͌̈́͊̂̌̊͐͊̂̋̑̂͗͑͜ͅŗ̵̮̺̱͔͖͖̖̲̯͚̬̰͎̜̺̫̠̮̺̰̮͖̳̜̈́̓̇̈́̓͊͋̓̈̀͌͊̆̈̂͑́̊̕͝í̸̢̡̨̢̡̡͇̪̗̬̹̺̝̪͍͙̻̯̲̮͔̼̟̰̞̱̩̱͉̭̹̬͚̼̮͎͚̙̤̱̰̙̯̩̼̬̊̋̓̏̅̒̔͋͑̿̀͛͊͒͌̄̔̉͠ͅͅê̷̛̘̣̞̮͉͙̣̘̦̝̯̰̠͉͉̖̞̘̰͕̻̯̰͖͙̜͖̮͉̖̪̲̪̩͇̥̠͎̲̜͓͈̥̋̈́̄͛͗̈́̿̀͌͘͜͜͠ͅͅͅñ̷̨̡̧̗̣̣̠̥̺̫͓̹̲͓̮̜͕̯̦͚͓̝̩̲͕̳̹͓̻̝̺̼͇̟̜̙̬̤͚̭̠̪̼̫̣̬͈̎̆̒̅͋͛̃͐͌͒̏̃͊̕͜͜ͅd̵̢̧̡̛͚͕͍͖̯̝̦̠̬̬̺̩̯̜̠̱̥̤̼͖̪͙̪̩̼̠͚̘͍̎̏̃.̸̨̩̖̱̭̯̤͔͓͎̙̼̲̮͍͉̦͓͙̠̦̲͈̯͉̯̱̲͙̤̳͍̏̽̂̂͊̈̀̇̐̉́̀̑͑́̌̈́̾̇̏̈͒̊̉̾̀̓̀̋͆͗͌̌̊͐͋̀̈́̀͑̐͋̾͊̚͜͠ͅͅ".into())
-                    .text("This is a UI dialog box.\n\nHello world!\n\nGoodbye world!".into())
-                    .font(this.fonts.unifont.clone())
-                    .offset(Offset {
-                        x: Zero,
-                        y: Px(-2),
-                    })
-                    .char_size(CharSize {
-                        width: Px(16),
-                        height: Px(32),
+                map_ref! {
+                    let theme = this.theme.signal_cloned(),
+                    let grid = this.active_grid.signal_cloned(),
+                    let open = sidebar.open.signal() => move {
+                        if *open {
+                            Some(UnitSidebar::render(theme, grid, &sidebar))
 
-                        /*width: ScreenHeight(1.0 / 80.0),
-                        height: ScreenHeight(2.0 / 80.0),*/
+                        } else {
+                            None
+                        }
+                    }
+                }
+            })
 
-                        /*width: ParentWidth(1.0 / 30.0),
-                        height: ParentWidth(4.0 / 30.0),*/
-                    })
-                    .build())
+            .child_signal({
+                let reveal_fog = this.reveal_fog();
+
+                map_ref! {
+                    let theme = this.theme.signal_cloned(),
+                    let grid = this.active_grid.signal_cloned() => move {
+                        Some(tile_info_panel(theme, grid, reveal_fog))
+                    }
+                }
+            })
 
-                /*.center(BitmapText::builder()
+            .child_signal(map_ref! {
+                let theme = this.theme.signal_cloned(),
+                let grid = this.active_grid.signal_cloned() => {
+                    Some(intel_panel(theme, grid))
+                }
+            })
 
-                    .font(this.fonts.unifont.clone())
-                    .char_size(CharSize {
-                        width: Px(32),
-                        height: Px(64),
-                    })
-                    .z_index(9000.0)
-                    .build())*/
+            .child_signal(this.theme.signal_cloned().map(clone!(this => move |theme| {
+                Some(Banner::render(&theme, &this.banner))
+            })))
 
-                .build())
+            .child_signal(this.theme.signal_cloned().map(clone!(this => move |theme| {
+                Some(Portrait::render(&theme, &this.portrait))
+            })))
+
+            .child_signal(this.perf_overlay.visible.signal().map(clone!(this => move |visible| {
+                if visible {
+                    Some(PerfOverlay::render(&this.perf_overlay))
+
+                } else {
+                    None
+                }
+            })))
 
             .build()
     }
 
-    pub async fn start_engine<Window>(self: &Arc<Self>, window: Window) -> GameEngine
+    /// Starts rendering the game into the window.
+    ///
+    /// The spritesheets and fonts are loaded from `assets`, which falls back to the built-in assets.
+    /// The custom nation palettes in `assets` are added to the built-in palettes.
+    pub async fn start_engine<Window>(self: &Arc<Self>, window: Window, assets: AssetOverrides) -> GameEngine
         where Window: engine::WindowHandle + 'static {
 
-        let screen_size = self.grid.lock_ref().screen_size;
+        let screen_size = self.screen_size;
 
         let mut engine = Engine::new(EngineSettings {
             window,
@@ -228,103 +791,49 @@ impl Game {
                 width: screen_size.width,
                 height: screen_size.height,
             },
+            log_level: Some(engine::LogLevel::WARN),
+            profile: false,
+            ui_scale: 1.0,
+            depth: engine::DepthSettings::default(),
+            quality: self.quality.get(),
+            power_preference: self.power_preference,
+            force_fallback_adapter: false,
+            crash_report: true,
+            pipeline_cache: None,
+            gpu_animation: true,
+            gpu_culling: true,
+            sprite_batching: true,
         }).await;
 
-        // TODO preprocess the images ?
-        fn palettize_spritesheet(palette: &RgbaImage, label: &'static str, bytes: &[u8]) -> IndexedImage {
-            let default_palette = palette.image.rows()
-                .take(1)
-                .flatten()
-                .collect::<Vec<&image::Rgba<u8>>>();
-
-            let spritesheet = RgbaImage::from_bytes(label, bytes);
-
-            let (width, height) = spritesheet.image.dimensions();
-
-            IndexedImage::from_fn(label, width, height, |x, y| {
-                let pixel = spritesheet.image.get_pixel(x, y);
-
-                let alpha = pixel[3];
-
-                if alpha > 0 {
-                    for (index, color) in default_palette.iter().enumerate() {
-                        if pixel == *color {
-                            return image::LumaA([index as u8, alpha]);
-                        }
-                    }
-
-                    panic!("Color not found in palette: {:?}", pixel);
-
-                } else {
-                    image::LumaA([0, 0])
-                }
-            })
-        }
-
         {
-            let effect = RgbaImage::from_bytes("effect", include_bytes!("../../../dist/sprites/effect.png"));
+            let effect = RgbaImage::from_bytes("effect", assets.get(&assets::EFFECT));
 
             let texture = Texture::new();
 
             texture.load(&mut engine, &effect);
 
             self.spritesheets.effect.load(&mut engine, SpritesheetSettings {
+                label: "effect",
                 texture: &texture,
                 palette: None,
+                draw_order: 3,
+                sorted: false,
             });
         }
 
-        {
-            let unit_palette = RgbaImage::from_bytes(
-                "units_palette",
-                include_bytes!("../../../dist/sprites/units_palette.png"),
-            );
-
-            let unit_small = palettize_spritesheet(
-                &unit_palette,
-                "units_small",
-                include_bytes!("../../../dist/sprites/units_small.png"),
-            );
-
-            let unit_big = palettize_spritesheet(
-                &unit_palette,
-                "units_big",
-                include_bytes!("../../../dist/sprites/units_big.png"),
-            );
-
-            let palette_texture = Texture::new();
-
-            palette_texture.load(&mut engine, &unit_palette);
-
-            let texture = Texture::new();
-
-            texture.load(&mut engine, &unit_small);
-
-            self.spritesheets.unit_small.load(&mut engine, SpritesheetSettings {
-                texture: &texture,
-                palette: Some(&palette_texture),
-            });
-
-            let texture = Texture::new();
-
-            texture.load(&mut engine, &unit_big);
-
-            self.spritesheets.unit_big.load(&mut engine, SpritesheetSettings {
-                texture: &texture,
-                palette: Some(&palette_texture),
-            });
-        }
+        // Only the spritesheet for the current appearance is loaded, see GameEngine::update_unit_spritesheet
+        let unit_spritesheet = UnitSpritesheet::new(&mut engine, assets.clone());
 
         {
             let buildings_palette = RgbaImage::from_bytes(
                 "buildings_palette",
-                include_bytes!("../../../dist/sprites/buildings_palette.png"),
+                assets.get(&assets::BUILDINGS_PALETTE),
             );
 
             let buildings_small = palettize_spritesheet(
                 &buildings_palette,
                 "buildings_small",
-                include_bytes!("../../../dist/sprites/buildings_small.png"),
+                assets.get(&assets::BUILDINGS_SMALL),
             );
 
             let texture = Texture::new();
@@ -334,21 +843,24 @@ impl Game {
             palette.load(&mut engine, &buildings_palette);
 
             self.spritesheets.building.load(&mut engine, SpritesheetSettings {
+                label: "building",
                 texture: &texture,
                 palette: Some(&palette),
+                draw_order: 1,
+                sorted: false,
             });
         }
 
         {
             let terrain_palette = RgbaImage::from_bytes(
                 "terrain_palette",
-                include_bytes!("../../../dist/sprites/terrain_palette.png"),
+                assets.get(&assets::TERRAIN_PALETTE),
             );
 
             let terrain_small = palettize_spritesheet(
                 &terrain_palette,
                 "terrain_small",
-                include_bytes!("../../../dist/sprites/terrain_small.png"),
+                assets.get(&assets::TERRAIN_SMALL),
             );
 
             let texture = Texture::new();
@@ -358,21 +870,53 @@ impl Game {
             palette.load(&mut engine, &terrain_palette);
 
             self.spritesheets.terrain.load(&mut engine, SpritesheetSettings {
+                label: "terrain",
                 texture: &texture,
                 palette: Some(&palette),
+                draw_order: 0,
+                sorted: false,
             });
         }
 
         {
-            let image = RgbaImage::from_bytes("hud", include_bytes!("../../../dist/sprites/hud.png"));
+            let image = RgbaImage::from_bytes("hud", assets.get(&assets::HUD));
 
             let texture = Texture::new();
 
             texture.load(&mut engine, &image);
 
             self.spritesheets.hud.load(&mut engine, SpritesheetSettings {
+                label: "hud",
                 texture: &texture,
                 palette: None,
+                draw_order: 4,
+                sorted: false,
+            });
+        }
+
+        {
+            let size = grid::OVERLAY_TILE_SIZE;
+
+            // Red tile with a darker outline, it is displayed with transparency on top of the terrain
+            let image = RgbaImage::from_fn("overlay", size, size, |x, y| {
+                if x == 0 || y == 0 || x == size - 1 || y == size - 1 {
+                    image::Rgba([160, 0, 0, 255])
+
+                } else {
+                    image::Rgba([255, 48, 48, 255])
+                }
+            });
+
+            let texture = Texture::new();
+
+            texture.load(&mut engine, &image);
+
+            self.spritesheets.overlay.load(&mut engine, SpritesheetSettings {
+                label: "overlay",
+                texture: &texture,
+                palette: None,
+                draw_order: 0,
+                sorted: false,
             });
         }
 
@@ -416,7 +960,7 @@ impl Game {
         {
             let image = GrayscaleImage::from_bytes(
                 "unifont_bmp",
-                include_bytes!("../../../dist/fonts/unifont_bmp.png"),
+                assets.get(&assets::UNIFONT_BMP),
             );
 
             let texture = Texture::new();
@@ -440,7 +984,7 @@ impl Game {
         {
             let image = GrayscaleImage::from_bytes(
                 "unifont_ascii",
-                include_bytes!("../../../dist/fonts/unifont_ascii.png"),
+                assets.get(&assets::UNIFONT_ASCII),
             );
 
             let texture = Texture::new();
@@ -462,95 +1006,105 @@ impl Game {
 
         self.init();
 
-        GameEngine {
+        let mut game_engine = GameEngine {
             game: self.clone(),
             engine,
-        }
+            unit_spritesheet,
+            last_render: None,
+        };
+
+        game_engine.update_unit_spritesheet();
+        game_engine.load_nation_palettes(&assets);
+
+        // Compiles the pipelines now, so that the first frame doesn't hitch
+        game_engine.engine.warmup();
+
+        game_engine
     }
 
     fn init(&self) {
         {
-            let grid = self.grid.lock_ref();
+            let grid = self.active_grid.lock_ref();
             let units = grid.units.lock_ref();
 
             use grid::{Coord, Nation};
-            use grid::unit::{Unit, UnitClass};
+            use grid::unit::{UnitClass};
             use grid::action::MoveDirection;
             use grid::explosion::ExplosionAnimation;
             use util::random::random;
 
             grid.spawn_future(clone!(grid => async move {
-                let fighter = Unit::new(
+                let fighter = grid.new_unit(
                     Coord { x: 12.0, y: 3.0 },
                     UnitClass::Fighter,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(fighter.clone());
+                grid.place_unit(fighter.clone());
 
-                let bomber = Unit::new(
+                let bomber = grid.new_unit(
                     Coord { x: 14.0, y: 3.0 },
                     UnitClass::Bomber,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(bomber.clone());
+                grid.place_unit(bomber.clone());
 
-                let black_bomb = Unit::new(
+                let black_bomb = grid.new_unit(
                     Coord { x: 16.0, y: 3.0 },
                     UnitClass::BlackBomb,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(black_bomb.clone());
+                grid.place_unit(black_bomb.clone());
 
-                let stealth = Unit::new(
+                let stealth = grid.new_unit(
                     Coord { x: 18.0, y: 3.0 },
                     UnitClass::Stealth,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(stealth.clone());
+                grid.place_unit(stealth.clone());
 
-                let bcopter = Unit::new(
+                let bcopter = grid.new_unit(
                     Coord { x: 20.0, y: 3.0 },
                     UnitClass::BCopter,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(bcopter.clone());
+                grid.place_unit(bcopter.clone());
 
-                let tcopter = Unit::new(
+                let tcopter = grid.new_unit(
                     Coord { x: 22.0, y: 3.0 },
                     UnitClass::TCopter,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(tcopter.clone());
+                grid.place_unit(tcopter.clone());
 
-                let tank = Unit::new(
+                let tank = grid.new_unit(
                     Coord { x: 12.0, y: 6.0 },
                     UnitClass::Tank,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(tank.clone());
+                grid.place_unit(tank.clone());
 
-                let battleship = Unit::new(
+                let battleship = grid.new_unit(
                     Coord { x: 16.0, y: 6.0 },
                     UnitClass::Battleship,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(battleship.clone());
+                grid.place_unit(battleship.clone());
 
-                let megatank = Unit::new(
+                let megatank = grid.new_unit(
                     Coord { x: 20.0, y: 6.0 },
                     UnitClass::MegaTank,
                     Nation::BlackHole,
                 );
 
-                grid.units.insert(megatank.clone());
+                grid.place_unit(megatank.clone());
             }));
 
             /*grid.spawn_futures([(1.0, 2.0)].into_iter().map(|(x, y)| {
@@ -633,7 +1187,7 @@ impl Game {
 
                     let coord = unit.coord.get();
 
-                    let fighter = Unit::new(
+                    let fighter = grid.new_unit(
                         coord,
                         UnitClass::Fighter,
                         Nation::BlackHole,
@@ -646,7 +1200,7 @@ impl Game {
                     grid.wait(1000.0).await;
 
 
-                    let battleship = Unit::new(
+                    let battleship = grid.new_unit(
                         coord,
                         UnitClass::Battleship,
                         Nation::GreenEarth,
@@ -658,7 +1212,7 @@ impl Game {
                     grid.destroy_unit(&battleship).await;
                     grid.wait(1000.0).await;
 
-                    let megatank = Unit::new(
+                    let megatank = grid.new_unit(
                         coord,
                         UnitClass::MegaTank,
                         Nation::BlueMoon,
@@ -716,26 +1270,227 @@ impl Game {
 }
 
 
+// TODO preprocess the images ?
+fn palettize_spritesheet(palette: &RgbaImage, label: &'static str, bytes: &[u8]) -> IndexedImage {
+    let default_palette = palette.image.rows()
+        .take(1)
+        .flatten()
+        .collect::<Vec<&image::Rgba<u8>>>();
+
+    let spritesheet = RgbaImage::from_bytes(label, bytes);
+
+    let (width, height) = spritesheet.image.dimensions();
+
+    IndexedImage::from_fn(label, width, height, |x, y| {
+        let pixel = spritesheet.image.get_pixel(x, y);
+
+        let alpha = pixel[3];
+
+        if alpha > 0 {
+            for (index, color) in default_palette.iter().enumerate() {
+                if pixel == *color {
+                    return image::LumaA([index as u8, alpha]);
+                }
+            }
+
+            panic!("Color not found in palette: {:?}", pixel);
+
+        } else {
+            image::LumaA([0, 0])
+        }
+    })
+}
+
+
+/// The unit spritesheet which is currently loaded.
+struct UnitSpritesheet {
+    assets: AssetOverrides,
+    palette_image: RgbaImage,
+    palette: Texture,
+    texture: Texture,
+    appearance: Option<UnitAppearance>,
+}
+
+impl UnitSpritesheet {
+    fn new(engine: &mut Engine, assets: AssetOverrides) -> Self {
+        let palette_image = RgbaImage::from_bytes(
+            "units_palette",
+            assets.get(&assets::UNITS_PALETTE),
+        );
+
+        let palette = Texture::new();
+
+        palette.load(engine, &palette_image);
+
+        Self {
+            assets,
+            palette_image,
+            palette,
+            texture: Texture::new(),
+            appearance: None,
+        }
+    }
+
+    /// Adds rows to the palette, which is shared by every unit spritesheet.
+    fn add_palette(&self, engine: &mut Engine, spritesheets: &Spritesheets, image: &RgbaImage) -> Result<u32, PaletteError> {
+        let spritesheet = match self.appearance.expect("Unit spritesheet is not loaded") {
+            UnitAppearance::DualStrikeSmall => &spritesheets.unit_small,
+            UnitAppearance::DualStrikeBig => &spritesheets.unit_big,
+        };
+
+        spritesheet.add_palette(engine, image)
+    }
+
+    /// Loads the spritesheet for the appearance and unloads the previous spritesheet.
+    fn load(&mut self, engine: &mut Engine, spritesheets: &Spritesheets, appearance: UnitAppearance) {
+        if self.appearance == Some(appearance) {
+            return;
+        }
+
+        if let Some(old) = self.appearance {
+            match old {
+                UnitAppearance::DualStrikeSmall => spritesheets.unit_small.unload(engine),
+                UnitAppearance::DualStrikeBig => spritesheets.unit_big.unload(engine),
+            }
+        }
+
+        let (label, spritesheet, image) = match appearance {
+            UnitAppearance::DualStrikeSmall => ("unit_small", &spritesheets.unit_small, palettize_spritesheet(
+                &self.palette_image,
+                "units_small",
+                self.assets.get(&assets::UNITS_SMALL),
+            )),
+
+            UnitAppearance::DualStrikeBig => ("unit_big", &spritesheets.unit_big, palettize_spritesheet(
+                &self.palette_image,
+                "units_big",
+                self.assets.get(&assets::UNITS_BIG),
+            )),
+        };
+
+        // This replaces the previous texture, which frees its GPU memory
+        self.texture.load(engine, &image);
+
+        spritesheet.load(engine, SpritesheetSettings {
+            label,
+            texture: &self.texture,
+            palette: Some(&self.palette),
+            draw_order: 2,
+            sorted: false,
+        });
+
+        self.appearance = Some(appearance);
+    }
+}
+
+
 pub struct GameEngine {
     game: Arc<Game>,
     engine: Engine,
+    unit_spritesheet: UnitSpritesheet,
+
+    /// The time of the most recent frame which was rendered.
+    last_render: Option<f64>,
 }
 
 impl GameEngine {
+    fn update_unit_spritesheet(&mut self) {
+        let appearance = self.game.unit_appearance.get();
+        self.unit_spritesheet.load(&mut self.engine, &self.game.spritesheets, appearance);
+    }
+
+    /// Adds the custom team colors to the palettes, nations with an invalid palette use the built-in colors.
+    fn load_nation_palettes(&mut self, assets: &AssetOverrides) {
+        let mut palettes = NationPalettes::default();
+
+        for nation in Nation::ALL {
+            if let Some(bytes) = assets.units_palette(*nation) {
+                let image = RgbaImage::from_bytes("nation_units_palette", bytes);
+
+                match self.unit_spritesheet.add_palette(&mut self.engine, &self.game.spritesheets, &image) {
+                    Ok(index) => { palettes.units.insert(*nation, index); },
+                    Err(error) => tracing::warn!(?nation, %error, "Skipping invalid units palette"),
+                }
+            }
+
+            if let Some(bytes) = assets.buildings_palette(*nation) {
+                let image = RgbaImage::from_bytes("nation_buildings_palette", bytes);
+
+                match self.game.spritesheets.building.add_palette(&mut self.engine, &image) {
+                    Ok(index) => { palettes.buildings.insert(*nation, index); },
+                    Err(error) => tracing::warn!(?nation, %error, "Skipping invalid buildings palette"),
+                }
+            }
+        }
+
+        self.game.nation_palettes.set(palettes);
+    }
+
+    fn should_render(&self, time: f64) -> bool {
+        if self.game.focused.get() {
+            return true;
+        }
+
+        match self.game.idle_policy.get() {
+            IdlePolicy::Render => true,
+            IdlePolicy::Throttle { fps } => self.last_render.map_or(true, |last| time - last >= 1000.0 / fps),
+            IdlePolicy::Pause => false,
+        }
+    }
+
     pub fn render(&mut self, time: f64) {
+        if !self.should_render(time) {
+            executor::run_futures();
+            return;
+        }
+
+        self.last_render = Some(time);
+
+        self.update_unit_spritesheet();
+
         {
-            let grid = self.game.grid.lock_ref();
+            let quality = self.game.quality.get();
+
+            self.engine.set_quality(quality);
+
+            let panes = self.game.panes.lock_ref();
 
-            grid.time.set(time);
+            for pane in panes.iter() {
+                pane.grid.particle_density.set_neq(quality.particle_density);
+                pane.grid.update_time(time);
+            }
+
+            self.game.screens.update_time(time);
+
+            self.engine.set_time(time);
+
+            // The engine was already warmed up, so the grid futures can start on the first frame.
+            // This is done every frame because panes can be added later.
+            for pane in panes.iter() {
+                pane.grid.start_futures();
+            }
 
             executor::run_futures();
 
-            // This ensures that we only start updating the grid after the first frame has been displayed.
-            // This is necessary to make sure that the engine is fully warmed up and initialized before
-            // it starts processing things.
-            grid.start_futures();
+            let mut effect = self.game.screen_effect.get();
+            self.game.screens.apply_transition(&mut effect);
+            self.engine.set_screen_effect(effect);
         }
 
         self.engine.render().unwrap();
+
+        // The panes are in display order, so the pane which is on top is used last
+        for pane in self.game.panes.lock_ref().iter() {
+            pane.update_location();
+        }
+
+        if self.game.perf_overlay.visible.get() {
+            self.game.perf_overlay.push(self.engine.stats());
+        }
+    }
+
+    /// Returns the current layout of the scene, used for debugging.
+    pub fn dump_tree(&mut self) -> engine::SceneSnapshot {
+        self.engine.dump_tree()
     }
 }
@@ -0,0 +1,572 @@
+//! The players and settings which are chosen before a match starts.
+//!
+//! Everything is stored in [`Mutable`] / [`MutableVec`], so the setup screens
+//! can display it reactively, and the network layer can sync the changes.
+
+use std::sync::Arc;
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+
+use crate::grid::{Grid, Nation, Teams};
+use crate::grid::map::{MapData};
+use rusted_battalions_game_core::replay::{ReplaySettings};
+use rusted_battalions_game_core::rules::{MatchRules};
+use rusted_battalions_game_core::unit::{UnitClass};
+
+pub use rusted_battalions_game_core::{Weather};
+
+
+/// Commanding Officer, each player chooses one CO before the match starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Co {
+    Andy,
+    Max,
+    Sami,
+    Nell,
+    Hachi,
+    Jake,
+    Rachel,
+
+    Olaf,
+    Grit,
+    Colin,
+    Sasha,
+
+    Eagle,
+    Drake,
+    Jess,
+    Javier,
+
+    Kanbei,
+    Sonja,
+    Sensei,
+    Grimm,
+
+    Flak,
+    Lash,
+    Adder,
+    Hawke,
+    Jugger,
+    Koal,
+    Kindle,
+    VonBolt,
+}
+
+impl Co {
+    pub const ALL: &[Self] = &[
+        Self::Andy,
+        Self::Max,
+        Self::Sami,
+        Self::Nell,
+        Self::Hachi,
+        Self::Jake,
+        Self::Rachel,
+        Self::Olaf,
+        Self::Grit,
+        Self::Colin,
+        Self::Sasha,
+        Self::Eagle,
+        Self::Drake,
+        Self::Jess,
+        Self::Javier,
+        Self::Kanbei,
+        Self::Sonja,
+        Self::Sensei,
+        Self::Grimm,
+        Self::Flak,
+        Self::Lash,
+        Self::Adder,
+        Self::Hawke,
+        Self::Jugger,
+        Self::Koal,
+        Self::Kindle,
+        Self::VonBolt,
+    ];
+
+    /// The name which is displayed to the player.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Andy => "Andy",
+            Self::Max => "Max",
+            Self::Sami => "Sami",
+            Self::Nell => "Nell",
+            Self::Hachi => "Hachi",
+            Self::Jake => "Jake",
+            Self::Rachel => "Rachel",
+            Self::Olaf => "Olaf",
+            Self::Grit => "Grit",
+            Self::Colin => "Colin",
+            Self::Sasha => "Sasha",
+            Self::Eagle => "Eagle",
+            Self::Drake => "Drake",
+            Self::Jess => "Jess",
+            Self::Javier => "Javier",
+            Self::Kanbei => "Kanbei",
+            Self::Sonja => "Sonja",
+            Self::Sensei => "Sensei",
+            Self::Grimm => "Grimm",
+            Self::Flak => "Flak",
+            Self::Lash => "Lash",
+            Self::Adder => "Adder",
+            Self::Hawke => "Hawke",
+            Self::Jugger => "Jugger",
+            Self::Koal => "Koal",
+            Self::Kindle => "Kindle",
+            Self::VonBolt => "Von Bolt",
+        }
+    }
+
+    /// The nation which the CO belongs to, any CO can be used by any nation.
+    pub fn nation(&self) -> Nation {
+        match self {
+            Self::Andy |
+            Self::Max |
+            Self::Sami |
+            Self::Nell |
+            Self::Hachi |
+            Self::Jake |
+            Self::Rachel => Nation::OrangeStar,
+
+            Self::Olaf |
+            Self::Grit |
+            Self::Colin |
+            Self::Sasha => Nation::BlueMoon,
+
+            Self::Eagle |
+            Self::Drake |
+            Self::Jess |
+            Self::Javier => Nation::GreenEarth,
+
+            Self::Kanbei |
+            Self::Sonja |
+            Self::Sensei |
+            Self::Grimm => Nation::YellowComet,
+
+            Self::Flak |
+            Self::Lash |
+            Self::Adder |
+            Self::Hawke |
+            Self::Jugger |
+            Self::Koal |
+            Self::Kindle |
+            Self::VonBolt => Nation::BlackHole,
+        }
+    }
+
+    /// The CO which is chosen by default for the nation.
+    pub fn default_for(nation: Nation) -> Self {
+        match nation {
+            Nation::OrangeStar => Self::Andy,
+            Nation::BlueMoon => Self::Olaf,
+            Nation::GreenEarth => Self::Eagle,
+            Nation::YellowComet => Self::Kanbei,
+            Nation::BlackHole => Self::Flak,
+        }
+    }
+}
+
+
+/// Who controls a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    /// A player on this computer.
+    Local,
+
+    /// A player who is connected over the network.
+    Remote,
+
+    /// The AI.
+    Computer,
+}
+
+
+/// A player who has joined the [`Lobby`], each player has a different [`Nation`].
+pub struct LobbyPlayer {
+    pub nation: Nation,
+
+    pub name: Mutable<String>,
+    pub controller: Mutable<Controller>,
+    pub co: Mutable<Co>,
+
+    /// Players on the same team are allies, every player starts on their own team.
+    pub team: Mutable<u32>,
+
+    /// Whether the player has accepted the current settings.
+    pub ready: Mutable<bool>,
+}
+
+impl LobbyPlayer {
+    fn new(nation: Nation, team: u32, controller: Controller) -> Arc<Self> {
+        Arc::new(Self {
+            nation,
+            name: Mutable::new(String::new()),
+            controller: Mutable::new(controller),
+            co: Mutable::new(Co::default_for(nation)),
+            team: Mutable::new(team),
+            ready: Mutable::new(false),
+        })
+    }
+}
+
+
+/// The rules for the match.
+pub struct MatchSettings {
+    pub fog: Mutable<bool>,
+    pub weather: Mutable<Weather>,
+
+    /// The funds which every player has at the start of the match.
+    pub starting_funds: Mutable<u32>,
+
+    /// The funds which every property gives to its owner at the start of their turn.
+    pub income: Mutable<u32>,
+
+    /// A team wins when it owns this many properties, `None` disables the capture limit.
+    pub capture_limit: Mutable<Option<u32>>,
+
+    /// Units which can't be built during the match.
+    pub banned_units: MutableVec<UnitClass>,
+}
+
+impl MatchSettings {
+    /// Returns a copy of the current settings, so that they can be saved in a [`Replay`](crate::Replay).
+    pub fn replay_settings(&self) -> ReplaySettings {
+        ReplaySettings {
+            fog: self.fog.get(),
+            weather: self.weather.get(),
+            starting_funds: self.starting_funds.get(),
+            income: self.income.get(),
+        }
+    }
+
+    /// Returns a copy of the current rules, which should be copied into [`Grid::rules`](crate::grid::Grid::rules) when the match starts.
+    pub fn rules(&self) -> MatchRules {
+        MatchRules {
+            starting_funds: self.starting_funds.get(),
+            income: self.income.get(),
+            capture_limit: self.capture_limit.get(),
+            banned_units: self.banned_units.lock_ref().to_vec(),
+        }
+    }
+
+    /// Bans the unit if it isn't banned, otherwise it unbans the unit.
+    pub fn toggle_ban(&self, class: UnitClass) {
+        let mut lock = self.banned_units.lock_mut();
+
+        if let Some(index) = lock.iter().position(|banned| *banned == class) {
+            lock.remove(index);
+
+        } else {
+            lock.push(class);
+        }
+    }
+
+    /// Short description of the settings, one setting per line.
+    pub fn summary(&self) -> impl Signal<Item = String> {
+        map_ref! {
+            let fog = self.fog.signal(),
+            let weather = self.weather.signal(),
+            let starting_funds = self.starting_funds.signal(),
+            let income = self.income.signal(),
+            let capture_limit = self.capture_limit.signal(),
+            let banned_units = self.banned_units.signal_vec().to_signal_cloned() => {
+                let fog = if *fog { "On" } else { "Off" };
+
+                let capture_limit = match capture_limit {
+                    Some(limit) => limit.to_string(),
+                    None => "None".to_string(),
+                };
+
+                let banned_units = if banned_units.is_empty() {
+                    "None".to_string()
+
+                } else {
+                    banned_units.iter().map(|class| format!("{:?}", class)).collect::<Vec<_>>().join(", ")
+                };
+
+                format!(
+                    "Fog      {}\nWeather  {:?}\nFunds    {}\nIncome   {}\nCapture  {}\nBanned   {}",
+                    fog,
+                    weather,
+                    starting_funds,
+                    income,
+                    capture_limit,
+                    banned_units,
+                )
+            }
+        }
+    }
+
+    fn new() -> Self {
+        Self {
+            fog: Mutable::new(false),
+            weather: Mutable::new(Weather::Clear),
+            starting_funds: Mutable::new(0),
+            income: Mutable::new(1000),
+            capture_limit: Mutable::new(None),
+            banned_units: MutableVec::new(),
+        }
+    }
+}
+
+
+/// The players and settings for a match which hasn't started yet.
+pub struct Lobby {
+    /// The players, in turn order.
+    pub players: MutableVec<Arc<LobbyPlayer>>,
+
+    /// The map which will be played, it is `None` until a map is selected.
+    pub map: Mutable<Option<Arc<MapData>>>,
+
+    pub settings: MatchSettings,
+}
+
+impl Lobby {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            players: MutableVec::new(),
+            map: Mutable::new(None),
+            settings: MatchSettings::new(),
+        })
+    }
+
+    /// Adds a player with the first nation which isn't used by another player.
+    ///
+    /// Returns `None` if every nation is already used.
+    pub fn add_player(&self, controller: Controller) -> Option<Arc<LobbyPlayer>> {
+        let mut lock = self.players.lock_mut();
+
+        let nation = Nation::ALL.iter()
+            .copied()
+            .find(|nation| !lock.iter().any(|player| player.nation == *nation))?;
+
+        let team = (0..).find(|team| !lock.iter().any(|player| player.team.get() == *team)).unwrap();
+
+        let player = LobbyPlayer::new(nation, team, controller);
+
+        lock.push_cloned(player.clone());
+
+        Some(player)
+    }
+
+    pub fn remove_player(&self, nation: Nation) {
+        self.players.lock_mut().retain(|player| player.nation != nation);
+    }
+
+    /// Changes the map, every player must accept the new map.
+    pub fn select_map(&self, map: Arc<MapData>) {
+        self.map.set(Some(map));
+        self.unready();
+    }
+
+    /// Marks every player as not ready, this should be called after changing the settings.
+    pub fn unready(&self) {
+        for player in self.players.lock_ref().iter() {
+            player.ready.set_neq(false);
+        }
+    }
+
+    /// Whether every player is ready.
+    pub fn all_ready(&self) -> impl Signal<Item = bool> {
+        self.players.signal_vec_cloned()
+            .map_signal(|player| player.ready.signal())
+            .to_signal_map(|ready| !ready.is_empty() && ready.iter().all(|ready| *ready))
+            .dedupe()
+    }
+
+    /// The number of different teams.
+    pub fn teams(&self) -> impl Signal<Item = usize> {
+        self.players.signal_vec_cloned()
+            .map_signal(|player| player.team.signal())
+            .to_signal_map(|teams| {
+                let mut teams = teams.to_vec();
+                teams.sort_unstable();
+                teams.dedup();
+                teams.len()
+            })
+            .dedupe()
+    }
+
+    /// The teams of the players, which should be copied into [`Grid::teams`](crate::grid::Grid::teams) when the match starts.
+    pub fn alliances(&self) -> Teams {
+        let mut teams = Teams::new();
+
+        for player in self.players.lock_ref().iter() {
+            teams.set_team(player.nation, player.team.get());
+        }
+
+        teams
+    }
+
+    /// Creates the grid for the match on the selected map, with the lobby's teams and settings.
+    ///
+    /// The grid's [`player`](Grid::player) is the first local player.
+    /// Returns `None` if a map hasn't been selected.
+    pub fn start_match(&self) -> Option<Arc<Grid>> {
+        let map = self.map.get_cloned()?;
+
+        let grid = Grid::new_match(&map);
+
+        let local = self.players.lock_ref().iter()
+            .find(|player| player.controller.get() == Controller::Local)
+            .map(|player| player.nation);
+
+        if let Some(nation) = local {
+            grid.player.set(nation);
+        }
+
+        grid.start(self.alliances(), self.settings.rules());
+
+        Some(grid)
+    }
+
+    /// Whether the match can start: a map is selected, there are at least 2 teams, and every player is ready.
+    pub fn can_start(&self) -> impl Signal<Item = bool> {
+        map_ref! {
+            let has_map = self.map.signal_ref(|map| map.is_some()),
+            let teams = self.teams(),
+            let ready = self.all_ready() => {
+                *has_map && *teams >= 2 && *ready
+            }
+        }.dedupe()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use futures::task::noop_waker_ref;
+    use futures_signals::signal::{Signal, SignalExt};
+    use super::{Lobby, Controller};
+    use crate::grid::{Nation};
+    use crate::grid::terrain::{TerrainClass};
+    use crate::grid::map::{MapData};
+
+    fn current<S>(signal: &mut S) -> Option<S::Item> where S: Signal + Unpin {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut output = None;
+
+        while let Poll::Ready(Some(value)) = signal.poll_change_unpin(&mut cx) {
+            output = Some(value);
+        }
+
+        output
+    }
+
+    #[test]
+    fn players() {
+        let lobby = Lobby::new();
+
+        for nation in Nation::ALL {
+            assert_eq!(lobby.add_player(Controller::Local).unwrap().nation, *nation);
+        }
+
+        assert!(lobby.add_player(Controller::Local).is_none());
+
+        lobby.remove_player(Nation::BlueMoon);
+
+        let player = lobby.add_player(Controller::Computer).unwrap();
+
+        assert_eq!(player.nation, Nation::BlueMoon);
+        assert_eq!(player.team.get(), 1);
+    }
+
+    #[test]
+    fn can_start() {
+        let lobby = Lobby::new();
+
+        let mut signal = lobby.can_start().boxed();
+
+        assert_eq!(current(&mut signal), Some(false));
+
+        let first = lobby.add_player(Controller::Local).unwrap();
+        let second = lobby.add_player(Controller::Remote).unwrap();
+
+        lobby.select_map(std::sync::Arc::new(MapData::new(4, 4, TerrainClass::Grass)));
+
+        first.ready.set(true);
+        second.ready.set(true);
+
+        assert_eq!(current(&mut signal), Some(true));
+
+        // Players on the same team can't play against each other
+        second.team.set(first.team.get());
+
+        assert_eq!(current(&mut signal), Some(false));
+
+        second.team.set(5);
+
+        assert_eq!(current(&mut signal), Some(true));
+
+        lobby.unready();
+
+        assert_eq!(current(&mut signal), Some(false));
+    }
+
+    #[test]
+    fn rules() {
+        use rusted_battalions_game_core::unit::{UnitClass};
+
+        let lobby = Lobby::new();
+
+        lobby.settings.capture_limit.set(Some(10));
+        lobby.settings.toggle_ban(UnitClass::Tank);
+        lobby.settings.toggle_ban(UnitClass::Mech);
+        lobby.settings.toggle_ban(UnitClass::Tank);
+
+        let rules = lobby.settings.rules();
+
+        assert_eq!(rules.capture_limit, Some(10));
+        assert_eq!(rules.banned_units, [UnitClass::Mech]);
+
+        assert!(lobby.start_match().is_none());
+
+        lobby.add_player(Controller::Remote).unwrap();
+        lobby.add_player(Controller::Local).unwrap();
+        lobby.select_map(std::sync::Arc::new(MapData::new(4, 4, TerrainClass::Grass)));
+
+        let grid = lobby.start_match().unwrap();
+
+        assert_eq!(*grid.rules.lock_ref(), rules);
+        assert!(!grid.can_build(UnitClass::Mech));
+        assert!(grid.can_build(UnitClass::Tank));
+        assert_eq!(grid.player.get(), Nation::BlueMoon);
+    }
+
+    #[test]
+    fn start_match() {
+        use crate::grid::building::{BuildingClass};
+        use crate::grid::map::{MapBuilding};
+
+        let lobby = Lobby::new();
+
+        let orange = lobby.add_player(Controller::Local).unwrap();
+        let blue = lobby.add_player(Controller::Computer).unwrap();
+        let green = lobby.add_player(Controller::Computer).unwrap();
+
+        green.team.set(orange.team.get());
+
+        let mut map = MapData::new(4, 4, TerrainClass::Grass);
+        map.buildings.push(MapBuilding { x: 0, y: 0, class: BuildingClass::Base, nation: Some(Nation::OrangeStar) });
+        map.buildings.push(MapBuilding { x: 3, y: 3, class: BuildingClass::Base, nation: Some(Nation::GreenEarth) });
+        lobby.select_map(std::sync::Arc::new(map));
+
+        let grid = lobby.start_match().unwrap();
+
+        {
+            let teams = grid.teams.lock_ref();
+            assert!(teams.are_allies(Nation::OrangeStar, Nation::GreenEarth));
+            assert!(!teams.are_allies(Nation::OrangeStar, Nation::BlueMoon));
+        }
+
+        assert_eq!(grid.player.get(), orange.nation);
+
+        let mut is_ally = grid.is_ally(Nation::GreenEarth).boxed();
+        assert_eq!(current(&mut is_ally), Some(true));
+
+        // Blue Moon doesn't have any units, so the allies have already won
+        assert_eq!(grid.winning_team(), Some(orange.team.get()));
+        assert_ne!(grid.winning_team(), Some(blue.team.get()));
+    }
+}
@@ -48,19 +48,6 @@ impl Renderer {
         })
     }
 
-    /*fn clear(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        let size = self.size.lock_ref();
-        ctx.clear_rect(0.0, 0.0, size.width, size.height);
-    }
-
-    fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d, delta: f64) {
-        let size = self.size.lock_ref();
-
-        log::info!("HELLO {}", delta);
-        ctx.set_fill_style(&JsValue::from("red"));
-        ctx.fill_rect(20.0, 20.0, size.width - 40.0, size.height - 40.0);
-    }*/
-
     pub fn render(this: &Arc<Self>) -> Dom {
         html!("div", {
             .child(html!("canvas" => web_sys::HtmlCanvasElement, {
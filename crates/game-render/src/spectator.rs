@@ -0,0 +1,107 @@
+//! Watching a match without playing in it.
+//!
+//! The spectator uses the same [`Game`](crate::Game) and renderer as a normal player,
+//! but it can only move the camera, and the grid is changed by the actions which are
+//! [`push`](Spectator::push)ed by the replay / network layer.
+
+use std::sync::{Arc, Mutex};
+use std::pin::Pin;
+use std::future::Future;
+use std::collections::VecDeque;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
+
+use crate::grid::{Grid};
+
+
+/// An action which changes the grid, such as [`Grid::move_unit`].
+pub type Playback = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectatorSettings {
+    /// Whether the units and buildings which are hidden by fog are displayed.
+    pub reveal_fog: bool,
+
+    /// How long to wait before playing an action, in milliseconds.
+    ///
+    /// This prevents a spectator from passing fog-free information to a player.
+    pub delay: f64,
+}
+
+impl Default for SpectatorSettings {
+    fn default() -> Self {
+        Self {
+            reveal_fog: true,
+            delay: 0.0,
+        }
+    }
+}
+
+
+/// Plays the actions of a match for a viewer who isn't playing in it.
+pub struct Spectator {
+    settings: SpectatorSettings,
+    grid: Arc<Grid>,
+
+    /// The grid time when each action was pushed.
+    queue: Mutex<VecDeque<(f64, Playback)>>,
+
+    pending: Mutable<usize>,
+}
+
+impl Spectator {
+    pub(crate) fn new(grid: Arc<Grid>, settings: SpectatorSettings) -> Arc<Self> {
+        Arc::new(Self {
+            settings,
+            grid,
+            queue: Mutex::new(VecDeque::new()),
+            pending: Mutable::new(0),
+        })
+    }
+
+    #[inline]
+    pub fn reveal_fog(&self) -> bool {
+        self.settings.reveal_fog
+    }
+
+    /// Adds an action to the end of the queue.
+    ///
+    /// Actions are played one at a time, in the same order they were pushed,
+    /// each action is played [`delay`](SpectatorSettings::delay) milliseconds after it was pushed.
+    pub fn push<F>(&self, action: F) where F: Future<Output = ()> + Send + 'static {
+        let mut queue = self.queue.lock().unwrap();
+        queue.push_back((self.grid.time.get(), Box::pin(action)));
+        self.pending.set(queue.len());
+    }
+
+    /// The number of actions which haven't been played yet.
+    pub fn pending(&self) -> impl Signal<Item = usize> {
+        self.pending.signal()
+    }
+
+    fn pop(&self) -> Option<(f64, Playback)> {
+        let mut queue = self.queue.lock().unwrap();
+        let action = queue.pop_front();
+        self.pending.set(queue.len());
+        action
+    }
+
+    /// Plays the queued actions, this never finishes.
+    pub(crate) fn play(self: Arc<Self>) -> impl Future<Output = ()> {
+        async move {
+            loop {
+                self.pending.signal_ref(|pending| *pending > 0).wait_for(true).await;
+
+                if let Some((pushed, action)) = self.pop() {
+                    let remaining = (pushed + self.settings.delay) - self.grid.time.get();
+
+                    if remaining > 0.0 {
+                        self.grid.wait(remaining).await;
+                    }
+
+                    action.await;
+                }
+            }
+        }
+    }
+}
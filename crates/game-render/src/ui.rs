@@ -1,3 +1,25 @@
 mod sprite_border;
+mod focus;
+mod theme;
+mod accessibility;
+mod controls;
+mod banner;
+mod power;
+mod transition;
+mod settings_summary;
+mod list;
+mod perf_overlay;
+mod portrait;
 
 pub use sprite_border::*;
+pub use focus::*;
+pub use theme::*;
+pub use accessibility::*;
+pub use controls::*;
+pub use banner::*;
+pub use power::*;
+pub use transition::*;
+pub use settings_summary::*;
+pub use list::*;
+pub use perf_overlay::*;
+pub use portrait::*;
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+use futures_signals::signal::{Mutable, Signal};
+
+
+/// Messages for screen readers, such as combat results.
+///
+/// The client displays the messages in an ARIA live region, so that they are read aloud.
+pub struct Announcer {
+    message: Mutable<Option<Arc<str>>>,
+}
+
+impl Announcer {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            message: Mutable::new(None),
+        })
+    }
+
+    /// Reads the message aloud, even if it's the same as the previous message.
+    pub fn announce<S>(&self, message: S) where S: Into<Arc<str>> {
+        self.message.set(Some(message.into()));
+    }
+
+    pub fn message_signal(&self) -> impl Signal<Item = Option<Arc<str>>> {
+        self.message.signal_cloned()
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
+use dominator::clone;
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Offset, Anchor, Size, ParentWidth, ParentHeight, SmallestWidth, SmallestHeight};
+
+use crate::ui::{SpriteBorder, Theme};
+
+
+/// Smoothly accelerates and then decelerates.
+fn ease(percent: f32) -> f32 {
+    percent * percent * (3.0 - (2.0 * percent))
+}
+
+
+/// Large banner which slides across the screen, such as "DAY 3" at the start of a turn.
+///
+/// While the banner is displayed the [`Game`](crate::Game) ignores input.
+pub struct Banner {
+    text: Mutable<Option<Arc<str>>>,
+
+    /// How far the banner has moved across the screen, from `0.0` to `1.0`.
+    percent: Mutable<f32>,
+}
+
+impl Banner {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            text: Mutable::new(None),
+            percent: Mutable::new(0.0),
+        })
+    }
+
+    /// Whether a banner is currently displayed.
+    pub fn is_active(&self) -> bool {
+        self.text.lock_ref().is_some()
+    }
+
+    /// Displays the banner until the timer reaches `1.0`.
+    pub(crate) async fn run<S>(&self, text: Arc<str>, timer: S) where S: Signal<Item = f64> {
+        self.percent.set(0.0);
+        self.text.set(Some(text));
+
+        timer.for_each(|percent| {
+            self.percent.set(percent as f32);
+            async {}
+        }).await;
+
+        self.text.set(None);
+    }
+
+    /// It slides in from the right, pauses in the middle, then slides out to the left.
+    fn slide(percent: f32) -> f32 {
+        if percent < 0.25 {
+            1.0 - ease(percent / 0.25)
+
+        } else if percent < 0.75 {
+            0.0
+
+        } else {
+            -ease((percent - 0.75) / 0.25)
+        }
+    }
+
+    pub(crate) fn render(theme: &Theme, this: &Arc<Self>) -> Node {
+        let theme = theme.clone();
+
+        engine::Stack::builder()
+            .child_signal(this.text.signal_cloned().map(clone!(this => move |text| {
+                text.map(|text| {
+                    SpriteBorder::builder()
+                        .apply(|builder| {
+                            builder
+                                .offset_signal(this.percent.signal_ref(|percent| {
+                                    Offset {
+                                        x: ParentWidth(Self::slide(*percent)),
+                                        y: ParentHeight(0.4),
+                                    }
+                                }))
+                                .size(Size {
+                                    width: ParentWidth(1.0),
+                                    height: SmallestHeight(1.0),
+                                })
+                        })
+
+                        .theme(&theme.dialog)
+
+                        .center(engine::BitmapText::builder()
+                            .text(text.to_string().into())
+                            .font(theme.banner.font.clone())
+                            .text_color(theme.banner.color)
+                            .char_size(theme.banner.char_size)
+                            .anchor(Anchor::Top)
+                            .size(Size {
+                                width: SmallestWidth(1.0),
+                                height: SmallestHeight(1.0),
+                            })
+                            .build())
+
+                        .build()
+                })
+            })))
+
+            .build()
+    }
+}
@@ -0,0 +1,179 @@
+use crate::ui::{FocusKey, FocusDirection};
+
+
+/// A logical input, which is independent of the physical key / button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    Confirm,
+    Cancel,
+
+    /// Selects the next unit which hasn't moved yet.
+    NextUnit,
+
+    EndTurn,
+
+    /// Moves the UI focus.
+    Focus(FocusKey),
+
+    /// Moves the camera of the active grid by 1 tile.
+    Pan(FocusDirection),
+
+    /// Shows / hides the list of the player's units.
+    UnitSidebar,
+
+    /// Shows / hides the tiles which the enemy units can attack.
+    DangerZone,
+
+    /// Shows / hides the damage dealt and units lost by each nation, see [`MatchStats`](crate::grid::stats::MatchStats).
+    Intel,
+
+    /// Opens / closes the summary of every army's units, properties, and income.
+    Status,
+
+    /// Shows / hides every unit and building sprite, this is intended for checking the spritesheets.
+    SpriteGallery,
+
+    /// Logs a snapshot of the scene layout, this is intended for debugging.
+    DumpScene,
+
+    /// Shows / hides the frame time graph, see [`PerfOverlay`](crate::ui::PerfOverlay).
+    PerfOverlay,
+}
+
+
+impl Action {
+    /// Whether the action changes the grid, instead of only changing the view.
+    pub fn changes_state(&self) -> bool {
+        match self {
+            Self::Confirm | Self::Cancel | Self::EndTurn => true,
+            _ => false,
+        }
+    }
+}
+
+
+/// A physical key or button.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Input {
+    Key {
+        /// The name of the key, this is the same as the
+        /// [`KeyboardEvent.key`](https://developer.mozilla.org/en-US/docs/Web/API/KeyboardEvent/key) property.
+        key: String,
+
+        /// Whether Shift must be held down.
+        shift: bool,
+    },
+
+    /// The index of a gamepad button, using the
+    /// [standard gamepad layout](https://w3c.github.io/gamepad/#remapping).
+    Button(u32),
+}
+
+impl Input {
+    /// Creates an [`Input::Key`] without Shift.
+    pub fn key(key: &str) -> Self {
+        Self::Key { key: key.to_string(), shift: false }
+    }
+
+    /// Creates an [`Input::Key`] with Shift.
+    pub fn shift_key(key: &str) -> Self {
+        Self::Key { key: key.to_string(), shift: true }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Binding {
+    pub input: Input,
+    pub action: Action,
+}
+
+
+/// Maps keys / buttons to [`Action`]s.
+///
+/// Each input is bound to at most 1 action, but an action can have multiple inputs.
+///
+/// With the `serde` feature it can be serialized, so that it can be saved with the rest of the settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ControlsConfig {
+    bindings: Vec<Binding>,
+}
+
+impl ControlsConfig {
+    /// Returns the action which is bound to the input.
+    pub fn action(&self, input: &Input) -> Option<Action> {
+        self.bindings.iter().find(|binding| binding.input == *input).map(|binding| binding.action)
+    }
+
+    /// Returns every input which is bound to the action.
+    pub fn inputs(&self, action: Action) -> impl Iterator<Item = &Input> {
+        self.bindings.iter().filter(move |binding| binding.action == action).map(|binding| &binding.input)
+    }
+
+    #[inline]
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Binds the input to the action, this replaces the existing binding for the input.
+    pub fn bind(&mut self, input: Input, action: Action) {
+        self.unbind(&input);
+        self.bindings.push(Binding { input, action });
+    }
+
+    /// Removes the binding for the input.
+    pub fn unbind(&mut self, input: &Input) {
+        self.bindings.retain(|binding| binding.input != *input);
+    }
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        let mut this = Self { bindings: vec![] };
+
+        this.bind(Input::key("Enter"), Action::Confirm);
+        this.bind(Input::key(" "), Action::Confirm);
+        this.bind(Input::key("Escape"), Action::Cancel);
+        this.bind(Input::key("Backspace"), Action::Cancel);
+        this.bind(Input::key("n"), Action::NextUnit);
+        this.bind(Input::key("e"), Action::EndTurn);
+        this.bind(Input::key("x"), Action::DangerZone);
+        this.bind(Input::key("u"), Action::UnitSidebar);
+        this.bind(Input::key("i"), Action::Intel);
+        this.bind(Input::key("t"), Action::Status);
+
+        this.bind(Input::key("Tab"), Action::Focus(FocusKey::Next));
+        this.bind(Input::shift_key("Tab"), Action::Focus(FocusKey::Previous));
+        this.bind(Input::key("ArrowUp"), Action::Focus(FocusKey::Direction(FocusDirection::Up)));
+        this.bind(Input::key("ArrowDown"), Action::Focus(FocusKey::Direction(FocusDirection::Down)));
+        this.bind(Input::key("ArrowLeft"), Action::Focus(FocusKey::Direction(FocusDirection::Left)));
+        this.bind(Input::key("ArrowRight"), Action::Focus(FocusKey::Direction(FocusDirection::Right)));
+
+        this.bind(Input::key("w"), Action::Pan(FocusDirection::Up));
+        this.bind(Input::key("s"), Action::Pan(FocusDirection::Down));
+        this.bind(Input::key("a"), Action::Pan(FocusDirection::Left));
+        this.bind(Input::key("d"), Action::Pan(FocusDirection::Right));
+
+        this.bind(Input::key("F7"), Action::PerfOverlay);
+        this.bind(Input::key("F8"), Action::SpriteGallery);
+        this.bind(Input::key("F9"), Action::DumpScene);
+
+        // Standard gamepad layout
+        this.bind(Input::Button(0), Action::Confirm);
+        this.bind(Input::Button(1), Action::Cancel);
+        this.bind(Input::Button(5), Action::NextUnit);
+        this.bind(Input::Button(9), Action::EndTurn);
+        this.bind(Input::Button(2), Action::DangerZone);
+        this.bind(Input::Button(3), Action::UnitSidebar);
+        this.bind(Input::Button(12), Action::Focus(FocusKey::Direction(FocusDirection::Up)));
+        this.bind(Input::Button(13), Action::Focus(FocusKey::Direction(FocusDirection::Down)));
+        this.bind(Input::Button(14), Action::Focus(FocusKey::Direction(FocusDirection::Left)));
+        this.bind(Input::Button(15), Action::Focus(FocusKey::Direction(FocusDirection::Right)));
+
+        this
+    }
+}
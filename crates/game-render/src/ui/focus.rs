@@ -0,0 +1,262 @@
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node};
+
+use crate::ui::{SpriteBorderBuilder};
+
+
+/// Identifier for a focusable UI element, this is created with [`FocusManager::register`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FocusId(u32);
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FocusDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+
+/// Input which moves the focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FocusKey {
+    /// Moves to the next element in the tab order.
+    Next,
+
+    /// Moves to the previous element in the tab order.
+    Previous,
+
+    /// Moves to the neighbor in that direction.
+    Direction(FocusDirection),
+}
+
+
+struct FocusState {
+    next_id: u32,
+
+    /// The tab order, this is the order in which the elements were registered.
+    order: Vec<FocusId>,
+
+    neighbors: HashMap<(FocusId, FocusDirection), FocusId>,
+
+    /// Text which is read aloud by screen readers when the element is focused.
+    labels: HashMap<FocusId, Arc<str>>,
+}
+
+
+/// Keeps track of which UI element is focused, and moves the focus with the keyboard / gamepad.
+///
+/// Tab navigation follows the registration order, arrow navigation follows the
+/// neighbors which were added with [`set_neighbor`](FocusManager::set_neighbor).
+pub struct FocusManager {
+    state: Mutex<FocusState>,
+    focused: Mutable<Option<FocusId>>,
+}
+
+impl FocusManager {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(FocusState {
+                next_id: 0,
+                order: vec![],
+                neighbors: HashMap::new(),
+                labels: HashMap::new(),
+            }),
+            focused: Mutable::new(None),
+        })
+    }
+
+    /// Adds a new focusable element to the end of the tab order.
+    pub fn register(&self) -> FocusId {
+        let mut lock = self.state.lock().unwrap();
+
+        let id = FocusId(lock.next_id);
+        lock.next_id += 1;
+
+        lock.order.push(id);
+
+        id
+    }
+
+    /// Removes the element, if it was focused then nothing will be focused.
+    pub fn unregister(&self, id: FocusId) {
+        let mut lock = self.state.lock().unwrap();
+
+        lock.order.retain(|x| *x != id);
+        lock.neighbors.retain(|(from, _), to| *from != id && *to != id);
+        lock.labels.remove(&id);
+
+        drop(lock);
+
+        if self.focused.get() == Some(id) {
+            self.focused.set(None);
+        }
+    }
+
+    /// When `from` is focused, pressing `direction` will move the focus to `to`.
+    ///
+    /// This is one-way, so if you want to be able to go back you must also add a link from `to` to `from`.
+    pub fn set_neighbor(&self, from: FocusId, direction: FocusDirection, to: FocusId) {
+        self.state.lock().unwrap().neighbors.insert((from, direction), to);
+    }
+
+    /// Sets the text which is read aloud by screen readers when the element is focused.
+    pub fn set_label<S>(&self, id: FocusId, label: S) where S: Into<Arc<str>> {
+        self.state.lock().unwrap().labels.insert(id, label.into());
+    }
+
+    pub fn label(&self, id: FocusId) -> Option<Arc<str>> {
+        self.state.lock().unwrap().labels.get(&id).cloned()
+    }
+
+    #[inline]
+    pub fn focus(&self, id: FocusId) {
+        self.focused.set_neq(Some(id));
+    }
+
+    #[inline]
+    pub fn blur(&self) {
+        self.focused.set_neq(None);
+    }
+
+    #[inline]
+    pub fn focused(&self) -> Option<FocusId> {
+        self.focused.get()
+    }
+
+    pub fn focused_signal(&self) -> impl Signal<Item = Option<FocusId>> {
+        self.focused.signal()
+    }
+
+    /// The label of the focused element, this is used for screen readers.
+    pub fn focused_label_signal(self: &Arc<Self>) -> impl Signal<Item = Option<Arc<str>>> {
+        let this = self.clone();
+        self.focused.signal().map(move |focused| focused.and_then(|id| this.label(id)))
+    }
+
+    pub fn is_focused(&self, id: FocusId) -> impl Signal<Item = bool> {
+        self.focused.signal_ref(move |focused| *focused == Some(id)).dedupe()
+    }
+
+    /// Moves the focus, returns `true` if the input was used.
+    ///
+    /// If nothing is focused then [`FocusKey::Next`] focuses the first element and
+    /// [`FocusKey::Previous`] focuses the last element.
+    pub fn navigate(&self, key: FocusKey) -> bool {
+        let lock = self.state.lock().unwrap();
+
+        let focused = self.focused.get();
+
+        let new_focus = match key {
+            FocusKey::Next | FocusKey::Previous => {
+                let len = lock.order.len();
+
+                if len == 0 {
+                    None
+
+                } else {
+                    let index = focused.and_then(|id| lock.order.iter().position(|x| *x == id));
+
+                    let index = match (key, index) {
+                        (FocusKey::Next, Some(index)) => (index + 1) % len,
+                        (FocusKey::Next, None) => 0,
+                        (_, Some(index)) => (index + len - 1) % len,
+                        (_, None) => len - 1,
+                    };
+
+                    Some(lock.order[index])
+                }
+            },
+
+            FocusKey::Direction(direction) => {
+                focused.and_then(|id| lock.neighbors.get(&(id, direction)).copied())
+            },
+        };
+
+        drop(lock);
+
+        if let Some(id) = new_focus {
+            self.focus(id);
+            true
+
+        } else {
+            false
+        }
+    }
+}
+
+
+/// Displays a border around the child when it is focused.
+pub struct FocusBorderBuilder {
+    focus: Option<(Arc<FocusManager>, FocusId)>,
+    aria_label: Option<Arc<str>>,
+    border: Option<SpriteBorderBuilder>,
+    child: Option<Node>,
+}
+
+impl FocusBorderBuilder {
+    #[inline]
+    pub fn focus(mut self, manager: &Arc<FocusManager>, id: FocusId) -> Self {
+        self.focus = Some((manager.clone(), id));
+        self
+    }
+
+    /// Text which is read aloud by screen readers when the child is focused.
+    #[inline]
+    pub fn aria_label<S>(mut self, label: S) -> Self where S: Into<Arc<str>> {
+        self.aria_label = Some(label.into());
+        self
+    }
+
+    /// The border which is displayed when focused, it doesn't need a center.
+    #[inline]
+    pub fn border(mut self, border: SpriteBorderBuilder) -> Self {
+        self.border = Some(border);
+        self
+    }
+
+    #[inline]
+    pub fn child(mut self, child: Node) -> Self {
+        self.child = Some(child);
+        self
+    }
+
+    pub fn build(self) -> Node {
+        let (manager, id) = self.focus.expect("Missing focus");
+        let border = self.border.expect("Missing border");
+        let child = self.child.expect("Missing child");
+
+        if let Some(label) = self.aria_label {
+            manager.set_label(id, label);
+        }
+
+        engine::Stack::builder()
+            .child(child)
+            .child(border
+                .center(engine::Stack::builder().build())
+                .apply(move |builder| builder.visible_signal(manager.is_focused(id)))
+                .build())
+            .build()
+    }
+}
+
+
+pub struct FocusBorder;
+
+impl FocusBorder {
+    #[inline]
+    pub fn builder() -> FocusBorderBuilder {
+        FocusBorderBuilder {
+            focus: None,
+            aria_label: None,
+            border: None,
+            child: None,
+        }
+    }
+}
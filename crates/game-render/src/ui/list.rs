@@ -0,0 +1,158 @@
+use std::ops::Range;
+use std::sync::Arc;
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, SignalExt, LocalBoxSignal};
+use futures_signals::signal_vec::{SignalVec, SignalVecExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, NodeRef, Size, SmallestWidth, SmallestHeight};
+
+
+/// The indexes of the items which are displayed.
+fn visible_range(len: usize, scroll: usize, rows: usize) -> Range<usize> {
+    let start = scroll.min(len.saturating_sub(rows));
+    let end = (start + rows).min(len);
+    start..end
+}
+
+
+/// Scrollable list which only creates Nodes for the items that are visible.
+///
+/// It always has `rows` Nodes, when the list is scrolled the Nodes are reused by changing
+/// which item they display. This keeps the layout fast even with hundreds of items, such
+/// as the list of replays or maps.
+///
+/// Every row must have the same height, so that [`row_at`](List::row_at) can find the clicked item.
+pub struct List {
+    rows: usize,
+
+    /// Index of the first displayed item.
+    scroll: Mutable<usize>,
+
+    /// Number of items, this is updated when the items change.
+    len: Mutable<usize>,
+
+    node_ref: NodeRef,
+}
+
+impl List {
+    /// Creates a list which displays up to `rows` items at the same time.
+    pub fn new(rows: usize) -> Arc<Self> {
+        assert!(rows > 0, "List must have at least 1 row");
+
+        Arc::new(Self {
+            rows,
+            scroll: Mutable::new(0),
+            len: Mutable::new(0),
+            node_ref: NodeRef::new(),
+        })
+    }
+
+    /// The indexes of the items which are currently displayed.
+    pub fn visible(&self) -> Range<usize> {
+        visible_range(self.len.get(), self.scroll.get(), self.rows)
+    }
+
+    /// Scrolls the list by the number of rows, negative numbers scroll upwards.
+    pub fn scroll_by(&self, rows: i32) {
+        let start = self.visible().start as i64;
+        let max = self.len.get().saturating_sub(self.rows) as i64;
+
+        self.scroll.set_neq((start + rows as i64).clamp(0, max) as usize);
+    }
+
+    /// Scrolls the list so that the item at `index` is visible.
+    pub fn scroll_to(&self, index: usize) {
+        let visible = self.visible();
+
+        if index < visible.start {
+            self.scroll.set_neq(index);
+
+        } else if index >= visible.start + self.rows {
+            self.scroll.set_neq(index + 1 - self.rows);
+        }
+    }
+
+    /// Returns whether the screen position is on top of the list.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.row_at(x, y).is_some()
+    }
+
+    /// Returns the index of the item at the screen position, or `None` if there isn't an item at that position.
+    pub fn row_at(&self, x: f32, y: f32) -> Option<usize> {
+        let location = self.node_ref.location()?;
+
+        let visible = self.visible();
+
+        if visible.is_empty() ||
+           x < location.position.x ||
+           y < location.position.y ||
+           x >= location.position.x + location.size.width ||
+           y >= location.position.y + location.size.height {
+            return None;
+        }
+
+        // Every row has the same height
+        let row = (((y - location.position.y) / location.size.height) * visible.len() as f32) as usize;
+
+        Some(visible.start + row)
+    }
+
+    /// Displays the items, `render_row` is called once for each row.
+    ///
+    /// The Signal which is passed to `render_row` is the item which the row displays,
+    /// or `None` if the row is empty, in which case the row is hidden.
+    pub fn render<A, S, F>(this: &Arc<Self>, items: S, mut render_row: F) -> Node
+        where A: Clone + 'static,
+              S: SignalVec<Item = A> + 'static,
+              F: FnMut(LocalBoxSignal<'static, Option<A>>) -> Node {
+
+        let this_len = this.len.clone();
+        let rows = this.rows;
+
+        let page = map_ref! {
+            let items = items.to_signal_cloned(),
+            let scroll = this.scroll.signal() => move {
+                this_len.set_neq(items.len());
+
+                items[visible_range(items.len(), *scroll, rows)].to_vec()
+            }
+        }.broadcast();
+
+        engine::Column::builder()
+            .node_ref(&this.node_ref)
+            .size(Size {
+                width: SmallestWidth(1.0),
+                height: SmallestHeight(1.0),
+            })
+            .children((0..rows).map(|row| {
+                let item = page.signal_ref(move |page| page.get(row).cloned());
+
+                engine::Stack::builder()
+                    .visible_signal(page.signal_ref(move |page| row < page.len()).dedupe())
+                    .size(Size {
+                        width: SmallestWidth(1.0),
+                        height: SmallestHeight(1.0),
+                    })
+                    .child(render_row(item.boxed_local()))
+                    .build()
+            }))
+            .build()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::visible_range;
+
+    #[test]
+    fn visible() {
+        assert_eq!(visible_range(0, 0, 5), 0..0);
+        assert_eq!(visible_range(3, 0, 5), 0..3);
+        assert_eq!(visible_range(100, 10, 5), 10..15);
+
+        // The scroll is clamped so the last page is always full
+        assert_eq!(visible_range(100, 98, 5), 95..100);
+        assert_eq!(visible_range(3, 2, 5), 0..3);
+    }
+}
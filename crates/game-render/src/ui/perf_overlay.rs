@@ -0,0 +1,209 @@
+use std::sync::{Arc, Mutex};
+use futures_signals::map_ref;
+use futures_signals::signal::{Mutable, SignalExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Offset, Size, ParentWidth, ParentHeight, ColorRgb, EngineStats};
+
+
+/// Number of frames which are displayed.
+pub const PERF_FRAMES: usize = 120;
+
+/// The time (in milliseconds) which fills the whole height of the graph.
+const PERF_MAX_TIME: f32 = 1000.0 / 30.0;
+
+/// The time (in milliseconds) of one frame at 60 FPS, a line is displayed at this height.
+const PERF_TARGET_TIME: f32 = 1000.0 / 60.0;
+
+const LAYOUT_COLOR: ColorRgb = ColorRgb { r: 0.3, g: 0.5, b: 1.0 };
+const DRAW_COLOR: ColorRgb = ColorRgb { r: 0.3, g: 0.9, b: 0.4 };
+const GPU_COLOR: ColorRgb = ColorRgb { r: 1.0, g: 0.6, b: 0.2 };
+
+
+/// Converts milliseconds into a percentage of the graph's height.
+fn graph_height(time: f32) -> f32 {
+    (time / PERF_MAX_TIME).min(1.0)
+}
+
+
+struct FrameBar {
+    layout: Mutable<f32>,
+    draw: Mutable<f32>,
+
+    /// This is `0.0` if the GPU doesn't support timestamp queries.
+    gpu: Mutable<f32>,
+}
+
+impl FrameBar {
+    fn new() -> Self {
+        Self {
+            layout: Mutable::new(0.0),
+            draw: Mutable::new(0.0),
+            gpu: Mutable::new(0.0),
+        }
+    }
+
+    /// A single segment of a bar, `below` is the height of the segments which are below this segment.
+    fn segment<A, B>(x: f32, width: f32, color: ColorRgb, below: A, height: B) -> Node
+        where A: futures_signals::signal::Signal<Item = f32> + 'static,
+              B: futures_signals::signal::Signal<Item = f32> + 'static {
+
+        let location = map_ref! {
+            let below = below,
+            let height = height => {
+                (graph_height(*below), graph_height(*below + *height))
+            }
+        }.broadcast();
+
+        engine::Rect::builder()
+            .color(color)
+            .offset_signal(location.signal_ref(move |(_, top)| {
+                Offset {
+                    x: ParentWidth(x),
+                    y: ParentHeight(1.0 - top),
+                }
+            }))
+            .size_signal(location.signal_ref(move |(bottom, top)| {
+                Size {
+                    width: ParentWidth(width),
+                    height: ParentHeight(top - bottom),
+                }
+            }))
+            .build()
+    }
+
+    /// The CPU time is on the left half of the bar, the GPU time is on the right half.
+    fn render(&self, index: usize) -> Node {
+        let width = 1.0 / PERF_FRAMES as f32;
+        let x = index as f32 * width;
+        let half = width * 0.5;
+
+        engine::Stack::builder()
+            .child(Self::segment(x, half, LAYOUT_COLOR, futures_signals::signal::always(0.0), self.layout.signal()))
+            .child(Self::segment(x, half, DRAW_COLOR, self.layout.signal(), self.draw.signal()))
+            .child(Self::segment(x + half, half, GPU_COLOR, futures_signals::signal::always(0.0), self.gpu.signal()))
+            .build()
+    }
+}
+
+
+/// Bar graph of how long the most recent frames took, this is intended for finding performance regressions.
+///
+/// Each frame has a bar, the left half is the CPU time (blue for layout, green for drawing) and the
+/// right half is the GPU time (orange). The GPU time is only displayed if
+/// [`EngineSettings::profile`](engine::EngineSettings::profile) is enabled.
+///
+/// The newest frame replaces the oldest frame, so only one bar changes every frame, the white line is the newest frame.
+pub struct PerfOverlay {
+    pub visible: Mutable<bool>,
+
+    bars: Vec<FrameBar>,
+
+    /// Index of the bar which is replaced by the next frame.
+    next: Mutex<usize>,
+    newest: Mutable<usize>,
+}
+
+impl PerfOverlay {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            visible: Mutable::new(false),
+            bars: (0..PERF_FRAMES).map(|_| FrameBar::new()).collect(),
+            next: Mutex::new(0),
+            newest: Mutable::new(0),
+        })
+    }
+
+    /// Records the stats of the most recently rendered frame.
+    pub(crate) fn push(&self, stats: &EngineStats) {
+        let mut next = self.next.lock().unwrap();
+
+        let bar = &self.bars[*next];
+
+        bar.layout.set_neq(stats.cpu.layout as f32);
+        bar.draw.set_neq(stats.cpu.draw as f32);
+        bar.gpu.set_neq(stats.gpu_time().unwrap_or(0.0) as f32);
+
+        self.newest.set_neq(*next);
+
+        *next = (*next + 1) % PERF_FRAMES;
+    }
+
+    pub(crate) fn render(this: &Arc<Self>) -> Node {
+        let width = 1.0 / PERF_FRAMES as f32;
+
+        engine::Stack::builder()
+            .offset(Offset {
+                x: ParentWidth(0.5),
+                y: ParentHeight(0.75),
+            })
+            .size(Size {
+                width: ParentWidth(0.48),
+                height: ParentHeight(0.2),
+            })
+
+            .child(engine::Rect::builder()
+                .color(ColorRgb { r: 0.0, g: 0.0, b: 0.0 })
+                .alpha(0.6)
+                .build())
+
+            .children(this.bars.iter().enumerate().map(|(index, bar)| bar.render(index)))
+
+            // The time of one frame at 60 FPS
+            .child(engine::Rect::builder()
+                .color(ColorRgb { r: 1.0, g: 0.2, b: 0.2 })
+                .offset(Offset {
+                    x: ParentWidth(0.0),
+                    y: ParentHeight(1.0 - graph_height(PERF_TARGET_TIME)),
+                })
+                .size(Size {
+                    width: ParentWidth(1.0),
+                    height: ParentHeight(0.01),
+                })
+                .build())
+
+            .child(engine::Rect::builder()
+                .color(ColorRgb { r: 1.0, g: 1.0, b: 1.0 })
+                .offset_signal(this.newest.signal_ref(move |newest| {
+                    Offset {
+                        x: ParentWidth(*newest as f32 * width),
+                        y: ParentHeight(0.0),
+                    }
+                }))
+                .size(Size {
+                    width: ParentWidth(width * 0.5),
+                    height: ParentHeight(1.0),
+                })
+                .build())
+
+            .build()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{PerfOverlay, PERF_FRAMES, graph_height};
+    use rusted_battalions_engine::{EngineStats, CpuStats};
+
+    #[test]
+    fn push() {
+        let overlay = PerfOverlay::new();
+
+        let stats = EngineStats {
+            cpu: CpuStats { layout: 2.0, draw: 3.0 },
+            ..EngineStats::default()
+        };
+
+        for _ in 0..(PERF_FRAMES + 1) {
+            overlay.push(&stats);
+        }
+
+        // The oldest frame was replaced
+        assert_eq!(overlay.newest.get(), 0);
+        assert_eq!(*overlay.next.lock().unwrap(), 1);
+        assert_eq!(overlay.bars[0].draw.get(), 3.0);
+        assert_eq!(overlay.bars[0].gpu.get(), 0.0);
+
+        assert_eq!(graph_height(1000.0), 1.0);
+    }
+}
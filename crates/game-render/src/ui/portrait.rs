@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
+use dominator::clone;
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Offset, Anchor, Size, ParentWidth, ParentHeight, SmallestWidth, SmallestHeight};
+
+use crate::lobby::{Co};
+use crate::ui::{SpriteBorder, Theme};
+
+
+/// Smoothly accelerates and then decelerates.
+fn ease(percent: f32) -> f32 {
+    percent * percent * (3.0 - (2.0 * percent))
+}
+
+
+/// The facial expression of a CO's portrait, each dialogue line can use a different emotion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Emotion {
+    #[default]
+    Normal,
+    Happy,
+    Angry,
+}
+
+impl Emotion {
+    pub const ALL: &[Self] = &[
+        Self::Normal,
+        Self::Happy,
+        Self::Angry,
+    ];
+
+    /// Text which is displayed after the CO's name, because there isn't any portrait art yet.
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Normal => "",
+            Self::Happy => " (happy)",
+            Self::Angry => " (angry)",
+        }
+    }
+}
+
+
+/// The CO who is speaking, it slides in from the left when they start speaking and slides out when they finish.
+///
+/// There isn't any portrait art yet, so it displays the CO's name and emotion.
+pub struct Portrait {
+    speaker: Mutable<Option<(Co, Emotion)>>,
+
+    /// How far the portrait has slid onto the screen, from `0.0` (hidden) to `1.0` (fully visible).
+    percent: Mutable<f32>,
+}
+
+impl Portrait {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            speaker: Mutable::new(None),
+            percent: Mutable::new(0.0),
+        })
+    }
+
+    /// The CO who is currently displayed.
+    pub fn speaker(&self) -> Option<(Co, Emotion)> {
+        self.speaker.get()
+    }
+
+    /// Changes the portrait, if the CO is already displayed then only the emotion changes,
+    /// otherwise the old CO slides out and the new CO slides in.
+    pub(crate) async fn show<S, F>(&self, co: Co, emotion: Emotion, timer: F) where F: Fn() -> S, S: Signal<Item = f64> {
+        match self.speaker.get() {
+            Some((old, _)) if old == co => {
+                self.speaker.set_neq(Some((co, emotion)));
+            },
+            old => {
+                if old.is_some() {
+                    self.hide(timer()).await;
+                }
+
+                self.speaker.set(Some((co, emotion)));
+                self.slide(timer(), false).await;
+            },
+        }
+    }
+
+    /// Slides the portrait off of the screen.
+    pub(crate) async fn hide<S>(&self, timer: S) where S: Signal<Item = f64> {
+        if self.speaker.lock_ref().is_some() {
+            self.slide(timer, true).await;
+            self.speaker.set(None);
+        }
+    }
+
+    async fn slide<S>(&self, timer: S, exit: bool) where S: Signal<Item = f64> {
+        timer.for_each(|percent| {
+            let percent = percent as f32;
+            self.percent.set(if exit { 1.0 - percent } else { percent });
+            async {}
+        }).await;
+    }
+
+    pub(crate) fn render(theme: &Theme, this: &Arc<Self>) -> Node {
+        let theme = theme.clone();
+
+        engine::Stack::builder()
+            .child_signal(this.speaker.signal().map(clone!(this => move |speaker| {
+                speaker.map(|(co, emotion)| {
+                    SpriteBorder::builder()
+                        .apply(|builder| {
+                            builder
+                                .offset_signal(this.percent.signal_ref(|percent| {
+                                    Offset {
+                                        x: ParentWidth(-0.3 * (1.0 - ease(*percent))),
+                                        y: ParentHeight(0.7),
+                                    }
+                                }))
+                                .size(Size {
+                                    width: ParentWidth(0.3),
+                                    height: SmallestHeight(1.0),
+                                })
+                        })
+
+                        .theme(&theme.dialog)
+
+                        .center(engine::BitmapText::builder()
+                            .text(format!("{}{}", co.name(), emotion.label()).into())
+                            .font(theme.text.font.clone())
+                            .text_color(theme.text.color)
+                            .char_size(theme.text.char_size)
+                            .anchor(Anchor::Top)
+                            .size(Size {
+                                width: SmallestWidth(1.0),
+                                height: SmallestHeight(1.0),
+                            })
+                            .build())
+
+                        .build()
+                })
+            })))
+
+            .build()
+    }
+}
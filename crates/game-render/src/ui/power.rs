@@ -0,0 +1,38 @@
+use rusted_battalions_engine::{ColorRgb, ScreenEffect};
+
+
+/// The full-screen effect which is displayed while a CO power is active, see [`Game::power_effect`](crate::Game::power_effect).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerEffect {
+    /// Tints the edges of the screen with the color.
+    Vignette(ColorRgb),
+
+    /// Inverts the colors of the screen.
+    Invert,
+
+    /// Lines which rush across the screen.
+    SpeedLines,
+}
+
+impl PowerEffect {
+    /// Returns the effect with an intensity from `0.0` to `1.0`, this is used for fading in / out.
+    pub(crate) fn screen_effect(&self, intensity: f32) -> ScreenEffect {
+        match self {
+            Self::Vignette(tint) => ScreenEffect {
+                tint: *tint,
+                vignette: intensity,
+                ..ScreenEffect::default()
+            },
+
+            Self::Invert => ScreenEffect {
+                invert: intensity,
+                ..ScreenEffect::default()
+            },
+
+            Self::SpeedLines => ScreenEffect {
+                speed_lines: intensity,
+                ..ScreenEffect::default()
+            },
+        }
+    }
+}
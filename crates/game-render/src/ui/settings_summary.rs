@@ -0,0 +1,48 @@
+use std::sync::Arc;
+use futures_signals::map_ref;
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, Offset, Size, ParentWidth, ParentHeight, SmallestWidth, SmallestHeight};
+
+use crate::ui::{SpriteBorder, Theme};
+use crate::lobby::{Lobby};
+
+
+/// Panel which displays the selected map and the [`MatchSettings`](crate::lobby::MatchSettings),
+/// it is displayed next to the map list while the players are choosing a map.
+pub fn settings_summary(theme: &Theme, lobby: &Arc<Lobby>) -> Node {
+    SpriteBorder::builder()
+        .apply(|builder| {
+            builder
+                .offset(Offset {
+                    x: ParentWidth(0.6),
+                    y: ParentHeight(0.05),
+                })
+                .size(Size {
+                    width: SmallestWidth(1.0),
+                    height: SmallestHeight(1.0),
+                })
+        })
+
+        .theme(&theme.dialog)
+
+        .center(engine::BitmapText::builder()
+            .text_signal(map_ref! {
+                let map = lobby.map.signal_ref(|map| {
+                    map.as_ref().map(|map| format!("{}x{}", map.width, map.height))
+                }),
+                let summary = lobby.settings.summary() => {
+                    let map = map.as_deref().unwrap_or("None");
+                    format!("Map      {}\n{}", map, summary).into()
+                }
+            })
+            .font(theme.text.font.clone())
+            .text_color(theme.text.color)
+            .char_size(theme.text.char_size)
+            .size(Size {
+                width: SmallestWidth(1.0),
+                height: SmallestHeight(1.0),
+            })
+            .build())
+
+        .build()
+}
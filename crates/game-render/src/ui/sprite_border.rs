@@ -1,9 +1,12 @@
 use rusted_battalions_engine as engine;
-use rusted_battalions_engine::{Tile, Node, Spritesheet};
+use rusted_battalions_engine::{Tile, Node, Spritesheet, Size, Origin};
+
+use crate::ui::{ThemeBorder};
 
 pub use rusted_battalions_engine::{BorderSize, RepeatTile, Repeat};
 
 
+#[derive(Debug, Clone, Copy)]
 pub struct QuadrantGrid {
     pub start_x: u32,
     pub start_y: u32,
@@ -129,6 +132,8 @@ pub struct SpriteBorderBuilder {
     quadrants: Option<Quadrants>,
     center: Option<Node>,
     repeat_tile: RepeatTile,
+    edge_repeat: Option<RepeatTile>,
+    corner_size: Option<Size>,
     builder: engine::BorderGridBuilder,
 }
 
@@ -172,40 +177,86 @@ impl SpriteBorderBuilder {
         self
     }
 
+    /// How the edges repeat their tile along their length.
+    ///
+    /// The up / down edges use the `width` and the left / right edges use the `height`.
+    ///
+    /// The default is to use the same repetition as [`repeat_tile`](Self::repeat_tile).
+    /// Use [`Repeat::None`] to stretch the edges instead.
+    #[inline]
+    pub fn edge_repeat(mut self, edge_repeat: RepeatTile) -> Self {
+        self.edge_repeat = Some(edge_repeat);
+        self
+    }
+
+    /// Draws the corners with a different size than the border size.
+    ///
+    /// Corners which are bigger than the border size will overlap the edges and center.
+    ///
+    /// The default is to use the border size.
+    #[inline]
+    pub fn corner_size(mut self, corner_size: Size) -> Self {
+        self.corner_size = Some(corner_size);
+        self
+    }
+
+    /// Sets the spritesheet, border size, quadrants, and repetition from the theme.
+    pub fn theme(mut self, theme: &ThemeBorder) -> Self {
+        self.edge_repeat = theme.edge_repeat;
+        self.corner_size = theme.corner_size;
+
+        self.spritesheet(theme.spritesheet.clone())
+            .border_size(theme.border_size)
+            .quadrants(theme.quadrants.into())
+            .repeat_tile(theme.repeat_tile)
+    }
+
     pub fn build(self) -> Node {
         let spritesheet = self.spritesheet.expect("Missing spritesheet");
         let border_size = self.border_size.expect("Missing border_size");
         let quadrants = self.quadrants.expect("Missing quadrants");
         let center = self.center.expect("Missing center");
 
+        let edge_repeat = self.edge_repeat.unwrap_or(self.repeat_tile);
+        let corner_size = self.corner_size;
+
+        let corner = |tile: Tile, origin: Origin| {
+            engine::Sprite::builder()
+                .spritesheet(spritesheet.clone())
+                .tile(tile)
+                .apply(|builder| {
+                    if let Some(corner_size) = corner_size {
+                        builder.size(corner_size).origin(origin)
+
+                    } else {
+                        builder
+                    }
+                })
+                .build()
+        };
+
         self.builder
             .border_size(border_size)
             .quadrants(engine::Quadrants {
-                up_left: engine::Sprite::builder()
-                    .spritesheet(spritesheet.clone())
-                    .tile(quadrants.up_left)
-                    .build(),
+                up_left: corner(quadrants.up_left, Origin { x: 0.0, y: 0.0 }),
 
                 up: engine::Sprite::builder()
                     .spritesheet(spritesheet.clone())
                     .tile(quadrants.up)
                     .repeat_tile(RepeatTile {
+                        width: edge_repeat.width,
                         height: Repeat::None,
-                        ..self.repeat_tile
                     })
                     .build(),
 
-                up_right: engine::Sprite::builder()
-                    .spritesheet(spritesheet.clone())
-                    .tile(quadrants.up_right)
-                    .build(),
+                up_right: corner(quadrants.up_right, Origin { x: 1.0, y: 0.0 }),
 
                 left: engine::Sprite::builder()
                     .spritesheet(spritesheet.clone())
                     .tile(quadrants.left)
                     .repeat_tile(RepeatTile {
                         width: Repeat::None,
-                        ..self.repeat_tile
+                        height: edge_repeat.height,
                     })
                     .build(),
 
@@ -223,28 +274,22 @@ impl SpriteBorderBuilder {
                     .tile(quadrants.right)
                     .repeat_tile(RepeatTile {
                         width: Repeat::None,
-                        ..self.repeat_tile
+                        height: edge_repeat.height,
                     })
                     .build(),
 
-                down_left: engine::Sprite::builder()
-                    .spritesheet(spritesheet.clone())
-                    .tile(quadrants.down_left)
-                    .build(),
+                down_left: corner(quadrants.down_left, Origin { x: 0.0, y: 1.0 }),
 
                 down: engine::Sprite::builder()
                     .spritesheet(spritesheet.clone())
                     .tile(quadrants.down)
                     .repeat_tile(RepeatTile {
+                        width: edge_repeat.width,
                         height: Repeat::None,
-                        ..self.repeat_tile
                     })
                     .build(),
 
-                down_right: engine::Sprite::builder()
-                    .spritesheet(spritesheet.clone())
-                    .tile(quadrants.down_right)
-                    .build(),
+                down_right: corner(quadrants.down_right, Origin { x: 1.0, y: 1.0 }),
             })
             .build()
     }
@@ -262,6 +307,8 @@ impl SpriteBorder {
             quadrants: None,
             center: None,
             repeat_tile: RepeatTile::default(),
+            edge_repeat: None,
+            corner_size: None,
             builder: engine::BorderGrid::builder(),
         }
     }
@@ -0,0 +1,110 @@
+use rusted_battalions_engine::{Spritesheet, BitmapFont, CharSize, ColorRgb, Size, Px};
+
+use crate::ui::{QuadrantGrid, BorderSize, RepeatTile, Repeat};
+
+
+/// The art which is used to draw a [`SpriteBorder`](crate::ui::SpriteBorder).
+#[derive(Clone)]
+pub struct ThemeBorder {
+    pub spritesheet: Spritesheet,
+    pub quadrants: QuadrantGrid,
+    pub border_size: BorderSize,
+    pub repeat_tile: RepeatTile,
+
+    /// See [`SpriteBorderBuilder::edge_repeat`](crate::ui::SpriteBorderBuilder::edge_repeat).
+    pub edge_repeat: Option<RepeatTile>,
+
+    /// See [`SpriteBorderBuilder::corner_size`](crate::ui::SpriteBorderBuilder::corner_size).
+    pub corner_size: Option<Size>,
+}
+
+
+#[derive(Clone)]
+pub struct ThemeText {
+    pub font: BitmapFont,
+    pub char_size: CharSize,
+    pub color: ColorRgb,
+}
+
+
+/// Describes how the UI looks, this can be swapped at runtime to change the skin of every widget.
+#[derive(Clone)]
+pub struct Theme {
+    pub name: &'static str,
+
+    /// Border for dialog boxes and menus.
+    pub dialog: ThemeBorder,
+
+    /// Border which is displayed around the focused widget.
+    pub focus: ThemeBorder,
+
+    pub text: ThemeText,
+
+    /// Text for the [`Banner`](crate::ui::Banner).
+    pub banner: ThemeText,
+}
+
+impl Theme {
+    /// Theme which matches Advance Wars: Dual Strike.
+    pub fn dual_strike(spritesheet: Spritesheet, font: BitmapFont) -> Self {
+        let border = ThemeBorder {
+            spritesheet,
+
+            quadrants: QuadrantGrid {
+                start_x: 11,
+                start_y: 59,
+
+                up_height: 5,
+                down_height: 5,
+                left_width: 5,
+                right_width: 5,
+
+                center_width: 16,
+                center_height: 16,
+            },
+
+            border_size: BorderSize::all(Px(10)),
+
+            repeat_tile: RepeatTile {
+                width: Repeat::Length(Px(32)),
+                height: Repeat::Length(Px(32)),
+            },
+
+            edge_repeat: None,
+            corner_size: None,
+        };
+
+        Self {
+            name: "Dual Strike",
+
+            dialog: border.clone(),
+
+            focus: ThemeBorder {
+                repeat_tile: RepeatTile::default(),
+                ..border
+            },
+
+            text: ThemeText {
+                font: font.clone(),
+
+                char_size: CharSize {
+                    width: Px(16),
+                    height: Px(32),
+                },
+
+                color: ColorRgb::default(),
+            },
+
+            banner: ThemeText {
+                font,
+
+                char_size: CharSize {
+                    width: Px(32),
+                    height: Px(64),
+                },
+
+                color: ColorRgb::default(),
+            },
+        }
+    }
+}
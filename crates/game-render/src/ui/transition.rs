@@ -0,0 +1,163 @@
+use std::sync::Arc;
+use std::future::Future;
+use futures_signals::signal::{Mutable, SignalExt};
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+use rusted_battalions_engine as engine;
+use rusted_battalions_engine::{Node, ScreenEffect};
+
+use crate::util::signal::{timer};
+
+
+/// The effect which is displayed while changing screens, see [`ScreenStack::transition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Fades the old screen to black, and then fades in the new screen.
+    Fade,
+
+    /// Covers the old screen with black from left to right, and then uncovers the new screen.
+    Wipe,
+
+    /// Pixelates the old screen, and then unpixelates the new screen.
+    Mosaic,
+}
+
+impl Transition {
+    /// Applies the transition with an intensity from `0.0` to `1.0`.
+    fn apply(&self, effect: &mut ScreenEffect, intensity: f32) {
+        match self {
+            Self::Fade => {
+                effect.fade = intensity;
+            },
+
+            Self::Wipe => {
+                effect.wipe = intensity;
+            },
+
+            // The screen also fades, so that the change of screens is hidden
+            Self::Mosaic => {
+                effect.mosaic = intensity;
+                effect.fade = intensity;
+            },
+        }
+    }
+}
+
+
+/// Creates the Node for a screen, it is called again every time that the screen is displayed.
+pub type Screen = Arc<dyn Fn() -> Node>;
+
+
+/// Full-screen UI, such as menus, which are displayed on top of the grids.
+///
+/// Only the top screen is displayed.
+pub struct ScreenStack {
+    screens: MutableVec<Screen>,
+
+    /// The current transition and its intensity.
+    transition: Mutable<Option<(Transition, f32)>>,
+
+    /// The render time, transitions don't use the grid time because the grid can be paused.
+    time: Mutable<f64>,
+}
+
+impl ScreenStack {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            screens: MutableVec::new(),
+            transition: Mutable::new(None),
+            time: Mutable::new(0.0),
+        })
+    }
+
+    /// Whether a transition is currently running.
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.lock_ref().is_some()
+    }
+
+    /// Whether any screen is displayed.
+    pub fn is_empty(&self) -> bool {
+        self.screens.lock_ref().is_empty()
+    }
+
+    /// Whether the screen is currently the top screen.
+    pub fn is_top(&self, screen: &Screen) -> bool {
+        self.screens.lock_ref().last().map_or(false, |top| Arc::ptr_eq(top, screen))
+    }
+
+    /// Displays the screen on top of the existing screens.
+    pub fn push(&self, screen: Screen) {
+        self.screens.lock_mut().push_cloned(screen);
+    }
+
+    /// Removes the top screen, which displays the screen below it.
+    pub fn pop(&self) -> Option<Screen> {
+        self.screens.lock_mut().pop()
+    }
+
+    /// Replaces the top screen, or pushes the screen if there aren't any screens.
+    pub fn replace(&self, screen: Screen) {
+        let mut lock = self.screens.lock_mut();
+        lock.pop();
+        lock.push_cloned(screen);
+    }
+
+    /// Changes screens with a transition which lasts for `duration` milliseconds.
+    ///
+    /// The old screen is covered by the transition, then `change` is called to change
+    /// the screens (for example with [`push`](ScreenStack::push) or [`pop`](ScreenStack::pop)),
+    /// and then the new screen is uncovered.
+    ///
+    /// If the returned future is dropped early then the transition is immediately removed,
+    /// but `change` is only called if the transition reached the halfway point.
+    pub fn transition<F>(self: &Arc<Self>, kind: Transition, duration: f64, change: F) -> impl Future<Output = ()>
+        where F: FnOnce(&Self) {
+
+        /// Removes the transition even if the future is cancelled.
+        struct Cleanup(Arc<ScreenStack>);
+
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                self.0.transition.set_neq(None);
+            }
+        }
+
+        let this = self.clone();
+        let half = duration / 2.0;
+
+        async move {
+            let cleanup = Cleanup(this);
+
+            timer(&cleanup.0.time, half).for_each(|percent| {
+                cleanup.0.transition.set_neq(Some((kind, percent as f32)));
+                async {}
+            }).await;
+
+            change(&cleanup.0);
+
+            timer(&cleanup.0.time, half).for_each(|percent| {
+                cleanup.0.transition.set_neq(Some((kind, 1.0 - percent as f32)));
+                async {}
+            }).await;
+        }
+    }
+
+    /// Advances the time, this must be called every frame with the render time.
+    pub(crate) fn update_time(&self, time: f64) {
+        self.time.set_neq(time);
+    }
+
+    /// Adds the current transition to the effect.
+    pub(crate) fn apply_transition(&self, effect: &mut ScreenEffect) {
+        if let Some((kind, intensity)) = *self.transition.lock_ref() {
+            kind.apply(effect, intensity);
+        }
+    }
+
+    pub(crate) fn render(this: &Arc<Self>) -> Node {
+        engine::Stack::builder()
+            .child_signal(this.screens.signal_vec_cloned().to_signal_map(|screens| {
+                screens.last().map(|screen| screen())
+            }))
+            .build()
+    }
+}
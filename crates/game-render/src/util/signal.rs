@@ -1,7 +1,42 @@
 use std::sync::Arc;
+use futures_signals::signal::{Mutable, Signal, SignalExt};
 use futures_signals::signal_vec::{MutableVec, SignalVec, MutableVecLockRef};
 
 
+/// Returns a Signal that will last for `duration` number of milliseconds, based on the `time`.
+///
+/// The value of the Signal is the percentage of time from now until `duration`:
+///
+///   0.0 = now
+///   1.0 = now + duration
+///
+/// Once the Signal reaches 1.0 it will stop.
+pub fn timer(time: &Mutable<f64>, duration: f64) -> impl Signal<Item = f64> + Send {
+    struct TimerState {
+        start: f64,
+        end: f64,
+    }
+
+    let mut state = None;
+
+    time.signal_ref(move |time| {
+        let state = state.get_or_insert_with(|| {
+            TimerState {
+                start: *time,
+                end: time + duration,
+            }
+        });
+
+        if *time >= state.end {
+            1.0
+
+        } else {
+            (time - state.start) / duration
+        }
+    }).stop_if(|value| *value == 1.0)
+}
+
+
 #[repr(transparent)]
 pub struct SortedVec<T> {
     mutable: MutableVec<Arc<T>>,
@@ -76,3 +111,73 @@ impl<T> SortedVec<T> {
         }
     }*/
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use futures::task::noop_waker_ref;
+    use futures_signals::signal_vec::{SignalVec, SignalVecExt, VecDiff};
+    use super::SortedVec;
+
+    fn changes<S>(signal: &mut S) -> Vec<VecDiff<Arc<u32>>> where S: SignalVec<Item = Arc<u32>> + Unpin {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut output = vec![];
+
+        while let Poll::Ready(Some(change)) = signal.poll_vec_change_unpin(&mut cx) {
+            output.push(change);
+        }
+
+        output
+    }
+
+    // Rendering relies on insert / remove only sending a single diff, so that the siblings are not rebuilt.
+    #[test]
+    fn insert_remove_single_diff() {
+        let a = Arc::new(1);
+        let b = Arc::new(2);
+        let c = Arc::new(3);
+
+        let vec = SortedVec::with_values(vec![a.clone(), b.clone()]);
+
+        let mut signal = vec.signal_vec();
+
+        assert!(matches!(changes(&mut signal).as_slice(), [VecDiff::Replace { values }] if values.len() == 2));
+
+        vec.insert(c.clone());
+
+        assert!(matches!(
+            changes(&mut signal).as_slice(),
+            [VecDiff::InsertAt { value, .. } | VecDiff::Push { value }] if Arc::ptr_eq(value, &c)
+        ));
+
+        vec.remove(&a);
+
+        assert!(matches!(changes(&mut signal).as_slice(), [VecDiff::RemoveAt { .. } | VecDiff::Pop {}]));
+
+        assert_eq!(vec.lock_ref().len(), 2);
+    }
+
+    // The order is by pointer, so it doesn't depend on the insertion order.
+    #[test]
+    fn order_is_stable() {
+        let values: Vec<Arc<u32>> = (0..10).map(Arc::new).collect();
+
+        let forward = SortedVec::with_values(vec![]);
+        let backward = SortedVec::with_values(vec![]);
+
+        for value in values.iter() {
+            forward.insert(value.clone());
+        }
+
+        for value in values.iter().rev() {
+            backward.insert(value.clone());
+        }
+
+        let forward = forward.lock_ref();
+        let backward = backward.lock_ref();
+
+        assert!(forward.iter().zip(backward.iter()).all(|(x, y)| Arc::ptr_eq(x, y)));
+    }
+}
@@ -0,0 +1,327 @@
+//! The [`Rules`] for Rusted Battalions matches, using the rules from the game core.
+//!
+//! The actions are [`ReplayAction`]s, so the [`history`](crate::Match::history) of a match can be saved as a replay.
+
+use std::sync::Arc;
+use rusted_battalions_game_core::{Nation};
+use rusted_battalions_game_core::action::{MoveDirection, TileOccupant, ResolvedPath, resolve_path};
+use rusted_battalions_game_core::audit::{StateSnapshot, UnitSnapshot, BuildingSnapshot};
+use rusted_battalions_game_core::map::{MapData};
+use rusted_battalions_game_core::replay::{ReplayAction};
+use rusted_battalions_game_core::team::{Teams};
+
+use crate::{Rules, PlayerId};
+
+
+fn unit_tile(unit: &UnitSnapshot) -> (i64, i64) {
+    (unit.x.round() as i64, unit.y.round() as i64)
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameError {
+    /// There isn't a unit with the id.
+    UnknownUnit(u32),
+
+    /// The unit is owned by a different nation than the player.
+    NotOwner(u32),
+
+    /// The unit is allied with the player, so the player can't destroy it.
+    Allied(u32),
+
+    /// It isn't the player's turn.
+    NotTurn,
+
+    /// The unit already moved or waited this turn.
+    AlreadyMoved(u32),
+
+    /// The unit doesn't have any fuel, so it can't move.
+    NoFuel(u32),
+
+    /// Ranks are only changed by destroying units, so the player can't set them.
+    SetRank,
+}
+
+
+/// Everything in the match, including the units which are hidden by fog.
+#[derive(Debug, Clone)]
+pub struct GameState {
+    pub map: Arc<MapData>,
+    pub snapshot: StateSnapshot,
+
+    /// The nation whose turn it is.
+    pub active: Nation,
+
+    /// The units which were destroyed by the last action, so the players who could see them are told about it.
+    pub destroyed: Vec<UnitSnapshot>,
+
+    /// Every tile which the unit moved through in the last [`MovePath`](ReplayAction::MovePath), starting with
+    /// the tile where the unit was, so only the players who can see the entire path are told about it.
+    pub moved: Vec<(i64, i64)>,
+}
+
+impl GameState {
+    /// The map must be the same map which the snapshot was created from.
+    pub fn new(map: Arc<MapData>, snapshot: StateSnapshot, active: Nation) -> Self {
+        Self {
+            map,
+            snapshot,
+            active,
+            destroyed: vec![],
+            moved: vec![],
+        }
+    }
+
+    fn unit(&self, id: u32) -> Option<&UnitSnapshot> {
+        self.snapshot.units.iter().find(|unit| unit.id == id)
+    }
+
+    fn units_at(&self, tile: (i64, i64)) -> impl Iterator<Item = &UnitSnapshot> {
+        self.snapshot.units.iter().filter(move |unit| unit_tile(unit) == tile)
+    }
+
+    /// The same as `Grid::move_cost` in the game renderer, buildings use the building's movement cost.
+    fn move_cost(&self, unit: &UnitSnapshot, (x, y): (i64, i64)) -> Option<u32> {
+        if x < 0 || y < 0 || x >= self.map.width as i64 || y >= self.map.height as i64 {
+            return None;
+        }
+
+        let (x, y) = (x as u32, y as u32);
+        let class = unit.class.movement_class();
+
+        match self.snapshot.buildings.iter().find(|building| building.x == x && building.y == y) {
+            Some(building) => building.class.info().move_cost(class),
+            None => self.map.get(x, y).info().move_cost(class),
+        }
+    }
+}
+
+
+/// What a player can see, the enemy units which are hidden by fog aren't included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameView {
+    pub turn: u32,
+
+    /// The nation whose turn it is.
+    pub active: Nation,
+
+    pub units: Vec<UnitSnapshot>,
+
+    /// The buildings are always visible, because they don't move.
+    pub buildings: Vec<BuildingSnapshot>,
+}
+
+
+pub struct GameRules {
+    /// The nation which is controlled by each player.
+    pub players: Vec<(PlayerId, Nation)>,
+
+    /// Allies share their vision.
+    pub teams: Teams,
+
+    /// Whether enemy units are hidden when they are outside of the player's vision.
+    pub fog: bool,
+}
+
+impl GameRules {
+    fn nation(&self, player: PlayerId) -> Option<Nation> {
+        self.players.iter().find(|(id, _)| *id == player).map(|(_, nation)| *nation)
+    }
+
+    fn is_allied(&self, player: PlayerId, nation: Nation) -> bool {
+        self.nation(player).is_some_and(|player| self.teams.are_allies(player, nation))
+    }
+
+    /// Returns how far the unit moves along the path, see [`resolve_path`].
+    ///
+    /// The unit can only stop on tiles which it can share, and it is trapped by units which aren't allied,
+    /// even if they are hidden by fog. This uses the same [`TileOccupant`] rule as `Grid::move_path` in the game renderer.
+    fn resolve_path(&self, state: &GameState, unit: &UnitSnapshot, path: &[MoveDirection]) -> ResolvedPath {
+        resolve_path(unit_tile(unit), unit.class.movement(), unit.fuel, path, |tile| {
+            let others = state.units_at(tile)
+                .filter(|other| other.id != unit.id)
+                .map(|other| (other.class, other.nation))
+                .collect::<Vec<_>>();
+
+            let occupant = TileOccupant::new((unit.class, unit.nation), &others, &self.teams);
+
+            (state.move_cost(unit, tile), occupant)
+        })
+    }
+
+    /// Whether the player can see the tile, either because it is inside the vision of an
+    /// allied unit, or because it is an allied property.
+    pub fn is_tile_visible(&self, state: &GameState, player: PlayerId, (x, y): (i64, i64)) -> bool {
+        if !self.fog {
+            return true;
+        }
+
+        let unit_vision = state.snapshot.units.iter()
+            .filter(|unit| self.is_allied(player, unit.nation))
+            .any(|unit| {
+                let (unit_x, unit_y) = unit_tile(unit);
+                (unit_x - x).unsigned_abs() + (unit_y - y).unsigned_abs() <= unit.class.vision() as u64
+            });
+
+        unit_vision || state.snapshot.buildings.iter().any(|building| {
+            building.x as i64 == x &&
+            building.y as i64 == y &&
+            building.nation.is_some_and(|nation| self.is_allied(player, nation))
+        })
+    }
+
+    fn is_unit_visible(&self, state: &GameState, player: PlayerId, unit: &UnitSnapshot) -> bool {
+        self.is_allied(player, unit.nation) || self.is_tile_visible(state, player, unit_tile(unit))
+    }
+
+    /// Returns the nation which plays after `nation`, in the order of [`players`](GameRules::players).
+    fn next_nation(&self, nation: Nation) -> Nation {
+        let index = self.players.iter().position(|(_, x)| *x == nation).map_or(0, |index| index + 1);
+        self.players.get(index).or(self.players.first()).map_or(nation, |(_, nation)| *nation)
+    }
+}
+
+impl Rules for GameRules {
+    type State = GameState;
+    type Action = ReplayAction;
+    type View = GameView;
+    type Error = GameError;
+
+    /// Paths which are blocked by terrain, movement or fuel aren't rejected, instead the unit
+    /// stops at the last free tile, the same as `Grid::move_path` in the game renderer.
+    ///
+    /// Players can only destroy enemy units which they can see.
+    fn validate(&self, state: &Self::State, player: PlayerId, action: &Self::Action) -> Result<(), Self::Error> {
+        if self.nation(player) != Some(state.active) {
+            return Err(GameError::NotTurn);
+        }
+
+        match action {
+            ReplayAction::MovePath { unit: id, path } => {
+                let unit = state.unit(*id).ok_or(GameError::UnknownUnit(*id))?;
+
+                if self.nation(player) != Some(unit.nation) {
+                    return Err(GameError::NotOwner(*id));
+                }
+
+                if unit.waited {
+                    return Err(GameError::AlreadyMoved(*id));
+                }
+
+                if unit.fuel == 0 && !path.is_empty() {
+                    return Err(GameError::NoFuel(*id));
+                }
+
+                Ok(())
+            },
+
+            ReplayAction::DestroyUnit { unit: id } => {
+                // Units which are hidden by fog are treated as if they don't exist, so they aren't revealed
+                let unit = state.unit(*id)
+                    .filter(|unit| self.is_unit_visible(state, player, unit))
+                    .ok_or(GameError::UnknownUnit(*id))?;
+
+                if self.is_allied(player, unit.nation) {
+                    return Err(GameError::Allied(*id));
+                }
+
+                Ok(())
+            },
+
+            ReplayAction::SetRank { .. } => Err(GameError::SetRank),
+
+            ReplayAction::EndTurn => Ok(()),
+        }
+    }
+
+    /// A [`MovePath`](ReplayAction::MovePath) is returned with only the steps which the unit moved,
+    /// so the players don't learn about the rest of the path, and replays move the unit the same way.
+    fn apply(&self, state: &mut Self::State, _player: PlayerId, action: &Self::Action) -> Self::Action {
+        // These are only used by is_visible for the current action
+        state.destroyed.clear();
+        state.moved.clear();
+
+        match action {
+            ReplayAction::MovePath { unit, path } => {
+                let index = state.snapshot.units.iter().position(|x| x.id == *unit).unwrap();
+                let resolved = self.resolve_path(state, &state.snapshot.units[index], path);
+                let path = &path[..resolved.steps];
+
+                let unit = &mut state.snapshot.units[index];
+
+                let mut tile = unit_tile(unit);
+                state.moved.push(tile);
+
+                for direction in path {
+                    tile = direction.step(tile);
+                    state.moved.push(tile);
+                }
+
+                unit.x = tile.0 as f32;
+                unit.y = tile.1 as f32;
+                unit.fuel -= resolved.steps as u32;
+                unit.waited = true;
+
+                ReplayAction::MovePath { unit: unit.id, path: path.to_vec() }
+            },
+
+            ReplayAction::DestroyUnit { unit } => {
+                let index = state.snapshot.units.iter().position(|x| x.id == *unit).unwrap();
+                let unit = state.snapshot.units.remove(index);
+                state.destroyed.push(unit);
+
+                action.clone()
+            },
+
+            ReplayAction::SetRank { .. } => unreachable!(),
+
+            ReplayAction::EndTurn => {
+                state.active = self.next_nation(state.active);
+                state.snapshot.turn += 1;
+
+                for unit in state.snapshot.units.iter_mut() {
+                    unit.waited = false;
+                }
+
+                action.clone()
+            },
+        }
+    }
+
+    fn view(&self, state: &Self::State, player: PlayerId) -> Self::View {
+        GameView {
+            turn: state.snapshot.turn,
+            active: state.active,
+
+            units: state.snapshot.units.iter()
+                .filter(|unit| self.is_unit_visible(state, player, unit))
+                .cloned()
+                .collect(),
+
+            buildings: state.snapshot.buildings.clone(),
+        }
+    }
+
+    fn is_visible(&self, state: &Self::State, player: PlayerId, action: &Self::Action) -> bool {
+        match action {
+            // The path isn't sent if any of it is inside of fog, because it would reveal where the unit came from
+            ReplayAction::MovePath { unit, .. } => {
+                state.unit(*unit).is_some_and(|unit| {
+                    self.is_allied(player, unit.nation) ||
+                    state.moved.iter().all(|tile| self.is_tile_visible(state, player, *tile))
+                })
+            },
+
+            // The unit was removed, so it uses the tile where it was destroyed
+            ReplayAction::DestroyUnit { unit } => {
+                state.destroyed.iter()
+                    .find(|x| x.id == *unit)
+                    .is_some_and(|unit| self.is_unit_visible(state, player, unit))
+            },
+
+            ReplayAction::SetRank { .. } => false,
+
+            ReplayAction::EndTurn => true,
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Hosts matches without rendering them.
+//!
+//! The server is authoritative: players send actions to the server, the server checks them with the
+//! [`Rules`], and then it sends the results to every player, filtered by what each player is allowed to see.
+//!
+//! This crate doesn't do any networking, instead the transport (such as a WebSocket) passes the
+//! incoming actions to [`Server::receive`] and sends the returned [`Outgoing`] messages to the players.
+//!
+//! The rules of Rusted Battalions are implemented by [`GameRules`](game::GameRules).
+
+use std::collections::HashMap;
+
+pub mod game;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MatchId(pub u32);
+
+
+/// The game rules which are used to check and apply actions.
+///
+/// The same rules are used by the client, so that the client can predict the result of an action.
+pub trait Rules {
+    /// Everything in the match, including the things which are hidden by fog.
+    type State;
+
+    type Action: Clone;
+
+    /// The part of the [`State`](Rules::State) which a player can see.
+    type View: Clone + PartialEq;
+
+    /// The reason why an action isn't allowed.
+    type Error;
+
+    /// Checks whether the player is allowed to do the action, this must not change the state.
+    fn validate(&self, state: &Self::State, player: PlayerId, action: &Self::Action) -> Result<(), Self::Error>;
+
+    /// Changes the state, this is only called after [`validate`](Rules::validate) succeeds.
+    ///
+    /// Returns the action which actually happened, which is sent to the players and saved in the history.
+    /// For example a move can stop early because the unit is trapped by a unit which was hidden by fog.
+    fn apply(&self, state: &mut Self::State, player: PlayerId, action: &Self::Action) -> Self::Action;
+
+    /// Returns what the player can see, things which are hidden by fog must not be included.
+    fn view(&self, state: &Self::State, player: PlayerId) -> Self::View;
+
+    /// Whether the player is allowed to see the action, this is called after the action is applied.
+    ///
+    /// For example an enemy unit which moves inside of fog should not be sent to the player.
+    fn is_visible(&self, state: &Self::State, player: PlayerId, action: &Self::Action) -> bool;
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerError<E> {
+    UnknownMatch(MatchId),
+
+    /// The player isn't in the match.
+    NotPlayer(PlayerId),
+
+    /// The action was rejected by the [`Rules`].
+    Rules(E),
+}
+
+
+/// A message which is sent from the server to a player.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outgoing<A, V, E> {
+    /// An action which was accepted, `player` is the player who did the action.
+    Action {
+        player: PlayerId,
+        action: A,
+    },
+
+    /// The player's view changed, for example because fog was revealed.
+    View(V),
+
+    /// The player's action was not accepted.
+    Rejected(ServerError<E>),
+}
+
+pub type OutgoingFor<R> = Outgoing<<R as Rules>::Action, <R as Rules>::View, <R as Rules>::Error>;
+
+
+/// A match which is hosted by the [`Server`].
+pub struct Match<R> where R: Rules {
+    rules: R,
+    state: R::State,
+
+    /// The players, and the most recent view which was sent to them.
+    players: Vec<(PlayerId, R::View)>,
+
+    /// Every action which was accepted, in order.
+    history: Vec<(PlayerId, R::Action)>,
+}
+
+impl<R> Match<R> where R: Rules {
+    pub fn new(rules: R, state: R::State, players: &[PlayerId]) -> Self {
+        let players = players.iter().map(|player| (*player, rules.view(&state, *player))).collect();
+
+        Self {
+            rules,
+            state,
+            players,
+            history: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn state(&self) -> &R::State {
+        &self.state
+    }
+
+    /// Every action which was accepted, in order. This can be saved as a replay.
+    #[inline]
+    pub fn history(&self) -> &[(PlayerId, R::Action)] {
+        &self.history
+    }
+
+    /// Returns the most recent view which was sent to the player.
+    pub fn view(&self, player: PlayerId) -> Option<&R::View> {
+        self.players.iter().find(|(id, _)| *id == player).map(|(_, view)| view)
+    }
+
+    /// Checks and applies the action, and returns the messages which should be sent to each player.
+    pub fn receive(&mut self, player: PlayerId, action: R::Action) -> Vec<(PlayerId, OutgoingFor<R>)> {
+        if self.view(player).is_none() {
+            return vec![(player, Outgoing::Rejected(ServerError::NotPlayer(player)))];
+        }
+
+        if let Err(error) = self.rules.validate(&self.state, player, &action) {
+            return vec![(player, Outgoing::Rejected(ServerError::Rules(error)))];
+        }
+
+        let action = self.rules.apply(&mut self.state, player, &action);
+
+        let mut outgoing = vec![];
+
+        for (id, view) in self.players.iter_mut() {
+            if *id == player || self.rules.is_visible(&self.state, *id, &action) {
+                outgoing.push((*id, Outgoing::Action { player, action: action.clone() }));
+            }
+
+            let new_view = self.rules.view(&self.state, *id);
+
+            if new_view != *view {
+                *view = new_view.clone();
+                outgoing.push((*id, Outgoing::View(new_view)));
+            }
+        }
+
+        self.history.push((player, action));
+
+        outgoing
+    }
+}
+
+
+/// Hosts multiple matches at the same time.
+pub struct Server<R> where R: Rules {
+    next_id: u32,
+    matches: HashMap<MatchId, Match<R>>,
+}
+
+impl<R> Server<R> where R: Rules {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            matches: HashMap::new(),
+        }
+    }
+
+    pub fn create_match(&mut self, rules: R, state: R::State, players: &[PlayerId]) -> MatchId {
+        let id = MatchId(self.next_id);
+        self.next_id += 1;
+
+        tracing::debug!(id = id.0, players = players.len(), "Match created");
+
+        self.matches.insert(id, Match::new(rules, state, players));
+        id
+    }
+
+    /// Removes the match, and returns it so that its history can be saved.
+    pub fn end_match(&mut self, id: MatchId) -> Option<Match<R>> {
+        self.matches.remove(&id)
+    }
+
+    #[inline]
+    pub fn get(&self, id: MatchId) -> Option<&Match<R>> {
+        self.matches.get(&id)
+    }
+
+    /// See [`Match::receive`].
+    pub fn receive(&mut self, id: MatchId, player: PlayerId, action: R::Action) -> Vec<(PlayerId, OutgoingFor<R>)> {
+        match self.matches.get_mut(&id) {
+            Some(m) => m.receive(player, action),
+            None => vec![(player, Outgoing::Rejected(ServerError::UnknownMatch(id)))],
+        }
+    }
+}
+
+impl<R> Default for Server<R> where R: Rules {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Server, Rules, PlayerId, MatchId, Outgoing, ServerError};
+
+    /// Each player has a position on a line, players can only see positions which are close to them.
+    struct Line;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Move(i32);
+
+    impl Rules for Line {
+        type State = Vec<i32>;
+        type Action = Move;
+        type View = Vec<Option<i32>>;
+        type Error = &'static str;
+
+        fn validate(&self, _state: &Self::State, _player: PlayerId, action: &Self::Action) -> Result<(), Self::Error> {
+            if action.0.abs() <= 2 { Ok(()) } else { Err("too far") }
+        }
+
+        fn apply(&self, state: &mut Self::State, player: PlayerId, action: &Self::Action) -> Self::Action {
+            state[player.0 as usize] += action.0;
+            action.clone()
+        }
+
+        fn view(&self, state: &Self::State, player: PlayerId) -> Self::View {
+            let me = state[player.0 as usize];
+            state.iter().map(|x| if (x - me).abs() <= 3 { Some(*x) } else { None }).collect()
+        }
+
+        fn is_visible(&self, _state: &Self::State, _player: PlayerId, _action: &Self::Action) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn receive() {
+        let p0 = PlayerId(0);
+        let p1 = PlayerId(1);
+
+        let mut server = Server::new();
+        let id = server.create_match(Line, vec![0, 7], &[p0, p1]);
+
+        assert_eq!(server.receive(id, p0, Move(5)), vec![(p0, Outgoing::Rejected(ServerError::Rules("too far")))]);
+        assert_eq!(server.receive(id, PlayerId(2), Move(1)), vec![(PlayerId(2), Outgoing::Rejected(ServerError::NotPlayer(PlayerId(2))))]);
+        assert_eq!(server.receive(MatchId(5), p0, Move(1)), vec![(p0, Outgoing::Rejected(ServerError::UnknownMatch(MatchId(5))))]);
+
+        // The other player is still hidden, so only the mover is told
+        assert_eq!(server.receive(id, p0, Move(2)), vec![
+            (p0, Outgoing::Action { player: p0, action: Move(2) }),
+            (p0, Outgoing::View(vec![Some(2), None])),
+        ]);
+
+        // Both players can now see each other
+        assert_eq!(server.receive(id, p1, Move(-2)), vec![
+            (p0, Outgoing::View(vec![Some(2), Some(5)])),
+            (p1, Outgoing::Action { player: p1, action: Move(-2) }),
+            (p1, Outgoing::View(vec![Some(2), Some(5)])),
+        ]);
+
+        assert_eq!(server.get(id).unwrap().history().len(), 2);
+    }
+}
@@ -0,0 +1,301 @@
+use std::sync::Arc;
+use rusted_battalions_server::{Server, PlayerId, MatchId, Outgoing, ServerError};
+use rusted_battalions_server::game::{GameRules, GameState, GameView, GameError};
+use rusted_battalions_game_core::{Nation};
+use rusted_battalions_game_core::action::{MoveDirection};
+use rusted_battalions_game_core::audit::{StateSnapshot, UnitSnapshot};
+use rusted_battalions_game_core::map::{MapData};
+use rusted_battalions_game_core::replay::{ReplayAction};
+use rusted_battalions_game_core::team::{Teams};
+use rusted_battalions_game_core::terrain::{TerrainClass};
+use rusted_battalions_game_core::unit::{UnitClass, Rank};
+
+
+const P0: PlayerId = PlayerId(0);
+const P1: PlayerId = PlayerId(1);
+
+
+fn infantry(id: u32, x: f32, nation: Nation) -> UnitSnapshot {
+    UnitSnapshot {
+        id,
+        x,
+        y: 0.0,
+        class: UnitClass::Infantry,
+        nation,
+        hp: 100,
+        fuel: 99,
+        kills: 0,
+        rank: Rank::Rookie,
+        waited: false,
+    }
+}
+
+/// A single row of grass, it is Orange Star's turn.
+fn new_match(teams: Teams, units: Vec<UnitSnapshot>) -> (Server<GameRules>, MatchId) {
+    let map = Arc::new(MapData::new(8, 1, TerrainClass::Grass));
+
+    let rules = GameRules {
+        players: vec![(P0, Nation::OrangeStar), (P1, Nation::BlueMoon)],
+        teams,
+        fog: true,
+    };
+
+    let mut server = Server::new();
+    let id = server.create_match(rules, GameState::new(map, StateSnapshot::new(1, 0, 2, units, vec![]), Nation::OrangeStar), &[P0, P1]);
+    (server, id)
+}
+
+/// The Orange Star infantry is on the left and the Blue Moon infantry is `distance` tiles to the right.
+fn server(distance: f32) -> (Server<GameRules>, MatchId) {
+    new_match(Teams::new(), vec![
+        infantry(0, 0.0, Nation::OrangeStar),
+        infantry(1, distance, Nation::BlueMoon),
+    ])
+}
+
+fn move_path(unit: u32, path: &[MoveDirection]) -> ReplayAction {
+    ReplayAction::MovePath { unit, path: path.to_vec() }
+}
+
+fn view(turn: u32, active: Nation, units: Vec<UnitSnapshot>) -> GameView {
+    GameView { turn, active, units, buildings: vec![] }
+}
+
+fn rejected(error: GameError) -> Outgoing<ReplayAction, GameView, GameError> {
+    Outgoing::Rejected(ServerError::Rules(error))
+}
+
+
+#[test]
+fn fog() {
+    use MoveDirection::{Left, Right};
+
+    let (mut server, id) = server(6.0);
+
+    assert_eq!(server.get(id).unwrap().view(P0), Some(&view(1, Nation::OrangeStar, vec![infantry(0, 0.0, Nation::OrangeStar)])));
+    assert_eq!(server.get(id).unwrap().view(P1), Some(&view(1, Nation::OrangeStar, vec![infantry(1, 6.0, Nation::BlueMoon)])));
+
+    assert_eq!(server.receive(id, P0, move_path(1, &[Left])), vec![(P0, rejected(GameError::NotOwner(1)))]);
+    assert_eq!(server.receive(id, P0, move_path(5, &[Right])), vec![(P0, rejected(GameError::UnknownUnit(5)))]);
+
+    let rank = ReplayAction::SetRank { unit: 0, rank: Rank::Veteran };
+    assert_eq!(server.receive(id, P0, rank), vec![(P0, rejected(GameError::SetRank))]);
+
+    // The Blue Moon player can't see the move, because it is outside of their vision
+    let moved = UnitSnapshot { fuel: 97, waited: true, ..infantry(0, 2.0, Nation::OrangeStar) };
+
+    assert_eq!(server.receive(id, P0, move_path(0, &[Right, Right])), vec![
+        (P0, Outgoing::Action { player: P0, action: move_path(0, &[Right, Right]) }),
+        (P0, Outgoing::View(view(1, Nation::OrangeStar, vec![moved]))),
+    ]);
+
+    // The Blue Moon player can see that the turn ended, and the unit can move again
+    let rested = UnitSnapshot { fuel: 97, ..infantry(0, 2.0, Nation::OrangeStar) };
+
+    assert_eq!(server.receive(id, P0, ReplayAction::EndTurn), vec![
+        (P0, Outgoing::Action { player: P0, action: ReplayAction::EndTurn }),
+        (P0, Outgoing::View(view(2, Nation::BlueMoon, vec![rested]))),
+        (P1, Outgoing::Action { player: P0, action: ReplayAction::EndTurn }),
+        (P1, Outgoing::View(view(2, Nation::BlueMoon, vec![infantry(1, 6.0, Nation::BlueMoon)]))),
+    ]);
+
+    // Now both players can see each other, but the move started in fog, so the Orange Star player isn't told about it
+    let both = view(2, Nation::BlueMoon, vec![
+        rested,
+        UnitSnapshot { fuel: 96, waited: true, ..infantry(1, 3.0, Nation::BlueMoon) },
+    ]);
+
+    assert_eq!(server.receive(id, P1, move_path(1, &[Left, Left, Left])), vec![
+        (P0, Outgoing::View(both.clone())),
+        (P1, Outgoing::Action { player: P1, action: move_path(1, &[Left, Left, Left]) }),
+        (P1, Outgoing::View(both)),
+    ]);
+
+    assert_eq!(server.get(id).unwrap().history().len(), 3);
+}
+
+#[test]
+fn turns() {
+    use MoveDirection::{Left, Right};
+
+    let (mut server, id) = server(6.0);
+
+    // It isn't Blue Moon's turn yet
+    assert_eq!(server.receive(id, P1, move_path(1, &[Left])), vec![(P1, rejected(GameError::NotTurn))]);
+    assert_eq!(server.receive(id, P1, ReplayAction::EndTurn), vec![(P1, rejected(GameError::NotTurn))]);
+
+    assert_eq!(server.receive(id, P0, move_path(0, &[Right])).len(), 2);
+
+    // The unit can only move once per turn
+    assert_eq!(server.receive(id, P0, move_path(0, &[Right])), vec![(P0, rejected(GameError::AlreadyMoved(0)))]);
+    assert_eq!(server.receive(id, P0, move_path(0, &[])), vec![(P0, rejected(GameError::AlreadyMoved(0)))]);
+
+    assert_eq!(server.receive(id, P0, ReplayAction::EndTurn).len(), 4);
+    assert_eq!(server.receive(id, P0, move_path(0, &[Right])), vec![(P0, rejected(GameError::NotTurn))]);
+
+    assert_eq!(server.receive(id, P1, move_path(1, &[Left])).len(), 2);
+    assert_eq!(server.receive(id, P1, ReplayAction::EndTurn).len(), 4);
+
+    // After every player ends their turn, the first player can move again
+    let state = server.get(id).unwrap().state();
+    assert_eq!(state.active, Nation::OrangeStar);
+    assert_eq!(state.snapshot.turn, 3);
+    assert!(state.snapshot.units.iter().all(|unit| !unit.waited));
+
+    assert_eq!(server.receive(id, P0, move_path(0, &[Right])).len(), 2);
+    assert_eq!(server.get(id).unwrap().state().snapshot.units[0].x, 2.0);
+}
+
+#[test]
+fn blocked() {
+    use MoveDirection::{Left, Right};
+
+    let (mut server, id) = server(7.0);
+
+    // The path goes outside of the map, so the unit doesn't move, but it still waits
+    assert_eq!(server.receive(id, P0, move_path(0, &[Left])), vec![
+        (P0, Outgoing::Action { player: P0, action: move_path(0, &[]) }),
+        (P0, Outgoing::View(view(1, Nation::OrangeStar, vec![UnitSnapshot { waited: true, ..infantry(0, 0.0, Nation::OrangeStar) }]))),
+    ]);
+
+    server.receive(id, P0, ReplayAction::EndTurn);
+    server.receive(id, P1, ReplayAction::EndTurn);
+
+    // The path costs more movement points than the unit has, so it stops at the last tile which it can reach
+    server.receive(id, P0, move_path(0, &[Right, Right, Right, Right]));
+
+    let unit = &server.get(id).unwrap().state().snapshot.units[0];
+    assert_eq!(unit.x, 3.0);
+    assert_eq!(unit.fuel, 96);
+
+    // The history has the steps which the unit moved, so a replay moves it the same way
+    assert_eq!(server.get(id).unwrap().history().last(), Some(&(P0, move_path(0, &[Right, Right, Right]))));
+}
+
+#[test]
+fn fuel() {
+    use MoveDirection::{Right};
+
+    let (mut server, id) = new_match(Teams::new(), vec![
+        UnitSnapshot { fuel: 2, ..infantry(0, 0.0, Nation::OrangeStar) },
+        infantry(1, 7.0, Nation::BlueMoon),
+    ]);
+
+    // It runs out of fuel after 2 tiles
+    server.receive(id, P0, move_path(0, &[Right, Right, Right]));
+
+    let unit = &server.get(id).unwrap().state().snapshot.units[0];
+    assert_eq!(unit.x, 2.0);
+    assert_eq!(unit.fuel, 0);
+
+    server.receive(id, P0, ReplayAction::EndTurn);
+    server.receive(id, P1, ReplayAction::EndTurn);
+
+    assert_eq!(server.receive(id, P0, move_path(0, &[Right])), vec![(P0, rejected(GameError::NoFuel(0)))]);
+}
+
+#[test]
+fn trapped() {
+    use MoveDirection::{Right};
+
+    let (mut server, id) = server(3.0);
+
+    // The enemy is hidden, so the path is accepted, but the unit stops before the enemy
+    let outgoing = server.receive(id, P0, move_path(0, &[Right, Right, Right]));
+
+    let state = server.get(id).unwrap().state();
+    assert_eq!(state.snapshot.units[0].x, 2.0);
+
+    let moved = UnitSnapshot { fuel: 97, waited: true, ..infantry(0, 2.0, Nation::OrangeStar) };
+    let both = view(1, Nation::OrangeStar, vec![moved, infantry(1, 3.0, Nation::BlueMoon)]);
+
+    // The unit started outside of the Blue Moon player's vision, so they only see where it stopped,
+    // and the Orange Star player is only told about the steps which the unit moved
+    assert_eq!(outgoing, vec![
+        (P0, Outgoing::Action { player: P0, action: move_path(0, &[Right, Right]) }),
+        (P0, Outgoing::View(both.clone())),
+        (P1, Outgoing::View(both)),
+    ]);
+
+    server.receive(id, P0, ReplayAction::EndTurn);
+
+    // Both players could see the unit, so both are told that it was destroyed
+    let destroy = ReplayAction::DestroyUnit { unit: 0 };
+    let enemy = infantry(1, 3.0, Nation::BlueMoon);
+
+    assert_eq!(server.receive(id, P1, destroy.clone()), vec![
+        (P0, Outgoing::Action { player: P1, action: destroy.clone() }),
+        (P0, Outgoing::View(view(2, Nation::BlueMoon, vec![]))),
+        (P1, Outgoing::Action { player: P1, action: destroy }),
+        (P1, Outgoing::View(view(2, Nation::BlueMoon, vec![enemy]))),
+    ]);
+}
+
+#[test]
+fn destroy() {
+    let (mut server, id) = new_match(Teams::new(), vec![
+        infantry(0, 0.0, Nation::OrangeStar),
+        infantry(1, 1.0, Nation::BlueMoon),
+        infantry(2, 7.0, Nation::BlueMoon),
+    ]);
+
+    let destroy = |unit| ReplayAction::DestroyUnit { unit };
+
+    // Players can't destroy their own units
+    assert_eq!(server.receive(id, P0, destroy(0)), vec![(P0, rejected(GameError::Allied(0)))]);
+
+    // The unit is hidden by fog, so it is rejected the same as a unit which doesn't exist
+    assert_eq!(server.receive(id, P0, destroy(2)), vec![(P0, rejected(GameError::UnknownUnit(2)))]);
+    assert_eq!(server.receive(id, P0, destroy(5)), vec![(P0, rejected(GameError::UnknownUnit(5)))]);
+
+    assert_eq!(server.receive(id, P0, destroy(1)).len(), 4);
+    assert_eq!(server.get(id).unwrap().state().destroyed.len(), 1);
+
+    // The destroyed units are only kept until the next action
+    server.receive(id, P0, ReplayAction::EndTurn);
+
+    let state = server.get(id).unwrap().state();
+    assert!(state.destroyed.is_empty());
+    assert_eq!(state.snapshot.units.iter().map(|unit| unit.id).collect::<Vec<_>>(), vec![0, 2]);
+}
+
+#[test]
+fn visible_path() {
+    use MoveDirection::{Right};
+
+    let (mut server, id) = new_match(Teams::new(), vec![
+        infantry(0, 2.0, Nation::OrangeStar),
+        infantry(1, 4.0, Nation::BlueMoon),
+    ]);
+
+    // The entire path is inside of the Blue Moon player's vision, so they are told about the move
+    let moved = UnitSnapshot { fuel: 98, waited: true, ..infantry(0, 3.0, Nation::OrangeStar) };
+    let both = view(1, Nation::OrangeStar, vec![moved, infantry(1, 4.0, Nation::BlueMoon)]);
+
+    assert_eq!(server.receive(id, P0, move_path(0, &[Right])), vec![
+        (P0, Outgoing::Action { player: P0, action: move_path(0, &[Right]) }),
+        (P0, Outgoing::View(both.clone())),
+        (P1, Outgoing::Action { player: P0, action: move_path(0, &[Right]) }),
+        (P1, Outgoing::View(both)),
+    ]);
+}
+
+#[test]
+fn allies() {
+    use MoveDirection::{Right};
+
+    let mut teams = Teams::new();
+    teams.set_team(Nation::BlueMoon, teams.team(Nation::OrangeStar));
+
+    let (mut server, id) = new_match(teams, vec![
+        infantry(0, 0.0, Nation::OrangeStar),
+        infantry(1, 1.0, Nation::BlueMoon),
+    ]);
+
+    // The unit moves through the allied unit, the same as in the game renderer
+    server.receive(id, P0, move_path(0, &[Right, Right]));
+
+    let unit = &server.get(id).unwrap().state().snapshot.units[0];
+    assert_eq!(unit.x, 2.0);
+    assert_eq!(unit.fuel, 97);
+}
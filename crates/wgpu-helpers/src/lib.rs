@@ -181,7 +181,7 @@ impl Primitive {
                 1 => {
                     Ok(format_ident!("{}", span = span, wgpu_name))
                 },
-                2 | 3 | 4 => {
+                2..=4 => {
                     Ok(format_ident!("{}x{}", span = span, wgpu_name, len))
                 },
                 _ => {
@@ -351,7 +351,7 @@ fn parse(input: DeriveInput) -> Result<TokenStream> {
         }
     };
 
-    Ok(TokenStream::from(output))
+    Ok(output)
 }
 
 